@@ -0,0 +1,40 @@
+//! Build information shown by `cyclemetrics info` and `--version`.
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    // Cargo sets one variable per enabled feature, so the list cannot go
+    // stale when features are added.
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(name, _)| {
+            let feature = name.strip_prefix("CARGO_FEATURE_")?;
+            Some(feature.to_lowercase().replace('_', "-"))
+        })
+        .filter(|feature| feature != "default")
+        .collect();
+    features.sort();
+    println!(
+        "cargo:rustc-env=CYCLEMETRICS_FEATURES={}",
+        features.join(", ")
+    );
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty())
+        .unwrap_or_else(|| "unknown commit".to_string());
+    println!("cargo:rustc-env=CYCLEMETRICS_GIT_COMMIT={commit}");
+
+    // Missing paths would rerun the script on every build, outside a clone.
+    println!("cargo:rerun-if-changed=build.rs");
+    for path in [".git/HEAD", ".git/refs/heads"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+}
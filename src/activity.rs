@@ -0,0 +1,567 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use chrono::{DateTime, FixedOffset};
+use gpx::Gpx;
+use time::OffsetDateTime;
+use tracing::{Level, debug};
+
+use crate::alternates::Fingerprint;
+use crate::energy::{Energy, estimate_energy};
+use crate::fit::{fit_to_gpx, is_fit};
+use crate::gpx::{
+    ELEVATION_NOISE_THRESHOLD, NameSource, activity_name, gpx_elevation_gain, gpx_elevation_noise,
+    gpx_elevation_profile, gpx_implausible_timestamps, gpx_peak, gpx_route_points,
+    gpx_start_end_date, gpx_start_point, gpx_total_distance, remove_duplicate_points,
+};
+use crate::intensity::{SensorSummary, sensor_summary};
+use crate::peaks::{Peak, highest_point};
+use crate::stream::read_track_points;
+use crate::transport::TransportSegment;
+use crate::tuning::{
+    DistanceSource, Tuning, cumulative_distance, filter_report, track_distance_m, tuned_distance_m,
+    tuned_elevation_gain, tuned_moving_seconds,
+};
+use crate::virtual_rides::is_virtual_ride;
+
+/// Device shown for files without a `creator`.
+pub const UNKNOWN_CREATOR: &str = "unknown";
+
+/// Below this speed, in m/s, the rider is considered stopped.
+pub const MOVING_MIN_SPEED: f64 = 0.5;
+
+/// Stable identity of an activity, independent of the file it was read from.
+///
+/// Made of the start timestamp and a content hash of the first and last track
+/// points, so renaming or moving a GPX file keeps the same id. A file without
+/// a track is told by all its route points and waypoints instead, and one
+/// without any point by its whole content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ActivityId {
+    start: i64,
+    hash: u64,
+}
+
+impl ActivityId {
+    /// `bytes` is the document `gpx` was read from.
+    pub fn from_gpx(gpx: &Gpx, bytes: &[u8]) -> Self {
+        let mut points = gpx
+            .tracks
+            .iter()
+            .flat_map(|track| track.segments.iter())
+            .flat_map(|segment| segment.points.iter());
+
+        let first = points.next();
+        let last = points.last().or(first);
+        if first.is_none() {
+            return Self::without_track(gpx, bytes);
+        }
+
+        let start = first
+            .and_then(|point| point.time)
+            .map_or(0, |time| OffsetDateTime::from(time).unix_timestamp());
+
+        let mut hash = Fnv1a::new();
+        for point in [first, last].into_iter().flatten() {
+            hash.write_point(
+                point.point().x(),
+                point.point().y(),
+                point.elevation,
+                point
+                    .time
+                    .map(|time| OffsetDateTime::from(time).unix_timestamp()),
+            );
+        }
+
+        Self {
+            start,
+            hash: hash.finish(),
+        }
+    }
+
+    /// Every route point and waypoint, or else the whole document, so that
+    /// planned routes never share an id.
+    fn without_track(gpx: &Gpx, bytes: &[u8]) -> Self {
+        let mut hash = Fnv1a::new();
+        let mut points = gpx
+            .routes
+            .iter()
+            .flat_map(|route| route.points.iter())
+            .chain(&gpx.waypoints)
+            .peekable();
+        if points.peek().is_none() {
+            hash.write(bytes);
+        }
+        for point in points {
+            hash.write_point(
+                point.point().x(),
+                point.point().y(),
+                point.elevation,
+                point
+                    .time
+                    .map(|time| OffsetDateTime::from(time).unix_timestamp()),
+            );
+        }
+
+        Self {
+            start: 0,
+            hash: hash.finish(),
+        }
+    }
+
+    /// Same id as [`ActivityId::from_gpx`], from the first and last track
+    /// points only.
+    pub fn from_endpoints(first: Option<&TrackPoint>, last: Option<&TrackPoint>) -> Self {
+        let last = last.or(first);
+        let start = first
+            .and_then(|point| point.time)
+            .map_or(0, |time| time.timestamp());
+
+        let mut hash = Fnv1a::new();
+        for point in [first, last].into_iter().flatten() {
+            hash.write_point(
+                point.lon,
+                point.lat,
+                point.elevation,
+                point.time.map(|time| time.timestamp()),
+            );
+        }
+
+        Self {
+            start,
+            hash: hash.finish(),
+        }
+    }
+}
+
+impl fmt::Display for ActivityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{:016x}", self.start, self.hash)
+    }
+}
+
+impl FromStr for ActivityId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, hash) = s
+            .rsplit_once('-')
+            .ok_or_else(|| anyhow::anyhow!("invalid activity id: {s}"))?;
+
+        Ok(Self {
+            start: start.parse()?,
+            hash: u64::from_str_radix(hash, 16)?,
+        })
+    }
+}
+
+/// FNV-1a, used because its output is stable across Rust releases, unlike
+/// `DefaultHasher`.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn write_point(&mut self, lon: f64, lat: f64, elevation: Option<f64>, time: Option<i64>) {
+        self.write(&lon.to_le_bytes());
+        self.write(&lat.to_le_bytes());
+        if let Some(elevation) = elevation {
+            self.write(&elevation.to_le_bytes());
+        }
+        if let Some(time) = time {
+            self.write(&time.to_le_bytes());
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Format of the file an activity was read from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileFormat {
+    #[default]
+    Gpx,
+    /// Read through [`crate::fit_to_gpx`].
+    Fit,
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Gpx => "GPX",
+            Self::Fit => "FIT",
+        })
+    }
+}
+
+/// A track point reduced to what the metrics need.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrackPoint {
+    pub lat: f64,
+    pub lon: f64,
+    pub elevation: Option<f64>,
+    pub time: Option<DateTime<FixedOffset>>,
+    /// From the Garmin `TrackPointExtension`, in bpm.
+    pub heart_rate: Option<f64>,
+    /// From a `power` or `PowerInWatts` extension, in watts.
+    pub power: Option<f64>,
+    /// From the Garmin `TrackPointExtension`, in rpm.
+    pub cadence: Option<f64>,
+    /// Distance from the start written by the device or the planner in a
+    /// `distance` extension, in meters.
+    pub distance_m: Option<f64>,
+    /// First point of a track segment; distances are not summed from the
+    /// previous point.
+    pub segment_start: bool,
+}
+
+/// Metrics computed from one GPX file.
+#[derive(Debug, Clone, Default)]
+pub struct Activity {
+    pub id: ActivityId,
+    pub path: PathBuf,
+    pub name: String,
+    pub name_source: NameSource,
+    pub start: Option<DateTime<FixedOffset>>,
+    /// Latest timestamp, with the offset it was recorded with: across a
+    /// daylight saving change it differs from the offset of `start`.
+    pub end: Option<DateTime<FixedOffset>>,
+    /// First track point, as (latitude, longitude).
+    pub start_point: Option<(f64, f64)>,
+    /// Last track point, as (latitude, longitude).
+    pub end_point: Option<(f64, f64)>,
+    pub distance_km: f64,
+    /// Whether `distance_km` is measured, embedded or estimated.
+    pub distance_source: DistanceSource,
+    pub elevation_gain: f64,
+    pub elevation_profile: Vec<(f64, f64)>, // (distance, elevation)
+    /// See [`gpx_peak`] and [`highest_point`]; kept when the points are
+    /// released.
+    pub peak: Option<Peak>,
+    /// Points of all the track segments, in order.
+    pub points: Vec<TrackPoint>,
+    /// Points of the routes, for files without a track.
+    pub route_points: Vec<TrackPoint>,
+    /// See [`crate::gpx::gpx_elevation_noise`].
+    pub elevation_noise: Option<f64>,
+    /// Elevation gain of the recorded elevations, once the DEM ones replaced
+    /// them in the points and the metrics.
+    pub raw_elevation_gain: Option<f64>,
+    /// Problems found while loading, shown in the detail pane.
+    pub warnings: Vec<String>,
+    /// Timestamps ignored, see [`crate::gpx::is_plausible_time`].
+    pub implausible_timestamps: usize,
+    /// Points removed as copies of the previous one, see
+    /// [`crate::gpx::remove_duplicate_points`].
+    pub duplicate_points: usize,
+    /// Recorded in a virtual world such as Zwift's; see
+    /// [`crate::virtual_rides::is_virtual_ride`].
+    pub virtual_ride: bool,
+    /// Device or application that wrote the file, from the GPX `creator`
+    /// attribute.
+    pub creator: Option<String>,
+    /// Parameters the distance, elevation gain and moving time are computed
+    /// with; see [`Activity::retune`].
+    pub tuning: Tuning,
+    /// Moving time kept by [`Activity::release_points`].
+    pub released_moving_seconds: Option<f64>,
+    /// Energy kept by [`Activity::release_points`].
+    pub released_energy: Option<Energy>,
+    /// Sensor averages kept by [`Activity::release_points`].
+    pub released_sensors: SensorSummary,
+    /// Stretches on a ferry or a train, see
+    /// [`crate::transport::detect_transport`].
+    pub transport: Vec<TransportSegment>,
+    /// Whether the distance and time of `transport` are left out of the
+    /// metrics; see [`Activity::exclude_transport`].
+    pub transport_excluded: bool,
+    pub format: FileFormat,
+    /// See [`Fingerprint`]; `None` without timestamps.
+    pub fingerprint: Option<Fingerprint>,
+    /// Files of the same ride in another format, left out for this one;
+    /// see [`Activity::is_alternate_of`].
+    pub alternates: Vec<PathBuf>,
+}
+
+impl Activity {
+    /// Compute the metrics of a GPX or FIT document held in memory; `path`
+    /// is only recorded.
+    pub fn from_bytes(path: PathBuf, bytes: &[u8]) -> anyhow::Result<Self> {
+        if is_fit(bytes) {
+            let mut activity = Self::from_bytes(path, fit_to_gpx(bytes)?.as_bytes())?;
+            activity.format = FileFormat::Fit;
+            return Ok(activity);
+        }
+        let mut gpx = gpx::read(bytes)?;
+        let duplicate_points = remove_duplicate_points(&mut gpx);
+        let (name, name_source) = activity_name(&gpx, &path);
+        let start_point = gpx_start_point(&gpx);
+        let points = read_track_points(bytes)?;
+        let fingerprint = Fingerprint::of(&points);
+        let times = gpx_start_end_date(&gpx);
+        let (distance_m, distance_source) =
+            track_distance_m(&points, gpx_total_distance(&gpx), &Tuning::default());
+
+        Ok(Self {
+            id: ActivityId::from_gpx(&gpx, bytes),
+            path,
+            name,
+            name_source,
+            start: times.map(|(start, _)| start),
+            end: times.map(|(_, end)| end),
+            start_point,
+            end_point: points.last().map(|point| (point.lat, point.lon)),
+            distance_km: distance_m / 1_000.0,
+            distance_source,
+            elevation_gain: gpx_elevation_gain(&gpx),
+            elevation_profile: gpx_elevation_profile(&gpx),
+            peak: gpx_peak(&gpx),
+            route_points: if points.is_empty() {
+                gpx_route_points(&gpx)
+            } else {
+                vec![]
+            },
+            points,
+            elevation_noise: gpx_elevation_noise(&gpx),
+            raw_elevation_gain: None,
+            warnings: vec![],
+            implausible_timestamps: gpx_implausible_timestamps(&gpx),
+            duplicate_points,
+            virtual_ride: is_virtual_ride(gpx.creator.as_deref(), start_point),
+            creator: gpx.creator.as_deref().and_then(device_name),
+            tuning: Tuning::default(),
+            released_moving_seconds: None,
+            released_energy: None,
+            released_sensors: SensorSummary::default(),
+            transport: vec![],
+            transport_excluded: false,
+            format: FileFormat::Gpx,
+            fingerprint,
+            alternates: vec![],
+        })
+    }
+
+    /// Whether the file had a track or a route to tell the ride by.
+    pub fn has_points(&self) -> bool {
+        self.start_point.is_some() || !self.route_points.is_empty()
+    }
+
+    /// Whether `other` is the same ride read from a file of another format:
+    /// with the same id when converted as it is, or else by their
+    /// [`Fingerprint`]s.
+    pub fn is_alternate_of(&self, other: &Activity) -> bool {
+        self.format != other.format
+            && (self.id == other.id
+                || self
+                    .fingerprint
+                    .zip(other.fingerprint)
+                    .is_some_and(|(a, b)| a.matches(&b)))
+    }
+
+    /// The creator, or [`UNKNOWN_CREATOR`].
+    pub fn device(&self) -> &str {
+        self.creator.as_deref().unwrap_or(UNKNOWN_CREATOR)
+    }
+
+    /// Whether the elevation looks like it came from GPS altitude rather than
+    /// a barometric altimeter.
+    pub fn has_low_quality_elevation(&self) -> bool {
+        self.elevation_noise
+            .is_some_and(|noise| noise > ELEVATION_NOISE_THRESHOLD)
+    }
+
+    /// Recompute the elevation gain, the elevation profile and its peak from
+    /// the elevations of the points, e.g. once taken from a DEM.
+    pub fn recompute_elevation(&mut self) {
+        self.elevation_gain = tuned_elevation_gain(&self.points, &self.tuning);
+        self.elevation_profile = cumulative_distance(&self.points, &self.tuning)
+            .filter_map(|(meters, point)| Some((meters / 1_000.0, point.elevation?)))
+            .collect();
+        self.peak = highest_point(&self.elevation_profile);
+    }
+
+    /// Recompute the distance, the elevation gain, the elevation profile and
+    /// its peak from the points with other parameters. Files read without their points
+    /// keep the metrics they were loaded with.
+    pub fn retune(&mut self, tuning: Tuning) {
+        if self.points.is_empty() {
+            return;
+        }
+        let before = (self.distance_km, self.elevation_gain);
+        let (distance_m, distance_source) = track_distance_m(
+            &self.points,
+            tuned_distance_m(&self.points, &tuning),
+            &tuning,
+        );
+        self.distance_km = distance_m / 1_000.0 - self.excluded_transport_km();
+        self.distance_source = distance_source;
+        self.tuning = tuning;
+        self.recompute_elevation();
+
+        if tracing::enabled!(Level::DEBUG) {
+            let filters = filter_report(&self.points, &tuning);
+            debug!(
+                path = %self.path.display(),
+                ?tuning,
+                jitter_dropped = filters.jitter,
+                spikes_dropped = filters.spikes,
+                distance_before_m = before.0 * 1_000.0,
+                distance_m,
+                ?distance_source,
+                elevation_gain_before_m = before.1,
+                elevation_gain_m = self.elevation_gain,
+                "metrics computed again with another tuning"
+            );
+        }
+    }
+
+    /// Drop the points and the elevation profile, which take most of the
+    /// memory of an activity, keeping the moving time.
+    pub fn release_points(&mut self) {
+        self.released_moving_seconds = self.moving_seconds();
+        self.released_energy = self.energy();
+        self.released_sensors = self.sensor_summary();
+        self.points = Vec::new();
+        self.elevation_profile = Vec::new();
+    }
+
+    /// Time from the first to the last timestamp, whatever the wall clock
+    /// did in between (midnight, daylight saving changes).
+    pub fn elapsed_seconds(&self) -> Option<f64> {
+        let elapsed = self.end? - self.start?;
+        Some(elapsed.num_milliseconds() as f64 / 1_000.0)
+    }
+
+    /// See [`tuned_moving_seconds`], under the tuning of the activity
+    /// ([`MOVING_MIN_SPEED`] by default), without the excluded transport;
+    /// without points, the time kept by [`Activity::release_points`].
+    pub fn moving_seconds(&self) -> Option<f64> {
+        if self.points.is_empty() {
+            return self.released_moving_seconds;
+        }
+        let transport: f64 = if self.transport_excluded {
+            self.transport.iter().map(|segment| segment.seconds).sum()
+        } else {
+            0.0
+        };
+        tuned_moving_seconds(&self.points, &self.tuning)
+            .map(|seconds| (seconds - transport).max(0.0))
+    }
+
+    /// Leave the distance and time of the ferry and train stretches out of
+    /// the metrics, as a ride for the sport only; they stay in `transport`.
+    pub fn exclude_transport(&mut self) {
+        if !self.transport_excluded {
+            self.transport_excluded = true;
+            self.distance_km -= self.excluded_transport_km();
+        }
+    }
+
+    fn excluded_transport_km(&self) -> f64 {
+        if !self.transport_excluded {
+            return 0.0;
+        }
+        self.transport.iter().map(|segment| segment.length_km).sum()
+    }
+
+    /// See [`estimate_energy`]; without points, the energy kept by
+    /// [`Activity::release_points`].
+    pub fn energy(&self) -> Option<Energy> {
+        if self.points.is_empty() {
+            return self.released_energy;
+        }
+        estimate_energy(&self.points)
+    }
+
+    /// See [`sensor_summary`]; without points, the averages kept by
+    /// [`Activity::release_points`].
+    pub fn sensor_summary(&self) -> SensorSummary {
+        if self.points.is_empty() {
+            return self.released_sensors;
+        }
+        sensor_summary(&self.points)
+    }
+
+    /// Average moving speed, in km/h.
+    pub fn average_speed(&self) -> Option<f64> {
+        self.moving_seconds()
+            .filter(|&seconds| seconds > 0.0)
+            .map(|seconds| self.distance_km / (seconds / 3600.0))
+    }
+}
+
+/// A `creator` attribute, trimmed; `None` when blank.
+pub(crate) fn device_name(creator: &str) -> Option<String> {
+    let creator = creator.trim();
+    (!creator.is_empty()).then(|| creator.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::stream_activity;
+    use std::fs;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn the_id_survives_renaming_and_moving_the_file() {
+        let dir = std::env::temp_dir().join(format!("cyclemetrics-id-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("archive")).unwrap();
+        let path = dir.join("Morning_Ride.gpx");
+        fs::copy(fixture("flat.gpx"), &path).unwrap();
+        let read = |path: &Path| Activity::from_bytes(path.to_path_buf(), &fs::read(path).unwrap());
+        let id = read(&path).unwrap().id;
+
+        let moved = dir.join("archive").join("2024-06-01 flat.gpx");
+        fs::rename(&path, &moved).unwrap();
+        assert_eq!(read(&moved).unwrap().id, id);
+        // Streamed without keeping the points, to the same id.
+        assert_eq!(stream_activity(&moved).unwrap().id, id);
+
+        let other = read(&fixture("mountain.gpx")).unwrap().id;
+        assert_ne!(other, id);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ids_round_trip_through_their_text() {
+        let id = Activity::from_bytes(fixture("flat.gpx"), &fs::read(fixture("flat.gpx")).unwrap())
+            .unwrap()
+            .id;
+        assert_eq!(id.to_string().parse::<ActivityId>().unwrap(), id);
+
+        // Undated rides, and rides before 1970, keep their start.
+        for id in [
+            ActivityId::default(),
+            ActivityId {
+                start: -86_400,
+                hash: 0xdead_beef,
+            },
+        ] {
+            assert_eq!(id.to_string().parse::<ActivityId>().unwrap(), id);
+        }
+        assert_eq!(ActivityId::default().to_string(), "0-0000000000000000");
+
+        assert!("ride.gpx".parse::<ActivityId>().is_err());
+        assert!("1717228800-not-hex".parse::<ActivityId>().is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use std::cmp::Ordering;
+
+use crate::activity::{Activity, FileFormat, TrackPoint};
+use crate::tuning::distance_m;
+
+/// Largest difference between the start times of two files of the same
+/// ride, in seconds: a device and the app next to it start a little apart.
+pub const ALTERNATE_START_S: i64 = 60;
+
+/// Largest difference between the durations, and between the distances, of
+/// two files of the same ride, as a share of the larger one.
+pub const ALTERNATE_TOLERANCE: f64 = 0.03;
+
+/// Side of the grid cells the shape of a ride is hashed on, in degrees:
+/// about 1 km, much more than the rounding of any format.
+pub const GEOMETRY_CELL_DEG: f64 = 0.01;
+
+/// Places along a ride, as shares of its distance, whose cells make its
+/// shape.
+const GEOMETRY_SAMPLES: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+/// What stays the same when a ride is saved in another format: its start,
+/// duration and distance, and a coarse outline of its shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fingerprint {
+    /// Unix timestamp of the first timed point.
+    pub start: i64,
+    pub seconds: f64,
+    pub distance_km: f64,
+    /// The cells of [`GEOMETRY_CELL_DEG`] the ride is in at each quarter
+    /// of its distance, as (latitude, longitude) indices.
+    pub geometry: [(i64, i64); GEOMETRY_SAMPLES.len()],
+}
+
+impl Fingerprint {
+    /// The fingerprint of a track; `None` without timestamps.
+    pub fn of(points: &[TrackPoint]) -> Option<Self> {
+        let start = points.iter().find_map(|point| point.time)?;
+        let end = points.iter().rev().find_map(|point| point.time)?;
+
+        let mut cumulative = Vec::with_capacity(points.len());
+        let mut distance = 0.0;
+        for (i, point) in points.iter().enumerate() {
+            if i > 0 {
+                distance += distance_m(&points[i - 1], point);
+            }
+            cumulative.push(distance);
+        }
+        let cell = |degrees: f64| (degrees / GEOMETRY_CELL_DEG).floor() as i64;
+        let geometry = GEOMETRY_SAMPLES.map(|share| {
+            let i = cumulative
+                .partition_point(|&at| at < share * distance)
+                .min(points.len() - 1);
+            (cell(points[i].lat), cell(points[i].lon))
+        });
+
+        Some(Self {
+            start: start.timestamp(),
+            seconds: (end - start).num_milliseconds() as f64 / 1_000.0,
+            distance_km: distance / 1_000.0,
+            geometry,
+        })
+    }
+
+    /// Whether both are the fingerprint of the same ride. The cells may be
+    /// next to each other, for a ride along their edge.
+    pub fn matches(&self, other: &Self) -> bool {
+        let close = |a: f64, b: f64| (a - b).abs() <= ALTERNATE_TOLERANCE * a.max(b);
+        (self.start - other.start).abs() <= ALTERNATE_START_S
+            && close(self.seconds, other.seconds)
+            && close(self.distance_km, other.distance_km)
+            && self
+                .geometry
+                .iter()
+                .zip(&other.geometry)
+                .all(|(a, b)| (a.0 - b.0).abs() <= 1 && (a.1 - b.1).abs() <= 1)
+    }
+}
+
+/// What makes a file the one kept among the files of the same ride in
+/// different formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+pub enum Preference {
+    /// Power recorded.
+    Power,
+    /// Heart rate recorded.
+    HeartRate,
+    /// A FIT file.
+    Fit,
+    /// A GPX file.
+    Gpx,
+}
+
+/// The richest file first: with power, then with heart rate, then FIT.
+pub const DEFAULT_PREFERENCES: [Preference; 4] = [
+    Preference::Power,
+    Preference::HeartRate,
+    Preference::Fit,
+    Preference::Gpx,
+];
+
+impl Preference {
+    fn holds(self, activity: &Activity) -> bool {
+        let sensors = activity.sensor_summary();
+        match self {
+            Self::Power => sensors.average_power.is_some(),
+            Self::HeartRate => sensors.average_heart_rate.is_some(),
+            Self::Fit => activity.format == FileFormat::Fit,
+            Self::Gpx => activity.format == FileFormat::Gpx,
+        }
+    }
+}
+
+/// Whether `a` is kept rather than `b`, of two files of the same ride: the
+/// first preference only one of them holds decides, and the first path when
+/// none does, whatever the loading order.
+pub fn is_preferred(a: &Activity, b: &Activity, preferences: &[Preference]) -> bool {
+    preferences
+        .iter()
+        .map(|preference| preference.holds(b).cmp(&preference.holds(a)))
+        .find(|order| order.is_ne())
+        .unwrap_or_else(|| a.path.cmp(&b.path))
+        == Ordering::Less
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::SyntheticRide;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    /// The same ride as recorded with a power meter or without.
+    fn ride(power: Option<f64>) -> SyntheticRide {
+        let ride = SyntheticRide::new().heart_rate(140.0);
+        match power {
+            Some(power) => ride.power(power),
+            None => ride,
+        }
+        .flat(3.0)
+        .climb(2.0, 6.0)
+        .noise(2.0)
+    }
+
+    fn fit(ride: &SyntheticRide) -> Activity {
+        Activity::from_bytes(PathBuf::from("ride.fit"), &ride.to_fit()).unwrap()
+    }
+
+    #[test]
+    fn the_same_ride_matches_across_formats() {
+        // The app next to the device records every other second.
+        let gpx = ride(None).interval(Duration::from_secs(2)).activity();
+        let fit = fit(&ride(Some(200.0)));
+        assert_eq!(fit.format, FileFormat::Fit);
+        assert_ne!(gpx.id, fit.id);
+        assert!(fit.is_alternate_of(&gpx));
+        assert!(gpx.is_alternate_of(&fit));
+
+        // Another ride along the same road, or the same ride made longer.
+        let later =
+            ride(None).start(chrono::DateTime::parse_from_rfc3339("2024-06-01T09:00:00Z").unwrap());
+        assert!(!fit.is_alternate_of(&later.activity()));
+        assert!(!fit.is_alternate_of(&ride(None).flat(1.0).activity()));
+        // Two files of the same format are not merged, but a file converted
+        // as it is is.
+        assert!(!gpx.is_alternate_of(&ride(None).activity()));
+        assert!(fit.is_alternate_of(&ride(Some(200.0)).activity()));
+    }
+
+    #[test]
+    fn the_richer_file_is_preferred() {
+        let gpx = ride(None).activity();
+        let fit_with_power = fit(&ride(Some(200.0)));
+        assert!(is_preferred(&fit_with_power, &gpx, &DEFAULT_PREFERENCES));
+        assert!(!is_preferred(&gpx, &fit_with_power, &DEFAULT_PREFERENCES));
+        assert!(is_preferred(&gpx, &fit_with_power, &[Preference::Gpx]));
+        // Power first, whatever the format.
+        let gpx_with_power = ride(Some(200.0)).activity();
+        let fit = fit(&ride(None));
+        assert!(is_preferred(&gpx_with_power, &fit, &DEFAULT_PREFERENCES));
+
+        // Without power in either, the format decides; without preferences,
+        // the path.
+        assert!(is_preferred(&fit, &gpx, &DEFAULT_PREFERENCES));
+        assert!(is_preferred(&fit, &gpx, &[]));
+        assert!(!is_preferred(&gpx, &fit, &[]));
+    }
+}
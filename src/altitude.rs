@@ -0,0 +1,107 @@
+use crate::activity::TrackPoint;
+
+/// Elevations the time spent above is measured at when not configured, in
+/// meters.
+pub const DEFAULT_ALTITUDE_THRESHOLDS_M: [f64; 3] = [1_500.0, 2_000.0, 2_500.0];
+
+/// Seconds spent at or above each of `thresholds_m`, in their order.
+///
+/// The time between two points is shared at the elevation where the line
+/// between them crosses a threshold, so that sparse and dense recordings of
+/// the same climb agree. Stops count, as the body stays up there; the time
+/// between points without an elevation or a timestamp, or from one segment
+/// to the next, does not.
+pub fn time_above(points: &[TrackPoint], thresholds_m: &[f64]) -> Vec<f64> {
+    let mut seconds = vec![0.0; thresholds_m.len()];
+    for pair in points.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        if b.segment_start {
+            continue;
+        }
+        let (Some(low), Some(high), Some(start), Some(end)) =
+            (a.elevation, b.elevation, a.time, b.time)
+        else {
+            continue;
+        };
+        let elapsed = (end - start).as_seconds_f64();
+        if elapsed <= 0.0 {
+            continue;
+        }
+        let (low, high) = (low.min(high), low.max(high));
+        for (seconds, &threshold) in seconds.iter_mut().zip(thresholds_m) {
+            *seconds += if low >= threshold {
+                elapsed
+            } else if high <= threshold {
+                0.0
+            } else {
+                elapsed * (high - threshold) / (high - low)
+            };
+        }
+    }
+    seconds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{SyntheticRide, minutes};
+
+    fn hours(seconds: &[f64]) -> Vec<f64> {
+        seconds
+            .iter()
+            .map(|seconds| (seconds / 3600.0 * 100.0).round() / 100.0)
+            .collect()
+    }
+
+    #[test]
+    fn time_above_is_weighted_by_time() {
+        // From 1,000 m to 3,000 m at 1,000 m an hour, half an hour at the
+        // top, then down as slowly.
+        let ride = SyntheticRide::new()
+            .elevation(1_000.0)
+            .speed_kmh(10.0)
+            .climb(20.0, 10.0)
+            .pause(minutes(30))
+            .descent(20.0, 10.0);
+        let thresholds = DEFAULT_ALTITUDE_THRESHOLDS_M;
+        assert_eq!(
+            hours(&time_above(&ride.points(), &thresholds)),
+            [3.5, 2.5, 1.5]
+        );
+
+        // A point a minute gives the same times, the crossings being
+        // interpolated.
+        let sparse = ride.interval(std::time::Duration::from_secs(60));
+        assert_eq!(
+            hours(&time_above(&sparse.points(), &thresholds)),
+            [3.5, 2.5, 1.5]
+        );
+        assert_eq!(time_above(&sparse.points(), &[]), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn gaps_in_the_recording_are_left_out() {
+        let point = |minute: i64, elevation: Option<f64>, segment_start: bool| TrackPoint {
+            elevation,
+            time: Some(
+                chrono::DateTime::parse_from_rfc3339("2024-07-14T08:00:00+02:00").unwrap()
+                    + chrono::Duration::minutes(minute),
+            ),
+            segment_start,
+            ..Default::default()
+        };
+        let points = [
+            point(0, Some(1_400.0), true),
+            // Crossing 1,500 m two thirds of the way.
+            point(30, Some(1_700.0), false),
+            point(40, None, false),
+            point(50, Some(1_700.0), false),
+            // The device off over lunch.
+            point(120, Some(1_700.0), true),
+            point(130, Some(1_700.0), false),
+        ];
+        let seconds = time_above(&points, &[1_500.0, 2_000.0]);
+        assert!((seconds[0] - 30.0 * 60.0).abs() < 1e-6, "{seconds:?}");
+        assert_eq!(seconds[1], 0.0);
+    }
+}
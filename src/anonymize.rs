@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, SecondsFormat, TimeDelta, Utc};
+use std::collections::hash_map::RandomState;
+use std::fmt::Write as _;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::activity::TrackPoint;
+use crate::stream::read_track_points;
+
+/// How a track is disguised by [`anonymize`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Anonymization {
+    /// Latitude and longitude added to the start, in degrees; the rest of the
+    /// track follows it.
+    pub offset: (f64, f64),
+    /// Clockwise turn of the whole track around its new start, in degrees.
+    pub rotation_deg: f64,
+    /// Day the first timestamp is moved to, keeping its time of day.
+    pub date: Option<NaiveDate>,
+    /// Keep the heart rate, cadence and power, which are left out by default.
+    pub keep_sensors: bool,
+}
+
+/// A random offset of up to 30° of latitude and 180° of longitude, far
+/// enough to tell nothing about where the ride took place.
+pub fn random_offset() -> (f64, f64) {
+    let random = |salt: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(salt);
+        hasher.finish() as f64 / u64::MAX as f64 * 2.0 - 1.0
+    };
+    (random(0) * 30.0, random(1) * 180.0)
+}
+
+/// The tracks of a GPX document moved, turned and rebased as asked, without
+/// their names, device, metadata, waypoints and routes.
+///
+/// The track is moved by a rotation of the sphere rather than by adding the
+/// offset to every point, so that the distances between points, and the
+/// elevations, stay the same.
+pub fn anonymize(bytes: &[u8], anonymization: &Anonymization) -> Result<String> {
+    let points = read_track_points(bytes)?;
+    let first = points.first().context("no track points to anonymize")?;
+
+    let start = unit_vector(first.lat, first.lon);
+    let (lat_offset, lon_offset) = anonymization.offset;
+    let target = unit_vector(
+        (first.lat + lat_offset).clamp(-80.0, 80.0),
+        first.lon + lon_offset,
+    );
+    let days = match (
+        anonymization.date,
+        points.iter().find_map(|point| point.time),
+    ) {
+        (Some(date), Some(time)) => (date - time.date_naive()).num_days(),
+        _ => 0,
+    };
+
+    let mut gpx = String::new();
+    gpx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    gpx.push_str(
+        "<gpx version=\"1.1\" creator=\"cyclemetrics\" \
+         xmlns=\"http://www.topografix.com/GPX/1/1\" \
+         xmlns:gpxtpx=\"http://www.garmin.com/xmlschemas/TrackPointExtension/v1\">\n",
+    );
+    gpx.push_str("  <trk>\n    <trkseg>\n");
+    for (i, point) in points.iter().enumerate() {
+        if i > 0 && point.segment_start {
+            gpx.push_str("    </trkseg>\n    <trkseg>\n");
+        }
+        let moved = rotate(unit_vector(point.lat, point.lon), start, target);
+        let turned = rotate_around(moved, target, -anonymization.rotation_deg.to_radians());
+        let (lat, lon) = coordinates(turned);
+        let _ = write!(gpx, "      <trkpt lat=\"{lat:.7}\" lon=\"{lon:.7}\">");
+        if let Some(elevation) = point.elevation {
+            let _ = write!(gpx, "<ele>{elevation}</ele>");
+        }
+        if let Some(time) = point.time {
+            let time = (time + TimeDelta::days(days)).with_timezone(&Utc);
+            let _ = write!(
+                gpx,
+                "<time>{}</time>",
+                time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+            );
+        }
+        write_extensions(&mut gpx, point, anonymization.keep_sensors);
+        gpx.push_str("</trkpt>\n");
+    }
+    gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+    Ok(gpx)
+}
+
+/// The distance extension, which depends on the points only, and the
+/// sensors when they are kept.
+fn write_extensions(gpx: &mut String, point: &TrackPoint, keep_sensors: bool) {
+    let heart_rate = point.heart_rate.filter(|_| keep_sensors);
+    let cadence = point.cadence.filter(|_| keep_sensors);
+    let power = point.power.filter(|_| keep_sensors);
+    if heart_rate.is_none() && cadence.is_none() && power.is_none() && point.distance_m.is_none() {
+        return;
+    }
+    gpx.push_str("<extensions>");
+    if heart_rate.is_some() || cadence.is_some() {
+        gpx.push_str("<gpxtpx:TrackPointExtension>");
+        if let Some(heart_rate) = heart_rate {
+            let _ = write!(gpx, "<gpxtpx:hr>{heart_rate}</gpxtpx:hr>");
+        }
+        if let Some(cadence) = cadence {
+            let _ = write!(gpx, "<gpxtpx:cad>{cadence}</gpxtpx:cad>");
+        }
+        gpx.push_str("</gpxtpx:TrackPointExtension>");
+    }
+    if let Some(power) = power {
+        let _ = write!(gpx, "<power>{power}</power>");
+    }
+    if let Some(distance) = point.distance_m {
+        let _ = write!(gpx, "<distance>{distance}</distance>");
+    }
+    gpx.push_str("</extensions>");
+}
+
+type Vector = [f64; 3];
+
+fn unit_vector(lat: f64, lon: f64) -> Vector {
+    let (lat, lon) = (lat.to_radians(), lon.to_radians());
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+/// Latitude and longitude of a unit vector, in degrees.
+fn coordinates([x, y, z]: Vector) -> (f64, f64) {
+    (
+        z.clamp(-1.0, 1.0).asin().to_degrees(),
+        y.atan2(x).to_degrees(),
+    )
+}
+
+fn dot(a: Vector, b: Vector) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Vector, b: Vector) -> Vector {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// `v` turned by the rotation of the sphere taking `from` to `to`.
+fn rotate(v: Vector, from: Vector, to: Vector) -> Vector {
+    let axis = cross(from, to);
+    let sin = dot(axis, axis).sqrt();
+    if sin < 1e-15 {
+        return v;
+    }
+    let angle = sin.atan2(dot(from, to));
+    rotate_around(v, axis.map(|c| c / sin), angle)
+}
+
+/// Rodrigues' rotation of `v` by `angle` radians, counterclockwise seen from
+/// outside the sphere, around the unit `axis`.
+fn rotate_around(v: Vector, axis: Vector, angle: f64) -> Vector {
+    let (sin, cos) = angle.sin_cos();
+    let k_cross_v = cross(axis, v);
+    let k_dot_v = dot(axis, v);
+    [0, 1, 2].map(|i| v[i] * cos + k_cross_v[i] * sin + axis[i] * k_dot_v * (1.0 - cos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_start_moves_by_the_offset() {
+        let from = unit_vector(45.0, 5.0);
+        let to = unit_vector(-12.5, 120.0);
+
+        let (lat, lon) = coordinates(rotate(from, from, to));
+        assert!((lat + 12.5).abs() < 1e-9 && (lon - 120.0).abs() < 1e-9);
+        assert_eq!(rotate(from, from, from), from);
+    }
+
+    #[test]
+    fn turns_are_clockwise() {
+        // A quarter turn clockwise around the start takes north to east.
+        let start = unit_vector(0.0, 0.0);
+        let north = unit_vector(1.0, 0.0);
+
+        let (lat, lon) = coordinates(rotate_around(north, start, -90f64.to_radians()));
+        assert!(lat.abs() < 1e-9 && (lon - 1.0).abs() < 1e-9, "{lat} {lon}");
+    }
+
+    #[test]
+    fn random_offsets_differ() {
+        let (lat, lon) = random_offset();
+        assert!(lat.abs() <= 30.0 && lon.abs() <= 180.0);
+        assert_ne!(random_offset(), random_offset());
+    }
+}
@@ -0,0 +1,341 @@
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+use std::collections::VecDeque;
+
+use crate::activity::TrackPoint;
+
+/// Windows of the best efforts, in seconds.
+pub const BEST_WINDOWS_S: [usize; 4] = [5, 60, 300, 1200];
+
+/// Longer gaps between two samples are pauses, counted as zero.
+const MAX_GAP_S: usize = 10;
+
+/// Faster than this between two points is a GPS glitch, in km/h.
+const MAX_SPEED_KMH: f64 = 120.0;
+
+/// A sprint gains at least this speed within [`SPRINT_WINDOW_S`], in km/h.
+const SPRINT_GAIN_KMH: f64 = 8.0;
+
+const SPRINT_WINDOW_S: usize = 5;
+
+/// Accelerations below this speed are not sprints, in km/h.
+const SPRINT_MIN_KMH: f64 = 30.0;
+
+/// Measure the best efforts are computed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BestMetric {
+    Power,
+    Speed,
+}
+
+impl BestMetric {
+    pub fn unit(self) -> &'static str {
+        match self {
+            Self::Power => "W",
+            Self::Speed => "km/h",
+        }
+    }
+}
+
+/// Highest average over each of [`BEST_WINDOWS_S`], `None` for windows
+/// longer than the ride.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bests {
+    pub metric: BestMetric,
+    pub values: [Option<f64>; BEST_WINDOWS_S.len()],
+}
+
+/// A hard acceleration, as detected by [`detect_sprints`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprint {
+    pub peak_kmh: f64,
+    /// Largest speed gain within [`SPRINT_WINDOW_S`], in km/h.
+    pub gain_kmh: f64,
+}
+
+/// Best efforts on the power when the ride has some, on the speed
+/// otherwise. Returns `None` without timestamps.
+pub fn bests(points: &[TrackPoint]) -> Option<Bests> {
+    let (metric, samples) = if points.iter().any(|point| point.power.is_some()) {
+        (BestMetric::Power, power_samples(points))
+    } else {
+        (BestMetric::Speed, speed_samples(points))
+    };
+    if samples.len() < 2 {
+        return None;
+    }
+
+    let series = per_second(&samples);
+    Some(Bests {
+        metric,
+        values: BEST_WINDOWS_S.map(|window| best_average(&series, window)),
+    })
+}
+
+/// The best of each window and metric over many rides, power first.
+pub fn all_time_bests<'a>(bests: impl IntoIterator<Item = &'a Bests>) -> Vec<Bests> {
+    let mut all_time: Vec<Bests> = vec![];
+    for ride in bests {
+        let Some(best) = all_time.iter_mut().find(|best| best.metric == ride.metric) else {
+            all_time.push(*ride);
+            continue;
+        };
+        for (best, value) in best.values.iter_mut().zip(ride.values) {
+            *best = match (*best, value) {
+                (Some(best), Some(value)) => Some(best.max(value)),
+                (best, value) => best.or(value),
+            };
+        }
+    }
+    all_time.sort_by_key(|best| best.metric != BestMetric::Power);
+    all_time
+}
+
+/// Stretches where the speed rises by [`SPRINT_GAIN_KMH`] within
+/// [`SPRINT_WINDOW_S`] to above [`SPRINT_MIN_KMH`], in order.
+pub fn detect_sprints(points: &[TrackPoint]) -> Vec<Sprint> {
+    let speeds = per_second(&speed_samples(points));
+    let slowest = sliding_min(&speeds, SPRINT_WINDOW_S + 1);
+
+    let mut sprints = vec![];
+    let mut current: Option<Sprint> = None;
+    for (&speed, &slowest) in speeds.iter().zip(&slowest) {
+        let gain = speed - slowest;
+        if speed >= SPRINT_MIN_KMH && gain >= SPRINT_GAIN_KMH {
+            let sprint = current.get_or_insert(Sprint {
+                peak_kmh: speed,
+                gain_kmh: gain,
+            });
+            sprint.peak_kmh = sprint.peak_kmh.max(speed);
+            sprint.gain_kmh = sprint.gain_kmh.max(gain);
+        } else if let Some(sprint) = current.take() {
+            sprints.push(sprint);
+        }
+    }
+    sprints.extend(current);
+    sprints
+}
+
+/// Power of the timed points, in seconds since the first one.
+fn power_samples(points: &[TrackPoint]) -> Vec<(f64, f64)> {
+    let Some(start) = points.iter().find_map(|point| point.time) else {
+        return vec![];
+    };
+    points
+        .iter()
+        .filter_map(|point| Some(((point.time? - start).as_seconds_f64(), point.power?)))
+        .collect()
+}
+
+/// Speed between consecutive timed points, in km/h, given at the second
+/// point; the first point has a speed of zero.
+fn speed_samples(points: &[TrackPoint]) -> Vec<(f64, f64)> {
+    let timed: Vec<&TrackPoint> = points.iter().filter(|point| point.time.is_some()).collect();
+    let Some(start) = timed.first().and_then(|point| point.time) else {
+        return vec![];
+    };
+
+    let mut samples = vec![(0.0, 0.0)];
+    for pair in timed.windows(2) {
+        let (Some(t1), Some(t2)) = (pair[0].time, pair[1].time) else {
+            continue;
+        };
+        let seconds = (t2 - t1).as_seconds_f64();
+        if seconds <= 0.0 {
+            continue;
+        }
+        let meters = Haversine.distance(
+            point!(x: pair[0].lon, y: pair[0].lat),
+            point!(x: pair[1].lon, y: pair[1].lat),
+        );
+        let speed = meters / seconds * 3.6;
+        if speed <= MAX_SPEED_KMH {
+            samples.push(((t2 - start).as_seconds_f64(), speed));
+        }
+    }
+    samples
+}
+
+/// One value per second: each sample fills the seconds since the previous
+/// one. Pauses longer than [`MAX_GAP_S`] are zeros, shortened to the longest
+/// window so a timestamp jump cannot grow the series.
+fn per_second(samples: &[(f64, f64)]) -> Vec<f64> {
+    let longest = BEST_WINDOWS_S[BEST_WINDOWS_S.len() - 1];
+    let mut series = vec![];
+    let Some(&(first, _)) = samples.first() else {
+        return series;
+    };
+    let mut previous = first.round() as i64;
+    for &(seconds, value) in &samples[1..] {
+        let seconds = seconds.round() as i64;
+        if seconds <= previous {
+            continue;
+        }
+        let gap = (seconds - previous) as usize;
+        if gap <= MAX_GAP_S {
+            series.extend(std::iter::repeat_n(value, gap));
+        } else {
+            series.extend(std::iter::repeat_n(0.0, gap.min(longest)));
+        }
+        previous = seconds;
+    }
+    series
+}
+
+/// Highest average over `window` consecutive values, from a running sum.
+fn best_average(series: &[f64], window: usize) -> Option<f64> {
+    if window == 0 || series.len() < window {
+        return None;
+    }
+    let mut sum: f64 = series[..window].iter().sum();
+    let mut best = sum;
+    for i in window..series.len() {
+        sum += series[i] - series[i - window];
+        best = best.max(sum);
+    }
+    Some(best / window as f64)
+}
+
+/// Minimum of the last `window` values at each position, in linear time: the
+/// deque holds the indices of increasing values that may still become the
+/// minimum.
+fn sliding_min(values: &[f64], window: usize) -> Vec<f64> {
+    let mut deque: VecDeque<usize> = VecDeque::new();
+    let mut minima = Vec::with_capacity(values.len());
+    for (i, &value) in values.iter().enumerate() {
+        while deque.back().is_some_and(|&back| values[back] >= value) {
+            deque.pop_back();
+        }
+        deque.push_back(i);
+        if deque.front().is_some_and(|&front| front + window <= i) {
+            deque.pop_front();
+        }
+        minima.push(values[deque[0]]);
+    }
+    minima
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::read_track_points;
+    use chrono::{DateTime, Duration};
+
+    /// A GPX file with one point per second riding north, with the power and
+    /// speed of each second.
+    fn synthetic_gpx(seconds: &[(f64, f64)]) -> String {
+        let start = DateTime::parse_from_rfc3339("2024-06-01T07:00:00Z").unwrap();
+        let mut gpx = String::from(
+            "<gpx version=\"1.1\" creator=\"test\" xmlns=\"http://www.topografix.com/GPX/1/1\"><trk><trkseg>",
+        );
+        let mut lat = 45.0;
+        for (i, &(power, speed_kmh)) in seconds.iter().enumerate() {
+            // Haversine meters per degree of latitude.
+            lat += speed_kmh / 3.6 / 111_195.08;
+            gpx.push_str(&format!(
+                "<trkpt lat=\"{lat}\" lon=\"5.0\"><time>{}</time><extensions><power>{power}</power></extensions></trkpt>",
+                (start + Duration::seconds(i as i64)).to_rfc3339(),
+            ));
+        }
+        gpx.push_str("</trkseg></trk></gpx>");
+        gpx
+    }
+
+    #[test]
+    fn twenty_minute_surge() {
+        // An hour at 200 W with 20 minutes at 300 W, and a 10 s kick at 600 W.
+        let mut seconds = vec![(200.0, 30.0); 3600];
+        seconds[1000..2200].fill((300.0, 30.0));
+        seconds[3000..3010].fill((600.0, 30.0));
+        let points = read_track_points(synthetic_gpx(&seconds).as_bytes()).unwrap();
+
+        let bests = bests(&points).unwrap();
+
+        assert_eq!(bests.metric, BestMetric::Power);
+        let [five_s, one_min, five_min, twenty_min] = bests.values.map(Option::unwrap);
+        assert_eq!(five_s, 600.0);
+        assert_eq!(one_min, 300.0);
+        assert_eq!(five_min, 300.0);
+        assert_eq!(twenty_min, 300.0);
+    }
+
+    #[test]
+    fn speed_without_power_meter() {
+        let points: Vec<TrackPoint> =
+            read_track_points(synthetic_gpx(&[(0.0, 36.0); 90]).as_bytes())
+                .unwrap()
+                .into_iter()
+                .map(|point| TrackPoint {
+                    power: None,
+                    ..point
+                })
+                .collect();
+
+        let bests = bests(&points).unwrap();
+
+        assert_eq!(bests.metric, BestMetric::Speed);
+        assert!((bests.values[1].unwrap() - 36.0).abs() < 0.01, "{bests:?}");
+        assert_eq!(bests.values[2], None);
+    }
+
+    #[test]
+    fn sprints_are_hard_accelerations() {
+        // Cruising at 28 km/h, a sprint to 50 km/h, then a slow climb in speed.
+        let mut speeds = vec![28.0; 60];
+        speeds.extend([34.0, 40.0, 46.0, 50.0, 50.0, 42.0, 35.0]);
+        speeds.extend((0..60).map(|i| 28.0 + i as f64 * 0.2));
+        let seconds: Vec<(f64, f64)> = speeds.iter().map(|&speed| (0.0, speed)).collect();
+        let points = read_track_points(synthetic_gpx(&seconds).as_bytes()).unwrap();
+
+        let sprints = detect_sprints(&points);
+
+        assert_eq!(sprints.len(), 1, "{sprints:?}");
+        assert!((sprints[0].peak_kmh - 50.0).abs() < 0.1);
+        assert!((sprints[0].gain_kmh - 22.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn sliding_min_matches_brute_force() {
+        let values = [5.0, 3.0, 4.0, 8.0, 1.0, 2.0, 9.0, 7.0, 6.0];
+        for window in 1..=4 {
+            let brute: Vec<f64> = (0..values.len())
+                .map(|i| {
+                    values[i.saturating_sub(window - 1)..=i]
+                        .iter()
+                        .copied()
+                        .fold(f64::INFINITY, f64::min)
+                })
+                .collect();
+            assert_eq!(sliding_min(&values, window), brute, "window {window}");
+        }
+    }
+
+    #[test]
+    fn all_time_bests_per_metric() {
+        let ride = |metric, values| Bests { metric, values };
+        let rides = [
+            ride(BestMetric::Speed, [Some(40.0), Some(35.0), None, None]),
+            ride(
+                BestMetric::Power,
+                [Some(800.0), Some(400.0), Some(300.0), None],
+            ),
+            ride(
+                BestMetric::Power,
+                [Some(700.0), Some(450.0), Some(280.0), Some(250.0)],
+            ),
+        ];
+
+        let all_time = all_time_bests(&rides);
+
+        assert_eq!(
+            all_time,
+            [
+                ride(
+                    BestMetric::Power,
+                    [Some(800.0), Some(450.0), Some(300.0), Some(250.0)]
+                ),
+                ride(BestMetric::Speed, [Some(40.0), Some(35.0), None, None]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use anyhow::{Context, Result, bail};
+use geo::prelude::Distance;
+use geo::{
+    BoundingRect, Contains, Coord, Haversine, Intersects, LineString, MultiPolygon, Polygon, Rect,
+    point,
+};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::activity::TrackPoint;
+
+/// Outlines built in, described at the top of the file.
+const OUTLINES: &str = include_str!("borders.txt");
+
+/// A country of the outlines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Country {
+    /// ISO 3166-1 alpha-2 code, such as "FR".
+    pub code: String,
+    pub name: String,
+    outline: MultiPolygon,
+    /// Checked first, as most points are far from most countries.
+    bounds: Rect,
+}
+
+impl Country {
+    fn contains(&self, at: Coord) -> bool {
+        self.bounds.intersects(&at) && self.outline.contains(&at)
+    }
+}
+
+/// Country outlines, to find the countries a ride passes through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Borders {
+    countries: Vec<Country>,
+}
+
+/// Distance of a ride in one country.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CountryDistance {
+    pub code: String,
+    pub name: String,
+    pub distance_km: f64,
+}
+
+impl Borders {
+    /// The outlines built in, read once.
+    pub fn embedded() -> &'static Borders {
+        static BORDERS: OnceLock<Borders> = OnceLock::new();
+        BORDERS.get_or_init(|| Borders::parse(OUTLINES).expect("the built-in outlines are valid"))
+    }
+
+    /// Outlines written as the built-in ones: one ring per line, with the
+    /// code, name and "longitude latitude" points separated by tabs and
+    /// commas. Lines starting with `#` are comments.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut rings: BTreeMap<(&str, &str), Vec<Polygon>> = BTreeMap::new();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (code, name, ring) =
+                parse_ring(line).with_context(|| format!("line {}", number + 1))?;
+            rings.entry((code, name)).or_default().push(ring);
+        }
+
+        let countries = rings
+            .into_iter()
+            .filter_map(|((code, name), polygons)| {
+                let outline = MultiPolygon::new(polygons);
+                Some(Country {
+                    code: code.to_string(),
+                    name: name.to_string(),
+                    bounds: outline.bounding_rect()?,
+                    outline,
+                })
+            })
+            .collect();
+        Ok(Self { countries })
+    }
+
+    /// Countries in the order of their codes.
+    pub fn countries(&self) -> &[Country] {
+        &self.countries
+    }
+
+    /// The country containing the point, if any.
+    pub fn country_at(&self, lat: f64, lon: f64) -> Option<&Country> {
+        let at = Coord { x: lon, y: lat };
+        self.countries.iter().find(|country| country.contains(at))
+    }
+
+    /// Distance of the ride in each country, in the order they are reached.
+    /// Each segment counts in the country of its middle, so a ride crossing
+    /// a border is split there; segment gaps and segments outside the
+    /// outlines, at sea or in countries left out, are not counted.
+    pub fn countries_crossed(&self, points: &[TrackPoint]) -> Vec<CountryDistance> {
+        let Some(ride) = ride_bounds(points) else {
+            return vec![];
+        };
+        let candidates: Vec<&Country> = self
+            .countries
+            .iter()
+            .filter(|country| country.bounds.intersects(&ride))
+            .collect();
+
+        let mut crossed: Vec<CountryDistance> = vec![];
+        let mut last: Option<&Country> = None;
+        for pair in points.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            if b.segment_start {
+                continue;
+            }
+            let middle = Coord {
+                x: (a.lon + b.lon) / 2.0,
+                y: (a.lat + b.lat) / 2.0,
+            };
+            // Successive segments are most often in the same country.
+            let country = last.filter(|country| country.contains(middle)).or_else(|| {
+                candidates
+                    .iter()
+                    .copied()
+                    .find(|country| country.contains(middle))
+            });
+            last = country.or(last);
+            let Some(country) = country else {
+                continue;
+            };
+
+            let distance_km =
+                Haversine.distance(point!(x: a.lon, y: a.lat), point!(x: b.lon, y: b.lat)) / 1000.0;
+            match crossed
+                .iter_mut()
+                .find(|crossed| crossed.code == country.code)
+            {
+                Some(crossed) => crossed.distance_km += distance_km,
+                None => crossed.push(CountryDistance {
+                    code: country.code.clone(),
+                    name: country.name.clone(),
+                    distance_km,
+                }),
+            }
+        }
+        crossed
+    }
+}
+
+/// One line of the outlines: the code, name and ring of a country.
+fn parse_ring(line: &str) -> Result<(&str, &str, Polygon)> {
+    let mut fields = line.split('\t');
+    let (Some(code), Some(name), Some(points), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        bail!("expected a code, a name and points separated by tabs");
+    };
+    let points = points
+        .split(',')
+        .map(|point| {
+            let (lon, lat) = point
+                .trim()
+                .split_once(' ')
+                .with_context(|| format!("point {point:?} is not \"lon lat\""))?;
+            Ok(Coord {
+                x: lon.parse()?,
+                y: lat.parse()?,
+            })
+        })
+        .collect::<Result<Vec<Coord>>>()?;
+    if points.len() < 4 {
+        bail!("a ring needs at least 3 points");
+    }
+    Ok((code, name, Polygon::new(LineString::new(points), vec![])))
+}
+
+/// See [`Borders::countries_crossed`], with the built-in outlines.
+pub fn countries_crossed(points: &[TrackPoint]) -> Vec<CountryDistance> {
+    Borders::embedded().countries_crossed(points)
+}
+
+fn ride_bounds(points: &[TrackPoint]) -> Option<Rect> {
+    let coords = points.iter().map(|point| Coord {
+        x: point.lon,
+        y: point.lat,
+    });
+    LineString::from_iter(coords).bounding_rect()
+}
+
+/// Rides and distance in one country, over several rides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CountryTotal {
+    pub code: String,
+    pub name: String,
+    pub rides: usize,
+    pub distance_km: f64,
+}
+
+/// Rides and distance per country, the most ridden first, then by code.
+pub fn country_totals<'a>(
+    rides: impl IntoIterator<Item = &'a [CountryDistance]>,
+) -> Vec<CountryTotal> {
+    let mut totals: BTreeMap<&str, CountryTotal> = BTreeMap::new();
+    for crossed in rides {
+        for country in crossed {
+            let total = totals.entry(&country.code).or_insert_with(|| CountryTotal {
+                code: country.code.clone(),
+                name: country.name.clone(),
+                rides: 0,
+                distance_km: 0.0,
+            });
+            total.rides += 1;
+            total.distance_km += country.distance_km;
+        }
+    }
+
+    let mut totals: Vec<CountryTotal> = totals.into_values().collect();
+    totals.sort_by(|a, b| {
+        b.distance_km
+            .total_cmp(&a.distance_km)
+            .then_with(|| a.code.cmp(&b.code))
+    });
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METERS_PER_DEGREE: f64 = 111_195.0;
+
+    /// South along the road through Le Perthus, from France into Spain, one
+    /// point every 100 m; the border is at 42.46°N.
+    fn le_perthus() -> Vec<TrackPoint> {
+        let step = 100.0 / METERS_PER_DEGREE;
+        (0..=188)
+            .map(|i| TrackPoint {
+                lat: 42.55 - i as f64 * step,
+                lon: 2.86,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_built_in_outlines_place_known_towns() {
+        let borders = Borders::embedded();
+        let code = |lat, lon| {
+            borders
+                .country_at(lat, lon)
+                .map(|country| country.code.as_str())
+        };
+        assert_eq!(code(48.86, 2.35), Some("FR"));
+        assert_eq!(code(46.20, 6.14), Some("CH"));
+        assert_eq!(code(42.51, 1.52), Some("AD"));
+        assert_eq!(code(47.66, 9.17), Some("DE"));
+        assert_eq!(code(41.93, 8.74), Some("FR"));
+        // In the Bay of Biscay.
+        assert_eq!(code(45.0, -3.0), None);
+        assert!(
+            borders
+                .countries()
+                .iter()
+                .all(|country| country.code.len() == 2)
+        );
+    }
+
+    #[test]
+    fn a_ride_across_a_border_is_split_there() {
+        let crossed = countries_crossed(&le_perthus());
+        let codes: Vec<&str> = crossed
+            .iter()
+            .map(|country| country.code.as_str())
+            .collect();
+        assert_eq!(codes, ["FR", "ES"]);
+        assert_eq!(crossed[0].name, "France");
+        // 0.09° and 0.0791° of latitude, give or take a segment.
+        assert!((crossed[0].distance_km - 10.0).abs() <= 0.1, "{crossed:?}");
+        assert!((crossed[1].distance_km - 8.8).abs() <= 0.1, "{crossed:?}");
+
+        let total: f64 = crossed.iter().map(|country| country.distance_km).sum();
+        assert!((total - 18.8).abs() < 1e-3);
+        assert!(countries_crossed(&le_perthus()[..1]).is_empty());
+    }
+
+    #[test]
+    fn totals_add_up_the_rides_of_each_country() {
+        let ride = le_perthus();
+        let france = countries_crossed(&ride[..50]);
+        let border = countries_crossed(&ride);
+        let totals = country_totals([france.as_slice(), border.as_slice()]);
+        let summary: Vec<(&str, usize)> = totals
+            .iter()
+            .map(|total| (total.code.as_str(), total.rides))
+            .collect();
+        assert_eq!(summary, [("FR", 2), ("ES", 1)]);
+        assert!((totals[0].distance_km - 14.9).abs() <= 0.1, "{totals:?}");
+    }
+
+    #[test]
+    fn malformed_outlines_tell_the_line() {
+        let error = Borders::parse("# comment\nXX\tNowhere\t0 0, 1 0, 1 1, 0 0\nYY\tBad\t0 0, 1")
+            .unwrap_err();
+        assert_eq!(format!("{error}"), "line 3");
+        let borders = Borders::parse("XX\tNowhere\t0 0, 1 0, 1 1, 0 0").unwrap();
+        assert_eq!(borders.country_at(0.2, 0.8).unwrap().name, "Nowhere");
+    }
+}
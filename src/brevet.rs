@@ -0,0 +1,210 @@
+use anyhow::{Result, bail};
+use chrono::{DateTime, FixedOffset, TimeDelta};
+
+use crate::activity::TrackPoint;
+use crate::tuning::distance_m;
+
+/// Fastest and slowest speeds allowed by the BRM rules, in km/h, on the
+/// stretch of the brevet up to the given kilometer.
+const BRM_SPEEDS: [(f64, f64, f64); 5] = [
+    (200.0, 34.0, 15.0),
+    (400.0, 32.0, 15.0),
+    (600.0, 30.0, 15.0),
+    (1_000.0, 28.0, 11.428),
+    (1_300.0, 26.0, 13.333),
+];
+
+/// Controls this close to the start close an hour later, at 20 km/h.
+const EARLY_CONTROL_KM: f64 = 60.0;
+
+/// Overall time limits of the standard brevets, by distance, in hours.
+const BRM_TIME_LIMITS: [(f64, f64); 5] = [
+    (200.0, 13.5),
+    (300.0, 20.0),
+    (400.0, 27.0),
+    (600.0, 40.0),
+    (1_000.0, 75.0),
+];
+
+/// A brevet whose finish is at most this much longer than a standard
+/// distance keeps the time limit of that distance.
+const FINISH_SLACK: f64 = 1.1;
+
+/// When a control opens and closes, in seconds from the start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControlWindow {
+    pub opens_seconds: f64,
+    pub closes_seconds: f64,
+}
+
+/// Time window of a control at `distance_km` under the BRM rules; the
+/// `finish` closes at the time limit of the brevet.
+pub fn control_window(distance_km: f64, finish: bool) -> ControlWindow {
+    let hours_at = |speed: fn(&(f64, f64, f64)) -> f64| {
+        let mut from = 0.0;
+        let mut hours = 0.0;
+        for stretch in &BRM_SPEEDS {
+            let km = distance_km.min(stretch.0) - from;
+            if km <= 0.0 {
+                break;
+            }
+            hours += km / speed(stretch);
+            from = stretch.0;
+        }
+        // Past the last stretch, at its speed.
+        let last = &BRM_SPEEDS[BRM_SPEEDS.len() - 1];
+        hours + (distance_km - last.0).max(0.0) / speed(last)
+    };
+    let opens = hours_at(|stretch| stretch.1);
+    let closes = if finish
+        && let Some(&(_, hours)) = BRM_TIME_LIMITS
+            .iter()
+            .rev()
+            .find(|&&(km, _)| km <= distance_km && distance_km <= km * FINISH_SLACK)
+    {
+        hours
+    } else if distance_km <= EARLY_CONTROL_KM {
+        distance_km / 20.0 + 1.0
+    } else {
+        hours_at(|stretch| stretch.2)
+    };
+    ControlWindow {
+        opens_seconds: opens * 3_600.0,
+        closes_seconds: closes * 3_600.0,
+    }
+}
+
+/// Fails unless the control distances are positive and increasing.
+pub fn check_controls(controls: &[f64]) -> Result<()> {
+    let mut previous = 0.0;
+    for &km in controls {
+        if km <= previous {
+            bail!("controls must be positive and increasing, got {km} km after {previous} km");
+        }
+        previous = km;
+    }
+    Ok(())
+}
+
+/// Whether a control was reached within its window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlStatus {
+    Early,
+    InTime,
+    Late,
+    NotReached,
+}
+
+/// A control, with when a ride reached it or a plan gets there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControlTime {
+    pub distance_km: f64,
+    pub window: ControlWindow,
+    /// Time from the start to the control, `None` when the ride is shorter
+    /// or has no timestamps there.
+    pub reached_seconds: Option<f64>,
+}
+
+impl ControlTime {
+    pub fn status(&self) -> ControlStatus {
+        match self.reached_seconds {
+            None => ControlStatus::NotReached,
+            Some(seconds) if seconds < self.window.opens_seconds => ControlStatus::Early,
+            Some(seconds) if seconds > self.window.closes_seconds => ControlStatus::Late,
+            Some(_) => ControlStatus::InTime,
+        }
+    }
+}
+
+/// When a recorded ride reached each control, the last one being the
+/// finish. Gaps between segments are ridden.
+pub fn control_times(points: &[TrackPoint], controls: &[f64]) -> Vec<ControlTime> {
+    let start = points.iter().find_map(|point| point.time);
+    controls
+        .iter()
+        .enumerate()
+        .map(|(i, &distance_km)| ControlTime {
+            distance_km,
+            window: control_window(distance_km, i + 1 == controls.len()),
+            reached_seconds: start.and_then(|start| {
+                let reached = reached_at(points, distance_km * 1_000.0)?;
+                Some((reached - start).as_seconds_f64())
+            }),
+        })
+        .collect()
+}
+
+/// Time at which the track is `target_m` from its start, interpolated
+/// between the two points around it.
+fn reached_at(points: &[TrackPoint], target_m: f64) -> Option<DateTime<FixedOffset>> {
+    let mut along = 0.0;
+    for pair in points.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        let step_m = distance_m(a, b);
+        if along + step_m >= target_m && step_m > 0.0 {
+            let (t1, t2) = (a.time?, b.time?);
+            let share = (target_m - along) / step_m;
+            let millis = ((t2 - t1).num_milliseconds() as f64 * share).round() as i64;
+            return Some(t1 + TimeDelta::milliseconds(millis));
+        }
+        along += step_m;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_follow_the_brm_speeds() {
+        let hours = |window: ControlWindow| {
+            (
+                window.opens_seconds / 3_600.0,
+                window.closes_seconds / 3_600.0,
+            )
+        };
+
+        // 200 km at 34 km/h, then 100 km at 32; closing at 15 km/h.
+        let (opens, closes) = hours(control_window(300.0, false));
+        assert!((opens - (200.0 / 34.0 + 100.0 / 32.0)).abs() < 1e-9);
+        assert!((closes - 20.0).abs() < 1e-9);
+        // Early controls close an hour later.
+        assert_eq!(hours(control_window(40.0, false)).1, 3.0);
+        // The finish of a 205 km brevet closes at the limit of a 200.
+        assert_eq!(hours(control_window(205.0, true)).1, 13.5);
+        assert!((hours(control_window(205.0, false)).1 - 205.0 / 15.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn controls_are_reached_between_two_points() {
+        let start = DateTime::parse_from_rfc3339("2024-06-01T06:00:00+02:00").unwrap();
+        // Every kilometer north in a minute, at 60 km/h.
+        let points: Vec<TrackPoint> = (0..=10)
+            .map(|i| TrackPoint {
+                lat: 45.0 + i as f64 * 1_000.0 / 111_195.08,
+                lon: 5.0,
+                time: Some(start + TimeDelta::minutes(i)),
+                ..Default::default()
+            })
+            .collect();
+
+        let times = control_times(&points, &[2.5, 12.0]);
+
+        let seconds = times[0].reached_seconds.unwrap();
+        assert!((seconds - 150.0).abs() < 0.5, "{seconds}");
+        assert_eq!(times[0].status(), ControlStatus::Early);
+        assert_eq!(times[1].reached_seconds, None);
+        assert_eq!(times[1].status(), ControlStatus::NotReached);
+    }
+
+    #[test]
+    fn controls_must_increase() {
+        assert!(check_controls(&[90.0, 174.0, 261.0]).is_ok());
+        assert_eq!(
+            check_controls(&[90.0, 80.0]).unwrap_err().to_string(),
+            "controls must be positive and increasing, got 80 km after 90 km"
+        );
+        assert!(check_controls(&[0.0]).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use chrono::{Datelike, Days, Months, NaiveDate};
+
+use crate::stats::percentile;
+
+/// Days of the last 12 months in weeks, Monday first, as in the calendar of
+/// the review.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalendarGrid {
+    /// Monday of the first week.
+    monday: NaiveDate,
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+}
+
+impl CalendarGrid {
+    /// The year ending on `today`.
+    pub fn last_year(today: NaiveDate) -> Self {
+        let first = today
+            .checked_sub_months(Months::new(12))
+            .and_then(|date| date.succ_opt())
+            .unwrap_or(today);
+        let monday = first - Days::new(u64::from(first.weekday().num_days_from_monday()));
+        Self {
+            monday,
+            first,
+            last: today,
+        }
+    }
+
+    pub fn weeks(&self) -> usize {
+        self.position(self.last).map_or(0, |(week, _)| week + 1)
+    }
+
+    /// Column (week) and row (weekday) of `date`, if shown.
+    pub fn position(&self, date: NaiveDate) -> Option<(usize, usize)> {
+        if date < self.first || date > self.last {
+            return None;
+        }
+        let days = (date - self.monday).num_days() as usize;
+        Some((days / 7, days % 7))
+    }
+
+    /// Day at a column and row, if shown.
+    pub fn date(&self, week: usize, weekday: usize) -> Option<NaiveDate> {
+        let date = self
+            .monday
+            .checked_add_days(Days::new((week * 7 + weekday) as u64))?;
+        self.position(date).map(|_| date)
+    }
+
+    /// `date` moved by `days`, kept within the grid.
+    pub fn step(&self, date: NaiveDate, days: i64) -> NaiveDate {
+        let moved = if days < 0 {
+            date.checked_sub_days(Days::new(days.unsigned_abs()))
+        } else {
+            date.checked_add_days(Days::new(days as u64))
+        };
+        moved.unwrap_or(date).clamp(self.first, self.last)
+    }
+}
+
+/// Heat levels of the calendar, from rest days to the longest days.
+pub const HEAT_LEVELS: usize = 5;
+
+/// Distance thresholds between the heat levels, taken from the quartiles of
+/// the days ridden, so that a year of short commutes and one of long rides
+/// both use every level.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HeatScale {
+    pub thresholds: [f64; HEAT_LEVELS - 2],
+}
+
+impl HeatScale {
+    pub fn new(distances_km: impl IntoIterator<Item = f64>) -> Self {
+        let mut distances: Vec<f64> = distances_km.into_iter().filter(|&km| km > 0.0).collect();
+        distances.sort_by(f64::total_cmp);
+        let quartile = |p| percentile(&distances, p).unwrap_or_default();
+        Self {
+            thresholds: [quartile(0.25), quartile(0.5), quartile(0.75)],
+        }
+    }
+
+    /// Heat level of a day, 0 when nothing was ridden.
+    pub fn level(&self, km: f64) -> usize {
+        if km <= 0.0 {
+            return 0;
+        }
+        1 + self
+            .thresholds
+            .iter()
+            .filter(|&&threshold| km > threshold)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn the_grid_covers_the_last_twelve_months() {
+        // 2024-06-15 is a Saturday, 2023-06-16 a Friday.
+        let grid = CalendarGrid::last_year(date("2024-06-15"));
+
+        assert_eq!(grid.first, date("2023-06-16"));
+        assert_eq!(grid.position(date("2023-06-16")), Some((0, 4)));
+        assert_eq!(grid.position(date("2023-06-19")), Some((1, 0)));
+        assert_eq!(grid.position(date("2024-06-15")), Some((52, 5)));
+        assert_eq!(grid.position(date("2023-06-15")), None);
+        assert_eq!(grid.weeks(), 53);
+        assert_eq!(grid.date(0, 3), None);
+        assert_eq!(grid.date(1, 0), Some(date("2023-06-19")));
+        assert_eq!(grid.date(52, 6), None);
+    }
+
+    #[test]
+    fn steps_stay_in_the_grid() {
+        let grid = CalendarGrid::last_year(date("2024-06-15"));
+
+        assert_eq!(grid.step(date("2024-06-10"), 7), date("2024-06-15"));
+        assert_eq!(grid.step(date("2024-06-10"), -1), date("2024-06-09"));
+        assert_eq!(grid.step(date("2023-06-20"), -7), date("2023-06-16"));
+    }
+
+    #[test]
+    fn heat_follows_the_distribution() {
+        let commutes = HeatScale::new([0.0, 8.0, 8.0, 9.0, 10.0, 12.0]);
+        assert_eq!(commutes.thresholds, [8.0, 9.0, 10.0]);
+        assert_eq!(
+            [0.0, 8.0, 9.0, 10.0, 12.0].map(|km| commutes.level(km)),
+            [0, 1, 2, 3, 4]
+        );
+
+        let long_rides = HeatScale::new([80.0, 120.0, 200.0, 300.0, 600.0]);
+        assert_eq!(long_rides.level(10.0), 1);
+        assert_eq!(long_rides.level(150.0), 2);
+        assert_eq!(long_rides.level(600.0), 4);
+
+        assert_eq!(HeatScale::new([]).level(5.0), 4);
+    }
+}
@@ -0,0 +1,467 @@
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+use std::collections::HashMap;
+
+use crate::activity::TrackPoint;
+use crate::exploration::{grid_cell, pack};
+use crate::stats::GRADE_SECTION_M;
+use crate::tuning::{DerivedPoint, Tuning, derived_points};
+
+/// Sections at least this steep belong to a climb, in percent.
+pub const CLIMB_MIN_GRADE_PERCENT: f64 = 3.0;
+
+/// Shorter or smaller climbs are left out.
+pub const CLIMB_MIN_LENGTH_M: f64 = 500.0;
+pub const CLIMB_MIN_ASCENT_M: f64 = 30.0;
+
+/// Two ascents of the same climb start and end within this distance, in
+/// meters.
+pub const CLIMB_MATCH_RADIUS_M: f64 = 100.0;
+
+/// Two ascents of the same climb differ in length by at most this share.
+pub(crate) const CLIMB_LENGTH_TOLERANCE: f64 = 0.1;
+
+/// A stretch of road climbing at [`CLIMB_MIN_GRADE_PERCENT`] or more,
+/// section after section.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Climb {
+    /// Bottom and top, as (latitude, longitude).
+    pub start: (f64, f64),
+    pub end: (f64, f64),
+    pub length_m: f64,
+    /// Elevation difference from the bottom to the top.
+    pub ascent_m: f64,
+    /// Time from the bottom to the top; `None` without timestamps.
+    pub seconds: Option<f64>,
+}
+
+impl Climb {
+    pub fn grade_percent(&self) -> f64 {
+        self.ascent_m / self.length_m * 100.0
+    }
+
+    /// Whether two ascents are of the same climb: same bottom and top within
+    /// [`CLIMB_MATCH_RADIUS_M`] and similar lengths.
+    pub fn matches(&self, other: &Climb) -> bool {
+        let near = |a: (f64, f64), b: (f64, f64)| {
+            Haversine.distance(point!(x: a.1, y: a.0), point!(x: b.1, y: b.0))
+                <= CLIMB_MATCH_RADIUS_M
+        };
+        near(self.start, other.start)
+            && near(self.end, other.end)
+            && (self.length_m - other.length_m).abs()
+                <= CLIMB_LENGTH_TOLERANCE * self.length_m.max(other.length_m)
+    }
+
+    fn is_significant(&self) -> bool {
+        self.length_m >= CLIMB_MIN_LENGTH_M && self.ascent_m >= CLIMB_MIN_ASCENT_M
+    }
+}
+
+/// Sections at most this steep belong to a descent, in percent.
+pub const DESCENT_MAX_GRADE_PERCENT: f64 = -4.0;
+
+/// Shorter descents, such as rollers, are left out.
+pub const DESCENT_MIN_LENGTH_M: f64 = 500.0;
+
+/// Rides on each side of the descending trend.
+pub const DESCENDING_TREND_RIDES: usize = 5;
+
+/// Width of the steps of the rate of climb, in seconds.
+pub const CLIMB_RATE_WINDOW_S: f64 = 60.0;
+
+/// A sustained stretch of road descending at [`DESCENT_MAX_GRADE_PERCENT`]
+/// or steeper, section after section.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Descent {
+    /// Top and bottom, as (latitude, longitude).
+    pub start: (f64, f64),
+    pub end: (f64, f64),
+    pub length_m: f64,
+    /// Elevation difference from the top to the bottom.
+    pub drop_m: f64,
+    /// Time from the top to the bottom; `None` without timestamps.
+    pub seconds: Option<f64>,
+}
+
+impl Descent {
+    pub fn grade_percent(&self) -> f64 {
+        -self.drop_m / self.length_m * 100.0
+    }
+}
+
+/// Distance and time on the timed descents of one or more rides.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DescendingSpeed {
+    pub descents: usize,
+    pub length_m: f64,
+    pub seconds: f64,
+}
+
+impl DescendingSpeed {
+    /// Average speed over all the descents, in km/h.
+    pub fn speed_kmh(&self) -> f64 {
+        self.length_m / self.seconds * 3.6
+    }
+
+    fn add(mut self, other: &DescendingSpeed) -> Self {
+        self.descents += other.descents;
+        self.length_m += other.length_m;
+        self.seconds += other.seconds;
+        self
+    }
+}
+
+/// A run of steep sections being followed, with the time at its start.
+/// The elevation difference is negative on descents.
+struct Run {
+    climb: Climb,
+    started: Option<f64>,
+}
+
+impl Run {
+    fn new(from: &DerivedPoint) -> Self {
+        let at = (from.point.lat, from.point.lon);
+        Self {
+            climb: Climb {
+                start: at,
+                end: at,
+                length_m: 0.0,
+                ascent_m: 0.0,
+                seconds: None,
+            },
+            started: from.seconds,
+        }
+    }
+
+    fn extend(&mut self, to: &DerivedPoint, length_m: f64, climb_m: f64) {
+        let climb = &mut self.climb;
+        climb.end = (to.point.lat, to.point.lon);
+        climb.length_m += length_m;
+        climb.ascent_m += climb_m;
+        climb.seconds = self.started.zip(to.seconds).map(|(start, end)| end - start);
+    }
+}
+
+/// The runs of [`GRADE_SECTION_M`] sections whose grade is `steep`, in
+/// order.
+fn graded_runs(points: &[TrackPoint], tuning: &Tuning, steep: impl Fn(f64) -> bool) -> Vec<Climb> {
+    let mut runs = vec![];
+    let mut current: Option<Run> = None;
+    let mut points = derived_points(
+        points.iter().filter(|point| point.elevation.is_some()),
+        tuning,
+    );
+    let Some(mut from) = points.next() else {
+        return runs;
+    };
+
+    for point in points {
+        let length_m = point.distance_m - from.distance_m;
+        if length_m < GRADE_SECTION_M {
+            continue;
+        }
+
+        let climb_m =
+            point.point.elevation.unwrap_or_default() - from.point.elevation.unwrap_or_default();
+        if steep(climb_m / length_m * 100.0) {
+            current
+                .get_or_insert_with(|| Run::new(&from))
+                .extend(&point, length_m, climb_m);
+        } else if let Some(run) = current.take() {
+            runs.push(run.climb);
+        }
+        from = point;
+    }
+    runs.extend(current.map(|run| run.climb));
+    runs
+}
+
+/// The climbs of a ride, in order, from its [`GRADE_SECTION_M`] sections.
+/// The segment gaps `tuning` does not join add no length.
+pub fn detect_climbs(points: &[TrackPoint], tuning: &Tuning) -> Vec<Climb> {
+    let mut climbs = graded_runs(points, tuning, |grade| grade >= CLIMB_MIN_GRADE_PERCENT);
+    climbs.retain(Climb::is_significant);
+    climbs
+}
+
+/// The sustained descents of a ride, in order, from its
+/// [`GRADE_SECTION_M`] sections.
+pub fn detect_descents(points: &[TrackPoint], tuning: &Tuning) -> Vec<Descent> {
+    graded_runs(points, tuning, |grade| grade <= DESCENT_MAX_GRADE_PERCENT)
+        .into_iter()
+        .filter(|run| run.length_m >= DESCENT_MIN_LENGTH_M)
+        .map(|run| Descent {
+            start: run.start,
+            end: run.end,
+            length_m: run.length_m,
+            drop_m: -run.ascent_m,
+            seconds: run.seconds,
+        })
+        .collect()
+}
+
+/// Distance and time on the timed descents; `None` without any.
+pub fn descending_speed(descents: &[Descent]) -> Option<DescendingSpeed> {
+    descents
+        .iter()
+        .filter_map(|descent| {
+            Some(DescendingSpeed {
+                descents: 1,
+                length_m: descent.length_m,
+                seconds: descent.seconds.filter(|&seconds| seconds > 0.0)?,
+            })
+        })
+        .reduce(|total, speed| total.add(&speed))
+}
+
+/// The last [`DESCENDING_TREND_RIDES`] rides, oldest first, added up, and
+/// the rides before them.
+pub fn descending_trend(
+    rides: &[DescendingSpeed],
+) -> (Option<DescendingSpeed>, Option<DescendingSpeed>) {
+    let split = rides.len().saturating_sub(DESCENDING_TREND_RIDES);
+    let previous = rides[split.saturating_sub(DESCENDING_TREND_RIDES)..split]
+        .iter()
+        .copied()
+        .reduce(|total, speed| total.add(&speed));
+    let recent = rides[split..]
+        .iter()
+        .copied()
+        .reduce(|total, speed| total.add(&speed));
+    (recent, previous)
+}
+
+/// Rate of climb of a ride, in m/min, over steps of at least
+/// [`CLIMB_RATE_WINDOW_S`], as (minutes since the start, rate) at the end
+/// of each step.
+pub fn climb_rate(points: &[TrackPoint], tuning: &Tuning) -> Vec<(f64, f64)> {
+    let mut rates = vec![];
+    let mut points = derived_points(points, tuning)
+        .filter_map(|point| Some((point.seconds?, point.point.elevation?)));
+    let Some(mut from) = points.next() else {
+        return rates;
+    };
+    for (seconds, elevation) in points {
+        let minutes = (seconds - from.0) / 60.0;
+        if minutes * 60.0 < CLIMB_RATE_WINDOW_S {
+            continue;
+        }
+        rates.push((seconds / 60.0, (elevation - from.1) / minutes));
+        from = (seconds, elevation);
+    }
+    rates
+}
+
+/// Ascents of one climb, keyed by ride.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClimbGroup<K> {
+    /// The first ascent found, which the others are matched against.
+    pub climb: Climb,
+    pub ascents: Vec<(K, Climb)>,
+}
+
+impl<K> ClimbGroup<K> {
+    /// Timed ascents, fastest first.
+    pub fn leaderboard(&self) -> Vec<&(K, Climb)> {
+        let mut timed: Vec<&(K, Climb)> = self
+            .ascents
+            .iter()
+            .filter(|(_, climb)| climb.seconds.is_some())
+            .collect();
+        timed.sort_by(|(_, a), (_, b)| a.seconds.unwrap().total_cmp(&b.seconds.unwrap()));
+        timed
+    }
+}
+
+/// Group the ascents of the same climbs, in the order given. Groups are
+/// indexed by the cell of their bottom, so each ascent is only compared
+/// with the climbs starting nearby.
+pub fn group_climbs<'a, K: Copy>(
+    climbs: impl IntoIterator<Item = (K, &'a Climb)>,
+) -> Vec<ClimbGroup<K>> {
+    let mut groups: Vec<ClimbGroup<K>> = vec![];
+    let mut by_cell: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (key, climb) in climbs {
+        let (row, column) = grid_cell(climb.start.0, climb.start.1, CLIMB_MATCH_RADIUS_M);
+        let found = (-1..=1)
+            .flat_map(|dr| (-1..=1).map(move |dc| pack((row + dr, column + dc))))
+            .filter_map(|cell| by_cell.get(&cell))
+            .flatten()
+            .copied()
+            .find(|&i| groups[i].climb.matches(climb));
+        match found {
+            Some(i) => groups[i].ascents.push((key, *climb)),
+            None => {
+                by_cell
+                    .entry(pack((row, column)))
+                    .or_default()
+                    .push(groups.len());
+                groups.push(ClimbGroup {
+                    climb: *climb,
+                    ascents: vec![(key, *climb)],
+                });
+            }
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::read_track_points;
+    use crate::tuning::cumulative_distance;
+    use chrono::{DateTime, Duration};
+    use std::path::Path;
+
+    fn fixture_points(name: &str) -> Vec<TrackPoint> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        read_track_points(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    /// A ride north along a road at 5 m per point: flat for `flat_m`, then
+    /// climbing 1 km at 6%, ridden at `climb_kmh`.
+    fn ride(flat_m: f64, climb_kmh: f64) -> Vec<TrackPoint> {
+        let start = DateTime::parse_from_rfc3339("2024-06-01T07:00:00Z").unwrap();
+        let mut points = vec![];
+        let mut seconds = 0.0;
+        let mut position = 0.0;
+        while position <= flat_m + 1_000.0 {
+            let climbing = position > flat_m;
+            let elevation = 200.0 + (position - flat_m).max(0.0) * 0.06;
+            points.push(TrackPoint {
+                lat: 45.0 + position / 111_195.08,
+                lon: 5.0,
+                elevation: Some(elevation),
+                time: Some(start + Duration::milliseconds((seconds * 1_000.0) as i64)),
+                ..Default::default()
+            });
+            let kmh = if climbing { climb_kmh } else { 30.0 };
+            seconds += 5.0 / (kmh / 3.6);
+            position += 5.0;
+        }
+        points
+    }
+
+    #[test]
+    fn climbs_are_found_after_the_flat() {
+        let climbs = detect_climbs(&ride(1_000.0, 12.0), &Tuning::default());
+
+        assert_eq!(climbs.len(), 1, "{climbs:?}");
+        let climb = climbs[0];
+        assert!((climb.length_m - 1_000.0).abs() <= GRADE_SECTION_M + 5.0);
+        assert!((climb.grade_percent() - 6.0).abs() < 0.1);
+        // 1 km at 12 km/h.
+        assert!((climb.seconds.unwrap() - 300.0).abs() <= 35.0);
+        assert!(detect_climbs(&ride(1_000.0, 12.0)[..150], &Tuning::default()).is_empty());
+    }
+
+    #[test]
+    fn a_climb_across_a_segment_gap_measures_as_the_distance() {
+        // A dropout halfway up the climb, 500 m further north.
+        let mut points = ride(1_000.0, 12.0);
+        let gap = points.len() - 100;
+        points[gap].segment_start = true;
+        for point in &mut points[gap..] {
+            point.lat += 500.0 / 111_195.08;
+        }
+        let tuning = Tuning::default();
+
+        let climbs = detect_climbs(&points, &tuning);
+        assert_eq!(climbs.len(), 1, "{climbs:?}");
+        let at = |(lat, lon): (f64, f64)| {
+            cumulative_distance(&points, &tuning)
+                .find(|(_, point)| (point.lat, point.lon) == (lat, lon))
+                .map(|(meters, _)| meters)
+                .unwrap()
+        };
+        let measured = at(climbs[0].end) - at(climbs[0].start);
+        assert!((climbs[0].length_m - measured).abs() < 1e-6, "{climbs:?}");
+        assert!(climbs[0].length_m < 1_000.0 + GRADE_SECTION_M);
+    }
+
+    #[test]
+    fn repeated_ascents_are_grouped() {
+        let slow = detect_climbs(&ride(1_000.0, 10.0), &Tuning::default());
+        let fast = detect_climbs(&ride(1_000.0, 14.0), &Tuning::default());
+        // The same road climbed from further away is another climb.
+        let elsewhere: Vec<Climb> = detect_climbs(&ride(3_000.0, 12.0), &Tuning::default());
+
+        let groups = group_climbs([
+            ("slow", &slow[0]),
+            ("elsewhere", &elsewhere[0]),
+            ("fast", &fast[0]),
+        ]);
+
+        assert_eq!(groups.len(), 2);
+        let leaderboard: Vec<&str> = groups[0]
+            .leaderboard()
+            .iter()
+            .map(|(ride, _)| *ride)
+            .collect();
+        assert_eq!(leaderboard, ["fast", "slow"]);
+    }
+
+    #[test]
+    fn the_long_descent_counts_but_not_the_roller() {
+        let points = fixture_points("alpine_descent.gpx");
+
+        let descents = detect_descents(&points, &Tuning::default());
+        assert_eq!(descents.len(), 1, "{descents:?}");
+        let descent = descents[0];
+        assert!((descent.length_m - 6_000.0).abs() <= GRADE_SECTION_M + 5.0);
+        assert!((descent.grade_percent() + 6.0).abs() < 0.1);
+        let speed = descending_speed(&descents).unwrap();
+        assert_eq!(speed.descents, 1);
+        assert!((speed.speed_kmh() - 54.0).abs() < 0.5, "{speed:?}");
+
+        let climbs = detect_climbs(&points, &Tuning::default());
+        assert_eq!(climbs.len(), 1, "{climbs:?}");
+        // From the flat after the descent: only the roller is left.
+        assert!(detect_descents(&points[445..], &Tuning::default()).is_empty());
+    }
+
+    #[test]
+    fn climb_rate_is_up_on_the_climb_and_down_on_the_descent() {
+        let points = fixture_points("alpine_descent.gpx");
+
+        let rates = climb_rate(&points, &Tuning::default());
+        // 1.8 m every 9 s up, 1.8 m every 2 s down.
+        let at = |minute: f64| {
+            rates
+                .iter()
+                .find(|&&(at, _)| at >= minute)
+                .map(|&(_, rate)| rate)
+                .unwrap()
+        };
+        assert!((at(10.0) - 12.0).abs() < 0.1, "{rates:?}");
+        assert!((at(36.5) + 54.0).abs() < 0.1, "{rates:?}");
+        assert!(rates.windows(2).all(|pair| pair[1].0 - pair[0].0 >= 1.0));
+        assert!(climb_rate(&points[..5], &Tuning::default()).is_empty());
+    }
+
+    #[test]
+    fn descending_trend_compares_the_last_rides_with_the_ones_before() {
+        let ride = |speed_kmh: f64| DescendingSpeed {
+            descents: 1,
+            length_m: speed_kmh / 3.6 * 100.0,
+            seconds: 100.0,
+        };
+        let mut rides: Vec<DescendingSpeed> = [40.0; 5].map(ride).to_vec();
+        rides.extend([50.0; 5].map(ride));
+        rides.insert(0, ride(10.0));
+
+        let (recent, previous) = descending_trend(&rides);
+        assert!((recent.unwrap().speed_kmh() - 50.0).abs() < 1e-9);
+        assert!((previous.unwrap().speed_kmh() - 40.0).abs() < 1e-9);
+        assert_eq!(recent.unwrap().descents, DESCENDING_TREND_RIDES);
+
+        let (recent, previous) = descending_trend(&rides[..3]);
+        assert_eq!(recent.unwrap().descents, 3);
+        assert!(previous.is_none());
+        assert_eq!(descending_trend(&[]), (None, None));
+    }
+}
@@ -0,0 +1,200 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// A key of the interface, with or without Control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub control: bool,
+}
+
+impl Key {
+    pub const fn new(code: KeyCode) -> Self {
+        Self {
+            code,
+            control: false,
+        }
+    }
+
+    pub const fn char(c: char) -> Self {
+        Self::new(KeyCode::Char(c))
+    }
+
+    pub const fn control(c: char) -> Self {
+        Self {
+            code: KeyCode::Char(c),
+            control: true,
+        }
+    }
+
+    /// Whether a key press is this key. Shift is ignored, as terminals report
+    /// it for characters such as `<` or `?`.
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        self.code == event.code && self.control == event.modifiers.contains(KeyModifiers::CONTROL)
+    }
+
+    /// How the key is written in the help, e.g. `^r` or `space`.
+    pub fn label(&self) -> String {
+        let code = match self.code {
+            KeyCode::Char(' ') => "space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            KeyCode::Tab => "Tab".to_string(),
+            KeyCode::Esc => "Esc".to_string(),
+            KeyCode::Enter => "Enter".to_string(),
+            code => code.to_string(),
+        };
+        if self.control {
+            format!("^{code}")
+        } else {
+            code
+        }
+    }
+}
+
+/// An action of the interface, run by its keys, from the command palette
+/// or listed in the help.
+#[derive(Debug)]
+pub struct UserCommand<T> {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub keys: &'static [Key],
+    pub run: fn(&mut T),
+}
+
+impl<T> UserCommand<T> {
+    /// The keys, e.g. `j ↓`.
+    pub fn keys_label(&self) -> String {
+        self.keys
+            .iter()
+            .map(Key::label)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// The command run by a key press.
+pub fn command_for<'a, T>(
+    commands: &'a [UserCommand<T>],
+    event: &KeyEvent,
+) -> Option<&'a UserCommand<T>> {
+    commands
+        .iter()
+        .find(|command| command.keys.iter().any(|key| key.matches(event)))
+}
+
+/// How well `query` matches `text`, ignoring case: its characters must
+/// appear in order. Consecutive characters and the starts of words score
+/// more; `None` when it does not match.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 2;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+/// The commands whose name or description match `query`, best first; all of
+/// them, in order, for an empty query. `label` gives the text shown for a
+/// name or a description, such as its translation.
+pub fn search<'a, T>(
+    commands: &'a [UserCommand<T>],
+    query: &str,
+    label: impl Fn(&'static str) -> &'a str,
+) -> Vec<&'a UserCommand<T>> {
+    let mut found: Vec<(u32, &UserCommand<T>)> = commands
+        .iter()
+        .filter_map(|command| {
+            let name = fuzzy_score(query, label(command.name));
+            let description = fuzzy_score(query, label(command.description));
+            // A match in the name counts more than one in the description.
+            let score = name.map(|score| score * 2).max(description)?;
+            Some((score, command))
+        })
+        .collect();
+    // Stable, so equal scores keep the registry order.
+    found.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    found.into_iter().map(|(_, command)| command).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMANDS: &[UserCommand<Vec<&str>>] = &[
+        UserCommand {
+            name: "archive",
+            description: "Move the ride to the archive",
+            keys: &[Key::char('a')],
+            run: |log| log.push("archive"),
+        },
+        UserCommand {
+            name: "redo",
+            description: "Redo the last undone change",
+            keys: &[Key::control('r')],
+            run: |log| log.push("redo"),
+        },
+        UserCommand {
+            name: "rename",
+            description: "Give the ride another name",
+            keys: &[Key::char('r')],
+            run: |log| log.push("rename"),
+        },
+    ];
+
+    fn names(commands: Vec<&UserCommand<Vec<&str>>>) -> Vec<&'static str> {
+        commands.iter().map(|command| command.name).collect()
+    }
+
+    #[test]
+    fn keys_find_their_command() {
+        let mut log = vec![];
+        for event in [
+            KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL),
+            KeyEvent::from(KeyCode::Char('r')),
+        ] {
+            (command_for(COMMANDS, &event).unwrap().run)(&mut log);
+        }
+
+        assert_eq!(log, ["redo", "rename"]);
+        assert!(command_for(COMMANDS, &KeyEvent::from(KeyCode::Char('z'))).is_none());
+        assert_eq!(Key::control('p').label(), "^p");
+        assert_eq!(Key::char(' ').label(), "space");
+    }
+
+    #[test]
+    fn fuzzy_search_ranks_names_first() {
+        assert_eq!(names(search(COMMANDS, "", |text| text)).len(), 3);
+        assert_eq!(
+            names(search(COMMANDS, "ren", |text| text)),
+            ["rename", "redo"]
+        );
+        // "ride" is in two descriptions; "re" starts two names.
+        assert_eq!(
+            names(search(COMMANDS, "re", |text| text)),
+            ["redo", "rename", "archive"]
+        );
+        assert_eq!(names(search(COMMANDS, "undone", |text| text)), ["redo"]);
+        assert!(search(COMMANDS, "xyz", |text| text).is_empty());
+    }
+
+    #[test]
+    fn consecutive_characters_score_more() {
+        assert!(fuzzy_score("arc", "archive") > fuzzy_score("arc", "a rich case"));
+        assert_eq!(fuzzy_score("ca", "archive"), None);
+    }
+}
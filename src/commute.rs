@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use crate::activity::Activity;
+use crate::config::{CommuteLocation, Config};
+
+const DEFAULT_COMMUTE_MAX_KM: f64 = 15.0;
+const DEFAULT_COMMUTE_MIN_REPEATS: usize = 20;
+
+/// Size of the grid cells used to recognize repeated routes, in degrees
+/// (~300 m of latitude).
+const ROUTE_GRID_DEG: f64 = 0.003;
+
+/// Tell for each activity whether it looks like a commute.
+///
+/// A ride is a commute when it goes from one configured commute location to
+/// another one, or when it is short and its start and end cells are shared by
+/// at least `commute_min_repeats` short rides. Virtual rides never are.
+pub fn detect_commutes<'a>(
+    activities: impl IntoIterator<Item = &'a Activity>,
+    config: &Config,
+) -> Vec<bool> {
+    let activities: Vec<&Activity> = activities.into_iter().collect();
+    let max_km = config.commute_max_km.unwrap_or(DEFAULT_COMMUTE_MAX_KM);
+    let min_repeats = config
+        .commute_min_repeats
+        .unwrap_or(DEFAULT_COMMUTE_MIN_REPEATS);
+
+    let routes: Vec<Option<RouteKey>> = activities
+        .iter()
+        .map(|activity| {
+            (activity.distance_km <= max_km)
+                .then(|| route_key(activity))
+                .flatten()
+        })
+        .collect();
+
+    let mut repeats: HashMap<RouteKey, usize> = HashMap::new();
+    for route in routes.iter().flatten() {
+        *repeats.entry(*route).or_default() += 1;
+    }
+
+    activities
+        .iter()
+        .zip(&routes)
+        .map(|(activity, route)| {
+            !activity.virtual_ride
+                && (between_locations(activity, &config.commute_locations)
+                    || route.is_some_and(|route| repeats[&route] >= min_repeats))
+        })
+        .collect()
+}
+
+/// Start and end grid cells; the order does not matter so both directions of
+/// a commute share a key.
+type RouteKey = ((i64, i64), (i64, i64));
+
+fn route_key(activity: &Activity) -> Option<RouteKey> {
+    let cell = |lat: f64, lon: f64| {
+        (
+            (lat / ROUTE_GRID_DEG).round() as i64,
+            (lon / ROUTE_GRID_DEG).round() as i64,
+        )
+    };
+    let (start, end) = activity.start_point.zip(activity.end_point)?;
+    let (start, end) = (cell(start.0, start.1), cell(end.0, end.1));
+
+    Some((start.min(end), start.max(end)))
+}
+
+fn between_locations(activity: &Activity, locations: &[CommuteLocation]) -> bool {
+    let Some((start, end)) = activity.start_point.zip(activity.end_point) else {
+        return false;
+    };
+    let location_of = |lat: f64, lon: f64| {
+        locations
+            .iter()
+            .position(|location| location.contains(lat, lon))
+    };
+
+    match (location_of(start.0, start.1), location_of(end.0, end.1)) {
+        (Some(from), Some(to)) => from != to,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ride(from: (f64, f64), to: (f64, f64), distance_km: f64) -> Activity {
+        Activity {
+            distance_km,
+            start_point: Some(from),
+            end_point: Some(to),
+            ..Default::default()
+        }
+    }
+
+    fn location(name: &str, lat: f64, lon: f64) -> CommuteLocation {
+        CommuteLocation {
+            name: name.to_string(),
+            lat,
+            lon,
+            radius_m: 200.0,
+        }
+    }
+
+    #[test]
+    fn rides_between_locations_are_commutes() {
+        let config = Config {
+            commute_locations: vec![location("home", 45.0, 5.0), location("work", 45.05, 5.0)],
+            ..Default::default()
+        };
+        let activities = [
+            ride((45.0005, 5.0), (45.05, 5.0005), 6.0),
+            ride((45.05, 5.0), (45.0, 5.0), 6.0),
+            // A loop from home is not a commute.
+            ride((45.0, 5.0), (45.0, 5.0), 6.0),
+            ride((45.0, 5.0), (46.0, 5.0), 120.0),
+        ];
+
+        assert_eq!(
+            detect_commutes(&activities, &config),
+            [true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn repeated_short_routes_are_commutes() {
+        let config = Config {
+            commute_min_repeats: Some(3),
+            ..Default::default()
+        };
+        let activities = [
+            ride((45.0, 5.0), (45.05, 5.0), 6.0),
+            ride((45.05, 5.0), (45.0, 5.0), 6.0),
+            ride((45.0, 5.0), (45.05, 5.0), 6.0),
+            // Same ends, but too long.
+            ride((45.0, 5.0), (45.05, 5.0), 60.0),
+            ride((45.0, 5.0), (45.2, 5.0), 6.0),
+        ];
+
+        assert_eq!(
+            detect_commutes(&activities, &config),
+            [true, true, true, false, false]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Weekday;
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::altitude::DEFAULT_ALTITUDE_THRESHOLDS_M;
+use crate::gpx::GradeModel;
+use crate::i18n::Lang;
+use crate::intensity::{IntensityThresholds, IntensityZones};
+use crate::privacy::PrivacyZone;
+use crate::rules::TagRule;
+use crate::share::ShareTemplate;
+use crate::stats::DEFAULT_WEEKEND;
+use crate::storage::write_atomic;
+use crate::theme::ThemeName;
+use crate::transport::TransportDetection;
+use crate::tuning::Tuning;
+
+/// User configuration, read from `config.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Places between which rides are tagged as commutes.
+    pub commute_locations: Vec<CommuteLocation>,
+    /// Rides shorter than this may be tagged as commutes when their route is
+    /// repeated often enough, in kilometers.
+    pub commute_max_km: Option<f64>,
+    /// How many times a short route must be ridden to count as a commute.
+    pub commute_min_repeats: Option<usize>,
+    /// Functional threshold power, in watts; used to detect intervals.
+    pub ftp: Option<f64>,
+    /// Share of the FTP above which an effort counts as an interval.
+    pub interval_ftp_fraction: Option<f64>,
+    /// Maximum heart rate, in bpm; used to classify the intensity of rides.
+    pub max_hr: Option<f64>,
+    /// Bounds of the intensity classes.
+    pub intensity: IntensityZones,
+    /// Distances of the brevet controls from the start, in kilometers, the
+    /// last one being the finish.
+    pub controls: Vec<f64>,
+    /// Weighting of the grade-adjusted distance, `piecewise` by default.
+    pub grade_model: Option<GradeModel>,
+    /// Automatic tagging rules, such as
+    /// `tag "gravel" when name contains "gravel" or avg_speed < 20`.
+    pub tag_rules: Vec<String>,
+    /// Places whose points are left out of exported geometry.
+    pub privacy_zones: Vec<PrivacyZone>,
+    /// Exports warn about the places outside of the zones where an activity
+    /// starts or ends with more than this many others, 2 by default.
+    pub privacy_warning_others: Option<usize>,
+    /// Share text of a ride, such as `{date} – {name}: {distance}`; see
+    /// [`ShareTemplate`].
+    pub share_template: Option<String>,
+    /// Language of the interface and the reviews, `en` by default.
+    pub lang: Option<Lang>,
+    /// Colors of the interface; by default, those matching the terminal
+    /// background when it tells, else `dark`.
+    pub theme: Option<ThemeName>,
+    /// Parameters of the distance, elevation gain and moving time, as set in
+    /// the settings popup.
+    pub tuning: Tuning,
+    /// Parameters of the detection of ferry and train stretches.
+    pub transport: TransportDetection,
+    /// Runs whose totals are kept for `history`, 20 by default.
+    pub run_history: Option<usize>,
+    /// Days of the weekend, such as `["fri", "sat"]`; Saturday and Sunday
+    /// by default.
+    pub weekend: Vec<String>,
+    /// Elevations the time spent above is measured at, in meters; 1500,
+    /// 2000 and 2500 by default.
+    pub altitude_thresholds: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommuteLocation {
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(default = "default_commute_radius")]
+    pub radius_m: f64,
+}
+
+impl CommuteLocation {
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        Haversine.distance(point!(x: lon, y: lat), point!(x: self.lon, y: self.lat))
+            <= self.radius_m
+    }
+}
+
+fn default_commute_radius() -> f64 {
+    200.0
+}
+
+impl Config {
+    /// `$CYCLEMETRICS_CONFIG`, `$XDG_CONFIG_HOME/cyclemetrics/config.toml` or
+    /// `~/.config/cyclemetrics/config.toml`, in that order.
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = std::env::var_os("CYCLEMETRICS_CONFIG") {
+            return PathBuf::from(path);
+        }
+        let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => std::env::var_os("HOME")
+                .map(PathBuf::from)
+                .unwrap_or_default()
+                .join(".config"),
+        };
+        dir.join("cyclemetrics/config.toml")
+    }
+
+    /// Name of the configured location covering a point, if any.
+    pub fn location_name(&self, lat: f64, lon: f64) -> Option<&str> {
+        self.commute_locations
+            .iter()
+            .find(|location| location.contains(lat, lon))
+            .map(|location| location.name.as_str())
+    }
+
+    /// Parse the tagging rules, failing on the first invalid one.
+    pub fn tag_rules(&self) -> Result<Vec<TagRule>> {
+        self.tag_rules
+            .iter()
+            .map(|rule| TagRule::parse(rule))
+            .collect()
+    }
+
+    /// The FTP and maximum heart rate of the configuration, or their
+    /// defaults, with the configured zones.
+    pub fn intensity_thresholds(&self) -> IntensityThresholds {
+        IntensityThresholds::new(self.ftp, self.max_hr, self.intensity)
+    }
+
+    /// Parse the days of the weekend, or give the default ones.
+    pub fn weekend_days(&self) -> Result<Vec<Weekday>> {
+        if self.weekend.is_empty() {
+            return Ok(DEFAULT_WEEKEND.to_vec());
+        }
+        self.weekend
+            .iter()
+            .map(|day| {
+                day.parse()
+                    .map_err(|_| anyhow!("invalid weekend day {day:?}, expected e.g. \"sat\""))
+            })
+            .collect()
+    }
+
+    /// The altitude thresholds, lowest first, or the default ones.
+    pub fn altitude_thresholds(&self) -> Vec<f64> {
+        if self.altitude_thresholds.is_empty() {
+            return DEFAULT_ALTITUDE_THRESHOLDS_M.to_vec();
+        }
+        let mut thresholds = self.altitude_thresholds.clone();
+        thresholds.sort_by(f64::total_cmp);
+        thresholds.dedup();
+        thresholds
+    }
+
+    /// Parse the share template, or give the default one.
+    pub fn share_template(&self) -> Result<ShareTemplate> {
+        self.share_template
+            .as_deref()
+            .map_or_else(|| Ok(ShareTemplate::default()), ShareTemplate::parse)
+    }
+
+    /// Read the configuration; a missing file gives the default one.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Write the tuning to the `[tuning]` table of the configuration file,
+    /// keeping the rest of the file as it is.
+    pub fn save_tuning(path: &Path, tuning: &Tuning) -> Result<()> {
+        let content = if path.exists() {
+            fs::read_to_string(path)?
+        } else {
+            String::new()
+        };
+        let mut kept = String::new();
+        let mut in_tuning = false;
+        for line in content.lines() {
+            let header = line.trim();
+            if header.starts_with('[') {
+                in_tuning = header == "[tuning]";
+            }
+            if !in_tuning {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+        }
+
+        let mut updated = kept.trim_end().to_string();
+        if !updated.is_empty() {
+            updated.push_str("\n\n");
+        }
+        updated.push_str("[tuning]\n");
+        updated.push_str(&toml::to_string(tuning)?);
+        // Fails when the tuning is also written another way, e.g. inline.
+        toml::from_str::<Config>(&updated)
+            .with_context(|| format!("updating {}", path.display()))?;
+
+        write_atomic(path, updated.as_bytes())
+    }
+}
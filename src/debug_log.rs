@@ -0,0 +1,26 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::Level;
+
+/// Name of the `--debug` trace in the data directory.
+pub const DEBUG_LOG_FILE: &str = "debug.jsonl";
+
+/// Send the debug events to `path`, one JSON object per line, replacing
+/// what an earlier run wrote there.
+pub fn init_debug_log(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    let file = File::create(path).with_context(|| format!("writing {}", path.display()))?;
+    tracing_subscriber::fmt()
+        .json()
+        .with_current_span(true)
+        .with_span_list(false)
+        .with_max_level(Level::DEBUG)
+        .with_writer(Mutex::new(file))
+        .try_init()
+        .map_err(|err| anyhow::anyhow!("{err}"))
+        .context("starting the debug log")
+}
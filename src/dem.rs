@@ -0,0 +1,182 @@
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::activity::TrackPoint;
+
+/// Value used by SRTM for missing samples.
+const VOID: i16 = -32768;
+
+/// One SRTM `.hgt` tile: a square grid of big-endian `i16` elevations covering
+/// one degree of latitude and longitude, first row being the northern edge.
+#[derive(Debug)]
+struct Tile {
+    size: usize,
+    samples: Vec<i16>,
+}
+
+impl Tile {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        let size = ((bytes.len() / 2) as f64).sqrt() as usize;
+        if size < 2 || size * size * 2 != bytes.len() {
+            bail!("invalid HGT tile of {} bytes", bytes.len());
+        }
+        let samples = bytes
+            .chunks_exact(2)
+            .map(|pair| i16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+
+        Ok(Self { size, samples })
+    }
+
+    fn sample(&self, row: usize, col: usize) -> Option<f64> {
+        let value = self.samples[row * self.size + col];
+        (value != VOID).then_some(f64::from(value))
+    }
+
+    /// Bilinear interpolation at an offset within the tile, both in `0..=1`
+    /// from the south-west corner.
+    fn elevation(&self, lat_offset: f64, lon_offset: f64) -> Option<f64> {
+        let last = (self.size - 1) as f64;
+        let y = (1.0 - lat_offset) * last;
+        let x = lon_offset * last;
+        let (row, col) = (
+            (y.floor() as usize).min(self.size - 2),
+            (x.floor() as usize).min(self.size - 2),
+        );
+        let (dy, dx) = (y - row as f64, x - col as f64);
+
+        let top = self.sample(row, col)? * (1.0 - dx) + self.sample(row, col + 1)? * dx;
+        let bottom = self.sample(row + 1, col)? * (1.0 - dx) + self.sample(row + 1, col + 1)? * dx;
+        Some(top * (1.0 - dy) + bottom * dy)
+    }
+}
+
+/// Tiles by the latitude and longitude of their south-west corner, `None`
+/// when the tile is not available.
+type TileCache = HashMap<(i32, i32), Option<Arc<Tile>>>;
+
+/// Offline digital elevation model backed by a directory of SRTM tiles.
+///
+/// Tiles are read lazily and shared between the loading threads.
+#[derive(Debug)]
+pub struct Dem {
+    dir: PathBuf,
+    tiles: Mutex<TileCache>,
+}
+
+impl Dem {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            tiles: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Name of the tile whose south-west corner is at `lat`, `lon`, e.g.
+    /// `N45E005`.
+    fn tile_name(lat: i32, lon: i32) -> String {
+        format!(
+            "{}{:02}{}{:03}",
+            if lat >= 0 { 'N' } else { 'S' },
+            lat.abs(),
+            if lon >= 0 { 'E' } else { 'W' },
+            lon.abs()
+        )
+    }
+
+    fn tile(&self, lat: i32, lon: i32) -> Option<Arc<Tile>> {
+        let mut tiles = self.tiles.lock().unwrap();
+        tiles
+            .entry((lat, lon))
+            .or_insert_with(|| {
+                let path = self.dir.join(format!("{}.hgt", Self::tile_name(lat, lon)));
+                let bytes = fs::read(path).ok()?;
+                Tile::parse(&bytes).ok().map(Arc::new)
+            })
+            .clone()
+    }
+
+    /// Elevation at a coordinate, `None` if the tile is missing or the sample
+    /// is void.
+    pub fn elevation(&self, lat: f64, lon: f64) -> Option<f64> {
+        let (tile_lat, tile_lon) = (lat.floor(), lon.floor());
+        self.tile(tile_lat as i32, tile_lon as i32)?
+            .elevation(lat - tile_lat, lon - tile_lon)
+    }
+
+    /// Replace every point elevation with the DEM value.
+    ///
+    /// The points are left untouched if any of them falls outside the
+    /// available tiles, and the names of the missing tiles are returned as
+    /// the error.
+    pub fn correct(&self, points: &mut [TrackPoint]) -> Result<()> {
+        let mut corrected = vec![];
+        let mut missing = vec![];
+        for point in points {
+            let (lat, lon) = (point.lat, point.lon);
+            match self.elevation(lat, lon) {
+                Some(elevation) => corrected.push((point, elevation)),
+                None => {
+                    let name = Self::tile_name(lat.floor() as i32, lon.floor() as i32);
+                    if !missing.contains(&name) {
+                        missing.push(name);
+                    }
+                }
+            }
+        }
+
+        if !missing.is_empty() {
+            bail!("missing DEM tiles: {}", missing.join(", "));
+        }
+        for (point, elevation) in corrected {
+            point.elevation = Some(elevation);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 3x3 tile rising from 0 m in the south to 200 m in the north, in a
+    /// directory of its own for the test `name`.
+    fn synthetic_dem(name: &str) -> Dem {
+        let dir =
+            std::env::temp_dir().join(format!("cyclemetrics-dem-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let bytes: Vec<u8> = [200i16, 200, 200, 100, 100, 100, 0, 0, 0]
+            .iter()
+            .flat_map(|sample| sample.to_be_bytes())
+            .collect();
+        fs::write(dir.join("N45E005.hgt"), bytes).unwrap();
+
+        Dem::new(dir)
+    }
+
+    #[test]
+    fn tile_names() {
+        assert_eq!(Dem::tile_name(45, 5), "N45E005");
+        assert_eq!(Dem::tile_name(-1, -73), "S01W073");
+    }
+
+    #[test]
+    fn bilinear_interpolation() {
+        let dem = synthetic_dem("bilinear");
+        assert_eq!(dem.elevation(45.0, 5.5), Some(0.0));
+        assert_eq!(dem.elevation(45.25, 5.5), Some(50.0));
+        assert_eq!(dem.elevation(45.75, 5.1), Some(150.0));
+        fs::remove_dir_all(&dem.dir).unwrap();
+    }
+
+    #[test]
+    fn missing_tile() {
+        let dem = synthetic_dem("missing");
+        assert_eq!(dem.elevation(46.5, 5.5), None);
+        fs::remove_dir_all(&dem.dir).unwrap();
+    }
+}
@@ -0,0 +1,115 @@
+use geo::prelude::Distance;
+use geo::{Geodesic, Haversine, point};
+
+use crate::activity::TrackPoint;
+use crate::tuning::{Tuning, kept_points};
+
+/// How the distance between two points is measured. The metrics use
+/// [`DistanceModel::Haversine`]; the others are there to compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceModel {
+    /// On a sphere of the mean radius of the Earth.
+    Haversine,
+    /// On the WGS84 ellipsoid, as most GPS devices and sites do.
+    Geodesic,
+    /// Haversine with the difference in elevation, along the slope.
+    ThreeD,
+}
+
+impl DistanceModel {
+    pub const ALL: [Self; 3] = [Self::Haversine, Self::Geodesic, Self::ThreeD];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Haversine => "haversine",
+            Self::Geodesic => "geodesic",
+            Self::ThreeD => "3D",
+        }
+    }
+
+    /// Meters from `a` to `b`; [`DistanceModel::ThreeD`] is flat where an
+    /// elevation is missing.
+    pub fn distance_m(self, a: &TrackPoint, b: &TrackPoint) -> f64 {
+        let (a_point, b_point) = (point!(x: a.lon, y: a.lat), point!(x: b.lon, y: b.lat));
+        match self {
+            Self::Haversine => Haversine.distance(a_point, b_point),
+            Self::Geodesic => Geodesic.distance(a_point, b_point),
+            Self::ThreeD => {
+                let flat = Haversine.distance(a_point, b_point);
+                match (a.elevation, b.elevation) {
+                    (Some(a), Some(b)) => flat.hypot(b - a),
+                    _ => flat,
+                }
+            }
+        }
+    }
+}
+
+/// Length of a track under each [`DistanceModel`], in km, by the order of
+/// [`DistanceModel::ALL`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelDistances {
+    pub km: [f64; 3],
+}
+
+impl ModelDistances {
+    /// Over the points the tuning keeps, like [`crate::tuned_distance_m`].
+    pub fn of(points: &[TrackPoint], tuning: &Tuning) -> Self {
+        let mut km = [0.0; 3];
+        let mut previous: Option<&TrackPoint> = None;
+        for (starts, point) in kept_points(points, tuning) {
+            if !starts && let Some(previous) = previous {
+                for (total, model) in km.iter_mut().zip(DistanceModel::ALL) {
+                    *total += model.distance_m(previous, point) / 1_000.0;
+                }
+            }
+            previous = Some(point);
+        }
+        Self { km }
+    }
+
+    pub fn km(&self, model: DistanceModel) -> f64 {
+        self.km[model as usize]
+    }
+
+    /// How much longer the track is under `model` than under the haversine
+    /// one, in percent; `None` for an empty track.
+    pub fn difference_percent(&self, model: DistanceModel) -> Option<f64> {
+        let haversine = self.km(DistanceModel::Haversine);
+        (haversine > 0.0).then(|| (self.km(model) / haversine - 1.0) * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::SyntheticRide;
+
+    #[test]
+    fn the_models_differ_a_little() {
+        // 2 km flat then 2 km at 10%, north from 45°N.
+        let points = SyntheticRide::new().flat(2.0).climb(2.0, 10.0).points();
+        let distances = ModelDistances::of(&points, &Tuning::default());
+
+        let haversine = distances.km(DistanceModel::Haversine);
+        assert!((haversine - 4.0).abs() < 0.05, "{distances:?}");
+        assert_eq!(
+            distances.difference_percent(DistanceModel::Haversine),
+            Some(0.0)
+        );
+        // A degree of latitude is shorter at 45° on the ellipsoid than on
+        // the sphere.
+        let geodesic = distances
+            .difference_percent(DistanceModel::Geodesic)
+            .unwrap();
+        assert!((-0.1..-0.03).contains(&geodesic), "{geodesic}");
+        // Along a 10% slope, 0.5% longer over half of the ride.
+        let three_d = distances.difference_percent(DistanceModel::ThreeD).unwrap();
+        assert!((0.2..0.3).contains(&three_d), "{three_d}");
+
+        assert_eq!(
+            ModelDistances::of(&[], &Tuning::default()).difference_percent(DistanceModel::ThreeD),
+            None
+        );
+    }
+}
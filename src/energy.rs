@@ -0,0 +1,148 @@
+use crate::activity::{MOVING_MIN_SPEED, TrackPoint};
+use crate::tuning::distance_m;
+
+/// Rider and bike, in kilograms, for the modelled energy.
+pub const SYSTEM_MASS_KG: f64 = 85.0;
+const ROLLING_RESISTANCE: f64 = 0.005;
+/// Drag coefficient times frontal area, on the hoods, in m².
+const DRAG_AREA_M2: f64 = 0.32;
+const AIR_DENSITY: f64 = 1.225;
+const GRAVITY: f64 = 9.81;
+
+/// Share of the energy of the food that reaches the pedals; at about 24%, a
+/// kilojoule of work costs about one kilocalorie.
+pub const MUSCLE_EFFICIENCY: f64 = 0.24;
+const KJ_PER_KCAL: f64 = 4.184;
+
+/// Share of the moving time with power readings above which the power meter
+/// is trusted over the model.
+pub const POWER_COVERAGE: f64 = 0.8;
+
+/// How an energy estimate was made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnergySource {
+    /// From a power meter.
+    Power,
+    /// From the speed and the grade, for a rider and bike of
+    /// [`SYSTEM_MASS_KG`] without wind.
+    Model,
+}
+
+/// Mechanical work of a ride.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Energy {
+    pub kilojoules: f64,
+    pub source: EnergySource,
+}
+
+/// Kilocalories burnt to produce `kilojoules` of work at the pedals.
+pub fn kcal(kilojoules: f64) -> f64 {
+    kilojoules / MUSCLE_EFFICIENCY / KJ_PER_KCAL
+}
+
+/// Work done while moving: the recorded power when it covers
+/// [`POWER_COVERAGE`] of the moving time, else the power needed against
+/// rolling resistance, air and gravity at the recorded speed. Descents give
+/// nothing back. `None` without timestamps.
+pub fn estimate_energy(points: &[TrackPoint]) -> Option<Energy> {
+    let (mut moving_s, mut powered_s) = (0.0, 0.0);
+    let (mut power_j, mut model_j) = (0.0, 0.0);
+    for pair in points.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        if b.segment_start {
+            continue;
+        }
+        let (Some(t1), Some(t2)) = (a.time, b.time) else {
+            continue;
+        };
+        let seconds = (t2 - t1).as_seconds_f64();
+        let distance = distance_m(a, b);
+        if seconds <= 0.0 || distance / seconds < MOVING_MIN_SPEED {
+            continue;
+        }
+        moving_s += seconds;
+        if let (Some(p1), Some(p2)) = (a.power, b.power) {
+            powered_s += seconds;
+            power_j += (p1 + p2) / 2.0 * seconds;
+        }
+        let speed = distance / seconds;
+        let climb = b.elevation.zip(a.elevation).map_or(0.0, |(e2, e1)| e2 - e1);
+        let watts = SYSTEM_MASS_KG * GRAVITY * (ROLLING_RESISTANCE * speed + climb / seconds)
+            + 0.5 * AIR_DENSITY * DRAG_AREA_M2 * speed.powi(3);
+        model_j += watts.max(0.0) * seconds;
+    }
+    if moving_s <= 0.0 {
+        return None;
+    }
+    Some(if powered_s >= POWER_COVERAGE * moving_s {
+        Energy {
+            kilojoules: power_j / 1_000.0,
+            source: EnergySource::Power,
+        }
+    } else {
+        Energy {
+            kilojoules: model_j / 1_000.0,
+            source: EnergySource::Model,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, TimeDelta};
+
+    /// Points going north every 100 m, 10 s apart, at the given elevations
+    /// and power.
+    fn ride(elevations: &[f64], power: Option<f64>) -> Vec<TrackPoint> {
+        let start = DateTime::parse_from_rfc3339("2024-06-01T07:00:00Z").unwrap();
+        elevations
+            .iter()
+            .enumerate()
+            .map(|(i, &elevation)| TrackPoint {
+                lat: 45.0 + i as f64 * 100.0 / 111_195.08,
+                lon: 5.0,
+                elevation: Some(elevation),
+                time: Some(start + TimeDelta::seconds(i as i64 * 10)),
+                power,
+                segment_start: i == 0,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn power_meters_are_trusted() {
+        // 100 steps of 10 s at 200 W.
+        let energy = estimate_energy(&ride(&[100.0; 101], Some(200.0))).unwrap();
+
+        assert_eq!(energy.source, EnergySource::Power);
+        assert!((energy.kilojoules - 200.0).abs() < 1e-9);
+        assert!((kcal(energy.kilojoules) - 199.17).abs() < 0.01);
+    }
+
+    #[test]
+    fn the_model_adds_rolling_air_and_climbing() {
+        // 10 m/s on the flat: 85 × 9.81 × 0.005 × 10 + 0.5 × 1.225 × 0.32 ×
+        // 1000 = 41.69 + 196 W, for 1000 s.
+        let flat = estimate_energy(&ride(&[100.0; 101], None)).unwrap();
+        assert_eq!(flat.source, EnergySource::Model);
+        assert!((flat.kilojoules - 237.69).abs() < 0.1, "{flat:?}");
+
+        // Climbing 1 m per step adds 85 × 9.81 × 1 J per step.
+        let elevations: Vec<f64> = (0..=100).map(f64::from).collect();
+        let climb = estimate_energy(&ride(&elevations, None)).unwrap();
+        assert!((climb.kilojoules - flat.kilojoules - 83.385).abs() < 0.1);
+
+        // Descending gives nothing back.
+        let descent: Vec<f64> = (0..=100).map(|i| 1_000.0 - f64::from(i) * 10.0).collect();
+        assert!(estimate_energy(&ride(&descent, None)).unwrap().kilojoules < 0.001);
+    }
+
+    #[test]
+    fn untimed_tracks_have_no_energy() {
+        let mut points = ride(&[100.0; 3], None);
+        points.iter_mut().for_each(|point| point.time = None);
+        assert_eq!(estimate_energy(&points), None);
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::anyhow;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Exit status of a failed command, telling scripts what went wrong; 0 is
+/// success.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// Some files could not be read, or the output could not be written.
+    Failed = 1,
+    /// No file matches the paths or patterns given.
+    NoFiles = 2,
+    /// Invalid arguments or configuration.
+    Usage = 3,
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+/// Error ending a command, with the status to exit with.
+#[derive(Debug)]
+pub struct Failure {
+    pub status: ExitStatus,
+    pub error: anyhow::Error,
+}
+
+impl Failure {
+    pub fn usage(error: impl Into<anyhow::Error>) -> Self {
+        Self {
+            status: ExitStatus::Usage,
+            error: error.into(),
+        }
+    }
+
+    pub fn no_files(patterns: &[PathBuf]) -> Self {
+        let patterns: Vec<String> = patterns
+            .iter()
+            .map(|pattern| pattern.display().to_string())
+            .collect();
+        Self {
+            status: ExitStatus::NoFiles,
+            error: anyhow!("no file matches {}", patterns.join(" ")),
+        }
+    }
+
+    /// Fails when any of the `total` files could not be read.
+    pub fn check_unreadable(failed: usize, total: usize) -> Result<(), Self> {
+        if failed == 0 {
+            return Ok(());
+        }
+        Err(Self {
+            status: ExitStatus::Failed,
+            error: anyhow!("{failed} of {total} files could not be read"),
+        })
+    }
+}
+
+impl From<anyhow::Error> for Failure {
+    fn from(error: anyhow::Error) -> Self {
+        Self {
+            status: ExitStatus::Failed,
+            error,
+        }
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        anyhow::Error::from(error).into()
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes_are_distinct() {
+        let codes =
+            [ExitStatus::Failed, ExitStatus::NoFiles, ExitStatus::Usage].map(|status| status as u8);
+        assert_eq!(codes, [1, 2, 3]);
+        assert_eq!(
+            Failure::no_files(&["*.gpx".into(), "rides/".into()]).to_string(),
+            "no file matches *.gpx rides/"
+        );
+        assert!(Failure::check_unreadable(0, 3).is_ok());
+        let failure = Failure::check_unreadable(1, 3).unwrap_err();
+        assert_eq!(failure.status, ExitStatus::Failed);
+        assert_eq!(failure.to_string(), "1 of 3 files could not be read");
+    }
+}
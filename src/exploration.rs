@@ -0,0 +1,414 @@
+use chrono::Datelike;
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::activity::{Activity, ActivityId, TrackPoint};
+
+/// Side of the grid cells roads are snapped to, in meters.
+pub const CELL_SIZE_M: f64 = 30.0;
+
+/// Cells remembered at most, about 120 000 km of distinct roads. Once full,
+/// every cell outside the set counts as new.
+const MAX_VISITED_CELLS: usize = 4_000_000;
+
+/// Side of the cells of the exploration score, in meters: about a km².
+pub const EXPLORATION_CELL_M: f64 = 1_000.0;
+
+/// Exploration cells remembered at most per year and over all years, well
+/// beyond the area of a country.
+const MAX_EXPLORED_CELLS: usize = 1_000_000;
+
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+fn cell(lat: f64, lon: f64) -> (i32, i32) {
+    grid_cell(lat, lon, CELL_SIZE_M)
+}
+
+/// Row and column of the grid cell of a point, for cells of `size_m` meters.
+pub(crate) fn grid_cell(lat: f64, lon: f64, size_m: f64) -> (i32, i32) {
+    let row = (lat * METERS_PER_DEGREE / size_m).floor();
+    // Columns are as wide as rows are high at the center of the row.
+    let row_lat = (row + 0.5) * size_m / METERS_PER_DEGREE;
+    let column = (lon * METERS_PER_DEGREE * row_lat.to_radians().cos() / size_m).floor();
+    (row as i32, column as i32)
+}
+
+/// The row in the high bits, the column in the low ones.
+pub(crate) fn pack((row, column): (i32, i32)) -> u64 {
+    (u64::from(row as u32) << 32) | u64::from(column as u32)
+}
+
+/// What new roads are computed from for one ride, much smaller than its
+/// points so it can be kept once they are dropped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoadTrace {
+    /// Cell each segment of the ride ends in, with the segment length in
+    /// meters; consecutive segments ending in the same cell are merged.
+    segments: Vec<((i32, i32), f64)>,
+    /// Cells along the ride, one sample per cell size so sparse recordings
+    /// leave no gaps, each once in the order reached.
+    cells: Vec<u64>,
+    /// Exploration cells of [`EXPLORATION_CELL_M`] along the same samples,
+    /// each once.
+    areas: Vec<u64>,
+}
+
+impl RoadTrace {
+    pub fn new(points: &[TrackPoint]) -> Self {
+        let mut trace = Self::default();
+        let mut seen = HashSet::new();
+        let mut areas = HashSet::new();
+        for pair in points.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            let distance =
+                Haversine.distance(point!(x: a.lon, y: a.lat), point!(x: b.lon, y: b.lat));
+            let end = cell(b.lat, b.lon);
+            match trace.segments.last_mut() {
+                Some((last, meters)) if *last == end => *meters += distance,
+                _ => trace.segments.push((end, distance)),
+            }
+
+            let steps = (distance / CELL_SIZE_M).ceil().max(1.0) as usize;
+            for step in 0..=steps {
+                let t = step as f64 / steps as f64;
+                let lat = a.lat + (b.lat - a.lat) * t;
+                let lon = a.lon + (b.lon - a.lon) * t;
+                let sample = pack(cell(lat, lon));
+                if seen.insert(sample) {
+                    trace.cells.push(sample);
+                    let area = pack(grid_cell(lat, lon, EXPLORATION_CELL_M));
+                    if areas.insert(area) {
+                        trace.areas.push(area);
+                    }
+                }
+            }
+        }
+        trace
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+}
+
+/// Packed grid cells, up to a number of them; once full, no more are
+/// added.
+#[derive(Debug)]
+struct CellSet {
+    cells: HashSet<u64>,
+    max: usize,
+}
+
+impl CellSet {
+    fn new(max: usize) -> Self {
+        Self {
+            cells: HashSet::new(),
+            max,
+        }
+    }
+
+    fn contains(&self, cell: u64) -> bool {
+        self.cells.contains(&cell)
+    }
+
+    /// A point is on a known road when its cell or a neighbouring one was
+    /// ridden, which absorbs the GPS offset between two rides.
+    fn contains_near(&self, (row, column): (i32, i32)) -> bool {
+        (-1..=1).any(|dr| (-1..=1).any(|dc| self.contains(pack((row + dr, column + dc)))))
+    }
+
+    /// Add cells until the set is full.
+    fn extend(&mut self, cells: impl IntoIterator<Item = u64>) {
+        for cell in cells {
+            if self.cells.len() >= self.max {
+                return;
+            }
+            self.cells.insert(cell);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+}
+
+/// Kilometers of each ride on roads not ridden by any earlier activity.
+///
+/// Activities are replayed by start date (undated ones last, then by id), so
+/// the result does not depend on the loading order. A segment is new when
+/// its end point lies away from every cell ridden before that ride; riding
+/// the same road twice within a ride counts it twice. Virtual rides and
+/// activities without track points (streamed large files) are left out.
+pub fn new_road_km<'a>(
+    activities: impl IntoIterator<Item = &'a Activity>,
+) -> BTreeMap<ActivityId, f64> {
+    let rides: Vec<(&Activity, RoadTrace)> = activities
+        .into_iter()
+        .map(|activity| (activity, RoadTrace::new(&activity.points)))
+        .collect();
+    new_road_km_from_traces(rides.iter().map(|(activity, trace)| (*activity, trace)))
+}
+
+/// [`new_road_km`] from the traces of the rides, for activities whose points
+/// are no longer in memory.
+pub fn new_road_km_from_traces<'a>(
+    rides: impl IntoIterator<Item = (&'a Activity, &'a RoadTrace)>,
+) -> BTreeMap<ActivityId, f64> {
+    let mut rides: Vec<(&Activity, &RoadTrace)> = rides
+        .into_iter()
+        .filter(|(activity, trace)| !activity.virtual_ride && !trace.is_empty())
+        .collect();
+    rides.sort_by_key(|(activity, _)| (activity.start.is_none(), activity.start, activity.id));
+
+    let mut visited = CellSet::new(MAX_VISITED_CELLS);
+    let mut new_roads = BTreeMap::new();
+    for (activity, trace) in rides {
+        let new_m: f64 = trace
+            .segments
+            .iter()
+            .filter(|(end, _)| !visited.contains_near(*end))
+            .map(|(_, meters)| meters)
+            .sum();
+        visited.extend(trace.cells.iter().copied());
+        new_roads.insert(activity.id, new_m / 1_000.0);
+    }
+
+    new_roads
+}
+
+/// New road kilometers per year the rides started in.
+pub fn new_km_by_year<'a>(
+    activities: impl IntoIterator<Item = &'a Activity>,
+    new_roads: &BTreeMap<ActivityId, f64>,
+) -> BTreeMap<i32, f64> {
+    let mut years = BTreeMap::new();
+    for activity in activities {
+        if let (Some(start), Some(km)) = (activity.start, new_roads.get(&activity.id)) {
+            *years.entry(start.year()).or_default() += km;
+        }
+    }
+    years
+}
+
+/// Distinct exploration cells visited in a year, against the years before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExplorationYear {
+    pub year: i32,
+    /// Cells of about a km², see [`EXPLORATION_CELL_M`].
+    pub cells: usize,
+    /// Cells not visited in any earlier year.
+    pub new_cells: usize,
+    /// The latest earlier year with rides, and its cells.
+    pub previous: Option<(i32, usize)>,
+}
+
+impl ExplorationYear {
+    /// Change of the cells over the previous year, in percent.
+    pub fn change_percent(&self) -> Option<f64> {
+        self.previous
+            .filter(|&(_, cells)| cells > 0)
+            .map(|(_, cells)| (self.cells as f64 / cells as f64 - 1.0) * 100.0)
+    }
+}
+
+/// Exploration cells visited each year the rides started in, oldest first.
+/// A cell visited in several years counts for each of them. Virtual rides
+/// are left out.
+pub fn exploration_by_year<'a>(
+    rides: impl IntoIterator<Item = (&'a Activity, &'a RoadTrace)>,
+) -> Vec<ExplorationYear> {
+    let mut years: BTreeMap<i32, CellSet> = BTreeMap::new();
+    for (activity, trace) in rides {
+        let Some(start) = activity.start.filter(|_| !activity.virtual_ride) else {
+            continue;
+        };
+        years
+            .entry(start.year())
+            .or_insert_with(|| CellSet::new(MAX_EXPLORED_CELLS))
+            .extend(trace.areas.iter().copied());
+    }
+
+    let mut explored = CellSet::new(MAX_EXPLORED_CELLS);
+    let mut previous = None;
+    let mut exploration = vec![];
+    for (year, cells) in years {
+        let new_cells = cells
+            .cells
+            .iter()
+            .filter(|&&cell| !explored.contains(cell))
+            .count();
+        explored.extend(cells.cells.iter().copied());
+        exploration.push(ExplorationYear {
+            year,
+            cells: cells.len(),
+            new_cells,
+            previous,
+        });
+        previous = Some((year, cells.len()));
+    }
+    exploration
+}
+
+/// [`exploration_by_year`] from the points of the rides.
+pub fn exploration<'a>(activities: impl IntoIterator<Item = &'a Activity>) -> Vec<ExplorationYear> {
+    let rides: Vec<(&Activity, RoadTrace)> = activities
+        .into_iter()
+        .map(|activity| (activity, RoadTrace::new(&activity.points)))
+        .collect();
+    exploration_by_year(rides.iter().map(|(activity, trace)| (*activity, trace)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    /// A 1 km square, one point every 10 m, shifted north by `offset_m`.
+    fn square_loop(offset_m: f64) -> Vec<TrackPoint> {
+        let step = 10.0 / METERS_PER_DEGREE;
+        let (lat0, lon0) = (45.0 + offset_m / METERS_PER_DEGREE, 5.0);
+        let side = 25;
+        let lon_step = step / 45f64.to_radians().cos();
+        let mut points = vec![];
+        for i in 0..side {
+            points.push((lat0, lon0 + i as f64 * lon_step));
+        }
+        for i in 0..side {
+            points.push((lat0 + i as f64 * step, lon0 + side as f64 * lon_step));
+        }
+        for i in 0..side {
+            points.push((
+                lat0 + side as f64 * step,
+                lon0 + (side - i) as f64 * lon_step,
+            ));
+        }
+        for i in 0..=side {
+            points.push((lat0 + (side - i) as f64 * step, lon0));
+        }
+        points
+            .into_iter()
+            .map(|(lat, lon)| TrackPoint {
+                lat,
+                lon,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn ride(id: &str, start: &str, points: Vec<TrackPoint>) -> Activity {
+        Activity {
+            id: id.parse().unwrap(),
+            start: Some(DateTime::parse_from_rfc3339(start).unwrap()),
+            distance_km: 1.0,
+            points,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn same_loop_twice_is_not_new() {
+        let first = ride(
+            "1-0000000000000001",
+            "2024-05-01T08:00:00Z",
+            square_loop(0.0),
+        );
+        let second = ride(
+            "2-0000000000000002",
+            "2024-06-01T08:00:00Z",
+            square_loop(8.0),
+        );
+
+        // Given in reverse order, replayed by date.
+        let new_roads = new_road_km([&second, &first]);
+
+        assert!((new_roads[&first.id] - 1.0).abs() < 0.02, "{new_roads:?}");
+        assert!(new_roads[&second.id] < 0.01, "{new_roads:?}");
+        assert_eq!(
+            new_km_by_year([&first, &second], &new_roads)
+                .keys()
+                .collect::<Vec<_>>(),
+            [&2024]
+        );
+    }
+
+    #[test]
+    fn sparse_recordings_leave_no_gaps() {
+        let dense = ride(
+            "1-0000000000000001",
+            "2024-05-01T08:00:00Z",
+            square_loop(0.0),
+        );
+        // One point every 120 m on the same loop.
+        let sparse_points = square_loop(0.0).into_iter().step_by(12).collect();
+        let sparse = ride("2-0000000000000002", "2024-04-01T08:00:00Z", sparse_points);
+
+        let new_roads = new_road_km([&dense, &sparse]);
+
+        assert!(new_roads[&dense.id] < 0.01, "{new_roads:?}");
+    }
+
+    #[test]
+    fn traces_give_the_same_new_roads() {
+        let first = ride(
+            "1-0000000000000001",
+            "2024-05-01T08:00:00Z",
+            square_loop(0.0),
+        );
+        let second = ride(
+            "2-0000000000000002",
+            "2024-06-01T08:00:00Z",
+            square_loop(200.0),
+        );
+        let traces = [&first, &second].map(|activity| RoadTrace::new(&activity.points));
+
+        let from_traces = new_road_km_from_traces([(&second, &traces[1]), (&first, &traces[0])]);
+
+        assert_eq!(from_traces, new_road_km([&first, &second]));
+        assert!(traces[0].segments.len() < first.points.len());
+    }
+
+    #[test]
+    fn cells_count_for_each_year() {
+        let home = ride(
+            "1-0000000000000001",
+            "2023-05-01T08:00:00Z",
+            square_loop(0.0),
+        );
+        let again = ride(
+            "2-0000000000000002",
+            "2024-05-01T08:00:00Z",
+            square_loop(0.0),
+        );
+        let away = ride(
+            "3-0000000000000003",
+            "2024-06-01T08:00:00Z",
+            square_loop(3_000.0),
+        );
+        let mut indoor = ride(
+            "4-0000000000000004",
+            "2025-01-01T08:00:00Z",
+            square_loop(0.0),
+        );
+        indoor.virtual_ride = true;
+
+        let years = exploration([&away, &home, &again, &indoor]);
+
+        assert_eq!(years.len(), 2, "{years:?}");
+        let (last, first) = (years[1], years[0]);
+        assert_eq!((first.year, first.new_cells), (2023, first.cells));
+        assert_eq!(first.previous, None);
+        assert_eq!(last.year, 2024);
+        assert!(last.cells > first.cells, "{years:?}");
+        assert_eq!(last.new_cells, last.cells - first.cells);
+        assert_eq!(last.previous, Some((2023, first.cells)));
+        let change = last.change_percent().unwrap();
+        assert!((change - (last.cells as f64 / first.cells as f64 - 1.0) * 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rides_without_points_are_skipped() {
+        let activity = Activity::default();
+        assert!(new_road_km([&activity]).is_empty());
+    }
+}
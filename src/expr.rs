@@ -0,0 +1,702 @@
+use anyhow::{Context, Result, bail};
+
+use crate::activity::Activity;
+
+/// An expression over the metrics of an activity, as written in the tagging
+/// rules (`name contains "gravel" or avg_speed < 20`) and the expression
+/// columns (`elevation_m / distance_km`).
+///
+/// Numbers combine with `+`, `-`, `*` and `/` and compare with `<`, `<=`,
+/// `>`, `>=`, `==` and `!=`; `name` compares with `==`, `!=` and
+/// `contains`, ignoring case. Conditions combine with `not`, `and` and
+/// `or`, from the tightest; parentheses group. Types are checked when
+/// parsing, so an expression that parses always evaluates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    node: Node,
+}
+
+impl Expr {
+    /// Parse a condition, for the tagging rules.
+    pub fn condition(source: &str) -> Result<Self> {
+        Self::parse(source, 0, Kind::Condition)
+    }
+
+    /// Parse an arithmetic expression, for the expression columns.
+    pub fn number(source: &str) -> Result<Self> {
+        Self::parse(source, 0, Kind::Number)
+    }
+
+    /// Parse `source` as it appears after `offset` characters of a longer
+    /// text, to which the columns of the errors refer.
+    pub(crate) fn parse(source: &str, offset: usize, kind: Kind) -> Result<Self> {
+        Parser::new(source, offset)?.rest(kind)
+    }
+
+    /// Whether a condition holds. Comparisons with a missing metric (no
+    /// timestamps for `avg_speed`) or a division by zero are false.
+    pub fn holds(&self, activity: &Activity) -> bool {
+        self.node.holds(activity)
+    }
+
+    /// Value of an arithmetic expression, `None` when a metric is missing or
+    /// on a division by zero.
+    pub fn evaluate(&self, activity: &Activity) -> Option<f64> {
+        self.node.number(activity)
+    }
+}
+
+/// Type of an expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Number,
+    Text,
+    Condition,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Number => "a number",
+            Kind::Text => "a string",
+            Kind::Condition => "a condition",
+        }
+    }
+}
+
+/// Metrics an expression can refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    DistanceKm,
+    ElevationM,
+    ElevationPerKm,
+    AvgSpeed,
+    MovingH,
+}
+
+impl Field {
+    const ALL: [(&str, Field); 6] = [
+        ("name", Field::Name),
+        ("distance_km", Field::DistanceKm),
+        ("elevation_m", Field::ElevationM),
+        ("elevation_per_km", Field::ElevationPerKm),
+        ("avg_speed", Field::AvgSpeed),
+        ("moving_h", Field::MovingH),
+    ];
+
+    fn kind(self) -> Kind {
+        match self {
+            Field::Name => Kind::Text,
+            _ => Kind::Number,
+        }
+    }
+
+    fn number(self, activity: &Activity) -> Option<f64> {
+        match self {
+            Field::Name => None,
+            Field::DistanceKm => Some(activity.distance_km),
+            Field::ElevationM => Some(activity.elevation_gain),
+            Field::ElevationPerKm => {
+                (activity.distance_km > 0.0).then(|| activity.elevation_gain / activity.distance_km)
+            }
+            Field::AvgSpeed => activity.average_speed(),
+            Field::MovingH => activity.moving_seconds().map(|seconds| seconds / 3600.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Comparison {
+    fn holds<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            Comparison::Lt => a < b,
+            Comparison::Le => a <= b,
+            Comparison::Gt => a > b,
+            Comparison::Ge => a >= b,
+            Comparison::Eq => a == b,
+            Comparison::Ne => a != b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Operator {
+    fn apply(self, a: f64, b: f64) -> Option<f64> {
+        match self {
+            Operator::Add => Some(a + b),
+            Operator::Subtract => Some(a - b),
+            Operator::Multiply => Some(a * b),
+            Operator::Divide => (b != 0.0).then(|| a / b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Text(String),
+    Field(Field),
+    Negate(Box<Node>),
+    Arithmetic(Box<Node>, Operator, Box<Node>),
+    Compare(Box<Node>, Comparison, Box<Node>),
+    Contains(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn number(&self, activity: &Activity) -> Option<f64> {
+        match self {
+            Node::Number(number) => Some(*number),
+            Node::Field(field) => field.number(activity),
+            Node::Negate(a) => a.number(activity).map(|a| -a),
+            Node::Arithmetic(a, operator, b) => {
+                operator.apply(a.number(activity)?, b.number(activity)?)
+            }
+            _ => None,
+        }
+    }
+
+    /// Texts are compared in lower case.
+    fn text(&self, activity: &Activity) -> Option<String> {
+        match self {
+            Node::Text(text) => Some(text.to_lowercase()),
+            Node::Field(Field::Name) => Some(activity.name.to_lowercase()),
+            _ => None,
+        }
+    }
+
+    fn holds(&self, activity: &Activity) -> bool {
+        match self {
+            Node::Or(a, b) => a.holds(activity) || b.holds(activity),
+            Node::And(a, b) => a.holds(activity) && b.holds(activity),
+            Node::Not(a) => !a.holds(activity),
+            Node::Compare(a, comparison, b) => match (a.number(activity), b.number(activity)) {
+                (Some(a), Some(b)) => comparison.holds(a, b),
+                _ => match (a.text(activity), b.text(activity)) {
+                    (Some(a), Some(b)) => comparison.holds(a, b),
+                    _ => false,
+                },
+            },
+            Node::Contains(a, b) => match (a.text(activity), b.text(activity)) {
+                (Some(a), Some(b)) => a.contains(&b),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(f64),
+    Text(String),
+    Comparison(Comparison),
+    Operator(Operator),
+    Open,
+    Close,
+}
+
+/// Recursive descent parser over the tokens of an expression, each with its
+/// column for the error messages.
+pub(crate) struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    end: usize,
+}
+
+/// A parsed part of an expression, with its type and the column it starts
+/// at.
+type Typed = (Node, Kind, usize);
+
+impl Parser {
+    pub(crate) fn new(source: &str, offset: usize) -> Result<Self> {
+        Ok(Self {
+            tokens: tokenize(source, offset)?,
+            position: 0,
+            end: offset + source.chars().count() + 1,
+        })
+    }
+
+    /// Whether the next token is `word`, taking it if so; for keywords
+    /// before an expression, such as `when` in the tagging rules.
+    pub(crate) fn keyword(&mut self, word: &str) -> bool {
+        let found = self.peek_word(word);
+        self.position += usize::from(found);
+        found
+    }
+
+    /// The next token if it is a string.
+    pub(crate) fn string(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Text(text)) => {
+                let text = text.clone();
+                self.position += 1;
+                Some(text)
+            }
+            _ => None,
+        }
+    }
+
+    /// Parse the remaining tokens as an expression of type `kind`.
+    pub(crate) fn rest(&mut self, kind: Kind) -> Result<Expr> {
+        let (node, found, column) = self.or()?;
+        if let Some((column, token)) = self.tokens.get(self.position) {
+            bail!("unexpected {} at column {column}", describe(token));
+        }
+        expect(kind, found, column)?;
+        Ok(Expr { node })
+    }
+
+    fn next(&mut self) -> Option<(usize, Token)> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Take the next token, returning its column.
+    fn advance(&mut self) -> usize {
+        self.next().map_or(self.end, |(column, _)| column)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn peek_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == word)
+    }
+
+    fn or(&mut self) -> Result<Typed> {
+        let (mut node, kind, column) = self.and()?;
+        if self.peek_word("or") {
+            expect(Kind::Condition, kind, column)?;
+        }
+        while self.peek_word("or") {
+            self.position += 1;
+            let (b, kind, b_column) = self.and()?;
+            expect(Kind::Condition, kind, b_column)?;
+            node = Node::Or(Box::new(node), Box::new(b));
+        }
+        Ok((node, kind, column))
+    }
+
+    fn and(&mut self) -> Result<Typed> {
+        let (mut node, kind, column) = self.not()?;
+        if self.peek_word("and") {
+            expect(Kind::Condition, kind, column)?;
+        }
+        while self.peek_word("and") {
+            self.position += 1;
+            let (b, kind, b_column) = self.not()?;
+            expect(Kind::Condition, kind, b_column)?;
+            node = Node::And(Box::new(node), Box::new(b));
+        }
+        Ok((node, kind, column))
+    }
+
+    fn not(&mut self) -> Result<Typed> {
+        if !self.peek_word("not") {
+            return self.comparison();
+        }
+        let column = self.advance();
+        let (node, kind, a_column) = self.not()?;
+        expect(Kind::Condition, kind, a_column)?;
+        Ok((Node::Not(Box::new(node)), Kind::Condition, column))
+    }
+
+    fn comparison(&mut self) -> Result<Typed> {
+        let (a, a_kind, column) = self.sum()?;
+        let contains = self.peek_word("contains");
+        let comparison = match self.peek() {
+            Some(Token::Comparison(comparison)) => Some(*comparison),
+            _ => None,
+        };
+        if !contains && comparison.is_none() {
+            return Ok((a, a_kind, column));
+        }
+        let op_column = self.advance();
+        let (b, b_kind, b_column) = self.sum()?;
+
+        let node = match comparison {
+            None => {
+                expect(Kind::Text, a_kind, column)?;
+                expect(Kind::Text, b_kind, b_column)?;
+                Node::Contains(Box::new(a), Box::new(b))
+            }
+            Some(comparison) => {
+                if a_kind != b_kind {
+                    bail!(
+                        "cannot compare {} with {} at column {op_column}",
+                        a_kind.name(),
+                        b_kind.name()
+                    );
+                }
+                if a_kind == Kind::Text && !matches!(comparison, Comparison::Eq | Comparison::Ne) {
+                    bail!("strings compare with `==`, `!=` or `contains` at column {op_column}");
+                }
+                Node::Compare(Box::new(a), comparison, Box::new(b))
+            }
+        };
+        Ok((node, Kind::Condition, column))
+    }
+
+    fn sum(&mut self) -> Result<Typed> {
+        let (mut node, kind, column) = self.product()?;
+        while let Some(Token::Operator(operator @ (Operator::Add | Operator::Subtract))) =
+            self.peek().cloned()
+        {
+            expect(Kind::Number, kind, column)?;
+            self.position += 1;
+            let (b, b_kind, b_column) = self.product()?;
+            expect(Kind::Number, b_kind, b_column)?;
+            node = Node::Arithmetic(Box::new(node), operator, Box::new(b));
+        }
+        Ok((node, kind, column))
+    }
+
+    fn product(&mut self) -> Result<Typed> {
+        let (mut node, kind, column) = self.unary()?;
+        while let Some(Token::Operator(operator @ (Operator::Multiply | Operator::Divide))) =
+            self.peek().cloned()
+        {
+            expect(Kind::Number, kind, column)?;
+            self.position += 1;
+            let (b, b_kind, b_column) = self.unary()?;
+            expect(Kind::Number, b_kind, b_column)?;
+            node = Node::Arithmetic(Box::new(node), operator, Box::new(b));
+        }
+        Ok((node, kind, column))
+    }
+
+    fn unary(&mut self) -> Result<Typed> {
+        if self.peek() != Some(&Token::Operator(Operator::Subtract)) {
+            return self.primary();
+        }
+        let column = self.advance();
+        let (node, kind, a_column) = self.unary()?;
+        expect(Kind::Number, kind, a_column)?;
+        Ok((Node::Negate(Box::new(node)), Kind::Number, column))
+    }
+
+    fn primary(&mut self) -> Result<Typed> {
+        match self.next() {
+            Some((column, Token::Number(number))) => {
+                Ok((Node::Number(number), Kind::Number, column))
+            }
+            Some((column, Token::Text(text))) => Ok((Node::Text(text), Kind::Text, column)),
+            Some((column, Token::Open)) => {
+                let (node, kind, _) = self.or()?;
+                match self.next() {
+                    Some((_, Token::Close)) => Ok((node, kind, column)),
+                    Some((column, token)) => {
+                        bail!(
+                            "expected `)` at column {column}, found {}",
+                            describe(&token)
+                        )
+                    }
+                    None => bail!("missing `)` at column {}", self.end),
+                }
+            }
+            Some((column, Token::Word(word))) => {
+                let Some(&(_, field)) = Field::ALL.iter().find(|(name, _)| *name == word) else {
+                    bail!(
+                        "unknown field `{word}` at column {column}, expected one of {}",
+                        field_names().join(", ")
+                    );
+                };
+                Ok((Node::Field(field), field.kind(), column))
+            }
+            Some((column, token)) => {
+                bail!(
+                    "expected a value at column {column}, found {}",
+                    describe(&token)
+                )
+            }
+            None => bail!("expected a value at column {}", self.end),
+        }
+    }
+}
+
+/// Names of the metrics, in the order of the error messages.
+fn field_names() -> Vec<&'static str> {
+    Field::ALL.iter().map(|(name, _)| *name).collect()
+}
+
+fn expect(expected: Kind, found: Kind, column: usize) -> Result<()> {
+    if expected != found {
+        bail!(
+            "expected {} at column {column}, found {}",
+            expected.name(),
+            found.name()
+        );
+    }
+    Ok(())
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("`{word}`"),
+        Token::Number(number) => format!("number {number}"),
+        Token::Text(text) => format!("string {text:?}"),
+        Token::Comparison(_) => "comparison".to_string(),
+        Token::Operator(_) => "operator".to_string(),
+        Token::Open => "`(`".to_string(),
+        Token::Close => "`)`".to_string(),
+    }
+}
+
+/// The tokens of `source` with their columns, counted from `offset`.
+fn tokenize(source: &str, offset: usize) -> Result<Vec<(usize, Token)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let column = offset + i + 1;
+        let c = chars[i];
+        let token = match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '+' => Token::Operator(Operator::Add),
+            '-' => Token::Operator(Operator::Subtract),
+            '*' => Token::Operator(Operator::Multiply),
+            '/' => Token::Operator(Operator::Divide),
+            '"' => {
+                let Some(len) = chars[i + 1..].iter().position(|&c| c == '"') else {
+                    bail!("unterminated string at column {column}");
+                };
+                let text = chars[i + 1..i + 1 + len].iter().collect();
+                i += len + 1;
+                Token::Text(text)
+            }
+            '<' | '>' | '=' | '!' => {
+                let equals = chars.get(i + 1) == Some(&'=');
+                let comparison = match (c, equals) {
+                    ('<', false) => Comparison::Lt,
+                    ('<', true) => Comparison::Le,
+                    ('>', false) => Comparison::Gt,
+                    ('>', true) => Comparison::Ge,
+                    ('=', true) => Comparison::Eq,
+                    ('!', true) => Comparison::Ne,
+                    _ => bail!("unexpected `{c}` at column {column}"),
+                };
+                i += usize::from(equals);
+                Token::Comparison(comparison)
+            }
+            _ if c.is_ascii_digit() || c == '.' => {
+                let len = chars[i..]
+                    .iter()
+                    .position(|&c| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(chars.len() - i);
+                let text: String = chars[i..i + len].iter().collect();
+                i += len - 1;
+                Token::Number(
+                    text.parse()
+                        .with_context(|| format!("invalid number at column {column}"))?,
+                )
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let len = chars[i..]
+                    .iter()
+                    .position(|&c| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(chars.len() - i);
+                let word = chars[i..i + len].iter().collect();
+                i += len - 1;
+                Token::Word(word)
+            }
+            _ => bail!("unexpected `{c}` at column {column}"),
+        };
+        tokens.push((column, token));
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(name: &str, distance_km: f64, elevation_gain: f64) -> Activity {
+        Activity {
+            name: name.to_string(),
+            distance_km,
+            elevation_gain,
+            ..Default::default()
+        }
+    }
+
+    fn evaluate(source: &str) -> Option<f64> {
+        Expr::number(source)
+            .unwrap()
+            .evaluate(&activity("Col", 40.0, 1000.0))
+    }
+
+    fn error(source: &str) -> String {
+        format!("{:#}", Expr::number(source).unwrap_err())
+    }
+
+    fn condition_error(source: &str) -> String {
+        format!("{:#}", Expr::condition(source).unwrap_err())
+    }
+
+    #[test]
+    fn arithmetic_follows_the_usual_precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), Some(7.0));
+        assert_eq!(evaluate("(1 + 2) * 3"), Some(9.0));
+        assert_eq!(evaluate("10 - 4 - 3"), Some(3.0));
+        assert_eq!(evaluate("12 / 3 / 2"), Some(2.0));
+        assert_eq!(evaluate("-2 * -3"), Some(6.0));
+        assert_eq!(evaluate("- (1 - 4)"), Some(3.0));
+        assert_eq!(evaluate("2.5*2"), Some(5.0));
+        assert_eq!(evaluate("distance_km-1"), Some(39.0));
+        assert_eq!(evaluate("elevation_m / distance_km"), Some(25.0));
+        assert_eq!(evaluate("elevation_per_km * 2"), Some(50.0));
+        assert_eq!(evaluate("((distance_km))"), Some(40.0));
+    }
+
+    #[test]
+    fn missing_values_spread() {
+        assert_eq!(evaluate("elevation_m / 0"), None);
+        assert_eq!(evaluate("1 / (distance_km - 40)"), None);
+        assert_eq!(evaluate("0 / 5"), Some(0.0));
+        // No timestamps, so no average speed.
+        assert_eq!(evaluate("avg_speed * 2 + 1"), None);
+        assert_eq!(evaluate("-moving_h"), None);
+
+        let division = Expr::condition("elevation_m / 0 >= 0 or not (1 / 0 < 0)").unwrap();
+        assert!(division.holds(&activity("Col", 40.0, 1000.0)));
+    }
+
+    #[test]
+    fn conditions_combine_numbers_and_names() {
+        let holds = |source: &str| {
+            Expr::condition(source)
+                .unwrap()
+                .holds(&activity("Col du Test", 40.0, 1000.0))
+        };
+
+        assert!(holds("elevation_m / distance_km > 20"));
+        assert!(holds("distance_km * 2 == 80"));
+        assert!(holds("1 < 2 and not 2 < 1"));
+        assert!(holds("name contains \"DU\""));
+        assert!(holds("name == \"col du test\""));
+        assert!(holds("name != \"Col\""));
+        assert!(holds("\"test\" == \"TEST\""));
+        assert!(holds(
+            "distance_km > 100 or distance_km > 30 and elevation_m > 500"
+        ));
+        assert!(!holds(
+            "(distance_km > 100 or distance_km > 30) and elevation_m > 5000"
+        ));
+        assert!(!holds("not not name contains \"gravel\""));
+        assert!(!holds("avg_speed > 0 or avg_speed <= 0"));
+    }
+
+    #[test]
+    fn types_are_checked_when_parsing() {
+        assert_eq!(
+            error("distance_km > 10"),
+            "expected a number at column 1, found a condition"
+        );
+        assert_eq!(
+            error("name * 2"),
+            "expected a number at column 1, found a string"
+        );
+        assert_eq!(
+            error("2 + \"km\""),
+            "expected a number at column 5, found a string"
+        );
+        assert_eq!(
+            error("-name"),
+            "expected a number at column 2, found a string"
+        );
+        assert_eq!(
+            condition_error("distance_km"),
+            "expected a condition at column 1, found a number"
+        );
+        assert_eq!(
+            condition_error("distance_km > 1 and name"),
+            "expected a condition at column 21, found a string"
+        );
+        assert_eq!(
+            condition_error("not distance_km"),
+            "expected a condition at column 5, found a number"
+        );
+        assert_eq!(
+            condition_error("distance_km == \"far\""),
+            "cannot compare a number with a string at column 13"
+        );
+        assert_eq!(
+            condition_error("name < \"b\""),
+            "strings compare with `==`, `!=` or `contains` at column 6"
+        );
+        assert_eq!(
+            condition_error("distance_km contains \"1\""),
+            "expected a string at column 1, found a number"
+        );
+    }
+
+    #[test]
+    fn syntax_errors_give_the_column() {
+        assert_eq!(
+            error("speed / 2"),
+            "unknown field `speed` at column 1, expected one of name, distance_km, \
+             elevation_m, elevation_per_km, avg_speed, moving_h"
+        );
+        assert_eq!(error("1 +"), "expected a value at column 4");
+        assert_eq!(error(""), "expected a value at column 1");
+        assert_eq!(error("(1 + 2"), "missing `)` at column 7");
+        assert_eq!(
+            error("(1 + 2 3"),
+            "expected `)` at column 8, found number 3"
+        );
+        assert_eq!(error("1 2"), "unexpected number 2 at column 3");
+        assert_eq!(error("1 + )"), "expected a value at column 5, found `)`");
+        assert_eq!(error("2 % 3"), "unexpected `%` at column 3");
+        assert_eq!(error("1 = 2"), "unexpected `=` at column 3");
+        assert_eq!(
+            error("1.2.3"),
+            "invalid number at column 1: invalid float literal"
+        );
+        assert_eq!(error("\"km"), "unterminated string at column 1");
+        assert_eq!(
+            condition_error("distance_km < 1 name"),
+            "unexpected `name` at column 17"
+        );
+        // Columns count from the start of the longer text.
+        assert_eq!(
+            format!("{:#}", Expr::parse("1 +", 10, Kind::Number).unwrap_err()),
+            "expected a value at column 14"
+        );
+        assert_eq!(
+            format!("{:#}", Expr::parse("é + x", 3, Kind::Number).unwrap_err()),
+            "unknown field `é` at column 4, expected one of name, distance_km, \
+             elevation_m, elevation_per_km, avg_speed, moving_h"
+        );
+    }
+}
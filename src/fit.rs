@@ -0,0 +1,345 @@
+use anyhow::{Context, Result, ensure};
+use chrono::DateTime;
+
+use crate::activity::TrackPoint;
+use crate::gpx::write_gpx;
+
+/// Bytes 8 to 11 of the header of every FIT file.
+const SIGNATURE: &[u8; 4] = b".FIT";
+
+/// Seconds from the Unix epoch to that of FIT timestamps,
+/// 1989-12-31T00:00:00Z.
+pub(crate) const FIT_EPOCH_S: i64 = 631_065_600;
+
+/// Global number of the message naming the device.
+pub(crate) const FILE_ID: u16 = 0;
+/// Global number of the message of a track point.
+pub(crate) const RECORD: u16 = 20;
+
+/// Degrees in a semicircle, the FIT unit of latitudes and longitudes.
+pub(crate) const DEGREES_PER_SEMICIRCLE: f64 = 180.0 / 2_147_483_648.0;
+
+/// Fields of the record message, by number.
+const TIMESTAMP: u8 = 253;
+const LATITUDE: u8 = 0;
+const LONGITUDE: u8 = 1;
+const ALTITUDE: u8 = 2;
+const HEART_RATE: u8 = 3;
+const CADENCE: u8 = 4;
+const DISTANCE: u8 = 5;
+const POWER: u8 = 7;
+const ENHANCED_ALTITUDE: u8 = 78;
+/// Field of the file id message.
+const MANUFACTURER: u8 = 1;
+
+/// Whether `bytes` are a FIT file rather than a GPX one, from the signature
+/// in its header.
+pub fn is_fit(bytes: &[u8]) -> bool {
+    bytes.get(8..12) == Some(SIGNATURE)
+}
+
+/// The track points of a FIT activity file, with the device that recorded
+/// it. Records without a position, e.g. on a home trainer, are left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FitActivity {
+    pub creator: Option<String>,
+    pub points: Vec<TrackPoint>,
+}
+
+/// Field of a message definition, as (number, size in bytes).
+type FieldDefinition = (u8, usize);
+
+/// Layout of the data messages of a local message type.
+#[derive(Debug, Clone)]
+struct Definition {
+    global: u16,
+    big_endian: bool,
+    fields: Vec<FieldDefinition>,
+    /// Size of the developer fields, skipped.
+    developer_size: usize,
+}
+
+/// Reads the data part of a FIT file, failing when it is cut short.
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8]> {
+        let taken = self
+            .bytes
+            .get(self.position..self.position + size)
+            .context("the FIT file is cut short")?;
+        self.position += size;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+}
+
+/// Read the records of a FIT activity file. Only the messages the metrics
+/// need are decoded; the others are skipped by their definition.
+pub fn read_fit(bytes: &[u8]) -> Result<FitActivity> {
+    ensure!(is_fit(bytes), "not a FIT file");
+    let header_size = usize::from(bytes[0]);
+    let data_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+    let end = header_size + data_size;
+    let stored_crc = bytes
+        .get(end..end + 2)
+        .context("the FIT file is cut short")?;
+    ensure!(
+        u16::from_le_bytes([stored_crc[0], stored_crc[1]]) == fit_crc(&bytes[..end]),
+        "the FIT file is damaged: its checksum does not match"
+    );
+
+    let mut cursor = Cursor {
+        bytes: &bytes[..end],
+        position: header_size,
+    };
+    let mut definitions: [Option<Definition>; 16] = Default::default();
+    let mut activity = FitActivity::default();
+    // Compressed timestamps are offsets from the last full one.
+    let mut last_timestamp: Option<u32> = None;
+    while cursor.position < end {
+        let header = cursor.byte()?;
+        let (local, compressed_offset) = if header & 0x80 != 0 {
+            (usize::from((header >> 5) & 0x03), Some(header & 0x1f))
+        } else if header & 0x40 != 0 {
+            let developer = header & 0x20 != 0;
+            definitions[usize::from(header & 0x0f)] =
+                Some(read_definition(&mut cursor, developer)?);
+            continue;
+        } else {
+            (usize::from(header & 0x0f), None)
+        };
+
+        let definition = definitions[local]
+            .as_ref()
+            .with_context(|| format!("the FIT file uses local message {local} undefined"))?;
+        let mut point = Record::default();
+        for &(number, size) in &definition.fields {
+            let value = unsigned(cursor.take(size)?, definition.big_endian);
+            match (definition.global, number) {
+                (FILE_ID, MANUFACTURER) => {
+                    activity.creator = value.and_then(manufacturer).map(str::to_string)
+                }
+                (RECORD, field) => point.set(field, size, value),
+                _ => {}
+            }
+        }
+        cursor.take(definition.developer_size)?;
+        if definition.global != RECORD {
+            continue;
+        }
+
+        let timestamp = match (point.timestamp, compressed_offset, last_timestamp) {
+            (Some(timestamp), _, _) => Some(timestamp),
+            (None, Some(offset), Some(last)) => {
+                let offset = u32::from(offset);
+                Some(last + (offset.wrapping_sub(last & 0x1f) & 0x1f))
+            }
+            _ => None,
+        };
+        last_timestamp = timestamp.or(last_timestamp);
+        if let (Some(lat), Some(lon)) = (point.lat, point.lon) {
+            activity.points.push(TrackPoint {
+                lat,
+                lon,
+                elevation: point.elevation,
+                time: timestamp
+                    .and_then(|timestamp| {
+                        DateTime::from_timestamp(FIT_EPOCH_S + i64::from(timestamp), 0)
+                    })
+                    .map(|time| time.fixed_offset()),
+                heart_rate: point.heart_rate,
+                power: point.power,
+                cadence: point.cadence,
+                distance_m: point.distance_m,
+                segment_start: false,
+            });
+        }
+    }
+
+    Ok(activity)
+}
+
+/// A FIT activity file as the GPX document [`crate::Activity::from_bytes`]
+/// reads.
+pub fn fit_to_gpx(bytes: &[u8]) -> Result<String> {
+    let activity = read_fit(bytes)?;
+    Ok(write_gpx(
+        activity.creator.as_deref().unwrap_or_default(),
+        None,
+        &activity.points,
+    ))
+}
+
+fn read_definition(cursor: &mut Cursor, developer: bool) -> Result<Definition> {
+    let header = cursor.take(4)?;
+    // A reserved byte, then the architecture.
+    let big_endian = header[1] == 1;
+    let global = [header[2], header[3]];
+    let global = if big_endian {
+        u16::from_be_bytes(global)
+    } else {
+        u16::from_le_bytes(global)
+    };
+    let count = cursor.byte()?;
+    let fields = (0..count)
+        .map(|_| {
+            let field = cursor.take(3)?;
+            Ok((field[0], usize::from(field[1])))
+        })
+        .collect::<Result<_>>()?;
+    let mut developer_size = 0;
+    if developer {
+        for _ in 0..cursor.byte()? {
+            developer_size += usize::from(cursor.take(3)?[1]);
+        }
+    }
+
+    Ok(Definition {
+        global,
+        big_endian,
+        fields,
+        developer_size,
+    })
+}
+
+/// An integer field, `None` when it holds the invalid value of its size
+/// (all bits set).
+fn unsigned(bytes: &[u8], big_endian: bool) -> Option<u64> {
+    if bytes.is_empty() || bytes.len() > 8 {
+        return None;
+    }
+    let mut value = 0;
+    for i in 0..bytes.len() {
+        let byte = if big_endian {
+            bytes[i]
+        } else {
+            bytes[bytes.len() - 1 - i]
+        };
+        value = value << 8 | u64::from(byte);
+    }
+    let invalid = u64::MAX >> (64 - 8 * bytes.len());
+    (value != invalid).then_some(value)
+}
+
+/// Fields of a record message, in the units of [`TrackPoint`].
+#[derive(Debug, Default)]
+struct Record {
+    timestamp: Option<u32>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    elevation: Option<f64>,
+    heart_rate: Option<f64>,
+    cadence: Option<f64>,
+    distance_m: Option<f64>,
+    power: Option<f64>,
+}
+
+impl Record {
+    fn set(&mut self, field: u8, size: usize, value: Option<u64>) {
+        let Some(value) = value else {
+            return;
+        };
+        match (field, size) {
+            (TIMESTAMP, 4) => self.timestamp = Some(value as u32),
+            (LATITUDE, 4) => self.lat = semicircles(value),
+            (LONGITUDE, 4) => self.lon = semicircles(value),
+            // Both in 1/5 m above -500 m; the enhanced one is wider.
+            (ALTITUDE, 2) if self.elevation.is_none() => {
+                self.elevation = Some(value as f64 / 5.0 - 500.0)
+            }
+            (ENHANCED_ALTITUDE, 4) => self.elevation = Some(value as f64 / 5.0 - 500.0),
+            (HEART_RATE, 1) => self.heart_rate = Some(value as f64),
+            (CADENCE, 1) => self.cadence = Some(value as f64),
+            (DISTANCE, 4) => self.distance_m = Some(value as f64 / 100.0),
+            (POWER, 2) => self.power = Some(value as f64),
+            _ => {}
+        }
+    }
+}
+
+/// A signed 32-bit position in degrees; its invalid value is `i32::MAX`.
+fn semicircles(value: u64) -> Option<f64> {
+    let semicircles = value as u32 as i32;
+    (semicircles != i32::MAX).then(|| f64::from(semicircles) * DEGREES_PER_SEMICIRCLE)
+}
+
+/// The makers of the devices most rides come from, by FIT manufacturer id.
+fn manufacturer(id: u64) -> Option<&'static str> {
+    match id {
+        1 => Some("Garmin"),
+        32 => Some("Wahoo Fitness"),
+        260 => Some("Zwift"),
+        289 => Some("Hammerhead"),
+        _ => None,
+    }
+}
+
+/// CRC-16 of FIT files, over the header and the data.
+pub(crate) fn fit_crc(bytes: &[u8]) -> u16 {
+    const TABLE: [u16; 16] = [
+        0x0000, 0xcc01, 0xd801, 0x1400, 0xf001, 0x3c00, 0x2800, 0xe401, 0xa001, 0x6c00, 0x7800,
+        0xb401, 0x5000, 0x9c01, 0x8801, 0x4400,
+    ];
+    bytes.iter().fold(0, |mut crc, &byte| {
+        for nibble in [byte & 0x0f, byte >> 4] {
+            let low = TABLE[usize::from(crc & 0x0f)];
+            crc = (crc >> 4) & 0x0fff;
+            crc = crc ^ low ^ TABLE[usize::from(nibble)];
+        }
+        crc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::SyntheticRide;
+
+    #[test]
+    fn records_read_back_as_written() {
+        let ride = SyntheticRide::new()
+            .heart_rate(150.0)
+            .power(230.0)
+            .flat(0.5)
+            .climb(0.5, 5.0);
+        let bytes = ride.to_fit();
+        assert!(is_fit(&bytes));
+        assert!(!is_fit(ride.to_gpx().as_bytes()));
+
+        let activity = read_fit(&bytes).unwrap();
+        assert_eq!(activity.creator.as_deref(), Some("Garmin"));
+        let points = ride.points();
+        assert_eq!(activity.points.len(), points.len());
+        for (read, written) in activity.points.iter().zip(&points) {
+            assert!((read.lat - written.lat).abs() < 1e-6);
+            assert!((read.lon - written.lon).abs() < 1e-6);
+            assert!((read.elevation.unwrap() - written.elevation.unwrap()).abs() < 0.11);
+            assert_eq!(
+                read.time.unwrap().timestamp(),
+                written.time.unwrap().timestamp()
+            );
+            assert_eq!(read.heart_rate, Some(150.0));
+            assert_eq!(read.power, Some(230.0));
+        }
+    }
+
+    #[test]
+    fn damaged_files_are_refused() {
+        let mut bytes = SyntheticRide::new().flat(0.2).to_fit();
+        let last = bytes.len() - 3;
+        bytes[last] ^= 0xff;
+        let err = read_fit(&bytes).unwrap_err();
+        assert!(err.to_string().contains("checksum"), "{err}");
+
+        bytes.truncate(40);
+        let err = read_fit(&bytes).unwrap_err();
+        assert!(err.to_string().contains("cut short"), "{err}");
+    }
+}
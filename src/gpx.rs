@@ -1,9 +1,15 @@
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, Datelike, FixedOffset, SecondsFormat, TimeDelta, Utc};
 use geo::prelude::Distance;
 use geo::{Haversine, point};
 use gpx::{Gpx, Time};
+use serde::Deserialize;
+use std::fmt::{self, Write as _};
+use std::path::Path;
 use time::OffsetDateTime;
 
+use crate::activity::TrackPoint;
+use crate::peaks::{Peak, PeakFinder};
+
 /// Sum the length of all track segments in a GPX.
 pub fn gpx_total_distance(gpx: &Gpx) -> f64 {
     gpx.tracks
@@ -23,7 +29,156 @@ pub fn gpx_total_distance(gpx: &Gpx) -> f64 {
 
 /// Returns the name of the first track in a GPX file, if present.
 pub fn gpx_track_name(gpx: &Gpx) -> Option<&str> {
-    gpx.tracks.get(0)?.name.as_deref()
+    gpx.tracks.first()?.name.as_deref()
+}
+
+/// Where the name of an activity comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NameSource {
+    Track,
+    Metadata,
+    Route,
+    FileName,
+    /// Last segment of the URL the file was downloaded from.
+    Url,
+    /// Nothing usable was found.
+    #[default]
+    Default,
+}
+
+impl fmt::Display for NameSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NameSource::Track => "track name",
+            NameSource::Metadata => "GPX metadata",
+            NameSource::Route => "route name",
+            NameSource::FileName => "file name",
+            NameSource::Url => "URL",
+            NameSource::Default => "default",
+        })
+    }
+}
+
+/// Name given to activities when nothing better is found.
+pub const DEFAULT_NAME: &str = "Activity";
+
+/// Returns the first non-blank name among the first track, the metadata and
+/// the first route.
+pub fn gpx_name(gpx: &Gpx) -> Option<(&str, NameSource)> {
+    let metadata = gpx
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.name.as_deref());
+    let route = gpx.routes.first().and_then(|route| route.name.as_deref());
+    [
+        (gpx_track_name(gpx), NameSource::Track),
+        (metadata, NameSource::Metadata),
+        (route, NameSource::Route),
+    ]
+    .into_iter()
+    .find_map(|(name, source)| Some((name?.trim(), source)).filter(|(name, _)| !name.is_empty()))
+}
+
+/// Name of an activity: the [`gpx_name`], then the cleaned file stem of
+/// `path`, then [`DEFAULT_NAME`].
+pub fn activity_name(gpx: &Gpx, path: &Path) -> (String, NameSource) {
+    if let Some((name, source)) = gpx_name(gpx) {
+        return (name.to_string(), source);
+    }
+    file_name_or_default(path)
+}
+
+/// The cleaned file stem of `path`, or [`DEFAULT_NAME`].
+pub fn file_name_or_default(path: &Path) -> (String, NameSource) {
+    path.file_stem()
+        .and_then(|stem| name_from_file_stem(&stem.to_string_lossy()))
+        .map(|name| (name, NameSource::FileName))
+        .unwrap_or_else(|| (DEFAULT_NAME.to_string(), NameSource::Default))
+}
+
+/// A readable name from a file stem, or `None` when only ids and dates are
+/// left.
+///
+/// Underscores become spaces; timestamps (`2024-06-02T07-12-44Z`,
+/// `2024-06-02-071244`), numbers, copy markers like `(1)`, device counters
+/// like the `-123-0` of Wahoo files and the `activity` prefix of Garmin
+/// Connect exports are removed.
+pub fn name_from_file_stem(stem: &str) -> Option<String> {
+    let stem = stem.replace('_', " ");
+    let bytes = stem.as_bytes();
+    let mut cleaned = String::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(len) = timestamp_len(&bytes[i..]) {
+            cleaned.push(' ');
+            i += len;
+        } else {
+            let c = stem[i..].chars().next()?;
+            cleaned.push(c);
+            i += c.len_utf8();
+        }
+    }
+
+    let words: Vec<&str> = cleaned
+        .split_whitespace()
+        .map(|word| {
+            let mut word = word.trim_matches(['-', '.']);
+            // Trailing counters, as in `4A2F-123-0`.
+            while let Some((head, tail)) = word.rsplit_once('-') {
+                if tail.is_empty() || !tail.bytes().all(|b| b.is_ascii_digit()) {
+                    break;
+                }
+                word = head;
+            }
+            word
+        })
+        .filter(|word| {
+            let word = word.trim_start_matches('(').trim_end_matches(')');
+            !word.bytes().all(|b| b.is_ascii_digit() || b == b'-')
+        })
+        .collect();
+
+    match words.as_slice() {
+        [] => None,
+        [word] if word.eq_ignore_ascii_case("activity") => None,
+        _ => Some(words.join(" ")),
+    }
+}
+
+/// Length of the date and time at the start of `bytes`: a `YYYY-MM-DD` date,
+/// optionally followed by a `T`, `-` or space and a time, and a `Z`.
+fn timestamp_len(bytes: &[u8]) -> Option<usize> {
+    let digits = |range: std::ops::Range<usize>| {
+        bytes
+            .get(range)
+            .is_some_and(|digits| digits.iter().all(u8::is_ascii_digit))
+    };
+    let at = |i: usize, options: &[u8]| bytes.get(i).is_some_and(|b| options.contains(b));
+    if !(digits(0..4) && at(4, b"-") && digits(5..7) && at(7, b"-") && digits(8..10)) {
+        return None;
+    }
+
+    let mut len = 10;
+    if at(len, b"T- ") && digits(len + 1..len + 3) {
+        // Hours, then minutes and maybe seconds, with or without separators.
+        let mut time = len + 3;
+        let mut groups = 0;
+        while groups < 2 {
+            let separator = usize::from(at(time, b"-:"));
+            if !digits(time + separator..time + separator + 2) {
+                break;
+            }
+            time += separator + 2;
+            groups += 1;
+        }
+        if groups > 0 {
+            len = time;
+        }
+    }
+    if at(len, b"Z") {
+        len += 1;
+    }
+    Some(len)
 }
 
 /// Returns the total elevation gain from a GPX file.
@@ -46,15 +201,50 @@ pub fn gpx_elevation_gain(gpx: &Gpx) -> f64 {
         .sum()
 }
 
-/// Returns the start and end date of the GPX file, if available.
-pub fn gpx_start_end_date(gpx: &Gpx) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
-    let times: Vec<DateTime<FixedOffset>> = gpx
-        .tracks
+/// Timestamps before this year are clock resets, such as the 1970-01-01 of
+/// a device that has no GPS time yet.
+pub const MIN_PLAUSIBLE_YEAR: i32 = 2000;
+
+/// Whether a ride can have been recorded at `time`: from
+/// [`MIN_PLAUSIBLE_YEAR`] to a day from now. Other timestamps are ignored,
+/// their points still counting for the distance.
+pub fn is_plausible_time(time: &DateTime<FixedOffset>) -> bool {
+    time.year() >= MIN_PLAUSIBLE_YEAR && *time <= Utc::now() + TimeDelta::days(1)
+}
+
+fn gpx_times(gpx: &Gpx) -> impl Iterator<Item = DateTime<FixedOffset>> + '_ {
+    gpx.tracks
         .iter()
         .flat_map(|track| track.segments.iter())
         .flat_map(|segment| segment.points.iter())
         .filter_map(|point| point.time.map(gpx_to_chrono))
-        .collect();
+}
+
+/// Number of timestamps that are not [plausible](is_plausible_time).
+pub fn gpx_implausible_timestamps(gpx: &Gpx) -> usize {
+    gpx_times(gpx)
+        .filter(|time| !is_plausible_time(time))
+        .count()
+}
+
+/// Remove the points identical to the previous one of their segment, as
+/// merging tools sometimes write every point twice, and count them.
+pub fn remove_duplicate_points(gpx: &mut Gpx) -> usize {
+    gpx.tracks
+        .iter_mut()
+        .flat_map(|track| track.segments.iter_mut())
+        .map(|segment| {
+            let before = segment.points.len();
+            segment.points.dedup();
+            before - segment.points.len()
+        })
+        .sum()
+}
+
+/// Returns the start and end date of the GPX file, if available, from the
+/// [plausible](is_plausible_time) timestamps.
+pub fn gpx_start_end_date(gpx: &Gpx) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+    let times: Vec<DateTime<FixedOffset>> = gpx_times(gpx).filter(is_plausible_time).collect();
 
     if times.is_empty() {
         None
@@ -66,6 +256,39 @@ pub fn gpx_start_end_date(gpx: &Gpx) -> Option<(DateTime<FixedOffset>, DateTime<
     }
 }
 
+/// Returns the (latitude, longitude) of the first track point, if any.
+pub fn gpx_start_point(gpx: &Gpx) -> Option<(f64, f64)> {
+    let point = gpx
+        .tracks
+        .iter()
+        .flat_map(|track| track.segments.iter())
+        .flat_map(|segment| segment.points.iter())
+        .next()?
+        .point();
+
+    Some((point.y(), point.x()))
+}
+
+/// Points of the routes, each route a segment; they have no timestamps.
+pub fn gpx_route_points(gpx: &Gpx) -> Vec<TrackPoint> {
+    gpx.routes
+        .iter()
+        .flat_map(|route| {
+            route
+                .points
+                .iter()
+                .enumerate()
+                .map(|(i, waypoint)| TrackPoint {
+                    lat: waypoint.point().y(),
+                    lon: waypoint.point().x(),
+                    elevation: waypoint.elevation,
+                    segment_start: i == 0,
+                    ..Default::default()
+                })
+        })
+        .collect()
+}
+
 pub fn gpx_elevation_profile(gpx: &Gpx) -> Vec<(f64, f64)> {
     let mut total_distance = 0.0;
 
@@ -91,8 +314,28 @@ pub fn gpx_elevation_profile(gpx: &Gpx) -> Vec<(f64, f64)> {
         .collect()
 }
 
+/// Highest point of the tracks, as [`crate::stream_activity`] finds it with
+/// a [`PeakFinder`]: unlike in [`gpx_elevation_profile`], the first point
+/// has a place and the points without an elevation count in the distance;
+/// the gaps between segments do not.
+pub fn gpx_peak(gpx: &Gpx) -> Option<Peak> {
+    let mut finder = PeakFinder::default();
+    let mut total_distance = 0.0;
+    for segment in gpx.tracks.iter().flat_map(|track| &track.segments) {
+        for (i, point) in segment.points.iter().enumerate() {
+            if i > 0 {
+                total_distance += Haversine.distance(segment.points[i - 1].point(), point.point());
+            }
+            if let Some(elevation) = point.elevation {
+                finder.push(total_distance, elevation);
+            }
+        }
+    }
+    finder.finish()
+}
+
 /// Returns the lowest and highest elevation from the elevation profile.
-pub fn elevation_profile_min_max(elevation_profile: &Vec<(f64, f64)>) -> Option<(f64, f64)> {
+pub fn elevation_profile_min_max(elevation_profile: &[(f64, f64)]) -> Option<(f64, f64)> {
     let elevations: Vec<f64> = elevation_profile.iter().map(|&(_, ele)| ele).collect();
     if elevations.is_empty() {
         None
@@ -103,6 +346,115 @@ pub fn elevation_profile_min_max(elevation_profile: &Vec<(f64, f64)>) -> Option<
     }
 }
 
+/// Length of the chunks over which the elevation noise is measured, in meters.
+const ELEVATION_NOISE_CHUNK_M: f64 = 500.0;
+
+/// Chunks steeper than this are not considered flat and are ignored.
+const ELEVATION_NOISE_MAX_GRADE: f64 = 0.03;
+
+/// Above this RMS (in meters) the elevation is considered GPS-derived.
+pub const ELEVATION_NOISE_THRESHOLD: f64 = 3.0;
+
+/// Returns the high-frequency elevation noise on low-gradient sections, as the
+/// RMS of the residuals of a linear fit over each flat chunk of the track.
+///
+/// Barometric altimeters stay well under a meter, GPS altitude oscillates by
+/// several meters. Returns `None` if the track has no flat section.
+pub fn gpx_elevation_noise(gpx: &Gpx) -> Option<f64> {
+    let mut residuals = vec![];
+
+    for segment in gpx.tracks.iter().flat_map(|track| track.segments.iter()) {
+        let mut distance = 0.0;
+        let mut chunk: Vec<(f64, f64)> = vec![];
+        let mut previous: Option<&gpx::Waypoint> = None;
+
+        for point in &segment.points {
+            if let Some(prev) = previous {
+                distance += Haversine.distance(prev.point(), point.point());
+            }
+            previous = Some(point);
+
+            let Some(elevation) = point.elevation else {
+                continue;
+            };
+            chunk.push((distance, elevation));
+
+            if distance - chunk[0].0 >= ELEVATION_NOISE_CHUNK_M {
+                flat_chunk_residuals(&chunk, &mut residuals);
+                chunk.clear();
+            }
+        }
+    }
+
+    if residuals.is_empty() {
+        None
+    } else {
+        let mean_square = residuals.iter().map(|r| r * r).sum::<f64>() / residuals.len() as f64;
+        Some(mean_square.sqrt())
+    }
+}
+
+/// Least-squares fit of elevation against distance; residuals are only kept
+/// when the fitted grade is low enough for the chunk to be considered flat.
+fn flat_chunk_residuals(chunk: &[(f64, f64)], residuals: &mut Vec<f64>) {
+    let n = chunk.len() as f64;
+    if n < 3.0 {
+        return;
+    }
+    let mean_d = chunk.iter().map(|&(d, _)| d).sum::<f64>() / n;
+    let mean_e = chunk.iter().map(|&(_, e)| e).sum::<f64>() / n;
+    let covariance: f64 = chunk
+        .iter()
+        .map(|&(d, e)| (d - mean_d) * (e - mean_e))
+        .sum();
+    let variance: f64 = chunk.iter().map(|&(d, _)| (d - mean_d).powi(2)).sum();
+    if variance == 0.0 {
+        return;
+    }
+
+    let grade = covariance / variance;
+    if grade.abs() > ELEVATION_NOISE_MAX_GRADE {
+        return;
+    }
+    residuals.extend(
+        chunk
+            .iter()
+            .map(|&(d, e)| e - (mean_e + grade * (d - mean_d))),
+    );
+}
+
+/// Grades beyond this are clamped before weighting, in percent.
+const MAX_WEIGHTED_GRADE: f64 = 25.0;
+
+/// Descents never count for less than this share of the flat.
+const MIN_GRADE_FACTOR: f64 = 0.8;
+
+/// How [`grade_factor`] weights a grade.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GradeModel {
+    /// +10% per percent of climb; -5% per percent of descent.
+    #[default]
+    Piecewise,
+    /// `1 + 0.08 g + 0.002 g²`, steeper climbs weighing more than linearly.
+    Polynomial,
+}
+
+/// Effort of riding one kilometer at `grade_percent` in flat kilometers.
+///
+/// Climbs count more, gentle descents slightly less, and steep descents, where
+/// braking takes over, no less than [`MIN_GRADE_FACTOR`]. Grades are clamped
+/// to ±[`MAX_WEIGHTED_GRADE`]. Both models give 1 on the flat and 2 at 10%.
+pub fn grade_factor(grade_percent: f64, model: GradeModel) -> f64 {
+    let g = grade_percent.clamp(-MAX_WEIGHTED_GRADE, MAX_WEIGHTED_GRADE);
+    let factor = match model {
+        GradeModel::Piecewise if g >= 0.0 => 1.0 + 0.1 * g,
+        GradeModel::Piecewise => 1.0 + 0.05 * g,
+        GradeModel::Polynomial => 1.0 + 0.08 * g + 0.002 * g * g,
+    };
+    factor.max(MIN_GRADE_FACTOR)
+}
+
 fn gpx_to_chrono(gpx_time: Time) -> DateTime<FixedOffset> {
     let offset_date_time: OffsetDateTime = gpx_time.into();
     let datetime_from_timestamp = DateTime::from_timestamp(
@@ -112,5 +464,141 @@ fn gpx_to_chrono(gpx_time: Time) -> DateTime<FixedOffset> {
     .unwrap();
     let offset = FixedOffset::east_opt(offset_date_time.offset().whole_seconds()).unwrap();
 
-    datetime_from_timestamp.with_timezone(&offset.into())
+    datetime_from_timestamp.with_timezone(&offset)
+}
+
+/// Write track points as a GPX 1.1 document, a track segment starting at
+/// each [`TrackPoint::segment_start`], with the sensors and the distance in
+/// the extensions read by [`crate::read_track_points`].
+pub fn write_gpx(creator: &str, name: Option<&str>, points: &[TrackPoint]) -> String {
+    let mut gpx = String::new();
+    gpx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        gpx,
+        "<gpx version=\"1.1\" creator=\"{}\" \
+         xmlns=\"http://www.topografix.com/GPX/1/1\" \
+         xmlns:gpxtpx=\"http://www.garmin.com/xmlschemas/TrackPointExtension/v1\">",
+        escape_xml(creator)
+    );
+    gpx.push_str("  <trk>\n");
+    if let Some(name) = name {
+        let _ = writeln!(gpx, "    <name>{}</name>", escape_xml(name));
+    }
+    gpx.push_str("    <trkseg>\n");
+    for (i, point) in points.iter().enumerate() {
+        if i > 0 && point.segment_start {
+            gpx.push_str("    </trkseg>\n    <trkseg>\n");
+        }
+        let _ = write!(
+            gpx,
+            "      <trkpt lat=\"{:.7}\" lon=\"{:.7}\">",
+            point.lat, point.lon
+        );
+        if let Some(elevation) = point.elevation {
+            let _ = write!(gpx, "<ele>{elevation:.2}</ele>");
+        }
+        if let Some(time) = point.time {
+            let _ = write!(
+                gpx,
+                "<time>{}</time>",
+                time.with_timezone(&Utc)
+                    .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+            );
+        }
+        let garmin = point.heart_rate.is_some() || point.cadence.is_some();
+        if garmin || point.power.is_some() || point.distance_m.is_some() {
+            gpx.push_str("<extensions>");
+            if let Some(power) = point.power {
+                let _ = write!(gpx, "<power>{power}</power>");
+            }
+            if let Some(distance) = point.distance_m {
+                let _ = write!(gpx, "<distance>{distance}</distance>");
+            }
+            if garmin {
+                gpx.push_str("<gpxtpx:TrackPointExtension>");
+                if let Some(heart_rate) = point.heart_rate {
+                    let _ = write!(gpx, "<gpxtpx:hr>{heart_rate}</gpxtpx:hr>");
+                }
+                if let Some(cadence) = point.cadence {
+                    let _ = write!(gpx, "<gpxtpx:cad>{cadence}</gpxtpx:cad>");
+                }
+                gpx.push_str("</gpxtpx:TrackPointExtension>");
+            }
+            gpx.push_str("</extensions>");
+        }
+        gpx.push_str("</trkpt>\n");
+    }
+    gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+    gpx
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Gpx {
+        let path = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+        gpx::read(std::fs::File::open(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn file_names_of_garmin_wahoo_and_strava() {
+        let name = |stem| name_from_file_stem(stem);
+
+        // Garmin Connect exports and raw Edge files.
+        assert_eq!(name("activity_15273839213"), None);
+        assert_eq!(name("2024-06-02-07-12-44"), None);
+        assert_eq!(
+            name("Lyon_Road_Cycling_2024-06-02-07-12-44"),
+            Some("Lyon Road Cycling".to_string())
+        );
+        // Wahoo ELEMNT files and app exports.
+        assert_eq!(
+            name("2024-06-02-071244-ELEMNT_BOLT_4A2F-123-0"),
+            Some("ELEMNT BOLT 4A2F".to_string())
+        );
+        assert_eq!(
+            name("wahoo_2024-06-02T07-12-44Z"),
+            Some("wahoo".to_string())
+        );
+        // Strava bulk exports and single downloads.
+        assert_eq!(name("11234567890"), None);
+        assert_eq!(name("Morning_Ride"), Some("Morning Ride".to_string()));
+        assert_eq!(
+            name("Col_du_Galibier (1)"),
+            Some("Col du Galibier".to_string())
+        );
+        assert_eq!(
+            name("Évening_Ride_2024-06-02"),
+            Some("Évening Ride".to_string())
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp_len(b"2024-06-02T07-12-44Z rest"), Some(20));
+        assert_eq!(timestamp_len(b"2024-06-02T07:12"), Some(16));
+        assert_eq!(timestamp_len(b"2024-06-02-071244-ELEMNT"), Some(17));
+        assert_eq!(timestamp_len(b"2024-06-02-ride"), Some(10));
+        assert_eq!(timestamp_len(b"2024-6-2"), None);
+    }
+
+    #[test]
+    fn barometric_elevation_is_not_noisy() {
+        let noise = gpx_elevation_noise(&fixture("barometric.gpx")).unwrap();
+        assert!(noise < 1.0, "noise = {noise}");
+    }
+
+    #[test]
+    fn gps_altitude_is_noisy() {
+        let noise = gpx_elevation_noise(&fixture("gps_altitude.gpx")).unwrap();
+        assert!(noise > ELEVATION_NOISE_THRESHOLD, "noise = {noise}");
+    }
 }
@@ -0,0 +1,66 @@
+/// Actions kept for undo; older ones are dropped.
+const MAX_HISTORY: usize = 100;
+
+/// Undo and redo stacks of reversible actions.
+#[derive(Debug, Clone)]
+pub struct History<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            undo: vec![],
+            redo: vec![],
+        }
+    }
+}
+
+impl<T: Clone> History<T> {
+    /// Record an action that was just applied; it can no longer be redone
+    /// past it.
+    pub fn push(&mut self, action: T) {
+        if self.undo.len() == MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.undo.push(action);
+        self.redo.clear();
+    }
+
+    /// The action to revert, moved to the redo stack.
+    pub fn undo(&mut self) -> Option<T> {
+        let action = self.undo.pop()?;
+        self.redo.push(action.clone());
+        Some(action)
+    }
+
+    /// The action to apply again, moved back to the undo stack.
+    pub fn redo(&mut self) -> Option<T> {
+        let action = self.redo.pop()?;
+        self.undo.push(action.clone());
+        Some(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_action_clears_redo() {
+        let mut history = History::default();
+        history.push(1);
+        history.push(2);
+
+        assert_eq!(history.undo(), Some(2));
+        assert_eq!(history.redo(), Some(2));
+        assert_eq!(history.undo(), Some(2));
+        history.push(3);
+
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo(), Some(3));
+        assert_eq!(history.undo(), Some(1));
+        assert_eq!(history.undo(), None);
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result, bail};
+use std::time::Duration;
+use ureq::Agent;
+
+/// Time allowed for a whole download unless `--timeout` is given.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Redirects followed before giving up.
+const MAX_REDIRECTS: u32 = 5;
+
+/// Larger bodies are not GPX files worth waiting for, in bytes.
+const MAX_BODY_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Download a GPX document, failing on HTTP errors and on bodies that are
+/// obviously not XML.
+pub fn download(url: &str, timeout: Option<Duration>) -> Result<Vec<u8>> {
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(timeout.unwrap_or(DEFAULT_TIMEOUT)))
+        .max_redirects(MAX_REDIRECTS)
+        .build()
+        .into();
+    let mut response = agent
+        .get(url)
+        .call()
+        .with_context(|| format!("downloading {url}"))?;
+    let body = response
+        .body_mut()
+        .with_config()
+        .limit(MAX_BODY_BYTES)
+        .read_to_vec()
+        .with_context(|| format!("downloading {url}"))?;
+
+    let start = body.iter().find(|byte| !byte.is_ascii_whitespace());
+    // A UTF-8 byte order mark may come before the XML declaration.
+    if !matches!(start, Some(b'<' | 0xEF)) {
+        bail!("{url} is not an XML document");
+    }
+
+    Ok(body)
+}
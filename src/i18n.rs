@@ -0,0 +1,975 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fmt;
+
+/// Language of the interface and the reports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    En,
+    Fr,
+}
+
+/// French texts, keyed by the English ones.
+const FR: &[(&str, &str)] = &[
+    // Interface.
+    ("Activities", "Activités"),
+    (
+        "Activities ({shown}/{total})",
+        "Activités ({shown}/{total})",
+    ),
+    ("Activity Detail", "Détail de l'activité"),
+    ("Summary", "Résumé"),
+    ("Distance models", "Modèles de distance"),
+    ("Weather", "Météo"),
+    ("Plan", "Plan"),
+    ("Intervals", "Intervalles"),
+    ("Terrain", "Terrain"),
+    ("Bests", "Records"),
+    ("Wind", "Vent"),
+    ("Ferries and trains", "Ferries et trains"),
+    ("Reference climbs", "Cols de référence"),
+    (
+        "{climb}: measured {measured} m vs official {official} m ({error}%)",
+        "{climb} : {measured} m mesurés pour {official} m officiels ({error} %)",
+    ),
+    ("Data quality", "Qualité des données"),
+    ("Notes", "Notes"),
+    ("Activity Elevation", "Profil de l'activité"),
+    ("Elevation", "Altitude"),
+    ("Distance (km)", "Distance (km)"),
+    ("Elevation (m)", "Altitude (m)"),
+    ("Rate of Climb", "Vitesse ascensionnelle"),
+    ("Time (min)", "Temps (min)"),
+    ("m/min", "m/min"),
+    ("No activity selected...", "Aucune activité sélectionnée..."),
+    (
+        "terminal too small (need {size})",
+        "terminal trop petit ({size} requis)",
+    ),
+    ("Grand Total: ", "Total : "),
+    (" Flat eq.: ", " Éq. plat : "),
+    (" Sport: ", " Sport : "),
+    (" (virtual included)", " (virtuel inclus)"),
+    (
+        " (virtual excluded, v to count)",
+        " (virtuel exclu, v pour l'inclure)",
+    ),
+    (
+        " ({count} duplicates skipped)",
+        " ({count} doublons ignorés)",
+    ),
+    (
+        " (showing latest {shown} of {total})",
+        " ({shown} plus récentes sur {total})",
+    ),
+    (
+        " ({count} duplicate paths skipped)",
+        " ({count} chemins en double ignorés)",
+    ),
+    (" ({count} failed)", " ({count} en échec)"),
+    (" Loaded {received}/{total}…", " Chargé {received}/{total}…"),
+    ("Range: ", "Plage : "),
+    ("ride", "sortie"),
+    ("rides", "sorties"),
+    ("day", "jour"),
+    ("days", "jours"),
+    (
+        "{rides} {distance} km ↑ {elevation}m moving {moving}",
+        "{rides} {distance} km ↑ {elevation}m en mouvement {moving}",
+    ),
+    (
+        "Rename (empty for the track name): ",
+        "Renommer (vide pour le nom de la trace) : ",
+    ),
+    ("Toggle tag: ", "Ajouter ou retirer l'étiquette : "),
+    ("Filter (Esc to clear): ", "Filtrer (Échap pour effacer) : "),
+    (
+        "Tour (empty to leave it): ",
+        "Voyage (vide pour le quitter) : ",
+    ),
+    ("Write the comparison to: ", "Écrire la comparaison dans : "),
+    (
+        "r rename  t tag  c commute  a archive  / filter  u undo  , tuning  : commands  ? help",
+        "r renommer  t étiquette  c trajet  a archiver  / filtrer  u annuler  , réglages  : commandes  ? aide",
+    ),
+    ("Commands", "Commandes"),
+    ("Keys", "Touches"),
+    ("No matching command", "Aucune commande correspondante"),
+    ("command palette", "palette de commandes"),
+    (
+        "Search the commands and run one",
+        "Chercher une commande et l'exécuter",
+    ),
+    ("help", "aide"),
+    ("Show or hide the keys", "Afficher ou masquer les touches"),
+    ("quit", "quitter"),
+    (
+        "Save the changes and exit",
+        "Enregistrer les modifications et quitter",
+    ),
+    ("next", "suivante"),
+    (
+        "Select the next ride, or scroll the detail down",
+        "Sélectionner la sortie suivante, ou faire défiler le détail vers le bas",
+    ),
+    ("previous", "précédente"),
+    (
+        "Select the previous ride, or scroll the detail up",
+        "Sélectionner la sortie précédente, ou faire défiler le détail vers le haut",
+    ),
+    ("switch pane", "changer de panneau"),
+    (
+        "Move the focus between the list and the detail",
+        "Passer de la liste au détail et inversement",
+    ),
+    ("focus list", "aller à la liste"),
+    ("Move the focus to the list", "Passer à la liste"),
+    ("focus detail", "aller au détail"),
+    ("Move the focus to the detail", "Passer au détail"),
+    ("combine days", "regrouper les jours"),
+    (
+        "List the rides of each day as one entry, or each on its own line",
+        "Lister les sorties de chaque jour en une seule entrée, ou chacune sur sa ligne",
+    ),
+    ("group months", "grouper par mois"),
+    (
+        "List the rides of each month under a header, or without",
+        "Lister les sorties de chaque mois sous un titre, ou sans",
+    ),
+    ("expand", "déplier"),
+    (
+        "Unfold the selected day or detail section, or fold it back",
+        "Déplier le jour ou la section du détail sélectionné, ou le replier",
+    ),
+    ("narrow list", "rétrécir la liste"),
+    ("Make the list narrower", "Rendre la liste plus étroite"),
+    ("widen list", "élargir la liste"),
+    ("Make the list wider", "Rendre la liste plus large"),
+    ("statistics", "statistiques"),
+    (
+        "Show or hide the statistics",
+        "Afficher ou masquer les statistiques",
+    ),
+    ("same route", "même itinéraire"),
+    (
+        "List the rides on the route of the selected one",
+        "Lister les sorties sur l'itinéraire de celle sélectionnée",
+    ),
+    ("climbs", "montées"),
+    (
+        "Show the leaderboards of the climbs of the selected ride",
+        "Afficher les classements des montées de la sortie sélectionnée",
+    ),
+    ("export comparison", "export de la comparaison"),
+    (
+        "Write the selected ride and the one it is compared with as CSV",
+        "Écrire en CSV la sortie sélectionnée et celle à laquelle elle est comparée",
+    ),
+    ("data quality", "qualité des données"),
+    (
+        "List the rides by the quality of their data, worst first",
+        "Lister les sorties par qualité de leurs données, la pire en premier",
+    ),
+    ("hide", "masquage"),
+    (
+        "Hide the marked or selected rides from the list and the totals",
+        "Masquer les sorties marquées ou choisies de la liste et des totaux",
+    ),
+    ("hidden rides", "sorties masquées"),
+    (
+        "List what the list leaves out and why, to show it again",
+        "Lister ce que la liste laisse de côté et pourquoi, pour l'afficher à nouveau",
+    ),
+    ("climb rate", "vitesse ascensionnelle"),
+    (
+        "Chart the rate of climb of the selected ride instead of its elevation",
+        "Tracer la vitesse ascensionnelle de la sortie sélectionnée au lieu de son altitude",
+    ),
+    ("distance models", "modèles de distance"),
+    (
+        "Compare the distance of the selected ride under each model",
+        "Comparer la distance de la sortie sélectionnée selon chaque modèle",
+    ),
+    ("tour", "voyage"),
+    (
+        "Add the marked or selected rides to a tour, or take them out",
+        "Ajouter les sorties marquées ou sélectionnées à un voyage, ou les en retirer",
+    ),
+    ("tour stages", "étapes du voyage"),
+    (
+        "Show the stages of the tour of the selected ride",
+        "Afficher les étapes du voyage de la sortie sélectionnée",
+    ),
+    ("scrub", "parcourir"),
+    (
+        "Move a cursor along the selected ride with the arrows",
+        "Déplacer un curseur le long de la sortie sélectionnée avec les flèches",
+    ),
+    ("count virtual rides", "compter les sorties virtuelles"),
+    (
+        "Count the virtual rides in the totals, or not",
+        "Compter ou non les sorties virtuelles dans les totaux",
+    ),
+    ("tuning", "réglages"),
+    (
+        "Adjust the parameters of the metrics",
+        "Ajuster les paramètres des mesures",
+    ),
+    ("rename ride", "renommer la sortie"),
+    (
+        "Rename the selected ride",
+        "Renommer la sortie sélectionnée",
+    ),
+    ("tag", "étiquette"),
+    (
+        "Add or remove a tag of the selected or marked rides",
+        "Ajouter ou retirer une étiquette des sorties sélectionnées ou marquées",
+    ),
+    ("commute", "trajet"),
+    (
+        "Toggle the commute tag of the selected or marked rides",
+        "Basculer l'étiquette trajet des sorties sélectionnées ou marquées",
+    ),
+    ("share", "partager"),
+    (
+        "Copy the share text of the selected ride",
+        "Copier le texte de partage de la sortie sélectionnée",
+    ),
+    ("Copied: {text}", "Copié : {text}"),
+    ("archive rides", "archiver les sorties"),
+    (
+        "Archive the selected or marked rides",
+        "Archiver les sorties sélectionnées ou marquées",
+    ),
+    ("mark", "marquer"),
+    (
+        "Mark the selected ride for the next commute toggle or archive",
+        "Marquer la sortie sélectionnée pour le prochain trajet ou archivage",
+    ),
+    ("filter", "filtrer"),
+    (
+        "Filter the list by name or tag",
+        "Filtrer la liste par nom ou étiquette",
+    ),
+    ("undo", "annuler"),
+    ("Undo the last change", "Annuler la dernière modification"),
+    ("redo", "rétablir"),
+    (
+        "Redo the last undone change",
+        "Rétablir la dernière modification annulée",
+    ),
+    ("reload ride", "relire la sortie"),
+    (
+        "Read the selected ride again from its file",
+        "Relire la sortie sélectionnée depuis son fichier",
+    ),
+    ("reload all", "tout relire"),
+    (
+        "Read every file again, with the ones added since",
+        "Relire tous les fichiers, avec ceux ajoutés depuis",
+    ),
+    ("range start", "début de plage"),
+    (
+        "Start a range at the selected ride, for a subtotal",
+        "Commencer une plage à la sortie sélectionnée, pour un sous-total",
+    ),
+    ("range end", "fin de plage"),
+    (
+        "End a range at the selected ride, for a subtotal",
+        "Finir une plage à la sortie sélectionnée, pour un sous-total",
+    ),
+    ("clear range", "effacer la plage"),
+    ("Remove the range", "Retirer la plage"),
+    ("zoom in", "zoomer"),
+    (
+        "Zoom the route map in on the middle of the route",
+        "Zoomer la carte sur le milieu du parcours",
+    ),
+    ("zoom out", "dézoomer"),
+    (
+        "Zoom the route map out, back to the whole route",
+        "Dézoomer la carte, jusqu'au parcours entier",
+    ),
+    ("Route", "Parcours"),
+    ("+/- zoom", "+/- zoom"),
+    (
+        "range start set, ] to set the end",
+        "début de plage défini, ] pour la fin",
+    ),
+    (
+        "range end set, [ to set the start",
+        "fin de plage définie, [ pour le début",
+    ),
+    ("undone: {action}", "annulé : {action}"),
+    ("redone: {action}", "rétabli : {action}"),
+    ("nothing to undo", "rien à annuler"),
+    ("nothing to redo", "rien à rétablir"),
+    ("{name} read again", "{name} relue"),
+    ("{path} is gone", "{path} n'existe plus"),
+    (
+        "reading every file again…",
+        "relecture de tous les fichiers…",
+    ),
+    (
+        "the files can be read again once they are loaded",
+        "les fichiers pourront être relus une fois chargés",
+    ),
+    (" [missing]", " [absent]"),
+    (
+        "{path} is gone, as last read:",
+        "{path} n'existe plus, tel que lu la dernière fois :",
+    ),
+    ("rename", "renommage"),
+    ("archive", "archivage"),
+    ("commute toggle", "bascule trajet"),
+    ("tag {tag}", "étiquette {tag}"),
+    ("untag {tag}", "retrait de l'étiquette {tag}"),
+    ("join tour {tour}", "ajout au voyage {tour}"),
+    ("leave tour", "retrait du voyage"),
+    ("show", "affichage"),
+    ("{count} marked", "{count} marquées"),
+    ("{action} of {name}", "{action} de {name}"),
+    (
+        "{action} of {count} activities",
+        "{action} de {count} activités",
+    ),
+    (" [commute]", " [trajet]"),
+    // Detail pane.
+    (
+        "Distance: {distance} (flat equivalent {flat}km) Uphill ↑: {elevation}m",
+        "Distance : {distance} (équivalent plat {flat}km) Dénivelé ↑ : {elevation}m",
+    ),
+    (
+        " (DEM corrected, recorded: {elevation}m)",
+        " (corrigé par MNT, enregistré : {elevation}m)",
+    ),
+    (
+        "max {elevation} m at km {km}",
+        "max {elevation} m au km {km}",
+    ),
+    ("haversine", "haversine"),
+    ("geodesic", "géodésique"),
+    ("  (used)", "  (utilisé)"),
+    ("measuring…", "mesure…"),
+    ("no points to measure", "aucun point à mesurer"),
+    ("name source: {source}", "origine du nom : {source}"),
+    ("renamed", "renommé"),
+    ("device: {device}", "appareil : {device}"),
+    (
+        "since last run: {rides}, {distance} km",
+        "depuis la dernière fois : {rides}, {distance} km",
+    ),
+    (
+        "since last run: {rides}, {distance} km; were files deleted?",
+        "depuis la dernière fois : {rides}, {distance} km ; des fichiers ont-ils été supprimés ?",
+    ),
+    (
+        "also as: {files} (kept: {format})",
+        "aussi en : {files} (gardé : {format})",
+    ),
+    (
+        "time: {start} to {end}, {elapsed} elapsed",
+        "horaire : {start} à {end}, {elapsed} écoulé",
+    ),
+    ("unknown", "inconnu"),
+    ("track name", "nom de la trace"),
+    ("GPX metadata", "métadonnées GPX"),
+    ("route name", "nom de l'itinéraire"),
+    ("file name", "nom du fichier"),
+    ("URL", "URL"),
+    ("default", "par défaut"),
+    ("countries: {countries}", "pays : {countries}"),
+    (
+        "new roads: {km} km ({share})",
+        "nouvelles routes : {km} km ({share})",
+    ),
+    (
+        "vs. my average: {comparison}",
+        "par rapport à ma moyenne : {comparison}",
+    ),
+    ("distance {change}", "distance {change}"),
+    ("elevation {change}", "dénivelé {change}"),
+    (
+        "speed {change} (similar length)",
+        "vitesse {change} (longueur similaire)",
+    ),
+    (
+        "weather: {temperature}°C, wind {wind} km/h, rain {rain} mm",
+        "météo : {temperature} °C, vent {wind} km/h, pluie {rain} mm",
+    ),
+    (
+        "weather: no data for that day",
+        "météo : pas de données pour ce jour",
+    ),
+    (
+        "Intervals (above {threshold} {unit}):",
+        "Intervalles (au-dessus de {threshold} {unit}) :",
+    ),
+    (
+        " after {recovery} recovery",
+        " après {recovery} de récupération",
+    ),
+    (
+        "sprints: {count} (top speed {speed} km/h)",
+        "sprints : {count} (vitesse max {speed} km/h)",
+    ),
+    ("score: {score}/100", "note : {score}/100"),
+    ("timestamps", "horodatages"),
+    ("elevation", "altitude"),
+    ("GPS jitter", "bruit GPS"),
+    ("gaps", "trous"),
+    ("duplicate points", "points en double"),
+    (
+        "GPS: {count} jittery points",
+        "GPS : {count} points bruités",
+    ),
+    (
+        "gaps: {distance} km without points",
+        "trous : {distance} km sans points",
+    ),
+    ("elevation: low quality", "altitude : faible qualité"),
+    (
+        "timestamps: {count} implausible ignored",
+        "horodatages : {count} improbables ignorés",
+    ),
+    ("intensity: {intensity}", "intensité : {intensity}"),
+    (
+        "points: {count} duplicates removed",
+        "points : {count} doublons supprimés",
+    ),
+    (
+        "Plan at {speed} km/h: {time}",
+        "Plan à {speed} km/h : {time}",
+    ),
+    ("bottle stop", "ravitaillement"),
+    (
+        "climb: {length} km at {grade}%, top at {top}",
+        "montée : {length} km à {grade} %, sommet à {top}",
+    ),
+    ("finish", "arrivée"),
+    ("Controls:", "Contrôles :"),
+    ("open {opens}–{closes}", "ouvert {opens}–{closes}"),
+    ("early", "en avance"),
+    ("in time", "dans les délais"),
+    ("late", "hors délais"),
+    ("not reached", "non atteint"),
+    (
+        "Cursor: {values} (←/→ 100 m, shift 1 km, esc leave)",
+        "Curseur : {values} (←/→ 100 m, maj 1 km, échap quitter)",
+    ),
+    ("warning: {warning}", "avertissement : {warning}"),
+    ("bests", "records"),
+    ("terrain", "terrain"),
+    ("time", "temps"),
+    ("climbing", "montée"),
+    ("flat", "plat"),
+    ("descending", "descente"),
+    (
+        "descending: {speed} km/h on {descents}",
+        "descente : {speed} km/h sur {descents}",
+    ),
+    (
+        "headwind on the way out: {speed} km/h",
+        "vent de face à l'aller : {speed} km/h",
+    ),
+    (
+        "tailwind on the way out: {speed} km/h",
+        "vent de dos à l'aller : {speed} km/h",
+    ),
+    (
+        "speed without the wind: {speed} km/h",
+        "vitesse sans le vent : {speed} km/h",
+    ),
+    (
+        "estimated from out-and-back symmetry, on {km} km",
+        "estimé par symétrie de l'aller-retour, sur {km} km",
+    ),
+    (
+        "{length} km at {speed} km/h from km {start} in {time}, without pedaling",
+        "{length} km à {speed} km/h depuis le km {start} en {time}, sans pédaler",
+    ),
+    (
+        "{length} km at {speed} km/h from km {start} in {time}, in a straight line",
+        "{length} km à {speed} km/h depuis le km {start} en {time}, en ligne droite",
+    ),
+    (
+        "left out of the distance and time",
+        "exclus de la distance et du temps",
+    ),
+    (
+        "counted in the distance and time, unless --exclude-transport",
+        "comptés dans la distance et le temps, sauf avec --exclude-transport",
+    ),
+    ("descent", "descente"),
+    ("descents", "descentes"),
+    ("unknown terrain", "terrain inconnu"),
+    ("n/a", "n.d."),
+    // Statistics overlay.
+    ("Statistics", "Statistiques"),
+    ("c calendar", "c calendrier"),
+    ("Calendar", "Calendrier"),
+    ("Rides by start time", "Sorties par heure de départ"),
+    ("Mon", "lun"),
+    ("Tue", "mar"),
+    ("Wed", "mer"),
+    ("Thu", "jeu"),
+    ("Fri", "ven"),
+    ("Sat", "sam"),
+    ("Sun", "dim"),
+    ("{date}: no ride", "{date} : aucune sortie"),
+    ("Top starting locations", "Principaux lieux de départ"),
+    ("From {name}: ", "Depuis {name} : "),
+    ("Rain: ", "Pluie : "),
+    (" Dry: ", " Sec : "),
+    (" No weather data: ", " Sans données météo : "),
+    ("Ride length: median ", "Longueur des sorties : médiane "),
+    ("Altitude record: ", "Record d'altitude : "),
+    ("Time above: ", "Temps en altitude : "),
+    ("Rides: ", "Sorties : "),
+    ("Distance: ", "Distance : "),
+    ("Elevation gain: ", "Dénivelé positif : "),
+    ("Moving time: ", "Temps en mouvement : "),
+    ("Longest ride: ", "Plus longue sortie : "),
+    (
+        " {arrow} {percent}% on {month} ({value})",
+        " {arrow} {percent} % sur {month} ({value})",
+    ),
+    (" ({month}: {value})", " ({month} : {value})"),
+    (
+        "No rides in {month} to compare with.",
+        "Aucune sortie en {month} pour comparer.",
+    ),
+    ("time above {thresholds}", "temps au-dessus de {thresholds}"),
+    (
+        ", {name} on {date}, at km {km}",
+        ", {name} le {date}, au km {km}",
+    ),
+    ("Weekdays and weekends", "Semaine et week-end"),
+    ("Weekdays", "En semaine"),
+    ("Weekends", "Le week-end"),
+    ("Undated", "Sans date"),
+    ("commutes", "trajets"),
+    ("leisure", "loisirs"),
+    ("All leisure", "Tous les loisirs"),
+    ("{name}: ", "{name} : "),
+    ("Energy this week: ", "Énergie cette semaine : "),
+    (", this month: ", ", ce mois-ci : "),
+    (" (mixed)", " (mixte)"),
+    ("Last {weeks} weeks: ", "{weeks} dernières semaines : "),
+    (
+        "Descending, last {rides}: ",
+        "Descente, {rides} les plus récentes : ",
+    ),
+    (
+        "{arrow} {percent}% on the rides before",
+        "{arrow} {percent} % sur les sorties précédentes",
+    ),
+    (
+        "{arrow} {percent}% on the {weeks} before",
+        "{arrow} {percent} % sur les {weeks} précédentes",
+    ),
+    (
+        ", half of the rides between ",
+        ", la moitié des sorties entre ",
+    ),
+    ("{low} and {high} km", "{low} et {high} km"),
+    ("New roads:", "Nouvelles routes :"),
+    ("Exploration:", "Exploration :"),
+    ("Devices", "Appareils"),
+    ("Countries", "Pays"),
+    ("Andorra", "Andorre"),
+    ("Austria", "Autriche"),
+    ("Belgium", "Belgique"),
+    ("Germany", "Allemagne"),
+    ("Italy", "Italie"),
+    ("Netherlands", "Pays-Bas"),
+    ("Spain", "Espagne"),
+    ("Switzerland", "Suisse"),
+    ("Best {unit}:", "Records {unit} :"),
+    (
+        "Rides per {km} km of length",
+        "Sorties par tranche de {km} km",
+    ),
+    ("Distance by grade (km)", "Distance par pente (km)"),
+    ("Ascent by grade (m)", "Dénivelé par pente (m)"),
+    // Same route popup.
+    ("Same route", "Même itinéraire"),
+    (
+        "Same route: ridden once",
+        "Même itinéraire : parcouru une fois",
+    ),
+    (
+        "Same route: ridden {count} times",
+        "Même itinéraire : parcouru {count} fois",
+    ),
+    ("No track points to compare", "Aucun point à comparer"),
+    ("undated", "sans date"),
+    ("{similarity}% similar", "{similarity}% similaire"),
+    (
+        "Time vs. the ride of {date}",
+        "Temps face à la sortie du {date}",
+    ),
+    (
+        "lost {time} on the {length} km climb at km {start}",
+        "{time} perdues dans la montée de {length} km au km {start}",
+    ),
+    (
+        "gained {time} on the {length} km climb at km {start}",
+        "{time} gagnées dans la montée de {length} km au km {start}",
+    ),
+    (
+        "lost {time} between km {start} and km {end}",
+        "{time} perdues entre le km {start} et le km {end}",
+    ),
+    (
+        "gained {time} between km {start} and km {end}",
+        "{time} gagnées entre le km {start} et le km {end}",
+    ),
+    ("  fastest", "  plus rapide"),
+    ("  slowest", "  plus lente"),
+    ("  ◀ this ride", "  ◀ cette sortie"),
+    // Climbs popup.
+    ("Climbs", "Montées"),
+    ("No climb on this ride", "Aucune montée sur cette sortie"),
+    ("{length} km at {grade}%", "{length} km à {grade} %"),
+    ("climbed {count}", "montée {count}"),
+    ("time", "fois"),
+    ("times", "fois"),
+    (
+        "new PR on {climb}: {time}, {gain} faster",
+        "nouveau record sur {climb} : {time}, {gain} plus vite",
+    ),
+    (
+        "new PRs on {count} climbs, l to see the climbs of a ride",
+        "nouveaux records sur {count} montées, l pour voir les montées d'une sortie",
+    ),
+    // Tuning popup.
+    ("Tuning", "Réglages"),
+    ("elevation threshold", "seuil de dénivelé"),
+    ("speed spike limit", "limite des pics de vitesse"),
+    ("moving speed cutoff", "vitesse minimale en mouvement"),
+    ("jitter distance", "distance de bruit GPS"),
+    ("sparse track correction", "correction des traces espacées"),
+    (
+        "unpaved speed variation",
+        "variation de vitesse hors goudron",
+    ),
+    ("unpaved slowdown", "ralentissement hors goudron"),
+    (
+        "distance: from the file, the points are sparse",
+        "distance : celle du fichier, les points sont espacés",
+    ),
+    (
+        "distance: estimated, the points are sparse (+{percent}%)",
+        "distance : estimée, les points sont espacés (+{percent} %)",
+    ),
+    ("off", "désactivé"),
+    ("default {value}", "par défaut {value}"),
+    (
+        "{count} large files keep the metrics they were loaded with",
+        "{count} gros fichiers gardent les mesures du chargement",
+    ),
+    ("What if, selected ride", "Comparaison, sortie sélectionnée"),
+    ("as recorded", "enregistrée"),
+    ("tuned", "réglée"),
+    ("elevation gain", "dénivelé"),
+    ("moving time", "temps en mouvement"),
+    ("average speed", "vitesse moyenne"),
+    (
+        "the tuning can change once the files are loaded",
+        "le réglage pourra changer une fois les fichiers chargés",
+    ),
+    ("loading…", "chargement…"),
+    (
+        "↑↓ select  +/- adjust  s save  Esc close",
+        "↑↓ choisir  +/- ajuster  s enregistrer  Échap fermer",
+    ),
+    ("tuning saved to {path}", "réglages enregistrés dans {path}"),
+    (" (tuned: {parameters})", " (réglé : {parameters})"),
+    ("segment joins {policy}", "raccords de segments {policy}"),
+    ("count", "comptés"),
+    ("ignore", "ignorés"),
+    ("auto", "auto"),
+    // Tours.
+    ("Tours", "Voyages"),
+    ("stage", "étape"),
+    ("stages", "étapes"),
+    (
+        "tour: {tour}, stage {stage} of {count}",
+        "voyage : {tour}, étape {stage} sur {count}",
+    ),
+    (
+        "↑↓ select  Enter go to  d remove stage  Esc close",
+        "↑↓ choisir  Entrée aller à  d retirer l'étape  Échap fermer",
+    ),
+    (
+        "no other timed ride of this route to compare with",
+        "aucune autre sortie chronométrée de ce parcours à comparer",
+    ),
+    (
+        "comparison written to {path}",
+        "comparaison écrite dans {path}",
+    ),
+    (
+        "not part of a tour, T adds it to one",
+        "ne fait partie d'aucun voyage, T l'ajoute à un voyage",
+    ),
+    (
+        "the stage is hidden by the filter",
+        "l'étape est masquée par le filtre",
+    ),
+    // Quality dashboard.
+    (
+        "↑↓ select  Enter data quality  Esc close",
+        "↑↓ choisir  Entrée qualité des données  Échap fermer",
+    ),
+    ("No track points to score", "Aucun point à évaluer"),
+    (
+        "↑↓ select  Enter show again  Esc close",
+        "↑↓ choisir  Entrée afficher à nouveau  Échap fermer",
+    ),
+    ("Nothing is hidden", "Rien n'est masqué"),
+    ("Hidden: {count}", "Masqué : {count}"),
+    ("Filtered out", "Exclues par le filtre"),
+    ("Duplicates", "Doublons"),
+    ("Without points", "Sans points"),
+    ("Hidden by hand", "Masquées à la main"),
+    ("Not read", "Non lus"),
+    (
+        "the file holds a ride already listed",
+        "le fichier contient une sortie déjà listée",
+    ),
+    (
+        "the file could not be read",
+        "le fichier n'a pas pu être lu",
+    ),
+    (
+        "Data quality: {count}, {average}/100 on average",
+        "Qualité des données : {count}, {average}/100 en moyenne",
+    ),
+    (
+        "the ride is hidden by the filter",
+        "la sortie est masquée par le filtre",
+    ),
+    // Yearly review.
+    ("{year} in review", "{year} en revue"),
+    ("{km} km", "{km} km"),
+    ("{elevation} m climbed", "{elevation} m de dénivelé"),
+    ("{moving} moving", "{moving} en mouvement"),
+    (
+        "Eddington number: {eddington}",
+        "Nombre d'Eddington : {eddington}",
+    ),
+    (
+        "Exploration: {cells} km², {new} never visited before",
+        "Exploration : {cells} km², dont {new} jamais visités avant",
+    ),
+    ("{change}% on {year}", "{change} % par rapport à {year}"),
+    (" ({change}%)", " ({change} %)"),
+    ("Unpaved (a guess):", "Non goudronné (estimation) :"),
+    (" ({percent}%)", " ({percent} %)"),
+    (
+        "likely unpaved: {distance} km ({percent}%), a guess from the speed",
+        "probablement non goudronné : {distance} km ({percent} %), estimé d'après la vitesse",
+    ),
+    ("unpaved (estimate)", "non goudronné (estimé)"),
+    ("longest streak: {streak}", "plus longue série : {streak}"),
+    ("Longest streak: {streak}", "Plus longue série : {streak}"),
+    ("Distance per month", "Distance par mois"),
+    ("Trend: {slope} km/month", "Tendance : {slope} km/mois"),
+    (
+        "dashed: trend, blue: {months}-month average",
+        "pointillés : tendance, bleu : moyenne sur {months} mois",
+    ),
+    (
+        "* trend, | {months}-month average",
+        "* tendance, | moyenne sur {months} mois",
+    ),
+    ("Riding days", "Jours de sortie"),
+    ("Longest rides", "Plus longues sorties"),
+    ("Hours per intensity", "Heures par intensité"),
+    ("Week of", "Semaine du"),
+    ("recovery", "récupération"),
+    ("hard", "intense"),
+    ("Date", "Date"),
+    ("Name", "Nom"),
+    ("Distance", "Distance"),
+    ("Biggest climb", "Plus gros dénivelé"),
+    ("Highest point", "Point culminant"),
+    (
+        "{elevation} m at km {km} of {name} on {date}",
+        "{elevation} m au km {km} de {name} le {date}",
+    ),
+    (
+        "Highest point: {elevation} m at km {km} of {name} on {date}",
+        "Point culminant : {elevation} m au km {km} de {name} le {date}",
+    ),
+    (
+        "{name} on {date}: {elevation} m over {distance} km",
+        "{name} le {date} : {elevation} m sur {distance} km",
+    ),
+    (
+        "Biggest climb: {name} on {date}, {elevation} m over {distance} km",
+        "Plus gros dénivelé : {name} le {date}, {elevation} m sur {distance} km",
+    ),
+    ("Jan", "janv."),
+    ("Feb", "févr."),
+    ("Mar", "mars"),
+    ("Apr", "avr."),
+    ("May", "mai"),
+    ("Jun", "juin"),
+    ("Jul", "juil."),
+    ("Aug", "août"),
+    ("Sep", "sept."),
+    ("Oct", "oct."),
+    ("Nov", "nov."),
+    ("Dec", "déc."),
+];
+
+impl Lang {
+    /// Translations of the English texts; a missing one stays in English.
+    fn translations(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::En => &[],
+            Self::Fr => FR,
+        }
+    }
+
+    /// Code of the language, as used in HTML.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Fr => "fr",
+        }
+    }
+
+    /// Decimal separator, and thousands separator if the numbers are
+    /// grouped.
+    fn separators(self) -> (char, Option<char>) {
+        match self {
+            Self::En => ('.', None),
+            Self::Fr => (',', Some('\u{a0}')),
+        }
+    }
+
+    /// Date format used when `--date-format` is not given.
+    pub fn date_format(self) -> &'static str {
+        match self {
+            Self::En => "%d-%m-%Y",
+            Self::Fr => "%d/%m/%Y",
+        }
+    }
+
+    /// The translation of an English text.
+    pub fn text(self, english: &str) -> &str {
+        self.translations()
+            .iter()
+            .find(|(key, _)| *key == english)
+            .map_or(english, |(_, translation)| translation)
+    }
+
+    /// The translation of a template, with each `{name}` replaced by its
+    /// value.
+    pub fn fill(self, template: &str, values: &[(&str, &dyn fmt::Display)]) -> String {
+        let mut text = self.text(template).to_string();
+        for (name, value) in values {
+            text = text.replace(&format!("{{{name}}}"), &value.to_string());
+        }
+        text
+    }
+
+    /// `count` followed by the translation of `one` or `other`.
+    pub fn count(self, count: usize, one: &str, other: &str) -> String {
+        let singular = match self {
+            Self::En => count == 1,
+            Self::Fr => count <= 1,
+        };
+        let noun = self.text(if singular { one } else { other });
+        format!("{count} {noun}")
+    }
+
+    /// `value` with `decimals` digits after the separator.
+    pub fn number(self, value: f64, decimals: usize) -> String {
+        let (decimal, thousands) = self.separators();
+        let text = format!("{value:.decimals$}");
+        let (sign, text) = match text.strip_prefix('-') {
+            // Rounded to zero, or the empty sum.
+            Some(text) if text.chars().all(|c| c == '0' || c == '.') => ("", text),
+            Some(text) => ("-", text),
+            None => ("", text.as_str()),
+        };
+        let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+
+        let mut number = sign.to_string();
+        for (i, digit) in integer.chars().enumerate() {
+            if let Some(separator) = thousands
+                && i > 0
+                && (integer.len() - i) % 3 == 0
+            {
+                number.push(separator);
+            }
+            number.push(digit);
+        }
+        if !fraction.is_empty() {
+            number.push(decimal);
+            number.push_str(fraction);
+        }
+        number
+    }
+
+    /// [`Self::number`] with a `+` before values that do not round to zero.
+    pub fn signed_number(self, value: f64, decimals: usize) -> String {
+        let number = self.number(value, decimals);
+        if value > 0.0 && number.chars().any(|c| ('1'..='9').contains(&c)) {
+            format!("+{number}")
+        } else {
+            number
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn french_numbers() {
+        assert_eq!(Lang::Fr.number(12345.678, 1), "12\u{a0}345,7");
+        assert_eq!(Lang::Fr.number(-1234.0, 0), "-1\u{a0}234");
+        assert_eq!(Lang::Fr.number(999.5, 2), "999,50");
+        assert_eq!(Lang::Fr.number(-0.2, 0), "0");
+        assert_eq!(Lang::Fr.signed_number(12.4, 0), "+12");
+        assert_eq!(Lang::Fr.signed_number(0.2, 0), "0");
+        assert_eq!(Lang::En.number(12345.678, 1), "12345.7");
+    }
+
+    #[test]
+    fn missing_translations_fall_back_to_english() {
+        assert_eq!(Lang::Fr.text("Statistics"), "Statistiques");
+        assert_eq!(Lang::Fr.text("not translated"), "not translated");
+        assert_eq!(
+            Lang::Fr.fill("Same route: ridden {count} times", &[("count", &3)]),
+            "Même itinéraire : parcouru 3 fois"
+        );
+        assert_eq!(Lang::Fr.count(0, "ride", "rides"), "0 sortie");
+        assert_eq!(Lang::En.count(0, "ride", "rides"), "0 rides");
+    }
+
+    #[test]
+    fn translations_keep_placeholders() {
+        for (english, french) in FR {
+            let placeholders = |text: &str| {
+                let mut names: Vec<String> = text
+                    .split('{')
+                    .skip(1)
+                    .filter_map(|part| Some(part.split_once('}')?.0.to_string()))
+                    .collect();
+                names.sort();
+                names
+            };
+            assert_eq!(placeholders(english), placeholders(french), "{english}");
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use serde::Deserialize;
+
+use crate::activity::{MOVING_MIN_SPEED, TrackPoint};
+use crate::energy::POWER_COVERAGE;
+use crate::stats::RideMetrics;
+use crate::tuning::distance_m;
+
+/// Functional threshold power assumed without `--ftp`, in watts.
+pub const DEFAULT_FTP: f64 = 200.0;
+
+/// Maximum heart rate assumed without `--max-hr`, in bpm.
+pub const DEFAULT_MAX_HR: f64 = 190.0;
+
+/// Meters of climbing worth a flat kilometer in the effort speed, as 1 km
+/// at 10% counts double in the grade-adjusted distance.
+const CLIMB_M_PER_FLAT_KM: f64 = 100.0;
+
+/// How hard a ride was, from its average power, heart rate or effort speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Intensity {
+    Recovery,
+    Endurance,
+    Tempo,
+    Hard,
+}
+
+impl Intensity {
+    pub const ALL: [Intensity; 4] = [
+        Intensity::Recovery,
+        Intensity::Endurance,
+        Intensity::Tempo,
+        Intensity::Hard,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Intensity::Recovery => "recovery",
+            Intensity::Endurance => "endurance",
+            Intensity::Tempo => "tempo",
+            Intensity::Hard => "hard",
+        }
+    }
+
+    /// The class of `value` given the upper bounds of the first three.
+    fn from_bounds(value: f64, bounds: [f64; 3]) -> Self {
+        let class = bounds.iter().filter(|&&bound| value >= bound).count();
+        Self::ALL[class]
+    }
+}
+
+/// Averages of the sensors over the moving time, each `None` when its
+/// readings cover less than [`POWER_COVERAGE`] of it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SensorSummary {
+    /// In watts.
+    pub average_power: Option<f64>,
+    /// In bpm.
+    pub average_heart_rate: Option<f64>,
+}
+
+/// Time-weighted averages of the power and heart rate between moving
+/// points.
+pub fn sensor_summary(points: &[TrackPoint]) -> SensorSummary {
+    let mut moving_s = 0.0;
+    // Seconds covered and sum of value × seconds.
+    let (mut power, mut heart_rate) = ((0.0, 0.0), (0.0, 0.0));
+    for pair in points.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        if b.segment_start {
+            continue;
+        }
+        let (Some(t1), Some(t2)) = (a.time, b.time) else {
+            continue;
+        };
+        let seconds = (t2 - t1).as_seconds_f64();
+        if seconds <= 0.0 || distance_m(a, b) / seconds < MOVING_MIN_SPEED {
+            continue;
+        }
+        moving_s += seconds;
+        for (sum, values) in [
+            (&mut power, a.power.zip(b.power)),
+            (&mut heart_rate, a.heart_rate.zip(b.heart_rate)),
+        ] {
+            if let Some((v1, v2)) = values {
+                sum.0 += seconds;
+                sum.1 += (v1 + v2) / 2.0 * seconds;
+            }
+        }
+    }
+    let average = |(seconds, sum): (f64, f64)| {
+        (seconds > 0.0 && seconds >= POWER_COVERAGE * moving_s).then(|| sum / seconds)
+    };
+    SensorSummary {
+        average_power: average(power),
+        average_heart_rate: average(heart_rate),
+    }
+}
+
+/// Lower bounds of the endurance, tempo and hard classes, overridable in the
+/// `[intensity]` table of the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IntensityZones {
+    /// Shares of the FTP.
+    pub power: [f64; 3],
+    /// Shares of the maximum heart rate.
+    pub heart_rate: [f64; 3],
+    /// Effort speeds, in km/h; see [`effort_speed`].
+    pub effort_speed: [f64; 3],
+}
+
+impl Default for IntensityZones {
+    fn default() -> Self {
+        Self {
+            power: [0.55, 0.75, 0.9],
+            heart_rate: [0.68, 0.78, 0.87],
+            effort_speed: [20.0, 25.0, 30.0],
+        }
+    }
+}
+
+/// What rides are classified against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntensityThresholds {
+    pub ftp: f64,
+    pub max_hr: f64,
+    pub zones: IntensityZones,
+}
+
+impl Default for IntensityThresholds {
+    fn default() -> Self {
+        Self::new(None, None, IntensityZones::default())
+    }
+}
+
+impl IntensityThresholds {
+    /// [`DEFAULT_FTP`] and [`DEFAULT_MAX_HR`] stand in for the missing
+    /// values.
+    pub fn new(ftp: Option<f64>, max_hr: Option<f64>, zones: IntensityZones) -> Self {
+        Self {
+            ftp: ftp.unwrap_or(DEFAULT_FTP),
+            max_hr: max_hr.unwrap_or(DEFAULT_MAX_HR),
+            zones,
+        }
+    }
+}
+
+/// Moving speed with every [`CLIMB_M_PER_FLAT_KM`] of climbing counted as
+/// one more kilometer, in km/h.
+pub fn effort_speed(ride: &RideMetrics) -> Option<f64> {
+    let hours = ride.moving_seconds.filter(|&seconds| seconds > 0.0)? / 3600.0;
+    Some((ride.distance_km + ride.elevation_gain / CLIMB_M_PER_FLAT_KM) / hours)
+}
+
+/// Intensity of a ride from its average power, else its average heart rate,
+/// else its effort speed. `None` without moving time.
+pub fn classify(
+    ride: &RideMetrics,
+    sensors: &SensorSummary,
+    thresholds: &IntensityThresholds,
+) -> Option<Intensity> {
+    ride.moving_seconds.filter(|&seconds| seconds > 0.0)?;
+    let zones = &thresholds.zones;
+    Some(match (sensors.average_power, sensors.average_heart_rate) {
+        (Some(power), _) => Intensity::from_bounds(power / thresholds.ftp, zones.power),
+        (None, Some(heart_rate)) => {
+            Intensity::from_bounds(heart_rate / thresholds.max_hr, zones.heart_rate)
+        }
+        (None, None) => Intensity::from_bounds(effort_speed(ride)?, zones.effort_speed),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, TimeDelta};
+
+    fn ride(distance_km: f64, elevation_gain: f64, hours: f64) -> RideMetrics {
+        RideMetrics {
+            name: String::new(),
+            date: None,
+            time: None,
+            distance_km,
+            elevation_gain,
+            moving_seconds: Some(hours * 3600.0),
+            average_speed: Some(distance_km / hours),
+            energy: None,
+            sensors: SensorSummary::default(),
+            peak: None,
+        }
+    }
+
+    #[test]
+    fn power_comes_first_then_heart_rate_then_speed() {
+        let thresholds = IntensityThresholds::new(Some(250.0), Some(200.0), Default::default());
+        let ride = ride(30.0, 0.0, 1.0);
+        let sensors = |average_power, average_heart_rate| SensorSummary {
+            average_power,
+            average_heart_rate,
+        };
+
+        // 30 km/h on the flat is at the bottom of hard.
+        assert_eq!(
+            classify(&ride, &sensors(None, None), &thresholds),
+            Some(Intensity::Hard)
+        );
+        // 150 bpm is 75% of 200: endurance.
+        assert_eq!(
+            classify(&ride, &sensors(None, Some(150.0)), &thresholds),
+            Some(Intensity::Endurance)
+        );
+        // 125 W is 50% of 250: recovery, whatever the heart rate.
+        assert_eq!(
+            classify(&ride, &sensors(Some(125.0), Some(150.0)), &thresholds),
+            Some(Intensity::Recovery)
+        );
+        assert_eq!(
+            classify(&ride, &sensors(Some(200.0), None), &thresholds),
+            Some(Intensity::Tempo)
+        );
+    }
+
+    #[test]
+    fn climbing_counts_in_the_effort_speed() {
+        let thresholds = IntensityThresholds::default();
+        let sensors = SensorSummary::default();
+
+        // 40 km in 2 hours is 20 km/h; 1000 m of climbing add 10 km.
+        let flat = ride(40.0, 0.0, 2.0);
+        assert_eq!(effort_speed(&flat), Some(20.0));
+        assert_eq!(
+            classify(&flat, &sensors, &thresholds),
+            Some(Intensity::Endurance)
+        );
+        let hilly = ride(40.0, 1_000.0, 2.0);
+        assert_eq!(effort_speed(&hilly), Some(25.0));
+        assert_eq!(
+            classify(&hilly, &sensors, &thresholds),
+            Some(Intensity::Tempo)
+        );
+
+        let untimed = RideMetrics {
+            moving_seconds: None,
+            ..flat
+        };
+        assert_eq!(classify(&untimed, &sensors, &thresholds), None);
+    }
+
+    #[test]
+    fn sensors_need_to_cover_the_moving_time() {
+        let start = DateTime::parse_from_rfc3339("2024-06-01T07:00:00Z").unwrap();
+        // Every 100 m in 10 s, with power on the first 9 steps out of 10.
+        let points: Vec<TrackPoint> = (0..=10)
+            .map(|i| TrackPoint {
+                lat: 45.0 + i as f64 * 100.0 / 111_195.08,
+                lon: 5.0,
+                time: Some(start + TimeDelta::seconds(i * 10)),
+                power: (i < 10).then_some(100.0 + i as f64 * 10.0),
+                heart_rate: (i < 5).then_some(120.0),
+                ..Default::default()
+            })
+            .collect();
+
+        let summary = sensor_summary(&points);
+
+        // The 9 steps with power average 105 to 185 W.
+        assert_eq!(summary.average_power, Some(145.0));
+        assert_eq!(summary.average_heart_rate, None);
+    }
+}
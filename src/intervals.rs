@@ -0,0 +1,188 @@
+use crate::activity::TrackPoint;
+use crate::config::Config;
+
+/// Efforts shorter than this are not intervals, in seconds.
+const MIN_INTERVAL_S: f64 = 30.0;
+
+/// Drops below the threshold shorter than this (a gear change, a bump) do
+/// not end an effort, in seconds.
+const MAX_DIP_S: f64 = 10.0;
+
+/// Without an FTP, the threshold is this share of the ride's average.
+const AVERAGE_FRACTION: f64 = 1.05;
+
+const DEFAULT_FTP_FRACTION: f64 = 0.9;
+
+/// Sensor the intervals are detected on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Power,
+    HeartRate,
+}
+
+impl Metric {
+    fn value(self, point: &TrackPoint) -> Option<f64> {
+        match self {
+            Self::Power => point.power,
+            Self::HeartRate => point.heart_rate,
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            Self::Power => "W",
+            Self::HeartRate => "bpm",
+        }
+    }
+}
+
+/// A sustained effort above the threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    /// Since the first timed point, in seconds.
+    pub start_s: f64,
+    pub duration_s: f64,
+    pub average_power: Option<f64>,
+    pub average_heart_rate: Option<f64>,
+    /// Time since the end of the previous interval, in seconds.
+    pub recovery_s: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Intervals {
+    pub metric: Metric,
+    pub threshold: f64,
+    pub intervals: Vec<Interval>,
+}
+
+/// Find the periods of at least [`MIN_INTERVAL_S`] where the power (or the
+/// heart rate, for rides without power) stays above a threshold.
+///
+/// The power threshold is the configured fraction of the FTP when there is
+/// one, otherwise [`AVERAGE_FRACTION`] of the ride's average. Returns `None`
+/// when the ride has neither sensor or no timestamps.
+pub fn detect_intervals(points: &[TrackPoint], config: &Config) -> Option<Intervals> {
+    let metric = [Metric::Power, Metric::HeartRate]
+        .into_iter()
+        .find(|metric| points.iter().any(|point| metric.value(point).is_some()))?;
+
+    let start = points.iter().find_map(|point| point.time)?;
+    let samples: Vec<(f64, &TrackPoint, f64)> = points
+        .iter()
+        .filter_map(|point| {
+            let seconds = (point.time? - start).as_seconds_f64();
+            Some((seconds, point, metric.value(point)?))
+        })
+        .collect();
+
+    let threshold = match (metric, config.ftp) {
+        (Metric::Power, Some(ftp)) => {
+            ftp * config.interval_ftp_fraction.unwrap_or(DEFAULT_FTP_FRACTION)
+        }
+        _ => mean(samples.iter().map(|&(_, _, value)| value))? * AVERAGE_FRACTION,
+    };
+
+    // First and last sample above the threshold of each effort.
+    let mut efforts = vec![];
+    let mut current: Option<(usize, usize)> = None;
+    for (i, &(seconds, _, value)) in samples.iter().enumerate() {
+        if value >= threshold {
+            current = Some(current.map_or((i, i), |(first, _)| (first, i)));
+        } else if let Some((first, last)) = current
+            && seconds - samples[last].0 > MAX_DIP_S
+        {
+            efforts.push((first, last));
+            current = None;
+        }
+    }
+    efforts.extend(current);
+
+    let mut intervals = vec![];
+    let mut previous_end: Option<f64> = None;
+    for (first, last) in efforts {
+        // An effort lasts until the sample that drops below the threshold.
+        let start_s = samples[first].0;
+        let end_s = samples
+            .get(last + 1)
+            .map_or(samples[last].0, |sample| sample.0);
+        if end_s - start_s < MIN_INTERVAL_S {
+            continue;
+        }
+
+        let effort = &samples[first..=last];
+        intervals.push(Interval {
+            start_s,
+            duration_s: end_s - start_s,
+            average_power: mean(effort.iter().filter_map(|(_, point, _)| point.power)),
+            average_heart_rate: mean(effort.iter().filter_map(|(_, point, _)| point.heart_rate)),
+            recovery_s: previous_end.map(|end| start_s - end),
+        });
+        previous_end = Some(end_s);
+    }
+
+    Some(Intervals {
+        metric,
+        threshold,
+        intervals,
+    })
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::read_track_points;
+    use std::path::Path;
+
+    fn fixture_points(name: &str) -> Vec<TrackPoint> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        read_track_points(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn four_by_eight_minutes() {
+        let points = fixture_points("intervals.gpx");
+
+        let detected = detect_intervals(&points, &Config::default()).unwrap();
+
+        assert_eq!(detected.metric, Metric::Power);
+        assert_eq!(detected.intervals.len(), 4);
+        for (i, interval) in detected.intervals.iter().enumerate() {
+            assert_eq!(interval.start_s, 600.0 + i as f64 * 720.0);
+            assert_eq!(interval.duration_s, 480.0);
+            assert_eq!(interval.average_heart_rate, Some(165.0));
+            assert_eq!(interval.recovery_s, (i > 0).then_some(240.0));
+        }
+        // The second effort has one 10 s dip at 200 W.
+        assert_eq!(detected.intervals[0].average_power, Some(300.0));
+        assert!(detected.intervals[1].average_power.unwrap() < 300.0);
+    }
+
+    #[test]
+    fn ftp_threshold() {
+        let points = fixture_points("intervals.gpx");
+        let config = Config {
+            ftp: Some(400.0),
+            ..Default::default()
+        };
+
+        let detected = detect_intervals(&points, &config).unwrap();
+
+        assert_eq!(detected.threshold, 360.0);
+        assert!(detected.intervals.is_empty());
+    }
+
+    #[test]
+    fn rides_without_sensors() {
+        assert_eq!(
+            detect_intervals(&fixture_points("flat.gpx"), &Config::default()),
+            None
+        );
+    }
+}
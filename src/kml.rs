@@ -0,0 +1,204 @@
+use anyhow::{Context, Result};
+use chrono::Datelike;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+
+use crate::activity::{Activity, ActivityId};
+use crate::privacy::{PrivacyZone, strip_private};
+
+/// Line colors cycled through by year, in KML `aabbggrr` order.
+const YEAR_COLORS: [&str; 6] = [
+    "ff0000ff", "ff00a5ff", "ff00ffff", "ff00ff00", "ffff0000", "ffff00ff",
+];
+
+/// Write the activities as a KML document, or as a KMZ archive when `path`
+/// ends with `.kmz`.
+pub fn export_kml(
+    activities: &[Activity],
+    path: &Path,
+    zones: &[PrivacyZone],
+    tours: &BTreeMap<ActivityId, String>,
+) -> Result<()> {
+    let document = kml_document(activities, zones, tours);
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+
+    let is_kmz = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("kmz"));
+    if is_kmz {
+        let mut archive = zip::ZipWriter::new(file);
+        archive.start_file("doc.kml", SimpleFileOptions::default())?;
+        archive.write_all(document.as_bytes())?;
+        archive.finish()?;
+    } else {
+        let mut file = file;
+        file.write_all(document.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Build a KML document with one folder per year and one `LineString`
+/// placemark per activity. Activities without a date go in an "Undated"
+/// folder, the stages of a tour (keyed by activity in `tours`) in a folder
+/// of the tour after the years, and virtual rides and those without track
+/// points are left out.
+///
+/// Points inside the privacy `zones` are removed, splitting the line around
+/// them; a ride that never leaves a zone keeps a placemark without geometry.
+/// The number of removed points is given in the descriptions.
+pub fn kml_document(
+    activities: &[Activity],
+    zones: &[PrivacyZone],
+    tours: &BTreeMap<ActivityId, String>,
+) -> String {
+    let mut years = vec![];
+    let mut by_year: BTreeMap<Option<i32>, Vec<&Activity>> = BTreeMap::new();
+    let mut by_tour: Vec<(&str, Vec<&Activity>)> = vec![];
+    for activity in activities
+        .iter()
+        .filter(|activity| !activity.virtual_ride && !activity.points.is_empty())
+    {
+        let year = activity.start.map(|start| start.year());
+        if !years.contains(&year) {
+            years.push(year);
+        }
+        let Some(tour) = tours.get(&activity.id) else {
+            by_year.entry(year).or_default().push(activity);
+            continue;
+        };
+        match by_tour.iter_mut().find(|(name, _)| name == tour) {
+            Some((_, stages)) => stages.push(activity),
+            None => by_tour.push((tour, vec![activity])),
+        }
+    }
+    years.sort();
+
+    let mut kml = String::new();
+    kml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n");
+    kml.push_str("<name>cyclemetrics</name>\n");
+    let mut removed = 0;
+
+    for (i, year) in years.iter().enumerate() {
+        let _ = writeln!(
+            kml,
+            "<Style id=\"{}\"><LineStyle><color>{}</color><width>3</width></LineStyle></Style>",
+            style_id(*year),
+            YEAR_COLORS[i % YEAR_COLORS.len()],
+        );
+    }
+
+    for (year, activities) in &by_year {
+        let folder = year.map_or("Undated".to_string(), |year| year.to_string());
+        let _ = writeln!(kml, "<Folder>\n<name>{folder}</name>");
+        for activity in activities {
+            removed += write_placemark(&mut kml, activity, &style_id(*year), zones);
+        }
+        kml.push_str("</Folder>\n");
+    }
+    for (tour, mut stages) in by_tour {
+        stages.sort_by_key(|activity| (activity.start.is_none(), activity.start));
+        let _ = writeln!(kml, "<Folder>\n<name>{}</name>", escape(tour));
+        for activity in stages {
+            let style = style_id(activity.start.map(|start| start.year()));
+            removed += write_placemark(&mut kml, activity, &style, zones);
+        }
+        kml.push_str("</Folder>\n");
+    }
+
+    if !zones.is_empty() {
+        let _ = writeln!(
+            kml,
+            "<description>{}</description>",
+            removed_points(removed)
+        );
+    }
+    kml.push_str("</Document>\n</kml>\n");
+    kml
+}
+
+fn removed_points(count: usize) -> String {
+    match count {
+        1 => "1 point removed by privacy zones".to_string(),
+        _ => format!("{count} points removed by privacy zones"),
+    }
+}
+
+fn style_id(year: Option<i32>) -> String {
+    year.map_or("undated".to_string(), |year| format!("year-{year}"))
+}
+
+/// Write the placemark of an activity, returning the number of points
+/// removed by the privacy zones.
+fn write_placemark(
+    kml: &mut String,
+    activity: &Activity,
+    style: &str,
+    zones: &[PrivacyZone],
+) -> usize {
+    let name = match activity.start {
+        Some(start) => format!("{} {}", start.format("%Y-%m-%d"), activity.name),
+        None => activity.name.clone(),
+    };
+    let absolute = activity
+        .points
+        .iter()
+        .all(|point| point.elevation.is_some());
+    let stripped = strip_private(&activity.points, zones);
+
+    let _ = writeln!(
+        kml,
+        "<Placemark>\n<name>{}</name>\n<styleUrl>#{style}</styleUrl>",
+        escape(&name),
+    );
+    if stripped.removed > 0 {
+        let _ = writeln!(
+            kml,
+            "<description>{}</description>",
+            removed_points(stripped.removed)
+        );
+    }
+    let multiple = stripped.segments.len() > 1;
+    if multiple {
+        kml.push_str("<MultiGeometry>\n");
+    }
+    for segment in &stripped.segments {
+        let _ = writeln!(
+            kml,
+            "<LineString>\n<altitudeMode>{}</altitudeMode>\n<coordinates>",
+            if absolute {
+                "absolute"
+            } else {
+                "clampToGround"
+            },
+        );
+        for point in segment.iter() {
+            match point.elevation {
+                Some(elevation) if absolute => {
+                    let _ = writeln!(kml, "{},{},{}", point.lon, point.lat, elevation);
+                }
+                _ => {
+                    let _ = writeln!(kml, "{},{}", point.lon, point.lat);
+                }
+            }
+        }
+        kml.push_str("</coordinates>\n</LineString>\n");
+    }
+    if multiple {
+        kml.push_str("</MultiGeometry>\n");
+    }
+    kml.push_str("</Placemark>\n");
+    stripped.removed
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
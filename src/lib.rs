@@ -1,7 +1,241 @@
+mod activity;
+mod alternates;
+mod altitude;
+#[cfg(feature = "tui")]
+mod anonymize;
+mod bests;
+#[cfg(feature = "borders")]
+mod borders;
+mod brevet;
+mod calendar;
+mod climbs;
+#[cfg(feature = "tui")]
+mod commands;
+#[cfg(feature = "tui")]
+mod commute;
+#[cfg(feature = "tui")]
+mod config;
+#[cfg(feature = "tui")]
+mod debug_log;
+#[cfg(feature = "tui")]
+mod dem;
+mod distance_models;
+mod energy;
+#[cfg(feature = "tui")]
+mod exit;
+mod exploration;
+mod expr;
+mod fit;
 mod gpx;
+#[cfg(feature = "tui")]
+mod history;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "tui")]
+mod i18n;
+mod intensity;
+#[cfg(feature = "tui")]
+mod intervals;
+#[cfg(feature = "tui")]
+mod kml;
+#[cfg(feature = "tui")]
+mod loader;
+#[cfg(feature = "tui")]
+mod lru;
+mod map;
+#[cfg(feature = "images")]
+mod map_image;
+mod peaks;
+mod plan;
+mod privacy;
+#[cfg(feature = "tui")]
+mod prometheus;
+mod quality;
+mod reference_climbs;
+#[cfg(feature = "tui")]
+mod report;
+#[cfg(feature = "tui")]
+mod review;
+mod route_diff;
+mod routes;
+mod rules;
+#[cfg(feature = "tui")]
 mod runner;
+#[cfg(feature = "tui")]
+mod runs;
+mod scrub;
+#[cfg(feature = "tui")]
+mod sections;
+mod share;
+#[cfg(feature = "tui")]
+mod sidecar;
+mod sparkline;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod storage;
+mod stream;
+mod surface;
+mod symbols;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
+#[cfg(feature = "tui")]
+mod theme;
+#[cfg(feature = "tui")]
+mod thumbnail;
+mod tour;
+mod transport;
+mod tuning;
+#[cfg(feature = "tui")]
+mod validate;
+mod virtual_rides;
+#[cfg(feature = "tui")]
+mod watch;
+#[cfg(feature = "tui")]
+mod weather;
+mod wind;
 
-pub use gpx::gpx_total_distance;
+pub use activity::{Activity, ActivityId, FileFormat, TrackPoint, UNKNOWN_CREATOR};
+pub use alternates::{
+    ALTERNATE_START_S, ALTERNATE_TOLERANCE, DEFAULT_PREFERENCES, Fingerprint, GEOMETRY_CELL_DEG,
+    Preference, is_preferred,
+};
+pub use altitude::{DEFAULT_ALTITUDE_THRESHOLDS_M, time_above};
+#[cfg(feature = "tui")]
+pub use anonymize::{Anonymization, anonymize, random_offset};
+pub use bests::{BEST_WINDOWS_S, BestMetric, Bests, Sprint, all_time_bests, bests, detect_sprints};
+#[cfg(feature = "borders")]
+pub use borders::{
+    Borders, Country, CountryDistance, CountryTotal, countries_crossed, country_totals,
+};
+pub use brevet::{
+    ControlStatus, ControlTime, ControlWindow, check_controls, control_times, control_window,
+};
+pub use calendar::{CalendarGrid, HEAT_LEVELS, HeatScale};
+pub use climbs::{
+    CLIMB_MATCH_RADIUS_M, CLIMB_MIN_ASCENT_M, CLIMB_MIN_GRADE_PERCENT, CLIMB_MIN_LENGTH_M,
+    CLIMB_RATE_WINDOW_S, Climb, ClimbGroup, DESCENDING_TREND_RIDES, DESCENT_MAX_GRADE_PERCENT,
+    DESCENT_MIN_LENGTH_M, DescendingSpeed, Descent, climb_rate, descending_speed, descending_trend,
+    detect_climbs, detect_descents, group_climbs,
+};
+pub use distance_models::{DistanceModel, ModelDistances};
+pub use energy::{
+    Energy, EnergySource, MUSCLE_EFFICIENCY, POWER_COVERAGE, SYSTEM_MASS_KG, estimate_energy, kcal,
+};
+#[cfg(feature = "tui")]
+pub use exit::{ExitStatus, Failure};
+pub use exploration::{
+    CELL_SIZE_M, EXPLORATION_CELL_M, ExplorationYear, RoadTrace, exploration, exploration_by_year,
+    new_km_by_year, new_road_km, new_road_km_from_traces,
+};
+pub use expr::Expr;
+pub use fit::{FitActivity, fit_to_gpx, is_fit, read_fit};
+pub use gpx::{
+    DEFAULT_NAME, GradeModel, MIN_PLAUSIBLE_YEAR, NameSource, activity_name,
+    elevation_profile_min_max, file_name_or_default, gpx_elevation_gain, gpx_elevation_noise,
+    gpx_elevation_profile, gpx_implausible_timestamps, gpx_name, gpx_peak, gpx_route_points,
+    gpx_start_end_date, gpx_start_point, gpx_total_distance, gpx_track_name, grade_factor,
+    is_plausible_time, name_from_file_stem, remove_duplicate_points, write_gpx,
+};
+#[cfg(feature = "tui")]
+pub use i18n::Lang;
+pub use intensity::{
+    DEFAULT_FTP, DEFAULT_MAX_HR, Intensity, IntensityThresholds, IntensityZones, SensorSummary,
+    classify, effort_speed, sensor_summary,
+};
+#[cfg(feature = "tui")]
+pub use kml::{export_kml, kml_document};
+#[cfg(feature = "tui")]
+pub use loader::{
+    LoadOptions, Loaded, MAX_STDIN_BYTES, Resolved, STDIN_PATH, is_stdin, is_url, load_activities,
+    load_activity, load_each, load_readable, resolve_paths, spawn_loader,
+};
+pub use map::{MAX_MAP_ZOOM, MapView, TILE_SIZE, mercator};
+#[cfg(feature = "images")]
+pub use map_image::{
+    Image, KITTY_DELETE, MAX_IMAGE_SIDE, Protocol, decode_png, encode_png, route_image,
+};
+pub use peaks::{PEAK_WINDOW_M, Peak, PeakFinder, highest_point};
+pub use plan::{
+    DEFAULT_PLAN_SPEED_KMH, DEFAULT_STOP_EVERY_KM, PlannedClimb, RidePlan, is_planned, plan_ride,
+};
+pub use privacy::{
+    EXPOSURE_MAX_OTHERS, EXPOSURE_RADIUS_M, ExposedPlace, PrivacyZone, Stripped, exposed_places,
+    strip_private,
+};
+#[cfg(feature = "tui")]
+pub use prometheus::{export_prometheus, prometheus_textfile};
+pub use quality::{
+    DUPLICATES_WEIGHT, ELEVATION_WEIGHT, FULL_PENALTY_SHARE, GAP_FACTOR, GAPS_WEIGHT, Issue,
+    JITTER_LEG_M, JITTER_TURN_DEG, JITTER_WEIGHT, Problem, QualityScore, TIMESTAMPS_WEIGHT,
+    gap_distance_m, issues, jitter_points, quality_score,
+};
+pub use reference_climbs::{
+    ClimbCheck, ReferenceClimb, check_reference_climbs, load_reference_climbs,
+    parse_reference_climbs,
+};
+#[cfg(feature = "tui")]
+pub use report::{
+    Column, ColumnExpr, FIELDS, Field, FieldValue, Format, JsonLine, TABLE_COLUMNS,
+    check_column_exprs, default_columns, default_fields, export_csv, parse_column_expr,
+    parse_field, sort_activities, sort_column, write_csv, write_json, write_json_line, write_table,
+};
+#[cfg(feature = "tui")]
+pub use review::{ReviewFormat, review_html, review_text};
+pub use route_diff::{
+    AlignedRides, AlignedStreams, DIFF_STEP_M, DIFF_STRETCH_KM, DiffSegment, RouteDiff,
+    align_rides, route_diff, write_aligned_csv,
+};
+pub use routes::{MIN_ROUTE_SIMILARITY, ROUTE_CELL_M, RouteSignature, matching_routes};
+pub use rules::TagRule;
+#[cfg(feature = "tui")]
 pub use runner::App;
+#[cfg(feature = "tui")]
 pub use runner::Args;
+#[cfg(feature = "tui")]
 pub use runner::run_cyclemetrics;
+#[cfg(feature = "tui")]
+pub use runs::{DEFAULT_RUN_HISTORY, RunDelta, RunTotals, record_run, write_run_history};
+pub use scrub::{Sample, sample_at};
+pub use share::{DEFAULT_SHARE_TEMPLATE, ShareTemplate};
+pub use sparkline::{ASCII_LEVELS, ascii_sparkline, braille_sparkline, unicode_locale};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{export_sqlite, remove_from_sqlite};
+pub use stats::{
+    CLUSTER_RADIUS_M, CommuteSplit, DEFAULT_WEEKEND, DayTotals, DeviceUsage, ENERGY_TREND_WEEKS,
+    EnergyTotals, EnergyTrend, GRADE_BUCKETS, GradeHistogram, LinearTrend, LocationCluster,
+    MOVING_AVERAGE_MONTHS, Period, PeriodTotals, RIDE_LENGTH_BUCKET_KM, Recency, RideMetrics,
+    START_SLOT_HOURS, START_SLOTS, STEEPNESS_GRADE_PERCENT, Steepness, Summary,
+    TERRAIN_GRADE_PERCENT, Terrain, TerrainSplit, WeekSplit, device_usage, grade_adjusted_km,
+    grade_adjustments, grade_histogram, moving_average, round_to_total, start_location_clusters,
+    steepness_runs, terrain_breakdown, week_split, weekly_distance_km,
+};
+pub use storage::{
+    CORRUPT_EXTENSION, Recovered, SCHEMA_VERSION, Stored, read_versioned, write_atomic,
+    write_versioned,
+};
+pub use stream::{read_track_points, stream_activity};
+pub use surface::{SURFACE_SECTION_M, SurfaceEstimate, estimate_surface};
+pub use symbols::{AsciiWriter, Symbols, ascii_cell, ascii_char, to_ascii};
+#[cfg(feature = "tui")]
+pub use theme::{Theme, ThemeName};
+#[cfg(feature = "tui")]
+pub use thumbnail::{
+    MAX_THUMBNAIL_BYTES, THUMBNAIL_SIZE, export_thumbnails, project, thumbnail_stem, thumbnail_svg,
+};
+pub use tour::{Stage, Tour, TourTotals, group_tours};
+pub use transport::{TransportDetection, TransportSegment, detect_transport};
+pub use tuning::{
+    DerivedPoint, DistanceSource, JOIN_MAX_GAP_S, JOIN_MAX_SPEED_KMH, SPARSE_SPACING_M,
+    SegmentJoins, TunedMetrics, Tuning, TuningParameter, cumulative_distance, derived_points,
+    median_spacing_m, starts_segment, track_distance_m, tuned_distance_m, tuned_elevation_gain,
+    tuned_moving_seconds,
+};
+#[cfg(feature = "tui")]
+pub use validate::{Status, Validation, validate_file, validate_files, write_validation_report};
+pub use virtual_rides::{VIRTUAL_TAG, is_virtual_ride, virtual_world};
+#[cfg(feature = "tui")]
+pub use watch::{Exports, WATCH_INTERVAL, Watch, WatchUpdate};
+pub use wind::{
+    MIN_OUT_AND_BACK_OVERLAP, WIND_MAX_GRADE_PERCENT, WIND_SEGMENT_M, WindEstimate, estimate_wind,
+};
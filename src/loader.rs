@@ -0,0 +1,509 @@
+use anyhow::{Context, Result, bail};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{Level, debug, debug_span};
+
+use crate::activity::Activity;
+use crate::alternates::{Preference, is_preferred};
+use crate::dem::Dem;
+use crate::gpx::NameSource;
+use crate::stream::stream_activity;
+use crate::tuning::{Tuning, filter_report, tuned_elevation_gain};
+
+/// Files larger than this are read with [`stream_activity`] unless the full
+/// detail is requested, in bytes.
+pub const STREAM_THRESHOLD_BYTES: u64 = 32 * 1024 * 1024;
+
+/// The command line argument reading a GPX document from the standard input.
+pub const STDIN_PATH: &str = "-";
+
+/// Largest document read from the standard input unless configured, in
+/// bytes, so that a runaway pipe does not fill the memory.
+pub const MAX_STDIN_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Settings applied to every loaded file.
+#[derive(Debug, Default)]
+pub struct LoadOptions {
+    /// Replace elevations with the DEM values before computing the gain.
+    pub dem: Option<Dem>,
+    /// Fully parse files above [`STREAM_THRESHOLD_BYTES`] instead of
+    /// streaming them, to keep their per-point detail.
+    pub full_detail: bool,
+    /// Time allowed to download a file given as a URL.
+    pub timeout: Option<Duration>,
+    /// Largest document read from the standard input, [`MAX_STDIN_BYTES`]
+    /// when `None`.
+    pub max_stdin_bytes: Option<u64>,
+    /// Which of the files of the same ride in different formats is kept,
+    /// see [`is_preferred`].
+    pub prefer: Vec<Preference>,
+}
+
+/// Result of loading a set of GPX files.
+#[derive(Debug, Default)]
+pub struct Loaded {
+    /// Activities sorted by start date, then id.
+    pub activities: Vec<Activity>,
+    /// Files skipped because an activity with the same id was already loaded.
+    /// The files of the same ride in another format are not listed, but in
+    /// [`Activity::alternates`].
+    pub duplicates: Vec<PathBuf>,
+    /// Files that could not be read, with the reason, in the order of
+    /// `paths`; only [`load_readable`] fills it.
+    pub failed: Vec<(PathBuf, anyhow::Error)>,
+}
+
+/// Whether a command line argument is an `http(s)://` URL rather than a path
+/// or a pattern.
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| path.starts_with("http://") || path.starts_with("https://"))
+}
+
+/// Whether a command line argument is [`STDIN_PATH`].
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN_PATH)
+}
+
+/// Files matched by the paths and patterns of the command line.
+#[derive(Debug, Default, PartialEq)]
+pub struct Resolved {
+    /// Sorted, each file once.
+    pub paths: Vec<PathBuf>,
+    /// Paths to a file already in `paths`, matched by overlapping patterns
+    /// or reached through another link.
+    pub duplicates: Vec<PathBuf>,
+}
+
+/// Expand the supplied paths / glob patterns into a sorted list of files,
+/// keeping the first path to each of them. URLs and [`STDIN_PATH`] are kept
+/// as they are.
+pub fn resolve_paths(patterns: &[PathBuf]) -> Result<Resolved> {
+    let mut paths = vec![];
+    for pattern in patterns {
+        if is_url(pattern) || is_stdin(pattern) {
+            paths.push(pattern.clone());
+            continue;
+        }
+        let pattern = pattern
+            .to_str()
+            .with_context(|| format!("non UTF-8 path: {}", pattern.display()))?;
+        for file_res in glob::glob(pattern)? {
+            paths.push(file_res?);
+        }
+    }
+    paths.sort();
+
+    let mut resolved = Resolved::default();
+    let mut seen = HashSet::new();
+    for path in paths {
+        let file = if is_url(&path) || is_stdin(&path) {
+            path.clone()
+        } else {
+            fs::canonicalize(&path).unwrap_or_else(|_| path.clone())
+        };
+        if seen.insert(file) {
+            resolved.paths.push(path);
+        } else {
+            resolved.duplicates.push(path);
+        }
+    }
+    Ok(resolved)
+}
+
+/// Read and compute the metrics of a single GPX file.
+///
+/// Files above [`STREAM_THRESHOLD_BYTES`] are streamed unless
+/// `options.full_detail` is set. URLs are downloaded, and named after their
+/// last path segment. [`STDIN_PATH`] reads the standard input to its end,
+/// without seeking.
+pub fn load_activity(path: &Path, options: &LoadOptions) -> Result<Activity> {
+    let _span = debug_span!("load", path = %path.display()).entered();
+    let started = Instant::now();
+    let result = read_activity(path, options);
+    match &result {
+        Ok(activity) => trace_activity(activity, started.elapsed()),
+        Err(err) => debug!(error = format!("{err:#}"), "file not read"),
+    }
+    result
+}
+
+fn read_activity(path: &Path, options: &LoadOptions) -> Result<Activity> {
+    if is_url(path) {
+        let bytes = download(path, options)?;
+        debug!(bytes = bytes.len(), "downloaded");
+        let mut activity = activity_from_bytes(path, &bytes, options)?;
+        activity.name = url_label(&path.to_string_lossy()).to_string();
+        activity.name_source = NameSource::Url;
+        return Ok(activity);
+    }
+
+    if is_stdin(path) {
+        let limit = options.max_stdin_bytes.unwrap_or(MAX_STDIN_BYTES);
+        let bytes = read_limited(io::stdin().lock(), limit)?;
+        debug!(bytes = bytes.len(), "read from the standard input");
+        return activity_from_bytes(path, &bytes, options);
+    }
+
+    let size = fs::metadata(path)
+        .with_context(|| format!("opening {}", path.display()))?
+        .len();
+    // FIT files are much smaller than GPX ones, and always read whole.
+    let fit = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("fit"));
+    if size > STREAM_THRESHOLD_BYTES && !options.full_detail && !fit {
+        debug!(
+            bytes = size,
+            threshold = STREAM_THRESHOLD_BYTES,
+            "large file streamed, without the per-point detail"
+        );
+        return stream_activity(path);
+    }
+
+    let bytes = fs::read(path).with_context(|| format!("opening {}", path.display()))?;
+    activity_from_bytes(path, &bytes, options)
+}
+
+/// Read a stream to its end, failing once it goes past `limit` bytes.
+fn read_limited(reader: impl Read, limit: u64) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    reader
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut bytes)
+        .context("reading the standard input")?;
+    if bytes.len() as u64 > limit {
+        bail!("the standard input is larger than {limit} bytes");
+    }
+    Ok(bytes)
+}
+
+/// Log what was read from a file and what each step of the metrics did to
+/// it, for `--debug`.
+fn trace_activity(activity: &Activity, elapsed: Duration) {
+    if !tracing::enabled!(Level::DEBUG) {
+        return;
+    }
+    let filters = filter_report(&activity.points, &activity.tuning);
+    let unfiltered = Tuning {
+        elevation_threshold_m: 0.0,
+        jitter_m: 0.0,
+        max_speed_kmh: 0.0,
+        ..activity.tuning
+    };
+    debug!(
+        parse_ms = elapsed.as_secs_f64() * 1_000.0,
+        points = activity.points.len(),
+        duplicate_points = activity.duplicate_points,
+        implausible_timestamps = activity.implausible_timestamps,
+        jitter_dropped = filters.jitter,
+        spikes_dropped = filters.spikes,
+        distance_raw_m = filters.raw_m,
+        distance_filtered_m = filters.filtered_m,
+        distance_m = activity.distance_km * 1_000.0,
+        distance_source = ?activity.distance_source,
+        elevation_raw_m = tuned_elevation_gain(&activity.points, &unfiltered),
+        elevation_gain_m = activity.elevation_gain,
+        elevation_recorded_m = activity.raw_elevation_gain,
+        elevation_noise_m = activity.elevation_noise,
+        "file read"
+    );
+}
+
+#[cfg(feature = "http")]
+fn download(url: &Path, options: &LoadOptions) -> Result<Vec<u8>> {
+    crate::http::download(&url.to_string_lossy(), options.timeout)
+}
+
+#[cfg(not(feature = "http"))]
+fn download(url: &Path, _options: &LoadOptions) -> Result<Vec<u8>> {
+    bail!("{} is a URL, which needs the `http` feature", url.display())
+}
+
+/// Last path segment of a URL, without the query and the fragment.
+fn url_label(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(path)
+}
+
+fn activity_from_bytes(path: &Path, bytes: &[u8], options: &LoadOptions) -> Result<Activity> {
+    let mut activity = Activity::from_bytes(path.to_path_buf(), bytes)
+        .with_context(|| format!("parsing {}", path.display()))?;
+
+    if let Some(dem) = &options.dem
+        && !activity.points.is_empty()
+    {
+        match dem.correct(&mut activity.points) {
+            Ok(()) => {
+                activity.raw_elevation_gain = Some(activity.elevation_gain);
+                activity.recompute_elevation();
+            }
+            Err(err) => {
+                debug!(error = %err, "elevation kept, the DEM does not cover the track");
+                activity.warnings.push(err.to_string());
+            }
+        }
+    }
+
+    Ok(activity)
+}
+
+/// Load all files in parallel. The output order does not depend on thread
+/// scheduling: activities are sorted by start date and id, and among files
+/// sharing an id the first one in `paths` is kept.
+pub fn load_activities(paths: &[PathBuf], options: &LoadOptions) -> Result<Loaded> {
+    let activities = paths
+        .par_iter()
+        .map(|path| load_activity(path, options))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(keep_first(activities, Loaded::default(), &options.prefer))
+}
+
+/// Like [`load_activities`], but the files that cannot be read are left out
+/// and listed in [`Loaded::failed`] instead of failing the whole load.
+pub fn load_readable(paths: &[PathBuf], options: &LoadOptions) -> Loaded {
+    let results: Vec<Result<Activity>> = paths
+        .par_iter()
+        .map(|path| load_activity(path, options))
+        .collect();
+
+    let mut loaded = Loaded::default();
+    let mut activities = vec![];
+    for (path, result) in paths.iter().zip(results) {
+        match result {
+            Ok(activity) => activities.push(activity),
+            Err(err) => loaded.failed.push((path.clone(), err)),
+        }
+    }
+    keep_first(activities, loaded, &options.prefer)
+}
+
+/// Add the activities to `loaded`, sorted, without the duplicates, and of
+/// the files of the same ride in different formats the one preferred.
+fn keep_first(activities: Vec<Activity>, mut loaded: Loaded, prefer: &[Preference]) -> Loaded {
+    // rayon keeps the input order, so the first file of a set of duplicates
+    // is always the same one.
+    let mut seen = HashSet::new();
+    for mut activity in activities {
+        if let Some(kept) = loaded
+            .activities
+            .iter_mut()
+            .find(|kept| kept.is_alternate_of(&activity))
+        {
+            if is_preferred(&activity, kept, prefer) {
+                std::mem::swap(kept, &mut activity);
+                kept.alternates.append(&mut activity.alternates);
+            }
+            kept.alternates.push(activity.path);
+            kept.alternates.sort();
+            seen.insert(kept.id);
+        } else if !activity.has_points() || seen.insert(activity.id) {
+            // Files without any point cannot be told to be the same ride.
+            loaded.activities.push(activity);
+        } else {
+            loaded.duplicates.push(activity.path);
+        }
+    }
+
+    loaded
+        .activities
+        .sort_by_key(|activity| (activity.start, activity.id));
+
+    loaded
+}
+
+/// Load the files in parallel, calling `on_loaded` with each path and its
+/// activity (or error) as soon as it is ready, in no particular order.
+/// Duplicates are not removed.
+pub fn load_each(
+    paths: &[PathBuf],
+    options: &LoadOptions,
+    on_loaded: impl Fn(&Path, Result<Activity>) + Sync,
+) {
+    paths
+        .par_iter()
+        .for_each(|path| on_loaded(path, load_activity(path, options)));
+}
+
+/// Load the files on a background thread with [`load_each`], sending each
+/// activity (or error) over the returned channel, which closes once every
+/// file has been sent.
+pub fn spawn_loader(paths: Vec<PathBuf>, options: LoadOptions) -> Receiver<Result<Activity>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        load_each(&paths, &options, |_, result| {
+            // The receiver is gone when the app exits before the end.
+            let _ = sender.send(result);
+        });
+    });
+
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::FileFormat;
+
+    #[test]
+    fn only_http_arguments_are_urls() {
+        assert!(is_url(Path::new("https://example.com/ride.gpx")));
+        assert!(is_url(Path::new("http://example.com/ride.gpx")));
+        assert!(!is_url(Path::new("ftp://example.com/ride.gpx")));
+        assert!(!is_url(Path::new("rides/http:/ride.gpx")));
+        assert!(!is_url(Path::new("*.gpx")));
+    }
+
+    #[test]
+    fn unreadable_files_are_listed() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let paths: Vec<PathBuf> = ["mountain.gpx", "corrupt.gpx", "flat.gpx", "missing.gpx"]
+            .iter()
+            .map(|name| fixtures.join(name))
+            .collect();
+
+        let loaded = load_readable(&paths, &LoadOptions::default());
+        let names: Vec<&str> = loaded
+            .activities
+            .iter()
+            .map(|activity| activity.name.as_str())
+            .collect();
+        assert_eq!(names, ["Flat ride", "Col du Test"]);
+        let failed: Vec<&PathBuf> = loaded.failed.iter().map(|(path, _)| path).collect();
+        assert_eq!(failed, [&paths[1], &paths[3]]);
+        assert!(load_activities(&paths, &LoadOptions::default()).is_err());
+    }
+
+    #[test]
+    fn the_same_ride_in_two_formats_is_listed_once() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let (fit, gpx) = (
+            fixtures.join("paired_ride.fit"),
+            fixtures.join("paired_ride.gpx"),
+        );
+
+        // Whatever the order, the FIT file with power is kept.
+        for paths in [[gpx.clone(), fit.clone()], [fit.clone(), gpx.clone()]] {
+            let loaded = load_activities(&paths, &LoadOptions::default()).unwrap();
+            assert_eq!(loaded.activities.len(), 1);
+            assert!(loaded.duplicates.is_empty());
+            let activity = &loaded.activities[0];
+            assert_eq!(activity.path, fit);
+            assert_eq!(activity.format, FileFormat::Fit);
+            assert_eq!(activity.alternates, std::slice::from_ref(&gpx));
+        }
+
+        let options = LoadOptions {
+            prefer: vec![Preference::Gpx],
+            ..LoadOptions::default()
+        };
+        let loaded = load_activities(&[fit.clone(), gpx.clone()], &options).unwrap();
+        assert_eq!(loaded.activities[0].path, gpx);
+        assert_eq!(loaded.activities[0].alternates, [fit]);
+    }
+
+    #[test]
+    fn routes_without_a_track_are_told_apart() {
+        let dir = std::env::temp_dir().join(format!("cyclemetrics-routes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let route = |lat: f64| {
+            format!(
+                r#"<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+                <rte><rtept lat="{lat}" lon="5.0"/><rtept lat="{}" lon="5.0"/></rte>
+                </gpx>"#,
+                lat + 0.01
+            )
+        };
+        let paths = [
+            dir.join("north.gpx"),
+            dir.join("south.gpx"),
+            dir.join("empty.gpx"),
+        ];
+        fs::write(&paths[0], route(46.0)).unwrap();
+        fs::write(&paths[1], route(44.0)).unwrap();
+        fs::write(&paths[2], r#"<gpx version="1.1" creator="a"></gpx>"#).unwrap();
+        let empty = dir.join("empty_too.gpx");
+        fs::write(&empty, r#"<gpx version="1.1" creator="b"></gpx>"#).unwrap();
+
+        let mut all = paths.to_vec();
+        all.push(empty);
+        let loaded = load_activities(&all, &LoadOptions::default()).unwrap();
+        assert_eq!(loaded.activities.len(), 4);
+        assert!(loaded.duplicates.is_empty());
+        assert_ne!(loaded.activities[0].id, loaded.activities[1].id);
+
+        // A file without any point is never a duplicate, even of itself.
+        let twice = [paths[2].clone(), paths[2].clone()];
+        let loaded = load_activities(&twice, &LoadOptions::default()).unwrap();
+        assert_eq!(loaded.activities.len(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn overlapping_patterns_give_each_file_once() {
+        let dir = std::env::temp_dir().join(format!("cyclemetrics-resolve-{}", std::process::id()));
+        let rides = dir.join("rides");
+        fs::create_dir_all(rides.join("2024")).unwrap();
+        for path in [rides.join("2024/alps.gpx"), rides.join("home.gpx")] {
+            fs::write(path, "<gpx></gpx>").unwrap();
+        }
+        let mut duplicates = 2;
+        #[cfg(unix)]
+        {
+            let link = rides.join("link.gpx");
+            let _ = fs::remove_file(&link);
+            std::os::unix::fs::symlink(rides.join("2024/alps.gpx"), link).unwrap();
+            duplicates += 2;
+        }
+
+        let patterns: Vec<PathBuf> = ["rides/*.gpx", "rides/2024/*.gpx", "rides/**/*.gpx"]
+            .iter()
+            .map(|pattern| dir.join(pattern))
+            .collect();
+        let resolved = resolve_paths(&patterns).unwrap();
+        assert_eq!(
+            resolved.paths,
+            [rides.join("2024/alps.gpx"), rides.join("home.gpx")]
+        );
+        assert_eq!(resolved.duplicates.len(), duplicates, "{resolved:?}");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn standard_input_is_read_up_to_the_limit() {
+        assert!(is_stdin(Path::new("-")));
+        assert!(!is_stdin(Path::new("./-")));
+        let patterns = [PathBuf::from("-")];
+        assert_eq!(resolve_paths(&patterns).unwrap().paths, patterns);
+
+        let document = b"<gpx></gpx>".as_slice();
+        assert_eq!(read_limited(document, 11).unwrap(), document);
+        let err = read_limited(document, 10).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the standard input is larger than 10 bytes"
+        );
+    }
+
+    #[test]
+    fn url_labels() {
+        assert_eq!(url_label("https://example.com/a/ride.gpx"), "ride.gpx");
+        assert_eq!(
+            url_label("https://example.com/a/ride.gpx?dl=1#top"),
+            "ride.gpx"
+        );
+        assert_eq!(url_label("https://example.com/rides/42/"), "42");
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+
+/// A small cache keeping the `capacity` most recently used values, for data
+/// that is cheap to load again but too large to keep for every activity.
+///
+/// Entries are searched linearly, which is faster than hashing for the
+/// handful of entries it is meant for.
+#[derive(Debug)]
+pub struct Lru<K, V> {
+    capacity: usize,
+    /// Least recently used first.
+    entries: VecDeque<(K, V)>,
+}
+
+impl<K: PartialEq, V> Lru<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity + 1),
+        }
+    }
+
+    /// The value of `key`, which becomes the most recently used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(index)?;
+        self.entries.push_back(entry);
+        self.entries.back().map(|(_, value)| value)
+    }
+
+    /// The value of `key`, leaving the order unchanged.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Add or replace a value, evicting the least recently used one beyond
+    /// the capacity.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.entries.retain(|(k, _)| *k != key);
+        self.entries.push_back((key, value));
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        self.entries.retain(|(k, _)| k != key);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let mut lru = Lru::new(2);
+        lru.insert("a", 1);
+        lru.insert("b", 2);
+        assert_eq!(lru.get(&"a"), Some(&1));
+
+        // Peeking does not count as a use.
+        assert_eq!(lru.peek(&"b"), Some(&2));
+        lru.insert("c", 3);
+
+        assert_eq!(lru.peek(&"b"), None);
+        assert_eq!(lru.get(&"a"), Some(&1));
+        assert_eq!(lru.get(&"c"), Some(&3));
+    }
+
+    #[test]
+    fn replacing_a_value_keeps_one_entry() {
+        let mut lru = Lru::new(2);
+        lru.insert("a", 1);
+        lru.insert("b", 2);
+        lru.insert("a", 10);
+        lru.insert("c", 3);
+
+        assert_eq!(lru.get(&"a"), Some(&10));
+        assert_eq!(lru.peek(&"b"), None);
+        assert_eq!(lru.entries.len(), 2);
+
+        lru.clear();
+        assert_eq!(lru.get(&"c"), None);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut lru = Lru::new(0);
+        lru.insert("a", 1);
+        assert_eq!(lru.peek(&"a"), None);
+    }
+}
@@ -1,10 +1,27 @@
-use anyhow::Result;
 use clap::Parser;
-use cyclemetrics::{Args, run_cyclemetrics};
+use cyclemetrics::{Args, ExitStatus, run_cyclemetrics};
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
-    // Parse CLI arguments
-    let args = Args::parse();
+fn main() -> ExitCode {
+    // Parse CLI arguments; help and version are not errors.
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(err) => {
+            let _ = err.print();
+            return if err.use_stderr() {
+                ExitStatus::Usage.into()
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+    };
 
-    run_cyclemetrics(args)
+    match run_cyclemetrics(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            // Same report as returning the error from `main`.
+            eprintln!("Error: {:?}", failure.error);
+            failure.status.into()
+        }
+    }
 }
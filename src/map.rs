@@ -0,0 +1,111 @@
+use crate::activity::TrackPoint;
+
+/// Side of the map tiles, in pixels.
+pub const TILE_SIZE: f64 = 256.0;
+
+/// Zoom steps of the route map beyond the whole route, each halving the area
+/// shown.
+pub const MAX_MAP_ZOOM: u32 = 8;
+
+/// Share of the route bounds left around it.
+const MAP_MARGIN: f64 = 0.05;
+
+/// Web Mercator coordinates of a point, from (0, 0) in the north-west to
+/// (1, 1) in the south-east, as the map tiles use.
+pub fn mercator(lat: f64, lon: f64) -> (f64, f64) {
+    let lat = lat.clamp(-85.051_128, 85.051_128).to_radians();
+    (
+        (lon + 180.0) / 360.0,
+        (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / std::f64::consts::PI) / 2.0,
+    )
+}
+
+/// Part of the world shown by the route map, in [`mercator`] coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapView {
+    pub center: (f64, f64),
+    /// World units per pixel.
+    pub scale: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl MapView {
+    /// The whole route in `width` × `height` pixels, zoomed `zoom` times
+    /// around its middle. `None` without points.
+    pub fn fit(points: &[TrackPoint], width: f64, height: f64, zoom: u32) -> Option<Self> {
+        let mut world = points.iter().map(|point| mercator(point.lat, point.lon));
+        let first = world.next()?;
+        let (min, max) = world.fold((first, first), |(min, max), (x, y)| {
+            ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))
+        });
+        let span = ((max.0 - min.0).max(1e-9), (max.1 - min.1).max(1e-9));
+        let scale = (span.0 / width).max(span.1 / height) * (1.0 + 2.0 * MAP_MARGIN)
+            / f64::from(1u32 << zoom.min(MAX_MAP_ZOOM));
+        Some(Self {
+            center: ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0),
+            scale,
+            width,
+            height,
+        })
+    }
+
+    /// Pixel of a world point, from the top left corner.
+    pub fn pixel(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            (x - self.center.0) / self.scale + self.width / 2.0,
+            (y - self.center.1) / self.scale + self.height / 2.0,
+        )
+    }
+
+    /// World point of a pixel.
+    pub fn world(&self, (px, py): (f64, f64)) -> (f64, f64) {
+        (
+            (px - self.width / 2.0) * self.scale + self.center.0,
+            (py - self.height / 2.0) * self.scale + self.center.1,
+        )
+    }
+
+    /// Zoom level of the tiles closest to the scale of the view.
+    pub fn tile_zoom(&self) -> u8 {
+        (1.0 / (self.scale * TILE_SIZE))
+            .log2()
+            .round()
+            .clamp(0.0, 19.0) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mercator_matches_the_tiles() {
+        assert_eq!(mercator(0.0, 0.0), (0.5, 0.5));
+        let (x, y) = mercator(85.051_128, -180.0);
+        assert!(x.abs() < 1e-12 && y.abs() < 1e-6);
+    }
+
+    #[test]
+    fn the_view_fits_the_route_and_zooms_on_its_middle() {
+        let point = |lat, lon| TrackPoint {
+            lat,
+            lon,
+            ..Default::default()
+        };
+        let points = [point(45.0, 5.0), point(45.1, 5.2)];
+
+        let view = MapView::fit(&points, 200.0, 100.0, 0).unwrap();
+        let (west, north) = view.pixel(mercator(45.1, 5.0));
+        let (east, south) = view.pixel(mercator(45.0, 5.2));
+        assert!(west >= 0.0 && north >= 0.0 && east <= 200.0 && south <= 100.0);
+        assert!(west > 5.0 || north > 2.0);
+
+        let zoomed = MapView::fit(&points, 200.0, 100.0, 1).unwrap();
+        assert_eq!(zoomed.center, view.center);
+        assert_eq!(zoomed.scale * 2.0, view.scale);
+        assert_eq!(zoomed.tile_zoom(), view.tile_zoom() + 1);
+        let (x, y) = view.world(view.pixel((0.3, 0.4)));
+        assert!((x - 0.3).abs() < 1e-12 && (y - 0.4).abs() < 1e-12);
+    }
+}
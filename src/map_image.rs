@@ -0,0 +1,491 @@
+use anyhow::{Context, Result, bail, ensure};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::activity::TrackPoint;
+use crate::map::{MapView, mercator};
+
+/// Background where there is no tile.
+const BACKGROUND: [u8; 4] = [0xf2, 0xef, 0xe9, 0xff];
+const ROUTE_COLOR: [u8; 4] = [0xd6, 0x27, 0x28, 0xff];
+const START_COLOR: [u8; 4] = [0x2c, 0xa0, 0x2c, 0xff];
+/// Half the width of the route line, in pixels.
+const ROUTE_HALF_WIDTH: i64 = 1;
+const START_RADIUS: i64 = 4;
+
+/// Largest side of the images sent to the terminal, in pixels.
+pub const MAX_IMAGE_SIDE: u32 = 1600;
+
+/// Bytes of base64 per kitty escape sequence.
+const KITTY_CHUNK: usize = 4096;
+
+/// Removes every image kitty shows.
+pub const KITTY_DELETE: &str = "\x1b_Ga=d,q=2\x1b\\";
+
+/// Terminal graphics protocols the route map can be drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// kitty, and WezTerm and Ghostty which implement it.
+    Kitty,
+    Iterm2,
+}
+
+impl Protocol {
+    /// The protocol of the terminal, from the environment variables it sets;
+    /// `None` for the others, which get the braille map.
+    pub fn detect(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let term_program = var("TERM_PROGRAM").unwrap_or_default();
+        if var("KITTY_WINDOW_ID").is_some()
+            || var("TERM").is_some_and(|term| term == "xterm-kitty" || term == "xterm-ghostty")
+            || term_program == "WezTerm"
+            || term_program == "ghostty"
+        {
+            Some(Self::Kitty)
+        } else if term_program == "iTerm.app"
+            || var("LC_TERMINAL").is_some_and(|terminal| terminal == "iTerm2")
+        {
+            Some(Self::Iterm2)
+        } else {
+            None
+        }
+    }
+
+    /// Escape sequence drawing a PNG image over `columns` × `rows` cells from
+    /// the cursor, which is left where it is.
+    pub fn escape(self, png: &[u8], columns: u16, rows: u16) -> String {
+        let data = STANDARD.encode(png);
+        match self {
+            Self::Kitty => {
+                let chunks: Vec<&str> = data
+                    .as_bytes()
+                    .chunks(KITTY_CHUNK)
+                    // Base64 is ASCII.
+                    .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+                    .collect();
+                let mut escape = String::new();
+                for (i, chunk) in chunks.iter().enumerate() {
+                    let more = u8::from(i + 1 < chunks.len());
+                    if i == 0 {
+                        escape.push_str(&format!(
+                            "\x1b_Ga=T,f=100,q=2,C=1,c={columns},r={rows},m={more};{chunk}\x1b\\"
+                        ));
+                    } else {
+                        escape.push_str(&format!("\x1b_Gm={more};{chunk}\x1b\\"));
+                    }
+                }
+                escape
+            }
+            Self::Iterm2 => format!(
+                "\x1b]1337;File=inline=1;size={};width={columns};height={rows};\
+                 preserveAspectRatio=0;doNotMoveCursor=1:{data}\x07",
+                png.len()
+            ),
+        }
+    }
+}
+
+/// An RGBA image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    fn filled(width: u32, height: u32, color: [u8; 4]) -> Self {
+        Self {
+            width,
+            height,
+            pixels: color.repeat((width * height) as usize),
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    fn set(&mut self, x: i64, y: i64, color: [u8; 4]) {
+        if (0..i64::from(self.width)).contains(&x) && (0..i64::from(self.height)).contains(&y) {
+            let i = ((y as u32 * self.width + x as u32) * 4) as usize;
+            self.pixels[i..i + 4].copy_from_slice(&color);
+        }
+    }
+
+    fn disc(&mut self, (cx, cy): (i64, i64), radius: i64, color: [u8; 4]) {
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                if dx * dx + dy * dy <= radius * radius {
+                    self.set(cx + dx, cy + dy, color);
+                }
+            }
+        }
+    }
+
+    /// A line of discs from `from` to `to`.
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), color: [u8; 4]) {
+        let steps = (to.0 - from.0)
+            .abs()
+            .max((to.1 - from.1).abs())
+            .ceil()
+            .max(1.0);
+        // Lines far outside the image are only clipped.
+        if steps > 100_000.0 {
+            return;
+        }
+        for step in 0..=steps as i64 {
+            let t = step as f64 / steps;
+            let x = from.0 + (to.0 - from.0) * t;
+            let y = from.1 + (to.1 - from.1) * t;
+            self.disc(
+                (x.round() as i64, y.round() as i64),
+                ROUTE_HALF_WIDTH,
+                color,
+            );
+        }
+    }
+}
+
+/// The route drawn over the tiles of `tiles_dir`, laid out as
+/// `<zoom>/<x>/<y>.png`, or over a plain background; the start is a dot.
+pub fn route_image(
+    points: &[TrackPoint],
+    width: u32,
+    height: u32,
+    zoom: u32,
+    tiles_dir: Option<&Path>,
+) -> Option<Image> {
+    let view = MapView::fit(points, f64::from(width), f64::from(height), zoom)?;
+    let mut image = Image::filled(width, height, BACKGROUND);
+    if let Some(dir) = tiles_dir {
+        draw_tiles(&mut image, &view, dir);
+    }
+
+    let mut previous: Option<(f64, f64)> = None;
+    for point in points {
+        let pixel = view.pixel(mercator(point.lat, point.lon));
+        if let Some(previous) = previous.filter(|_| !point.segment_start) {
+            image.line(previous, pixel, ROUTE_COLOR);
+        }
+        previous = Some(pixel);
+    }
+    let start = view.pixel(mercator(points[0].lat, points[0].lon));
+    image.disc(
+        (start.0.round() as i64, start.1.round() as i64),
+        START_RADIUS,
+        START_COLOR,
+    );
+    Some(image)
+}
+
+/// Fill the image with the tiles closest to the scale of the view, scaled
+/// to it; missing and unreadable tiles leave the background.
+fn draw_tiles(image: &mut Image, view: &MapView, dir: &Path) {
+    let zoom = view.tile_zoom();
+    let tiles_per_side = f64::from(1u32 << zoom);
+    let mut tiles: HashMap<(i64, i64), Option<Image>> = HashMap::new();
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let (wx, wy) = view.world((f64::from(x) + 0.5, f64::from(y) + 0.5));
+            if !(0.0..1.0).contains(&wx) || !(0.0..1.0).contains(&wy) {
+                continue;
+            }
+            let (tx, ty) = (wx * tiles_per_side, wy * tiles_per_side);
+            let key = (tx as i64, ty as i64);
+            let tile = tiles.entry(key).or_insert_with(|| {
+                let path = dir.join(format!("{zoom}/{}/{}.png", key.0, key.1));
+                fs::read(path)
+                    .ok()
+                    .and_then(|bytes| decode_png(&bytes).ok())
+            });
+            if let Some(tile) = tile {
+                // Tiles of 512 pixels are scaled as those of 256.
+                let px = (tx.fract() * f64::from(tile.width)) as u32;
+                let py = (ty.fract() * f64::from(tile.height)) as u32;
+                let color = tile.pixel(px.min(tile.width - 1), py.min(tile.height - 1));
+                image.set(i64::from(x), i64::from(y), color);
+            }
+        }
+    }
+}
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// The image as an 8-bit RGBA PNG.
+pub fn encode_png(image: &Image) -> Vec<u8> {
+    let mut png = PNG_SIGNATURE.to_vec();
+    let mut header = vec![];
+    header.extend(image.width.to_be_bytes());
+    header.extend(image.height.to_be_bytes());
+    // Bit depth 8, RGBA, deflate, no filter method, no interlace.
+    header.extend([8, 6, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+
+    let mut encoder = ZlibEncoder::new(vec![], Compression::fast());
+    for row in image.pixels.chunks((image.width * 4) as usize) {
+        // Filter type 0, the row as is.
+        let _ = encoder.write_all(&[0]);
+        let _ = encoder.write_all(row);
+    }
+    let data = encoder.finish().unwrap_or_default();
+    write_chunk(&mut png, b"IDAT", &data);
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    png.extend(crc.finalize().to_be_bytes());
+}
+
+/// Decode a non-interlaced 8-bit PNG in grayscale, RGB, palette or with an
+/// alpha channel, as map tiles are.
+pub fn decode_png(bytes: &[u8]) -> Result<Image> {
+    let rest = bytes
+        .strip_prefix(&PNG_SIGNATURE)
+        .context("not a PNG file")?;
+    let mut chunks = vec![];
+    let mut rest = rest;
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into()?) as usize;
+        ensure!(rest.len() >= 12 + length, "truncated PNG chunk");
+        chunks.push((&rest[4..8], &rest[8..8 + length]));
+        rest = &rest[12 + length..];
+    }
+
+    let header = chunks
+        .iter()
+        .find(|(kind, _)| kind == b"IHDR")
+        .map(|(_, data)| *data)
+        .filter(|data| data.len() == 13)
+        .context("PNG without header")?;
+    let width = u32::from_be_bytes(header[0..4].try_into()?);
+    let height = u32::from_be_bytes(header[4..8].try_into()?);
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+    ensure!(width > 0 && height > 0, "empty PNG");
+    ensure!(
+        depth == 8 && interlace == 0,
+        "unsupported PNG: bit depth {depth}, interlaced {interlace}"
+    );
+    let channels = match color_type {
+        0 | 3 => 1,
+        4 => 2,
+        2 => 3,
+        6 => 4,
+        _ => bail!("unsupported PNG color type {color_type}"),
+    };
+    let chunk = |name: &[u8]| {
+        chunks
+            .iter()
+            .find(|(kind, _)| *kind == name)
+            .map(|(_, data)| *data)
+    };
+    let palette = chunk(b"PLTE").unwrap_or_default();
+    let transparency = chunk(b"tRNS").unwrap_or_default();
+
+    let compressed: Vec<u8> = chunks
+        .iter()
+        .filter(|(kind, _)| kind == b"IDAT")
+        .flat_map(|(_, data)| data.iter().copied())
+        .collect();
+    let mut data = vec![];
+    ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut data)?;
+
+    let stride = width as usize * channels;
+    ensure!(
+        data.len() >= (stride + 1) * height as usize,
+        "truncated PNG data"
+    );
+    let mut previous = vec![0u8; stride];
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    for row in data.chunks(stride + 1).take(height as usize) {
+        let (filter, row) = (row[0], &row[1..]);
+        let mut current = row.to_vec();
+        unfilter(filter, &mut current, &previous, channels)?;
+        for sample in current.chunks(channels) {
+            pixels.extend(match color_type {
+                0 => [sample[0], sample[0], sample[0], 0xff],
+                4 => [sample[0], sample[0], sample[0], sample[1]],
+                2 => [sample[0], sample[1], sample[2], 0xff],
+                6 => [sample[0], sample[1], sample[2], sample[3]],
+                _ => {
+                    let index = usize::from(sample[0]);
+                    let rgb = palette
+                        .get(index * 3..index * 3 + 3)
+                        .context("PNG palette index out of range")?;
+                    [
+                        rgb[0],
+                        rgb[1],
+                        rgb[2],
+                        transparency.get(index).copied().unwrap_or(0xff),
+                    ]
+                }
+            });
+        }
+        previous = current;
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Undo the filter of one row, given the previous one unfiltered.
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], channels: usize) -> Result<()> {
+    for i in 0..row.len() {
+        let left = if i >= channels { row[i - channels] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= channels {
+            previous[i - channels]
+        } else {
+            0
+        };
+        row[i] = row[i].wrapping_add(match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => bail!("unknown PNG filter {filter}"),
+        });
+    }
+    Ok(())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = (
+        (p - i16::from(a)).abs(),
+        (p - i16::from(b)).abs(),
+        (p - i16::from(c)).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route() -> Vec<TrackPoint> {
+        [(45.0, 5.0), (45.01, 5.0), (45.01, 5.02)]
+            .into_iter()
+            .map(|(lat, lon)| TrackPoint {
+                lat,
+                lon,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn terminals_are_detected_from_the_environment() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(
+            Protocol::detect(env(&[("TERM", "xterm-kitty")])),
+            Some(Protocol::Kitty)
+        );
+        assert_eq!(
+            Protocol::detect(env(&[("TERM_PROGRAM", "WezTerm")])),
+            Some(Protocol::Kitty)
+        );
+        assert_eq!(
+            Protocol::detect(env(&[("TERM_PROGRAM", "iTerm.app")])),
+            Some(Protocol::Iterm2)
+        );
+        assert_eq!(Protocol::detect(env(&[("TERM", "xterm-256color")])), None);
+    }
+
+    #[test]
+    fn kitty_images_are_sent_in_chunks() {
+        let escape = Protocol::Kitty.escape(&[0; 4000], 40, 10);
+
+        assert!(escape.starts_with("\x1b_Ga=T,f=100,q=2,C=1,c=40,r=10,m=1;AAAA"));
+        assert_eq!(escape.matches("\x1b\\").count(), 2);
+        assert!(escape.contains("\x1b\\\x1b_Gm=0;"));
+
+        let escape = Protocol::Iterm2.escape(b"png", 40, 10);
+        assert_eq!(
+            escape,
+            "\x1b]1337;File=inline=1;size=3;width=40;height=10;\
+             preserveAspectRatio=0;doNotMoveCursor=1:cG5n\x07"
+        );
+    }
+
+    #[test]
+    fn images_survive_a_png_round_trip() {
+        let image = route_image(&route(), 64, 48, 0, None).unwrap();
+
+        assert_eq!(decode_png(&encode_png(&image)).unwrap(), image);
+    }
+
+    #[test]
+    fn the_route_is_drawn_over_the_tiles() {
+        let points = route();
+        let view = MapView::fit(&points, 64.0, 48.0, 0).unwrap();
+        let zoom = view.tile_zoom();
+        let dir = std::env::temp_dir().join(format!("cyclemetrics-tiles-{}", std::process::id()));
+        // Every tile the map could use is plain blue.
+        let tiles = 1u32 << zoom;
+        let (x, y) = mercator(45.005, 5.01);
+        let (tx, ty) = ((x * f64::from(tiles)) as u32, (y * f64::from(tiles)) as u32);
+        let blue = encode_png(&Image::filled(256, 256, [0, 0, 0xff, 0xff]));
+        for tx in tx.saturating_sub(1)..=tx + 1 {
+            let tile_dir = dir.join(format!("{zoom}/{tx}"));
+            fs::create_dir_all(&tile_dir).unwrap();
+            for ty in ty.saturating_sub(1)..=ty + 1 {
+                fs::write(tile_dir.join(format!("{ty}.png")), &blue).unwrap();
+            }
+        }
+
+        let image = route_image(&points, 64, 48, 0, Some(&dir)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let start = view.pixel(mercator(45.0, 5.0));
+        assert_eq!(image.pixel(start.0 as u32, start.1 as u32), START_COLOR);
+        let corner = view.pixel(mercator(45.0, 5.02));
+        assert_eq!(
+            image.pixel(corner.0 as u32, corner.1 as u32),
+            [0, 0, 0xff, 0xff]
+        );
+        let turn = view.pixel(mercator(45.01, 5.0));
+        assert_eq!(image.pixel(turn.0 as u32, turn.1 as u32), ROUTE_COLOR);
+        assert!(
+            route_image(&points, 64, 48, 0, None)
+                .unwrap()
+                .pixels
+                .chunks(4)
+                .all(|pixel| pixel != [0, 0, 0xff, 0xff])
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+
+/// Distance over which the elevation is smoothed, in meters.
+pub const PEAK_WINDOW_M: f64 = 50.0;
+
+/// Points with an elevation always in the window, however far apart, so that
+/// a spike of a point or two is outvoted.
+const PEAK_MIN_POINTS: usize = 5;
+
+/// Steepest grade from the smoothed elevation to a point kept as it is; a
+/// steeper one is a spike of the altimeter, not a road.
+const PEAK_MAX_GRADE: f64 = 0.3;
+
+/// Highest point of a ride, on the smoothed elevation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Peak {
+    pub elevation_m: f64,
+    /// Along the ride from the start.
+    pub distance_km: f64,
+}
+
+/// Finds the highest point of a ride from its elevations, one at a time.
+///
+/// The elevation is smoothed by the median of the last [`PEAK_WINDOW_M`];
+/// the points of the window within a road's grade of it keep their own
+/// elevation, so that the top of a pass is not shaved while a spike of a
+/// barometric altimeter does not claim the peak. Points without an
+/// elevation are skipped: the distance given with the others still counts
+/// them.
+#[derive(Debug, Clone, Default)]
+pub struct PeakFinder {
+    /// Points of the window, as (distance in meters, elevation).
+    window: VecDeque<(f64, f64)>,
+    peak: Option<Peak>,
+}
+
+impl PeakFinder {
+    pub fn push(&mut self, distance_m: f64, elevation: f64) {
+        self.window.push_back((distance_m, elevation));
+        while self.window.len() > PEAK_MIN_POINTS && distance_m - self.window[1].0 >= PEAK_WINDOW_M
+        {
+            self.window.pop_front();
+        }
+        if self.window.len() >= PEAK_MIN_POINTS {
+            self.measure();
+        }
+    }
+
+    /// The highest point seen; tracks of fewer points than a window are
+    /// measured on those.
+    pub fn finish(mut self) -> Option<Peak> {
+        if self.peak.is_none() && !self.window.is_empty() {
+            self.measure();
+        }
+        self.peak
+    }
+
+    /// Keep the highest point of the window in line with its median.
+    fn measure(&mut self) {
+        let mut points: Vec<(f64, f64)> = self.window.iter().copied().collect();
+        points.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (median_m, median) = points[(points.len() - 1) / 2];
+        let highest = points
+            .iter()
+            .rev()
+            .find(|&&(distance_m, elevation)| {
+                elevation - median <= (distance_m - median_m).abs() * PEAK_MAX_GRADE
+            })
+            .copied()
+            .unwrap_or((median_m, median));
+        let (distance_m, elevation_m) = highest;
+        if self.peak.is_none_or(|peak| elevation_m > peak.elevation_m) {
+            self.peak = Some(Peak {
+                elevation_m,
+                distance_km: distance_m / 1_000.0,
+            });
+        }
+    }
+}
+
+/// The highest point of an elevation profile, as (distance in km,
+/// elevation); see [`PeakFinder`].
+pub fn highest_point(profile: &[(f64, f64)]) -> Option<Peak> {
+    let mut finder = PeakFinder::default();
+    for &(km, elevation) in profile {
+        finder.push(km * 1_000.0, elevation);
+    }
+    finder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A climb to 1,000 m at km 5, down again by km 10, a point every 50 m.
+    fn climb() -> Vec<(f64, f64)> {
+        (0..=200)
+            .map(|i| {
+                let km = f64::from(i) * 0.05;
+                (km, 1_000.0 - (km - 5.0).abs() * 100.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_peak_is_on_the_smoothed_elevation() {
+        let peak = highest_point(&climb()).unwrap();
+        assert!((peak.elevation_m - 1_000.0).abs() < 0.01, "{peak:?}");
+        assert!((peak.distance_km - 5.0).abs() < 0.01, "{peak:?}");
+
+        // Barometric spikes at km 8 claim nothing.
+        let mut profile = climb();
+        profile[160].1 = 2_500.0;
+        profile[161].1 = 1_100.0;
+        assert_eq!(highest_point(&profile), Some(peak));
+    }
+
+    #[test]
+    fn partial_and_short_profiles_have_a_peak() {
+        // Elevation lost in the middle of the climb: the distance goes on.
+        let profile: Vec<_> = climb()
+            .into_iter()
+            .filter(|&(km, _)| !(2.0..5.5).contains(&km))
+            .collect();
+        let peak = highest_point(&profile).unwrap();
+        assert!(peak.elevation_m > 900.0, "{peak:?}");
+        assert!((5.5..6.0).contains(&peak.distance_km), "{peak:?}");
+
+        let peak = highest_point(&[(0.0, 120.0), (1.0, 180.0)]).unwrap();
+        assert_eq!(peak.elevation_m, 180.0);
+        assert_eq!(highest_point(&[]), None);
+    }
+}
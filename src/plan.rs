@@ -0,0 +1,245 @@
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+
+use crate::activity::TrackPoint;
+use crate::brevet::{ControlTime, control_window};
+use crate::climbs::{Climb, detect_climbs};
+use crate::gpx::GradeModel;
+use crate::stats::grade_adjustments;
+use crate::tuning::{SegmentJoins, Tuning};
+
+/// Speed assumed on the flat by the ride plans, in km/h.
+pub const DEFAULT_PLAN_SPEED_KMH: f64 = 25.0;
+
+/// Distance between two bottle stops of the ride plans, in kilometers.
+pub const DEFAULT_STOP_EVERY_KM: f64 = 20.0;
+
+/// A climb of a planned ride, with when it is reached.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedClimb {
+    pub climb: Climb,
+    /// Distance from the start to the bottom, in kilometers.
+    pub distance_km: f64,
+    /// Time from the start to the bottom and to the top, in seconds.
+    pub arrival_seconds: f64,
+    pub top_seconds: f64,
+}
+
+/// Times of a ride that is yet to be ridden.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RidePlan {
+    pub speed_kmh: f64,
+    pub distance_km: f64,
+    pub total_seconds: f64,
+    pub climbs: Vec<PlannedClimb>,
+    /// Bottle stops, as the distance from the start in kilometers and the
+    /// time to get there in seconds.
+    pub stops: Vec<(f64, f64)>,
+    /// Brevet controls, with the time to get there; those past the finish
+    /// are not reached.
+    pub controls: Vec<ControlTime>,
+}
+
+/// Whether points are a route or a planned track rather than a recording:
+/// none has a timestamp.
+pub fn is_planned(points: &[TrackPoint]) -> bool {
+    points.len() > 1 && points.iter().all(|point| point.time.is_none())
+}
+
+/// Plan a ride at `speed_kmh` on the flat, slower uphill and faster downhill
+/// as the grade-adjusted distance weighs the grades, with a stop every
+/// `stop_every_km`, and when each of the `controls` is reached. Gaps between
+/// segments are ridden.
+pub fn plan_ride(
+    points: &[TrackPoint],
+    speed_kmh: f64,
+    stop_every_km: f64,
+    controls: &[f64],
+    model: GradeModel,
+) -> RidePlan {
+    let along = distances_m(points);
+    let distance_m = along.last().copied().unwrap_or_default();
+    let clock = Clock::new(points, model, speed_kmh);
+
+    let mut climbs = vec![];
+    let mut from = 0;
+    let ridden = Tuning {
+        segment_joins: SegmentJoins::Count,
+        ..Tuning::default()
+    };
+    for climb in detect_climbs(points, &ridden) {
+        let position = |start: usize, (lat, lon): (f64, f64)| {
+            (start..points.len()).find(|&i| points[i].lat == lat && points[i].lon == lon)
+        };
+        let Some(bottom) = position(from, climb.start) else {
+            continue;
+        };
+        let top = position(bottom, climb.end).unwrap_or(points.len() - 1);
+        from = top;
+        climbs.push(PlannedClimb {
+            distance_km: along[bottom] / 1_000.0,
+            arrival_seconds: clock.seconds_at(along[bottom]),
+            top_seconds: clock.seconds_at(along[top]),
+            climb,
+        });
+    }
+
+    let stops = if stop_every_km > 0.0 {
+        (1..)
+            .map(|n| n as f64 * stop_every_km)
+            .take_while(|&km| km * 1_000.0 < distance_m)
+            .map(|km| (km, clock.seconds_at(km * 1_000.0)))
+            .collect()
+    } else {
+        vec![]
+    };
+    let controls = controls
+        .iter()
+        .enumerate()
+        .map(|(i, &km)| ControlTime {
+            distance_km: km,
+            window: control_window(km, i + 1 == controls.len()),
+            reached_seconds: (km * 1_000.0 <= distance_m).then(|| clock.seconds_at(km * 1_000.0)),
+        })
+        .collect();
+
+    RidePlan {
+        speed_kmh,
+        distance_km: distance_m / 1_000.0,
+        total_seconds: clock.seconds_at(distance_m),
+        climbs,
+        stops,
+        controls,
+    }
+}
+
+/// Distance from the start at each point, in meters.
+fn distances_m(points: &[TrackPoint]) -> Vec<f64> {
+    let mut total = 0.0;
+    let mut previous: Option<&TrackPoint> = None;
+    points
+        .iter()
+        .map(|point| {
+            if let Some(previous) = previous {
+                total += Haversine.distance(
+                    point!(x: previous.lon, y: previous.lat),
+                    point!(x: point.lon, y: point.lat),
+                );
+            }
+            previous = Some(point);
+            total
+        })
+        .collect()
+}
+
+/// Time to reach a distance along the ride: the grade-adjusted distance so
+/// far at the flat speed.
+struct Clock {
+    /// End of each graded section from the start, and the grade-adjusted
+    /// meters added up to there.
+    sections: Vec<(f64, f64)>,
+    speed_m_s: f64,
+}
+
+impl Clock {
+    fn new(points: &[TrackPoint], model: GradeModel, speed_kmh: f64) -> Self {
+        let mut end = 0.0;
+        let mut extra = 0.0;
+        let sections = grade_adjustments(points, model)
+            .map(|(length_m, extra_m)| {
+                end += length_m;
+                extra += extra_m;
+                (end, extra)
+            })
+            .collect();
+        Self {
+            sections,
+            speed_m_s: speed_kmh / 3.6,
+        }
+    }
+
+    fn seconds_at(&self, distance_m: f64) -> f64 {
+        // Within a section the extra meters grow evenly; after the last
+        // one, the remaining stretch counts as flat.
+        let mut previous = (0.0, 0.0);
+        let mut extra_m = 0.0;
+        for &(end, extra) in &self.sections {
+            if distance_m <= end {
+                let share = (distance_m - previous.0) / (end - previous.0);
+                extra_m = previous.1 + (extra - previous.1) * share;
+                break;
+            }
+            previous = (end, extra);
+            extra_m = extra;
+        }
+        (distance_m + extra_m) / self.speed_m_s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points going north every 100 m, at the given elevations.
+    fn route(elevations: &[f64]) -> Vec<TrackPoint> {
+        elevations
+            .iter()
+            .enumerate()
+            .map(|(i, &elevation)| TrackPoint {
+                lat: 45.0 + i as f64 * 100.0 / 111_195.08,
+                lon: 5.0,
+                elevation: Some(elevation),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flat_routes_go_at_the_flat_speed() {
+        let points = route(&[100.0; 301]);
+
+        let plan = plan_ride(&points, 30.0, 12.0, &[15.0, 45.0], GradeModel::Piecewise);
+
+        assert!((plan.distance_km - 30.0).abs() < 0.001);
+        assert!((plan.total_seconds - 3_600.0).abs() < 0.5);
+        assert_eq!(plan.stops.len(), 2);
+        assert!((plan.stops[1].1 - 2_880.0).abs() < 0.5);
+        let arrivals: Vec<Option<f64>> = plan
+            .controls
+            .iter()
+            .map(|control| control.reached_seconds.map(f64::round))
+            .collect();
+        assert_eq!(arrivals, [Some(1_800.0), None]);
+        assert!(plan.climbs.is_empty());
+    }
+
+    #[test]
+    fn climbs_take_as_long_as_their_adjusted_distance() {
+        // 2 km flat, 2 km at 5%, 2 km flat.
+        let elevations: Vec<f64> = (0..=60)
+            .map(|i| 100.0 + (i.clamp(20, 40) - 20) as f64 * 5.0)
+            .collect();
+        let points = route(&elevations);
+
+        let plan = plan_ride(&points, 25.0, 0.0, &[], GradeModel::Piecewise);
+
+        // The climb counts 1.5 times: 7 flat kilometers at 25 km/h.
+        assert!((plan.total_seconds - 7.0 / 25.0 * 3_600.0).abs() < 1.0);
+        let [climb] = &plan.climbs[..] else {
+            panic!("{:?}", plan.climbs)
+        };
+        assert!((climb.distance_km - 2.0).abs() < 0.001);
+        assert!((climb.arrival_seconds - 288.0).abs() < 0.5);
+        assert!((climb.top_seconds - 720.0).abs() < 0.5);
+        assert!(plan.stops.is_empty());
+    }
+
+    #[test]
+    fn recordings_are_not_planned() {
+        let mut points = route(&[100.0; 3]);
+        assert!(is_planned(&points));
+        points[1].time =
+            Some(chrono::DateTime::parse_from_rfc3339("2024-06-01T07:00:00Z").unwrap());
+        assert!(!is_planned(&points));
+    }
+}
@@ -0,0 +1,235 @@
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+use serde::Deserialize;
+use std::cmp::Reverse;
+
+use crate::activity::{Activity, TrackPoint};
+use crate::stats::cluster_points;
+
+/// Start and end points within this of one another are the same place, in
+/// meters.
+pub const EXPOSURE_RADIUS_M: f64 = 200.0;
+
+/// A place is given away by exported geometry when activities start or end
+/// there with more than this many others, by default.
+pub const EXPOSURE_MAX_OTHERS: usize = 2;
+
+/// A circle around a private place, such as home, whose points are left out
+/// of exported geometry.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrivacyZone {
+    pub lat: f64,
+    pub lon: f64,
+    pub radius_m: f64,
+}
+
+impl PrivacyZone {
+    pub fn contains(&self, point: &TrackPoint) -> bool {
+        Haversine.distance(
+            point!(x: point.lon, y: point.lat),
+            point!(x: self.lon, y: self.lat),
+        ) <= self.radius_m
+    }
+}
+
+/// What is left of a track once the private points are taken out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stripped<'a> {
+    /// Runs of consecutive public points, with at least two points each.
+    pub segments: Vec<&'a [TrackPoint]>,
+    /// Points inside a zone, plus lone points between two zones, which
+    /// cannot be drawn as a line.
+    pub removed: usize,
+}
+
+/// Split the track around the points inside any of the zones, so no line is
+/// drawn across a zone.
+pub fn strip_private<'a>(points: &'a [TrackPoint], zones: &[PrivacyZone]) -> Stripped<'a> {
+    let segments: Vec<&[TrackPoint]> = points
+        .split(|point| zones.iter().any(|zone| zone.contains(point)))
+        .filter(|run| run.len() >= 2)
+        .collect();
+    let kept: usize = segments.iter().map(|segment| segment.len()).sum();
+    Stripped {
+        segments,
+        removed: points.len() - kept,
+    }
+}
+
+/// A place outside of the privacy zones where many activities start or end,
+/// such as home.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExposedPlace {
+    /// As (latitude, longitude).
+    pub center: (f64, f64),
+    /// Indices of the activities starting or ending there, in their order.
+    pub activities: Vec<usize>,
+}
+
+/// The places where an activity starts or ends along with more than
+/// `max_others` other activities, most visited first. Points inside a zone
+/// are left out, as are virtual rides.
+pub fn exposed_places(
+    activities: &[Activity],
+    zones: &[PrivacyZone],
+    max_others: usize,
+) -> Vec<ExposedPlace> {
+    let public = |&(lat, lon): &(f64, f64)| {
+        let point = TrackPoint {
+            lat,
+            lon,
+            ..Default::default()
+        };
+        !zones.iter().any(|zone| zone.contains(&point))
+    };
+    let (owners, points): (Vec<usize>, Vec<(f64, f64)>) = activities
+        .iter()
+        .enumerate()
+        .filter(|(_, activity)| !activity.virtual_ride)
+        .flat_map(|(i, activity)| {
+            [activity.start_point, activity.end_point]
+                .into_iter()
+                .flatten()
+                .filter(public)
+                .map(move |point| (i, point))
+        })
+        .unzip();
+
+    let mut places: Vec<ExposedPlace> = cluster_points(points, EXPOSURE_RADIUS_M)
+        .into_iter()
+        .filter_map(|cluster| {
+            let mut activities: Vec<usize> = cluster.members.iter().map(|&j| owners[j]).collect();
+            // A ride starting and ending there counts once.
+            activities.dedup();
+            (activities.len() > max_others + 1).then_some(ExposedPlace {
+                center: cluster.center,
+                activities,
+            })
+        })
+        .collect();
+    places.sort_by_key(|place| Reverse(place.activities.len()));
+    places
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(lat: f64, lon: f64) -> TrackPoint {
+        TrackPoint {
+            lat,
+            lon,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn zones_are_circles_on_the_ground() {
+        let zone = PrivacyZone {
+            lat: 60.0,
+            lon: 5.0,
+            radius_m: 80.0,
+        };
+        // 0.0012° is ~67 m of longitude at 60°N, but ~134 m of latitude.
+        assert!(zone.contains(&point(60.0, 5.0012)));
+        assert!(!zone.contains(&point(60.0012, 5.0)));
+    }
+
+    #[test]
+    fn tracks_are_split_around_zones() {
+        let zone = PrivacyZone {
+            lat: 45.0,
+            lon: 5.0,
+            radius_m: 150.0,
+        };
+        // One point every ~111 m, leaving home, riding through it, and back.
+        let points: Vec<TrackPoint> = [0.0, 0.001, 0.002, 0.003, 0.004, 0.0, 0.002, 0.003, 0.0]
+            .iter()
+            .map(|&delta| point(45.0 + delta, 5.0))
+            .collect();
+
+        let stripped = strip_private(&points, &[zone]);
+
+        assert_eq!(stripped.segments, [&points[2..5], &points[6..8]]);
+        assert_eq!(stripped.removed, 4);
+        assert_eq!(strip_private(&points, &[]).segments, [&points[..]]);
+    }
+
+    #[test]
+    fn places_of_many_rides_are_exposed() {
+        let ride = |start: (f64, f64), end: (f64, f64)| Activity {
+            start_point: Some(start),
+            end_point: Some(end),
+            ..Default::default()
+        };
+        let home = (45.0, 5.0);
+        // ~100 m from home, and far away.
+        let door = (45.0009, 5.0);
+        let work = (45.1, 5.1);
+        let activities = [
+            ride(home, home),
+            ride(door, work),
+            ride(work, door),
+            ride((46.0, 6.0), (46.1, 6.1)),
+            Activity {
+                virtual_ride: true,
+                ..ride(home, home)
+            },
+        ];
+
+        // Three rides at home, each with two others.
+        let places = exposed_places(&activities, &[], 1);
+        assert_eq!(places.len(), 1, "{places:?}");
+        assert_eq!(places[0].activities, [0, 1, 2]);
+        assert!((places[0].center.0 - 45.00045).abs() < 1e-9, "{places:?}");
+        assert_eq!(exposed_places(&activities, &[], 2), []);
+
+        // Two rides at work.
+        let places = exposed_places(&activities, &[], 0);
+        assert_eq!(places.len(), 2, "{places:?}");
+        assert_eq!(places[1].activities, [1, 2]);
+
+        let zone = PrivacyZone {
+            lat: 45.0,
+            lon: 5.0,
+            radius_m: 300.0,
+        };
+        assert_eq!(exposed_places(&activities, &[zone], 1), []);
+    }
+
+    #[test]
+    fn thousands_of_rides_are_clustered() {
+        // Rides from 50 homes, each on its own 5 km grid cell.
+        let activities: Vec<Activity> = (0..5_000)
+            .map(|i| {
+                let home = (45.0 + f64::from(i % 50) * 0.05, 5.0);
+                Activity {
+                    start_point: Some(home),
+                    end_point: Some((home.0 + 0.01, 5.01)),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        let places = exposed_places(&activities, &[], 2);
+
+        assert_eq!(places.len(), 100);
+        assert!(places.iter().all(|place| place.activities.len() == 100));
+    }
+
+    #[test]
+    fn tracks_inside_a_zone_leave_nothing() {
+        let zone = PrivacyZone {
+            lat: 45.0,
+            lon: 5.0,
+            radius_m: 500.0,
+        };
+        let points = [point(45.0, 5.0), point(45.001, 5.0), point(45.0, 5.001)];
+
+        let stripped = strip_private(&points, &[zone]);
+
+        assert!(stripped.segments.is_empty());
+        assert_eq!(stripped.removed, 3);
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::activity::Activity;
+use crate::stats::{Summary, device_usage};
+use crate::storage::write_atomic;
+
+/// Write the totals of the activities as a node_exporter textfile. The file
+/// is written next to `path` and renamed over it, so that a scrape never
+/// reads half of it.
+pub fn export_prometheus(activities: &[Activity], path: &Path) -> Result<()> {
+    // node_exporter only reads `*.prom` files, not the temporary one.
+    write_atomic(path, prometheus_textfile(activities).as_bytes())
+}
+
+/// Totals of the activities started in one month.
+#[derive(Debug, Default)]
+struct Month {
+    activities: usize,
+    distance_m: f64,
+    elevation_m: f64,
+}
+
+/// The metrics in the Prometheus text format: overall totals, totals per
+/// month and device, and the start of the last activity.
+pub fn prometheus_textfile(activities: &[Activity]) -> String {
+    let mut months: BTreeMap<String, Month> = BTreeMap::new();
+    for activity in activities {
+        if let Some(start) = activity.start {
+            let month = months.entry(start.format("%Y-%m").to_string()).or_default();
+            month.activities += 1;
+            month.distance_m += activity.distance_km * 1_000.0;
+            month.elevation_m += activity.elevation_gain;
+        }
+    }
+
+    let summary = Summary::new(activities);
+    let mut text = String::new();
+    gauge(
+        &mut text,
+        "activity_count",
+        "Number of activities.",
+        [(None, summary.rides() as f64)],
+    );
+    gauge(
+        &mut text,
+        "total_distance_meters",
+        "Distance of every activity.",
+        [(None, summary.total_distance_km() * 1_000.0)],
+    );
+    gauge(
+        &mut text,
+        "total_elevation_meters",
+        "Elevation gain of every activity.",
+        [(None, summary.total_elevation_gain())],
+    );
+    if let Some(last) = activities.iter().filter_map(|a| a.start).max() {
+        gauge(
+            &mut text,
+            "last_activity_timestamp_seconds",
+            "Start of the last activity, in seconds since the epoch.",
+            [(None, last.timestamp() as f64)],
+        );
+    }
+
+    let by_month = |value: fn(&Month) -> f64| {
+        months
+            .iter()
+            .map(move |(month, totals)| (Some(("month", month.as_str())), value(totals)))
+    };
+    gauge(
+        &mut text,
+        "month_activity_count",
+        "Number of activities started in the month.",
+        by_month(|month| month.activities as f64),
+    );
+    gauge(
+        &mut text,
+        "month_distance_meters",
+        "Distance of the activities started in the month.",
+        by_month(|month| month.distance_m),
+    );
+    gauge(
+        &mut text,
+        "month_elevation_meters",
+        "Elevation gain of the activities started in the month.",
+        by_month(|month| month.elevation_m),
+    );
+
+    let devices = device_usage(activities);
+    gauge(
+        &mut text,
+        "device_distance_meters",
+        "Distance recorded by each device.",
+        devices.iter().map(|usage| {
+            (
+                Some(("device", usage.device.as_str())),
+                usage.distance_km * 1_000.0,
+            )
+        }),
+    );
+    text
+}
+
+/// A gauge with its `HELP` and `TYPE` lines, and one sample per label value;
+/// nothing when there are no samples.
+fn gauge<'a>(
+    text: &mut String,
+    name: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (Option<(&'a str, &'a str)>, f64)>,
+) {
+    let mut samples = samples.into_iter().peekable();
+    if samples.peek().is_none() {
+        return;
+    }
+    let _ = writeln!(text, "# HELP cyclemetrics_{name} {help}");
+    let _ = writeln!(text, "# TYPE cyclemetrics_{name} gauge");
+    for (label, value) in samples {
+        // An empty sum is -0.
+        let value = value + 0.0;
+        match label {
+            Some((label, label_value)) => {
+                let _ = writeln!(
+                    text,
+                    "cyclemetrics_{name}{{{label}=\"{}\"}} {value}",
+                    escape_label(label_value)
+                );
+            }
+            None => {
+                let _ = writeln!(text, "cyclemetrics_{name} {value}");
+            }
+        }
+    }
+}
+
+/// A label value with its backslashes, double quotes and line feeds escaped.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(
+            escape_label("Edge \"530\"\\Pro\nv2"),
+            "Edge \\\"530\\\"\\\\Pro\\nv2"
+        );
+    }
+
+    #[test]
+    fn months_and_devices_are_labeled() {
+        let ride = |start: &str, distance_km, creator: &str| Activity {
+            start: Some(chrono::DateTime::parse_from_rfc3339(start).unwrap()),
+            distance_km,
+            elevation_gain: 100.0,
+            creator: Some(creator.to_string()),
+            ..Default::default()
+        };
+        let activities = [
+            ride("2024-06-01T07:00:00Z", 20.5, "Edge"),
+            ride("2024-06-30T07:00:00Z", 10.0, "Edge"),
+            ride("2024-07-01T07:00:00Z", 30.0, "Wahoo \"Bolt\""),
+        ];
+
+        let text = prometheus_textfile(&activities);
+
+        assert!(text.starts_with(
+            "# HELP cyclemetrics_activity_count Number of activities.\n\
+             # TYPE cyclemetrics_activity_count gauge\n\
+             cyclemetrics_activity_count 3\n"
+        ));
+        assert!(text.contains("\ncyclemetrics_total_distance_meters 60500\n"));
+        assert!(text.contains("\ncyclemetrics_last_activity_timestamp_seconds 1719817200\n"));
+        assert!(text.contains("\ncyclemetrics_month_distance_meters{month=\"2024-06\"} 30500\n"));
+        assert!(text.contains("\ncyclemetrics_month_activity_count{month=\"2024-07\"} 1\n"));
+        assert!(text.contains(
+            "\ncyclemetrics_device_distance_meters{device=\"Wahoo \\\"Bolt\\\"\"} 30000\n"
+        ));
+    }
+
+    #[test]
+    fn no_activities_only_count_zero() {
+        assert_eq!(
+            prometheus_textfile(&[]),
+            "# HELP cyclemetrics_activity_count Number of activities.\n\
+             # TYPE cyclemetrics_activity_count gauge\n\
+             cyclemetrics_activity_count 0\n\
+             # HELP cyclemetrics_total_distance_meters Distance of every activity.\n\
+             # TYPE cyclemetrics_total_distance_meters gauge\n\
+             cyclemetrics_total_distance_meters 0\n\
+             # HELP cyclemetrics_total_elevation_meters Elevation gain of every activity.\n\
+             # TYPE cyclemetrics_total_elevation_meters gauge\n\
+             cyclemetrics_total_elevation_meters 0\n"
+        );
+    }
+}
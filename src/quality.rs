@@ -0,0 +1,462 @@
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+use std::fmt;
+
+use crate::activity::{Activity, MOVING_MIN_SPEED, TrackPoint};
+use crate::tuning::distance_m;
+
+/// Faster than this between two points is a GPS glitch, in km/h.
+const MAX_PLAUSIBLE_SPEED_KMH: f64 = 120.0;
+
+/// Points of the [quality score](quality_score) that missing, out-of-order
+/// or ignored timestamps can take off, out of 100: the speeds and times
+/// rest on them.
+pub const TIMESTAMPS_WEIGHT: f64 = 30.0;
+/// Points that a missing elevation takes off; a GPS-derived one takes off
+/// half.
+pub const ELEVATION_WEIGHT: f64 = 20.0;
+/// Points that positions wandering back and forth can take off.
+pub const JITTER_WEIGHT: f64 = 20.0;
+/// Points that recording gaps while moving can take off.
+pub const GAPS_WEIGHT: f64 = 15.0;
+/// Points that duplicate points can take off.
+pub const DUPLICATES_WEIGHT: f64 = 15.0;
+
+/// Share of the points, or of the distance for the gaps, from which a
+/// problem takes its whole weight off; less takes off in proportion.
+pub const FULL_PENALTY_SHARE: f64 = 0.1;
+
+/// A point turning back by more than this from the way it came, in
+/// degrees, over legs shorter than [`JITTER_LEG_M`], is GPS jitter.
+pub const JITTER_TURN_DEG: f64 = 120.0;
+pub const JITTER_LEG_M: f64 = 15.0;
+
+/// Intervals between two points this many times longer than the usual one,
+/// covered while moving, are gaps in the recording such as tunnels.
+pub const GAP_FACTOR: f64 = 3.0;
+
+/// A data-quality problem of an activity.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    NoPoints,
+    NoTimestamps,
+    NoElevation,
+    /// Points whose timestamp is before the previous one.
+    OutOfOrder(usize),
+    /// Timestamps ignored, such as the 1970 ones of a clock reset; see
+    /// [`crate::gpx::is_plausible_time`].
+    ImplausibleTimestamps(usize),
+    /// Points removed as copies of the previous one.
+    DuplicatePoints(usize),
+    /// Fastest implausible speed between two points, in km/h.
+    SuspiciousSpeed(f64),
+    /// Elevation that looks GPS-derived.
+    NoisyElevation,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::NoPoints => write!(f, "no track points"),
+            Issue::NoTimestamps => write!(f, "no timestamps"),
+            Issue::NoElevation => write!(f, "no elevation"),
+            Issue::OutOfOrder(count) => write!(f, "{count} out-of-order points"),
+            Issue::ImplausibleTimestamps(count) => {
+                write!(f, "{count} implausible timestamps ignored")
+            }
+            Issue::DuplicatePoints(count) => write!(f, "{count} duplicate points removed"),
+            Issue::SuspiciousSpeed(speed) => write!(f, "suspicious speed of {speed:.0} km/h"),
+            Issue::NoisyElevation => write!(f, "low quality elevation"),
+        }
+    }
+}
+
+/// Every issue found in an activity, in the order of [`Issue`].
+///
+/// Checks needing points only run when there are some.
+pub fn issues(activity: &Activity) -> Vec<Issue> {
+    let points = &activity.points;
+    if points.is_empty() {
+        return vec![Issue::NoPoints];
+    }
+
+    let mut issues = vec![];
+    if !has_timestamps(points) {
+        issues.push(Issue::NoTimestamps);
+    }
+    if !has_elevation(points) {
+        issues.push(Issue::NoElevation);
+    }
+    issues.extend(out_of_order(points).map(Issue::OutOfOrder));
+    if activity.implausible_timestamps > 0 {
+        issues.push(Issue::ImplausibleTimestamps(
+            activity.implausible_timestamps,
+        ));
+    }
+    if activity.duplicate_points > 0 {
+        issues.push(Issue::DuplicatePoints(activity.duplicate_points));
+    }
+    issues.extend(suspicious_speed(points).map(Issue::SuspiciousSpeed));
+    if activity.has_low_quality_elevation() {
+        issues.push(Issue::NoisyElevation);
+    }
+
+    issues
+}
+
+pub fn has_timestamps(points: &[TrackPoint]) -> bool {
+    points.iter().any(|point| point.time.is_some())
+}
+
+pub fn has_elevation(points: &[TrackPoint]) -> bool {
+    points.iter().any(|point| point.elevation.is_some())
+}
+
+/// Number of timed points earlier than the previous timed point.
+pub fn out_of_order(points: &[TrackPoint]) -> Option<usize> {
+    let times: Vec<_> = points.iter().filter_map(|point| point.time).collect();
+    let count = times.windows(2).filter(|pair| pair[1] < pair[0]).count();
+    (count > 0).then_some(count)
+}
+
+/// Highest speed between two consecutive timed points above
+/// [`MAX_PLAUSIBLE_SPEED_KMH`], in km/h.
+pub fn suspicious_speed(points: &[TrackPoint]) -> Option<f64> {
+    points
+        .windows(2)
+        .filter_map(|pair| {
+            let seconds = (pair[1].time? - pair[0].time?).as_seconds_f64();
+            let meters = Haversine.distance(
+                point!(x: pair[0].lon, y: pair[0].lat),
+                point!(x: pair[1].lon, y: pair[1].lat),
+            );
+            (seconds > 0.0).then(|| meters / seconds * 3.6)
+        })
+        .filter(|&speed| speed > MAX_PLAUSIBLE_SPEED_KMH)
+        .max_by(f64::total_cmp)
+}
+
+/// A part of the [quality score](quality_score), with the weight it can
+/// take off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    Timestamps,
+    Elevation,
+    Jitter,
+    Gaps,
+    Duplicates,
+}
+
+impl Problem {
+    pub fn weight(self) -> f64 {
+        match self {
+            Problem::Timestamps => TIMESTAMPS_WEIGHT,
+            Problem::Elevation => ELEVATION_WEIGHT,
+            Problem::Jitter => JITTER_WEIGHT,
+            Problem::Gaps => GAPS_WEIGHT,
+            Problem::Duplicates => DUPLICATES_WEIGHT,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Problem::Timestamps => "timestamps",
+            Problem::Elevation => "elevation",
+            Problem::Jitter => "GPS jitter",
+            Problem::Gaps => "gaps",
+            Problem::Duplicates => "duplicate points",
+        }
+    }
+}
+
+/// How trustworthy the data of an activity is, from 0 to 100.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityScore {
+    /// Points taken off by each problem found, the most first.
+    pub penalties: Vec<(Problem, f64)>,
+    /// Points found to be GPS jitter, see [`jitter_points`].
+    pub jitter_points: usize,
+    /// Distance crossed without points, see [`gap_distance_m`], in km.
+    pub gap_km: f64,
+}
+
+impl QualityScore {
+    pub fn score(&self) -> u8 {
+        let lost: f64 = self.penalties.iter().map(|(_, points)| points).sum();
+        (100.0 - lost).round().clamp(0.0, 100.0) as u8
+    }
+}
+
+/// Score of the data of an activity: each problem takes off up to its
+/// weight, in proportion to how much of the ride it touches, up to
+/// [`FULL_PENALTY_SHARE`]. `None` without points, as for the files streamed
+/// without them.
+pub fn quality_score(activity: &Activity) -> Option<QualityScore> {
+    let points = &activity.points;
+    if points.is_empty() {
+        return None;
+    }
+    let share = |count: usize, total: usize| count as f64 / total.max(1) as f64;
+
+    let timestamps = if has_timestamps(points) {
+        let untimed = points.iter().filter(|point| point.time.is_none()).count();
+        share(
+            untimed + out_of_order(points).unwrap_or_default(),
+            points.len(),
+        )
+    } else {
+        1.0
+    };
+    let elevation = if !has_elevation(points) {
+        1.0
+    } else if activity.has_low_quality_elevation() {
+        0.5
+    } else {
+        0.0
+    };
+    let jitter_points = jitter_points(points);
+    let gap_m = gap_distance_m(points);
+    let length_m: f64 = points
+        .windows(2)
+        .map(|pair| distance_m(&pair[0], &pair[1]))
+        .sum();
+    let gaps = if length_m > 0.0 {
+        gap_m / length_m
+    } else {
+        0.0
+    };
+    let duplicates = share(
+        activity.duplicate_points,
+        points.len() + activity.duplicate_points,
+    );
+
+    let mut penalties: Vec<(Problem, f64)> = [
+        (Problem::Timestamps, timestamps),
+        (Problem::Elevation, elevation),
+        (Problem::Jitter, share(jitter_points, points.len())),
+        (Problem::Gaps, gaps),
+        (Problem::Duplicates, duplicates),
+    ]
+    .into_iter()
+    .map(|(problem, share)| {
+        // The elevation is judged as a whole, not point by point.
+        let severity = match problem {
+            Problem::Elevation => share,
+            _ => (share / FULL_PENALTY_SHARE).min(1.0),
+        };
+        (problem, severity * problem.weight())
+    })
+    .filter(|&(_, points)| points > 0.0)
+    .collect();
+    penalties.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    Some(QualityScore {
+        penalties,
+        jitter_points,
+        gap_km: gap_m / 1_000.0,
+    })
+}
+
+/// Number of points the GPS put back where it came from: they turn back by
+/// more than [`JITTER_TURN_DEG`] over legs shorter than [`JITTER_LEG_M`], or
+/// are reached faster than a bike goes.
+pub fn jitter_points(points: &[TrackPoint]) -> usize {
+    let limit = JITTER_TURN_DEG.to_radians().cos();
+    points
+        .windows(3)
+        .filter(|triple| {
+            let [a, b, c] = triple else {
+                return false;
+            };
+            // Direction of a leg, with the longitude shrunk to the latitude.
+            let leg = |from: &TrackPoint, to: &TrackPoint| {
+                let scale = from.lat.to_radians().cos();
+                ((to.lon - from.lon) * scale, to.lat - from.lat)
+            };
+            let (into, out) = (leg(a, b), leg(b, c));
+            let lengths = (into.0.hypot(into.1), out.0.hypot(out.1));
+            let short = distance_m(a, b) < JITTER_LEG_M && distance_m(b, c) < JITTER_LEG_M;
+            let too_fast = suspicious_speed(&triple[..2]).is_some();
+            too_fast
+                || (short
+                    && lengths.0 > 0.0
+                    && lengths.1 > 0.0
+                    && (into.0 * out.0 + into.1 * out.1) / (lengths.0 * lengths.1) < limit)
+        })
+        .count()
+}
+
+/// Distance crossed in straight lines over the gaps in the recording: the
+/// intervals [`GAP_FACTOR`] times longer than the usual one, covered faster
+/// than [`MOVING_MIN_SPEED`], in meters.
+pub fn gap_distance_m(points: &[TrackPoint]) -> f64 {
+    let intervals: Vec<(f64, f64)> = points
+        .windows(2)
+        .filter_map(|pair| {
+            let seconds = (pair[1].time? - pair[0].time?).as_seconds_f64();
+            (seconds > 0.0).then(|| (seconds, distance_m(&pair[0], &pair[1])))
+        })
+        .collect();
+    let mut seconds: Vec<f64> = intervals.iter().map(|&(seconds, _)| seconds).collect();
+    seconds.sort_by(f64::total_cmp);
+    let Some(&usual) = seconds.get(seconds.len() / 2) else {
+        return 0.0;
+    };
+    intervals
+        .iter()
+        .filter(|&&(seconds, meters)| {
+            seconds >= GAP_FACTOR * usual && meters / seconds > MOVING_MIN_SPEED
+        })
+        .fold(0.0, |total, &(_, meters)| total + meters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn point(lat: f64, time: Option<&str>) -> TrackPoint {
+        TrackPoint {
+            lat,
+            lon: 5.0,
+            time: time.map(|time| DateTime::parse_from_rfc3339(time).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn missing_timestamps_and_elevation() {
+        let points = [point(45.0, None), point(45.001, None)];
+        assert!(!has_timestamps(&points));
+        assert!(!has_elevation(&points));
+
+        let points = [
+            point(45.0, Some("2024-06-01T07:00:00Z")),
+            TrackPoint {
+                elevation: Some(200.0),
+                ..point(45.001, None)
+            },
+        ];
+        assert!(has_timestamps(&points));
+        assert!(has_elevation(&points));
+    }
+
+    #[test]
+    fn out_of_order_points() {
+        let points = [
+            point(45.0, Some("2024-06-01T07:00:10Z")),
+            point(45.0001, Some("2024-06-01T07:00:00Z")),
+            point(45.0002, None),
+            point(45.0003, Some("2024-06-01T07:00:20Z")),
+        ];
+        assert_eq!(out_of_order(&points), Some(1));
+        assert_eq!(out_of_order(&points[1..]), None);
+    }
+
+    #[test]
+    fn suspicious_speeds() {
+        // About 111 m in 10 s, then 1.1 km in 10 s.
+        let points = [
+            point(45.0, Some("2024-06-01T07:00:00Z")),
+            point(45.001, Some("2024-06-01T07:00:10Z")),
+            point(45.011, Some("2024-06-01T07:00:20Z")),
+        ];
+        assert_eq!(suspicious_speed(&points[..2]), None);
+        assert_eq!(suspicious_speed(&points).map(f64::round), Some(400.0));
+    }
+
+    #[test]
+    fn activities_without_points() {
+        assert_eq!(issues(&Activity::default()), [Issue::NoPoints]);
+        assert_eq!(quality_score(&Activity::default()), None);
+    }
+
+    fn score_of(name: &str) -> QualityScore {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        let activity = Activity::from_bytes(path.clone(), &std::fs::read(&path).unwrap()).unwrap();
+        quality_score(&activity).unwrap()
+    }
+
+    #[test]
+    fn each_problem_takes_its_weight_off() {
+        // Each fixture has one problem all along, and takes its whole weight
+        // off.
+        for (name, problem) in [
+            ("clock_reset.gpx", Problem::Timestamps),
+            ("no_timestamps.gpx", Problem::Timestamps),
+            ("no_elevation.gpx", Problem::Elevation),
+            ("jitter.gpx", Problem::Jitter),
+            ("tunnel.gpx", Problem::Gaps),
+            ("duplicated.gpx", Problem::Duplicates),
+        ] {
+            let quality = score_of(name);
+            assert_eq!(quality.penalties, [(problem, problem.weight())], "{name}");
+            assert_eq!(
+                f64::from(quality.score()),
+                100.0 - problem.weight(),
+                "{name}"
+            );
+        }
+        // GPS-derived elevation takes half.
+        assert_eq!(
+            score_of("gps_altitude.gpx").penalties,
+            [(Problem::Elevation, ELEVATION_WEIGHT / 2.0)]
+        );
+        for name in [
+            "barometric.gpx",
+            "mountain.gpx",
+            "auto_pause.gpx",
+            "ferry.gpx",
+        ] {
+            assert_eq!(score_of(name).score(), 100, "{name}");
+        }
+
+        let jitter = score_of("jitter.gpx");
+        assert_eq!(jitter.jitter_points, 18);
+        let tunnel = score_of("tunnel.gpx");
+        assert!((tunnel.gap_km - 0.167).abs() < 0.001, "{tunnel:?}");
+    }
+
+    #[test]
+    fn problems_add_up_in_proportion() {
+        // The tunnel without its elevation, and one duplicate in 21 points:
+        // about half the share that takes the whole weight off.
+        let path =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tunnel.gpx");
+        let mut activity =
+            Activity::from_bytes(path.clone(), &std::fs::read(&path).unwrap()).unwrap();
+        for point in &mut activity.points {
+            point.elevation = None;
+        }
+        activity.duplicate_points = 1;
+        let quality = quality_score(&activity).unwrap();
+        let problems: Vec<Problem> = quality
+            .penalties
+            .iter()
+            .map(|&(problem, _)| problem)
+            .collect();
+        assert_eq!(
+            problems,
+            [Problem::Elevation, Problem::Gaps, Problem::Duplicates]
+        );
+        let duplicates = quality.penalties[2].1;
+        assert!((duplicates - DUPLICATES_WEIGHT / 21.0 / FULL_PENALTY_SHARE).abs() < 1e-9);
+        assert_eq!(quality.score(), 58);
+    }
+
+    #[test]
+    fn jitter_turns_back_on_short_legs() {
+        let points = [
+            point(45.0, Some("2024-06-01T07:00:00Z")),
+            point(45.00005, Some("2024-06-01T07:00:05Z")),
+            point(45.0, Some("2024-06-01T07:00:10Z")),
+            point(45.00005, Some("2024-06-01T07:00:15Z")),
+        ];
+        assert_eq!(jitter_points(&points), 2);
+        // The same turns over long legs are a ride back and forth.
+        let points = [point(45.0, None), point(45.001, None), point(45.0, None)];
+        assert_eq!(jitter_points(&points), 0);
+    }
+}
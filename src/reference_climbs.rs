@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::activity::TrackPoint;
+use crate::climbs::{CLIMB_LENGTH_TOLERANCE, CLIMB_MATCH_RADIUS_M};
+use crate::tuning::{Tuning, derived_points};
+
+/// A climb whose length and elevation gain are known, such as those given
+/// on the signs along the road, as listed by the user.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReferenceClimb {
+    pub name: String,
+    /// Bottom and top, as `[latitude, longitude]`.
+    pub start: (f64, f64),
+    pub end: (f64, f64),
+    /// Official length, in meters.
+    pub length_m: f64,
+    /// Official elevation difference from the bottom to the top, in meters.
+    pub gain_m: f64,
+    /// How close to the bottom and the top a ride must pass, in meters.
+    #[serde(default = "default_radius")]
+    pub radius_m: f64,
+}
+
+fn default_radius() -> f64 {
+    CLIMB_MATCH_RADIUS_M
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReferenceFile {
+    #[serde(default)]
+    climb: Vec<ReferenceClimb>,
+}
+
+/// Read the `[[climb]]` tables of a TOML file.
+pub fn load_reference_climbs(path: &Path) -> Result<Vec<ReferenceClimb>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    parse_reference_climbs(&content).with_context(|| format!("parsing {}", path.display()))
+}
+
+pub fn parse_reference_climbs(content: &str) -> Result<Vec<ReferenceClimb>> {
+    Ok(toml::from_str::<ReferenceFile>(content)?.climb)
+}
+
+/// An ascent of a reference climb, measured against its official figures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClimbCheck {
+    pub name: String,
+    pub length_m: f64,
+    pub official_length_m: f64,
+    /// Elevation difference from the bottom to the top.
+    pub gain_m: f64,
+    pub official_gain_m: f64,
+}
+
+impl ClimbCheck {
+    /// How far the measured gain is from the official one, in percent.
+    pub fn gain_error_percent(&self) -> f64 {
+        (self.gain_m - self.official_gain_m) / self.official_gain_m * 100.0
+    }
+}
+
+/// The ascents of the reference climbs in a ride, in the order of the list.
+/// An ascent goes from the point closest to the bottom to the point closest
+/// to the top after it, each within the radius of the climb; riding it down
+/// is no ascent, and a way much longer or shorter than the official length
+/// is another road.
+pub fn check_reference_climbs(
+    points: &[TrackPoint],
+    references: &[ReferenceClimb],
+    tuning: &Tuning,
+) -> Vec<ClimbCheck> {
+    references
+        .iter()
+        .flat_map(|reference| {
+            ascents(points, reference)
+                .into_iter()
+                .filter_map(|(bottom, top)| check(&points[bottom..=top], reference, tuning))
+        })
+        .collect()
+}
+
+/// First and last index of each pass by the bottom then the top.
+fn ascents(points: &[TrackPoint], reference: &ReferenceClimb) -> Vec<(usize, usize)> {
+    let distance = |point: &TrackPoint, (lat, lon): (f64, f64)| {
+        Haversine.distance(point!(x: point.lon, y: point.lat), point!(x: lon, y: lat))
+    };
+    let mut ascents = vec![];
+    // Closest point to the bottom of the last pass there, and to the top
+    // since.
+    let mut bottom: Option<(usize, f64)> = None;
+    let mut top: Option<(usize, f64)> = None;
+    let mut near_bottom = false;
+    for (i, point) in points.iter().enumerate() {
+        let to_top = distance(point, reference.end);
+        if bottom.is_some() && to_top <= reference.radius_m {
+            if top.is_none_or(|(_, closest)| to_top < closest) {
+                top = Some((i, to_top));
+            }
+            continue;
+        }
+        if let (Some((from, _)), Some((to, _))) = (bottom, top.take()) {
+            ascents.push((from, to));
+            bottom = None;
+        }
+        let to_bottom = distance(point, reference.start);
+        if to_bottom <= reference.radius_m {
+            if !near_bottom || bottom.is_none_or(|(_, closest)| to_bottom < closest) {
+                bottom = Some((i, to_bottom));
+            }
+            near_bottom = true;
+        } else {
+            near_bottom = false;
+        }
+    }
+    if let (Some((from, _)), Some((to, _))) = (bottom, top) {
+        ascents.push((from, to));
+    }
+    ascents
+}
+
+fn check(points: &[TrackPoint], reference: &ReferenceClimb, tuning: &Tuning) -> Option<ClimbCheck> {
+    let length_m = derived_points(points, tuning).last()?.distance_m;
+    if (length_m - reference.length_m).abs() > CLIMB_LENGTH_TOLERANCE * reference.length_m {
+        return None;
+    }
+    let gain_m = points.last()?.elevation? - points.first()?.elevation?;
+    Some(ClimbCheck {
+        name: reference.name.clone(),
+        length_m,
+        official_length_m: reference.length_m,
+        gain_m,
+        official_gain_m: reference.gain_m,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::Activity;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn ascents_are_measured_against_the_official_figures() {
+        let references = load_reference_climbs(&fixture("climbs.toml")).unwrap();
+        assert_eq!(references.len(), 3);
+        assert_eq!(references[1].radius_m, CLIMB_MATCH_RADIUS_M);
+
+        // Up the climb and back down the other side of the road.
+        let path = fixture("mountain.gpx");
+        let ride = Activity::from_bytes(path.clone(), &fs::read(&path).unwrap()).unwrap();
+        let checks = check_reference_climbs(&ride.points, &references, &Tuning::default());
+        assert_eq!(checks.len(), 1, "{checks:?}");
+        let check = &checks[0];
+        assert_eq!(check.name, "Col du Test");
+        assert_eq!(check.gain_m, 280.0);
+        assert_eq!(check.official_gain_m, 282.0);
+        assert!(
+            (check.gain_error_percent() + 0.71).abs() < 0.01,
+            "{check:?}"
+        );
+        assert!((check.length_m - 1_557.0).abs() < 1.0, "{check:?}");
+
+        // Only the way down: the climb is not ridden up.
+        let down = &ride.points[7..];
+        assert_eq!(
+            check_reference_climbs(down, &references[..1], &Tuning::default()),
+            []
+        );
+        // Twice up the climb.
+        let twice = [&ride.points[..8], &ride.points[..8]].concat();
+        assert_eq!(
+            check_reference_climbs(&twice, &references[..1], &Tuning::default()).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn invalid_reference_files_are_reported() {
+        assert_eq!(parse_reference_climbs("").unwrap(), []);
+        let missing_gain =
+            "[[climb]]\nname = \"A\"\nstart = [45.0, 6.0]\nend = [45.1, 6.0]\nlength_m = 1000\n";
+        assert!(parse_reference_climbs(missing_gain).is_err());
+        assert!(load_reference_climbs(&fixture("nowhere.toml")).is_err());
+    }
+}
@@ -0,0 +1,690 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+use crate::activity::{Activity, ActivityId};
+use crate::expr::{Expr, Kind};
+use crate::quality::quality_score;
+use crate::storage::write_atomic;
+use crate::tuning::DistanceSource;
+
+/// How the loaded activities are presented.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Interactive terminal interface
+    #[default]
+    Tui,
+    /// JSON array on stdout
+    Json,
+    /// CSV with a header row on stdout
+    Csv,
+    /// Table drawn with box characters, with a totals row, on stdout
+    Table,
+}
+
+/// A value of a [`Field`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Text(String),
+    /// Rounded to `decimals`, so the output is stable across platforms.
+    Number {
+        value: f64,
+        decimals: usize,
+    },
+    Flag(bool),
+    Missing,
+}
+
+impl FieldValue {
+    fn number(value: f64, decimals: usize) -> Self {
+        Self::Number {
+            value: round(value, decimals as i32),
+            decimals,
+        }
+    }
+
+    /// Order of the values of a column, missing ones last either way.
+    fn compare(&self, other: &Self, descending: bool) -> Ordering {
+        let ordering = match (self, other) {
+            (Self::Missing, Self::Missing) => return Ordering::Equal,
+            (Self::Missing, _) => return Ordering::Greater,
+            (_, Self::Missing) => return Ordering::Less,
+            (Self::Number { value: a, .. }, Self::Number { value: b, .. }) => a.total_cmp(b),
+            (Self::Text(a), Self::Text(b)) => a.cmp(b),
+            (Self::Flag(a), Self::Flag(b)) => a.cmp(b),
+            _ => Ordering::Equal,
+        };
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    /// The value as written in the CSV output and the table.
+    fn text(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            Self::Number { value, decimals } => format!("{value:.decimals$}"),
+            Self::Flag(flag) => flag.to_string(),
+            Self::Missing => String::new(),
+        }
+    }
+}
+
+impl Serialize for FieldValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Text(text) => serializer.serialize_str(text),
+            Self::Number { value, .. } => serializer.serialize_f64(*value),
+            Self::Flag(flag) => serializer.serialize_bool(*flag),
+            Self::Missing => serializer.serialize_none(),
+        }
+    }
+}
+
+/// A metric of the headless formats: a key of the JSON objects and a column
+/// of the CSV output and the table.
+#[derive(Debug)]
+pub struct Field {
+    pub name: &'static str,
+    /// Shorter name `--columns` also takes.
+    pub alias: Option<&'static str>,
+    /// Whether the JSON and CSV outputs have it without `--columns`.
+    pub default: bool,
+    pub value: fn(&Activity) -> FieldValue,
+    /// Value of the totals row of the table, blank when `None`.
+    pub total: Option<fn(&[Activity]) -> FieldValue>,
+}
+
+/// Every field, in the order of the outputs; adding one here makes it
+/// available to all the headless formats.
+pub const FIELDS: &[Field] = &[
+    Field {
+        name: "id",
+        alias: None,
+        default: true,
+        value: |activity| FieldValue::Text(activity.id.to_string()),
+        total: None,
+    },
+    Field {
+        name: "path",
+        alias: None,
+        default: true,
+        value: |activity| FieldValue::Text(activity.path.display().to_string()),
+        total: None,
+    },
+    Field {
+        name: "name",
+        alias: None,
+        default: true,
+        value: |activity| FieldValue::Text(activity.name.clone()),
+        total: None,
+    },
+    Field {
+        name: "date",
+        alias: None,
+        default: false,
+        value: |activity| {
+            activity.start.map_or(FieldValue::Missing, |start| {
+                FieldValue::Text(start.date_naive().to_string())
+            })
+        },
+        total: None,
+    },
+    Field {
+        name: "start",
+        alias: None,
+        default: true,
+        value: |activity| {
+            activity.start.map_or(FieldValue::Missing, |start| {
+                FieldValue::Text(start.to_rfc3339())
+            })
+        },
+        total: None,
+    },
+    Field {
+        name: "distance_km",
+        alias: Some("distance"),
+        default: true,
+        value: |activity| FieldValue::number(activity.distance_km, 3),
+        total: Some(|activities| {
+            FieldValue::number(activities.iter().map(|a| a.distance_km).sum(), 3)
+        }),
+    },
+    Field {
+        // Whether the distance of a sparse track is not the one in the file
+        // but an estimate.
+        name: "distance_estimated",
+        alias: None,
+        default: true,
+        value: |activity| FieldValue::Flag(activity.distance_source == DistanceSource::Corrected),
+        total: None,
+    },
+    Field {
+        name: "elevation_gain_m",
+        alias: Some("elevation"),
+        default: true,
+        value: |activity| FieldValue::number(activity.elevation_gain, 1),
+        total: Some(|activities| {
+            FieldValue::number(activities.iter().map(|a| a.elevation_gain).sum(), 1)
+        }),
+    },
+    Field {
+        // See `highest_point`; missing without elevation.
+        name: "max_elevation_m",
+        alias: None,
+        default: true,
+        value: |activity| {
+            activity.peak.map_or(FieldValue::Missing, |peak| {
+                FieldValue::number(peak.elevation_m, 0)
+            })
+        },
+        total: Some(|activities| {
+            activities
+                .iter()
+                .filter_map(|activity| activity.peak)
+                .map(|peak| peak.elevation_m)
+                .reduce(f64::max)
+                .map_or(FieldValue::Missing, |highest| {
+                    FieldValue::number(highest, 0)
+                })
+        }),
+    },
+    Field {
+        // Where along the ride the highest point is.
+        name: "max_elevation_km",
+        alias: None,
+        default: true,
+        value: |activity| {
+            activity.peak.map_or(FieldValue::Missing, |peak| {
+                FieldValue::number(peak.distance_km, 1)
+            })
+        },
+        total: None,
+    },
+    Field {
+        name: "avg_speed_kmh",
+        alias: Some("speed"),
+        default: false,
+        value: |activity| {
+            activity
+                .average_speed()
+                .map_or(FieldValue::Missing, |speed| FieldValue::number(speed, 1))
+        },
+        total: Some(|activities| {
+            let (mut km, mut seconds) = (0.0, 0.0);
+            for activity in activities {
+                if let Some(moving) = activity.moving_seconds().filter(|&moving| moving > 0.0) {
+                    km += activity.distance_km;
+                    seconds += moving;
+                }
+            }
+            if seconds > 0.0 {
+                FieldValue::number(km / (seconds / 3600.0), 1)
+            } else {
+                FieldValue::Missing
+            }
+        }),
+    },
+    Field {
+        // See `quality_score`; missing without points.
+        name: "quality",
+        alias: None,
+        default: false,
+        value: |activity| {
+            quality_score(activity).map_or(FieldValue::Missing, |quality| {
+                FieldValue::number(f64::from(quality.score()), 0)
+            })
+        },
+        total: None,
+    },
+];
+
+/// Columns of the table without `--columns`.
+pub const TABLE_COLUMNS: &[&str] = &[
+    "date",
+    "name",
+    "distance_km",
+    "elevation_gain_m",
+    "avg_speed_kmh",
+];
+
+/// The field of a name or alias, for `--columns`.
+pub fn parse_field(name: &str) -> Result<&'static Field, String> {
+    FIELDS
+        .iter()
+        .find(|field| field.name == name || field.alias == Some(name))
+        .ok_or_else(|| {
+            let names: Vec<_> = FIELDS.iter().map(|field| field.name).collect();
+            format!("unknown column, expected one of {}", names.join(", "))
+        })
+}
+
+/// Decimals of the values of the expression columns.
+const EXPR_DECIMALS: usize = 2;
+
+/// A column of `--column-expr`, computed from the metrics, such as
+/// `gain_per_km = elevation_m / distance_km`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnExpr {
+    pub name: String,
+    pub expr: Expr,
+}
+
+/// A `NAME = EXPRESSION` column, for `--column-expr`; the columns of the
+/// errors count from the start of `source`.
+pub fn parse_column_expr(source: &str) -> Result<ColumnExpr, String> {
+    let Some((name, expr)) = source.split_once('=') else {
+        return Err("expected `NAME = EXPRESSION`".to_string());
+    };
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(format!(
+            "invalid column name {name:?}, expected letters, digits and `_`"
+        ));
+    }
+    if parse_field(name).is_ok() {
+        return Err(format!("column `{name}` already exists"));
+    }
+    let offset = source.len() - expr.len();
+    let expr = Expr::parse(expr, source[..offset].chars().count(), Kind::Number)
+        .map_err(|err| format!("{err:#}"))?;
+    Ok(ColumnExpr {
+        name: name.to_string(),
+        expr,
+    })
+}
+
+/// A column of the headless formats: a field, or a column of
+/// `--column-expr`.
+#[derive(Debug, Clone, Copy)]
+pub enum Column<'a> {
+    Field(&'static Field),
+    Expr(&'a ColumnExpr),
+}
+
+impl Column<'_> {
+    pub fn name(&self) -> &str {
+        match self {
+            Column::Field(field) => field.name,
+            Column::Expr(column) => &column.name,
+        }
+    }
+
+    /// Missing when a metric is, or on a division by zero.
+    pub fn value(&self, activity: &Activity) -> FieldValue {
+        match self {
+            Column::Field(field) => (field.value)(activity),
+            Column::Expr(column) => column
+                .expr
+                .evaluate(activity)
+                .map_or(FieldValue::Missing, |value| {
+                    FieldValue::number(value, EXPR_DECIMALS)
+                }),
+        }
+    }
+
+    /// Value of the totals row of the table; expression columns have none.
+    fn total(&self, activities: &[Activity]) -> FieldValue {
+        match self {
+            Column::Field(Field {
+                total: Some(total), ..
+            }) => total(activities),
+            _ => FieldValue::Missing,
+        }
+    }
+}
+
+/// The column of `--sort`, a field or an expression column, and whether
+/// the order is descending (`-NAME`).
+pub fn sort_column<'a>(key: &str, exprs: &'a [ColumnExpr]) -> Result<(Column<'a>, bool), String> {
+    let (name, descending) = match key.strip_prefix('-') {
+        Some(name) => (name, true),
+        None => (key, false),
+    };
+    let column = match exprs.iter().find(|column| column.name == name) {
+        Some(column) => Column::Expr(column),
+        None => Column::Field(parse_field(name).map_err(|err| format!("--sort {key}: {err}"))?),
+    };
+    Ok((column, descending))
+}
+
+/// Fail on two expression columns of the same name.
+pub fn check_column_exprs(exprs: &[ColumnExpr]) -> Result<(), String> {
+    for (i, column) in exprs.iter().enumerate() {
+        if exprs[..i].iter().any(|other| other.name == column.name) {
+            return Err(format!(
+                "--column-expr: column `{}` is defined twice",
+                column.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Order the activities by a column, keeping the order of equal values;
+/// missing values come last either way.
+pub fn sort_activities(activities: &mut Vec<Activity>, column: Column, descending: bool) {
+    let mut keyed: Vec<(FieldValue, Activity)> = activities
+        .drain(..)
+        .map(|activity| (column.value(&activity), activity))
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| a.compare(b, descending));
+    activities.extend(keyed.into_iter().map(|(_, activity)| activity));
+}
+
+/// The fields of a format without `--columns`.
+pub fn default_fields(format: Format) -> Vec<&'static Field> {
+    match format {
+        Format::Table => TABLE_COLUMNS
+            .iter()
+            .filter_map(|name| parse_field(name).ok())
+            .collect(),
+        _ => FIELDS.iter().filter(|field| field.default).collect(),
+    }
+}
+
+/// The columns of a format without `--columns` nor `--column-expr`.
+pub fn default_columns(format: Format) -> Vec<Column<'static>> {
+    default_fields(format)
+        .into_iter()
+        .map(Column::Field)
+        .collect()
+}
+
+/// Metrics of one activity, as written by the headless formats: the values
+/// of some fields, in order.
+#[derive(Debug)]
+pub struct ActivityReport {
+    pub values: Vec<(String, FieldValue)>,
+}
+
+impl ActivityReport {
+    pub fn new(activity: &Activity, columns: &[Column]) -> Self {
+        Self {
+            values: columns
+                .iter()
+                .map(|column| (column.name().to_string(), column.value(activity)))
+                .collect(),
+        }
+    }
+}
+
+impl From<&Activity> for ActivityReport {
+    fn from(activity: &Activity) -> Self {
+        Self::new(activity, &default_columns(Format::Json))
+    }
+}
+
+impl Serialize for ActivityReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.values.len()))?;
+        for (name, value) in &self.values {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    // Adding 0.0 turns -0.0 (the sum of an empty iterator) into 0.0.
+    (value * factor).round() / factor + 0.0
+}
+
+pub fn write_json(activities: &[Activity], columns: &[Column], out: &mut impl Write) -> Result<()> {
+    let reports: Vec<ActivityReport> = activities
+        .iter()
+        .map(|activity| ActivityReport::new(activity, columns))
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &reports)?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+/// One line of the JSON lines output: the metrics of a file with an `ok`
+/// status, or why it could not be read.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum JsonLine {
+    Ok(ActivityReport),
+    Error { path: String, message: String },
+}
+
+impl JsonLine {
+    pub fn new(path: &Path, result: &Result<Activity>) -> Self {
+        match result {
+            Ok(activity) => Self::Ok(activity.into()),
+            Err(err) => Self::Error {
+                path: path.display().to_string(),
+                message: format!("{err:#}"),
+            },
+        }
+    }
+}
+
+/// Write a line and flush it, so readers get it before the next file is
+/// done.
+pub fn write_json_line(line: &JsonLine, out: &mut impl Write) -> Result<()> {
+    serde_json::to_writer(&mut *out, line)?;
+    writeln!(out)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+pub fn write_csv(activities: &[Activity], columns: &[Column], out: &mut impl Write) -> Result<()> {
+    let header: Vec<_> = columns.iter().map(|column| column.name()).collect();
+    writeln!(out, "{}", header.join(","))?;
+    for activity in activities {
+        let row: Vec<_> = columns
+            .iter()
+            .map(|column| csv_field(&column.value(activity).text()))
+            .collect();
+        writeln!(out, "{}", row.join(","))?;
+    }
+
+    Ok(())
+}
+
+/// Write the activities to a CSV file with the default columns. The rows
+/// already there are kept, those of the same activities replaced and those
+/// of `removed` left out, so that exporting again never repeats a ride.
+pub fn export_csv(activities: &[Activity], removed: &[ActivityId], path: &Path) -> Result<()> {
+    let mut new = Vec::new();
+    write_csv(activities, &default_columns(Format::Csv), &mut new)?;
+    let new = String::from_utf8(new)?;
+    let old = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+    };
+
+    let mut new_rows = csv_records(&new);
+    let header = new_rows.remove(0);
+    let mut old_rows = csv_records(&old);
+    if !old_rows.is_empty() && old_rows.remove(0) != header {
+        bail!("{} has other columns than the export", path.display());
+    }
+    // The id comes first, and never needs quoting.
+    let id = |row: &str| row.split(',').next().unwrap_or_default().to_string();
+    let removed: HashSet<String> = removed.iter().map(ToString::to_string).collect();
+    let mut fresh: BTreeMap<String, &str> = new_rows.iter().map(|row| (id(row), *row)).collect();
+
+    let mut text = format!("{header}\n");
+    for row in old_rows {
+        let row_id = id(row);
+        if removed.contains(&row_id) {
+            continue;
+        }
+        text.push_str(fresh.remove(&row_id).unwrap_or(row));
+        text.push('\n');
+    }
+    for row in new_rows {
+        if fresh.contains_key(&id(row)) {
+            text.push_str(row);
+            text.push('\n');
+        }
+    }
+
+    write_atomic(path, text.as_bytes())
+}
+
+/// The records of a CSV text, without their line end; a quoted field may
+/// hold a line break.
+fn csv_records(text: &str) -> Vec<&str> {
+    let mut records = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '\n' if !quoted => {
+                records.push(text[start..i].trim_end_matches('\r'));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < text.len() {
+        records.push(&text[start..]);
+    }
+
+    records
+}
+
+/// Narrowest a text column is cut down to, in characters.
+const MIN_TEXT_WIDTH: usize = 4;
+
+/// Write the columns of the activities as a table drawn with box characters,
+/// numbers aligned on the right, with a totals row. Text columns, widest
+/// first, are cut with an ellipsis to fit in `width` characters.
+pub fn write_table(
+    activities: &[Activity],
+    columns: &[Column],
+    width: Option<usize>,
+    out: &mut impl Write,
+) -> Result<()> {
+    let rows: Vec<Vec<FieldValue>> = activities
+        .iter()
+        .map(|activity| {
+            columns
+                .iter()
+                .map(|column| column.value(activity))
+                .collect()
+        })
+        .collect();
+    let mut totals: Vec<FieldValue> = columns
+        .iter()
+        .map(|column| column.total(activities))
+        .collect();
+    // The label goes in the first column without a total.
+    if let Some(label) = totals
+        .iter_mut()
+        .find(|value| **value == FieldValue::Missing)
+    {
+        *label = FieldValue::Text(format!("total ({})", activities.len()));
+    }
+
+    let length = |text: &str| text.chars().count();
+    let mut widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            rows.iter()
+                .chain([&totals])
+                .map(|row| length(&row[i].text()))
+                .fold(length(column.name()), usize::max)
+        })
+        .collect();
+    if let Some(width) = width {
+        // Borders and padding: "│ " before each column and " │" at the end.
+        let frame = 3 * columns.len() + 1;
+        let is_text = |i: usize| rows.iter().any(|row| matches!(row[i], FieldValue::Text(_)));
+        while widths.iter().sum::<usize>() + frame > width {
+            let Some(widest) = (0..columns.len())
+                .filter(|&i| is_text(i) && widths[i] > MIN_TEXT_WIDTH)
+                .max_by_key(|&i| widths[i])
+            else {
+                break;
+            };
+            widths[widest] -= 1;
+        }
+    }
+
+    let line = |left: &str, middle: &str, right: &str| {
+        let parts: Vec<_> = widths.iter().map(|width| "─".repeat(width + 2)).collect();
+        format!("{left}{}{right}", parts.join(middle))
+    };
+    let cells = |values: Vec<(String, bool)>| {
+        let cells: Vec<_> = values
+            .into_iter()
+            .zip(&widths)
+            .map(|((text, numeric), &width)| {
+                let text = truncate(&text, width);
+                if numeric {
+                    format!(" {text:>width$} ")
+                } else {
+                    format!(" {text:<width$} ")
+                }
+            })
+            .collect();
+        format!("│{}│", cells.join("│"))
+    };
+    let row = |values: &[FieldValue]| {
+        cells(
+            values
+                .iter()
+                .map(|value| (value.text(), matches!(value, FieldValue::Number { .. })))
+                .collect(),
+        )
+    };
+
+    writeln!(out, "{}", line("┌", "┬", "┐"))?;
+    writeln!(
+        out,
+        "{}",
+        cells(
+            columns
+                .iter()
+                .map(|column| (column.name().to_string(), false))
+                .collect()
+        )
+    )?;
+    writeln!(out, "{}", line("├", "┼", "┤"))?;
+    for values in &rows {
+        writeln!(out, "{}", row(values))?;
+    }
+    writeln!(out, "{}", line("├", "┼", "┤"))?;
+    writeln!(out, "{}", row(&totals))?;
+    writeln!(out, "{}", line("└", "┴", "┘"))?;
+
+    Ok(())
+}
+
+/// The text cut to `width` characters, the last one an ellipsis.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut text: String = text.chars().take(width.saturating_sub(1)).collect();
+    text.push('…');
+    text
+}
+
+/// Quote a field when it contains a separator, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,979 @@
+use chrono::{Datelike, NaiveDate};
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::exploration::ExplorationYear;
+use crate::i18n::Lang;
+use crate::intensity::{Intensity, IntensityThresholds};
+use crate::kml::escape;
+use crate::peaks::Peak;
+use crate::stats::{
+    DayTotals, LinearTrend, MOVING_AVERAGE_MONTHS, RideMetrics, Summary, WeekSplit, moving_average,
+};
+
+/// How a yearly review is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReviewFormat {
+    /// Self-contained HTML page
+    #[default]
+    Html,
+    /// Plain text with ASCII charts
+    Text,
+}
+
+pub(crate) const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Rides listed in the top rides table.
+const TOP_RIDES: usize = 10;
+
+/// Colors of the calendar cells, from rest days to the longest days.
+const HEAT_COLORS: [&str; 5] = ["#ebedf0", "#c6e48b", "#7bc96f", "#239a3b", "#196127"];
+
+/// Characters of the calendar cells in the text review.
+const HEAT_CHARS: [char; 5] = ['.', ':', '+', '*', '#'];
+
+const BAR_WIDTH: usize = 40;
+
+/// Colors of the intensity classes, in the order of [`Intensity::ALL`].
+const INTENSITY_COLORS: [&str; 4] = ["#4a90d9", "#239a3b", "#e6a700", "#d73a49"];
+
+/// Heat level of a day, 0 when nothing was ridden.
+fn heat_level(km: f64, max_km: f64) -> usize {
+    if km <= 0.0 || max_km <= 0.0 {
+        return 0;
+    }
+    ((km / max_km * 4.0).ceil() as usize).clamp(1, 4)
+}
+
+/// Every day of `year` with its calendar column (week) and row (weekday,
+/// Monday first).
+fn calendar(year: i32) -> Vec<(NaiveDate, usize, usize)> {
+    let Some(first) = NaiveDate::from_ymd_opt(year, 1, 1) else {
+        return vec![];
+    };
+    let offset = first.weekday().num_days_from_monday() as usize;
+    first
+        .iter_days()
+        .take_while(|date| date.year() == year)
+        .enumerate()
+        .map(|(i, date)| {
+            (
+                date,
+                (i + offset) / 7,
+                date.weekday().num_days_from_monday() as usize,
+            )
+        })
+        .collect()
+}
+
+fn format_hours(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).round() as i64;
+    format!("{}h{:02}", minutes / 60, minutes % 60)
+}
+
+fn ride_date(ride: &RideMetrics) -> String {
+    ride.date.map(|date| date.to_string()).unwrap_or_default()
+}
+
+/// Figures and texts shared by both formats.
+struct Figures<'a> {
+    lang: Lang,
+    days: BTreeMap<NaiveDate, f64>,
+    months: [f64; 12],
+    /// Months of the year counted in the trend, first and last.
+    trend_months: Option<(usize, usize)>,
+    /// Kilometers per month fitted over the counted months, by month index.
+    trend: Option<LinearTrend>,
+    /// Moving average of the counted months.
+    averages: [Option<f64>; 12],
+    top: Vec<&'a RideMetrics>,
+    climb: Option<&'a RideMetrics>,
+    highest: Option<(&'a RideMetrics, Peak)>,
+    /// Moving hours per intensity class of each week, by Monday.
+    weeks: BTreeMap<NaiveDate, [f64; 4]>,
+}
+
+impl<'a> Figures<'a> {
+    fn new(
+        year: i32,
+        summary: &'a Summary,
+        thresholds: &IntensityThresholds,
+        until: NaiveDate,
+        lang: Lang,
+    ) -> Self {
+        let series: Vec<(usize, f64)> = summary
+            .monthly_series(until)
+            .into_iter()
+            .filter(|(first, _)| first.year() == year)
+            .map(|(first, km)| (first.month0() as usize, km))
+            .collect();
+        let points: Vec<(f64, f64)> = series
+            .iter()
+            .map(|&(month, km)| (month as f64, km))
+            .collect();
+        let values: Vec<f64> = series.iter().map(|&(_, km)| km).collect();
+        let mut averages = [None; 12];
+        for (&(month, _), average) in series
+            .iter()
+            .zip(moving_average(&values, MOVING_AVERAGE_MONTHS))
+        {
+            averages[month] = average;
+        }
+
+        Self {
+            lang,
+            days: summary.daily_distance_km(),
+            months: summary.monthly_distance_km(year),
+            trend_months: series.first().zip(series.last()).map(|(a, b)| (a.0, b.0)),
+            trend: LinearTrend::fit(&points),
+            averages,
+            top: summary.longest_rides(TOP_RIDES),
+            climb: summary.biggest_climb(),
+            highest: summary.highest_point(),
+            weeks: summary.weekly_intensity_hours(thresholds),
+        }
+    }
+
+    fn intensity(&self, intensity: Intensity) -> &'static str {
+        self.lang.text(intensity.name())
+    }
+
+    fn max_day_km(&self) -> f64 {
+        self.days.values().copied().fold(0.0, f64::max)
+    }
+
+    fn max_month_km(&self) -> f64 {
+        self.months.iter().copied().fold(0.0, f64::max)
+    }
+
+    /// The trend at each counted month, by month index.
+    fn trend_at(&self) -> Vec<(usize, f64)> {
+        match (self.trend, self.trend_months) {
+            (Some(trend), Some((first, last))) => (first..=last)
+                .map(|month| (month, trend.at(month as f64).max(0.0)))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The slope of the trend, e.g. "Trend: +38 km/month".
+    fn trend_text(&self) -> Option<String> {
+        let trend = self.trend?;
+        Some(self.lang.fill(
+            "Trend: {slope} km/month",
+            &[("slope", &self.lang.signed_number(trend.slope, 0))],
+        ))
+    }
+
+    fn month(&self, month: usize) -> &'static str {
+        self.lang.text(MONTHS[month])
+    }
+
+    fn title(&self, year: i32) -> String {
+        self.lang.fill("{year} in review", &[("year", &year)])
+    }
+
+    /// Rides, distance, elevation gain and moving time.
+    fn totals(&self, summary: &Summary) -> [String; 4] {
+        let lang = self.lang;
+        [
+            lang.count(summary.rides(), "ride", "rides"),
+            lang.fill(
+                "{km} km",
+                &[("km", &lang.number(summary.total_distance_km(), 0))],
+            ),
+            lang.fill(
+                "{elevation} m climbed",
+                &[("elevation", &lang.number(summary.total_elevation_gain(), 0))],
+            ),
+            lang.fill(
+                "{moving} moving",
+                &[("moving", &format_hours(summary.total_moving_seconds()))],
+            ),
+        ]
+    }
+
+    /// Rides, distance and elevation gain of a line of [`WeekSplit::rows`].
+    fn week_totals(&self, totals: DayTotals) -> [String; 3] {
+        let lang = self.lang;
+        [
+            lang.count(totals.rides, "ride", "rides"),
+            lang.fill("{km} km", &[("km", &lang.number(totals.distance_km, 0))]),
+            lang.fill(
+                "{elevation} m climbed",
+                &[("elevation", &lang.number(totals.elevation_gain, 0))],
+            ),
+        ]
+    }
+
+    /// Exploration cells of the year, against the previous year with rides.
+    fn exploration(&self, exploration: &ExplorationYear) -> String {
+        let lang = self.lang;
+        let mut text = lang.fill(
+            "Exploration: {cells} km², {new} never visited before",
+            &[
+                ("cells", &lang.number(exploration.cells as f64, 0)),
+                ("new", &lang.number(exploration.new_cells as f64, 0)),
+            ],
+        );
+        if let (Some(change), Some((year, _))) =
+            (exploration.change_percent(), exploration.previous)
+        {
+            text.push_str(", ");
+            text.push_str(&lang.fill(
+                "{change}% on {year}",
+                &[("change", &lang.signed_number(change, 0)), ("year", &year)],
+            ));
+        }
+        text
+    }
+}
+
+/// A review of the rides of `summary` as an HTML page with inline CSS and
+/// SVG charts, readable offline. `week` splits the same rides between the
+/// weekdays and the weekend. Rides are classified by intensity against
+/// `thresholds`; `exploration` is that of the year, when known. The trend and
+/// moving average of the monthly distance count the months up to `until`,
+/// so that an incomplete month can be left out.
+pub fn review_html(
+    year: i32,
+    summary: &Summary,
+    week: &WeekSplit,
+    thresholds: &IntensityThresholds,
+    exploration: Option<&ExplorationYear>,
+    until: NaiveDate,
+    lang: Lang,
+) -> String {
+    let figures = Figures::new(year, summary, thresholds, until, lang);
+    let mut html = String::new();
+
+    let title = figures.title(year);
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; color: #222; }}\n\
+         table {{ border-collapse: collapse; }}\n\
+         td, th {{ padding: 0.2em 0.8em; text-align: left; }}\n\
+         td.number {{ text-align: right; }}\n\
+         .totals span {{ display: inline-block; margin-right: 2em; font-size: 1.2em; }}\n\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n",
+        lang.code(),
+    );
+
+    html.push_str("<p class=\"totals\">");
+    for total in figures.totals(summary) {
+        let _ = write!(html, "<span>{total}</span>");
+    }
+    html.push_str("</p>\n");
+    let _ = writeln!(
+        html,
+        "<p>{}. {}.</p>",
+        lang.fill(
+            "Eddington number: {eddington}",
+            &[(
+                "eddington",
+                &format!("<strong>{}</strong>", summary.eddington_number())
+            )],
+        ),
+        lang.fill(
+            "Longest streak: {streak}",
+            &[(
+                "streak",
+                &format!(
+                    "<strong>{}</strong>",
+                    lang.count(summary.longest_streak(), "day", "days")
+                )
+            )],
+        ),
+    );
+    if let Some(exploration) = exploration {
+        let _ = writeln!(html, "<p>{}.</p>", figures.exploration(exploration));
+    }
+
+    // Rounded alike, the parts add up to their totals.
+    let rows = week.rounded(0).rows();
+    if !rows.is_empty() {
+        let _ = writeln!(
+            html,
+            "<h2>{}</h2>\n<table>",
+            lang.text("Weekdays and weekends")
+        );
+        for (name, part, totals) in rows {
+            let indent = if part {
+                " style=\"padding-left: 2em\""
+            } else {
+                ""
+            };
+            let _ = write!(html, "<tr><td{indent}>{}</td>", lang.text(name));
+            for total in figures.week_totals(totals) {
+                let _ = write!(html, "<td class=\"number\">{total}</td>");
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+    }
+
+    let _ = writeln!(html, "<h2>{}</h2>", lang.text("Distance per month"));
+    monthly_svg(&mut html, &figures);
+    if let Some(trend) = figures.trend_text() {
+        let _ = writeln!(
+            html,
+            "<p>{trend} <small>({})</small></p>",
+            lang.fill(
+                "dashed: trend, blue: {months}-month average",
+                &[("months", &MOVING_AVERAGE_MONTHS)],
+            )
+        );
+    }
+
+    let _ = writeln!(html, "<h2>{}</h2>", lang.text("Riding days"));
+    calendar_svg(&mut html, year, &figures);
+
+    let _ = writeln!(html, "<h2>{}</h2>\n<table>", lang.text("Longest rides"));
+    html.push_str("<tr>");
+    for heading in ["Date", "Name", "Distance", "Elevation"] {
+        let _ = write!(html, "<th>{}</th>", lang.text(heading));
+    }
+    html.push_str("</tr>\n");
+    for ride in &figures.top {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td class=\"number\">{} km</td>\
+             <td class=\"number\">{} m</td></tr>",
+            ride_date(ride),
+            escape(&ride.name),
+            lang.number(ride.distance_km, 1),
+            lang.number(ride.elevation_gain, 0),
+        );
+    }
+    html.push_str("</table>\n");
+
+    if !figures.weeks.is_empty() {
+        let _ = writeln!(
+            html,
+            "<h2>{}</h2>\n<table>",
+            lang.text("Hours per intensity")
+        );
+        let _ = write!(html, "<tr><th>{}</th>", lang.text("Week of"));
+        for (intensity, color) in Intensity::ALL.into_iter().zip(INTENSITY_COLORS) {
+            let _ = write!(
+                html,
+                "<th><span style=\"color: {color}\">●</span> {}</th>",
+                figures.intensity(intensity)
+            );
+        }
+        html.push_str("</tr>\n");
+        for (monday, hours) in &figures.weeks {
+            let _ = write!(html, "<tr><td>{monday}</td>");
+            for hours in hours {
+                let _ = write!(
+                    html,
+                    "<td class=\"number\">{}</td>",
+                    format_hours(hours * 3600.0)
+                );
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+    }
+
+    if let Some(climb) = figures.climb {
+        let _ = writeln!(
+            html,
+            "<h2>{}</h2>\n<p>{}.</p>",
+            lang.text("Biggest climb"),
+            lang.fill(
+                "{name} on {date}: {elevation} m over {distance} km",
+                &[
+                    ("name", &escape(&climb.name)),
+                    ("date", &ride_date(climb)),
+                    ("elevation", &lang.number(climb.elevation_gain, 0)),
+                    ("distance", &lang.number(climb.distance_km, 1)),
+                ],
+            ),
+        );
+    }
+
+    if let Some((ride, peak)) = figures.highest {
+        let _ = writeln!(
+            html,
+            "<h2>{}</h2>\n<p>{}.</p>",
+            lang.text("Highest point"),
+            lang.fill(
+                "{elevation} m at km {km} of {name} on {date}",
+                &[
+                    ("elevation", &lang.number(peak.elevation_m, 0)),
+                    ("km", &lang.number(peak.distance_km, 1)),
+                    ("name", &escape(&ride.name)),
+                    ("date", &ride_date(ride)),
+                ],
+            ),
+        );
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn monthly_svg(html: &mut String, figures: &Figures) {
+    const BAR: f64 = 40.0;
+    const HEIGHT: f64 = 150.0;
+    let max = figures.max_month_km();
+
+    let _ = writeln!(
+        html,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">",
+        BAR * 12.0,
+        HEIGHT + 40.0,
+    );
+    for (month, km) in figures.months.iter().enumerate() {
+        let height = if max > 0.0 { km / max * HEIGHT } else { 0.0 };
+        let x = month as f64 * BAR;
+        let _ = writeln!(
+            html,
+            "<rect x=\"{:.0}\" y=\"{:.1}\" width=\"{:.0}\" height=\"{height:.1}\" fill=\"#239a3b\">\
+             <title>{} {} km</title></rect>",
+            x + 4.0,
+            HEIGHT + 15.0 - height,
+            BAR - 8.0,
+            figures.month(month),
+            figures.lang.number(*km, 0),
+        );
+        let _ = writeln!(
+            html,
+            "<text x=\"{:.0}\" y=\"{:.0}\" font-size=\"11\" text-anchor=\"middle\">{}</text>",
+            x + BAR / 2.0,
+            HEIGHT + 12.0 - height,
+            figures.lang.number(*km, 0),
+        );
+        let _ = writeln!(
+            html,
+            "<text x=\"{:.0}\" y=\"{:.0}\" font-size=\"12\" text-anchor=\"middle\">{}</text>",
+            x + BAR / 2.0,
+            HEIGHT + 32.0,
+            figures.month(month),
+        );
+    }
+    // Both lines go through the middle of the bars, clipped to the chart.
+    let points = |values: &[(usize, f64)]| {
+        values
+            .iter()
+            .map(|&(month, km)| {
+                let height = if max > 0.0 {
+                    km.min(max) / max * HEIGHT
+                } else {
+                    0.0
+                };
+                format!(
+                    "{:.0},{:.1}",
+                    month as f64 * BAR + BAR / 2.0,
+                    HEIGHT + 15.0 - height
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let averages: Vec<(usize, f64)> = (0..12)
+        .filter_map(|month| Some((month, figures.averages[month]?)))
+        .collect();
+    if averages.len() > 1 {
+        let _ = writeln!(
+            html,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"#4a90d9\" stroke-width=\"2\"/>",
+            points(&averages),
+        );
+    }
+    let trend = figures.trend_at();
+    if !trend.is_empty() {
+        let _ = writeln!(
+            html,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"#d73a49\" stroke-width=\"2\" \
+             stroke-dasharray=\"6 4\"/>",
+            points(&trend),
+        );
+    }
+    html.push_str("</svg>\n");
+}
+
+fn calendar_svg(html: &mut String, year: i32, figures: &Figures) {
+    const CELL: usize = 12;
+    let max = figures.max_day_km();
+
+    let _ = writeln!(
+        html,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">",
+        CELL * 54,
+        CELL * 7,
+    );
+    for (date, week, weekday) in calendar(year) {
+        let km = figures.days.get(&date).copied().unwrap_or_default();
+        let _ = writeln!(
+            html,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\">\
+             <title>{date} {} km</title></rect>",
+            week * CELL,
+            weekday * CELL,
+            CELL - 2,
+            CELL - 2,
+            HEAT_COLORS[heat_level(km, max)],
+            figures.lang.number(km, 0),
+        );
+    }
+    html.push_str("</svg>\n");
+}
+
+/// The same review as [`review_html`], as plain text.
+pub fn review_text(
+    year: i32,
+    summary: &Summary,
+    week: &WeekSplit,
+    thresholds: &IntensityThresholds,
+    exploration: Option<&ExplorationYear>,
+    until: NaiveDate,
+    lang: Lang,
+) -> String {
+    let figures = Figures::new(year, summary, thresholds, until, lang);
+    let mut text = String::new();
+
+    let _ = writeln!(text, "{}\n", figures.title(year));
+    let _ = writeln!(text, "{}", figures.totals(summary).join(", "));
+    let _ = writeln!(
+        text,
+        "{}, {}",
+        lang.fill(
+            "Eddington number: {eddington}",
+            &[("eddington", &summary.eddington_number())],
+        ),
+        lang.fill(
+            "longest streak: {streak}",
+            &[(
+                "streak",
+                &lang.count(summary.longest_streak(), "day", "days")
+            )],
+        ),
+    );
+    if let Some(exploration) = exploration {
+        let _ = writeln!(text, "{}", figures.exploration(exploration));
+    }
+
+    let rows = week.rounded(0).rows();
+    if !rows.is_empty() {
+        let _ = writeln!(text, "\n{}", lang.text("Weekdays and weekends"));
+        for (name, part, totals) in rows {
+            let _ = writeln!(
+                text,
+                "{}{}{}",
+                if part { "  " } else { "" },
+                lang.fill("{name}: ", &[("name", &lang.text(name))]),
+                figures.week_totals(totals).join(", "),
+            );
+        }
+    }
+
+    let _ = writeln!(text, "\n{}", lang.text("Distance per month"));
+    let max = figures.max_month_km();
+    let month_width = (0..12)
+        .map(|month| figures.month(month).chars().count())
+        .max()
+        .unwrap_or_default();
+    let trend = figures.trend_at();
+    for (month, km) in figures.months.iter().enumerate() {
+        let width = if max > 0.0 {
+            (km / max * BAR_WIDTH as f64).round() as usize
+        } else {
+            0
+        };
+        let mut bar: Vec<char> = format!("{:<BAR_WIDTH$}", "#".repeat(width))
+            .chars()
+            .collect();
+        let column = |km: f64| {
+            let column = if max > 0.0 {
+                km / max * BAR_WIDTH as f64
+            } else {
+                0.0
+            };
+            (column.round() as usize).clamp(1, BAR_WIDTH) - 1
+        };
+        if let Some(average) = figures.averages[month] {
+            bar[column(average)] = '|';
+        }
+        if let Some(&(_, km)) = trend.iter().find(|&&(counted, _)| counted == month) {
+            bar[column(km)] = '*';
+        }
+        let _ = writeln!(
+            text,
+            "{:<month_width$} {} {} km",
+            figures.month(month),
+            bar.into_iter().collect::<String>(),
+            lang.number(*km, 0),
+        );
+    }
+    if let Some(trend) = figures.trend_text() {
+        let _ = writeln!(
+            text,
+            "{trend} ({})",
+            lang.fill(
+                "* trend, | {months}-month average",
+                &[("months", &MOVING_AVERAGE_MONTHS)],
+            )
+        );
+    }
+
+    let _ = writeln!(text, "\n{}", lang.text("Riding days"));
+    let max = figures.max_day_km();
+    let mut grid = vec![vec![' '; 54]; 7];
+    for (date, week, weekday) in calendar(year) {
+        let km = figures.days.get(&date).copied().unwrap_or_default();
+        grid[weekday][week] = HEAT_CHARS[heat_level(km, max)];
+    }
+    for row in grid {
+        text.push_str(row.into_iter().collect::<String>().trim_end());
+        text.push('\n');
+    }
+
+    let _ = writeln!(text, "\n{}", lang.text("Longest rides"));
+    for ride in &figures.top {
+        let _ = writeln!(
+            text,
+            "{:<10} {:>7} km {:>6} m  {}",
+            ride_date(ride),
+            lang.number(ride.distance_km, 1),
+            lang.number(ride.elevation_gain, 0),
+            ride.name,
+        );
+    }
+
+    if !figures.weeks.is_empty() {
+        let _ = writeln!(text, "\n{}", lang.text("Hours per intensity"));
+        let _ = write!(text, "{:<10}", lang.text("Week of"));
+        let widths =
+            Intensity::ALL.map(|intensity| figures.intensity(intensity).chars().count().max(5));
+        for (intensity, width) in Intensity::ALL.into_iter().zip(widths) {
+            let _ = write!(text, " {:>width$}", figures.intensity(intensity));
+        }
+        text.push('\n');
+        for (monday, hours) in &figures.weeks {
+            let _ = write!(text, "{:<10}", monday.to_string());
+            for (hours, width) in hours.iter().zip(widths) {
+                let _ = write!(text, " {:>width$}", format_hours(hours * 3600.0));
+            }
+            text.push('\n');
+        }
+    }
+
+    if let Some(climb) = figures.climb {
+        let _ = writeln!(
+            text,
+            "\n{}",
+            lang.fill(
+                "Biggest climb: {name} on {date}, {elevation} m over {distance} km",
+                &[
+                    ("name", &climb.name),
+                    ("date", &ride_date(climb)),
+                    ("elevation", &lang.number(climb.elevation_gain, 0)),
+                    ("distance", &lang.number(climb.distance_km, 1)),
+                ],
+            ),
+        );
+    }
+    if let Some((ride, peak)) = figures.highest {
+        let _ = writeln!(
+            text,
+            "{}",
+            lang.fill(
+                "Highest point: {elevation} m at km {km} of {name} on {date}",
+                &[
+                    ("elevation", &lang.number(peak.elevation_m, 0)),
+                    ("km", &lang.number(peak.distance_km, 1)),
+                    ("name", &ride.name),
+                    ("date", &ride_date(ride)),
+                ],
+            ),
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::Activity;
+    use crate::stats::{DEFAULT_WEEKEND, week_split};
+
+    fn end_of_2024() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()
+    }
+
+    #[test]
+    fn calendar_starts_on_monday() {
+        // 2024-01-01 was a Monday, 2025-01-01 a Wednesday.
+        let days = calendar(2024);
+        assert_eq!(days.len(), 366);
+        assert_eq!((days[0].1, days[0].2), (0, 0));
+        assert_eq!((days[7].1, days[7].2), (1, 0));
+
+        let days = calendar(2025);
+        assert_eq!((days[0].1, days[0].2), (0, 2));
+        assert_eq!((days[5].1, days[5].2), (1, 0));
+    }
+
+    #[test]
+    fn french_review() {
+        let text = review_text(
+            2024,
+            &Summary::default(),
+            &WeekSplit::default(),
+            &Default::default(),
+            None,
+            end_of_2024(),
+            Lang::Fr,
+        );
+        assert!(text.starts_with("2024 en revue\n"));
+        assert!(text.contains("0 sortie, 0 km, 0 m de dénivelé"), "{text}");
+        assert!(text.contains("\njanv. "));
+        assert!(
+            review_html(
+                2024,
+                &Summary::default(),
+                &WeekSplit::default(),
+                &Default::default(),
+                None,
+                end_of_2024(),
+                Lang::Fr
+            )
+            .contains("<html lang=\"fr\">")
+        );
+    }
+
+    #[test]
+    fn exploration_is_compared_with_the_year_before() {
+        let exploration = ExplorationYear {
+            year: 2024,
+            cells: 150,
+            new_cells: 40,
+            previous: Some((2022, 120)),
+        };
+        let text = review_text(
+            2024,
+            &Summary::default(),
+            &WeekSplit::default(),
+            &Default::default(),
+            Some(&exploration),
+            end_of_2024(),
+            Lang::En,
+        );
+        assert!(
+            text.contains("Exploration: 150 km², 40 never visited before, +25% on 2022\n"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn heat_levels() {
+        assert_eq!(heat_level(0.0, 100.0), 0);
+        assert_eq!(heat_level(1.0, 100.0), 1);
+        assert_eq!(heat_level(50.0, 100.0), 2);
+        assert_eq!(heat_level(100.0, 100.0), 4);
+    }
+
+    #[test]
+    fn empty_reviews() {
+        let html = review_html(
+            2024,
+            &Summary::default(),
+            &WeekSplit::default(),
+            &Default::default(),
+            None,
+            end_of_2024(),
+            Lang::En,
+        );
+        assert!(html.contains("0 rides"));
+        assert!(!html.contains("Biggest climb"));
+        assert!(
+            review_text(
+                2024,
+                &Summary::default(),
+                &WeekSplit::default(),
+                &Default::default(),
+                None,
+                end_of_2024(),
+                Lang::En
+            )
+            .contains("Eddington number: 0")
+        );
+    }
+
+    #[test]
+    fn monthly_distance_has_a_trend() {
+        let ride = |month: u32, distance_km: f64| Activity {
+            start: Some(
+                chrono::DateTime::parse_from_rfc3339(&format!("2024-{month:02}-10T08:00:00Z"))
+                    .unwrap(),
+            ),
+            distance_km,
+            ..Default::default()
+        };
+        // Growing from March, with a rest in May and a ride on the
+        // incomplete current month.
+        let summary = Summary::new(&[
+            ride(3, 100.0),
+            ride(4, 150.0),
+            ride(6, 250.0),
+            ride(7, 300.0),
+            ride(8, 20.0),
+        ]);
+        let until = NaiveDate::from_ymd_opt(2024, 7, 31).unwrap();
+        let text = review_text(
+            2024,
+            &summary,
+            &WeekSplit::default(),
+            &Default::default(),
+            None,
+            until,
+            Lang::En,
+        );
+        assert!(text.contains("Trend: +50 km/month"), "{text}");
+        // The average from May, the trend from March to July.
+        let row = |month: &str| {
+            text.lines()
+                .find(|line| line.starts_with(month))
+                .unwrap()
+                .to_string()
+        };
+        assert!(
+            row("Mar").contains('*') && !row("Mar").contains('|'),
+            "{text}"
+        );
+        assert!(row("May").contains('|'), "{text}");
+        assert!(!row("Aug").contains('*'), "{text}");
+        assert!(!row("Jan").contains('*'), "{text}");
+
+        let html = review_html(
+            2024,
+            &summary,
+            &WeekSplit::default(),
+            &Default::default(),
+            None,
+            until,
+            Lang::En,
+        );
+        assert_eq!(html.matches("<polyline").count(), 2, "{html}");
+        assert!(html.contains("Trend: +50 km/month"));
+
+        // Counting August brings it down.
+        let until = NaiveDate::from_ymd_opt(2024, 8, 12).unwrap();
+        let text = review_text(
+            2024,
+            &summary,
+            &WeekSplit::default(),
+            &Default::default(),
+            None,
+            until,
+            Lang::Fr,
+        );
+        assert!(text.contains("Tendance : +9 km/mois"), "{text}");
+    }
+
+    #[test]
+    fn the_highest_point_is_told() {
+        let ride = |name: &str, day: u32, elevation_m: f64| Activity {
+            name: name.to_string(),
+            start: Some(
+                chrono::DateTime::parse_from_rfc3339(&format!("2024-07-{day:02}T08:00:00Z"))
+                    .unwrap(),
+            ),
+            distance_km: 90.0,
+            peak: Some(Peak {
+                elevation_m,
+                distance_km: 58.3,
+            }),
+            ..Default::default()
+        };
+        let summary = Summary::new(&[ride("Galibier", 14, 2_642.0), ride("Vercors", 20, 1_450.0)]);
+        let text = review_text(
+            2024,
+            &summary,
+            &WeekSplit::default(),
+            &Default::default(),
+            None,
+            end_of_2024(),
+            Lang::En,
+        );
+        assert!(
+            text.contains("Highest point: 2642 m at km 58.3 of Galibier on 2024-07-14"),
+            "{text}"
+        );
+        let html = review_html(
+            2024,
+            &summary,
+            &WeekSplit::default(),
+            &Default::default(),
+            None,
+            end_of_2024(),
+            Lang::Fr,
+        );
+        assert!(html.contains("Point culminant"), "{html}");
+    }
+
+    #[test]
+    fn weekdays_and_weekends_are_split() {
+        let ride = |day: u32, distance_km: f64| Activity {
+            start: Some(
+                chrono::DateTime::parse_from_rfc3339(&format!("2024-06-{day:02}T08:00:00+02:00"))
+                    .unwrap(),
+            ),
+            distance_km,
+            elevation_gain: 100.0,
+            ..Default::default()
+        };
+        // Monday and Tuesday commutes, a Wednesday ride and a weekend.
+        let rides = [
+            (ride(10, 12.4), true),
+            (ride(11, 12.4), true),
+            (ride(12, 40.4), false),
+            (ride(15, 80.4), false),
+            (ride(16, 60.4), false),
+        ];
+        let summary = Summary::new(rides.iter().map(|(activity, _)| activity));
+        let week = week_split(
+            rides.iter().map(|(activity, commute)| (activity, *commute)),
+            &DEFAULT_WEEKEND,
+        );
+        let text = review_text(
+            2024,
+            &summary,
+            &week,
+            &Default::default(),
+            None,
+            end_of_2024(),
+            Lang::En,
+        );
+        assert!(
+            text.contains(
+                "Weekdays and weekends\n\
+                 Weekdays: 3 rides, 65 km, 300 m climbed\n  \
+                 commutes: 2 rides, 25 km, 200 m climbed\n  \
+                 leisure: 1 ride, 40 km, 100 m climbed\n\
+                 Weekends: 2 rides, 141 km, 200 m climbed\n  \
+                 commutes: 0 rides, 0 km, 0 m climbed\n  \
+                 leisure: 2 rides, 141 km, 200 m climbed\n\
+                 All leisure: 3 rides, 181 km, 300 m climbed\n"
+            ),
+            "{text}"
+        );
+        let html = review_html(
+            2024,
+            &summary,
+            &week,
+            &Default::default(),
+            None,
+            end_of_2024(),
+            Lang::Fr,
+        );
+        assert!(html.contains("<h2>Semaine et week-end</h2>"), "{html}");
+    }
+}
@@ -0,0 +1,418 @@
+use std::io::{self, Write};
+
+use crate::activity::TrackPoint;
+use crate::climbs::detect_climbs;
+use crate::tuning::{Tuning, cumulative_distance};
+
+/// Spacing of the grid two rides are resampled on, in meters.
+pub const DIFF_STEP_M: f64 = 100.0;
+
+/// Longest stretch between two climbs over which the time difference is
+/// summed, in kilometers.
+pub const DIFF_STRETCH_KM: f64 = 5.0;
+
+/// Time gained or lost on a part of the route.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffSegment {
+    pub start_km: f64,
+    pub end_km: f64,
+    /// Seconds lost against the reference; negative when gained.
+    pub seconds: f64,
+    /// Whether the part is a climb of the reference.
+    pub climb: bool,
+}
+
+/// Where a ride gained or lost time against another ride of the same route.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RouteDiff {
+    /// Seconds behind the reference, every [`DIFF_STEP_M`] along the route,
+    /// by distance in kilometers.
+    pub deltas: Vec<(f64, f64)>,
+    /// The climbs of the reference and the stretches between them, most
+    /// time gained or lost first.
+    pub segments: Vec<DiffSegment>,
+}
+
+/// Compare the elapsed time of `ride` with that of `reference` along the
+/// route. Distances are taken as shares of the length of each ride, so that
+/// small detours and GPS noise do not shift the rest of the route. `None`
+/// when either ride has no timestamps.
+pub fn route_diff(ride: &[TrackPoint], reference: &[TrackPoint]) -> Option<RouteDiff> {
+    let ride = TimedProfile::new(ride)?;
+    let reference_profile = TimedProfile::new(reference)?;
+    let scale = ride.length_m() / reference_profile.length_m();
+    let delta_at = |distance_m: f64| {
+        ride.seconds_at(distance_m * scale) - reference_profile.seconds_at(distance_m)
+    };
+
+    let length_m = reference_profile.length_m();
+    let steps = (length_m / DIFF_STEP_M).ceil() as usize;
+    let deltas = (0..=steps)
+        .map(|step| {
+            let distance_m = (step as f64 * DIFF_STEP_M).min(length_m);
+            (distance_m / 1_000.0, delta_at(distance_m))
+        })
+        .collect();
+
+    let mut parts = vec![];
+    let mut position_m = 0.0;
+    for (bottom_m, top_m) in reference_profile.climbs(reference) {
+        parts.extend(stretches(position_m, bottom_m).map(|(start, end)| (start, end, false)));
+        parts.push((bottom_m, top_m, true));
+        position_m = top_m;
+    }
+    parts.extend(stretches(position_m, length_m).map(|(start, end)| (start, end, false)));
+    let mut segments: Vec<DiffSegment> = parts
+        .into_iter()
+        .filter(|&(start_m, end_m, _)| end_m > start_m)
+        .map(|(start_m, end_m, climb)| DiffSegment {
+            start_km: start_m / 1_000.0,
+            end_km: end_m / 1_000.0,
+            seconds: delta_at(end_m) - delta_at(start_m),
+            climb,
+        })
+        .collect();
+    segments.sort_by(|a, b| b.seconds.abs().total_cmp(&a.seconds.abs()));
+
+    Some(RouteDiff { deltas, segments })
+}
+
+/// `start_m` to `end_m` cut in equal stretches of at most
+/// [`DIFF_STRETCH_KM`].
+fn stretches(start_m: f64, end_m: f64) -> impl Iterator<Item = (f64, f64)> {
+    let count = ((end_m - start_m) / (DIFF_STRETCH_KM * 1_000.0))
+        .ceil()
+        .max(1.0) as usize;
+    let length = (end_m - start_m) / count as f64;
+    (0..count).map(move |i| {
+        (
+            start_m + i as f64 * length,
+            start_m + (i + 1) as f64 * length,
+        )
+    })
+}
+
+/// Distance from the start and elapsed time at each timed point.
+struct TimedProfile {
+    samples: Vec<(f64, f64)>,
+    /// Distance from the start at each kept point, to place the climbs.
+    positions: Vec<((f64, f64), f64)>,
+}
+
+impl TimedProfile {
+    fn new(points: &[TrackPoint]) -> Option<Self> {
+        let start = points.iter().find_map(|point| point.time)?;
+        let mut samples = vec![];
+        let mut positions = vec![];
+        for (distance_m, point) in cumulative_distance(points, &Tuning::default()) {
+            positions.push(((point.lat, point.lon), distance_m));
+            if let Some(time) = point.time {
+                samples.push((distance_m, (time - start).as_seconds_f64()));
+            }
+        }
+        let profile = Self { samples, positions };
+        (profile.samples.len() > 1 && profile.length_m() > 0.0).then_some(profile)
+    }
+
+    fn length_m(&self) -> f64 {
+        self.samples
+            .last()
+            .map_or(0.0, |&(distance_m, _)| distance_m)
+    }
+
+    /// Elapsed time when `distance_m` was reached, interpolated between the
+    /// points around it.
+    fn seconds_at(&self, distance_m: f64) -> f64 {
+        value_at(&self.samples, distance_m)
+    }
+
+    /// Bottom and top of the climbs of `points`, as distances from the start.
+    fn climbs(&self, points: &[TrackPoint]) -> Vec<(f64, f64)> {
+        let position = |coordinates: (f64, f64), from: f64| {
+            self.positions
+                .iter()
+                .find(|&&(point, distance_m)| point == coordinates && distance_m >= from)
+                .map(|&(_, distance_m)| distance_m)
+        };
+        let mut from = 0.0;
+        detect_climbs(points, &Tuning::default())
+            .iter()
+            .filter_map(|climb| {
+                let bottom = position(climb.start, from)?;
+                let top = position(climb.end, bottom)?;
+                from = top;
+                Some((bottom, top))
+            })
+            .collect()
+    }
+}
+
+/// Value of a stream of `(distance, value)` samples at `distance_m`,
+/// interpolated between the samples around it.
+fn value_at(samples: &[(f64, f64)], distance_m: f64) -> f64 {
+    let after = samples.partition_point(|&(distance, _)| distance < distance_m);
+    match (samples.get(after.wrapping_sub(1)), samples.get(after)) {
+        (Some(&(d1, v1)), Some(&(d2, v2))) if d2 > d1 => {
+            v1 + (v2 - v1) * (distance_m - d1) / (d2 - d1)
+        }
+        (_, Some(&(_, v))) | (Some(&(_, v)), None) => v,
+        (None, None) => 0.0,
+    }
+}
+
+/// The streams of one ride every [`DIFF_STEP_M`] from its start, each
+/// `None` when the ride does not record it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AlignedStreams {
+    /// Elapsed time since the start.
+    pub seconds: Option<Vec<f64>>,
+    pub elevation_m: Option<Vec<f64>>,
+    pub heart_rate: Option<Vec<f64>>,
+    pub power: Option<Vec<f64>>,
+    /// Length of the whole ride.
+    pub length_km: f64,
+}
+
+impl AlignedStreams {
+    /// Speed over the step ending at row `row`, in km/h; `None` on the
+    /// first row and without timestamps.
+    pub fn speed_kmh(&self, row: usize, distances_km: &[f64]) -> Option<f64> {
+        let seconds = self.seconds.as_ref()?;
+        let previous = row.checked_sub(1)?;
+        let elapsed = seconds[row] - seconds[previous];
+        (elapsed > 0.0).then(|| (distances_km[row] - distances_km[previous]) / elapsed * 3_600.0)
+    }
+}
+
+/// Two rides side by side on the same distance grid, the rows stopping at
+/// the end of the shorter ride.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AlignedRides {
+    pub distances_km: Vec<f64>,
+    pub ride: AlignedStreams,
+    pub reference: AlignedStreams,
+}
+
+/// Resample the streams of `ride` and `reference` every [`DIFF_STEP_M`] of
+/// distance from their start, as [`route_diff`] does with the time.
+/// Unlike it, the distances are not stretched to the same length: each row
+/// is at the same distance on both rides. `None` when either has no length.
+pub fn align_rides(ride: &[TrackPoint], reference: &[TrackPoint]) -> Option<AlignedRides> {
+    let length = |points: &[TrackPoint]| {
+        cumulative_distance(points, &Tuning::default())
+            .last()
+            .map_or(0.0, |(distance_m, _)| distance_m)
+    };
+    let shorter_m = length(ride).min(length(reference));
+    if shorter_m <= 0.0 {
+        return None;
+    }
+    // Rounded so that a ride of a whole number of steps gets no last row of
+    // a few micrometers.
+    let steps = (shorter_m / DIFF_STEP_M - 1e-6).ceil() as usize;
+    let distances_m: Vec<f64> = (0..=steps)
+        .map(|step| (step as f64 * DIFF_STEP_M).min(shorter_m))
+        .collect();
+
+    let streams = |points: &[TrackPoint]| {
+        let start = points.iter().find_map(|point| point.time);
+        let stream = |value: &dyn Fn(&TrackPoint) -> Option<f64>| {
+            let samples: Vec<(f64, f64)> = cumulative_distance(points, &Tuning::default())
+                .filter_map(|(distance_m, point)| Some((distance_m, value(point)?)))
+                .collect();
+            (!samples.is_empty()).then(|| {
+                distances_m
+                    .iter()
+                    .map(|&distance_m| value_at(&samples, distance_m))
+                    .collect()
+            })
+        };
+        AlignedStreams {
+            seconds: stream(&|point| Some((point.time? - start?).as_seconds_f64())),
+            elevation_m: stream(&|point| point.elevation),
+            heart_rate: stream(&|point| point.heart_rate),
+            power: stream(&|point| point.power),
+            length_km: length(points) / 1_000.0,
+        }
+    };
+    Some(AlignedRides {
+        ride: streams(ride),
+        reference: streams(reference),
+        distances_km: distances_m
+            .iter()
+            .map(|distance_m| distance_m / 1_000.0)
+            .collect(),
+    })
+}
+
+/// Write two aligned rides as CSV, one row per distance step, with the
+/// columns of the streams each ride has. The names of the rides, and where
+/// the longer one was cut, go in `#` comments before the header.
+pub fn write_aligned_csv(
+    aligned: &AlignedRides,
+    names: (&str, &str),
+    out: &mut impl Write,
+) -> io::Result<()> {
+    writeln!(out, "# ride: {}", names.0)?;
+    writeln!(out, "# reference: {}", names.1)?;
+    let (ride_km, reference_km) = (aligned.ride.length_km, aligned.reference.length_km);
+    if (ride_km - reference_km).abs() * 1_000.0 >= DIFF_STEP_M {
+        writeln!(
+            out,
+            "# the rides are {ride_km:.1} and {reference_km:.1} km long; the rows stop at {:.1} km",
+            ride_km.min(reference_km)
+        )?;
+    }
+
+    // Name, value on each row and decimals of each column.
+    let mut columns: Vec<(String, Vec<Option<f64>>, usize)> = vec![];
+    for (prefix, streams) in [("ride", &aligned.ride), ("reference", &aligned.reference)] {
+        let speeds: Option<Vec<Option<f64>>> = streams.seconds.as_ref().map(|_| {
+            (0..aligned.distances_km.len())
+                .map(|row| streams.speed_kmh(row, &aligned.distances_km))
+                .collect()
+        });
+        let values = |values: &Option<Vec<f64>>| {
+            values
+                .as_ref()
+                .map(|values| values.iter().copied().map(Some).collect())
+        };
+        let named = [
+            ("seconds", values(&streams.seconds), 1),
+            ("speed_kmh", speeds, 1),
+            ("elevation_m", values(&streams.elevation_m), 1),
+            ("heart_rate_bpm", values(&streams.heart_rate), 0),
+            ("power_w", values(&streams.power), 0),
+        ];
+        for (name, values, decimals) in named {
+            if let Some(values) = values {
+                columns.push((format!("{prefix}_{name}"), values, decimals));
+            }
+        }
+    }
+
+    let mut header = vec!["distance_km".to_string()];
+    header.extend(columns.iter().map(|(name, _, _)| name.clone()));
+    writeln!(out, "{}", header.join(","))?;
+    for (row, distance_km) in aligned.distances_km.iter().enumerate() {
+        let mut fields = vec![format!("{distance_km:.3}")];
+        fields.extend(columns.iter().map(|(_, values, decimals)| {
+            values[row].map_or(String::new(), |value| format!("{value:.decimals$}"))
+        }));
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, TimeDelta};
+
+    /// Points going north every 100 m at the given elevations, `seconds`
+    /// apart.
+    fn ride(elevations: &[f64], seconds: impl Fn(usize) -> f64) -> Vec<TrackPoint> {
+        let start = DateTime::parse_from_rfc3339("2024-06-01T07:00:00Z").unwrap();
+        elevations
+            .iter()
+            .enumerate()
+            .map(|(i, &elevation)| TrackPoint {
+                lat: 45.0 + i as f64 * 100.0 / 111_195.08,
+                lon: 5.0,
+                elevation: Some(elevation),
+                time: Some(start + TimeDelta::milliseconds((seconds(i) * 1_000.0) as i64)),
+                segment_start: i == 0,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_uniformly_slower_ride_loses_time_linearly() {
+        // 12 km at 30 km/h, against the same route 5% slower.
+        let reference = ride(&[100.0; 121], |i| i as f64 * 12.0);
+        let slower = ride(&[100.0; 121], |i| i as f64 * 12.0 * 1.05);
+
+        let diff = route_diff(&slower, &reference).unwrap();
+
+        assert!((diff.deltas.last().unwrap().0 - 12.0).abs() < 0.001);
+        assert!((diff.deltas[1].0 - 0.1).abs() < 1e-9);
+        for &(km, seconds) in &diff.deltas {
+            assert!((seconds - km * 120.0 * 0.05).abs() < 0.01, "{km} {seconds}");
+        }
+        // Three stretches of 4 km, each 24 s slower.
+        assert_eq!(diff.segments.len(), 3);
+        for segment in &diff.segments {
+            assert!((segment.end_km - segment.start_km - 4.0).abs() < 0.001);
+            assert!((segment.seconds - 24.0).abs() < 0.01);
+            assert!(!segment.climb);
+        }
+    }
+
+    #[test]
+    fn time_lost_on_a_climb_is_found() {
+        // 2 km flat, 2 km at 5%, 2 km flat; the ride is 60 s slower on the
+        // climb only.
+        let elevations: Vec<f64> = (0..=60)
+            .map(|i| 100.0 + (i.clamp(20, 40) - 20) as f64 * 5.0)
+            .collect();
+        let reference = ride(&elevations, |i| i as f64 * 20.0);
+        let slower = ride(&elevations, |i| {
+            i as f64 * 20.0 + (i.clamp(20, 40) - 20) as f64 * 3.0
+        });
+
+        let diff = route_diff(&slower, &reference).unwrap();
+
+        let worst = diff.segments[0];
+        assert!(worst.climb);
+        assert!((worst.start_km - 2.0).abs() < 0.001 && (worst.end_km - 4.0).abs() < 0.001);
+        assert!((worst.seconds - 60.0).abs() < 0.01);
+        assert!(
+            diff.segments[1..]
+                .iter()
+                .all(|segment| segment.seconds.abs() < 0.01)
+        );
+        assert!((diff.deltas.last().unwrap().1 - 60.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn aligned_rides_stop_at_the_shorter_one() {
+        // 1 km at 36 km/h with a heart rate, against 1.5 km without.
+        let mut ride = ride(&[100.0; 11], |i| i as f64 * 10.0);
+        ride.iter_mut()
+            .for_each(|point| point.heart_rate = Some(140.0));
+        let reference = self::ride(&[100.0; 16], |i| i as f64 * 12.0);
+
+        let aligned = align_rides(&ride, &reference).unwrap();
+        assert_eq!(aligned.distances_km.len(), 11);
+        assert!((aligned.distances_km[10] - 1.0).abs() < 0.001);
+        assert_eq!(aligned.reference.heart_rate, None);
+        assert!((aligned.ride.speed_kmh(5, &aligned.distances_km).unwrap() - 36.0).abs() < 0.1);
+        assert_eq!(aligned.ride.speed_kmh(0, &aligned.distances_km), None);
+
+        let mut csv = vec![];
+        write_aligned_csv(&aligned, ("Monday", "Tuesday"), &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "# ride: Monday");
+        assert!(lines[2].contains("the rows stop at 1.0 km"), "{csv}");
+        assert_eq!(
+            lines[3],
+            "distance_km,ride_seconds,ride_speed_kmh,ride_elevation_m,ride_heart_rate_bpm,\
+             reference_seconds,reference_speed_kmh,reference_elevation_m"
+        );
+        assert_eq!(lines[4], "0.000,0.0,,100.0,140,0.0,,100.0");
+        assert_eq!(lines[5], "0.100,10.0,36.0,100.0,140,12.0,30.0,100.0");
+        assert_eq!(lines.len(), 4 + 11);
+    }
+
+    #[test]
+    fn untimed_rides_are_not_compared() {
+        let timed = ride(&[100.0; 3], |i| i as f64);
+        let mut untimed = timed.clone();
+        untimed.iter_mut().for_each(|point| point.time = None);
+
+        assert_eq!(route_diff(&untimed, &timed), None);
+        assert_eq!(route_diff(&timed, &untimed), None);
+    }
+}
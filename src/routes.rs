@@ -0,0 +1,210 @@
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+use std::collections::HashSet;
+
+use crate::activity::TrackPoint;
+use crate::exploration::{grid_cell, pack};
+
+/// Side of the grid cells and spacing of the samples of a signature, in
+/// meters. A sample is near the other track when it lies in one of its
+/// cells or a neighbouring one, i.e. within 100 to 280 m.
+pub const ROUTE_CELL_M: f64 = 100.0;
+
+/// Share of each track near the other one for two rides to follow the same
+/// route.
+pub const MIN_ROUTE_SIMILARITY: f64 = 0.8;
+
+/// Degrees of latitude per meter, to widen the bounding boxes.
+const DEGREES_PER_METER: f64 = 1.0 / 111_320.0;
+
+/// Coarse geometry of a ride, computed once at load time so a ride can be
+/// compared with a thousand others while the interface waits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteSignature {
+    /// Cells of the points sampled every [`ROUTE_CELL_M`] along the track.
+    samples: Vec<(i32, i32)>,
+    cells: HashSet<u64>,
+    /// South-west and north-east corners, as (latitude, longitude).
+    bounds: ((f64, f64), (f64, f64)),
+    length_m: f64,
+}
+
+impl RouteSignature {
+    /// `None` for tracks of less than two points.
+    pub fn new(points: &[TrackPoint]) -> Option<Self> {
+        if points.len() < 2 {
+            return None;
+        }
+        let (first, last) = (&points[0], &points[points.len() - 1]);
+
+        let mut samples = vec![grid_cell(first.lat, first.lon, ROUTE_CELL_M)];
+        let mut length_m = 0.0;
+        // Distance from the last sample.
+        let mut travelled = 0.0;
+        for pair in points.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            let distance =
+                Haversine.distance(point!(x: a.lon, y: a.lat), point!(x: b.lon, y: b.lat));
+            let mut along = ROUTE_CELL_M - travelled;
+            while along <= distance {
+                let t = along / distance;
+                let (lat, lon) = (a.lat + (b.lat - a.lat) * t, a.lon + (b.lon - a.lon) * t);
+                samples.push(grid_cell(lat, lon, ROUTE_CELL_M));
+                along += ROUTE_CELL_M;
+            }
+            travelled = (travelled + distance) % ROUTE_CELL_M;
+            length_m += distance;
+        }
+        samples.push(grid_cell(last.lat, last.lon, ROUTE_CELL_M));
+        samples.dedup();
+
+        let (mut south_west, mut north_east) = ((first.lat, first.lon), (first.lat, first.lon));
+        for point in points {
+            south_west = (south_west.0.min(point.lat), south_west.1.min(point.lon));
+            north_east = (north_east.0.max(point.lat), north_east.1.max(point.lon));
+        }
+
+        Some(Self {
+            cells: samples.iter().copied().map(pack).collect(),
+            samples,
+            bounds: (south_west, north_east),
+            length_m,
+        })
+    }
+
+    /// Share of this track near `other`.
+    fn coverage_by(&self, other: &Self) -> f64 {
+        let near = self
+            .samples
+            .iter()
+            .filter(|&&(row, column)| {
+                (-1..=1).any(|dr| {
+                    (-1..=1).any(|dc| other.cells.contains(&pack((row + dr, column + dc))))
+                })
+            })
+            .count();
+        near as f64 / self.samples.len() as f64
+    }
+
+    /// Whether the bounding boxes, widened by two cells, overlap.
+    fn overlaps(&self, other: &Self) -> bool {
+        let margin = 2.0 * ROUTE_CELL_M * DEGREES_PER_METER;
+        let (a, b) = (self.bounds, other.bounds);
+        // A degree of longitude is shorter away from the equator.
+        let lon_margin = margin / a.0.0.abs().max(a.1.0.abs()).to_radians().cos().max(0.01);
+        a.0.0 - margin <= b.1.0
+            && b.0.0 - margin <= a.1.0
+            && a.0.1 - lon_margin <= b.1.1
+            && b.0.1 - lon_margin <= a.1.1
+    }
+
+    /// Share of each track near the other, the lower of the two, so a ride
+    /// covering only half of a loop does not match it. The direction of
+    /// travel does not matter.
+    pub fn similarity(&self, other: &Self) -> f64 {
+        let (shorter, longer) = if self.length_m < other.length_m {
+            (self.length_m, other.length_m)
+        } else {
+            (other.length_m, self.length_m)
+        };
+        // The cheap checks rule out most rides.
+        if shorter < longer / 2.0 || !self.overlaps(other) {
+            return 0.0;
+        }
+        self.coverage_by(other).min(other.coverage_by(self))
+    }
+}
+
+/// The rides of `others` following the same route as `route`, with their
+/// similarity, in the order given.
+pub fn matching_routes<'a, K>(
+    route: &RouteSignature,
+    others: impl IntoIterator<Item = (K, &'a RouteSignature)>,
+) -> Vec<(K, f64)> {
+    others
+        .into_iter()
+        .map(|(key, other)| (key, route.similarity(other)))
+        .filter(|&(_, similarity)| similarity >= MIN_ROUTE_SIMILARITY)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METERS_PER_DEGREE: f64 = 111_320.0;
+
+    /// A rectangle of `width_m` by 2 km starting at the south-west corner,
+    /// with a point every 20 m, shifted north by `offset_m`.
+    fn loop_points(width_m: f64, offset_m: f64) -> Vec<TrackPoint> {
+        let (lat0, lon0) = (45.0 + offset_m / METERS_PER_DEGREE, 5.0);
+        let lon_per_m = 1.0 / (METERS_PER_DEGREE * 45f64.to_radians().cos());
+        let corners = [
+            (0.0, 0.0),
+            (0.0, width_m),
+            (2_000.0, width_m),
+            (2_000.0, 0.0),
+            (0.0, 0.0),
+        ];
+        let mut points = vec![];
+        for pair in corners.windows(2) {
+            let ((n1, e1), (n2, e2)) = (pair[0], pair[1]);
+            let steps = (f64::abs(n2 - n1).max(f64::abs(e2 - e1)) / 20.0) as usize;
+            for i in 0..steps {
+                let t = i as f64 / steps as f64;
+                points.push(TrackPoint {
+                    lat: lat0 + (n1 + (n2 - n1) * t) / METERS_PER_DEGREE,
+                    lon: lon0 + (e1 + (e2 - e1) * t) * lon_per_m,
+                    ..Default::default()
+                });
+            }
+        }
+        points
+    }
+
+    fn signature(points: &[TrackPoint]) -> RouteSignature {
+        RouteSignature::new(points).unwrap()
+    }
+
+    #[test]
+    fn same_loop_in_both_directions() {
+        let loop_ = signature(&loop_points(1_000.0, 0.0));
+        let shifted = signature(&loop_points(1_000.0, 40.0));
+        let mut points = loop_points(1_000.0, 0.0);
+        points.reverse();
+        let reversed = signature(&points);
+
+        assert!(loop_.similarity(&shifted) > 0.95);
+        assert_eq!(loop_.similarity(&reversed), 1.0);
+    }
+
+    #[test]
+    fn other_routes_do_not_match() {
+        let narrow = signature(&loop_points(1_000.0, 0.0));
+        let wide = signature(&loop_points(3_000.0, 0.0));
+        // Out and back on the first half of the loop.
+        let mut half = loop_points(1_000.0, 0.0)[..150].to_vec();
+        half.extend(half.clone().into_iter().rev());
+        let half = signature(&half);
+
+        assert!(narrow.similarity(&wide) < MIN_ROUTE_SIMILARITY);
+        assert!(narrow.similarity(&half) < MIN_ROUTE_SIMILARITY);
+        assert_eq!(half.coverage_by(&narrow), 1.0);
+
+        let matches = matching_routes(
+            &narrow,
+            [("wide", &wide), ("half", &half), ("same", &narrow)],
+        );
+        assert_eq!(matches, [("same", 1.0)]);
+    }
+
+    #[test]
+    fn far_away_rides_are_ruled_out() {
+        let here = signature(&loop_points(1_000.0, 0.0));
+        let there = signature(&loop_points(1_000.0, 50_000.0));
+
+        assert!(!here.overlaps(&there));
+        assert_eq!(here.similarity(&there), 0.0);
+        assert_eq!(RouteSignature::new(&loop_points(1_000.0, 0.0)[..1]), None);
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{Context, Result, bail};
+
+use crate::activity::Activity;
+use crate::expr::{Expr, Kind, Parser};
+
+/// A tag given to every activity matching a condition, written as
+/// `tag "gravel" when name contains "gravel" or avg_speed < 20`.
+///
+/// Conditions are [`Expr`] conditions over the metric fields, such as
+/// `elevation_m / distance_km > 12`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagRule {
+    pub tag: String,
+    condition: Expr,
+}
+
+impl TagRule {
+    pub fn parse(rule: &str) -> Result<Self> {
+        let mut parser = Parser::new(rule, 0).with_context(|| match quoted_tag(rule) {
+            Some(tag) => format!("tag rule {tag:?}"),
+            None => format!("tag rule {rule:?}"),
+        })?;
+        let tag = match (
+            parser.keyword("tag"),
+            parser.string(),
+            parser.keyword("when"),
+        ) {
+            (true, Some(tag), true) => tag,
+            _ => bail!("tag rule {rule:?}: expected `tag \"<name>\" when <condition>`"),
+        };
+        let condition = parser
+            .rest(Kind::Condition)
+            .with_context(|| format!("tag rule {tag:?}"))?;
+
+        Ok(Self { tag, condition })
+    }
+
+    pub fn matches(&self, activity: &Activity) -> bool {
+        self.condition.holds(activity)
+    }
+}
+
+/// Name of a rule that does not tokenize, for its error message.
+fn quoted_tag(rule: &str) -> Option<&str> {
+    let rest = rule.trim_start().strip_prefix("tag")?.trim_start();
+    rest.strip_prefix('"')?.split('"').next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(name: &str, distance_km: f64, elevation_gain: f64) -> Activity {
+        Activity {
+            name: name.to_string(),
+            distance_km,
+            elevation_gain,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let rule = TagRule::parse(
+            r#"tag "gravel" when name contains "Gravel" or distance_km < 20 and elevation_per_km > 12"#,
+        )
+        .unwrap();
+
+        assert_eq!(rule.tag, "gravel");
+
+        assert!(rule.matches(&activity("Sunday gravel loop", 80.0, 0.0)));
+        assert!(rule.matches(&activity("Hills", 10.0, 200.0)));
+        assert!(!rule.matches(&activity("Hills", 30.0, 1000.0)));
+        assert!(!rule.matches(&activity("Flat", 10.0, 50.0)));
+    }
+
+    #[test]
+    fn parentheses_and_not() {
+        let rule =
+            TagRule::parse(r#"tag "long" when not (distance_km <= 100 or elevation_m == 0)"#)
+                .unwrap();
+
+        assert!(rule.matches(&activity("Audax", 200.0, 1500.0)));
+        assert!(!rule.matches(&activity("Audax", 200.0, 0.0)));
+    }
+
+    #[test]
+    fn missing_metrics_never_match() {
+        // No timestamps, so no average speed.
+        let rule = TagRule::parse(r#"tag "slow" when avg_speed < 20"#).unwrap();
+        assert!(!rule.matches(&activity("Ride", 20.0, 0.0)));
+    }
+
+    #[test]
+    fn errors_name_the_rule_and_column() {
+        let error = |condition: &str| {
+            let rule = format!("tag \"gravel\" when {condition}");
+            format!("{:#}", TagRule::parse(&rule).unwrap_err())
+        };
+
+        assert_eq!(
+            error("speed < 20"),
+            "tag rule \"gravel\": unknown field `speed` at column 19, expected one of name, \
+             distance_km, elevation_m, elevation_per_km, avg_speed, moving_h"
+        );
+        assert_eq!(
+            error("distance_km < 20 and"),
+            "tag rule \"gravel\": expected a value at column 39"
+        );
+        assert_eq!(
+            error("name contains \"gravel"),
+            "tag rule \"gravel\": unterminated string at column 33"
+        );
+        assert_eq!(
+            error("distance_km < \"far\""),
+            "tag rule \"gravel\": cannot compare a number with a string at column 31"
+        );
+        assert_eq!(
+            error("(distance_km < 20"),
+            "tag rule \"gravel\": missing `)` at column 36"
+        );
+        assert_eq!(
+            TagRule::parse("gravel when distance_km > 0")
+                .unwrap_err()
+                .to_string(),
+            "tag rule \"gravel when distance_km > 0\": expected `tag \"<name>\" when <condition>`"
+        );
+    }
+}
@@ -1,324 +1,9348 @@
-use anyhow::{Ok, Result};
-use clap::Parser;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
-use gpx::read;
+use anyhow::{Context, Result, anyhow};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, Days, FixedOffset, Local, Months, NaiveDate, Weekday};
+use clap::{Parser, Subcommand, ValueEnum};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::{
-    DefaultTerminal,
+    DefaultTerminal, Terminal,
+    backend::Backend,
     buffer::Buffer,
     layout::{Constraint, Layout, Rect},
-    style::{Modifier, Style, Stylize, palette::tailwind::SLATE},
+    style::{Color, Style, Styled, Stylize},
     symbols::{self, Marker},
-    text::{Line, Text},
+    text::{Line, Span, Text},
     widgets::{
-        Axis, Block, Borders, Chart, Dataset, HighlightSpacing, List, ListItem, ListState, Padding,
-        Paragraph, StatefulWidget, Widget, Wrap,
+        Axis, Bar, BarChart, BarGroup, Block, Borders, Chart, Clear, Dataset, HighlightSpacing,
+        List, ListItem, ListState, Padding, Paragraph, Sparkline, StatefulWidget, Widget, Wrap,
+        canvas::{Canvas, Line as CanvasLine, Points},
     },
 };
-use std::fs::File;
-use std::io::BufReader;
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
 
-use crate::{
-    gpx::{
-        elevation_profile_min_max, gpx_elevation_gain, gpx_elevation_profile, gpx_start_end_date,
-        gpx_track_name,
-    },
-    gpx_total_distance,
+use crate::activity::{Activity, ActivityId, TrackPoint};
+use crate::alternates::{DEFAULT_PREFERENCES, Preference, is_preferred};
+use crate::altitude::{DEFAULT_ALTITUDE_THRESHOLDS_M, time_above};
+use crate::anonymize::{Anonymization, anonymize, random_offset};
+use crate::bests::{BEST_WINDOWS_S, Bests, Sprint, all_time_bests, bests, detect_sprints};
+#[cfg(feature = "borders")]
+use crate::borders::{CountryDistance, countries_crossed, country_totals};
+use crate::brevet::{ControlStatus, ControlTime, check_controls, control_times};
+use crate::calendar::{CalendarGrid, HeatScale};
+use crate::climbs::{
+    Climb, ClimbGroup, DESCENDING_TREND_RIDES, DescendingSpeed, climb_rate, descending_speed,
+    descending_trend, detect_climbs, detect_descents, group_climbs,
+};
+use crate::commands::{Key, UserCommand, command_for, search};
+use crate::commute::detect_commutes;
+use crate::config::Config;
+use crate::debug_log::{DEBUG_LOG_FILE, init_debug_log};
+use crate::dem::Dem;
+use crate::distance_models::{DistanceModel, ModelDistances};
+use crate::exit::Failure;
+use crate::exploration::{
+    RoadTrace, exploration, exploration_by_year, new_km_by_year, new_road_km_from_traces,
+};
+use crate::gpx::{GradeModel, elevation_profile_min_max};
+use crate::history::History;
+use crate::i18n::Lang;
+use crate::intensity::{Intensity, IntensityThresholds, classify};
+use crate::intervals::{Intervals, detect_intervals};
+use crate::kml::export_kml;
+use crate::loader::{
+    LoadOptions, Loaded, Resolved, is_stdin, is_url, load_activities, load_activity, load_each,
+    load_readable, resolve_paths, spawn_loader,
+};
+use crate::lru::Lru;
+use crate::map::{MAX_MAP_ZOOM, MapView, mercator};
+#[cfg(feature = "images")]
+use crate::map_image::{KITTY_DELETE, MAX_IMAGE_SIDE, Protocol, encode_png, route_image};
+use crate::plan::{DEFAULT_PLAN_SPEED_KMH, DEFAULT_STOP_EVERY_KM, RidePlan, is_planned, plan_ride};
+use crate::privacy::{EXPOSURE_MAX_OTHERS, ExposedPlace, exposed_places};
+use crate::quality::{Problem, QualityScore, quality_score};
+use crate::reference_climbs::{
+    ClimbCheck, ReferenceClimb, check_reference_climbs, load_reference_climbs,
 };
+use crate::report::{
+    Column, ColumnExpr, Field, Format, JsonLine, check_column_exprs, default_columns,
+    default_fields, parse_column_expr, parse_field, sort_activities, sort_column, write_csv,
+    write_json, write_json_line, write_table,
+};
+use crate::review::{MONTHS, ReviewFormat, review_html, review_text};
+use crate::route_diff::{DiffSegment, RouteDiff, align_rides, route_diff, write_aligned_csv};
+use crate::routes::{RouteSignature, matching_routes};
+use crate::rules::TagRule;
+use crate::runs::{DEFAULT_RUN_HISTORY, RunTotals, record_run, write_run_history};
+use crate::scrub::{Sample, sample_at};
+use crate::sections::{Section, SectionLayout};
+use crate::share::ShareTemplate;
+use crate::sidecar::{Session, Sidecar, Store, archive_gpx};
+use crate::sparkline::{ascii_sparkline, braille_sparkline, unicode_locale};
+use crate::stats::{
+    CLUSTER_RADIUS_M, DEFAULT_WEEKEND, ENERGY_TREND_WEEKS, EnergyTotals, GRADE_BUCKETS,
+    GradeHistogram, Period, PeriodTotals, RIDE_LENGTH_BUCKET_KM, Recency, RideMetrics,
+    START_SLOT_HOURS, START_SLOTS, Summary, TerrainSplit, WeekSplit, device_usage,
+    grade_adjusted_km, grade_histogram, start_location_clusters, steepness_runs, terrain_breakdown,
+    week_split, weekly_distance_km,
+};
+use crate::surface::{SurfaceEstimate, estimate_surface};
+use crate::symbols::{AsciiWriter, Symbols, ascii_cell, to_ascii};
+use crate::theme::{Theme, ThemeName};
+use crate::thumbnail::export_thumbnails;
+use crate::tour::{Tour, group_tours};
+use crate::transport::detect_transport;
+use crate::tuning::{DistanceSource, SegmentJoins, TunedMetrics, Tuning, TuningParameter};
+use crate::validate::{Status, validate_file, validate_files, write_validation_report};
+use crate::virtual_rides::VIRTUAL_TAG;
+use crate::watch::{Exports, WATCH_INTERVAL, Watch};
+use crate::weather::WeatherCache;
+use crate::wind::{WindEstimate, estimate_wind};
+
+/// `--version`: the crate version and the commit it was built from.
+const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("CYCLEMETRICS_GIT_COMMIT"),
+    ")"
+);
+
+/// Number of start location clusters listed in the statistics overlay.
+const STATS_TOP_LOCATIONS: usize = 5;
+
+/// Rows of the start time table of the statistics overlay.
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Devices listed in the statistics overlay.
+const STATS_TOP_DEVICES: usize = 5;
+
+/// Pixels of a terminal cell when the terminal does not tell.
+#[cfg(feature = "images")]
+const DEFAULT_CELL_PIXELS: (u16, u16) = (8, 16);
+
+/// Pixels of an image covering `area`, at most [`MAX_IMAGE_SIDE`] wide or
+/// high.
+#[cfg(feature = "images")]
+fn image_size(area: Rect) -> (u32, u32) {
+    let (cell_width, cell_height) = crossterm::terminal::window_size()
+        .ok()
+        .filter(|size| size.width > 0 && size.columns > 0 && size.rows > 0)
+        .map_or(DEFAULT_CELL_PIXELS, |size| {
+            (size.width / size.columns, size.height / size.rows)
+        });
+    let width = u32::from(area.width) * u32::from(cell_width.max(1));
+    let height = u32::from(area.height) * u32::from(cell_height.max(1));
+    let shrink = (f64::from(width.max(height)) / f64::from(MAX_IMAGE_SIDE)).max(1.0);
+    (
+        ((f64::from(width) / shrink) as u32).max(1),
+        ((f64::from(height) / shrink) as u32).max(1),
+    )
+}
+
+/// Parts of the route listed under the time difference with the fastest
+/// ride.
+const ROUTE_DIFF_TOP_SEGMENTS: usize = 3;
+
+/// Problems listed per ride in the quality dashboard.
+const QUALITY_TOP_PROBLEMS: usize = 2;
+
+/// Ascents listed per climb in the climbs popup.
+const CLIMB_LEADERBOARD_TOP: usize = 10;
 
-const SELECTED_STYLE: Style = Style::new().add_modifier(Modifier::BOLD);
+/// Data quality scores from this up are shown as good, and below
+/// [`QUALITY_POOR`] as poor.
+const QUALITY_GOOD: u8 = 90;
+const QUALITY_POOR: u8 = 60;
+
+/// Weeks of distance drawn next to the grand total.
+const SPARKLINE_WEEKS: usize = 12;
+
+/// Reference climbs read next to the configuration file by default.
+const REFERENCE_CLIMBS_FILE: &str = "climbs.toml";
+
+/// Default share of the width given to the list pane, in percent.
+const DEFAULT_SPLIT: u16 = 50;
+/// Step used by `<` and `>` to resize the panes, in percent.
+const SPLIT_STEP: u16 = 5;
+/// Panes never get narrower than this; below twice this width the panes are
+/// stacked vertically instead.
+const MIN_PANE_WIDTH: u16 = 30;
+/// Below this width of the list, each ride takes two lines: its name, then
+/// its date, distance and elevation.
+const NARROW_LIST_WIDTH: u16 = 50;
+/// How often loaded files are added to the list when no key is pressed.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Where the interface reads its input from.
+trait EventSource {
+    /// The next event, waiting at most `timeout` for one.
+    fn next_event(&mut self, timeout: Duration) -> io::Result<Option<Event>>;
+}
+
+/// The events of the terminal the interface runs in.
+struct TerminalEvents;
+
+impl EventSource for TerminalEvents {
+    fn next_event(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        if event::poll(timeout)? {
+            event::read().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+/// Below this size only a message asking for a larger terminal is shown.
+const MIN_WIDTH: u16 = 80;
+const MIN_HEIGHT: u16 = 15;
 
 /// Compute the total track distance of one or more GPX files.
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version = VERSION, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Args {
-    /// Paths or glob patterns pointing to GPX files
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Paths or glob patterns pointing to GPX or FIT files, or `-` to read
+    /// one from the standard input
     #[arg(required = true)]
     gpx_files: Vec<PathBuf>,
+
+    /// Directory holding activity sidecars and the session state
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+
+    /// Configuration file (defaults to ~/.config/cyclemetrics/config.toml)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Leave files whose elevation looks GPS-derived out of elevation totals
+    #[arg(long)]
+    trusted_elevation_only: bool,
+
+    /// Directory of SRTM `.hgt` tiles used to correct elevations
+    #[arg(long, value_name = "DIR")]
+    dem: Option<PathBuf>,
+
+    /// Output format; anything but `tui` prints to stdout without a terminal
+    #[arg(long, value_enum, default_value_t)]
+    format: Format,
+
+    /// Print the metrics instead of opening the interface, as JSON unless
+    /// `--format` says otherwise; implied by reading the standard input
+    #[arg(long)]
+    no_tui: bool,
+
+    /// Largest GPX document read from the standard input, in bytes (256 MiB
+    /// by default)
+    #[arg(long, value_name = "BYTES")]
+    max_stdin_bytes: Option<u64>,
+
+    /// Fields of the JSON, CSV or table output, in order, such as
+    /// `date,name,distance,elevation,speed`
+    #[arg(long, value_name = "NAME,...", value_delimiter = ',', value_parser = parse_field)]
+    columns: Vec<&'static Field>,
+
+    /// Column computed from the metrics, added after the others, such as
+    /// `gain_per_km = elevation_m / distance_km`; may be repeated
+    #[arg(long, value_name = "NAME = EXPR", value_parser = parse_column_expr)]
+    column_expr: Vec<ColumnExpr>,
+
+    /// Order the JSON, CSV or table output by a column, or by an expression
+    /// column; `-NAME` for the descending order
+    #[arg(long, value_name = "[-]NAME", allow_hyphen_values = true)]
+    sort: Option<String>,
+
+    /// Width the table is fitted to, instead of that of the terminal
+    #[arg(long, value_name = "CHARS")]
+    width: Option<usize>,
+
+    /// CSV or JSON file of daily weather (date, temp_c, wind_kmh, precipitation_mm)
+    #[arg(long, value_name = "FILE")]
+    weather_cache: Option<PathBuf>,
+
+    /// TOML file of climbs with their official length and gain, which the
+    /// rides are measured against (defaults to `climbs.toml` next to the
+    /// configuration file)
+    #[arg(long, value_name = "FILE")]
+    reference_climbs: Option<PathBuf>,
+
+    /// Only list activities with this tag; may be repeated
+    #[arg(long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
+
+    /// Whether virtual rides (Zwift) are listed and exported
+    #[arg(long, value_enum, default_value_t)]
+    activity_type: ActivityType,
+
+    /// Only keep activities recorded by this device (the GPX creator,
+    /// `unknown` for files without one), ignoring case
+    #[arg(long, value_name = "NAME")]
+    creator: Option<String>,
+
+    /// Only keep the N most recent activities, after the other filters
+    #[arg(long, value_name = "N")]
+    latest: Option<usize>,
+
+    /// Keep the activities without a date along with the `--latest` ones
+    #[arg(long, requires = "latest")]
+    include_undated: bool,
+
+    /// Disable colors, as does setting the `NO_COLOR` environment variable
+    #[arg(long)]
+    no_color: bool,
+
+    /// Draw with ASCII characters only, as when the locale is not UTF-8, and
+    /// write the reports of the commands in ASCII too
+    #[arg(long, global = true)]
+    ascii: bool,
+
+    /// Draw all the rides of the list alike, instead of brighter the more
+    /// recent they are
+    #[arg(long)]
+    no_recency_colors: bool,
+
+    /// Colors of the interface, instead of the configured `theme` or the one
+    /// matching the terminal background
+    #[arg(long, value_enum)]
+    theme: Option<ThemeName>,
+
+    /// Language of the interface and the reviews, instead of the configured one
+    #[arg(long, value_enum, global = true)]
+    lang: Option<Lang>,
+
+    /// strftime format of the dates in the list (`%d-%m-%Y` in English)
+    #[arg(long, value_name = "FORMAT")]
+    date_format: Option<String>,
+
+    /// Whether the distance and time across the gaps between track segments
+    /// count, instead of the configured `segment_joins` (`ignore` by default)
+    #[arg(long, value_enum, value_name = "POLICY")]
+    segment_joins: Option<SegmentJoins>,
+
+    /// Keep the per-point detail of very large files, at the cost of memory
+    #[arg(long)]
+    full_detail: bool,
+
+    /// Which of the files of the same ride in different formats, such as a
+    /// FIT and a GPX, is listed, the others shown as its alternates: the
+    /// first of these that only one of them meets decides
+    #[arg(
+        long,
+        value_enum,
+        value_name = "PREFERENCE,...",
+        value_delimiter = ',',
+        default_values_t = DEFAULT_PREFERENCES,
+        global = true
+    )]
+    prefer: Vec<Preference>,
+
+    /// Leave the distance and time on ferries and trains out of the rides
+    /// and the totals
+    #[arg(long)]
+    exclude_transport: bool,
+
+    /// Speed on the flat of the ride plans of routes, in km/h
+    #[arg(long, value_name = "KM/H", default_value_t = DEFAULT_PLAN_SPEED_KMH, global = true)]
+    plan_speed: f64,
+
+    /// Distance between the bottle stops of the ride plans, in kilometers
+    #[arg(long, value_name = "KM", default_value_t = DEFAULT_STOP_EVERY_KM, global = true)]
+    stop_every: f64,
+
+    /// Distances of the brevet controls, in kilometers, such as
+    /// `90,174,261,360`, instead of the configured `controls`
+    #[arg(long, value_name = "KM,...", value_delimiter = ',', global = true)]
+    controls: Vec<f64>,
+
+    /// Functional threshold power, in watts, instead of the configured `ftp`
+    #[arg(long, value_name = "WATTS", global = true)]
+    ftp: Option<f64>,
+
+    /// Maximum heart rate, in bpm, instead of the configured `max_hr`
+    #[arg(long, value_name = "BPM", global = true)]
+    max_hr: Option<f64>,
+
+    /// Write the activities to a KML file, zipped as KMZ if the path ends in `.kmz`
+    #[arg(long, value_name = "PATH")]
+    export_kml: Option<PathBuf>,
+
+    /// Write an SVG miniature of the route of each activity to a directory
+    #[arg(long, value_name = "DIR")]
+    export_thumbnails: Option<PathBuf>,
+
+    /// Export the geometry without first listing the places where many
+    /// activities start or end outside of the privacy zones
+    #[arg(long)]
+    ack_privacy: bool,
+
+    /// Write the totals as a Prometheus textfile for the node_exporter
+    /// textfile collector
+    #[arg(long, value_name = "PATH")]
+    export_prometheus: Option<PathBuf>,
+
+    /// Write the activities to a CSV file, replacing the rows of the same
+    /// activities and keeping the others
+    #[arg(long, value_name = "PATH")]
+    export_csv: Option<PathBuf>,
+
+    /// Keep the CSV, Prometheus and SQLite exports up to date as the files
+    /// matching the patterns come, change and go, until interrupted
+    #[arg(long, conflicts_with = "latest")]
+    watch: bool,
+
+    /// Print the share text of each activity, from the configured
+    /// `share_template`, instead of opening the interface
+    #[arg(long)]
+    share: bool,
+
+    /// Print nothing but the errors; the exit status tells what went wrong
+    #[arg(long, global = true)]
+    quiet: bool,
+
+    /// Stop at the first file that cannot be read, instead of going on with
+    /// the others
+    #[arg(long, global = true)]
+    fail_fast: bool,
+
+    /// Log how each file is read and what the filters drop, as JSON lines,
+    /// to `debug.jsonl` in the data directory
+    #[arg(long, global = true)]
+    debug: bool,
+
+    /// Where `--debug` writes its log; implies `--debug`
+    #[arg(long, value_name = "PATH", global = true)]
+    debug_file: Option<PathBuf>,
+
+    /// Directory of map tiles, as `<zoom>/<x>/<y>.png`, drawn under the route
+    /// map; nothing is downloaded
+    #[cfg(feature = "images")]
+    #[arg(long, value_name = "DIR")]
+    tiles_dir: Option<PathBuf>,
+
+    /// Seconds allowed to download each file given as a URL (30 by default)
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Write the activities to a SQLite database, updating the existing rows
+    #[cfg(feature = "sqlite")]
+    #[arg(long, value_name = "PATH")]
+    export_sqlite: Option<PathBuf>,
+
+    /// Also write every track point to the SQLite database
+    #[cfg(feature = "sqlite")]
+    #[arg(long, requires = "export_sqlite")]
+    with_points: bool,
+}
+
+/// Activities kept, by where they were ridden.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum ActivityType {
+    /// Outdoor and virtual rides
+    #[default]
+    All,
+    /// Leave virtual rides out
+    Outdoor,
+    /// Only virtual rides
+    Virtual,
+}
+
+impl ActivityType {
+    fn matches(self, activity: &Activity) -> bool {
+        match self {
+            Self::All => true,
+            Self::Outdoor => !activity.virtual_ride,
+            Self::Virtual => activity.virtual_ride,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the version, commit and features of this build, the formats it
+    /// reads and where it keeps its files
+    Info,
+    /// Parse every file and report its problems, without loading anything;
+    /// fails if any file cannot be read
+    Validate {
+        /// Paths or glob patterns pointing to GPX files
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Print the metrics of every file as JSON
+    Metrics {
+        /// One JSON object per line and file, written as soon as the file is
+        /// read, in no particular order; failed files get an `error` status
+        #[arg(long)]
+        jsonl: bool,
+        /// Paths or glob patterns pointing to GPX files
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Write a summary of the rides of one year
+    Review {
+        /// Year of the rides to review
+        #[arg(long)]
+        year: i32,
+        /// File to write the review to, instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t)]
+        format: ReviewFormat,
+        /// Count the current month, still incomplete, in the trend of the
+        /// distance per month
+        #[arg(long)]
+        include_current_month: bool,
+        /// Paths or glob patterns pointing to GPX files
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Write a copy of a GPX file that can be shared: moved elsewhere on the
+    /// globe, without names, device or sensor data, with the same distances
+    /// and elevations
+    Anonymize {
+        input: PathBuf,
+        output: PathBuf,
+        /// Latitude and longitude added to the start, in degrees, instead of
+        /// a random offset
+        #[arg(long, value_name = "LAT,LON", value_parser = parse_offset, allow_hyphen_values = true)]
+        offset: Option<(f64, f64)>,
+        /// Turn the track clockwise around its start, in degrees
+        #[arg(
+            long,
+            value_name = "DEGREES",
+            default_value_t = 0.0,
+            allow_hyphen_values = true
+        )]
+        rotate: f64,
+        /// Move the timestamps to this day (YYYY-MM-DD), keeping the time of
+        /// day
+        #[arg(long)]
+        date: Option<NaiveDate>,
+        /// Keep the heart rate, cadence and power
+        #[arg(long)]
+        keep_sensors: bool,
+    },
+    /// Print the totals of the archive at the last runs of the interface,
+    /// with what changed from one run to the next
+    History,
+    /// Print the plan of each route or untimed track: the time to ride it at
+    /// `--plan-speed`, when each climb is reached and where to refill bottles
+    Plan {
+        /// Paths or glob patterns pointing to GPX files
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+}
+
+/// `LAT,LON` in degrees.
+fn parse_offset(value: &str) -> Result<(f64, f64), String> {
+    let (lat, lon) = value
+        .split_once(',')
+        .ok_or_else(|| format!("expected LAT,LON, got {value:?}"))?;
+    let parse = |number: &str| {
+        number
+            .trim()
+            .parse::<f64>()
+            .map_err(|err| format!("{number:?}: {err}"))
+    };
+    Ok((parse(lat)?, parse(lon)?))
 }
 
 #[derive(Debug)]
 pub struct App {
     file_list: FileList,
-    grand_total_km: f64,
+    trusted_elevation_only: bool,
+    /// Files skipped for holding an activity already listed.
+    duplicates: Vec<PathBuf>,
+    /// Paths skipped for leading to a file already matched by another one.
+    duplicate_paths: Vec<PathBuf>,
+    /// Baselines the selected ride is compared against.
+    summary: Summary,
+    /// Kilometers of each ride on roads not ridden before.
+    new_roads: BTreeMap<ActivityId, f64>,
+    weather: Option<WeatherCache>,
+    /// Climbs of known length and gain, which the rides are measured against.
+    reference_climbs: Vec<ReferenceClimb>,
+    config: Config,
+    /// File the configuration was read from, where the tuning is saved.
+    config_path: PathBuf,
+    /// Parameters the metrics of the loaded activities are computed with.
+    tuning: Tuning,
+    store: Store,
+    focus: Focus,
+    split: u16,
+    detail_scroll: u16,
+    /// Keys of the sections of the detail pane folded to their title.
+    folded: BTreeSet<&'static str>,
+    /// Where the sections of the detail pane were on the last frame.
+    detail_layout: SectionLayout,
+    show_stats: bool,
+    /// Day under the cursor of the calendar, while it is open over the
+    /// statistics.
+    calendar: Option<NaiveDate>,
+    /// Kilometer under the cursor of the elevation profile, while scrubbing
+    /// the selected ride.
+    scrub: Option<f64>,
+    show_routes: bool,
+    /// Row selected in the settings popup, while it is open.
+    settings: Option<usize>,
+    show_help: bool,
+    /// Command palette, while it is open.
+    palette: Option<Palette>,
+    lang: Lang,
+    theme: Theme,
+    /// Format of the dates in the list, instead of the one of the language.
+    date_format: Option<String>,
+    /// Flat speed and bottle stop spacing of the ride plans of routes.
+    plan_speed_kmh: f64,
+    stop_every_km: f64,
+    /// Times the route map is zoomed in on the middle of the route.
+    map_zoom: u32,
+    /// Cells of the route map left blank for an image on the last frame.
+    map_area: Option<Rect>,
+    /// Set when the terminal can show images.
+    #[cfg(feature = "images")]
+    images: Option<MapImages>,
+    /// Whether virtual rides count in the totals.
+    count_virtual: bool,
+    show_climbs: bool,
+    /// Whether the rate of climb is charted instead of the elevation.
+    show_climb_rate: bool,
+    /// Whether the detail compares the distance models.
+    show_distance_models: bool,
+    /// Distances of the rides under each model, computed once asked for
+    /// and kept for the session.
+    model_distances: BTreeMap<ActivityId, ModelDistances>,
+    /// Ride whose model distances are computed in the background.
+    model_distances_computing: Option<(ActivityId, Receiver<ModelDistances>)>,
+    /// Stages of a tour, while they are listed.
+    tour_view: Option<TourView>,
+    /// Row under the cursor of the quality dashboard, while it is open.
+    quality_view: Option<usize>,
+    /// Row under the cursor of the hidden view, while it is open.
+    hidden_view: Option<usize>,
+    /// Section the detail pane scrolls to on the next frame, once its
+    /// place is known.
+    section_jump: Option<&'static str>,
+    /// Whether the rides of a day are listed as one entry.
+    combine_days: bool,
+    /// Whether the rides of each month are listed under a header.
+    group_months: bool,
+    /// Days whose rides are listed under their entry.
+    expanded_days: BTreeSet<NaiveDate>,
+    /// Ascents of the climbs of every ride, grouped once all are loaded.
+    climb_groups: Vec<ClimbGroup<ActivityId>>,
+    /// Rides on the route of the selected one, cached for the popup.
+    route_matches: Option<RouteMatches>,
+    /// Metrics of the selected ride under the default tuning, cached for the
+    /// what-if table of the settings.
+    raw_metrics: Option<(ActivityId, TunedMetrics)>,
+    color: bool,
+    /// Whether the terminal lacks Unicode, for the footer sparkline.
+    ascii: bool,
+    /// Whether the rows of the list get brighter the more recent they are.
+    recency_colors: bool,
+    /// Day the recency of the rides is told from, moved on every tick so
+    /// that it follows midnight.
+    today: NaiveDate,
+    /// Activities the next commute toggle or archive applies to, instead of
+    /// the selected one.
+    marked: BTreeSet<ActivityId>,
+    /// Bounds set with `[` and `]`; the shown activities between them get a
+    /// subtotal. Kept as sort keys so they survive filtering and archiving.
+    range_start: Option<SortKey>,
+    range_end: Option<SortKey>,
+    /// Archived files, moved on exit.
+    archived: Vec<FileItem>,
+    history: History<Action>,
+    filter: Filter,
+    rules: Vec<TagRule>,
+    share: ShareTemplate,
+    weekend: Vec<Weekday>,
+    /// Elevations the time spent above is measured at, lowest first.
+    altitude_thresholds: Vec<f64>,
+    /// Text to copy to the clipboard once the frame is drawn.
+    clipboard: Option<String>,
+    /// Files still being loaded, until they all are.
+    loading: Option<Loading>,
+    /// Whether large files are read with their points; see
+    /// [`LoadOptions::full_detail`].
+    full_detail: bool,
+    /// See [`LoadOptions::prefer`].
+    prefer: Vec<Preference>,
+    /// Whether the ferry and train stretches are left out of the metrics.
+    exclude_transport: bool,
+    /// Files and patterns of the command line, matched again when every
+    /// file is read again.
+    sources: Vec<PathBuf>,
+    /// DEM tiles and download timeout the files were first read with.
+    dem_dir: Option<PathBuf>,
+    timeout: Option<Duration>,
+    /// Points of the activities viewed last, read again from their files.
+    details: Lru<ActivityId, Detail>,
+    /// Activity whose points are being read in the background.
+    detail_loading: Option<(ActivityId, Receiver<Result<Activity>>)>,
+    /// Errors of the files that could not be loaded.
+    failures: Vec<String>,
+    /// Id of the activity selected in the previous session, until it is
+    /// loaded.
+    restore: Option<String>,
+    /// Fields of the session written by a newer version, saved back as they
+    /// are.
+    session_unknown: toml::Table,
+    /// Totals of the archive at the last runs, this one included once the
+    /// files are loaded.
+    runs: Vec<RunTotals>,
+    /// Prompt reading text from the status line, while it is open.
+    prompt: Option<Prompt>,
+    /// Outcome of the last action, shown under the totals.
+    status: Option<String>,
     exit: bool,
 }
 
+/// How the route map is drawn as an image, and the one on screen.
+#[cfg(feature = "images")]
 #[derive(Debug)]
-struct FileList {
-    files: Vec<FileItem>,
-    state: ListState,
+struct MapImages {
+    protocol: Protocol,
+    tiles_dir: Option<PathBuf>,
+    placed: Option<(ActivityId, u32, Rect)>,
 }
 
-#[derive(Debug, Clone)]
-struct FileItem {
-    file_name: String,
-    distance: f64,
-    elevation: String,
-    elevation_profile: Vec<(f64, f64)>, // (distance, elevation)
+/// Rides following the route of one ride, itself included.
+#[derive(Debug)]
+struct RouteMatches {
+    id: ActivityId,
+    /// Files loaded when the matches were computed.
+    files: usize,
+    /// With their similarity.
+    rides: Vec<(ActivityId, f64)>,
 }
 
-pub fn run_cyclemetrics(args: Args) -> Result<()> {
-    let mut terminal = ratatui::init();
-    let result = App::default().run(&mut terminal, args);
-    ratatui::restore();
+/// Activities whose points are kept once read again, beyond those of the
+/// files too small to be worth releasing.
+const DETAIL_CACHE_SIZE: usize = 8;
 
-    result
+/// Activities arriving from the background loader.
+#[derive(Debug)]
+struct Loading {
+    receiver: Receiver<Result<Activity>>,
+    received: usize,
+    total: usize,
+    /// Whether the files are already in the list and read again for their
+    /// points, after a tuning change.
+    reload: bool,
 }
 
-impl Default for App {
-    fn default() -> Self {
-        Self {
-            file_list: FileList {
-                files: vec![],
-                state: ListState::default(),
-            },
-            grand_total_km: 0.0,
-            exit: false,
-        }
-    }
+/// Per-point data of an activity, which the list does not keep.
+#[derive(Debug)]
+struct Detail {
+    points: Vec<TrackPoint>,
+    elevation_profile: Vec<(f64, f64)>,
 }
 
-impl FileItem {
-    fn new(
-        file_name: String,
-        distance: f64,
-        elevation: String,
-        elevation_profile: Vec<(f64, f64)>,
-    ) -> Self {
-        Self {
-            file_name,
-            distance,
-            elevation,
-            elevation_profile,
-        }
-    }
+/// An undoable user action.
+#[derive(Debug, Clone)]
+struct Action {
+    description: String,
+    changes: Vec<Change>,
 }
 
-impl App {
-    pub fn run(mut self, terminal: &mut DefaultTerminal, args: Args) -> Result<()> {
-        // Iterate over the supplied paths / glob patterns
-        for gpx_path in &args.gpx_files {
-            // Resolve glob patterns if necessary
-            let files = glob::glob(gpx_path.to_str().unwrap())?;
-            for file_res in files {
-                let file_path = file_res?;
-
-                // Read the GPX file
-                let file = File::open(&file_path)?;
-                let reader = BufReader::new(file);
-                let gpx = read(reader)?;
-
-                // Compute distance
-                let distance_m = gpx_total_distance(&gpx);
-                let distance_km = distance_m / 1_000.0;
-
-                let name = gpx_track_name(&gpx).unwrap_or("Activity");
-                let elevation = gpx_elevation_gain(&gpx);
-
-                let start_end_dates = gpx_start_end_date(&gpx);
-
-                let elevation_profile = gpx_elevation_profile(&gpx);
-
-                self.file_list.files.push(FileItem::new(
-                    start_end_dates.map_or(String::new(), |(start, _)| {
-                        format!("{} {}", start.format("%d-%m-%Y"), name.to_string())
-                    }),
-                    distance_km,
-                    format!("{}", elevation.round()),
-                    elevation_profile,
-                ));
-                self.grand_total_km += distance_km;
-            }
-        }
+/// One reversible edit of the application state.
+///
+/// Nothing is written to disk until exit, so undoing leaves no trace.
+#[derive(Debug, Clone)]
+enum Change {
+    Sidecar {
+        id: ActivityId,
+        before: Box<Sidecar>,
+        after: Box<Sidecar>,
+    },
+    Marks {
+        before: BTreeSet<ActivityId>,
+        after: BTreeSet<ActivityId>,
+    },
+    /// Removal of a file from the list.
+    Archive(Box<FileItem>),
+}
 
-        if self.file_list.state.selected().is_none() {
-            self.file_list.state.select_first();
-        }
+/// Search of the command palette, with the position of the selected match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Palette {
+    query: String,
+    selected: usize,
+}
 
-        while !self.exit {
-            terminal.draw(|frame| frame.render_widget(&mut self, frame.area()))?;
-            self.handle_events()?;
-        }
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Prompt {
+    kind: PromptKind,
+    text: String,
+}
 
-        Ok(())
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptKind {
+    Rename,
+    /// Toggle a manual tag.
+    Tag,
+    /// Narrow the list as the text is typed.
+    Filter,
+    /// Put the targets in a tour, or take them out of theirs.
+    Tour,
+    /// File the comparison of the same-route popup is written to.
+    Comparison,
+}
 
-    fn handle_events(&mut self) -> Result<()> {
-        match event::read()? {
-            // it's important to check that the event is a key press event as
-            // crossterm also emits key release and repeat events on Windows.
-            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                self.handle_key_event(key_event)
-            }
-            _ => {}
-        };
-        Ok(())
-    }
+/// Tour whose stages are listed, with the stage under the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TourView {
+    name: String,
+    stage: usize,
+}
 
-    fn handle_key_event(&mut self, key_event: KeyEvent) {
-        match key_event.code {
-            KeyCode::Char('q') => self.exit(),
-            KeyCode::Char('j') | KeyCode::Down => self.select_next(),
-            KeyCode::Char('k') | KeyCode::Up => self.select_previous(),
-            _ => {}
+/// Why an activity or a file is left out of the list and the totals, in
+/// the order of the hidden view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Exclusion {
+    Filtered,
+    Duplicate,
+    Empty,
+    Manual,
+    Failed,
+}
+
+impl Exclusion {
+    fn title(self) -> &'static str {
+        match self {
+            Self::Filtered => "Filtered out",
+            Self::Duplicate => "Duplicates",
+            Self::Empty => "Without points",
+            Self::Manual => "Hidden by hand",
+            Self::Failed => "Not read",
         }
     }
+}
 
-    fn exit(&mut self) {
-        self.exit = true;
-    }
+/// Entry of the hidden view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HiddenItem {
+    exclusion: Exclusion,
+    label: String,
+    /// The file in the list, none for the files not kept at all.
+    index: Option<usize>,
+}
 
-    fn select_next(&mut self) {
-        self.file_list.state.select_next();
+/// Activities shown in the list.
+#[derive(Debug, Clone, Default)]
+struct Filter {
+    /// Tags every shown activity has, from `--tag`.
+    tags: Vec<String>,
+    /// Text typed after `/`, found in the name or the tags.
+    text: String,
+    activity_type: ActivityType,
+    /// Device the activities were recorded with, from `--creator`.
+    creator: Option<String>,
+    /// Most recent activities kept out of the matching ones, from `--latest`.
+    latest: Option<usize>,
+    include_undated: bool,
+}
+
+impl Filter {
+    fn is_active(&self) -> bool {
+        !self.tags.is_empty()
+            || !self.text.is_empty()
+            || self.activity_type != ActivityType::All
+            || self.creator.is_some()
+            || self.latest.is_some()
     }
 
-    fn select_previous(&mut self) {
-        self.file_list.state.select_previous();
+    fn matches(&self, file: &FileItem) -> bool {
+        let tags = file.tags();
+        let text = self.text.to_lowercase();
+        self.activity_type.matches(&file.activity)
+            && creator_matches(self.creator.as_deref(), &file.activity)
+            && self.tags.iter().all(|tag| tags.contains(&tag.as_str()))
+            && (file.name().to_lowercase().contains(&text)
+                || tags.iter().any(|tag| tag.to_lowercase().contains(&text)))
     }
 }
 
-impl Widget for &mut App {
-    fn render(self, area: Rect, buf: &mut Buffer) {
-        let [main_area, footer_area] =
-            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(area);
+/// Activities listed per place in the privacy warnings of the exports.
+const EXPOSURE_LISTED_ACTIVITIES: usize = 5;
 
-        let [list_area, detail_area] =
-            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(main_area);
+/// One warning per place where many of the exported activities start or end.
+fn exposure_warnings(places: &[ExposedPlace], activities: &[Activity]) -> Vec<String> {
+    places
+        .iter()
+        .map(|place| {
+            let mut listed: Vec<String> = place
+                .activities
+                .iter()
+                .take(EXPOSURE_LISTED_ACTIVITIES)
+                .map(|&i| activities[i].path.display().to_string())
+                .collect();
+            let more = place
+                .activities
+                .len()
+                .saturating_sub(EXPOSURE_LISTED_ACTIVITIES);
+            if more > 0 {
+                listed.push(format!("{more} more"));
+            }
+            let (lat, lon) = place.center;
+            format!(
+                "{} activities start or end near {lat:.5}, {lon:.5}, outside of the privacy \
+                 zones ({}); add a privacy zone there or pass --ack-privacy",
+                place.activities.len(),
+                listed.join(", ")
+            )
+        })
+        .collect()
+}
 
-        self.render_list(list_area, buf);
-        self.render_detail(detail_area, buf);
-        self.render_footer(footer_area, buf);
-    }
+/// Whether the activity was recorded by `creator`, if one is given.
+fn creator_matches(creator: Option<&str>, activity: &Activity) -> bool {
+    creator.is_none_or(|creator| activity.device().eq_ignore_ascii_case(creator))
 }
 
-impl App {
-    fn render_list(&mut self, area: Rect, buf: &mut Buffer) {
-        let block = Block::new()
-            .title(Line::raw("Activities").centered())
-            .borders(Borders::RIGHT)
-            .border_set(symbols::border::EMPTY);
+/// Keep the `latest` most recent of `items`, in their order, and the undated
+/// ones too when `include_undated`.
+fn keep_latest<T>(
+    items: &mut Vec<T>,
+    latest: usize,
+    include_undated: bool,
+    start: impl Fn(&T) -> Option<DateTime<FixedOffset>>,
+) {
+    let mut dated: Vec<usize> = (0..items.len())
+        .filter(|&i| start(&items[i]).is_some())
+        .collect();
+    dated.sort_by_key(|&i| Reverse(start(&items[i])));
+    let mut kept = vec![include_undated; items.len()];
+    for &i in &dated {
+        kept[i] = false;
+    }
+    for &i in dated.iter().take(latest) {
+        kept[i] = true;
+    }
+    let mut kept = kept.into_iter();
+    items.retain(|_| kept.next().unwrap_or(false));
+}
 
-        let items: Vec<ListItem> = self
-            .file_list
-            .files
-            .iter()
-            .enumerate()
-            .map(|(_, file)| ListItem::from(file))
-            .collect();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    List,
+    Detail,
+}
 
-        let list = List::new(items)
-            .block(block)
-            .highlight_style(SELECTED_STYLE)
-            .highlight_symbol(">> ")
-            .highlight_spacing(HighlightSpacing::Always);
+#[derive(Debug)]
+struct FileList {
+    files: Vec<FileItem>,
+    /// Indices of the files matching the filter.
+    visible: Vec<usize>,
+    /// Files matching the filter before `--latest` keeps the most recent.
+    matching: usize,
+    /// Lines of the list, from the visible files; the list state selects
+    /// among them.
+    rows: Vec<Row>,
+    state: ListState,
+}
 
-        StatefulWidget::render(list, area, buf, &mut self.file_list.state);
-    }
+/// A line of the list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Row {
+    /// A file, indented when it is listed under its day.
+    File { index: usize, nested: bool },
+    /// The rides of one day as a single entry, and whether they are listed
+    /// below it.
+    Day {
+        date: NaiveDate,
+        files: Vec<usize>,
+        expanded: bool,
+    },
+    /// The header of a month, by its first day, over its visible rides.
+    Month { month: NaiveDate, files: Vec<usize> },
+}
 
-    fn render_footer(&mut self, area: Rect, buf: &mut Buffer) {
-        let grand_total = Text::from(vec![Line::from(vec![
-            "Grand Total: ".into(),
-            format_distance(self.grand_total_km).to_string().yellow(),
-        ])]);
-        Paragraph::new(grand_total).centered().render(area, buf);
-    }
+/// Position of an activity in the list order.
+type SortKey = (Option<DateTime<FixedOffset>>, ActivityId);
 
-    fn render_detail(&mut self, area: Rect, buf: &mut Buffer) {
-        let [distance_area, elevation_area] =
-            Layout::vertical([Constraint::Fill(1), Constraint::Fill(1)]).areas(area);
+#[derive(Debug, Clone)]
+struct FileItem {
+    activity: Activity,
+    sidecar: Sidecar,
+    /// The sidecar as it is on disk.
+    saved: Sidecar,
+    auto_commute: bool,
+    /// Tags given by the tagging rules.
+    auto_tags: Vec<String>,
+    /// Grade-adjusted distance, in equivalent flat kilometers.
+    flat_km: f64,
+    bests: Option<Bests>,
+    sprints: Vec<Sprint>,
+    route: Option<RouteSignature>,
+    terrain: Vec<TerrainSplit>,
+    /// Guessed unpaved distance, none without timestamps.
+    surface: Option<SurfaceEstimate>,
+    climbs: Vec<Climb>,
+    /// Ascents of the reference climbs.
+    climb_checks: Vec<ClimbCheck>,
+    /// Seconds spent above each of [`App::altitude_thresholds`].
+    time_above: Vec<f64>,
+    /// Efforts above the power or heart rate threshold of the config.
+    intervals: Option<Intervals>,
+    /// Score of the data, none without points.
+    quality: Option<QualityScore>,
+    descending: Option<DescendingSpeed>,
+    /// Wind of an out-and-back ride, none for virtual rides.
+    wind: Option<WindEstimate>,
+    /// Countries passed through, none for virtual rides.
+    #[cfg(feature = "borders")]
+    countries: Vec<CountryDistance>,
+    road_trace: RoadTrace,
+    grades: GradeHistogram,
+    /// Whether the points were dropped once the metrics above were computed,
+    /// to be read again from the file when needed.
+    released: bool,
+    /// Whether the file was gone when read again; the metrics are those of
+    /// the last reading.
+    missing: bool,
+    /// Whether the file held neither track nor route points. Such rides
+    /// are hidden unless shown by hand.
+    empty: bool,
+}
 
-        self.render_information(distance_area, buf);
-        self.render_elevation_chart(elevation_area, buf);
+pub fn run_cyclemetrics(args: Args) -> Result<(), Failure> {
+    if args.debug || args.debug_file.is_some() {
+        let path = args.debug_file.clone().unwrap_or_else(|| {
+            args.data_dir
+                .clone()
+                .unwrap_or_else(Store::default_dir)
+                .join(DEBUG_LOG_FILE)
+        });
+        init_debug_log(&path)?;
+    }
+    let headless = Headless {
+        quiet: args.quiet,
+        fail_fast: args.fail_fast,
+        ascii: args.ascii,
+    };
+    match &args.command {
+        Some(Command::Info) => return Ok(info(&mut headless.output())?),
+        Some(Command::History) => {
+            let store = Store::new(args.data_dir.clone().unwrap_or_else(Store::default_dir));
+            let session = store.session()?;
+            for warning in store.take_warnings() {
+                headless.warn(&warning);
+            }
+            return Ok(write_run_history(&session.runs, &mut headless.output())?);
+        }
+        Some(Command::Validate { paths }) => return validate(paths, headless),
+        Some(Command::Metrics { jsonl, paths }) => return metrics(*jsonl, paths, headless),
+        Some(Command::Review {
+            year,
+            output,
+            format,
+            include_current_month,
+            paths,
+        }) => {
+            return review(
+                *year,
+                output.as_deref(),
+                *format,
+                *include_current_month,
+                &args,
+                paths,
+                headless,
+            );
+        }
+        Some(Command::Anonymize {
+            input,
+            output,
+            offset,
+            rotate,
+            date,
+            keep_sensors,
+        }) => {
+            let anonymization = Anonymization {
+                offset: offset.unwrap_or_else(random_offset),
+                rotation_deg: *rotate,
+                date: *date,
+                keep_sensors: *keep_sensors,
+            };
+            let bytes = fs::read(input).with_context(|| format!("reading {}", input.display()))?;
+            let gpx = anonymize(&bytes, &anonymization)
+                .with_context(|| format!("anonymizing {}", input.display()))?;
+            return Ok(
+                fs::write(output, gpx).with_context(|| format!("writing {}", output.display()))?
+            );
+        }
+        Some(Command::Plan { paths }) => {
+            let config = load_config(&args)?;
+            let lang = args.lang.or(config.lang).unwrap_or_default();
+            let grade_model = config.grade_model.unwrap_or_default();
+            let controls = if args.controls.is_empty() {
+                &config.controls
+            } else {
+                &args.controls
+            };
+            check_controls(controls).map_err(Failure::usage)?;
+            return plan(
+                args.plan_speed,
+                args.stop_every,
+                controls,
+                grade_model,
+                lang,
+                paths,
+                headless,
+            );
+        }
+        None => {}
     }
 
-    fn render_information(&mut self, area: Rect, buf: &mut Buffer) {
-        let info = if let Some(i) = self.file_list.state.selected() {
-            let file_info: FileItem = self.file_list.files[i].clone();
+    if let Some(format) = &args.date_format
+        && StrftimeItems::new(format).any(|item| item == Item::Error)
+    {
+        return Err(Failure::usage(anyhow!("invalid --date-format {format:?}")));
+    }
+    check_column_exprs(&args.column_expr).map_err(|err| Failure::usage(anyhow!(err)))?;
+    let sort = args
+        .sort
+        .as_deref()
+        .map(|key| sort_column(key, &args.column_expr))
+        .transpose()
+        .map_err(|err| Failure::usage(anyhow!(err)))?;
 
-            format!(
-                "Distance: {} Uphill ↑: {:>4}m",
-                format_distance(file_info.distance),
-                file_info.elevation,
-            )
+    let Resolved {
+        paths,
+        duplicates: duplicate_paths,
+    } = resolve_paths(&args.gpx_files).map_err(Failure::usage)?;
+    #[cfg(feature = "http")]
+    let timeout = args.timeout.map(Duration::from_secs);
+    #[cfg(not(feature = "http"))]
+    let timeout = None;
+    let options = LoadOptions {
+        dem: args.dem.clone().map(Dem::new),
+        full_detail: args.full_detail,
+        timeout,
+        max_stdin_bytes: args.max_stdin_bytes,
+        prefer: args.prefer.clone(),
+    };
+    // The terminal interface cannot share the standard input with a pipe.
+    let format =
+        if args.format == Format::Tui && (args.no_tui || paths.iter().any(|path| is_stdin(path))) {
+            Format::Json
         } else {
-            "No activity selected...".to_string()
+            args.format
         };
 
-        // We show the list item's info under the list in this paragraph
-        let block = Block::new()
-            .title(Line::raw("Activity Detail").centered())
-            .border_set(symbols::border::EMPTY)
-            .padding(Padding::horizontal(1));
+    let exports = Exports {
+        prometheus: args.export_prometheus.clone(),
+        csv: args.export_csv.clone(),
+        #[cfg(feature = "sqlite")]
+        sqlite: args.export_sqlite.clone(),
+        #[cfg(feature = "sqlite")]
+        with_points: args.with_points,
+    };
+    if args.watch && exports.is_empty() {
+        return Err(Failure::usage(anyhow!(
+            "--watch needs --export-csv, --export-prometheus or --export-sqlite"
+        )));
+    }
+    if args.watch && paths.iter().any(|path| is_stdin(path)) {
+        return Err(Failure::usage(anyhow!(
+            "--watch cannot watch the standard input"
+        )));
+    }
 
-        // We can now render the item infoq
-        Paragraph::new(info)
-            .block(block)
-            .wrap(Wrap { trim: false })
-            .render(area, buf);
+    // The terminal interface alone fills the list as files are loaded.
+    if format == Format::Tui
+        && args.export_kml.is_none()
+        && args.export_thumbnails.is_none()
+        && exports.is_empty()
+        && !args.share
+    {
+        let total = paths.len();
+        let receiver = spawn_loader(paths, options);
+        let app = App {
+            duplicate_paths,
+            ..App::default()
+        };
+        return Ok(run_app(app, args, receiver, total)?);
+    }
+    // A watched directory may fill later.
+    if paths.is_empty() && !args.watch {
+        return Err(Failure::no_files(&args.gpx_files));
     }
+    headless.warn_duplicate_paths(&duplicate_paths);
 
-    fn render_elevation_chart(&mut self, area: Rect, buf: &mut Buffer) {
-        let block = Block::new()
-            .title(Line::raw("Activity Elevation").centered())
-            .border_set(symbols::border::EMPTY)
-            .padding(Padding::horizontal(1));
+    let matches = |activity: &Activity| {
+        args.activity_type.matches(activity) && creator_matches(args.creator.as_deref(), activity)
+    };
+    let detection = args
+        .exclude_transport
+        .then(|| load_config(&args))
+        .transpose()?
+        .map(|config| config.transport);
+    let prepare = |activity: &mut Activity| {
+        if let Some(segment_joins) = args.segment_joins {
+            activity.retune(Tuning {
+                segment_joins,
+                ..Tuning::default()
+            });
+        }
+        if let Some(detection) = &detection
+            && !activity.virtual_ride
+        {
+            activity.transport = detect_transport(&activity.points, detection, &activity.tuning);
+            activity.exclude_transport();
+        }
+    };
 
-        let (data, distance) = if let Some(i) = self.file_list.state.selected() {
-            (
-                self.file_list.files[i].elevation_profile.clone(),
-                self.file_list.files[i]
-                    .elevation_profile
-                    .clone()
-                    .iter()
-                    .last()
-                    .map_or(1000.0, |f| f.0),
-            )
-        } else {
-            (vec![], 100.0)
-        };
+    let mut loaded = headless.load(&paths, &options)?;
+    loaded.activities.retain(|activity| matches(activity));
+    if let Some(latest) = args.latest {
+        keep_latest(
+            &mut loaded.activities,
+            latest,
+            args.include_undated,
+            |activity| activity.start,
+        );
+    }
+    for activity in &mut loaded.activities {
+        prepare(activity);
+    }
 
-        let y_bounds = if let Some((min, max)) = elevation_profile_min_max(&data) {
-            [min - 10.0, max + 50.0]
-        } else {
-            [0.0, 1000.0]
+    #[cfg(feature = "sqlite")]
+    let export_points = args.export_sqlite.is_some() && args.with_points;
+    #[cfg(not(feature = "sqlite"))]
+    let export_points = false;
+    if !args.ack_privacy
+        && (args.export_kml.is_some() || args.export_thumbnails.is_some() || export_points)
+    {
+        let config = load_config(&args)?;
+        let places = exposed_places(
+            &loaded.activities,
+            &config.privacy_zones,
+            config.privacy_warning_others.unwrap_or(EXPOSURE_MAX_OTHERS),
+        );
+        for warning in exposure_warnings(&places, &loaded.activities) {
+            headless.warn(&warning);
+        }
+    }
+    if args.export_kml.is_some() || args.export_thumbnails.is_some() {
+        let config = load_config(&args)?;
+        if let Some(path) = &args.export_kml {
+            let store = Store::new(args.data_dir.clone().unwrap_or_else(Store::default_dir));
+            let mut tours = BTreeMap::new();
+            for activity in &loaded.activities {
+                if let Some(tour) = store.sidecar(activity)?.tour {
+                    tours.insert(activity.id, tour);
+                }
+            }
+            for warning in store.take_warnings() {
+                headless.warn(&warning);
+            }
+            export_kml(&loaded.activities, path, &config.privacy_zones, &tours)?;
+        }
+        if let Some(dir) = &args.export_thumbnails {
+            for warning in export_thumbnails(&loaded.activities, dir, &config.privacy_zones)? {
+                headless.warn(&warning);
+            }
+        }
+    }
+    exports.write(&loaded.activities, &[], &loaded.activities)?;
+    if args.watch {
+        let keep = |activity: &mut Activity| {
+            let kept = matches(activity);
+            if kept {
+                prepare(activity);
+            }
+            kept
         };
+        let watch = Watch::new(
+            args.gpx_files.clone(),
+            options,
+            keep,
+            exports,
+            loaded.activities,
+        )?;
+        return watch_exports(watch, headless);
+    }
 
-        let dataset = Dataset::default()
-            .name("Elevation")
-            .marker(Marker::Dot)
-            .graph_type(ratatui::widgets::GraphType::Line)
-            .style(Style::default().green())
-            .data(&data);
+    let failed = loaded.failed.len();
+    if args.share {
+        let template = load_config(&args)?
+            .share_template()
+            .map_err(Failure::usage)?;
+        let mut out = headless.output();
+        for activity in &loaded.activities {
+            writeln!(out, "{}", template.render(activity))?;
+        }
+        return Failure::check_unreadable(failed, paths.len());
+    }
 
-        Chart::new(vec![dataset])
-            .block(block)
-            .x_axis(
-                Axis::default()
-                    .title("Distance (km)")
-                    .labels(["0".to_string(), format_distance(distance)])
-                    .bounds([0.0, distance])
-                    .style(Style::default()),
-            )
-            .y_axis(
-                Axis::default()
-                    .title("Elevation (m)")
-                    .bounds(y_bounds)
-                    .labels(y_bounds.iter().map(|item| format!("{:>8.2}", item)))
-                    .style(Style::default()),
-            )
-            .render(area, buf);
+    if let Some((column, descending)) = sort {
+        sort_activities(&mut loaded.activities, column, descending);
+    }
+    let fields = if args.columns.is_empty() {
+        default_fields(format)
+    } else {
+        args.columns.clone()
+    };
+    let columns: Vec<Column> = fields
+        .into_iter()
+        .map(Column::Field)
+        .chain(args.column_expr.iter().map(Column::Expr))
+        .collect();
+    match format {
+        Format::Tui => {}
+        Format::Json => write_json(&loaded.activities, &columns, &mut headless.output())?,
+        Format::Csv => write_csv(&loaded.activities, &columns, &mut headless.output())?,
+        Format::Table => {
+            let width = args.width.or_else(|| {
+                io::stdout()
+                    .is_terminal()
+                    .then(|| crossterm::terminal::size().ok())
+                    .flatten()
+                    .map(|(columns, _)| columns.into())
+            });
+            write_table(&loaded.activities, &columns, width, &mut headless.output())?;
+        }
+    }
+    if format != Format::Tui {
+        return Failure::check_unreadable(failed, paths.len());
+    }
+
+    // The interface tells the files that could not be read.
+    let app = App {
+        duplicates: loaded.duplicates,
+        ..Default::default()
+    };
+    let total = loaded.activities.len() + failed;
+    let (sender, receiver) = mpsc::channel();
+    for activity in loaded.activities {
+        sender.send(Ok(activity)).map_err(anyhow::Error::from)?;
+    }
+    for (_, err) in loaded.failed {
+        sender.send(Err(err)).map_err(anyhow::Error::from)?;
+    }
+    drop(sender);
+
+    Ok(run_app(app, args, receiver, total)?)
+}
+
+/// The configuration file of `--config`, or the default one.
+fn load_config(args: &Args) -> Result<Config, Failure> {
+    Config::load(args.config.as_deref().unwrap_or(&Config::default_path())).map_err(Failure::usage)
+}
+
+/// How the commands without an interface report: `--quiet`, `--fail-fast`
+/// and `--ascii`.
+#[derive(Debug, Clone, Copy)]
+struct Headless {
+    quiet: bool,
+    fail_fast: bool,
+    ascii: bool,
+}
+
+impl Headless {
+    /// Where the reports go: stdout, or nowhere when quiet.
+    fn output(self) -> Box<dyn Write> {
+        if self.quiet {
+            Box::new(io::sink())
+        } else if self.ascii {
+            Box::new(AsciiWriter::new(io::stdout().lock()))
+        } else {
+            Box::new(io::stdout().lock())
+        }
+    }
+
+    fn warn(self, warning: &str) {
+        if !self.quiet {
+            eprintln!("warning: {warning}");
+        }
+    }
+
+    /// Load the files, printing why each unreadable one is left out; with
+    /// `fail_fast` the first one fails the load instead.
+    fn load(self, paths: &[PathBuf], options: &LoadOptions) -> Result<Loaded> {
+        if self.fail_fast {
+            return load_activities(paths, options);
+        }
+        let loaded = load_readable(paths, options);
+        for (_, err) in &loaded.failed {
+            eprintln!("error: {err:#}");
+        }
+        Ok(loaded)
+    }
+
+    /// The files matching the patterns of a command, failing without any.
+    fn paths(self, patterns: &[PathBuf]) -> Result<Vec<PathBuf>, Failure> {
+        let resolved = resolve_paths(patterns).map_err(Failure::usage)?;
+        if resolved.paths.is_empty() {
+            return Err(Failure::no_files(patterns));
+        }
+        self.warn_duplicate_paths(&resolved.duplicates);
+        Ok(resolved.paths)
+    }
+
+    /// Tell about the files matched more than once, read only once.
+    fn warn_duplicate_paths(self, duplicates: &[PathBuf]) {
+        if !duplicates.is_empty() {
+            self.warn(&format!("{} duplicate path(s) skipped", duplicates.len()));
+        }
+    }
+}
+
+/// Look at the watched files every [`WATCH_INTERVAL`] and update the
+/// exports, until interrupted. Errors are told and the next look tries again,
+/// as files come and go while being synced.
+fn watch_exports(
+    mut watch: Watch<impl FnMut(&mut Activity) -> bool>,
+    headless: Headless,
+) -> Result<(), Failure> {
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        let update = match watch.poll() {
+            Ok(update) => update,
+            Err(err) => {
+                eprintln!("error: {err:#}");
+                continue;
+            }
+        };
+        for (_, err) in &update.failed {
+            eprintln!("error: {err:#}");
+        }
+        if !headless.quiet && update.updated + update.removed > 0 {
+            eprintln!(
+                "{} activity(ies) exported, {} removed",
+                update.updated, update.removed
+            );
+        }
+    }
+}
+
+fn run_app(app: App, args: Args, receiver: Receiver<Result<Activity>>, total: usize) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, args, receiver, total);
+    ratatui::restore();
+
+    result
+}
+
+/// Print what this build can do and where it keeps its files, the first
+/// things to know about a bug report.
+fn info(out: &mut impl Write) -> Result<()> {
+    let mut formats = vec!["GPX 1.0 and 1.1 files", "FIT activity files"];
+    if cfg!(feature = "http") {
+        formats.push("GPX and FIT files from http(s) URLs");
+    }
+    writeln!(out, "cyclemetrics {VERSION}")?;
+    writeln!(
+        out,
+        "platform: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )?;
+    writeln!(out, "features: {}", env!("CYCLEMETRICS_FEATURES"))?;
+    writeln!(out, "input formats: {}", formats.join(", "))?;
+    writeln!(out, "config: {}", Config::default_path().display())?;
+    writeln!(
+        out,
+        "data: {} (sidecars and session)",
+        Store::default_dir().display()
+    )?;
+    Ok(())
+}
+
+/// Print the validation report of the files, failing if any could not be
+/// read; with `fail_fast`, the files after the first one that fails are
+/// not checked.
+fn validate(patterns: &[PathBuf], headless: Headless) -> Result<(), Failure> {
+    let paths = headless.paths(patterns)?;
+    let validations = if headless.fail_fast {
+        let mut validations = vec![];
+        for path in &paths {
+            let validation = validate_file(path);
+            let failed = validation.status == Status::Failed;
+            validations.push(validation);
+            if failed {
+                break;
+            }
+        }
+        validations
+    } else {
+        validate_files(&paths)
+    };
+    write_validation_report(&validations, &mut headless.output())?;
+
+    let failed = validations
+        .iter()
+        .filter(|validation| validation.status == Status::Failed)
+        .count();
+    if failed > 0 {
+        return Err(anyhow!("{failed} of {} files failed validation", validations.len()).into());
+    }
+    Ok(())
+}
+
+/// Write the metrics of the files as a JSON array, or as JSON lines while
+/// they are read; fails after the last line if any file could not be read.
+fn metrics(jsonl: bool, patterns: &[PathBuf], headless: Headless) -> Result<(), Failure> {
+    let paths = headless.paths(patterns)?;
+    let total = paths.len();
+    if !jsonl {
+        let loaded = headless.load(&paths, &LoadOptions::default())?;
+        write_json(
+            &loaded.activities,
+            &default_columns(Format::Json),
+            &mut headless.output(),
+        )?;
+        return Failure::check_unreadable(loaded.failed.len(), total);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let mut failed = 0;
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            load_each(&paths, &LoadOptions::default(), |path, result| {
+                let _ = sender.send(JsonLine::new(path, &result));
+            });
+        });
+        let mut out = headless.output();
+        for line in receiver {
+            failed += usize::from(matches!(line, JsonLine::Error { .. }));
+            write_json_line(&line, &mut out)?;
+            // The files still loading are not written.
+            if failed > 0 && headless.fail_fast {
+                break;
+            }
+        }
+        anyhow::Ok(())
+    })?;
+
+    Failure::check_unreadable(failed, total)
+}
+
+/// Write the review of the rides started in `year`.
+fn review(
+    year: i32,
+    output: Option<&Path>,
+    format: ReviewFormat,
+    include_current_month: bool,
+    args: &Args,
+    patterns: &[PathBuf],
+    headless: Headless,
+) -> Result<(), Failure> {
+    let config = load_config(args)?;
+    let lang = args.lang.or(config.lang).unwrap_or_default();
+    let thresholds = IntensityThresholds::new(
+        args.ftp.or(config.ftp),
+        args.max_hr.or(config.max_hr),
+        config.intensity,
+    );
+    let paths = headless.paths(patterns)?;
+    let loaded = headless.load(&paths, &LoadOptions::default())?;
+    let failed = loaded.failed.len();
+    // Every year, for the comparison with the years before.
+    let exploration = exploration(&loaded.activities)
+        .into_iter()
+        .find(|exploration| exploration.year == year);
+    // Every year too, for the routes repeated often enough to be commutes.
+    let detected = detect_commutes(&loaded.activities, &config);
+    let store = Store::new(args.data_dir.clone().unwrap_or_else(Store::default_dir));
+    let mut rides = vec![];
+    let mut commutes = vec![];
+    for (activity, detected) in loaded.activities.into_iter().zip(detected) {
+        if activity.start.is_some_and(|start| start.year() == year) {
+            commutes.push(store.sidecar(&activity)?.commute.unwrap_or(detected));
+            rides.push(activity);
+        }
+    }
+    for warning in store.take_warnings() {
+        headless.warn(&warning);
+    }
+    let summary = Summary::new(&rides);
+    let weekend = config.weekend_days().map_err(Failure::usage)?;
+    let week = week_split(rides.iter().zip(commutes), &weekend);
+    // The last day of the previous month, unless the current one counts.
+    let today = Local::now().date_naive();
+    let until = if include_current_month {
+        today
+    } else {
+        today - Days::new(u64::from(today.day()))
+    };
+    let exploration = exploration.as_ref();
+    let review = match format {
+        ReviewFormat::Html => {
+            review_html(year, &summary, &week, &thresholds, exploration, until, lang)
+        }
+        ReviewFormat::Text => {
+            review_text(year, &summary, &week, &thresholds, exploration, until, lang)
+        }
+    };
+
+    match output {
+        Some(path) => {
+            let review = if headless.ascii {
+                to_ascii(&review)
+            } else {
+                review.into()
+            };
+            fs::write(path, review.as_bytes())
+                .with_context(|| format!("writing {}", path.display()))?
+        }
+        None => headless.output().write_all(review.as_bytes())?,
+    }
+    Failure::check_unreadable(failed, paths.len())
+}
+
+/// Print the ride plan of every route and untimed track of the files.
+fn plan(
+    speed_kmh: f64,
+    stop_every_km: f64,
+    controls: &[f64],
+    grade_model: GradeModel,
+    lang: Lang,
+    patterns: &[PathBuf],
+    headless: Headless,
+) -> Result<(), Failure> {
+    if speed_kmh <= 0.0 {
+        return Err(Failure::usage(anyhow!(
+            "--plan-speed must be positive, got {speed_kmh}"
+        )));
+    }
+    let paths = headless.paths(patterns)?;
+    let loaded = headless.load(&paths, &LoadOptions::default())?;
+    let mut out = headless.output();
+    for activity in &loaded.activities {
+        let Some(points) = planned_points(activity) else {
+            headless.warn(&format!(
+                "{} has timestamps, it is a recording rather than a plan",
+                activity.path.display()
+            ));
+            continue;
+        };
+        let plan = plan_ride(points, speed_kmh, stop_every_km, controls, grade_model);
+        writeln!(out, "{}\n{}", activity.name, format_plan(&plan, lang))?;
+    }
+    Failure::check_unreadable(loaded.failed.len(), paths.len())
+}
+
+/// Points of a route, or of a track without timestamps.
+fn planned_points(activity: &Activity) -> Option<&[TrackPoint]> {
+    if is_planned(&activity.points) {
+        Some(&activity.points)
+    } else if activity.points.is_empty() && is_planned(&activity.route_points) {
+        Some(&activity.route_points)
+    } else {
+        None
+    }
+}
+
+/// A ride plan as a table of the stops, the climbs and the finish by
+/// distance.
+fn format_plan(plan: &RidePlan, lang: Lang) -> String {
+    let mut rows: Vec<(f64, f64, String)> = plan
+        .stops
+        .iter()
+        .map(|&(km, seconds)| (km, seconds, lang.text("bottle stop").to_string()))
+        .collect();
+    rows.extend(plan.climbs.iter().map(|planned| {
+        let climb = &planned.climb;
+        (
+            planned.distance_km,
+            planned.arrival_seconds,
+            lang.fill(
+                "climb: {length} km at {grade}%, top at {top}",
+                &[
+                    ("length", &lang.number(climb.length_m / 1_000.0, 1)),
+                    ("grade", &lang.number(climb.grade_percent(), 1)),
+                    ("top", &format_duration(planned.top_seconds)),
+                ],
+            ),
+        )
+    }));
+    rows.sort_by(|a, b| a.0.total_cmp(&b.0));
+    rows.push((
+        plan.distance_km,
+        plan.total_seconds,
+        lang.text("finish").to_string(),
+    ));
+
+    let mut text = lang.fill(
+        "Plan at {speed} km/h: {time}",
+        &[
+            ("speed", &lang.number(plan.speed_kmh, 1)),
+            ("time", &format_duration(plan.total_seconds)),
+        ],
+    );
+    for (km, seconds, what) in rows {
+        text.push_str(&format!(
+            "\n{:>7} km {:>8}  {what}",
+            lang.number(km, 1),
+            format_duration(seconds)
+        ));
+    }
+    if !plan.controls.is_empty() {
+        text.push_str(&format!("\n\n{}", format_controls(&plan.controls, lang)));
+    }
+    text
+}
+
+/// When each brevet control was (or is to be) reached, against its window.
+fn format_controls(controls: &[ControlTime], lang: Lang) -> String {
+    let mut text = lang.text("Controls:").to_string();
+    for control in controls {
+        let status = match control.status() {
+            ControlStatus::Early => "early",
+            ControlStatus::InTime => "in time",
+            ControlStatus::Late => "late",
+            ControlStatus::NotReached => "not reached",
+        };
+        text.push_str(&format!(
+            "\n{:>7} km {:>8}  {}  {}",
+            lang.number(control.distance_km, 1),
+            control
+                .reached_seconds
+                .map_or("–".to_string(), format_duration),
+            lang.fill(
+                "open {opens}–{closes}",
+                &[
+                    ("opens", &format_duration(control.window.opens_seconds)),
+                    ("closes", &format_duration(control.window.closes_seconds)),
+                ],
+            ),
+            lang.text(status)
+        ));
+    }
+    text
+}
+
+/// Values of the ride under the scrub cursor, leaving out those the file
+/// does not have.
+fn format_sample(sample: &Sample, lang: Lang) -> String {
+    let mut values = vec![format!("{} km", lang.number(sample.distance_km, 2))];
+    if let Some(time) = sample.time {
+        values.push(time.format("%H:%M:%S").to_string());
+    }
+    if let Some(elevation) = sample.elevation {
+        values.push(format!("{} m", lang.number(elevation, 0)));
+    }
+    if let Some(grade) = sample.grade_percent {
+        values.push(format!("{}%", lang.number(grade, 1)));
+    }
+    if let Some(speed) = sample.speed_kmh {
+        values.push(format!("{} km/h", lang.number(speed, 1)));
+    }
+    if let Some(heart_rate) = sample.heart_rate {
+        values.push(format!("{} bpm", lang.number(heart_rate, 0)));
+    }
+    if let Some(power) = sample.power {
+        values.push(format!("{} W", lang.number(power, 0)));
+    }
+    lang.fill(
+        "Cursor: {values} (←/→ 100 m, shift 1 km, esc leave)",
+        &[("values", &values.join("  "))],
+    )
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            file_list: FileList {
+                files: vec![],
+                visible: vec![],
+                rows: vec![],
+                matching: 0,
+                state: ListState::default(),
+            },
+            trusted_elevation_only: false,
+            duplicates: vec![],
+            duplicate_paths: vec![],
+            summary: Summary::default(),
+            new_roads: BTreeMap::new(),
+            weather: None,
+            reference_climbs: vec![],
+            config: Config::default(),
+            config_path: Config::default_path(),
+            tuning: Tuning::default(),
+            store: Store::new(Store::default_dir()),
+            focus: Focus::List,
+            split: DEFAULT_SPLIT,
+            detail_scroll: 0,
+            folded: BTreeSet::new(),
+            detail_layout: SectionLayout::default(),
+            show_stats: false,
+            calendar: None,
+            scrub: None,
+            show_routes: false,
+            show_climbs: false,
+            show_climb_rate: false,
+            show_distance_models: false,
+            model_distances: BTreeMap::new(),
+            model_distances_computing: None,
+            tour_view: None,
+            quality_view: None,
+            hidden_view: None,
+            section_jump: None,
+            combine_days: false,
+            group_months: false,
+            expanded_days: BTreeSet::new(),
+            climb_groups: vec![],
+            settings: None,
+            show_help: false,
+            palette: None,
+            lang: Lang::default(),
+            theme: Theme::default(),
+            date_format: None,
+            plan_speed_kmh: DEFAULT_PLAN_SPEED_KMH,
+            stop_every_km: DEFAULT_STOP_EVERY_KM,
+            map_zoom: 0,
+            map_area: None,
+            #[cfg(feature = "images")]
+            images: None,
+            count_virtual: false,
+            route_matches: None,
+            raw_metrics: None,
+            color: true,
+            ascii: false,
+            recency_colors: true,
+            today: Local::now().date_naive(),
+            marked: BTreeSet::new(),
+            range_start: None,
+            range_end: None,
+            archived: vec![],
+            history: History::default(),
+            filter: Filter::default(),
+            rules: vec![],
+            share: ShareTemplate::default(),
+            weekend: DEFAULT_WEEKEND.to_vec(),
+            altitude_thresholds: DEFAULT_ALTITUDE_THRESHOLDS_M.to_vec(),
+            clipboard: None,
+            loading: None,
+            full_detail: false,
+            prefer: DEFAULT_PREFERENCES.to_vec(),
+            exclude_transport: false,
+            sources: vec![],
+            dem_dir: None,
+            timeout: None,
+            details: Lru::new(DETAIL_CACHE_SIZE),
+            detail_loading: None,
+            failures: vec![],
+            restore: None,
+            session_unknown: toml::Table::new(),
+            runs: vec![],
+            prompt: None,
+            status: None,
+            exit: false,
+        }
     }
 }
 
-impl From<&FileItem> for ListItem<'_> {
-    fn from(value: &FileItem) -> Self {
-        let line = Line::styled(format!("{}", value.file_name), SLATE.c200);
+/// Tags given automatically: `virtual` and those of the tagging rules.
+fn auto_tags(rules: &[TagRule], activity: &Activity) -> Vec<String> {
+    activity
+        .virtual_ride
+        .then(|| VIRTUAL_TAG.to_string())
+        .into_iter()
+        .chain(
+            rules
+                .iter()
+                .filter(|rule| rule.matches(activity))
+                .map(|rule| rule.tag.clone()),
+        )
+        .collect()
+}
+
+impl FileItem {
+    fn new(
+        activity: Activity,
+        sidecar: Sidecar,
+        auto_commute: bool,
+        auto_tags: Vec<String>,
+        grade_model: GradeModel,
+    ) -> Self {
+        Self {
+            flat_km: grade_adjusted_km(&activity, grade_model),
+            bests: bests(&activity.points),
+            sprints: detect_sprints(&activity.points),
+            route: RouteSignature::new(&activity.points),
+            terrain: terrain_breakdown(&activity),
+            surface: estimate_surface(&activity.points, &activity.tuning),
+            climbs: detect_climbs(&activity.points, &activity.tuning),
+            climb_checks: vec![],
+            time_above: vec![],
+            intervals: None,
+            quality: quality_score(&activity),
+            descending: descending_speed(&detect_descents(&activity.points, &activity.tuning)),
+            wind: if activity.virtual_ride {
+                None
+            } else {
+                estimate_wind(&activity.points, &activity.tuning)
+            },
+            #[cfg(feature = "borders")]
+            countries: if activity.virtual_ride {
+                vec![]
+            } else {
+                countries_crossed(&activity.points)
+            },
+            road_trace: RoadTrace::new(&activity.points),
+            grades: grade_histogram([&activity]),
+            released: false,
+            missing: false,
+            empty: false,
+            activity,
+            saved: sidecar.clone(),
+            sidecar,
+            auto_commute,
+            auto_tags,
+        }
+    }
+
+    /// Drop the points of an activity read from a file, which can be read
+    /// again.
+    fn release_points(&mut self) {
+        if !self.activity.points.is_empty() && self.activity.path.is_file() {
+            self.activity.release_points();
+            self.released = true;
+        }
+    }
+
+    fn intensity(&self, thresholds: &IntensityThresholds) -> Option<Intensity> {
+        classify(
+            &RideMetrics::new(&self.activity),
+            &self.activity.sensor_summary(),
+            thresholds,
+        )
+    }
+
+    fn sort_key(&self) -> SortKey {
+        (self.activity.start, self.activity.id)
+    }
+
+    fn is_commute(&self) -> bool {
+        self.sidecar.commute.unwrap_or(self.auto_commute)
+    }
+
+    /// Why the ride is left out whatever the filter, if it is.
+    fn hidden_by(&self) -> Option<Exclusion> {
+        match self.sidecar.hidden {
+            Some(true) => Some(Exclusion::Manual),
+            None if self.empty => Some(Exclusion::Empty),
+            _ => None,
+        }
+    }
+
+    /// Manual tags first, then the automatic ones and the codes of the
+    /// countries passed through.
+    fn tags(&self) -> Vec<&str> {
+        let mut tags: Vec<&str> = vec![];
+        let automatic = self.auto_tags.iter().map(String::as_str);
+        #[cfg(feature = "borders")]
+        let automatic = automatic.chain(self.countries.iter().map(|country| country.code.as_str()));
+        for tag in self
+            .sidecar
+            .tags
+            .iter()
+            .map(String::as_str)
+            .chain(automatic)
+        {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+
+    fn name(&self) -> &str {
+        self.sidecar.name.as_deref().unwrap_or(&self.activity.name)
+    }
+
+    fn file_name(&self, date_format: &str) -> String {
+        self.activity.start.map_or(String::new(), |start| {
+            format!("{} {}", start.format(date_format), self.name())
+        })
+    }
 
-        ListItem::new(line)
+    /// Date and name of the ride, or its name alone when undated.
+    fn label(&self, date_format: &str) -> String {
+        match self.file_name(date_format) {
+            name if name.is_empty() => self.name().to_string(),
+            name => name,
+        }
     }
 }
 
-fn format_distance(distance: f64) -> String {
-    format!("{:>8.3}km", distance)
+impl App {
+    /// Run the interface while the activities of `receiver` come in, `total`
+    /// being the number of files expected.
+    pub fn run(
+        mut self,
+        terminal: &mut DefaultTerminal,
+        args: Args,
+        receiver: Receiver<Result<Activity>>,
+        total: usize,
+    ) -> Result<()> {
+        self.configure(args, receiver, total)?;
+        self.drive(terminal, &mut TerminalEvents, &mut io::stdout())
+    }
+
+    /// Set the interface up from the command line, the configuration and the
+    /// previous session.
+    fn configure(
+        &mut self,
+        args: Args,
+        receiver: Receiver<Result<Activity>>,
+        total: usize,
+    ) -> Result<()> {
+        if let Some(dir) = &args.data_dir {
+            self.store = Store::new(dir.clone());
+        }
+
+        self.trusted_elevation_only = args.trusted_elevation_only;
+        self.full_detail = args.full_detail;
+        self.prefer = args.prefer.clone();
+        self.exclude_transport = args.exclude_transport;
+        self.sources = args.gpx_files.clone();
+        self.dem_dir = args.dem.clone();
+        #[cfg(feature = "http")]
+        {
+            self.timeout = args.timeout.map(Duration::from_secs);
+        }
+        self.color =
+            !args.no_color && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+        self.ascii = args.ascii || !unicode_locale(|name| std::env::var(name).ok());
+        self.recency_colors = !args.no_recency_colors;
+
+        self.weather = args
+            .weather_cache
+            .as_deref()
+            .map(WeatherCache::load)
+            .transpose()?;
+        self.config_path = args.config.unwrap_or_else(Config::default_path);
+        self.config = Config::load(&self.config_path)?;
+        let reference_climbs = args
+            .reference_climbs
+            .unwrap_or_else(|| self.config_path.with_file_name(REFERENCE_CLIMBS_FILE));
+        if reference_climbs.exists() {
+            self.reference_climbs = load_reference_climbs(&reference_climbs)?;
+        }
+        self.config.ftp = args.ftp.or(self.config.ftp);
+        self.config.max_hr = args.max_hr.or(self.config.max_hr);
+        if !args.controls.is_empty() {
+            self.config.controls = args.controls;
+        }
+        check_controls(&self.config.controls)?;
+        self.tuning = self.config.tuning;
+        if let Some(segment_joins) = args.segment_joins {
+            self.tuning.segment_joins = segment_joins;
+        }
+        self.rules = self.config.tag_rules()?;
+        self.share = self.config.share_template()?;
+        self.weekend = self.config.weekend_days()?;
+        self.altitude_thresholds = self.config.altitude_thresholds();
+        self.lang = args.lang.or(self.config.lang).unwrap_or_default();
+        let colorfgbg = std::env::var("COLORFGBG").ok();
+        self.theme = Theme::new(
+            args.theme
+                .or(self.config.theme)
+                .or_else(|| ThemeName::detect(colorfgbg.as_deref()))
+                .unwrap_or(ThemeName::Dark),
+        );
+        self.date_format = args.date_format;
+        self.plan_speed_kmh = args.plan_speed;
+        self.stop_every_km = args.stop_every;
+        #[cfg(feature = "images")]
+        {
+            self.images =
+                Protocol::detect(|name| std::env::var(name).ok()).map(|protocol| MapImages {
+                    protocol,
+                    tiles_dir: args.tiles_dir,
+                    placed: None,
+                });
+        }
+        self.filter.tags = args.tags;
+        self.filter.activity_type = args.activity_type;
+        self.filter.creator = args.creator;
+        self.filter.latest = args.latest;
+        self.filter.include_undated = args.include_undated;
+        self.loading = Some(Loading {
+            receiver,
+            received: 0,
+            total,
+            reload: false,
+        });
+
+        let session = self.store.session()?;
+        self.restore = session.selected;
+        self.split = session.split.unwrap_or(DEFAULT_SPLIT);
+        self.combine_days = session.combine_days;
+        self.group_months = session.group_months;
+        self.expanded_days = session
+            .expanded_days
+            .iter()
+            .filter_map(|day| day.parse().ok())
+            .collect();
+        self.folded = DETAIL_SECTIONS
+            .iter()
+            .map(|section| section.key)
+            .filter(|key| session.folded_sections.iter().any(|folded| folded == key))
+            .collect();
+        self.session_unknown = session.unknown;
+        self.runs = session.runs;
+        Ok(())
+    }
+
+    /// Draw and handle the `events` until the interface is left, then save
+    /// the changes. Escape sequences the backend does not know of, for the
+    /// clipboard and the map images, are written to `out`.
+    fn drive<B: Backend>(
+        mut self,
+        terminal: &mut Terminal<B>,
+        events: &mut impl EventSource,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        while !self.exit {
+            self.tick(terminal, out)?;
+            match events.next_event(TICK)? {
+                // Drawn again by the next tick, without waiting for a key.
+                Some(Event::Resize(..)) => self.resize(terminal)?,
+                Some(event) => self.handle_event(event),
+                None => {}
+            }
+        }
+        #[cfg(feature = "images")]
+        if self
+            .images
+            .as_ref()
+            .is_some_and(|images| images.protocol == Protocol::Kitty)
+        {
+            out.write_all(KITTY_DELETE.as_bytes())?;
+            out.flush()?;
+        }
+
+        self.save_changes()?;
+        self.store.save_session(&self.session())
+    }
+
+    /// Take in the activities loaded since the last tick and draw the
+    /// interface.
+    fn tick<B: Backend>(&mut self, terminal: &mut Terminal<B>, out: &mut impl Write) -> Result<()> {
+        self.today = Local::now().date_naive();
+        self.receive()?;
+        self.load_detail();
+        self.compute_model_distances();
+        terminal.draw(|frame| frame.render_widget(&mut *self, frame.area()))?;
+        if let Some(text) = self.clipboard.take() {
+            out.write_all(clipboard_escape(&text).as_bytes())?;
+            out.flush()?;
+        }
+        #[cfg(feature = "images")]
+        self.place_map_image(terminal, out)?;
+        Ok(())
+    }
+
+    /// Fit the interface to a new size of the terminal: the cells drawn at
+    /// the old size are cleared, images included, and the selection is kept
+    /// in the list, the offset not past it.
+    fn resize<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        terminal.autoresize()?;
+        terminal.clear()?;
+        #[cfg(feature = "images")]
+        if let Some(images) = &mut self.images {
+            images.placed = None;
+        }
+        self.file_list.keep_selection_in_view();
+        Ok(())
+    }
+
+    /// What to restore when the browser opens again.
+    fn session(&self) -> Session {
+        Session {
+            selected: self.selected().map(|file| file.activity.id.to_string()),
+            split: Some(self.split),
+            combine_days: self.combine_days,
+            group_months: self.group_months,
+            expanded_days: self
+                .expanded_days
+                .iter()
+                .map(|day| day.to_string())
+                .collect(),
+            folded_sections: self.folded.iter().map(|key| key.to_string()).collect(),
+            runs: self.runs.clone(),
+            unknown: self.session_unknown.clone(),
+        }
+    }
+
+    /// Draw the route map of the selected ride as an image over the cells
+    /// left blank for it, unless it is already there. Kitty images are
+    /// removed first; iTerm2 images are overwritten, after clearing the
+    /// screen when the map moved.
+    #[cfg(feature = "images")]
+    fn place_map_image<B: Backend>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        use crossterm::cursor::{MoveTo, RestorePosition, SavePosition};
+        use crossterm::style::Print;
+
+        let Some(images) = &self.images else {
+            return Ok(());
+        };
+        let wanted = self
+            .map_area
+            .zip(self.selected())
+            .map(|(area, file)| (file.activity.id, self.map_zoom, area));
+        if wanted == images.placed {
+            return Ok(());
+        }
+        let (protocol, placed) = (images.protocol, images.placed);
+
+        if protocol == Protocol::Kitty && placed.is_some() {
+            out.write_all(KITTY_DELETE.as_bytes())?;
+        }
+        if protocol == Protocol::Iterm2
+            && placed
+                .zip(wanted)
+                .is_some_and(|(placed, wanted)| placed.2 != wanted.2)
+        {
+            terminal.clear()?;
+            terminal.draw(|frame| frame.render_widget(&mut *self, frame.area()))?;
+        }
+
+        let png = wanted.and_then(|(_, zoom, area)| {
+            let points = self.selected().and_then(|file| self.map_points(file))?;
+            let (width, height) = image_size(area);
+            let tiles_dir = self.images.as_ref()?.tiles_dir.as_deref();
+            Some(encode_png(&route_image(
+                points, width, height, zoom, tiles_dir,
+            )?))
+        });
+        let placed = match (wanted, png) {
+            (Some((id, zoom, area)), Some(png)) => {
+                crossterm::queue!(
+                    out,
+                    SavePosition,
+                    MoveTo(area.x, area.y),
+                    Print(protocol.escape(&png, area.width, area.height)),
+                    RestorePosition
+                )?;
+                Some((id, zoom, area))
+            }
+            // Tried again once the points are read.
+            _ => None,
+        };
+        out.flush()?;
+        if let Some(images) = &mut self.images {
+            images.placed = placed;
+        }
+        Ok(())
+    }
+
+    /// Add the activities loaded since the last tick, keeping the selected
+    /// one selected.
+    fn receive(&mut self) -> Result<()> {
+        let Some(loading) = &mut self.loading else {
+            return Ok(());
+        };
+        let mut results = vec![];
+        let done = loop {
+            match loading.receiver.try_recv() {
+                Ok(result) => results.push(result),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        loading.received += results.len();
+        if results.is_empty() && !done {
+            return Ok(());
+        }
+
+        let reload = loading.reload;
+        let mut selected = self.selected().map(|file| file.activity.id);
+        for result in results {
+            match result {
+                Ok(activity) if reload => self.reload(activity),
+                Ok(activity) => self.insert(activity)?,
+                Err(err) => {
+                    let error = format!("{err:#}");
+                    self.status = Some(error.clone());
+                    self.failures.push(error);
+                }
+            }
+        }
+        let warnings = self.store.take_warnings();
+        if !warnings.is_empty() {
+            self.status = Some(warnings.join(", "));
+        }
+        if let Some(restore) = &self.restore
+            && let Some(file) = self
+                .file_list
+                .files
+                .iter()
+                .find(|file| file.activity.id.to_string() == *restore)
+        {
+            selected = Some(file.activity.id);
+            self.restore = None;
+        }
+        if done {
+            self.loading = None;
+            self.finish_loading();
+            // The climbs do not depend on the tuning.
+            if !reload {
+                self.group_climbs();
+                self.record_run(Local::now().fixed_offset());
+            }
+        }
+
+        self.refresh_list();
+        if let Some(id) = selected {
+            self.file_list.select(id);
+        }
+        Ok(())
+    }
+
+    /// Add a loaded activity to the list. Of two files with the same
+    /// activity, the first path is kept whatever the loading order, and of
+    /// two files of the same ride in different formats the one preferred,
+    /// like [`load_activities`] does.
+    fn insert(&mut self, mut activity: Activity) -> Result<()> {
+        let files = &mut self.file_list.files;
+        if let Some(i) = files
+            .iter()
+            .position(|file| !file.missing && file.activity.is_alternate_of(&activity))
+        {
+            if !is_preferred(&activity, &files[i].activity, &self.prefer) {
+                let kept = &mut files[i].activity;
+                kept.alternates.push(activity.path);
+                kept.alternates.sort();
+                return Ok(());
+            }
+            let replaced = files.remove(i).activity;
+            activity.alternates.extend(replaced.alternates);
+            activity.alternates.push(replaced.path);
+            activity.alternates.sort();
+        } else if let Some(i) = files
+            .iter()
+            .position(|file| file.activity.id == activity.id)
+        {
+            // A file found gone is replaced by the one found now, if moved.
+            if !files[i].missing {
+                if activity.path >= files[i].activity.path {
+                    self.duplicates.push(activity.path);
+                    return Ok(());
+                }
+                self.duplicates.push(files[i].activity.path.clone());
+            }
+            files.remove(i);
+        }
+
+        let sidecar = self.store.sidecar(&activity)?;
+        let file = self.file_item(activity, sidecar);
+        self.file_list.insert(file);
+        Ok(())
+    }
+
+    /// The list entry of an activity just read, its points released.
+    fn file_item(&self, mut activity: Activity, sidecar: Sidecar) -> FileItem {
+        self.find_transport(&mut activity);
+        if !self.tuning.is_default() {
+            activity.retune(self.tuning);
+        }
+        let tags = auto_tags(&self.rules, &activity);
+        let mut file = FileItem::new(
+            activity,
+            sidecar,
+            false,
+            tags,
+            self.config.grade_model.unwrap_or_default(),
+        );
+        if !file.activity.virtual_ride {
+            file.climb_checks = check_reference_climbs(
+                &file.activity.points,
+                &self.reference_climbs,
+                &file.activity.tuning,
+            );
+            file.time_above = time_above(&file.activity.points, &self.altitude_thresholds);
+        }
+        file.intervals = detect_intervals(&file.activity.points, &self.config);
+        file.empty = file.activity.points.is_empty() && file.activity.route_points.is_empty();
+        file.release_points();
+        file
+    }
+
+    /// Compute the metrics of the loaded activities with other parameters.
+    /// Files whose points were released are read again in the background.
+    fn retune(&mut self, tuning: Tuning) {
+        if self.loading.as_ref().is_some_and(|loading| !loading.reload) {
+            self.status = Some(
+                self.lang
+                    .text("the tuning can change once the files are loaded")
+                    .to_string(),
+            );
+            return;
+        }
+        self.tuning = tuning;
+        self.details.clear();
+        self.detail_loading = None;
+        self.model_distances.clear();
+        self.model_distances_computing = None;
+        let grade_model = self.config.grade_model.unwrap_or_default();
+        let mut released = vec![];
+        for file in &mut self.file_list.files {
+            if file.released {
+                released.push(file.activity.path.clone());
+                continue;
+            }
+            file.activity.retune(tuning);
+            file.flat_km = grade_adjusted_km(&file.activity, grade_model);
+            file.terrain = terrain_breakdown(&file.activity);
+            file.surface = estimate_surface(&file.activity.points, &tuning);
+            file.auto_tags = auto_tags(&self.rules, &file.activity);
+        }
+        if released.is_empty() {
+            self.loading = None;
+            self.finish_loading();
+        } else {
+            // A reload still running is replaced, its results being stale.
+            self.loading = Some(Loading {
+                total: released.len(),
+                receiver: spawn_loader(released, self.load_options()),
+                received: 0,
+                reload: true,
+            });
+        }
+        self.refresh_list();
+    }
+
+    /// Options to read a file again with, for its points, which carry the
+    /// DEM elevations as when first read.
+    fn load_options(&self) -> LoadOptions {
+        LoadOptions {
+            dem: self.dem_dir.clone().map(Dem::new),
+            full_detail: self.full_detail,
+            ..LoadOptions::default()
+        }
+    }
+
+    /// Options to read a file afresh with, as it was first read.
+    fn read_options(&self) -> LoadOptions {
+        LoadOptions {
+            dem: self.dem_dir.clone().map(Dem::new),
+            full_detail: self.full_detail,
+            timeout: self.timeout,
+            ..LoadOptions::default()
+        }
+    }
+
+    /// Read the selected ride, or the rides of the selected day, again from
+    /// their files, e.g. once fixed in an editor. A file deleted since stays
+    /// listed as missing.
+    fn reload_selected(&mut self) {
+        let lang = self.lang;
+        if self.loading.is_some() {
+            self.status = Some(
+                lang.text("the files can be read again once they are loaded")
+                    .to_string(),
+            );
+            return;
+        }
+        let ids: Vec<ActivityId> = self
+            .file_list
+            .selected_files()
+            .into_iter()
+            .map(|i| self.file_list.files[i].activity.id)
+            .collect();
+        let options = self.read_options();
+        let mut selected = None;
+        let mut statuses = vec![];
+        for id in ids {
+            let files = &mut self.file_list.files;
+            let Some(index) = files.iter().position(|file| file.activity.id == id) else {
+                continue;
+            };
+            let path = files[index].activity.path.clone();
+            match load_activity(&path, &options) {
+                Ok(activity) => {
+                    let name = activity.name.clone();
+                    match self.replace(index, activity) {
+                        Ok(id) => {
+                            selected.get_or_insert(id);
+                            statuses.push(lang.fill("{name} read again", &[("name", &name)]));
+                        }
+                        Err(err) => statuses.push(format!("{err:#}")),
+                    }
+                }
+                Err(_) if !is_url(&path) && !path.exists() => {
+                    files[index].missing = true;
+                    selected.get_or_insert(id);
+                    statuses.push(lang.fill("{path} is gone", &[("path", &path.display())]));
+                }
+                Err(err) => statuses.push(format!("{err:#}")),
+            }
+        }
+        if statuses.is_empty() {
+            return;
+        }
+
+        self.route_matches = None;
+        self.raw_metrics = None;
+        self.finish_loading();
+        self.group_climbs();
+        self.status = Some(statuses.join(", "));
+        self.refresh_list();
+        if let Some(id) = selected {
+            self.file_list.select(id);
+        }
+    }
+
+    /// Put an activity read again in place of the file at `index`. Its
+    /// edits and mark carry over when its id changed with its points,
+    /// unless the new id already has edits of its own.
+    fn replace(&mut self, index: usize, activity: Activity) -> Result<ActivityId> {
+        let saved = self.store.sidecar(&activity)?;
+        let old = self.file_list.files.remove(index);
+        let id = activity.id;
+        if self.marked.remove(&old.activity.id) {
+            self.marked.insert(id);
+        }
+        if self
+            .detail_loading
+            .as_ref()
+            .is_some_and(|(loading, _)| *loading == old.activity.id)
+        {
+            self.detail_loading = None;
+        }
+        self.details.remove(&old.activity.id);
+        if let Some(i) = self
+            .file_list
+            .files
+            .iter()
+            .position(|file| file.activity.id == id)
+        {
+            // Now the same activity as another file.
+            self.file_list.files.remove(i);
+        }
+
+        let sidecar = if id == old.activity.id || saved == Sidecar::default() {
+            old.sidecar
+        } else {
+            saved.clone()
+        };
+        let mut file = self.file_item(activity, sidecar);
+        file.saved = saved;
+        self.file_list.insert(file);
+        Ok(id)
+    }
+
+    /// Read every file again, with those now matching the command line.
+    /// Edits are saved first and read back with the rides; files deleted
+    /// since stay listed as missing.
+    fn reload_all(&mut self) {
+        let lang = self.lang;
+        if self.loading.is_some() {
+            self.status = Some(
+                lang.text("the files can be read again once they are loaded")
+                    .to_string(),
+            );
+            return;
+        }
+        let resolved = match resolve_paths(&self.sources).and_then(|resolved| {
+            self.save_sidecars()?;
+            Ok(resolved)
+        }) {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                self.status = Some(format!("{err:#}"));
+                return;
+            }
+        };
+        self.duplicate_paths = resolved.duplicates;
+        // Archived files are only moved on exit.
+        let paths: Vec<PathBuf> = resolved
+            .paths
+            .into_iter()
+            .filter(|path| !self.archived.iter().any(|file| file.activity.path == *path))
+            .collect();
+
+        self.restore = self.selected().map(|file| file.activity.id.to_string());
+        let files = std::mem::take(&mut self.file_list.files);
+        self.file_list.files = files
+            .into_iter()
+            .filter(|file| !paths.contains(&file.activity.path))
+            .map(|mut file| {
+                file.missing = true;
+                file
+            })
+            .collect();
+        self.details.clear();
+        self.detail_loading = None;
+        self.model_distances.clear();
+        self.model_distances_computing = None;
+        self.route_matches = None;
+        self.raw_metrics = None;
+        self.failures.clear();
+        self.duplicates.clear();
+        self.status = Some(lang.text("reading every file again…").to_string());
+        self.loading = Some(Loading {
+            total: paths.len(),
+            receiver: spawn_loader(paths, self.read_options()),
+            received: 0,
+            reload: false,
+        });
+        self.refresh_list();
+    }
+
+    /// Look for the ferry and train stretches of a ride, and leave them out
+    /// of its metrics with `--exclude-transport`.
+    fn find_transport(&self, activity: &mut Activity) {
+        if activity.virtual_ride {
+            return;
+        }
+        activity.transport =
+            detect_transport(&activity.points, &self.config.transport, &activity.tuning);
+        if self.exclude_transport {
+            activity.exclude_transport();
+        }
+    }
+
+    /// Replace the metrics of a released file with those of the activity
+    /// read again, with the current tuning. The warnings and the ferry and
+    /// train stretches of the first reading are kept.
+    fn reload(&mut self, mut activity: Activity) {
+        let Some(file) = self.file_list.files.iter_mut().find(|file| {
+            file.released && file.activity.id == activity.id && file.activity.path == activity.path
+        }) else {
+            return;
+        };
+        activity.transport = std::mem::take(&mut file.activity.transport);
+        if file.activity.transport_excluded {
+            activity.exclude_transport();
+        }
+        if !self.tuning.is_default() {
+            activity.retune(self.tuning);
+        }
+        activity.warnings = std::mem::take(&mut file.activity.warnings);
+        let grade_model = self.config.grade_model.unwrap_or_default();
+        file.flat_km = grade_adjusted_km(&activity, grade_model);
+        file.terrain = terrain_breakdown(&activity);
+        file.surface = estimate_surface(&activity.points, &activity.tuning);
+        if !activity.virtual_ride {
+            file.wind = estimate_wind(&activity.points, &activity.tuning);
+        }
+        activity.release_points();
+        file.auto_tags = auto_tags(&self.rules, &activity);
+        file.activity = activity;
+    }
+
+    /// Points of the selected activity, when they were released: the ones
+    /// read on an earlier tick are taken from the cache, which keeps them as
+    /// the most recently viewed, and the others are read in the background.
+    fn load_detail(&mut self) {
+        if let Some((id, receiver)) = &self.detail_loading {
+            let id = *id;
+            match receiver.try_recv() {
+                Ok(result) => {
+                    self.detail_loading = None;
+                    let detail = match result {
+                        Ok(mut activity) => {
+                            if !self.tuning.is_default() {
+                                activity.retune(self.tuning);
+                            }
+                            Detail {
+                                points: activity.points,
+                                elevation_profile: activity.elevation_profile,
+                            }
+                        }
+                        // Cached empty so the file is not read on every tick.
+                        Err(err) => {
+                            self.status = Some(format!("{err:#}"));
+                            Detail {
+                                points: vec![],
+                                elevation_profile: vec![],
+                            }
+                        }
+                    };
+                    self.details.insert(id, detail);
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => self.detail_loading = None,
+            }
+        }
+
+        let Some((id, path)) = self
+            .selected()
+            .filter(|file| file.released && !file.missing)
+            .map(|file| (file.activity.id, file.activity.path.clone()))
+        else {
+            return;
+        };
+        if self.details.get(&id).is_some() {
+            return;
+        }
+        let options = self.load_options();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // The receiver is gone when another activity was selected since.
+            let _ = sender.send(load_activity(&path, &options));
+        });
+        self.detail_loading = Some((id, receiver));
+    }
+
+    /// Distances of the selected ride under every model, while they are
+    /// compared: computed in the background, as the points of a long ride
+    /// take a while, and kept for the session.
+    fn compute_model_distances(&mut self) {
+        if let Some((id, receiver)) = &self.model_distances_computing {
+            let id = *id;
+            match receiver.try_recv() {
+                Ok(distances) => {
+                    self.model_distances_computing = None;
+                    self.model_distances.insert(id, distances);
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => self.model_distances_computing = None,
+            }
+        }
+
+        if !self.show_distance_models {
+            return;
+        }
+        let Some(file) = self.selected() else {
+            return;
+        };
+        let id = file.activity.id;
+        if self.model_distances.contains_key(&id) {
+            return;
+        }
+        // Released points are read again first, see `load_detail`.
+        let Some(points) = self
+            .points(file)
+            .filter(|points| !points.is_empty())
+            .map(<[TrackPoint]>::to_vec)
+        else {
+            return;
+        };
+        let tuning = self.tuning;
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(ModelDistances::of(&points, &tuning));
+        });
+        self.model_distances_computing = Some((id, receiver));
+    }
+
+    /// Points of a file, `None` while they are read again.
+    fn points<'a>(&'a self, file: &'a FileItem) -> Option<&'a [TrackPoint]> {
+        if !file.released {
+            return Some(&file.activity.points);
+        }
+        self.details
+            .peek(&file.activity.id)
+            .map(|detail| detail.points.as_slice())
+    }
+
+    /// Points of a file, read again now when they were released and are
+    /// not cached.
+    fn read_points<'a>(&'a self, file: &'a FileItem) -> Result<Cow<'a, [TrackPoint]>> {
+        match self.points(file) {
+            Some(points) => Ok(Cow::Borrowed(points)),
+            None => Ok(Cow::Owned(
+                load_activity(&file.activity.path, &self.load_options())?.points,
+            )),
+        }
+    }
+
+    /// Elevation profile of a file, `None` while it is read again.
+    fn elevation_profile<'a>(&'a self, file: &'a FileItem) -> Option<&'a [(f64, f64)]> {
+        if !file.released {
+            return Some(&file.activity.elevation_profile);
+        }
+        self.details
+            .peek(&file.activity.id)
+            .map(|detail| detail.elevation_profile.as_slice())
+    }
+
+    /// Metrics comparing every activity with the others, computed once all
+    /// are loaded.
+    fn finish_loading(&mut self) {
+        let activities = self.file_list.files.iter().map(|file| &file.activity);
+        let commutes = detect_commutes(activities.clone(), &self.config);
+        self.new_roads = new_road_km_from_traces(
+            self.file_list
+                .files
+                .iter()
+                .map(|file| (&file.activity, &file.road_trace)),
+        );
+        for (file, commute) in self.file_list.files.iter_mut().zip(commutes) {
+            file.auto_commute = commute;
+        }
+    }
+
+    /// Add the totals of the archive just loaded to the history of the runs,
+    /// and tell what changed since the last one, warning about rides gone.
+    fn record_run(&mut self, now: DateTime<FixedOffset>) {
+        let activities = self
+            .file_list
+            .files
+            .iter()
+            .filter(|file| !file.missing)
+            .map(|file| &file.activity);
+        let keep = self.config.run_history.unwrap_or(DEFAULT_RUN_HISTORY);
+        let Some(delta) = record_run(&mut self.runs, RunTotals::of(activities, now), keep) else {
+            return;
+        };
+        if delta.is_none() {
+            return;
+        }
+        let lang = self.lang;
+        let mut rides = lang.count(delta.rides.unsigned_abs() as usize, "ride", "rides");
+        rides.insert(0, if delta.rides < 0 { '-' } else { '+' });
+        let template = if delta.is_loss() {
+            "since last run: {rides}, {distance} km; were files deleted?"
+        } else {
+            "since last run: {rides}, {distance} km"
+        };
+        let mut distance = lang.number(delta.distance_km, 0);
+        if !distance.starts_with('-') {
+            distance.insert(0, '+');
+        }
+        let message = lang.fill(template, &[("rides", &rides), ("distance", &distance)]);
+        self.status = Some(match self.status.take() {
+            Some(status) => format!("{message}, {status}"),
+            None => message,
+        });
+    }
+
+    /// Group the ascents of the same climbs, and tell when the latest ascent
+    /// of a climb is the fastest.
+    fn group_climbs(&mut self) {
+        self.climb_groups = group_climbs(self.file_list.files.iter().flat_map(|file| {
+            file.climbs
+                .iter()
+                .map(move |climb| (file.activity.id, climb))
+        }));
+
+        let lang = self.lang;
+        let records: Vec<String> = self
+            .climb_groups
+            .iter()
+            .filter_map(|group| {
+                // Ascents are in date order, the latest timed one is last.
+                let latest = group
+                    .ascents
+                    .iter()
+                    .rfind(|(_, climb)| climb.seconds.is_some())?;
+                let leaderboard = group.leaderboard();
+                let (best, previous) = (leaderboard.first()?, leaderboard.get(1)?);
+                if best.0 != latest.0 {
+                    return None;
+                }
+                let (time, previous) = (best.1.seconds?, previous.1.seconds?);
+                Some(lang.fill(
+                    "new PR on {climb}: {time}, {gain} faster",
+                    &[
+                        ("climb", &format_climb(&group.climb, lang)),
+                        ("time", &format_duration(time)),
+                        ("gain", &format_duration(previous - time)),
+                    ],
+                ))
+            })
+            .collect();
+        match records.as_slice() {
+            [] => {}
+            [record] => self.status = Some(record.clone()),
+            _ => {
+                self.status = Some(lang.fill(
+                    "new PRs on {count} climbs, l to see the climbs of a ride",
+                    &[("count", &records.len())],
+                ))
+            }
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        match event {
+            // it's important to check that the event is a key press event as
+            // crossterm also emits key release and repeat events on Windows.
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                self.handle_key_event(key_event)
+            }
+            _ => {}
+        };
+    }
+
+    fn handle_key_event(&mut self, key_event: KeyEvent) {
+        if self.prompt.is_some() {
+            self.handle_prompt_key(key_event);
+            return;
+        }
+        if let Some(row) = self.settings {
+            self.handle_settings_key(key_event, row);
+            return;
+        }
+        if let Some(palette) = self.palette.take() {
+            self.handle_palette_key(key_event, palette);
+            return;
+        }
+        if let Some(day) = self.calendar {
+            self.handle_calendar_key(key_event, day);
+            return;
+        }
+        if let Some(km) = self.scrub {
+            self.handle_scrub_key(key_event, km);
+            return;
+        }
+        if let Some(view) = self.tour_view.take() {
+            self.handle_tour_key(key_event, view);
+            return;
+        }
+        if let Some(row) = self.quality_view.take() {
+            self.handle_quality_key(key_event, row);
+            return;
+        }
+        if let Some(row) = self.hidden_view.take() {
+            self.handle_hidden_key(key_event, row);
+            return;
+        }
+        if self.show_stats && Key::char('c').matches(&key_event) {
+            self.calendar = Some(Local::now().date_naive());
+            return;
+        }
+        self.status = None;
+
+        if let Some(command) = command_for(COMMANDS, &key_event) {
+            (command.run)(self);
+        }
+    }
+
+    /// Keys of the calendar: the arrows move the cursor by a day or a week,
+    /// `c` and Esc go back to the statistics.
+    fn handle_calendar_key(&mut self, key_event: KeyEvent, day: NaiveDate) {
+        let days = match key_event.code {
+            KeyCode::Esc | KeyCode::Char('c') => {
+                self.calendar = None;
+                return;
+            }
+            KeyCode::Up | KeyCode::Char('k') => -1,
+            KeyCode::Down | KeyCode::Char('j') => 1,
+            KeyCode::Left | KeyCode::Char('h') => -7,
+            KeyCode::Right | KeyCode::Char('l') => 7,
+            _ => return,
+        };
+        let grid = CalendarGrid::last_year(Local::now().date_naive());
+        self.calendar = Some(grid.step(day, days));
+    }
+
+    /// Keys of the scrub cursor: the arrows move it by 100 m, or a kilometer
+    /// with Shift, Home and End go to the ends, `p` and Esc leave.
+    fn handle_scrub_key(&mut self, key_event: KeyEvent, km: f64) {
+        let step = if key_event.modifiers.contains(KeyModifiers::SHIFT) {
+            1.0
+        } else {
+            0.1
+        };
+        let target = match key_event.code {
+            KeyCode::Esc | KeyCode::Char('p') => {
+                self.scrub = None;
+                return;
+            }
+            KeyCode::Left | KeyCode::Char('h') => km - step,
+            KeyCode::Right | KeyCode::Char('l') => km + step,
+            KeyCode::Home => 0.0,
+            KeyCode::End => f64::INFINITY,
+            _ => return,
+        };
+        // The sample holds the ends of the ride.
+        if let Some(sample) = self.scrub_sample(target) {
+            self.scrub = Some(sample.distance_km);
+        }
+    }
+
+    /// The selected ride at `km`, once its points are read.
+    fn scrub_sample(&self, km: f64) -> Option<Sample> {
+        let points = self.points(self.selected()?)?;
+        sample_at(points, &self.tuning, km)
+    }
+
+    fn start_scrub(&mut self) {
+        if self.scrub_sample(0.0).is_some() {
+            self.scrub = Some(0.0);
+            self.focus = Focus::Detail;
+        }
+    }
+
+    /// Keys of the command palette: typing searches the commands, the arrows
+    /// select one and Enter runs it.
+    fn handle_palette_key(&mut self, key_event: KeyEvent, mut palette: Palette) {
+        let matches = self.palette_matches(&palette.query).len();
+        match key_event.code {
+            KeyCode::Esc => return,
+            KeyCode::Enter => {
+                if let Some(command) = self.palette_matches(&palette.query).get(palette.selected) {
+                    self.status = None;
+                    (command.run)(self);
+                }
+                return;
+            }
+            KeyCode::Up => palette.selected = palette.selected.saturating_sub(1),
+            KeyCode::Down => {
+                palette.selected = (palette.selected + 1).min(matches.saturating_sub(1))
+            }
+            KeyCode::Backspace => {
+                palette.query.pop();
+                palette.selected = 0;
+            }
+            KeyCode::Char(c) if !key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                palette.query.push(c);
+                palette.selected = 0;
+            }
+            _ => {}
+        }
+        self.palette = Some(palette);
+    }
+
+    /// The commands matching a search of the palette, in the language of
+    /// the interface.
+    fn palette_matches(&self, query: &str) -> Vec<&'static UserCommand<App>> {
+        let lang = self.lang;
+        search(COMMANDS, query, |text| lang.text(text))
+    }
+
+    fn open_rename(&mut self) {
+        self.prompt = self.selected().map(|file| Prompt {
+            kind: PromptKind::Rename,
+            text: file.sidecar.name.clone().unwrap_or_default(),
+        })
+    }
+
+    fn open_tag(&mut self) {
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Tag,
+            text: String::new(),
+        })
+    }
+
+    fn open_filter(&mut self) {
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Filter,
+            text: self.filter.text.clone(),
+        })
+    }
+
+    fn open_tour(&mut self) {
+        self.prompt = self.selected().map(|file| Prompt {
+            kind: PromptKind::Tour,
+            text: file.sidecar.tour.clone().unwrap_or_default(),
+        })
+    }
+
+    /// Ask where to write the selected ride and the one of the same route
+    /// it is compared with, side by side.
+    fn open_comparison_export(&mut self) {
+        self.update_route_matches();
+        let rides = self.route_rides();
+        let Some((file, reference)) = self.selected().zip(self.route_reference(&rides)) else {
+            self.status = Some(
+                self.lang
+                    .text("no other timed ride of this route to compare with")
+                    .to_string(),
+            );
+            return;
+        };
+        let stem = |file: &FileItem| {
+            file.activity
+                .path
+                .file_stem()
+                .map_or("ride".into(), |stem| stem.to_string_lossy().into_owned())
+        };
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Comparison,
+            text: format!("{}-vs-{}.csv", stem(file), stem(reference)),
+        });
+    }
+
+    /// Write the comparison of the selected ride, its files read again when
+    /// their points were released.
+    fn export_comparison(&self, path: &Path) -> Result<()> {
+        let rides = self.route_rides();
+        let (file, reference) = self
+            .selected()
+            .zip(self.route_reference(&rides))
+            .context("no other timed ride of this route to compare with")?;
+        let aligned = align_rides(&self.read_points(file)?, &self.read_points(reference)?)
+            .context("the rides have no length to compare")?;
+        let label = |file: &FileItem| file.label(self.date_format());
+        let mut csv = vec![];
+        write_aligned_csv(&aligned, (&label(file), &label(reference)), &mut csv)?;
+        fs::write(path, csv).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// List the stages of the tour of the selected ride, starting on it.
+    fn open_tour_view(&mut self) {
+        let Some(file) = self.selected() else {
+            return;
+        };
+        let id = file.activity.id;
+        let Some(name) = file.sidecar.tour.clone() else {
+            self.status = Some(
+                self.lang
+                    .text("not part of a tour, T adds it to one")
+                    .to_string(),
+            );
+            return;
+        };
+        let stage = self
+            .tour(&name)
+            .and_then(|tour| tour.stages.iter().position(|stage| stage.id == id))
+            .unwrap_or_default();
+        self.tour_view = Some(TourView { name, stage });
+    }
+
+    /// Keys of the tour view: the arrows move between the stages, Enter
+    /// selects the stage in the list, `d` takes it out of the tour, `o` and
+    /// Esc close the view.
+    fn handle_tour_key(&mut self, key_event: KeyEvent, mut view: TourView) {
+        let Some(tour) = self.tour(&view.name) else {
+            return;
+        };
+        let last = tour.stages.len() - 1;
+        let id = tour.stages[view.stage.min(last)].id;
+        match key_event.code {
+            KeyCode::Esc | KeyCode::Char('o') | KeyCode::Char('q') => return,
+            KeyCode::Up | KeyCode::Char('k') => view.stage = view.stage.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => view.stage = (view.stage + 1).min(last),
+            KeyCode::Home => view.stage = 0,
+            KeyCode::End => view.stage = last,
+            KeyCode::Enter => {
+                if self.select_activity(id) {
+                    return;
+                }
+                self.status = Some(
+                    self.lang
+                        .text("the stage is hidden by the filter")
+                        .to_string(),
+                );
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(index) = self
+                    .file_list
+                    .files
+                    .iter()
+                    .position(|file| file.activity.id == id)
+                {
+                    self.edit_sidecars("leave tour", &[index], |sidecar| sidecar.tour = None);
+                }
+                if last == 0 {
+                    return;
+                }
+                view.stage = view.stage.min(last - 1);
+            }
+            _ => {}
+        }
+        self.tour_view = Some(view);
+    }
+
+    /// The files with a quality score, the worst first.
+    fn quality_ranking(&self) -> Vec<(&FileItem, &QualityScore)> {
+        let mut files: Vec<_> = self
+            .file_list
+            .files
+            .iter()
+            .filter_map(|file| Some((file, file.quality.as_ref()?)))
+            .collect();
+        files.sort_by_key(|(file, quality)| (quality.score(), file.sort_key()));
+        files
+    }
+
+    /// Keys of the quality dashboard: the arrows move between the rides,
+    /// Enter selects the ride and shows its data quality in the detail, `Q`
+    /// and Esc close the dashboard.
+    fn handle_quality_key(&mut self, key_event: KeyEvent, mut row: usize) {
+        let ranking = self.quality_ranking();
+        let last = ranking.len().saturating_sub(1);
+        let id = ranking.get(row.min(last)).map(|(file, _)| file.activity.id);
+        match key_event.code {
+            KeyCode::Esc | KeyCode::Char('Q') | KeyCode::Char('q') => return,
+            KeyCode::Up | KeyCode::Char('k') => row = row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => row = (row + 1).min(last),
+            KeyCode::Home => row = 0,
+            KeyCode::End => row = last,
+            KeyCode::Enter => {
+                if let Some(id) = id {
+                    if self.select_activity(id) {
+                        self.focus = Focus::Detail;
+                        self.folded.remove("quality");
+                        self.section_jump = Some("quality");
+                        return;
+                    }
+                    self.status = Some(
+                        self.lang
+                            .text("the ride is hidden by the filter")
+                            .to_string(),
+                    );
+                }
+            }
+            _ => {}
+        }
+        self.quality_view = Some(row);
+    }
+
+    /// Everything left out of the list, by reason: the rides the filter
+    /// or the user hides, those without points, and the files skipped or
+    /// not read.
+    fn hidden_items(&self) -> Vec<HiddenItem> {
+        let date_format = self.date_format();
+        let visible: BTreeSet<usize> = self.file_list.visible.iter().copied().collect();
+        let mut items: Vec<HiddenItem> = self
+            .file_list
+            .files
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !visible.contains(i))
+            .map(|(i, file)| HiddenItem {
+                exclusion: file.hidden_by().unwrap_or(Exclusion::Filtered),
+                label: file.label(date_format),
+                index: Some(i),
+            })
+            .collect();
+        items.extend(self.duplicates.iter().map(|path| HiddenItem {
+            exclusion: Exclusion::Duplicate,
+            label: path.display().to_string(),
+            index: None,
+        }));
+        items.extend(self.failures.iter().map(|error| HiddenItem {
+            exclusion: Exclusion::Failed,
+            label: error.clone(),
+            index: None,
+        }));
+        items.sort_by_key(|item| item.exclusion);
+        items
+    }
+
+    /// Keys of the hidden view: the arrows move between the entries, Enter
+    /// shows again a ride hidden by hand or for having no points, `H` and
+    /// Esc close the view.
+    fn handle_hidden_key(&mut self, key_event: KeyEvent, mut row: usize) {
+        let items = self.hidden_items();
+        let last = items.len().saturating_sub(1);
+        match key_event.code {
+            KeyCode::Esc | KeyCode::Char('H') | KeyCode::Char('q') => return,
+            KeyCode::Up | KeyCode::Char('k') => row = row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => row = (row + 1).min(last),
+            KeyCode::Home => row = 0,
+            KeyCode::End => row = last,
+            KeyCode::Enter => {
+                if let Some(item) = items.get(row.min(last)) {
+                    self.restore_hidden(item);
+                }
+            }
+            _ => {}
+        }
+        self.hidden_view = Some(row);
+    }
+
+    fn restore_hidden(&mut self, item: &HiddenItem) {
+        let shown = match item.exclusion {
+            Exclusion::Manual => None,
+            // Shown despite having no points.
+            Exclusion::Empty => Some(false),
+            Exclusion::Filtered => {
+                self.status = Some(
+                    self.lang
+                        .text("the ride is hidden by the filter")
+                        .to_string(),
+                );
+                return;
+            }
+            Exclusion::Duplicate => {
+                self.status = Some(
+                    self.lang
+                        .text("the file holds a ride already listed")
+                        .to_string(),
+                );
+                return;
+            }
+            Exclusion::Failed => {
+                self.status = Some(self.lang.text("the file could not be read").to_string());
+                return;
+            }
+        };
+        if let Some(index) = item.index {
+            self.edit_sidecars("show", &[index], |sidecar| sidecar.hidden = shown);
+        }
+    }
+
+    /// Hide the marked or selected rides from the list and the totals.
+    fn hide(&mut self) {
+        let targets = self.targets();
+        self.edit_sidecars("hide", &targets, |sidecar| sidecar.hidden = Some(true));
+    }
+
+    /// Select an activity in the list, unless the filter hides it.
+    fn select_activity(&mut self, id: ActivityId) -> bool {
+        if !self.file_list.select(id) {
+            return false;
+        }
+        self.restore = None;
+        self.scrub = None;
+        self.detail_scroll = 0;
+        true
+    }
+
+    /// List the rides of each day as one entry, or each on its own line,
+    /// keeping the selected ride.
+    fn toggle_combine_days(&mut self) {
+        let selected = self.selected().map(|file| file.activity.id);
+        self.combine_days = !self.combine_days;
+        self.refresh_list();
+        if let Some(id) = selected {
+            self.file_list.select(id);
+        }
+    }
+
+    /// List the rides of each month under a header, or without, keeping the
+    /// selected ride.
+    fn toggle_group_months(&mut self) {
+        let selected = self.selected().map(|file| file.activity.id);
+        self.group_months = !self.group_months;
+        self.refresh_list();
+        if let Some(id) = selected {
+            self.file_list.select(id);
+        }
+    }
+
+    /// Show the distance models of the selected ride in the detail, scrolled
+    /// to, or hide them.
+    fn toggle_distance_models(&mut self) {
+        self.show_distance_models = !self.show_distance_models;
+        if self.show_distance_models {
+            self.folded.remove("distance_models");
+            self.section_jump = Some("distance_models");
+        }
+    }
+
+    /// Unfold the selected day, or the detail section at the top of the
+    /// pane when it has the focus, or fold it back.
+    fn expand(&mut self) {
+        match self.focus {
+            Focus::List => self.toggle_day(),
+            Focus::Detail => self.toggle_section(),
+        }
+    }
+
+    /// Fold the section at the top of the detail pane to its title, or
+    /// unfold it, keeping its title at the top.
+    fn toggle_section(&mut self) {
+        let Some((key, start)) = self.detail_layout.at(usize::from(self.detail_scroll)) else {
+            return;
+        };
+        if !self.folded.remove(key) {
+            self.folded.insert(key);
+        }
+        self.detail_scroll = u16::try_from(start).unwrap_or(u16::MAX);
+    }
+
+    /// List the rides of the selected day below its entry, or fold them
+    /// back, also from one of the rides.
+    fn toggle_day(&mut self) {
+        let list = &self.file_list;
+        let date = match list.state.selected().and_then(|i| list.rows.get(i)) {
+            Some(Row::Day { date, .. }) => *date,
+            Some(Row::File {
+                index,
+                nested: true,
+            }) => match list.files[*index].activity.start {
+                Some(start) => start.date_naive(),
+                None => return,
+            },
+            _ => return,
+        };
+        if !self.expanded_days.remove(&date) {
+            self.expanded_days.insert(date);
+        }
+        self.refresh_list();
+        let position = self
+            .file_list
+            .rows
+            .iter()
+            .position(|row| matches!(row, Row::Day { date: day, .. } if *day == date));
+        self.file_list.state.select(position);
+    }
+
+    /// Every tour, from the stages of all the loaded rides.
+    fn tours(&self) -> Vec<Tour> {
+        group_tours(self.file_list.files.iter().filter_map(|file| {
+            Some((
+                file.sidecar.tour.as_deref()?,
+                file.name().to_string(),
+                &file.activity,
+            ))
+        }))
+    }
+
+    fn tour(&self, name: &str) -> Option<Tour> {
+        self.tours().into_iter().find(|tour| tour.name == name)
+    }
+
+    fn set_range_start(&mut self) {
+        self.range_start = self.selected().map(FileItem::sort_key);
+        if self.range_end.is_none() {
+            self.status = Some(
+                self.lang
+                    .text("range start set, ] to set the end")
+                    .to_string(),
+            );
+        }
+    }
+
+    fn set_range_end(&mut self) {
+        self.range_end = self.selected().map(FileItem::sort_key);
+        if self.range_start.is_none() {
+            self.status = Some(
+                self.lang
+                    .text("range end set, [ to set the start")
+                    .to_string(),
+            );
+        }
+    }
+
+    fn clear_range(&mut self) {
+        self.range_start = None;
+        self.range_end = None;
+    }
+
+    /// Next activity, or scroll the detail down when it has the focus.
+    fn move_down(&mut self) {
+        match self.focus {
+            Focus::List => self.select_next(),
+            Focus::Detail => self.detail_scroll = self.detail_scroll.saturating_add(1),
+        }
+    }
+
+    fn move_up(&mut self) {
+        match self.focus {
+            Focus::List => self.select_previous(),
+            Focus::Detail => self.detail_scroll = self.detail_scroll.saturating_sub(1),
+        }
+    }
+
+    fn toggle_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::List => Focus::Detail,
+            Focus::Detail => Focus::List,
+        };
+    }
+
+    fn exit(&mut self) {
+        self.exit = true;
+    }
+
+    fn select_next(&mut self) {
+        self.restore = None;
+        self.scrub = None;
+        self.file_list.state.select_next();
+        self.detail_scroll = 0;
+    }
+
+    fn select_previous(&mut self) {
+        self.restore = None;
+        self.scrub = None;
+        self.file_list.state.select_previous();
+        self.detail_scroll = 0;
+    }
+
+    /// Keys of the settings popup: the arrows select a parameter, `+` and `-`
+    /// change it and `s` saves the tuning to the configuration file.
+    fn handle_settings_key(&mut self, key_event: KeyEvent, row: usize) {
+        let parameter = TuningParameter::ALL[row];
+        let mut tuning = self.tuning;
+        match key_event.code {
+            KeyCode::Char('k') | KeyCode::Up => self.settings = Some(row.saturating_sub(1)),
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.settings = Some((row + 1).min(TuningParameter::ALL.len() - 1))
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Right => {
+                tuning.adjust(parameter, 1.0);
+                self.retune(tuning);
+            }
+            KeyCode::Char('-') | KeyCode::Left => {
+                tuning.adjust(parameter, -1.0);
+                self.retune(tuning);
+            }
+            KeyCode::Char('s') => {
+                self.status = Some(match Config::save_tuning(&self.config_path, &self.tuning) {
+                    Ok(()) => self.lang.fill(
+                        "tuning saved to {path}",
+                        &[("path", &self.config_path.display())],
+                    ),
+                    Err(err) => format!("{err:#}"),
+                });
+            }
+            KeyCode::Esc | KeyCode::Char(',') | KeyCode::Char('q') => self.settings = None,
+            _ => {}
+        }
+    }
+
+    /// Keys typed in a prompt. The filter applies as it is typed and Esc
+    /// clears it; the other prompts apply on Enter.
+    fn handle_prompt_key(&mut self, key_event: KeyEvent) {
+        let Some(prompt) = &mut self.prompt else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Char(c) => prompt.text.push(c),
+            KeyCode::Backspace => {
+                prompt.text.pop();
+            }
+            KeyCode::Esc => {
+                if prompt.kind == PromptKind::Filter {
+                    self.filter.text.clear();
+                    self.refresh_list();
+                }
+                self.prompt = None;
+                return;
+            }
+            KeyCode::Enter => {
+                if let Some(prompt) = self.prompt.take() {
+                    self.submit(prompt);
+                }
+                return;
+            }
+            _ => return,
+        }
+
+        if prompt.kind == PromptKind::Filter {
+            self.filter.text = prompt.text.clone();
+            self.refresh_list();
+        }
+    }
+
+    /// An empty name restores the track name.
+    fn submit(&mut self, prompt: Prompt) {
+        let text = prompt.text.trim().to_string();
+        match prompt.kind {
+            PromptKind::Rename => {
+                let name = (!text.is_empty()).then_some(text);
+                if let Some(index) = self.file_list.selected_index() {
+                    self.edit_sidecars("rename", &[index], |sidecar| sidecar.name = name.clone());
+                }
+            }
+            PromptKind::Tag if !text.is_empty() => self.toggle_tag(&text),
+            PromptKind::Tour => {
+                let targets = self.targets();
+                let tour = (!text.is_empty()).then_some(text);
+                let what = match &tour {
+                    Some(tour) => self.lang.fill("join tour {tour}", &[("tour", tour)]),
+                    None => "leave tour".to_string(),
+                };
+                self.edit_sidecars(&what, &targets, |sidecar| sidecar.tour = tour.clone());
+            }
+            PromptKind::Comparison if !text.is_empty() => {
+                self.status = Some(match self.export_comparison(Path::new(&text)) {
+                    Ok(()) => self
+                        .lang
+                        .fill("comparison written to {path}", &[("path", &text)]),
+                    Err(err) => format!("{err:#}"),
+                });
+            }
+            PromptKind::Tag | PromptKind::Filter | PromptKind::Comparison => {}
+        }
+    }
+
+    /// Indices of the shown marked activities, or of the selected one when
+    /// none is marked.
+    fn targets(&self) -> Vec<usize> {
+        if self.marked.is_empty() {
+            return self.file_list.selected_files();
+        }
+        self.file_list
+            .visible
+            .iter()
+            .copied()
+            .filter(|&i| self.marked.contains(&self.file_list.files[i].activity.id))
+            .collect()
+    }
+
+    /// Add a manual tag to the targets, or remove it if they all have it.
+    fn toggle_tag(&mut self, tag: &str) {
+        let targets = self.targets();
+        let remove = targets.iter().all(|&i| {
+            self.file_list.files[i]
+                .sidecar
+                .tags
+                .iter()
+                .any(|t| t == tag)
+        });
+        let what = if remove { "untag {tag}" } else { "tag {tag}" };
+        let what = self.lang.fill(what, &[("tag", &tag)]);
+        self.edit_sidecars(&what, &targets, |sidecar| {
+            sidecar.tags.retain(|t| t != tag);
+            if !remove {
+                sidecar.tags.push(tag.to_string());
+            }
+        });
+    }
+
+    /// Apply the filter again, keeping the selected position, and update the
+    /// baselines to the shown activities.
+    fn refresh_list(&mut self) {
+        let list = &mut self.file_list;
+        list.visible = (0..list.files.len())
+            .filter(|&i| list.files[i].hidden_by().is_none() && self.filter.matches(&list.files[i]))
+            .collect();
+        list.matching = list.visible.len();
+        if let Some(latest) = self.filter.latest {
+            keep_latest(
+                &mut list.visible,
+                latest,
+                self.filter.include_undated,
+                |&i| list.files[i].activity.start,
+            );
+        }
+        list.update_rows(self.combine_days, self.group_months, &self.expanded_days);
+        let last = list.rows.len().checked_sub(1);
+        let selected = list.state.selected().or(Some(0));
+        list.state
+            .select(selected.zip(last).map(|(i, last)| i.min(last)));
+
+        self.summary = Summary::new(
+            self.visible_files()
+                .filter(|file| !file.missing)
+                .map(|file| &file.activity),
+        );
+    }
+
+    fn visible_files(&self) -> impl Iterator<Item = &FileItem> {
+        self.file_list
+            .visible
+            .iter()
+            .map(|&i| &self.file_list.files[i])
+    }
+
+    /// Copy the share text of the selected ride, also shown in the status
+    /// line for terminals that do not support copying.
+    fn copy_share(&mut self) {
+        let Some(file) = self.selected() else {
+            return;
+        };
+        let text = self.share.render(&file.activity);
+        self.status = Some(self.lang.fill("Copied: {text}", &[("text", &text)]));
+        self.clipboard = Some(text);
+    }
+
+    fn toggle_commute(&mut self) {
+        let targets = self.targets();
+        let commute: Vec<bool> = targets
+            .iter()
+            .map(|&i| !self.file_list.files[i].is_commute())
+            .collect();
+        let mut commute = commute.into_iter();
+        self.edit_sidecars("commute toggle", &targets, |sidecar| {
+            sidecar.commute = commute.next()
+        });
+    }
+
+    fn edit_sidecars(&mut self, what: &str, targets: &[usize], mut edit: impl FnMut(&mut Sidecar)) {
+        let changes = targets
+            .iter()
+            .filter_map(|&i| self.file_list.files.get(i))
+            .map(|file| {
+                let mut after = file.sidecar.clone();
+                edit(&mut after);
+                Change::Sidecar {
+                    id: file.activity.id,
+                    before: Box::new(file.sidecar.clone()),
+                    after: Box::new(after),
+                }
+            })
+            .collect();
+        self.record(self.describe(what, targets), changes);
+    }
+
+    /// Downloaded activities have no file to move and are left alone.
+    fn archive(&mut self) {
+        let targets: Vec<usize> = self
+            .targets()
+            .into_iter()
+            .filter(|&i| !is_url(&self.file_list.files[i].activity.path))
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+        let mut changes = vec![];
+        if !self.marked.is_empty() {
+            changes.push(Change::Marks {
+                before: self.marked.clone(),
+                after: BTreeSet::new(),
+            });
+        }
+        for &index in &targets {
+            changes.push(Change::Archive(Box::new(
+                self.file_list.files[index].clone(),
+            )));
+        }
+        self.record(self.describe("archive", &targets), changes);
+    }
+
+    /// Mark the selected ride, or every ride of the selected day, or clear
+    /// the marks when they are all marked.
+    fn toggle_mark(&mut self) {
+        let ids: Vec<ActivityId> = self
+            .file_list
+            .selected_files()
+            .iter()
+            .map(|&i| self.file_list.files[i].activity.id)
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+        let mut after = self.marked.clone();
+        if ids.iter().all(|id| after.contains(id)) {
+            for id in &ids {
+                after.remove(id);
+            }
+        } else {
+            after.extend(ids);
+        }
+        let description = self.lang.fill("{count} marked", &[("count", &after.len())]);
+        let before = self.marked.clone();
+        self.record(description, vec![Change::Marks { before, after }]);
+        self.select_next();
+    }
+
+    /// `rename of <name>` for one activity, `rename of 3 activities` for more.
+    fn describe(&self, what: &str, targets: &[usize]) -> String {
+        let action = self.lang.text(what);
+        match targets {
+            [index] => self.lang.fill(
+                "{action} of {name}",
+                &[
+                    ("action", &action),
+                    (
+                        "name",
+                        &self.file_list.files[*index].file_name(self.date_format()),
+                    ),
+                ],
+            ),
+            _ => self.lang.fill(
+                "{action} of {count} activities",
+                &[("action", &action), ("count", &targets.len())],
+            ),
+        }
+    }
+
+    /// Style of a ride of the list, brighter the more recent. Dates are
+    /// those shown, in the time zone of the ride.
+    fn row_style(&self, start: Option<DateTime<FixedOffset>>) -> Style {
+        match start {
+            Some(start) if self.recency_colors => self
+                .theme
+                .recency_style(Recency::of(start.date_naive(), self.today)),
+            _ => self.theme.text,
+        }
+    }
+
+    fn date_format(&self) -> &str {
+        self.date_format
+            .as_deref()
+            .unwrap_or(self.lang.date_format())
+    }
+
+    /// Apply and record a new action.
+    fn record(&mut self, description: String, changes: Vec<Change>) {
+        if changes.is_empty() {
+            return;
+        }
+        for change in &changes {
+            self.apply(change, true);
+        }
+        self.status = Some(description.clone());
+        self.history.push(Action {
+            description,
+            changes,
+        });
+    }
+
+    fn undo(&mut self) {
+        self.status = Some(match self.history.undo() {
+            Some(action) => {
+                for change in action.changes.iter().rev() {
+                    self.apply(change, false);
+                }
+                self.lang
+                    .fill("undone: {action}", &[("action", &action.description)])
+            }
+            None => self.lang.text("nothing to undo").to_string(),
+        });
+    }
+
+    fn redo(&mut self) {
+        self.status = Some(match self.history.redo() {
+            Some(action) => {
+                for change in &action.changes {
+                    self.apply(change, true);
+                }
+                self.lang
+                    .fill("redone: {action}", &[("action", &action.description)])
+            }
+            None => self.lang.text("nothing to redo").to_string(),
+        });
+    }
+
+    /// Apply a change, or revert it when `forward` is false.
+    fn apply(&mut self, change: &Change, forward: bool) {
+        match change {
+            Change::Sidecar { id, before, after } => {
+                let file = self
+                    .file_list
+                    .files
+                    .iter_mut()
+                    .find(|file| file.activity.id == *id);
+                if let Some(file) = file {
+                    file.sidecar = Sidecar::clone(if forward { after } else { before });
+                }
+            }
+            Change::Marks { before, after } => {
+                self.marked = if forward { after } else { before }.clone();
+            }
+            Change::Archive(item) => {
+                let id = item.activity.id;
+                if forward {
+                    self.file_list.files.retain(|file| file.activity.id != id);
+                    self.archived.push(*item.clone());
+                } else {
+                    self.archived.retain(|file| file.activity.id != id);
+                    self.file_list.insert(*item.clone());
+                }
+            }
+        }
+        // Renames and tags can change what the filter lets through.
+        self.refresh_list();
+    }
+
+    /// Write the edited sidecars and move the archived files.
+    fn save_changes(&self) -> Result<()> {
+        self.save_sidecars()?;
+        for file in self.archived.iter().filter(|file| !file.missing) {
+            archive_gpx(&file.activity.path)?;
+        }
+
+        Ok(())
+    }
+
+    fn save_sidecars(&self) -> Result<()> {
+        for file in self.file_list.files.iter().chain(&self.archived) {
+            if file.sidecar != file.saved {
+                self.store.save_sidecar(file.activity.id, &file.sidecar)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Shown activities counted in the totals: virtual rides only when
+    /// toggled with `v`.
+    fn counted_files(&self) -> impl Iterator<Item = &FileItem> {
+        self.visible_files()
+            .filter(|file| !file.missing && (self.count_virtual || !file.activity.virtual_ride))
+    }
+
+    /// The symbols to draw with, ASCII ones with `--ascii`.
+    fn symbols(&self) -> &'static Symbols {
+        Symbols::new(self.ascii)
+    }
+
+    /// Distance of the counted activities over the last [`SPARKLINE_WEEKS`]
+    /// weeks as bars, `None` with fewer than two weeks ridden.
+    fn weekly_sparkline(&self) -> Option<String> {
+        let today = Local::now().date_naive();
+        let weeks = weekly_distance_km(
+            self.counted_files().map(|file| &file.activity),
+            today,
+            SPARKLINE_WEEKS,
+        );
+        if weeks.iter().filter(|&&km| km > 0.0).count() < 2 {
+            return None;
+        }
+        Some(if self.ascii {
+            ascii_sparkline(&weeks)
+        } else {
+            braille_sparkline(&weeks)
+        })
+    }
+
+    /// Distance and elevation gain of the counted activities, optionally
+    /// leaving commutes out.
+    fn totals(&self, include_commutes: bool) -> (f64, f64) {
+        self.counted_files()
+            .filter(|file| include_commutes || !file.is_commute())
+            .fold((0.0, 0.0), |(distance, elevation), file| {
+                let activity = &file.activity;
+                let trusted =
+                    !(self.trusted_elevation_only && activity.has_low_quality_elevation());
+                (
+                    distance + activity.distance_km,
+                    elevation
+                        + if trusted {
+                            activity.elevation_gain
+                        } else {
+                            0.0
+                        },
+                )
+            })
+    }
+
+    /// Both range bounds, in list order.
+    fn range(&self) -> Option<(SortKey, SortKey)> {
+        let (start, end) = (self.range_start?, self.range_end?);
+        Some((start.min(end), start.max(end)))
+    }
+
+    fn in_range(&self, file: &FileItem) -> bool {
+        self.range()
+            .is_some_and(|(start, end)| (start..=end).contains(&file.sort_key()))
+    }
+
+    /// Rides, distance, elevation gain and moving time of the counted
+    /// activities in the range, e.g. `4 rides 180.2 km ↑ 2100 m 7:12:00`.
+    fn range_subtotal(&self) -> Option<String> {
+        self.range()?;
+        let files: Vec<&FileItem> = self
+            .counted_files()
+            .filter(|file| self.in_range(file))
+            .collect();
+        let distance: f64 = files.iter().map(|file| file.activity.distance_km).sum();
+        let elevation: f64 = files.iter().map(|file| file.activity.elevation_gain).sum();
+        let moving: f64 = files
+            .iter()
+            .filter_map(|file| file.activity.moving_seconds())
+            .sum();
+
+        let lang = self.lang;
+        Some(lang.fill(
+            "{rides} {distance} km ↑ {elevation}m moving {moving}",
+            &[
+                ("rides", &lang.count(files.len(), "ride", "rides")),
+                ("distance", &lang.number(distance, 1)),
+                ("elevation", &lang.number(elevation, 0)),
+                ("moving", &format_duration(moving)),
+            ],
+        ))
+    }
+
+    /// Grade-adjusted distance of the shown activities.
+    fn flat_km(&self) -> f64 {
+        self.counted_files().map(|file| file.flat_km).sum()
+    }
+
+    /// Distance, elevation and speed of a ride relative to the means of the
+    /// loaded rides, e.g. `distance +12% elevation -5%`.
+    fn comparison(&self, activity: &Activity) -> Option<String> {
+        let lang = self.lang;
+        let mut parts = vec![];
+        if let Some(mean) = self.summary.mean_distance_km() {
+            let change = format_relative(activity.distance_km, mean, lang);
+            parts.push(lang.fill("distance {change}", &[("change", &change)]));
+        }
+        if let Some(mean) = self.summary.mean_elevation_gain() {
+            let change = format_relative(activity.elevation_gain, mean, lang);
+            parts.push(lang.fill("elevation {change}", &[("change", &change)]));
+        }
+        let speed = activity.average_speed();
+        let mean_speed = self.summary.mean_speed_for_distance(activity.distance_km);
+        if let (Some(speed), Some(mean)) = (speed, mean_speed) {
+            let change = format_relative(speed, mean, lang);
+            parts.push(lang.fill("speed {change} (similar length)", &[("change", &change)]));
+        }
+
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+
+    fn selected(&self) -> Option<&FileItem> {
+        self.file_list
+            .selected_index()
+            .map(|i| &self.file_list.files[i])
+    }
+}
+
+impl FileList {
+    /// Bring the selection back into the rows, and the offset back to it at
+    /// most: rendering then scrolls down to show it, at any height.
+    fn keep_selection_in_view(&mut self) {
+        let last = self.rows.len().checked_sub(1);
+        let selected = self.state.selected().zip(last).map(|(i, last)| i.min(last));
+        let offset = self.state.offset().min(selected.unwrap_or_default());
+        self.state.select(selected);
+        *self.state.offset_mut() = offset;
+    }
+
+    /// Insert a file at its place in the start date and id order.
+    fn insert(&mut self, file: FileItem) {
+        let index = self
+            .files
+            .partition_point(|other| other.sort_key() < file.sort_key());
+        self.files.insert(index, file);
+    }
+
+    /// Index in `files` of the selected activity, the first ride of a
+    /// selected day.
+    fn selected_index(&self) -> Option<usize> {
+        self.selected_files().first().copied()
+    }
+
+    /// Indices in `files` of the selected activity, or of the rides of the
+    /// selected day.
+    fn selected_files(&self) -> Vec<usize> {
+        match self.state.selected().and_then(|i| self.rows.get(i)) {
+            Some(Row::File { index, .. }) => vec![*index],
+            Some(Row::Day { files, .. }) => files.clone(),
+            Some(Row::Month { .. }) | None => vec![],
+        }
+    }
+
+    /// First day of the month whose header is selected.
+    fn selected_month(&self) -> Option<NaiveDate> {
+        match self.state.selected().and_then(|i| self.rows.get(i)) {
+            Some(Row::Month { month, .. }) => Some(*month),
+            _ => None,
+        }
+    }
+
+    /// Select the row of an activity, or of its day when the rides of the
+    /// day are folded; false when the filter hides it.
+    fn select(&mut self, id: ActivityId) -> bool {
+        let files = &self.files;
+        let position = self.rows.iter().position(|row| match row {
+            Row::File { index, .. } => files[*index].activity.id == id,
+            Row::Day {
+                files: day,
+                expanded,
+                ..
+            } => !expanded && day.iter().any(|&i| files[i].activity.id == id),
+            Row::Month { .. } => false,
+        });
+        self.state.select(position.or(self.state.selected()));
+        position.is_some()
+    }
+
+    /// List the visible files, combining the rides of each day with more
+    /// than one when `combine` is set; the rides of the `expanded` days
+    /// follow their entry. With `months`, the dated rides of each month
+    /// follow its header.
+    fn update_rows(&mut self, combine: bool, months: bool, expanded: &BTreeSet<NaiveDate>) {
+        let date = |i: usize| self.files[i].activity.start.map(|start| start.date_naive());
+        self.rows.clear();
+        let mut rest = self.visible.as_slice();
+        while let Some(&first) = rest.first() {
+            let day = date(first).filter(|_| combine);
+            let count = rest.iter().take_while(|&&i| date(i) == day).count();
+            let (files, after) = rest.split_at(count.max(1));
+            rest = after;
+            match day {
+                Some(date) if files.len() > 1 => {
+                    let expanded = expanded.contains(&date);
+                    self.rows.push(Row::Day {
+                        date,
+                        files: files.to_vec(),
+                        expanded,
+                    });
+                    if expanded {
+                        self.rows.extend(files.iter().map(|&index| Row::File {
+                            index,
+                            nested: true,
+                        }));
+                    }
+                }
+                _ => self.rows.extend(files.iter().map(|&index| Row::File {
+                    index,
+                    nested: false,
+                })),
+            }
+        }
+        if !months {
+            return;
+        }
+        let month = |i: usize| date(i).map(|date| Period::Month.start(date));
+        let mut current = None;
+        for row in std::mem::take(&mut self.rows) {
+            let first = match &row {
+                Row::File { index, .. } => month(*index),
+                Row::Day { date, .. } => Some(Period::Month.start(*date)),
+                Row::Month { .. } => None,
+            };
+            if let Some(first) = first
+                && current != Some(first)
+            {
+                current = Some(first);
+                self.rows.push(Row::Month {
+                    month: first,
+                    files: self
+                        .visible
+                        .iter()
+                        .copied()
+                        .filter(|&i| month(i) == Some(first))
+                        .collect(),
+                });
+            }
+            self.rows.push(row);
+        }
+    }
+}
+
+impl Widget for &mut App {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        self.render_screen(area, buf);
+        // What has no symbol of its own, like borders and charts.
+        if self.ascii {
+            for cell in buf.content.iter_mut() {
+                if let Some(c) = cell.symbol().chars().next()
+                    && !c.is_ascii()
+                {
+                    cell.set_char(ascii_cell(c));
+                }
+            }
+        }
+    }
+}
+
+impl App {
+    fn render_screen(&mut self, area: Rect, buf: &mut Buffer) {
+        if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+            Paragraph::new(self.lang.fill(
+                "terminal too small (need {size})",
+                &[("size", &format!("{MIN_WIDTH}×{MIN_HEIGHT}"))],
+            ))
+            .centered()
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+            return;
+        }
+
+        let footer_height = if self.range().is_some() { 2 } else { 1 };
+        let [main_area, footer_area, status_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(footer_height),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        let [list_area, detail_area] = split_panes(main_area, self.split);
+
+        self.render_list(list_area, buf);
+        self.render_detail(detail_area, buf);
+        self.render_footer(footer_area, buf);
+        self.render_status(status_area, buf);
+
+        if self.show_stats {
+            self.render_stats(main_area, buf);
+        }
+        if let Some(day) = self.calendar {
+            self.render_calendar(main_area, buf, day);
+        }
+        if self.show_routes {
+            self.render_routes(main_area, buf);
+        }
+        if self.show_climbs {
+            self.render_climbs(main_area, buf);
+        }
+        if let Some(view) = &self.tour_view {
+            self.render_tour(main_area, buf, view);
+        }
+        if let Some(row) = self.quality_view {
+            self.render_quality(main_area, buf, row);
+        }
+        if let Some(row) = self.hidden_view {
+            self.render_hidden(main_area, buf, row);
+        }
+        if let Some(row) = self.settings {
+            self.render_settings(main_area, buf, row);
+        }
+        if self.show_help {
+            self.render_help(main_area, buf);
+        }
+        if let Some(palette) = &self.palette {
+            self.render_palette(main_area, buf, palette);
+        }
+
+        if !self.color {
+            // Modifiers such as bold are kept, they degrade gracefully.
+            for cell in &mut buf.content {
+                cell.set_fg(Color::Reset).set_bg(Color::Reset);
+            }
+        }
+    }
+}
+
+/// Every action of the interface: the key dispatcher, the command palette
+/// and the help all read it.
+const COMMANDS: &[UserCommand<App>] = &[
+    UserCommand {
+        name: "command palette",
+        description: "Search the commands and run one",
+        keys: &[Key::char(':'), Key::control('p')],
+        run: |app| app.palette = Some(Palette::default()),
+    },
+    UserCommand {
+        name: "help",
+        description: "Show or hide the keys",
+        keys: &[Key::char('?')],
+        run: |app| app.show_help = !app.show_help,
+    },
+    UserCommand {
+        name: "quit",
+        description: "Save the changes and exit",
+        keys: &[Key::char('q')],
+        run: App::exit,
+    },
+    UserCommand {
+        name: "next",
+        description: "Select the next ride, or scroll the detail down",
+        keys: &[Key::char('j'), Key::new(KeyCode::Down)],
+        run: App::move_down,
+    },
+    UserCommand {
+        name: "previous",
+        description: "Select the previous ride, or scroll the detail up",
+        keys: &[Key::char('k'), Key::new(KeyCode::Up)],
+        run: App::move_up,
+    },
+    UserCommand {
+        name: "switch pane",
+        description: "Move the focus between the list and the detail",
+        keys: &[Key::new(KeyCode::Tab)],
+        run: App::toggle_focus,
+    },
+    UserCommand {
+        name: "focus list",
+        description: "Move the focus to the list",
+        keys: &[Key::new(KeyCode::Left)],
+        run: |app| app.focus = Focus::List,
+    },
+    UserCommand {
+        name: "focus detail",
+        description: "Move the focus to the detail",
+        keys: &[Key::new(KeyCode::Right)],
+        run: |app| app.focus = Focus::Detail,
+    },
+    UserCommand {
+        name: "combine days",
+        description: "List the rides of each day as one entry, or each on its own line",
+        keys: &[Key::char('d')],
+        run: App::toggle_combine_days,
+    },
+    UserCommand {
+        name: "group months",
+        description: "List the rides of each month under a header, or without",
+        keys: &[Key::char('M')],
+        run: App::toggle_group_months,
+    },
+    UserCommand {
+        name: "expand",
+        description: "Unfold the selected day or detail section, or fold it back",
+        keys: &[Key::new(KeyCode::Enter)],
+        run: App::expand,
+    },
+    UserCommand {
+        name: "narrow list",
+        description: "Make the list narrower",
+        keys: &[Key::char('<')],
+        run: |app| app.split = app.split.saturating_sub(SPLIT_STEP).max(SPLIT_STEP),
+    },
+    UserCommand {
+        name: "widen list",
+        description: "Make the list wider",
+        keys: &[Key::char('>')],
+        run: |app| app.split = (app.split + SPLIT_STEP).min(100 - SPLIT_STEP),
+    },
+    UserCommand {
+        name: "statistics",
+        description: "Show or hide the statistics",
+        keys: &[Key::char('s')],
+        run: |app| app.show_stats = !app.show_stats,
+    },
+    UserCommand {
+        name: "same route",
+        description: "List the rides on the route of the selected one",
+        keys: &[Key::char('m')],
+        run: |app| app.show_routes = !app.show_routes,
+    },
+    UserCommand {
+        name: "climbs",
+        description: "Show the leaderboards of the climbs of the selected ride",
+        keys: &[Key::char('l')],
+        run: |app| app.show_climbs = !app.show_climbs,
+    },
+    UserCommand {
+        name: "tour",
+        description: "Add the marked or selected rides to a tour, or take them out",
+        keys: &[Key::char('T')],
+        run: App::open_tour,
+    },
+    UserCommand {
+        name: "tour stages",
+        description: "Show the stages of the tour of the selected ride",
+        keys: &[Key::char('o')],
+        run: App::open_tour_view,
+    },
+    UserCommand {
+        name: "export comparison",
+        description: "Write the selected ride and the one it is compared with as CSV",
+        keys: &[Key::char('x')],
+        run: App::open_comparison_export,
+    },
+    UserCommand {
+        name: "data quality",
+        description: "List the rides by the quality of their data, worst first",
+        keys: &[Key::char('Q')],
+        run: |app| app.quality_view = Some(0),
+    },
+    UserCommand {
+        name: "hide",
+        description: "Hide the marked or selected rides from the list and the totals",
+        keys: &[Key::char('h')],
+        run: App::hide,
+    },
+    UserCommand {
+        name: "hidden rides",
+        description: "List what the list leaves out and why, to show it again",
+        keys: &[Key::char('H')],
+        run: |app| app.hidden_view = Some(0),
+    },
+    UserCommand {
+        name: "climb rate",
+        description: "Chart the rate of climb of the selected ride instead of its elevation",
+        keys: &[Key::char('e')],
+        run: |app| app.show_climb_rate = !app.show_climb_rate,
+    },
+    UserCommand {
+        name: "distance models",
+        description: "Compare the distance of the selected ride under each model",
+        keys: &[Key::char('D')],
+        run: App::toggle_distance_models,
+    },
+    UserCommand {
+        name: "scrub",
+        description: "Move a cursor along the selected ride with the arrows",
+        keys: &[Key::char('p')],
+        run: App::start_scrub,
+    },
+    UserCommand {
+        name: "count virtual rides",
+        description: "Count the virtual rides in the totals, or not",
+        keys: &[Key::char('v')],
+        run: |app| app.count_virtual = !app.count_virtual,
+    },
+    UserCommand {
+        name: "tuning",
+        description: "Adjust the parameters of the metrics",
+        keys: &[Key::char(',')],
+        run: |app| app.settings = Some(0),
+    },
+    UserCommand {
+        name: "rename ride",
+        description: "Rename the selected ride",
+        keys: &[Key::char('r')],
+        run: App::open_rename,
+    },
+    UserCommand {
+        name: "tag",
+        description: "Add or remove a tag of the selected or marked rides",
+        keys: &[Key::char('t')],
+        run: App::open_tag,
+    },
+    UserCommand {
+        name: "commute",
+        description: "Toggle the commute tag of the selected or marked rides",
+        keys: &[Key::char('c')],
+        run: App::toggle_commute,
+    },
+    UserCommand {
+        name: "share",
+        description: "Copy the share text of the selected ride",
+        keys: &[Key::char('y')],
+        run: App::copy_share,
+    },
+    UserCommand {
+        name: "archive rides",
+        description: "Archive the selected or marked rides",
+        keys: &[Key::char('a')],
+        run: App::archive,
+    },
+    UserCommand {
+        name: "mark",
+        description: "Mark the selected ride for the next commute toggle or archive",
+        keys: &[Key::char(' ')],
+        run: App::toggle_mark,
+    },
+    UserCommand {
+        name: "filter",
+        description: "Filter the list by name or tag",
+        keys: &[Key::char('/')],
+        run: App::open_filter,
+    },
+    UserCommand {
+        name: "undo",
+        description: "Undo the last change",
+        keys: &[Key::char('u')],
+        run: App::undo,
+    },
+    UserCommand {
+        name: "redo",
+        description: "Redo the last undone change",
+        keys: &[Key::control('y')],
+        run: App::redo,
+    },
+    UserCommand {
+        name: "reload ride",
+        description: "Read the selected ride again from its file",
+        keys: &[Key::char('R')],
+        run: App::reload_selected,
+    },
+    UserCommand {
+        name: "reload all",
+        description: "Read every file again, with the ones added since",
+        keys: &[Key::control('r')],
+        run: App::reload_all,
+    },
+    UserCommand {
+        name: "range start",
+        description: "Start a range at the selected ride, for a subtotal",
+        keys: &[Key::char('[')],
+        run: App::set_range_start,
+    },
+    UserCommand {
+        name: "range end",
+        description: "End a range at the selected ride, for a subtotal",
+        keys: &[Key::char(']')],
+        run: App::set_range_end,
+    },
+    UserCommand {
+        name: "zoom in",
+        description: "Zoom the route map in on the middle of the route",
+        keys: &[Key::char('+'), Key::char('=')],
+        run: App::zoom_in,
+    },
+    UserCommand {
+        name: "zoom out",
+        description: "Zoom the route map out, back to the whole route",
+        keys: &[Key::char('-')],
+        run: App::zoom_out,
+    },
+    UserCommand {
+        name: "clear range",
+        description: "Remove the range",
+        keys: &[Key::new(KeyCode::Esc)],
+        run: App::clear_range,
+    },
+];
+
+/// Parts of the detail pane, in order; each can be folded to its title.
+const DETAIL_SECTIONS: &[Section<App, FileItem>] = &[
+    Section {
+        key: "summary",
+        title: "Summary",
+        body: |app, file| app.summary_section(file).map(Text::from),
+    },
+    Section {
+        key: "distance_models",
+        title: "Distance models",
+        body: |app, file| app.distance_models_section(file).map(Text::from),
+    },
+    Section {
+        key: "weather",
+        title: "Weather",
+        body: |app, file| app.weather_section(file).map(Text::from),
+    },
+    Section {
+        key: "wind",
+        title: "Wind",
+        body: |app, file| app.wind_section(file).map(Text::from),
+    },
+    Section {
+        key: "transport",
+        title: "Ferries and trains",
+        body: App::transport_section,
+    },
+    Section {
+        key: "plan",
+        title: "Plan",
+        body: |app, file| app.plan_section(file).map(Text::from),
+    },
+    Section {
+        key: "intervals",
+        title: "Intervals",
+        body: |app, file| app.intervals_section(file).map(Text::from),
+    },
+    Section {
+        key: "terrain",
+        title: "Terrain",
+        body: |app, file| app.terrain_section(file).map(Text::from),
+    },
+    Section {
+        key: "reference_climbs",
+        title: "Reference climbs",
+        body: |app, file| app.reference_climbs_section(file).map(Text::from),
+    },
+    Section {
+        key: "bests",
+        title: "Bests",
+        body: |app, file| app.bests_section(file).map(Text::from),
+    },
+    Section {
+        key: "quality",
+        title: "Data quality",
+        body: |app, file| app.quality_section(file).map(Text::from),
+    },
+    Section {
+        key: "notes",
+        title: "Notes",
+        body: |app, file| app.notes_section(file).map(Text::from),
+    },
+];
+
+/// The middle of `area`, for the overlays.
+fn popup_area(area: Rect) -> Rect {
+    let [_, area, _] = Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Percentage(80),
+        Constraint::Fill(1),
+    ])
+    .areas(area);
+    let [_, area, _] = Layout::vertical([
+        Constraint::Fill(1),
+        Constraint::Percentage(80),
+        Constraint::Fill(1),
+    ])
+    .areas(area);
+    area
+}
+
+/// Split the main area between the list and the detail panes, `split` being
+/// the list share in percent. Narrow terminals get a stacked layout.
+fn split_panes(area: Rect, split: u16) -> [Rect; 2] {
+    if area.width < 2 * MIN_PANE_WIDTH {
+        return Layout::vertical([Constraint::Fill(1), Constraint::Fill(1)]).areas(area);
+    }
+
+    let list_width = (u32::from(area.width) * u32::from(split) / 100) as u16;
+    let list_width = list_width.clamp(MIN_PANE_WIDTH, area.width - MIN_PANE_WIDTH);
+    Layout::horizontal([Constraint::Length(list_width), Constraint::Fill(1)]).areas(area)
+}
+
+impl App {
+    fn title(&self, title: impl Into<Cow<'static, str>>, pane: Focus) -> Line<'static> {
+        if self.focus == pane {
+            Line::styled(title, self.theme.focused_title).centered()
+        } else {
+            Line::raw(title).centered()
+        }
+    }
+
+    fn render_list(&mut self, area: Rect, buf: &mut Buffer) {
+        let title = if self.filter.is_active() {
+            self.lang.fill(
+                "Activities ({shown}/{total})",
+                &[
+                    ("shown", &self.file_list.visible.len()),
+                    ("total", &self.file_list.files.len()),
+                ],
+            )
+        } else {
+            self.lang.text("Activities").to_string()
+        };
+        let block = Block::new()
+            .title(self.title(title, Focus::List))
+            .borders(Borders::RIGHT)
+            .border_set(symbols::border::EMPTY);
+
+        // What the highlight symbol leaves of the pane.
+        let width = block.inner(area).width.saturating_sub(3);
+        let items: Vec<ListItem> = self
+            .file_list
+            .rows
+            .iter()
+            .map(|row| match row {
+                Row::File { index, nested } => {
+                    let file = &self.file_list.files[*index];
+                    let indent = if *nested { 3 } else { 0 };
+                    let mut text = file.list_text(
+                        self.marked.contains(&file.activity.id),
+                        self.in_range(file),
+                        width.saturating_sub(indent),
+                        self,
+                    );
+                    if *nested {
+                        text.lines[0].spans.insert(0, "   ".into());
+                    }
+                    ListItem::new(text)
+                }
+                Row::Day {
+                    files, expanded, ..
+                } => ListItem::new(self.day_line(files, *expanded)),
+                Row::Month { month, files } => ListItem::new(self.month_line(*month, files)),
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(self.theme.selected)
+            .highlight_symbol(">> ")
+            .highlight_spacing(HighlightSpacing::Always);
+
+        StatefulWidget::render(list, area, buf, &mut self.file_list.state);
+    }
+
+    /// The rides of a day as one line: names, summed distance, elevation
+    /// gain and moving time, and the span from the first start to the last
+    /// end.
+    fn day_line(&self, indices: &[usize], expanded: bool) -> Line<'static> {
+        let lang = self.lang;
+        let symbols = self.symbols();
+        let mut files: Vec<&FileItem> = indices.iter().map(|&i| &self.file_list.files[i]).collect();
+        files.sort_by_key(|file| file.activity.start);
+        let first = files[0].activity.start.expect("days group dated rides");
+        let names: Vec<&str> = files
+            .iter()
+            .map(|file| file.activity.name.as_str())
+            .collect();
+        let distance: f64 = files.iter().map(|file| file.activity.distance_km).sum();
+        let elevation: f64 = files.iter().map(|file| file.activity.elevation_gain).sum();
+        let moving: f64 = files
+            .iter()
+            .filter_map(|file| file.activity.moving_seconds())
+            .sum();
+        let last = files
+            .iter()
+            .filter_map(|file| file.activity.end.or(file.activity.start))
+            .max()
+            .unwrap_or(first);
+        let mut line = Line::styled(
+            format!("{} {}", first.format(self.date_format()), names.join(" + ")),
+            self.row_style(Some(first)),
+        );
+        line.spans.insert(
+            0,
+            format!(
+                "{} ",
+                if expanded {
+                    symbols.unfolded
+                } else {
+                    symbols.folded
+                }
+            )
+            .into(),
+        );
+        if files
+            .iter()
+            .all(|file| self.marked.contains(&file.activity.id))
+        {
+            line.spans.insert(0, "* ".set_style(self.theme.marked));
+        }
+        if files.iter().any(|file| self.in_range(file)) {
+            line.spans.insert(
+                0,
+                format!("{} ", symbols.range).set_style(self.theme.accent),
+            );
+        }
+        line.push_span(
+            lang.fill(
+                " ({rides}, {distance}, {elevation} m, {moving}, {start}–{end})",
+                &[
+                    ("rides", &lang.count(files.len(), "ride", "rides")),
+                    ("distance", &format_distance(distance, lang)),
+                    ("elevation", &lang.number(elevation, 0)),
+                    ("moving", &format_duration(moving)),
+                    ("start", &first.format("%H:%M")),
+                    ("end", &last.format("%H:%M")),
+                ],
+            )
+            .set_style(self.theme.muted),
+        );
+        line
+    }
+
+    /// The header of a month: its name, then its rides and distance.
+    fn month_line(&self, month: NaiveDate, indices: &[usize]) -> Line<'static> {
+        let lang = self.lang;
+        let distance: f64 = indices
+            .iter()
+            .map(|&i| self.file_list.files[i].activity.distance_km)
+            .sum();
+        Line::from(vec![
+            self.month_name(month).set_style(self.theme.heading),
+            lang.fill(
+                " ({rides}, {distance})",
+                &[
+                    ("rides", &lang.count(indices.len(), "ride", "rides")),
+                    ("distance", &format_distance(distance, lang).trim_start()),
+                ],
+            )
+            .set_style(self.theme.muted),
+        ])
+    }
+
+    fn month_name(&self, month: NaiveDate) -> String {
+        format!(
+            "{} {}",
+            self.lang.text(MONTHS[month.month0() as usize]),
+            month.year()
+        )
+    }
+
+    /// The statistics of a month, each against the same month a year before
+    /// when it had rides.
+    fn month_lines(&self, month: NaiveDate) -> Vec<Line<'static>> {
+        let lang = self.lang;
+        let symbols = self.symbols();
+        let months = self.summary.totals_by_period(Period::Month);
+        let totals = months.get(&month).cloned().unwrap_or_default();
+        let year_before = month.checked_sub_months(Months::new(12));
+        let before =
+            year_before.and_then(|first| Some((self.month_name(first), months.get(&first)?)));
+
+        let distance = |km: f64| format_distance(km, lang).trim_start().to_string();
+        let elevation = |m: f64| format!("{} m", lang.number(m, 0));
+        // The value of a year before, after how much the month differs.
+        let change = |value: f64, before: f64, month: &str, text: String| {
+            let text = if before > 0.0 {
+                let change = (value / before - 1.0) * 100.0;
+                lang.fill(
+                    " {arrow} {percent}% on {month} ({value})",
+                    &[
+                        (
+                            "arrow",
+                            &if change >= 0.0 {
+                                symbols.up
+                            } else {
+                                symbols.down
+                            },
+                        ),
+                        ("percent", &lang.number(change.abs(), 0)),
+                        ("month", &month),
+                        ("value", &text),
+                    ],
+                )
+            } else {
+                lang.fill(
+                    " ({month}: {value})",
+                    &[("month", &month), ("value", &text)],
+                )
+            };
+            text.set_style(self.theme.muted)
+        };
+
+        let mut lines = vec![
+            Line::from(self.month_name(month).set_style(self.theme.heading)),
+            Line::default(),
+        ];
+        let figure = |label: &'static str,
+                      metric: fn(&PeriodTotals) -> f64,
+                      text: &dyn Fn(f64) -> String| {
+            let value = metric(&totals);
+            let mut spans = vec![
+                lang.text(label).into(),
+                text(value).set_style(self.theme.value),
+            ];
+            if let Some((name, before)) = &before {
+                spans.push(change(value, metric(before), name, text(metric(before))));
+            }
+            Line::from(spans)
+        };
+        lines.extend([
+            figure("Rides: ", |totals| totals.totals.rides as f64, &|rides| {
+                rides.to_string()
+            }),
+            figure("Distance: ", |totals| totals.totals.distance_km, &distance),
+            figure(
+                "Elevation gain: ",
+                |totals| totals.totals.elevation_gain,
+                &elevation,
+            ),
+            figure(
+                "Moving time: ",
+                |totals| totals.moving_seconds,
+                &format_duration,
+            ),
+        ]);
+        if let Some((name, km)) = &totals.longest {
+            let mut spans = vec![
+                lang.text("Longest ride: ").into(),
+                format!("{name}, {}", distance(*km)).set_style(self.theme.value),
+            ];
+            if let Some((month, before)) = &before
+                && let Some((_, before)) = &before.longest
+            {
+                spans.push(change(*km, *before, month, distance(*before)));
+            }
+            lines.push(Line::from(spans));
+        }
+        if before.is_none()
+            && let Some(first) = year_before
+        {
+            lines.push(Line::default());
+            lines.push(Line::from(
+                lang.fill(
+                    "No rides in {month} to compare with.",
+                    &[("month", &self.month_name(first))],
+                )
+                .set_style(self.theme.muted),
+            ));
+        }
+        lines
+    }
+
+    fn render_footer(&mut self, area: Rect, buf: &mut Buffer) {
+        let (total_km, total_elevation) = self.totals(true);
+        let (sport_km, _) = self.totals(false);
+        let lang = self.lang;
+        let mut spans = vec![
+            lang.text("Grand Total: ").into(),
+            format_distance(total_km, lang).set_style(self.theme.value),
+        ];
+        if let Some(sparkline) = self.weekly_sparkline() {
+            spans.push(" ".into());
+            spans.push(sparkline.set_style(self.theme.value));
+        }
+        spans.extend([
+            format!(" {} ", self.symbols().up).into(),
+            format!("{}m", lang.number(total_elevation, 0)).set_style(self.theme.value),
+            lang.text(" Flat eq.: ").into(),
+            format!("{}km", lang.number(self.flat_km(), 0)).set_style(self.theme.value),
+            lang.text(" Sport: ").into(),
+            format_distance(sport_km, lang).set_style(self.theme.value),
+        ]);
+        if self.count_virtual {
+            spans.push(lang.text(" (virtual included)").set_style(self.theme.muted));
+        } else if self.visible_files().any(|file| file.activity.virtual_ride) {
+            spans.push(
+                lang.text(" (virtual excluded, v to count)")
+                    .set_style(self.theme.muted),
+            );
+        }
+        if !self.tuning.is_default() {
+            let mut changed: Vec<String> = self
+                .tuning
+                .changed()
+                .map(|parameter| {
+                    format!(
+                        "{} {}",
+                        lang.text(parameter.label()),
+                        format_parameter(parameter, &self.tuning, lang)
+                    )
+                })
+                .collect();
+            if self.tuning.segment_joins != SegmentJoins::default() {
+                changed.push(lang.fill(
+                    "segment joins {policy}",
+                    &[("policy", &lang.text(self.tuning.segment_joins.name()))],
+                ));
+            }
+            spans.push(
+                lang.fill(
+                    " (tuned: {parameters})",
+                    &[("parameters", &changed.join(", "))],
+                )
+                .set_style(self.theme.notice),
+            );
+        }
+        if self.filter.latest.is_some() {
+            spans.push(
+                lang.fill(
+                    " (showing latest {shown} of {total})",
+                    &[
+                        ("shown", &self.file_list.visible.len()),
+                        ("total", &self.file_list.matching),
+                    ],
+                )
+                .set_style(self.theme.muted),
+            );
+        }
+        if !self.duplicates.is_empty() {
+            spans.push(
+                lang.fill(
+                    " ({count} duplicates skipped)",
+                    &[("count", &self.duplicates.len())],
+                )
+                .set_style(self.theme.muted),
+            );
+        }
+        if !self.duplicate_paths.is_empty() {
+            spans.push(
+                lang.fill(
+                    " ({count} duplicate paths skipped)",
+                    &[("count", &self.duplicate_paths.len())],
+                )
+                .set_style(self.theme.muted),
+            );
+        }
+        if !self.failures.is_empty() {
+            spans.push(
+                lang.fill(" ({count} failed)", &[("count", &self.failures.len())])
+                    .set_style(self.theme.bad),
+            );
+        }
+        if let Some(loading) = &self.loading {
+            spans.push(
+                lang.fill(
+                    " Loaded {received}/{total}…",
+                    &[("received", &loading.received), ("total", &loading.total)],
+                )
+                .set_style(self.theme.muted),
+            );
+        }
+        let mut lines = vec![Line::from(spans)];
+        if let Some(subtotal) = self.range_subtotal() {
+            lines.push(Line::from(vec![
+                lang.text("Range: ").set_style(self.theme.accent),
+                subtotal.into(),
+            ]));
+        }
+        let grand_total = Text::from(lines);
+        Paragraph::new(grand_total).centered().render(area, buf);
+    }
+
+    fn render_status(&self, area: Rect, buf: &mut Buffer) {
+        let line = match (&self.prompt, &self.status) {
+            (Some(prompt), _) => {
+                let label = match prompt.kind {
+                    PromptKind::Rename => "Rename (empty for the track name): ",
+                    PromptKind::Tag => "Toggle tag: ",
+                    PromptKind::Filter => "Filter (Esc to clear): ",
+                    PromptKind::Tour => "Tour (empty to leave it): ",
+                    PromptKind::Comparison => "Write the comparison to: ",
+                };
+                Line::from(vec![
+                    self.lang.text(label).into(),
+                    format!("{}_", prompt.text).set_style(self.theme.value),
+                ])
+            }
+            (None, Some(status)) => Line::raw(status.clone()),
+            (None, None) => self
+                .lang
+                .text(
+                    "r rename  t tag  c commute  a archive  / filter  u undo  , tuning  : commands  ? help",
+                )
+                .set_style(self.theme.muted)
+                .into(),
+        };
+        Paragraph::new(line).centered().render(area, buf);
+    }
+
+    /// Rides, distance and elevation gain of the weekdays and of the
+    /// weekend, commutes apart when some are tagged; rounded so that the
+    /// parts add up to the totals.
+    fn week_split_lines(&self, split: &WeekSplit) -> Vec<Line<'static>> {
+        let lang = self.lang;
+        let mut lines = vec![Line::from(
+            lang.text("Weekdays and weekends")
+                .set_style(self.theme.heading),
+        )];
+        lines.extend(
+            split
+                .rounded(0)
+                .rows()
+                .into_iter()
+                .map(|(name, part, totals)| {
+                    let indent = if part { "  " } else { "" };
+                    Line::from(vec![
+                        format!(
+                            "{indent}{}",
+                            lang.fill("{name}: ", &[("name", &lang.text(name))])
+                        )
+                        .into(),
+                        lang.count(totals.rides, "ride", "rides")
+                            .set_style(self.theme.value),
+                        " / ".into(),
+                        format!("{} km", lang.number(totals.distance_km, 0))
+                            .set_style(self.theme.value),
+                        " / ".into(),
+                        format!(
+                            "{} {} m",
+                            self.symbols().up,
+                            lang.number(totals.elevation_gain, 0)
+                        )
+                        .set_style(self.theme.value),
+                    ])
+                }),
+        );
+        lines
+    }
+
+    fn render_stats(&self, area: Rect, buf: &mut Buffer) {
+        let area = popup_area(area);
+
+        let clusters = start_location_clusters(
+            self.visible_files().map(|file| &file.activity),
+            CLUSTER_RADIUS_M,
+        );
+
+        let lang = self.lang;
+        let km = |km: f64| format!("{} km", lang.number(km, 0));
+        let mut lines = vec![Line::from(
+            lang.text("Top starting locations")
+                .set_style(self.theme.heading),
+        )];
+        lines.extend(clusters.iter().take(STATS_TOP_LOCATIONS).map(|cluster| {
+            let (lat, lon) = cluster.center;
+            let label = match self.config.location_name(lat, lon) {
+                Some(name) => lang.fill("From {name}: ", &[("name", &name)]),
+                None => format!("{:>9}, {:>9}: ", lang.number(lat, 4), lang.number(lon, 4)),
+            };
+            Line::from(vec![
+                label.into(),
+                lang.count(cluster.rides, "ride", "rides")
+                    .set_style(self.theme.value),
+                " / ".into(),
+                km(cluster.distance_km).set_style(self.theme.value),
+            ])
+        }));
+
+        let devices = device_usage(self.visible_files().map(|file| &file.activity));
+        if devices.len() > 1 {
+            lines.push(Line::from(
+                lang.text("Devices").set_style(self.theme.heading),
+            ));
+            lines.extend(devices.iter().take(STATS_TOP_DEVICES).map(|usage| {
+                Line::from(vec![
+                    format!("{}: ", lang.text(&usage.device)).into(),
+                    lang.count(usage.rides, "ride", "rides")
+                        .set_style(self.theme.value),
+                    " / ".into(),
+                    km(usage.distance_km).set_style(self.theme.value),
+                ])
+            }));
+        }
+
+        #[cfg(feature = "borders")]
+        {
+            let countries =
+                country_totals(self.visible_files().map(|file| file.countries.as_slice()));
+            if !countries.is_empty() {
+                lines.push(Line::from(
+                    lang.text("Countries").set_style(self.theme.heading),
+                ));
+                lines.extend(countries.iter().map(|total| {
+                    Line::from(vec![
+                        format!("{} ({}): ", lang.text(&total.name), total.code).into(),
+                        lang.count(total.rides, "ride", "rides")
+                            .set_style(self.theme.value),
+                        " / ".into(),
+                        km(total.distance_km).set_style(self.theme.value),
+                    ])
+                }));
+            }
+        }
+
+        if let Some(weather) = &self.weather {
+            let (rain, dry, unknown) =
+                weather.rain_split(self.visible_files().map(|file| &file.activity));
+            lines.push(Line::from(vec![
+                lang.text("Rain: ").into(),
+                km(rain).set_style(self.theme.value),
+                lang.text(" Dry: ").into(),
+                km(dry).set_style(self.theme.value),
+                lang.text(" No weather data: ").into(),
+                km(unknown).set_style(self.theme.value),
+            ]));
+        }
+
+        if let (Some(median), Some(p25), Some(p75)) = (
+            self.summary.median_distance_km(),
+            self.summary.distance_percentile(0.25),
+            self.summary.distance_percentile(0.75),
+        ) {
+            lines.push(Line::from(vec![
+                lang.text("Ride length: median ").into(),
+                km(median).set_style(self.theme.value),
+                lang.text(", half of the rides between ").into(),
+                lang.fill(
+                    "{low} and {high} km",
+                    &[
+                        ("low", &lang.number(p25, 0)),
+                        ("high", &lang.number(p75, 0)),
+                    ],
+                )
+                .set_style(self.theme.value),
+            ]));
+        }
+
+        if let Some((ride, peak)) = self.summary.highest_point() {
+            let date = ride.date.map(|date| date.to_string()).unwrap_or_default();
+            lines.push(Line::from(vec![
+                lang.text("Altitude record: ").into(),
+                format!("{} m", lang.number(peak.elevation_m, 0)).set_style(self.theme.value),
+                lang.fill(
+                    ", {name} on {date}, at km {km}",
+                    &[
+                        ("name", &ride.name),
+                        ("date", &date),
+                        ("km", &lang.number(peak.distance_km, 1)),
+                    ],
+                )
+                .into(),
+            ]));
+        }
+
+        let mut seconds = vec![0.0; self.altitude_thresholds.len()];
+        for file in self.visible_files() {
+            for (total, seconds) in seconds.iter_mut().zip(&file.time_above) {
+                *total += seconds;
+            }
+        }
+        if let Some(above) = self.time_above_text(&seconds) {
+            lines.push(Line::from(vec![
+                lang.text("Time above: ").into(),
+                above.set_style(self.theme.value),
+            ]));
+        }
+
+        let split = week_split(
+            self.visible_files()
+                .filter(|file| !file.missing)
+                .map(|file| (&file.activity, file.is_commute())),
+            &self.weekend,
+        );
+        if split.total().rides > 0 {
+            lines.extend(self.week_split_lines(&split));
+        }
+
+        let today = Local::now().date_naive();
+        let week = self.summary.energy_by_period(Period::Week);
+        let month = self.summary.energy_by_period(Period::Month);
+        // Power-based and modelled rides added together are flagged.
+        let energy = |totals: EnergyTotals, mixed: bool| {
+            let mut spans = vec![
+                lang.fill(
+                    "{kj} kJ / {kcal} kcal",
+                    &[
+                        ("kj", &lang.number(totals.kilojoules, 0)),
+                        ("kcal", &lang.number(totals.kcal(), 0)),
+                    ],
+                )
+                .set_style(self.theme.value),
+            ];
+            if mixed {
+                spans.push(lang.text(" (mixed)").set_style(self.theme.muted));
+            }
+            spans
+        };
+        let period = |periods: &BTreeMap<NaiveDate, EnergyTotals>, period: Period| {
+            let totals = periods
+                .get(&period.start(today))
+                .copied()
+                .unwrap_or_default();
+            energy(totals, totals.mixed())
+        };
+        let trend = self.summary.energy_trend(today);
+        if trend.recent.kilojoules > 0.0 || trend.previous.kilojoules > 0.0 {
+            let mut spans = vec![lang.text("Energy this week: ").into()];
+            spans.extend(period(&week, Period::Week));
+            spans.push(lang.text(", this month: ").into());
+            spans.extend(period(&month, Period::Month));
+            lines.push(Line::from(spans));
+
+            let mut spans = vec![
+                lang.fill("Last {weeks} weeks: ", &[("weeks", &ENERGY_TREND_WEEKS)])
+                    .into(),
+            ];
+            spans.extend(energy(trend.recent, false));
+            if let Some(change) = trend.change_percent() {
+                let symbols = self.symbols();
+                let arrow = if change >= 0.0 {
+                    symbols.up
+                } else {
+                    symbols.down
+                };
+                spans.push(" ".into());
+                spans.push(
+                    lang.fill(
+                        "{arrow} {percent}% on the {weeks} before",
+                        &[
+                            ("arrow", &arrow),
+                            ("percent", &lang.number(change.abs(), 0)),
+                            ("weeks", &ENERGY_TREND_WEEKS),
+                        ],
+                    )
+                    .set_style(self.theme.value),
+                );
+            }
+            if trend.mixed() {
+                spans.push(lang.text(" (mixed)").set_style(self.theme.muted));
+            }
+            lines.push(Line::from(spans));
+        }
+
+        let years = new_km_by_year(
+            self.visible_files().map(|file| &file.activity),
+            &self.new_roads,
+        );
+        if !years.is_empty() {
+            let mut spans = vec![lang.text("New roads:").into()];
+            for (year, distance) in years {
+                spans.push(format!(" {year} ").into());
+                spans.push(km(distance).set_style(self.theme.value));
+            }
+            lines.push(Line::from(spans));
+        }
+
+        let years = exploration_by_year(
+            self.visible_files()
+                .map(|file| (&file.activity, &file.road_trace)),
+        );
+        if !years.is_empty() {
+            let mut spans = vec![lang.text("Exploration:").into()];
+            for year in years {
+                spans.push(format!(" {} ", year.year).into());
+                spans.push(
+                    format!("{} km²", lang.number(year.cells as f64, 0))
+                        .set_style(self.theme.value),
+                );
+                if let Some(change) = year.change_percent() {
+                    spans.push(
+                        lang.fill(
+                            " ({change}%)",
+                            &[("change", &lang.signed_number(change, 0))],
+                        )
+                        .set_style(self.theme.muted),
+                    );
+                }
+            }
+            lines.push(Line::from(spans));
+        }
+
+        let mut unpaved: BTreeMap<i32, SurfaceEstimate> = BTreeMap::new();
+        for file in self.visible_files() {
+            if let (Some(start), Some(surface)) = (file.activity.start, file.surface) {
+                let year = unpaved.entry(start.year()).or_default();
+                year.unpaved_km += surface.unpaved_km;
+                year.judged_km += surface.judged_km;
+            }
+        }
+        if !unpaved.is_empty() {
+            let mut spans = vec![lang.text("Unpaved (a guess):").into()];
+            for (year, surface) in unpaved {
+                spans.push(format!(" {year} ").into());
+                spans.push(km(surface.unpaved_km).set_style(self.theme.value));
+                spans.push(
+                    lang.fill(
+                        " ({percent}%)",
+                        &[("percent", &lang.number(surface.unpaved_percent(), 0))],
+                    )
+                    .set_style(self.theme.muted),
+                );
+            }
+            lines.push(Line::from(spans));
+        }
+
+        for bests in all_time_bests(self.visible_files().filter_map(|file| file.bests.as_ref())) {
+            let mut spans = vec![
+                lang.fill("Best {unit}:", &[("unit", &bests.metric.unit())])
+                    .into(),
+            ];
+            for (window, value) in BEST_WINDOWS_S.iter().zip(bests.values) {
+                if let Some(value) = value {
+                    spans.push(format!(" {} ", format_window(*window)).into());
+                    spans.push(lang.number(value, 0).set_style(self.theme.value));
+                }
+            }
+            lines.push(Line::from(spans));
+        }
+
+        let mut descending: Vec<(SortKey, DescendingSpeed)> = self
+            .visible_files()
+            .filter_map(|file| Some((file.sort_key(), file.descending?)))
+            .collect();
+        descending.sort_by_key(|&(key, _)| key);
+        let descending: Vec<DescendingSpeed> =
+            descending.into_iter().map(|(_, speed)| speed).collect();
+        if let (Some(recent), previous) = descending_trend(&descending) {
+            let mut spans = vec![
+                lang.fill(
+                    "Descending, last {rides}: ",
+                    &[(
+                        "rides",
+                        &lang.count(
+                            descending.len().min(DESCENDING_TREND_RIDES),
+                            "ride",
+                            "rides",
+                        ),
+                    )],
+                )
+                .into(),
+                format!("{} km/h", lang.number(recent.speed_kmh(), 1)).set_style(self.theme.value),
+            ];
+            if let Some(previous) = previous {
+                let change = (recent.speed_kmh() / previous.speed_kmh() - 1.0) * 100.0;
+                let symbols = self.symbols();
+                let arrow = if change >= 0.0 {
+                    symbols.up
+                } else {
+                    symbols.down
+                };
+                spans.push(" ".into());
+                spans.push(
+                    lang.fill(
+                        "{arrow} {percent}% on the rides before",
+                        &[
+                            ("arrow", &arrow),
+                            ("percent", &lang.number(change.abs(), 0)),
+                        ],
+                    )
+                    .set_style(self.theme.value),
+                );
+            }
+            lines.push(Line::from(spans));
+        }
+
+        let tours = self.tours();
+        if !tours.is_empty() {
+            lines.push(Line::from(lang.text("Tours").set_style(self.theme.heading)));
+        }
+        for tour in tours {
+            let totals = tour.totals();
+            lines.push(Line::from(vec![
+                format!("{}: ", tour.name).into(),
+                format!(
+                    "{}, {} km, {} m, {}",
+                    lang.count(tour.stages.len(), "stage", "stages"),
+                    lang.number(totals.distance_km, 1),
+                    lang.number(totals.elevation_gain, 0),
+                    format_duration(totals.moving_seconds),
+                )
+                .set_style(self.theme.value),
+            ]));
+        }
+
+        let block = Block::bordered()
+            .title(Line::raw(lang.text("Statistics")).centered())
+            .title_bottom(Line::raw(lang.text("c calendar")).centered())
+            .padding(Padding::horizontal(1));
+        let inner = block.inner(area);
+
+        Clear.render(area, buf);
+        block.render(area, buf);
+
+        let slots = self.start_slot_lines();
+        let [locations_area, lengths_area, slots_area, histogram_area] = Layout::vertical([
+            Constraint::Length(lines.len() as u16 + 1),
+            Constraint::Length(4),
+            Constraint::Length(slots.len() as u16 + 1),
+            Constraint::Fill(1),
+        ])
+        .areas(inner);
+        Paragraph::new(lines).render(locations_area, buf);
+        Paragraph::new(slots).render(slots_area, buf);
+
+        Sparkline::default()
+            .block(
+                Block::new().title(
+                    Line::raw(lang.fill(
+                        "Rides per {km} km of length",
+                        &[("km", &RIDE_LENGTH_BUCKET_KM)],
+                    ))
+                    .set_style(self.theme.heading),
+                ),
+            )
+            .data(self.summary.distance_histogram(RIDE_LENGTH_BUCKET_KM))
+            .style(self.theme.chart)
+            .render(lengths_area, buf);
+
+        let mut histogram = GradeHistogram::default();
+        for file in self.visible_files() {
+            histogram.add(&file.grades);
+        }
+        let [distance_area, ascent_area] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(histogram_area);
+        grade_bar_chart(
+            lang.text("Distance by grade (km)"),
+            &histogram.distance_km,
+            &self.theme,
+        )
+        .render(distance_area, buf);
+        grade_bar_chart(
+            lang.text("Ascent by grade (m)"),
+            &histogram.ascent_m,
+            &self.theme,
+        )
+        .render(ascent_area, buf);
+    }
+
+    /// The kilometers of each color of the calendar and the start times.
+    fn heat_legend(&self, scale: &HeatScale) -> Line<'static> {
+        let lang = self.lang;
+        let [low, middle, high] = scale.thresholds.map(|km| lang.number(km, 0));
+        let legend = [
+            "0".to_string(),
+            format!("≤ {low}"),
+            format!("≤ {middle}"),
+            format!("≤ {high}"),
+            format!("> {high} km"),
+        ];
+        let mut spans = vec![];
+        for (color, label) in self.theme.heat.iter().zip(legend) {
+            spans.push(Span::styled("■", Style::new().fg(*color)));
+            spans.push(format!(" {label}  ").into());
+        }
+        Line::from(spans)
+    }
+
+    /// Kilometers of the rides by weekday and start time, as a heat table.
+    fn start_slot_lines(&self) -> Vec<Line<'static>> {
+        let lang = self.lang;
+        let slots = self.summary.start_slots();
+        let scale = HeatScale::new(slots.iter().flatten().map(|slot| slot.distance_km));
+
+        let mut hours = "    ".to_string();
+        for slot in 0..START_SLOTS as u32 {
+            hours.push_str(&format!("{:<3}", slot * START_SLOT_HOURS));
+        }
+        let mut lines = vec![
+            Line::from(
+                lang.text("Rides by start time")
+                    .set_style(self.theme.heading),
+            ),
+            Line::from(hours).set_style(self.theme.muted),
+        ];
+        for (weekday, row) in WEEKDAYS.iter().zip(slots) {
+            let mut spans = vec![format!("{:<4}", lang.text(weekday)).into()];
+            for slot in row {
+                let color = self.theme.heat[scale.level(slot.distance_km)];
+                spans.push(Span::styled("  ", Style::new().bg(color)));
+                spans.push(" ".into());
+            }
+            lines.push(Line::from(spans));
+        }
+
+        lines.push(self.heat_legend(&scale));
+        lines
+    }
+
+    /// The last 12 months of the shown rides, two days per character, with
+    /// the totals of the day under the cursor.
+    fn render_calendar(&self, area: Rect, buf: &mut Buffer, cursor: NaiveDate) {
+        let area = popup_area(area);
+        let lang = self.lang;
+        let grid = CalendarGrid::last_year(Local::now().date_naive());
+        let days = self.summary.daily_totals();
+        let scale = HeatScale::new(
+            days.range(grid.first..=grid.last)
+                .map(|(_, day)| day.distance_km),
+        );
+        let color = |date: Option<NaiveDate>| match date {
+            Some(date) if date == cursor => self.theme.calendar_cursor,
+            Some(date) => {
+                let km = days.get(&date).map_or(0.0, |day| day.distance_km);
+                self.theme.heat[scale.level(km)]
+            }
+            None => Color::Reset,
+        };
+
+        let mut months = String::new();
+        for week in 0..grid.weeks() {
+            let first_of_month = (0..7)
+                .filter_map(|weekday| grid.date(week, weekday))
+                .find(|date| date.day() == 1);
+            if let Some(date) = first_of_month
+                && months.chars().count() <= week
+            {
+                let padding = week - months.chars().count();
+                months.push_str(&" ".repeat(padding));
+                months.push_str(lang.text(MONTHS[date.month0() as usize]));
+                months.push(' ');
+            }
+        }
+        let mut lines = vec![Line::from(months).set_style(self.theme.muted)];
+        // Each character shows a day in its upper half and the next one in
+        // its lower half, Sunday alone at the bottom.
+        for weekday in (0..7).step_by(2) {
+            lines.push(Line::from(
+                (0..grid.weeks())
+                    .map(|week| {
+                        let upper = color(grid.date(week, weekday));
+                        let lower = color(grid.date(week, weekday + 1));
+                        Span::styled("▀", Style::new().fg(upper).bg(lower))
+                    })
+                    .collect::<Vec<_>>(),
+            ));
+        }
+        lines.push(Line::default());
+
+        let date = cursor.format(self.date_format()).to_string();
+        lines.push(match days.get(&cursor) {
+            Some(day) => Line::from(vec![
+                format!("{date}: ").into(),
+                lang.count(day.rides, "ride", "rides")
+                    .set_style(self.theme.value),
+                " / ".into(),
+                format!("{} km", lang.number(day.distance_km, 1)).set_style(self.theme.value),
+                " / ".into(),
+                format!("{} m", lang.number(day.elevation_gain, 0)).set_style(self.theme.value),
+            ]),
+            None => Line::raw(lang.fill("{date}: no ride", &[("date", &date)])),
+        });
+
+        lines.push(self.heat_legend(&scale));
+
+        let block = Block::bordered()
+            .title(Line::raw(lang.text("Calendar")).centered())
+            .padding(Padding::horizontal(1));
+        Clear.render(area, buf);
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+
+    /// Find the rides on the route of the selected one, unless they are
+    /// already known for that ride and the files loaded.
+    fn update_route_matches(&mut self) {
+        let Some(file) = self.selected() else {
+            self.route_matches = None;
+            return;
+        };
+        let (id, files) = (file.activity.id, self.file_list.files.len());
+        if self
+            .route_matches
+            .as_ref()
+            .is_some_and(|matches| matches.id == id && matches.files == files)
+        {
+            return;
+        }
+
+        let rides = match &file.route {
+            Some(route) => matching_routes(
+                route,
+                self.file_list
+                    .files
+                    .iter()
+                    .filter_map(|file| Some((file.activity.id, file.route.as_ref()?))),
+            ),
+            None => vec![],
+        };
+        self.route_matches = Some(RouteMatches { id, files, rides });
+    }
+
+    /// The rides on the route of the selected one, with their similarity,
+    /// in the order of the list.
+    fn route_rides(&self) -> Vec<(&FileItem, f64)> {
+        let mut rides: Vec<(&FileItem, f64)> = self
+            .route_matches
+            .iter()
+            .flat_map(|matches| &matches.rides)
+            .filter_map(|&(id, similarity)| {
+                let file = self
+                    .file_list
+                    .files
+                    .iter()
+                    .find(|file| file.activity.id == id)?;
+                Some((file, similarity))
+            })
+            .collect();
+        rides.sort_by_key(|(file, _)| file.sort_key());
+        rides
+    }
+
+    /// The ride the selected one is compared against: the fastest other
+    /// ride of the route.
+    fn route_reference<'a>(&self, rides: &[(&'a FileItem, f64)]) -> Option<&'a FileItem> {
+        let selected = self.selected().map(|file| file.activity.id);
+        rides
+            .iter()
+            .filter(|(file, _)| Some(file.activity.id) != selected)
+            .filter_map(|&(file, _)| Some((file, file.activity.moving_seconds()?)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(file, _)| file)
+    }
+
+    fn render_routes(&mut self, area: Rect, buf: &mut Buffer) {
+        self.update_route_matches();
+        let area = popup_area(area);
+
+        let rides = self.route_rides();
+
+        let moving = |file: &FileItem| file.activity.moving_seconds();
+        let timed = rides.iter().filter_map(|&(file, _)| moving(file));
+        let fastest = timed.clone().min_by(f64::total_cmp);
+        let slowest = timed.max_by(f64::total_cmp);
+
+        let lang = self.lang;
+        let date_format = format!("{} %H:%M", self.date_format());
+        let selected = self.selected().map(|file| file.activity.id);
+        let lines: Vec<Line> = if rides.is_empty() {
+            vec![Line::from(lang.text("No track points to compare"))]
+        } else {
+            rides
+                .iter()
+                .map(|&(file, similarity)| {
+                    let start = file
+                        .activity
+                        .start
+                        .map_or(lang.text("undated").to_string(), |start| {
+                            start.format(&date_format).to_string()
+                        });
+                    let seconds = moving(file);
+                    let similar = format!("{:>3.0}", similarity * 100.0);
+                    let mut spans = vec![
+                        format!("{start:<16}").into(),
+                        format!("{:>9}", seconds.map_or("-".to_string(), format_duration))
+                            .set_style(self.theme.value),
+                        format!(
+                            "{:>7} km/h",
+                            file.activity
+                                .average_speed()
+                                .map_or("-".to_string(), |speed| lang.number(speed, 1))
+                        )
+                        .into(),
+                        format!(
+                            "  {}",
+                            lang.fill("{similarity}% similar", &[("similarity", &similar)])
+                        )
+                        .into(),
+                    ];
+                    if rides.len() > 1 && seconds == fastest {
+                        spans.push(lang.text("  fastest").set_style(self.theme.good));
+                    } else if rides.len() > 1 && seconds == slowest {
+                        spans.push(lang.text("  slowest").set_style(self.theme.bad));
+                    }
+                    if Some(file.activity.id) == selected {
+                        spans.push(lang.text("  ◀ this ride").set_style(self.theme.selected));
+                    }
+                    Line::from(spans)
+                })
+                .collect()
+        };
+
+        let title = match rides.len() {
+            0 => lang.text("Same route").to_string(),
+            1 => lang.text("Same route: ridden once").to_string(),
+            count => lang.fill("Same route: ridden {count} times", &[("count", &count)]),
+        };
+        let block = Block::bordered()
+            .title(Line::raw(title).centered())
+            .padding(Padding::horizontal(1));
+
+        let reference = self.route_reference(&rides);
+        let diff = self
+            .selected()
+            .zip(reference)
+            .and_then(|(file, reference)| {
+                let diff = route_diff(self.points(file)?, self.points(reference)?)?;
+                Some((diff, reference))
+            });
+
+        Clear.render(area, buf);
+        let Some((diff, reference)) = diff else {
+            Paragraph::new(lines).block(block).render(area, buf);
+            return;
+        };
+        let [list_area, diff_area] = Layout::vertical([
+            Constraint::Length(lines.len() as u16 + 2),
+            Constraint::Fill(1),
+        ])
+        .areas(area);
+        Paragraph::new(lines).block(block).render(list_area, buf);
+        let start = reference
+            .activity
+            .start
+            .map_or(lang.text("undated").to_string(), |start| {
+                start.format(&date_format).to_string()
+            });
+        self.render_route_diff(diff_area, buf, &diff, &start);
+    }
+
+    /// Time behind the reference ride along the route, and where the most
+    /// time was gained or lost.
+    fn render_route_diff(&self, area: Rect, buf: &mut Buffer, diff: &RouteDiff, reference: &str) {
+        let lang = self.lang;
+        let block = Block::bordered()
+            .title(
+                Line::raw(lang.fill("Time vs. the ride of {date}", &[("date", &reference)]))
+                    .centered(),
+            )
+            .padding(Padding::horizontal(1));
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let segments: Vec<Line> = diff
+            .segments
+            .iter()
+            .filter(|segment| segment.seconds.abs() >= 1.0)
+            .take(ROUTE_DIFF_TOP_SEGMENTS)
+            .map(|segment| {
+                let text = format_diff_segment(segment, lang);
+                if segment.seconds > 0.0 {
+                    text.set_style(self.theme.bad).into()
+                } else {
+                    text.set_style(self.theme.good).into()
+                }
+            })
+            .collect();
+        let [chart_area, segments_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(segments.len() as u16),
+        ])
+        .areas(inner);
+        Paragraph::new(segments).render(segments_area, buf);
+
+        let distance = diff.deltas.last().map_or(1.0, |&(km, _)| km);
+        let (low, high) = diff
+            .deltas
+            .iter()
+            .fold((0.0f64, 0.0f64), |(low, high), &(_, seconds)| {
+                (low.min(seconds), high.max(seconds))
+            });
+        let zero = [(0.0, 0.0), (distance, 0.0)];
+        Chart::new(vec![
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(ratatui::widgets::GraphType::Line)
+                .style(self.theme.reference)
+                .data(&zero),
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(ratatui::widgets::GraphType::Line)
+                .style(self.theme.value)
+                .data(&diff.deltas),
+        ])
+        .x_axis(
+            Axis::default()
+                .title(lang.text("Distance (km)"))
+                .labels(["0".to_string(), lang.number(distance, 1)])
+                .bounds([0.0, distance]),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([low - 1.0, high + 1.0])
+                .labels([format_signed_duration(low), format_signed_duration(high)]),
+        )
+        .render(chart_area, buf);
+    }
+
+    /// Leaderboard of each climb of the selected ride.
+    fn render_climbs(&self, area: Rect, buf: &mut Buffer) {
+        let area = popup_area(area);
+        let lang = self.lang;
+        let date_format = self.date_format();
+
+        let mut lines = vec![];
+        for (id, climb) in self
+            .selected()
+            .into_iter()
+            .flat_map(|file| file.climbs.iter().map(|climb| (file.activity.id, climb)))
+        {
+            let Some(group) = self
+                .climb_groups
+                .iter()
+                .find(|group| group.ascents.contains(&(id, *climb)))
+            else {
+                continue;
+            };
+            if !lines.is_empty() {
+                lines.push(Line::default());
+            }
+            let times = lang.count(group.ascents.len(), "time", "times");
+            lines.push(Line::from(vec![
+                format_climb(climb, lang).set_style(self.theme.heading),
+                format!("  {}", lang.fill("climbed {count}", &[("count", &times)]))
+                    .set_style(self.theme.muted),
+            ]));
+
+            let leaderboard = group.leaderboard();
+            for (rank, &(ride, ascent)) in leaderboard.iter().enumerate() {
+                if rank >= CLIMB_LEADERBOARD_TOP && *ride != id {
+                    continue;
+                }
+                let start = self
+                    .file_list
+                    .files
+                    .iter()
+                    .find(|file| file.activity.id == *ride)
+                    .and_then(|file| file.activity.start);
+                let seconds = ascent.seconds.unwrap_or_default();
+                let mut spans = vec![
+                    format!("{:>3}. ", rank + 1).into(),
+                    format!(
+                        "{:<12}",
+                        start.map_or(lang.text("undated").to_string(), |start| {
+                            start.format(date_format).to_string()
+                        })
+                    )
+                    .into(),
+                    format!("{:>9}", format_duration(seconds)).set_style(self.theme.value),
+                    format!(
+                        "{:>7} km/h",
+                        lang.number(ascent.length_m / seconds * 3.6, 1)
+                    )
+                    .into(),
+                ];
+                if rank == 0 && leaderboard.len() > 1 {
+                    spans.push(lang.text("  fastest").set_style(self.theme.good));
+                }
+                if *ride == id {
+                    spans.push(lang.text("  ◀ this ride").set_style(self.theme.selected));
+                }
+                lines.push(Line::from(spans));
+            }
+        }
+        if lines.is_empty() {
+            lines.push(Line::from(lang.text("No climb on this ride")));
+        }
+
+        let block = Block::bordered()
+            .title(Line::raw(lang.text("Climbs")).centered())
+            .padding(Padding::horizontal(1));
+
+        Clear.render(area, buf);
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+
+    fn render_tour(&self, area: Rect, buf: &mut Buffer, view: &TourView) {
+        let area = popup_area(area);
+        let lang = self.lang;
+        let date_format = self.date_format();
+        let Some(tour) = self.tour(&view.name) else {
+            return;
+        };
+
+        let mut lines = vec![
+            Line::from(lang.text("↑↓ select  Enter go to  d remove stage  Esc close"))
+                .style(self.theme.muted),
+        ];
+        lines.push(Line::default());
+        for (i, (stage, totals)) in tour.stages.iter().zip(tour.running_totals()).enumerate() {
+            let line = Line::from(vec![
+                format!("{:>3}. ", i + 1).into(),
+                format!(
+                    "{:<12}",
+                    stage
+                        .start
+                        .map_or(lang.text("undated").to_string(), |start| {
+                            start.format(date_format).to_string()
+                        })
+                )
+                .into(),
+                format!("{:<24} ", stage.name).into(),
+                format!(
+                    "{:>7} km {:>6} m {:>9}",
+                    lang.number(stage.totals.distance_km, 1),
+                    lang.number(stage.totals.elevation_gain, 0),
+                    format_duration(stage.totals.moving_seconds),
+                )
+                .set_style(self.theme.value),
+                format!(
+                    "  Σ {} km, {} m, {}",
+                    lang.number(totals.distance_km, 1),
+                    lang.number(totals.elevation_gain, 0),
+                    format_duration(totals.moving_seconds),
+                )
+                .set_style(self.theme.muted),
+            ]);
+            lines.push(if i == view.stage {
+                line.style(self.theme.menu_selected)
+            } else {
+                line
+            });
+        }
+
+        let block = Block::bordered()
+            .title(Line::raw(tour.name).centered())
+            .padding(Padding::horizontal(1));
+
+        Clear.render(area, buf);
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+
+    /// Rides by the score of their data, the worst first, with the problems
+    /// that take the most off.
+    fn render_quality(&self, area: Rect, buf: &mut Buffer, row: usize) {
+        let area = popup_area(area);
+        let lang = self.lang;
+        let date_format = self.date_format();
+        let ranking = self.quality_ranking();
+
+        let mut lines = vec![
+            Line::from(lang.text("↑↓ select  Enter data quality  Esc close"))
+                .style(self.theme.muted),
+            Line::default(),
+        ];
+        if ranking.is_empty() {
+            lines.push(Line::from(lang.text("No track points to score")));
+        }
+        // Keep the cursor in sight.
+        let shown = usize::from(area.height.saturating_sub(2)).saturating_sub(lines.len());
+        let row = row.min(ranking.len().saturating_sub(1));
+        let skip = (row + 1).saturating_sub(shown);
+        for (i, (file, quality)) in ranking.iter().enumerate().skip(skip).take(shown) {
+            let score = quality.score();
+            let top = quality.penalties.len().min(QUALITY_TOP_PROBLEMS);
+            let line = Line::from(vec![
+                quality_indicator(score, &self.theme, self.symbols()),
+                format!("{score:>3}  ").set_style(self.theme.value),
+                format!(
+                    "{:<12}",
+                    file.activity
+                        .start
+                        .map_or(lang.text("undated").to_string(), |start| {
+                            start.format(date_format).to_string()
+                        })
+                )
+                .into(),
+                format!("{:<24} ", file.name()).into(),
+                format_penalties(&quality.penalties[..top], lang).set_style(self.theme.muted),
+            ]);
+            lines.push(if i == row {
+                line.style(self.theme.menu_selected)
+            } else {
+                line
+            });
+        }
+
+        let title = if ranking.is_empty() {
+            lang.text("Data quality").to_string()
+        } else {
+            let average = ranking
+                .iter()
+                .map(|(_, quality)| f64::from(quality.score()))
+                .sum::<f64>()
+                / ranking.len() as f64;
+            lang.fill(
+                "Data quality: {count}, {average}/100 on average",
+                &[
+                    ("count", &lang.count(ranking.len(), "ride", "rides")),
+                    ("average", &lang.number(average, 0)),
+                ],
+            )
+        };
+        let block = Block::bordered()
+            .title(Line::raw(title).centered())
+            .padding(Padding::horizontal(1));
+
+        Clear.render(area, buf);
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+
+    fn render_hidden(&self, area: Rect, buf: &mut Buffer, row: usize) {
+        let area = popup_area(area);
+        let lang = self.lang;
+        let items = self.hidden_items();
+
+        let mut lines = vec![
+            Line::from(lang.text("↑↓ select  Enter show again  Esc close")).style(self.theme.muted),
+        ];
+        if items.is_empty() {
+            lines.push(Line::default());
+            lines.push(Line::from(lang.text("Nothing is hidden")));
+        }
+        let row = row.min(items.len().saturating_sub(1));
+        let mut selected = 0;
+        for (i, item) in items.iter().enumerate() {
+            if i == 0 || items[i - 1].exclusion != item.exclusion {
+                let count = items
+                    .iter()
+                    .filter(|other| other.exclusion == item.exclusion)
+                    .count();
+                lines.push(Line::default());
+                lines.push(Line::from(vec![
+                    lang.text(item.exclusion.title())
+                        .set_style(self.theme.accent),
+                    format!(" ({count})").set_style(self.theme.muted),
+                ]));
+            }
+            let line = Line::from(format!("  {}", item.label));
+            lines.push(if i == row {
+                selected = lines.len() - 1;
+                line.style(self.theme.menu_selected)
+            } else {
+                line
+            });
+        }
+        // Keep the cursor in sight.
+        let shown = usize::from(area.height.saturating_sub(2));
+        let skip = (selected + 1).saturating_sub(shown);
+
+        let block = Block::bordered()
+            .title(Line::raw(lang.fill("Hidden: {count}", &[("count", &items.len())])).centered())
+            .padding(Padding::horizontal(1));
+
+        Clear.render(area, buf);
+        Paragraph::new(lines.split_off(skip))
+            .block(block)
+            .render(area, buf);
+    }
+
+    /// Compute the metrics of the selected ride under the default tuning,
+    /// unless they are already known. They do not change with the tuning.
+    fn update_raw_metrics(&mut self) {
+        let Some(file) = self.selected() else {
+            self.raw_metrics = None;
+            return;
+        };
+        let id = file.activity.id;
+        if self.raw_metrics.is_some_and(|(raw, _)| raw == id) {
+            return;
+        }
+        // Large files keep no points, and released ones may be read again.
+        self.raw_metrics = self
+            .points(file)
+            .filter(|points| !points.is_empty())
+            .map(|points| (id, TunedMetrics::new(points, &Tuning::default())));
+    }
+
+    /// The selected ride as recorded next to its tuned metrics.
+    fn what_if_lines(&self) -> Vec<Line<'static>> {
+        let lang = self.lang;
+        let (Some(file), Some((_, raw))) = (self.selected(), self.raw_metrics) else {
+            return vec![];
+        };
+        let tuned = TunedMetrics {
+            distance_km: file.activity.distance_km,
+            elevation_gain: file.activity.elevation_gain,
+            moving_seconds: file.activity.moving_seconds(),
+            unpaved_km: file.surface.map(|surface| surface.unpaved_km),
+        };
+        let duration = |seconds: Option<f64>| seconds.map_or("–".to_string(), format_duration);
+        let speed = |metrics: &TunedMetrics| {
+            metrics.average_speed().map_or("–".to_string(), |speed| {
+                format!("{} km/h", lang.number(speed, 1))
+            })
+        };
+        let unpaved = |metrics: &TunedMetrics| {
+            metrics
+                .unpaved_km
+                .map_or("–".to_string(), |km| format!("{} km", lang.number(km, 1)))
+        };
+        let rows = [
+            (
+                "distance",
+                format!("{} km", lang.number(raw.distance_km, 2)),
+                format!("{} km", lang.number(tuned.distance_km, 2)),
+            ),
+            (
+                "elevation gain",
+                format!("{} m", lang.number(raw.elevation_gain, 0)),
+                format!("{} m", lang.number(tuned.elevation_gain, 0)),
+            ),
+            (
+                "moving time",
+                duration(raw.moving_seconds),
+                duration(tuned.moving_seconds),
+            ),
+            ("average speed", speed(&raw), speed(&tuned)),
+            ("unpaved (estimate)", unpaved(&raw), unpaved(&tuned)),
+        ];
+
+        let mut lines = vec![Line::from(vec![
+            format!("{:<24}", lang.text("What if, selected ride")).set_style(self.theme.heading),
+            format!("{:>12}", lang.text("as recorded")).set_style(self.theme.muted),
+            format!("{:>12}", lang.text("tuned")).set_style(self.theme.muted),
+        ])];
+        for (label, raw, tuned) in rows {
+            let style = if raw == tuned {
+                self.theme.value
+            } else {
+                self.theme.notice
+            };
+            lines.push(Line::from(vec![
+                format!("{:<24}", lang.text(label)).into(),
+                format!("{raw:>12}").set_style(self.theme.value),
+                format!("{tuned:>12}").set_style(style),
+            ]));
+        }
+        lines
+    }
+
+    fn render_settings(&mut self, area: Rect, buf: &mut Buffer, row: usize) {
+        self.update_raw_metrics();
+        let area = popup_area(area);
+        let lang = self.lang;
+        let default = Tuning::default();
+
+        let mut lines: Vec<Line> = TuningParameter::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, &parameter)| {
+                let mut spans = vec![
+                    format!("{:<24}", lang.text(parameter.label())).into(),
+                    format!("{:>10}", format_parameter(parameter, &self.tuning, lang))
+                        .set_style(self.theme.value),
+                ];
+                if parameter.value(&self.tuning) != parameter.value(&default) {
+                    spans.push(
+                        format!(
+                            "  {}",
+                            lang.fill(
+                                "default {value}",
+                                &[("value", &format_parameter(parameter, &default, lang))],
+                            )
+                        )
+                        .set_style(self.theme.muted),
+                    );
+                }
+                let line = Line::from(spans);
+                if i == row {
+                    line.style(self.theme.menu_selected)
+                } else {
+                    line
+                }
+            })
+            .collect();
+        let without_points = self
+            .file_list
+            .files
+            .iter()
+            .filter(|file| file.activity.points.is_empty() && !file.released)
+            .count();
+        let what_if = self.what_if_lines();
+        if !what_if.is_empty() {
+            lines.push(Line::default());
+            lines.extend(what_if);
+        }
+        if without_points > 0 {
+            lines.push(Line::default());
+            lines.push(
+                lang.fill(
+                    "{count} large files keep the metrics they were loaded with",
+                    &[("count", &without_points)],
+                )
+                .set_style(self.theme.muted)
+                .into(),
+            );
+        }
+        lines.push(Line::default());
+        lines.push(
+            lang.text("↑↓ select  +/- adjust  s save  Esc close")
+                .set_style(self.theme.muted)
+                .into(),
+        );
+
+        let block = Block::bordered()
+            .title(Line::raw(lang.text("Tuning")).centered())
+            .padding(Padding::horizontal(1));
+
+        Clear.render(area, buf);
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+
+    fn render_help(&self, area: Rect, buf: &mut Buffer) {
+        let area = popup_area(area);
+        let lang = self.lang;
+        let lines: Vec<Line> = COMMANDS
+            .iter()
+            .map(|command| command_line(command, lang, &self.theme))
+            .collect();
+
+        let block = Block::bordered()
+            .title(Line::raw(lang.text("Keys")).centered())
+            .padding(Padding::horizontal(1));
+        Clear.render(area, buf);
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+
+    fn render_palette(&self, area: Rect, buf: &mut Buffer, palette: &Palette) {
+        let area = popup_area(area);
+        let lang = self.lang;
+        let mut lines = vec![
+            Line::from(vec![
+                "> ".into(),
+                format!("{}_", palette.query).set_style(self.theme.value),
+            ]),
+            Line::default(),
+        ];
+        let matches = self.palette_matches(&palette.query);
+        if matches.is_empty() {
+            lines.push(
+                lang.text("No matching command")
+                    .set_style(self.theme.muted)
+                    .into(),
+            );
+        }
+        for (i, command) in matches.into_iter().enumerate() {
+            let line = command_line(command, lang, &self.theme);
+            lines.push(if i == palette.selected {
+                line.style(self.theme.menu_selected)
+            } else {
+                line
+            });
+        }
+
+        let block = Block::bordered()
+            .title(Line::raw(lang.text("Commands")).centered())
+            .padding(Padding::horizontal(1));
+        Clear.render(area, buf);
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+
+    fn render_detail(&mut self, area: Rect, buf: &mut Buffer) {
+        let [distance_area, charts_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Fill(1)]).areas(area);
+        let [elevation_area, map_area] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(charts_area);
+
+        self.render_information(distance_area, buf);
+        self.render_elevation_chart(elevation_area, buf);
+        self.render_map(map_area, buf);
+    }
+
+    /// Points of the route map: the track, or the route of a planned ride.
+    fn map_points<'a>(&'a self, file: &'a FileItem) -> Option<&'a [TrackPoint]> {
+        let points = self.points(file)?;
+        if points.is_empty() {
+            Some(&file.activity.route_points)
+                .filter(|points| !points.is_empty())
+                .map(Vec::as_slice)
+        } else {
+            Some(points)
+        }
+    }
+
+    /// Whether the route map is drawn as an image rather than in braille:
+    /// the terminal can show images and no popup covers the panes.
+    #[cfg(feature = "images")]
+    fn map_as_image(&self) -> bool {
+        self.images.is_some()
+            && !(self.show_stats
+                || self.calendar.is_some()
+                || self.show_routes
+                || self.show_climbs
+                || self.tour_view.is_some()
+                || self.quality_view.is_some()
+                || self.hidden_view.is_some()
+                || self.settings.is_some()
+                || self.show_help
+                || self.palette.is_some())
+    }
+
+    #[cfg(not(feature = "images"))]
+    fn map_as_image(&self) -> bool {
+        false
+    }
+
+    /// The route of the selected ride, in braille, or blank cells for the
+    /// image of [`App::place_map_image`].
+    fn render_map(&mut self, area: Rect, buf: &mut Buffer) {
+        let lang = self.lang;
+        let mut title = lang.text("Route").to_string();
+        if self.map_zoom > 0 {
+            title.push_str(&format!(" ×{}", 1u32 << self.map_zoom));
+        }
+        let block = Block::new()
+            .title(Line::raw(title).centered())
+            .title_bottom(
+                Line::raw(lang.text("+/- zoom"))
+                    .centered()
+                    .set_style(self.theme.muted),
+            )
+            .border_set(symbols::border::EMPTY)
+            .padding(Padding::horizontal(1));
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        self.map_area = None;
+        if inner.is_empty() {
+            return;
+        }
+        if self.map_as_image() {
+            self.map_area = Some(inner);
+            return;
+        }
+        let Some(points) = self.selected().and_then(|file| self.map_points(file)) else {
+            return;
+        };
+        // Braille dots are about square, 2 by 4 to a cell.
+        let (width, height) = (f64::from(inner.width) * 2.0, f64::from(inner.height) * 4.0);
+        let Some(view) = MapView::fit(points, width, height, self.map_zoom) else {
+            return;
+        };
+        let dots: Vec<(f64, f64)> = points
+            .iter()
+            .map(|point| {
+                let (x, y) = view.pixel(mercator(point.lat, point.lon));
+                (x, height - y)
+            })
+            .collect();
+        let start = dots[0];
+        Canvas::default()
+            .marker(Marker::Braille)
+            .x_bounds([0.0, width])
+            .y_bounds([0.0, height])
+            .paint(|ctx| {
+                let mut previous: Option<(f64, f64)> = None;
+                for (point, &dot) in points.iter().zip(&dots) {
+                    match previous {
+                        Some(from) if !point.segment_start => {
+                            // Points within a dot of the last one drawn add nothing.
+                            if (dot.0 - from.0).abs() < 1.0 && (dot.1 - from.1).abs() < 1.0 {
+                                continue;
+                            }
+                            ctx.draw(&CanvasLine::new(
+                                from.0,
+                                from.1,
+                                dot.0,
+                                dot.1,
+                                self.theme.map_track,
+                            ));
+                        }
+                        _ => {}
+                    }
+                    previous = Some(dot);
+                }
+                ctx.layer();
+                ctx.draw(&Points {
+                    coords: &[start],
+                    color: self.theme.map_start,
+                });
+            })
+            .render(inner, buf);
+    }
+
+    fn zoom_in(&mut self) {
+        self.map_zoom = (self.map_zoom + 1).min(MAX_MAP_ZOOM);
+    }
+
+    fn zoom_out(&mut self) {
+        self.map_zoom = self.map_zoom.saturating_sub(1);
+    }
+
+    /// The detail of the selected ride: the scrub cursor values, then each
+    /// section of [`DETAIL_SECTIONS`] under its title, scrolled as one text.
+    fn render_information(&mut self, area: Rect, buf: &mut Buffer) {
+        let lang = self.lang;
+        let block = Block::new()
+            .title(self.title(lang.text("Activity Detail").to_string(), Focus::Detail))
+            .border_set(symbols::border::EMPTY)
+            .padding(Padding::horizontal(1));
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let Some(file_info) = self.selected() else {
+            self.detail_layout = SectionLayout::default();
+            let text = match self.file_list.selected_month() {
+                Some(month) => Text::from(self.month_lines(month)),
+                None => Text::from(lang.text("No activity selected...")),
+            };
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .render(inner, buf);
+            return;
+        };
+
+        let mut preamble = vec![];
+        if let Some(sample) = self.scrub.and_then(|km| self.scrub_sample(km)) {
+            preamble.push(Line::from(format_sample(&sample, lang)));
+        }
+        if self.points(file_info).is_none() {
+            preamble.push(Line::from(lang.text("loading…").to_string()));
+        }
+        if !preamble.is_empty() {
+            preamble.push(Line::default());
+        }
+        let mut sections = vec![];
+        for section in DETAIL_SECTIONS {
+            let Some(body) = (section.body)(self, file_info) else {
+                continue;
+            };
+            let folded = self.folded.contains(section.key);
+            let marker = if folded {
+                self.symbols().folded
+            } else {
+                self.symbols().unfolded
+            };
+            let mut lines = vec![Line::from(format!("{marker} {}", lang.text(section.title)))];
+            if !folded {
+                lines.extend(body.lines);
+                lines.push(Line::default());
+            }
+            sections.push((section.key, lines));
+        }
+
+        let height = |lines: &[Line<'static>]| {
+            Paragraph::new(lines.to_vec())
+                .wrap(Wrap { trim: false })
+                .line_count(inner.width)
+        };
+        let layout = SectionLayout::new(
+            height(&preamble),
+            sections.iter().map(|(key, lines)| (*key, height(lines))),
+        );
+        if let Some(key) = self.section_jump.take()
+            && let Some(&(_, start)) = layout.starts.iter().find(|(shown, _)| *shown == key)
+        {
+            self.detail_scroll = u16::try_from(start).unwrap_or(u16::MAX);
+        }
+        let last = u16::try_from(layout.height.saturating_sub(1)).unwrap_or(u16::MAX);
+        self.detail_scroll = self.detail_scroll.min(last);
+        let scroll = usize::from(self.detail_scroll);
+        let cursor = layout
+            .at(scroll)
+            .filter(|_| self.focus == Focus::Detail)
+            .map(|(key, _)| key);
+        for (key, lines) in &mut sections {
+            let style = if cursor == Some(*key) {
+                self.theme.menu_selected
+            } else {
+                self.theme.heading
+            };
+            lines[0] = std::mem::take(&mut lines[0]).style(style);
+        }
+
+        // Each part is drawn on its own, from the line at the top of the pane.
+        let mut top = 0;
+        let mut y = inner.y;
+        for lines in std::iter::once(preamble).chain(sections.into_iter().map(|(_, lines)| lines)) {
+            let lines_height = height(&lines);
+            let skip = scroll.saturating_sub(top).min(lines_height);
+            top += lines_height;
+            let shown = (lines_height - skip).min(usize::from(inner.bottom() - y)) as u16;
+            if shown == 0 {
+                continue;
+            }
+            Paragraph::new(lines)
+                .wrap(Wrap { trim: false })
+                .scroll((skip as u16, 0))
+                .render(
+                    Rect {
+                        y,
+                        height: shown,
+                        ..inner
+                    },
+                    buf,
+                );
+            y += shown;
+        }
+        self.detail_layout = layout;
+    }
+
+    /// Time spent above each threshold reached, e.g. `1500 m 1:20:00, 2000 m
+    /// 35:00`; `None` below all of them.
+    fn time_above_text(&self, seconds: &[f64]) -> Option<String> {
+        let reached: Vec<String> = self
+            .altitude_thresholds
+            .iter()
+            .zip(seconds)
+            .filter(|&(_, &seconds)| seconds >= 1.0)
+            .map(|(&threshold, &seconds)| {
+                format!(
+                    "{} m {}",
+                    self.lang.number(threshold, 0),
+                    format_duration(seconds)
+                )
+            })
+            .collect();
+        (!reached.is_empty()).then(|| reached.join(", "))
+    }
+
+    /// Distance, times, origin and standing of the ride.
+    fn summary_section(&self, file_info: &FileItem) -> Option<String> {
+        let lang = self.lang;
+        let mut info = String::new();
+        if file_info.missing {
+            info += &lang.fill(
+                "{path} is gone, as last read:",
+                &[("path", &file_info.activity.path.display())],
+            );
+            info.push('\n');
+        }
+        info += &lang.fill(
+            "Distance: {distance} (flat equivalent {flat}km) Uphill ↑: {elevation}m",
+            &[
+                (
+                    "distance",
+                    &format_distance(file_info.activity.distance_km, lang),
+                ),
+                ("flat", &lang.number(file_info.flat_km, 1)),
+                (
+                    "elevation",
+                    &format!("{:>4}", lang.number(file_info.activity.elevation_gain, 0)),
+                ),
+            ],
+        );
+        if let Some(recorded) = file_info.activity.raw_elevation_gain {
+            info.push_str(&lang.fill(
+                " (DEM corrected, recorded: {elevation}m)",
+                &[("elevation", &format!("{:>4}", lang.number(recorded, 0)))],
+            ));
+        }
+        if let Some(peak) = file_info.activity.peak {
+            info.push('\n');
+            info.push_str(&lang.fill(
+                "max {elevation} m at km {km}",
+                &[
+                    ("elevation", &lang.number(peak.elevation_m, 0)),
+                    ("km", &lang.number(peak.distance_km, 1)),
+                ],
+            ));
+        }
+        if let Some(above) = self.time_above_text(&file_info.time_above) {
+            info.push('\n');
+            info.push_str(&lang.fill("time above {thresholds}", &[("thresholds", &above)]));
+        }
+        match file_info.activity.distance_source {
+            DistanceSource::Track => {}
+            DistanceSource::Embedded => {
+                info.push('\n');
+                info.push_str(lang.text("distance: from the file, the points are sparse"));
+            }
+            DistanceSource::Corrected => {
+                info.push('\n');
+                info.push_str(&lang.fill(
+                    "distance: estimated, the points are sparse (+{percent}%)",
+                    &[(
+                        "percent",
+                        &lang.number(self.tuning.sparse_correction_percent, 0),
+                    )],
+                ));
+            }
+        }
+        if let (Some(start), Some(end)) = (file_info.activity.start, file_info.activity.end) {
+            info.push('\n');
+            info.push_str(&format_times(start, end, lang));
+        }
+        let source = match file_info.sidecar.name {
+            Some(_) => "renamed".to_string(),
+            None => file_info.activity.name_source.to_string(),
+        };
+        info.push('\n');
+        info.push_str(&lang.fill("name source: {source}", &[("source", &lang.text(&source))]));
+        info.push('\n');
+        info.push_str(&lang.fill(
+            "device: {device}",
+            &[("device", &lang.text(file_info.activity.device()))],
+        ));
+        if !file_info.activity.alternates.is_empty() {
+            let files: Vec<_> = file_info
+                .activity
+                .alternates
+                .iter()
+                .map(|path| {
+                    path.file_name()
+                        .unwrap_or(path.as_os_str())
+                        .to_string_lossy()
+                })
+                .collect();
+            info.push('\n');
+            info.push_str(&lang.fill(
+                "also as: {files} (kept: {format})",
+                &[
+                    ("files", &files.join(", ")),
+                    ("format", &file_info.activity.format),
+                ],
+            ));
+        }
+        if let Some(tour) = file_info
+            .sidecar
+            .tour
+            .as_deref()
+            .and_then(|name| self.tour(name))
+            && let Some(stage) = tour
+                .stages
+                .iter()
+                .position(|stage| stage.id == file_info.activity.id)
+        {
+            info.push('\n');
+            info.push_str(&lang.fill(
+                "tour: {tour}, stage {stage} of {count}",
+                &[
+                    ("tour", &tour.name),
+                    ("stage", &(stage + 1)),
+                    ("count", &tour.stages.len()),
+                ],
+            ));
+        }
+        if let Some(descending) = &file_info.descending {
+            info.push('\n');
+            info.push_str(&lang.fill(
+                "descending: {speed} km/h on {descents}",
+                &[
+                    ("speed", &lang.number(descending.speed_kmh(), 1)),
+                    (
+                        "descents",
+                        &lang.count(descending.descents, "descent", "descents"),
+                    ),
+                ],
+            ));
+        }
+        #[cfg(feature = "borders")]
+        if !file_info.countries.is_empty() {
+            let countries: Vec<String> = file_info
+                .countries
+                .iter()
+                .map(|country| {
+                    format!(
+                        "{} ({}) {} km",
+                        lang.text(&country.name),
+                        country.code,
+                        lang.number(country.distance_km, 1)
+                    )
+                })
+                .collect();
+            info.push('\n');
+            info.push_str(&lang.fill(
+                "countries: {countries}",
+                &[("countries", &countries.join(", "))],
+            ));
+        }
+        if let Some(&new_km) = self.new_roads.get(&file_info.activity.id) {
+            info.push('\n');
+            info.push_str(&lang.fill(
+                "new roads: {km} km ({share})",
+                &[
+                    ("km", &lang.number(new_km, 1)),
+                    (
+                        "share",
+                        &format_share(new_km, file_info.activity.distance_km, lang),
+                    ),
+                ],
+            ));
+        }
+        if let Some(comparison) = self.comparison(&file_info.activity) {
+            info.push('\n');
+            info.push_str(&lang.fill(
+                "vs. my average: {comparison}",
+                &[("comparison", &comparison)],
+            ));
+        }
+        Some(info)
+    }
+
+    fn weather_section(&self, file_info: &FileItem) -> Option<String> {
+        let lang = self.lang;
+        let weather = self.weather.as_ref()?;
+        Some(match weather.for_activity(&file_info.activity) {
+            Some(day) => lang.fill(
+                "weather: {temperature}°C, wind {wind} km/h, rain {rain} mm",
+                &[
+                    ("temperature", &lang.number(day.temp_c, 0)),
+                    ("wind", &lang.number(day.wind_kmh, 0)),
+                    ("rain", &lang.number(day.precipitation_mm, 1)),
+                ],
+            ),
+            None => lang.text("weather: no data for that day").to_string(),
+        })
+    }
+
+    /// Wind of an out-and-back ride, with the speed it would have been
+    /// ridden at without it.
+    fn wind_section(&self, file_info: &FileItem) -> Option<String> {
+        let lang = self.lang;
+        let wind = file_info.wind?;
+        let component = if wind.headwind_out_kmh >= 0.0 {
+            "headwind on the way out: {speed} km/h"
+        } else {
+            "tailwind on the way out: {speed} km/h"
+        };
+        Some(
+            [
+                lang.fill(
+                    component,
+                    &[("speed", &lang.number(wind.headwind_out_kmh.abs(), 1))],
+                ),
+                lang.fill(
+                    "speed without the wind: {speed} km/h",
+                    &[("speed", &lang.number(wind.neutral_speed_kmh, 1))],
+                ),
+                lang.fill(
+                    "estimated from out-and-back symmetry, on {km} km",
+                    &[("km", &lang.number(wind.matched_km, 1))],
+                ),
+            ]
+            .join("\n"),
+        )
+    }
+
+    /// Stretches on a ferry or a train, struck through when they are left
+    /// out of the metrics.
+    fn transport_section(&self, file_info: &FileItem) -> Option<Text<'static>> {
+        let lang = self.lang;
+        let activity = &file_info.activity;
+        if activity.transport.is_empty() {
+            return None;
+        }
+        let style = if activity.transport_excluded {
+            Style::new().crossed_out()
+        } else {
+            Style::new()
+        };
+        let mut lines: Vec<Line> = activity
+            .transport
+            .iter()
+            .map(|segment| {
+                let text = lang.fill(
+                    if segment.by_sensors {
+                        "{length} km at {speed} km/h from km {start} in {time}, without pedaling"
+                    } else {
+                        "{length} km at {speed} km/h from km {start} in {time}, in a straight line"
+                    },
+                    &[
+                        ("length", &lang.number(segment.length_km, 1)),
+                        ("speed", &lang.number(segment.speed_kmh(), 1)),
+                        ("start", &lang.number(segment.start_km, 1)),
+                        ("time", &format_duration(segment.seconds)),
+                    ],
+                );
+                Line::styled(text, style)
+            })
+            .collect();
+        lines.push(Line::from(lang.text(if activity.transport_excluded {
+            "left out of the distance and time"
+        } else {
+            "counted in the distance and time, unless --exclude-transport"
+        })));
+        Some(Text::from(lines))
+    }
+
+    /// The plan of a route, or the brevet controls of a ride.
+    fn plan_section(&self, file_info: &FileItem) -> Option<String> {
+        let lang = self.lang;
+        let points = self.points(file_info);
+        let route_points = file_info.activity.route_points.as_slice();
+        let planned = match points {
+            Some(points) if is_planned(points) => Some(points),
+            _ if is_planned(route_points) => Some(route_points),
+            _ => None,
+        };
+        if let Some(points) = planned {
+            let plan = plan_ride(
+                points,
+                self.plan_speed_kmh,
+                self.stop_every_km,
+                &self.config.controls,
+                self.config.grade_model.unwrap_or_default(),
+            );
+            return Some(format_plan(&plan, lang));
+        }
+        let points = points.filter(|points| !points.is_empty())?;
+        if self.config.controls.is_empty() {
+            return None;
+        }
+        let controls = control_times(points, &self.config.controls);
+        Some(format_controls(&controls, lang))
+    }
+
+    fn intervals_section(&self, file_info: &FileItem) -> Option<String> {
+        let lang = self.lang;
+        let detected = file_info.intervals.as_ref()?;
+        if detected.intervals.is_empty() {
+            return None;
+        }
+        let mut info = lang.fill(
+            "Intervals (above {threshold} {unit}):",
+            &[
+                ("threshold", &lang.number(detected.threshold, 0)),
+                ("unit", &detected.metric.unit()),
+            ],
+        );
+        for (i, interval) in detected.intervals.iter().enumerate() {
+            info.push_str(&format!(
+                "\n{}. {}",
+                i + 1,
+                format_duration(interval.duration_s)
+            ));
+            if let Some(power) = interval.average_power {
+                info.push_str(&format!(" {} W", lang.number(power, 0)));
+            }
+            if let Some(heart_rate) = interval.average_heart_rate {
+                info.push_str(&format!(" {} bpm", lang.number(heart_rate, 0)));
+            }
+            if let Some(recovery) = interval.recovery_s {
+                info.push_str(&lang.fill(
+                    " after {recovery} recovery",
+                    &[("recovery", &format_duration(recovery))],
+                ));
+            }
+        }
+        Some(info)
+    }
+
+    fn terrain_section(&self, file_info: &FileItem) -> Option<String> {
+        let lang = self.lang;
+        let mut text = format_terrain(&file_info.terrain, lang);
+        if let Some(surface) = file_info.surface {
+            text.push('\n');
+            text.push_str(&lang.fill(
+                "likely unpaved: {distance} km ({percent}%), a guess from the speed",
+                &[
+                    ("distance", &lang.number(surface.unpaved_km, 1)),
+                    ("percent", &lang.number(surface.unpaved_percent(), 0)),
+                ],
+            ));
+        }
+        Some(text)
+    }
+
+    /// The distance under each model, against the haversine one of the
+    /// metrics, once computed.
+    fn distance_models_section(&self, file_info: &FileItem) -> Option<String> {
+        if !self.show_distance_models {
+            return None;
+        }
+        let lang = self.lang;
+        let Some(distances) = self.model_distances.get(&file_info.activity.id) else {
+            let text = match self.points(file_info) {
+                Some([]) => "no points to measure",
+                _ => "measuring…",
+            };
+            return Some(lang.text(text).to_string());
+        };
+        let lines: Vec<_> = DistanceModel::ALL
+            .into_iter()
+            .map(|model| {
+                let mut line = format!(
+                    "{:<11} {:>9} km",
+                    lang.text(model.name()),
+                    lang.number(distances.km(model), 3)
+                );
+                if model == DistanceModel::Haversine {
+                    line.push_str(lang.text("  (used)"));
+                } else if let Some(difference) = distances.difference_percent(model) {
+                    line.push_str(&format!("  {}%", lang.signed_number(difference, 2)));
+                }
+                line
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Elevation gain of the reference climbs ridden, against the official
+    /// one: a check of the altimeter of the device.
+    fn reference_climbs_section(&self, file_info: &FileItem) -> Option<String> {
+        let lang = self.lang;
+        let lines: Vec<_> = file_info
+            .climb_checks
+            .iter()
+            .map(|check| {
+                let error = check.gain_error_percent();
+                lang.fill(
+                    "{climb}: measured {measured} m vs official {official} m ({error}%)",
+                    &[
+                        ("climb", &check.name),
+                        ("measured", &lang.number(check.gain_m, 0)),
+                        ("official", &lang.number(check.official_gain_m, 0)),
+                        (
+                            "error",
+                            &format!(
+                                "{}{}",
+                                if error >= 0.0 { "+" } else { "" },
+                                lang.number(error, 1)
+                            ),
+                        ),
+                    ],
+                )
+            })
+            .collect();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    /// Best efforts, sprints and intensity of the ride.
+    fn bests_section(&self, file_info: &FileItem) -> Option<String> {
+        let lang = self.lang;
+        let mut lines = vec![];
+        if let Some(bests) = &file_info.bests {
+            lines.push(format_bests(bests, lang));
+        }
+        if let Some(peak) = file_info
+            .sprints
+            .iter()
+            .map(|sprint| sprint.peak_kmh)
+            .max_by(f64::total_cmp)
+        {
+            lines.push(lang.fill(
+                "sprints: {count} (top speed {speed} km/h)",
+                &[
+                    ("count", &file_info.sprints.len()),
+                    ("speed", &lang.number(peak, 1)),
+                ],
+            ));
+        }
+        if let Some(intensity) = file_info.intensity(&self.config.intensity_thresholds()) {
+            lines.push(lang.fill(
+                "intensity: {intensity}",
+                &[("intensity", &lang.text(intensity.name()))],
+            ));
+        }
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    /// What was wrong with the file, and fixed or left out when reading it.
+    fn quality_section(&self, file_info: &FileItem) -> Option<String> {
+        let lang = self.lang;
+        let activity = &file_info.activity;
+        let mut lines = vec![];
+        if let Some(quality) = &file_info.quality {
+            let mut line = lang.fill("score: {score}/100", &[("score", &quality.score())]);
+            if !quality.penalties.is_empty() {
+                line.push_str(&format!(
+                    " ({})",
+                    format_penalties(&quality.penalties, lang)
+                ));
+            }
+            lines.push(line);
+            if quality.jitter_points > 0 {
+                lines.push(lang.fill(
+                    "GPS: {count} jittery points",
+                    &[("count", &quality.jitter_points)],
+                ));
+            }
+            if quality.gap_km > 0.0 {
+                lines.push(lang.fill(
+                    "gaps: {distance} km without points",
+                    &[("distance", &lang.number(quality.gap_km, 2))],
+                ));
+            }
+        }
+        if activity.has_low_quality_elevation() {
+            lines.push(lang.text("elevation: low quality").to_string());
+        }
+        if activity.implausible_timestamps > 0 {
+            lines.push(lang.fill(
+                "timestamps: {count} implausible ignored",
+                &[("count", &activity.implausible_timestamps)],
+            ));
+        }
+        if activity.duplicate_points > 0 {
+            lines.push(lang.fill(
+                "points: {count} duplicates removed",
+                &[("count", &activity.duplicate_points)],
+            ));
+        }
+        for warning in &activity.warnings {
+            lines.push(lang.fill("warning: {warning}", &[("warning", warning)]));
+        }
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    fn notes_section(&self, file_info: &FileItem) -> Option<String> {
+        file_info.sidecar.notes.clone()
+    }
+
+    fn render_elevation_chart(&mut self, area: Rect, buf: &mut Buffer) {
+        if self.show_climb_rate {
+            return self.render_climb_rate_chart(area, buf);
+        }
+        let lang = self.lang;
+        let block = Block::new()
+            .title(Line::raw(lang.text("Activity Elevation")).centered())
+            .border_set(symbols::border::EMPTY)
+            .padding(Padding::horizontal(1));
+
+        let (data, distance) = if let Some(file) = self.selected() {
+            let profile = self.elevation_profile(file).unwrap_or_default();
+            (profile.to_vec(), profile.last().map_or(1000.0, |f| f.0))
+        } else {
+            (vec![], 100.0)
+        };
+
+        let y_bounds = if let Some((min, max)) = elevation_profile_min_max(&data) {
+            [min - 10.0, max + 50.0]
+        } else {
+            [0.0, 1000.0]
+        };
+
+        // Charts color whole datasets: one per stretch, the first of each
+        // steepness named in the legend.
+        let mut named = vec![];
+        let mut datasets = steepness_runs(&data)
+            .into_iter()
+            .map(|(steepness, run)| {
+                let dataset = Dataset::default()
+                    .marker(Marker::Dot)
+                    .graph_type(ratatui::widgets::GraphType::Line)
+                    .style(Style::default().fg(self.theme.steepness_color(steepness)))
+                    .data(run);
+                if named.contains(&steepness) {
+                    return dataset;
+                }
+                named.push(steepness);
+                dataset.name(steepness.label())
+            })
+            .collect::<Vec<_>>();
+        let cursor = self.scrub.map(|km| [(km, y_bounds[0]), (km, y_bounds[1])]);
+        if let Some(cursor) = &cursor {
+            datasets.push(
+                Dataset::default()
+                    .marker(Marker::Braille)
+                    .graph_type(ratatui::widgets::GraphType::Line)
+                    .style(self.theme.value)
+                    .data(cursor),
+            );
+        }
+
+        Chart::new(datasets)
+            .block(block)
+            .x_axis(
+                Axis::default()
+                    .title(lang.text("Distance (km)"))
+                    .labels(["0".to_string(), format_distance(distance, lang)])
+                    .bounds([0.0, distance])
+                    .style(Style::default()),
+            )
+            .y_axis(
+                Axis::default()
+                    .title(lang.text("Elevation (m)"))
+                    .bounds(y_bounds)
+                    .labels(
+                        y_bounds
+                            .iter()
+                            .map(|&item| format!("{:>8}", lang.number(item, 2))),
+                    )
+                    .style(Style::default()),
+            )
+            .render(area, buf);
+    }
+
+    /// Rate of climb of the selected ride over time, up above the axis and
+    /// down below it.
+    fn render_climb_rate_chart(&self, area: Rect, buf: &mut Buffer) {
+        let lang = self.lang;
+        let block = Block::new()
+            .title(Line::raw(lang.text("Rate of Climb")).centered())
+            .border_set(symbols::border::EMPTY)
+            .padding(Padding::horizontal(1));
+
+        let data = self
+            .selected()
+            .and_then(|file| Some(climb_rate(self.points(file)?, &file.activity.tuning)))
+            .unwrap_or_default();
+        let minutes = data.last().map_or(60.0, |&(minutes, _)| minutes);
+        let extent = data
+            .iter()
+            .map(|&(_, rate)| rate.abs())
+            .fold(10.0, f64::max)
+            .ceil();
+        let up: Vec<(f64, f64)> = data
+            .iter()
+            .map(|&(minute, rate)| (minute, rate.max(0.0)))
+            .collect();
+        let down: Vec<(f64, f64)> = data
+            .iter()
+            .map(|&(minute, rate)| (minute, rate.min(0.0)))
+            .collect();
+        let datasets = vec![
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(ratatui::widgets::GraphType::Line)
+                .style(self.theme.chart)
+                .data(&up),
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(ratatui::widgets::GraphType::Line)
+                .style(self.theme.muted)
+                .data(&down),
+        ];
+
+        Chart::new(datasets)
+            .block(block)
+            .x_axis(
+                Axis::default()
+                    .title(lang.text("Time (min)"))
+                    .labels(["0".to_string(), lang.number(minutes, 0)])
+                    .bounds([0.0, minutes])
+                    .style(Style::default()),
+            )
+            .y_axis(
+                Axis::default()
+                    .title(lang.text("m/min"))
+                    .bounds([-extent, extent])
+                    .labels(
+                        [-extent, 0.0, extent]
+                            .iter()
+                            .map(|&item| format!("{:>8}", lang.number(item, 0))),
+                    )
+                    .style(Style::default()),
+            )
+            .render(area, buf);
+    }
+}
+
+impl FileItem {
+    /// The ride as a list item `width` columns wide: one line with its date,
+    /// name and badges, or two below [`NARROW_LIST_WIDTH`], its date,
+    /// distance and elevation going under the name. The name is cut in the
+    /// middle so that the badges and tags after it stay in view.
+    fn list_text(&self, marked: bool, in_range: bool, width: u16, app: &App) -> Text<'static> {
+        let symbols = app.symbols();
+        let mut before = vec![];
+        if in_range {
+            before.push(format!("{} ", symbols.range).set_style(app.theme.accent));
+        }
+        if marked {
+            before.push("* ".set_style(app.theme.marked));
+        }
+        before.push(match self.intensity(&app.config.intensity_thresholds()) {
+            Some(intensity) => format!("{} ", symbols.dot).fg(app.theme.intensity_color(intensity)),
+            None => "  ".into(),
+        });
+        if let Some(quality) = &self.quality {
+            before.push(quality_indicator(quality.score(), &app.theme, symbols));
+        }
+
+        let mut after = vec![];
+        if self.missing {
+            after.push(
+                app.lang
+                    .text(" [missing]")
+                    .to_string()
+                    .set_style(app.theme.bad),
+            );
+        }
+        if self.is_commute() {
+            after.push(
+                app.lang
+                    .text(" [commute]")
+                    .to_string()
+                    .set_style(app.theme.muted),
+            );
+        }
+        for tag in self.tags() {
+            after.push(" ".into());
+            after.push(Span::styled(
+                format!(" {tag} "),
+                Style::new()
+                    .fg(app.theme.tag_text)
+                    .bg(app.theme.tag_color(tag)),
+            ));
+        }
+
+        let date = self
+            .activity
+            .start
+            .map(|start| start.format(app.date_format()).to_string());
+        let narrow = width < NARROW_LIST_WIDTH;
+        let mut room =
+            usize::from(width).saturating_sub(before.iter().chain(&after).map(Span::width).sum());
+        let mut name = String::new();
+        if let Some(date) = &date
+            && !narrow
+        {
+            name = format!("{date} ");
+            room = room.saturating_sub(name.chars().count());
+        }
+        name.push_str(&ellipsize_middle(self.name(), room, symbols.ellipsis));
+        let mut line = Line::from(before);
+        line.push_span(name.set_style(app.row_style(self.activity.start)));
+        line.spans.extend(after);
+        if !narrow {
+            return line.into();
+        }
+
+        let lang = app.lang;
+        let mut details: Vec<String> = date.into_iter().collect();
+        details.push(format!("{} km", lang.number(self.activity.distance_km, 1)));
+        details.push(format!(
+            "{} m",
+            lang.number(self.activity.elevation_gain, 0)
+        ));
+        Text::from(vec![
+            line,
+            Line::styled(
+                details.join(&format!(" {} ", symbols.separator)),
+                app.theme.muted,
+            )
+            .right_aligned(),
+        ])
+    }
+}
+
+/// `text` cut in the middle to at most `width` characters, `ellipsis` in
+/// place of the cut, so that both its start and its end can still be told
+/// apart.
+fn ellipsize_middle(text: &str, width: usize, ellipsis: &str) -> String {
+    let count = text.chars().count();
+    if count <= width {
+        return text.to_string();
+    }
+    let Some(kept) = width.checked_sub(ellipsis.chars().count()) else {
+        return String::new();
+    };
+    let head: String = text.chars().take(kept.div_ceil(2)).collect();
+    let tail: String = text.chars().skip(count - kept / 2).collect();
+    format!("{}{ellipsis}{}", head.trim_end(), tail.trim_start())
+}
+
+/// One bar per grade bucket, labelled with the rounded value.
+fn grade_bar_chart<'a>(title: &'a str, values: &[f64], theme: &Theme) -> BarChart<'a> {
+    let bars: Vec<Bar> = GRADE_BUCKETS
+        .iter()
+        .zip(values)
+        .map(|(&(label, _), &value)| {
+            Bar::default()
+                .label(label.into())
+                .value(value.round() as u64)
+                .style(theme.chart)
+        })
+        .collect();
+
+    BarChart::default()
+        .block(Block::new().title(Line::raw(title).set_style(theme.heading)))
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(6)
+        .bar_gap(1)
+}
+
+/// A bar as high as the data quality score, in the colors of the theme.
+fn quality_indicator(score: u8, theme: &Theme, symbols: &Symbols) -> Span<'static> {
+    let bars = symbols.bars;
+    let bar = bars[usize::from(score) * (bars.len() - 1) / 100];
+    let style = if score >= QUALITY_GOOD {
+        theme.good
+    } else if score < QUALITY_POOR {
+        theme.bad
+    } else {
+        theme.muted
+    };
+    format!("{bar} ").set_style(style)
+}
+
+/// Problems of a quality score and the points they take off, e.g.
+/// `timestamps −30, gaps −15`.
+fn format_penalties(penalties: &[(Problem, f64)], lang: Lang) -> String {
+    penalties
+        .iter()
+        .map(|&(problem, points)| format!("{} −{}", lang.text(problem.name()), points.round()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Signed difference to a mean, in percent.
+fn format_relative(value: f64, mean: f64, lang: Lang) -> String {
+    if mean == 0.0 {
+        return lang.text("n/a").to_string();
+    }
+    let change = lang.number((value - mean) / mean * 100.0, 0);
+    let sign = if change.starts_with('-') { "" } else { "+" };
+    format!("{sign}{change}%")
+}
+
+/// Share of a total, in percent.
+fn format_share(value: f64, total: f64, lang: Lang) -> String {
+    if total == 0.0 {
+        return lang.text("n/a").to_string();
+    }
+    format!("{}%", lang.number(value / total * 100.0, 0))
+}
+
+/// `5s`, `1min` or `20min`.
+fn format_window(seconds: usize) -> String {
+    if seconds < 60 {
+        format!("{seconds}s")
+    } else {
+        format!("{}min", seconds / 60)
+    }
+}
+
+/// A header row of windows over a row of best efforts.
+fn format_bests(bests: &Bests, lang: Lang) -> String {
+    let mut windows = format!("{:<8}", lang.text("bests"));
+    let mut values = format!("{:<8}", bests.metric.unit());
+    for (window, value) in BEST_WINDOWS_S.iter().zip(bests.values) {
+        windows.push_str(&format!("{:>7}", format_window(*window)));
+        let value = value.map_or("-".to_string(), |value| lang.number(value, 0));
+        values.push_str(&format!("{value:>7}"));
+    }
+    format!("{windows}\n{values}")
+}
+
+/// A row of distance, moving time and speed per terrain.
+fn format_terrain(splits: &[TerrainSplit], lang: Lang) -> String {
+    let mut table = format!(
+        "{:<16}{:>8}{:>9}{:>7}",
+        lang.text("terrain"),
+        "km",
+        lang.text("time"),
+        "km/h"
+    );
+    for split in splits {
+        table.push_str(&format!(
+            "\n{:<16}{:>8}{:>9}{:>7}",
+            lang.text(&split.terrain.to_string()),
+            lang.number(split.distance_km, 1),
+            split
+                .moving_seconds
+                .map_or("-".to_string(), format_duration),
+            split
+                .average_speed()
+                .map_or("-".to_string(), |speed| lang.number(speed, 1)),
+        ));
+    }
+    table
+}
+
+/// Keys, name and description of a command, for the help and the palette.
+fn command_line(command: &UserCommand<App>, lang: Lang, theme: &Theme) -> Line<'static> {
+    Line::from(vec![
+        format!("{:>8}  ", command.keys_label()).set_style(theme.value),
+        format!("{:<22}", lang.text(command.name)).set_style(theme.heading),
+        lang.text(command.description)
+            .to_string()
+            .set_style(theme.muted),
+    ])
+}
+
+/// Length and grade of a climb, e.g. `2.4 km at 6.1%`.
+fn format_climb(climb: &Climb, lang: Lang) -> String {
+    lang.fill(
+        "{length} km at {grade}%",
+        &[
+            ("length", &lang.number(climb.length_m / 1_000.0, 1)),
+            ("grade", &lang.number(climb.grade_percent(), 1)),
+        ],
+    )
+}
+
+/// Value of a tuning parameter with its unit, `off` for a disabled limit.
+fn format_parameter(parameter: TuningParameter, tuning: &Tuning, lang: Lang) -> String {
+    let value = parameter.value(tuning);
+    if parameter == TuningParameter::MaxSpeed && value == 0.0 {
+        return lang.text("off").to_string();
+    }
+    let decimals = if parameter == TuningParameter::MovingMinSpeed {
+        1
+    } else {
+        0
+    };
+    format!("{} {}", lang.number(value, decimals), parameter.unit())
+}
+
+/// Start and end of a ride on the clocks they were recorded with, the end
+/// marked when it falls on a later day, with the time elapsed in between.
+fn format_times(start: DateTime<FixedOffset>, end: DateTime<FixedOffset>, lang: Lang) -> String {
+    // Across a daylight saving change, the offsets tell why the clock times
+    // do not match the elapsed time.
+    let pattern = if start.offset() == end.offset() {
+        "%H:%M"
+    } else {
+        "%H:%M (UTC%:z)"
+    };
+    let mut until = end.format(pattern).to_string();
+    let days = (end.date_naive() - start.date_naive()).num_days();
+    if days > 0 {
+        until.push_str(&format!(" +{}", lang.count(days as usize, "day", "days")));
+    }
+    lang.fill(
+        "time: {start} to {end}, {elapsed} elapsed",
+        &[
+            ("start", &start.format(pattern)),
+            ("end", &until),
+            (
+                "elapsed",
+                &format_duration((end - start).num_milliseconds() as f64 / 1_000.0),
+            ),
+        ],
+    )
+}
+
+/// `m:ss`, or `h:mm:ss` from one hour.
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+/// OSC 52 escape sequence setting the clipboard of the terminal, which
+/// also works over SSH.
+fn clipboard_escape(text: &str) -> String {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+
+    format!("\x1b]52;c;{}\x07", STANDARD.encode(text))
+}
+
+/// A duration with its sign, `+` when behind.
+fn format_signed_duration(seconds: f64) -> String {
+    let sign = if seconds < 0.0 { "-" } else { "+" };
+    format!("{sign}{}", format_duration(seconds.abs()))
+}
+
+/// "lost 1:42 on the 3.2 km climb at km 18", or the stretch between two
+/// kilometers.
+fn format_diff_segment(segment: &DiffSegment, lang: Lang) -> String {
+    let time = format_duration(segment.seconds.abs());
+    let (start, end) = (
+        lang.number(segment.start_km, 0),
+        lang.number(segment.end_km, 0),
+    );
+    let length = lang.number(segment.end_km - segment.start_km, 1);
+    match (segment.seconds > 0.0, segment.climb) {
+        (true, true) => lang.fill(
+            "lost {time} on the {length} km climb at km {start}",
+            &[("time", &time), ("length", &length), ("start", &start)],
+        ),
+        (false, true) => lang.fill(
+            "gained {time} on the {length} km climb at km {start}",
+            &[("time", &time), ("length", &length), ("start", &start)],
+        ),
+        (true, false) => lang.fill(
+            "lost {time} between km {start} and km {end}",
+            &[("time", &time), ("start", &start), ("end", &end)],
+        ),
+        (false, false) => lang.fill(
+            "gained {time} between km {start} and km {end}",
+            &[("time", &time), ("start", &start), ("end", &end)],
+        ),
+    }
+}
+
+fn format_distance(distance: f64, lang: Lang) -> String {
+    format!("{:>8}km", lang.number(distance, 3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::TrackPoint;
+    use ratatui::backend::TestBackend;
+    use ratatui::style::Modifier;
+
+    fn render(app: &mut App, width: u16, height: u16) -> Buffer {
+        let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+        app.render(buf.area, &mut buf);
+        buf
+    }
+
+    fn text(buf: &Buffer) -> String {
+        buf.content.iter().map(|cell| cell.symbol()).collect()
+    }
+
+    /// Style of the first cell of `needle` on the screen.
+    fn style_of(buf: &Buffer, needle: &str) -> Style {
+        let needle: Vec<String> = needle.chars().map(String::from).collect();
+        let start = (0..buf.content.len())
+            .find(|&i| {
+                buf.content[i..]
+                    .iter()
+                    .map(|cell| cell.symbol())
+                    .take(needle.len())
+                    .eq(needle.iter().map(String::as_str))
+            })
+            .unwrap();
+        buf.content[start].style()
+    }
+
+    fn store_dir(names: &[&str]) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "cyclemetrics-runner-{}-{}",
+            std::process::id(),
+            names.join("-")
+        ))
+    }
+
+    fn app_with_files(names: &[&str]) -> App {
+        let mut app = App {
+            store: Store::new(store_dir(names)),
+            ..Default::default()
+        };
+        for (i, name) in names.iter().enumerate() {
+            let activity = Activity {
+                id: format!("{i}-{i:016x}").parse().unwrap(),
+                name: name.to_string(),
+                ..Default::default()
+            };
+            app.file_list.files.push(FileItem::new(
+                activity,
+                Sidecar::default(),
+                false,
+                vec![],
+                GradeModel::default(),
+            ));
+        }
+        app.refresh_list();
+        app
+    }
+
+    fn press(app: &mut App, code: KeyCode) {
+        app.handle_key_event(KeyEvent::from(code));
+    }
+
+    fn names(app: &App) -> Vec<&str> {
+        app.file_list
+            .files
+            .iter()
+            .map(|file| file.activity.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn every_command_has_its_own_keys() {
+        let mut keys = vec![];
+        for command in COMMANDS {
+            assert!(!command.keys.is_empty(), "{}", command.name);
+            for key in command.keys {
+                assert!(!keys.contains(key), "{} is bound twice", key.label());
+                keys.push(*key);
+            }
+            assert_eq!(
+                COMMANDS
+                    .iter()
+                    .filter(|other| other.name == command.name)
+                    .count(),
+                1
+            );
+        }
+
+        // Each key reaches its command, whose handler runs.
+        let mut app = app_with_files(&["commands"]);
+        for command in COMMANDS {
+            for key in command.keys {
+                let modifiers = if key.control {
+                    KeyModifiers::CONTROL
+                } else {
+                    KeyModifiers::NONE
+                };
+                let event = KeyEvent::new(key.code, modifiers);
+                assert!(std::ptr::eq(
+                    command_for(COMMANDS, &event).unwrap(),
+                    command
+                ));
+            }
+            (command.run)(&mut app);
+            app.prompt = None;
+            app.palette = None;
+            app.settings = None;
+        }
+    }
+
+    #[test]
+    fn palette_runs_the_selected_command() {
+        let mut app = app_with_files(&["a", "b"]);
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('p'), KeyModifiers::CONTROL));
+        for c in "arch".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        let screen = text(&render(&mut app, 140, 30));
+        assert!(screen.contains("> arch_"), "{screen}");
+        assert!(screen.contains("archive rides"));
+        assert!(!screen.contains("switch pane"));
+
+        press(&mut app, KeyCode::Enter);
+        assert!(app.palette.is_none());
+        assert_eq!(names(&app), ["b"]);
+
+        // Esc closes the palette without running anything.
+        press(&mut app, KeyCode::Char(':'));
+        press(&mut app, KeyCode::Char('q'));
+        press(&mut app, KeyCode::Esc);
+        assert!(app.palette.is_none() && !app.exit);
+
+        app.lang = Lang::Fr;
+        press(&mut app, KeyCode::Char('?'));
+        let screen = text(&render(&mut app, 140, 40));
+        assert!(screen.contains("Touches"), "{screen}");
+        assert!(screen.contains("^p  palette de commandes"));
+    }
+
+    #[test]
+    fn y_copies_the_share_text() {
+        let mut app = app_with_files(&["Col"]);
+        app.file_list.files[0].activity.distance_km = 42.26;
+        app.share = ShareTemplate::parse("{name}: {distance}, {avg_speed}").unwrap();
+
+        press(&mut app, KeyCode::Char('y'));
+
+        assert_eq!(app.clipboard.as_deref(), Some("Col: 42.3 km, ?"));
+        assert_eq!(app.status.as_deref(), Some("Copied: Col: 42.3 km, ?"));
+        assert_eq!(clipboard_escape("Col"), "\x1b]52;c;Q29s\x07");
+    }
+
+    #[test]
+    fn undo_and_redo_marked_archive() {
+        let mut app = app_with_files(&["a", "b", "c"]);
+        press(&mut app, KeyCode::Char(' '));
+        press(&mut app, KeyCode::Char(' '));
+        press(&mut app, KeyCode::Char('c'));
+        assert!(app.file_list.files[0].is_commute() && app.file_list.files[1].is_commute());
+
+        press(&mut app, KeyCode::Char('a'));
+        assert_eq!(names(&app), ["c"]);
+        assert!(app.marked.is_empty());
+
+        press(&mut app, KeyCode::Char('u'));
+        assert_eq!(names(&app), ["a", "b", "c"]);
+        assert_eq!(app.marked.len(), 2);
+        assert_eq!(
+            app.status.as_deref(),
+            Some("undone: archive of 2 activities")
+        );
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::CONTROL));
+        assert_eq!(names(&app), ["c"]);
+        assert_eq!(app.archived.len(), 2);
+    }
+
+    #[test]
+    fn virtual_rides_are_counted_on_demand() {
+        let mut app = App {
+            store: Store::new(store_dir(&["virtual"])),
+            ..Default::default()
+        };
+        let activity = |id: &str, virtual_ride| Activity {
+            id: id.parse().unwrap(),
+            name: id.to_string(),
+            distance_km: 20.0,
+            virtual_ride,
+            // Rides without points are hidden.
+            points: vec![TrackPoint::default()],
+            ..Default::default()
+        };
+        app.insert(activity("1-0000000000000001", false)).unwrap();
+        app.insert(activity("2-0000000000000002", true)).unwrap();
+        app.refresh_list();
+
+        let tags: Vec<Vec<&str>> = app.file_list.files.iter().map(FileItem::tags).collect();
+        assert_eq!(tags, [vec![], vec![VIRTUAL_TAG]]);
+        assert_eq!(app.totals(true).0, 20.0);
+        assert!(text(&render(&mut app, 120, 20)).contains("virtual excluded"));
+
+        press(&mut app, KeyCode::Char('v'));
+        assert_eq!(app.totals(true).0, 40.0);
+    }
+
+    #[test]
+    fn interface_in_french() {
+        let mut app = app_with_files(&["Col"]);
+        app.file_list.files[0].activity.start =
+            Some(DateTime::parse_from_rfc3339("2024-07-14T08:00:00+02:00").unwrap());
+        app.file_list.files[0].activity.distance_km = 1234.5;
+        app.lang = Lang::Fr;
+
+        let screen = text(&render(&mut app, 140, 30));
+        assert!(screen.contains("Activités"), "{screen}");
+        assert!(screen.contains("14/07/2024 Col"));
+        assert!(screen.contains("Total : 1\u{a0}234,500km"));
+
+        app.date_format = Some("%Y %b %d".to_string());
+        press(&mut app, KeyCode::Char('u'));
+        let screen = text(&render(&mut app, 140, 30));
+        assert!(screen.contains("2024 Jul 14 Col"));
+        assert!(screen.contains("rien à annuler"));
+    }
+
+    #[test]
+    fn undone_rename_leaves_no_trace() {
+        let mut app = app_with_files(&["renamed"]);
+        press(&mut app, KeyCode::Char('r'));
+        for c in "Col".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.file_list.files[0].sidecar.name.as_deref(), Some("Col"));
+
+        press(&mut app, KeyCode::Char('u'));
+        app.save_changes().unwrap();
+
+        assert_eq!(app.file_list.files[0].sidecar.name, None);
+        assert!(!store_dir(&["renamed"]).exists());
+    }
+
+    fn visible_names(app: &App) -> Vec<&str> {
+        app.visible_files().map(FileItem::name).collect()
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn filter_on_names_and_tags() {
+        let mut app = app_with_files(&["Gravel loop", "Col", "Commute"]);
+        app.file_list.files[1].auto_tags = vec!["climb".to_string()];
+        app.filter.tags = vec!["climb".to_string()];
+        app.refresh_list();
+        assert_eq!(visible_names(&app), ["Col"]);
+
+        app.filter.tags.clear();
+        press(&mut app, KeyCode::Char('/'));
+        type_text(&mut app, "l");
+        assert_eq!(visible_names(&app), ["Gravel loop", "Col"]);
+        type_text(&mut app, "o");
+        assert_eq!(visible_names(&app), ["Gravel loop"]);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.selected().map(FileItem::name), Some("Gravel loop"));
+
+        press(&mut app, KeyCode::Char('/'));
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(visible_names(&app).len(), 3);
+    }
+
+    #[test]
+    fn manual_tags_combine_with_rules_and_undo() {
+        let mut app = app_with_files(&["a", "b"]);
+        app.file_list.files[0].auto_tags = vec!["gravel".to_string()];
+        press(&mut app, KeyCode::Char(' '));
+        press(&mut app, KeyCode::Char(' '));
+        press(&mut app, KeyCode::Char('t'));
+        type_text(&mut app, "gravel");
+        press(&mut app, KeyCode::Enter);
+
+        assert_eq!(app.file_list.files[0].tags(), ["gravel"]);
+        assert_eq!(app.file_list.files[1].tags(), ["gravel"]);
+        assert_eq!(app.status.as_deref(), Some("tag gravel of 2 activities"));
+
+        press(&mut app, KeyCode::Char('u'));
+        assert_eq!(app.file_list.files[0].tags(), ["gravel"]);
+        assert!(app.file_list.files[1].tags().is_empty());
+    }
+
+    #[test]
+    fn rides_of_a_day_combine_into_one_entry() {
+        let mut app = app_with_files(&["home", "out", "day before"]);
+        for (file, (start, end, km)) in app.file_list.files.iter_mut().zip([
+            (
+                "2024-07-02T17:00:00+02:00",
+                "2024-07-02T18:00:00+02:00",
+                30.0,
+            ),
+            (
+                "2024-07-02T08:00:00+02:00",
+                "2024-07-02T09:30:00+02:00",
+                45.0,
+            ),
+            (
+                "2024-07-01T08:00:00+02:00",
+                "2024-07-01T10:00:00+02:00",
+                60.0,
+            ),
+        ]) {
+            file.activity.start = Some(DateTime::parse_from_rfc3339(start).unwrap());
+            file.activity.end = Some(DateTime::parse_from_rfc3339(end).unwrap());
+            file.activity.distance_km = km;
+        }
+        let totals = app.totals(true);
+
+        press(&mut app, KeyCode::Char('d'));
+        assert_eq!(app.file_list.rows.len(), 2);
+        let screen = text(&render(&mut app, 160, 30));
+        assert!(screen.contains("▸ 02-07-2024 out + home"), "{screen}");
+        assert!(screen.contains("(2 rides,   75.000km"), "{screen}");
+        assert!(screen.contains("08:00–18:00"), "{screen}");
+        assert!(screen.contains("Grand Total:  135.000km"), "{screen}");
+        assert_eq!(app.totals(true), totals);
+        assert_eq!(app.targets(), [0, 1]);
+
+        press(&mut app, KeyCode::Char(' '));
+        assert_eq!(app.marked.len(), 2);
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Char(' '));
+        assert!(app.marked.is_empty());
+
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.file_list.rows.len(), 4);
+        assert_eq!(
+            app.file_list.rows[1],
+            Row::File {
+                index: 0,
+                nested: true
+            }
+        );
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.selected().map(FileItem::name), Some("out"));
+        assert_eq!(app.session().expanded_days, ["2024-07-02"]);
+        assert!(app.session().combine_days);
+
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.file_list.rows.len(), 2);
+        assert_eq!(app.file_list.state.selected(), Some(0));
+
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Char('d'));
+        assert_eq!(app.file_list.rows.len(), 3);
+        assert_eq!(app.selected().map(FileItem::name), Some("day before"));
+    }
+
+    #[test]
+    fn month_headers_tell_the_month_against_the_year_before() {
+        let mut app = app_with_files(&["lake", "hills", "col", "coast"]);
+        for (file, (start, km, hours)) in app.file_list.files.iter_mut().zip([
+            ("2023-07-08T08:00:00+02:00", 40.0, 2.0),
+            ("2024-06-15T08:00:00+02:00", 30.0, 1.0),
+            ("2024-07-02T08:00:00+02:00", 70.0, 4.0),
+            ("2024-07-20T08:00:00+02:00", 50.0, 2.0),
+        ]) {
+            let start = DateTime::parse_from_rfc3339(start).unwrap();
+            file.activity.start = Some(start);
+            file.activity.released_moving_seconds = Some(hours * 3600.0);
+            file.activity.distance_km = km;
+        }
+
+        press(&mut app, KeyCode::Char('M'));
+        assert_eq!(app.file_list.rows.len(), 7);
+        assert!(app.session().group_months);
+        assert_eq!(app.selected().map(FileItem::name), Some("lake"));
+        press(&mut app, KeyCode::Up);
+        let screen = text(&render(&mut app, 160, 30));
+        assert!(screen.contains("Jul 2024 (2 rides, 120.000km)"), "{screen}");
+        assert!(
+            screen.contains("No rides in Jul 2022 to compare with."),
+            "{screen}"
+        );
+
+        for _ in 0..4 {
+            press(&mut app, KeyCode::Down);
+        }
+        assert_eq!(app.selected().map(FileItem::name), None);
+        assert!(app.targets().is_empty());
+        let screen = text(&render(&mut app, 160, 30));
+        assert!(
+            screen.contains("Rides: 2 ↑ 100% on Jul 2023 (1)"),
+            "{screen}"
+        );
+        assert!(
+            screen.contains("Distance: 120.000km ↑ 200% on Jul 2023 (40.000km)"),
+            "{screen}"
+        );
+        assert!(
+            screen.contains("Moving time: 6:00:00 ↑ 200% on Jul 2023 (2:00:00)"),
+            "{screen}"
+        );
+        assert!(
+            screen.contains("Longest ride: col, 70.000km ↑ 75% on Jul 2023"),
+            "{screen}"
+        );
+
+        // Back to the rides alone, on the one below the header.
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Char('M'));
+        assert_eq!(app.file_list.rows.len(), 4);
+        assert_eq!(app.selected().map(FileItem::name), Some("col"));
+    }
+
+    #[test]
+    fn marked_rides_become_the_stages_of_a_tour() {
+        let mut app = app_with_files(&["day two", "day one", "commute"]);
+        for (file, (start, km)) in app.file_list.files.iter_mut().zip([
+            ("2024-07-02T08:00:00+02:00", 120.0),
+            ("2024-07-01T08:00:00+02:00", 100.0),
+            ("2024-07-03T08:00:00+02:00", 10.0),
+        ]) {
+            file.activity.start = Some(DateTime::parse_from_rfc3339(start).unwrap());
+            file.activity.distance_km = km;
+        }
+        press(&mut app, KeyCode::Char(' '));
+        press(&mut app, KeyCode::Char(' '));
+        press(&mut app, KeyCode::Char('T'));
+        type_text(&mut app, "Alps");
+        press(&mut app, KeyCode::Enter);
+
+        assert_eq!(
+            app.status.as_deref(),
+            Some("join tour Alps of 2 activities")
+        );
+        let tours: Vec<Option<&str>> = app
+            .file_list
+            .files
+            .iter()
+            .map(|file| file.sidecar.tour.as_deref())
+            .collect();
+        assert_eq!(tours, [Some("Alps"), Some("Alps"), None]);
+
+        app.file_list.state.select(Some(0));
+        press(&mut app, KeyCode::Char('o'));
+        assert_eq!(
+            app.tour_view,
+            Some(TourView {
+                name: "Alps".to_string(),
+                stage: 1
+            })
+        );
+        let screen = text(&render(&mut app, 140, 30));
+        assert!(screen.contains("1. 01-07-2024  day one"), "{screen}");
+        assert!(screen.contains("Σ 220.0 km"), "{screen}");
+
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.selected().map(FileItem::name), Some("day one"));
+        assert!(app.tour_view.is_none());
+
+        app.show_stats = true;
+        let screen = text(&render(&mut app, 140, 60));
+        assert!(screen.contains("Alps: 2 stages, 220.0 km"), "{screen}");
+        app.show_stats = false;
+
+        press(&mut app, KeyCode::Char('o'));
+        press(&mut app, KeyCode::Char('d'));
+        assert!(app.file_list.files[1].sidecar.tour.is_none());
+        assert_eq!(app.tour_view.as_ref().map(|view| view.stage), Some(0));
+        press(&mut app, KeyCode::Char('d'));
+        assert!(app.tour_view.is_none());
+        assert!(app.tours().is_empty());
+
+        press(&mut app, KeyCode::Char('o'));
+        assert_eq!(
+            app.status.as_deref(),
+            Some("not part of a tour, T adds it to one")
+        );
+    }
+
+    #[test]
+    fn route_popup_lists_rides_on_the_same_route() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mountain.gpx");
+        let points = crate::stream::read_track_points(&fs::read(path).unwrap()).unwrap();
+        let mut app = app_with_files(&["a", "b", "c"]);
+        for i in [0, 2] {
+            app.file_list.files[i].route = RouteSignature::new(&points);
+        }
+        app.file_list.state.select(Some(0));
+
+        press(&mut app, KeyCode::Char('m'));
+        let screen = text(&render(&mut app, 120, 30));
+
+        assert!(screen.contains("Same route: ridden 2 times"), "{screen}");
+        assert_eq!(screen.matches("% similar").count(), 2);
+        assert!(screen.contains("◀ this ride"));
+
+        press(&mut app, KeyCode::Char('m'));
+        assert!(!text(&render(&mut app, 120, 30)).contains("Same route"));
+    }
+
+    #[test]
+    fn route_popup_shows_where_time_was_lost() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mountain.gpx");
+        let points = crate::stream::read_track_points(&fs::read(path).unwrap()).unwrap();
+        let start = points[0].time.unwrap();
+        let slower: Vec<TrackPoint> = points
+            .iter()
+            .map(|point| TrackPoint {
+                time: point.time.map(|time| start + (time - start) * 21 / 20),
+                ..*point
+            })
+            .collect();
+        let mut app = app_with_files(&["fast", "slow"]);
+        for (file, points) in app.file_list.files.iter_mut().zip([points, slower]) {
+            file.route = RouteSignature::new(&points);
+            file.activity.points = points;
+        }
+        let slow = app
+            .file_list
+            .visible
+            .iter()
+            .position(|&i| app.file_list.files[i].name() == "slow");
+        app.file_list.state.select(slow);
+
+        press(&mut app, KeyCode::Char('m'));
+        let screen = text(&render(&mut app, 120, 40));
+
+        assert!(screen.contains("Time vs. the ride of"), "{screen}");
+        assert!(screen.contains("lost "), "{screen}");
+        assert!(!screen.contains("gained "), "{screen}");
+
+        // Both rides go to a CSV, side by side.
+        press(&mut app, KeyCode::Char('x'));
+        let prompt = app.prompt.as_mut().unwrap();
+        assert_eq!(prompt.kind, PromptKind::Comparison);
+        let dir = store_dir(&["comparison"]);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("slow-vs-fast.csv");
+        prompt.text = path.display().to_string();
+        press(&mut app, KeyCode::Enter);
+        let csv = fs::read_to_string(&path).unwrap();
+        assert!(
+            csv.starts_with("# ride: slow\n# reference: fast\n"),
+            "{csv}"
+        );
+        assert!(
+            csv.contains("distance_km,ride_seconds,ride_speed_kmh,ride_elevation_m,"),
+            "{csv}"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn route_map_zooms_with_plus_and_minus() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mountain.gpx");
+        let points = crate::stream::read_track_points(&fs::read(path).unwrap()).unwrap();
+        let mut app = app_with_files(&["a"]);
+        app.file_list.files[0].activity.points = points;
+        app.file_list.state.select(Some(0));
+
+        let screen = text(&render(&mut app, 120, 40));
+        assert!(screen.contains("Route"), "{screen}");
+        assert!(screen.contains("+/- zoom"), "{screen}");
+        assert!(screen.contains(|c| ('\u{2801}'..='\u{28ff}').contains(&c)));
+
+        press(&mut app, KeyCode::Char('+'));
+        press(&mut app, KeyCode::Char('='));
+        assert!(text(&render(&mut app, 120, 40)).contains("Route ×4"));
+        for _ in 0..3 {
+            press(&mut app, KeyCode::Char('-'));
+        }
+        assert_eq!(app.map_zoom, 0);
+    }
+
+    #[test]
+    fn the_cursor_scrubs_the_selected_ride() {
+        let start = chrono::DateTime::parse_from_rfc3339("2024-06-01T07:00:00+02:00").unwrap();
+        // Every 100 m north, 20 s apart, climbing 5 m at each point.
+        let points: Vec<TrackPoint> = (0..=20)
+            .map(|i| TrackPoint {
+                lat: 45.0 + i as f64 * 100.0 / 111_195.08,
+                lon: 5.0,
+                elevation: Some(200.0 + i as f64 * 5.0),
+                time: Some(start + chrono::TimeDelta::seconds(i * 20)),
+                ..Default::default()
+            })
+            .collect();
+        let mut app = app_with_files(&["a"]);
+        app.file_list.files[0].activity.points = points;
+        app.file_list.state.select(Some(0));
+
+        press(&mut app, KeyCode::Char('p'));
+        assert_eq!(app.focus, Focus::Detail);
+        press(&mut app, KeyCode::Right);
+        app.handle_key_event(KeyEvent::new(KeyCode::Right, KeyModifiers::SHIFT));
+        let screen = text(&render(&mut app, 160, 40));
+        assert!(
+            screen.contains("Cursor: 1.10 km  07:03:40  255 m  5.0%  18.0 km/h"),
+            "{screen}"
+        );
+
+        // The cursor stops at the finish.
+        app.handle_key_event(KeyEvent::new(KeyCode::Right, KeyModifiers::SHIFT));
+        app.handle_key_event(KeyEvent::new(KeyCode::Right, KeyModifiers::SHIFT));
+        assert!((app.scrub.unwrap() - 2.0).abs() < 1e-6);
+
+        // `j` does not select another ride while scrubbing; Esc leaves.
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.scrub, None);
+        assert!(!text(&render(&mut app, 160, 40)).contains("Cursor:"));
+    }
+
+    #[test]
+    fn files_are_inserted_in_order_while_loading() {
+        let (sender, receiver) = mpsc::channel();
+        let mut app = App {
+            store: Store::new(store_dir(&["loading"])),
+            loading: Some(Loading {
+                receiver,
+                received: 0,
+                total: 4,
+                reload: false,
+            }),
+            ..Default::default()
+        };
+        let activity = |id: &str, path: &str| Activity {
+            id: id.parse().unwrap(),
+            name: id.to_string(),
+            path: PathBuf::from(path),
+            points: vec![TrackPoint::default()],
+            ..Default::default()
+        };
+
+        sender
+            .send(Ok(activity("2-0000000000000002", "b.gpx")))
+            .unwrap();
+        app.receive().unwrap();
+        assert_eq!(
+            app.selected().map(FileItem::name),
+            Some("2-0000000000000002")
+        );
+        let buf = render(&mut app, 100, 20);
+        assert!(text(&buf).contains("Loaded 1/4…"));
+
+        sender
+            .send(Ok(activity("1-0000000000000001", "a.gpx")))
+            .unwrap();
+        sender
+            .send(Ok(activity("2-0000000000000002", "a2.gpx")))
+            .unwrap();
+        app.receive().unwrap();
+        assert_eq!(names(&app), ["1-0000000000000001", "2-0000000000000002"]);
+        assert_eq!(
+            app.selected().map(FileItem::name),
+            Some("2-0000000000000002")
+        );
+        // The duplicate with the first path replaces the other one.
+        assert_eq!(
+            app.file_list.files[1].activity.path,
+            PathBuf::from("a2.gpx")
+        );
+        assert_eq!(app.duplicates.len(), 1);
+
+        sender.send(Err(anyhow::anyhow!("parsing c.gpx"))).unwrap();
+        drop(sender);
+        app.receive().unwrap();
+        assert!(app.loading.is_none());
+        assert_eq!(app.failures.len(), 1);
+        assert_eq!(app.status.as_deref(), Some("parsing c.gpx"));
+    }
+
+    #[test]
+    fn duplicate_paths_are_counted_in_the_footer() {
+        let mut app = app_with_files(&["a", "b"]);
+        app.duplicate_paths = vec![PathBuf::from("rides/./a.gpx"), PathBuf::from("link.gpx")];
+        let buf = render(&mut app, 160, 20);
+        assert!(text(&buf).contains("(2 duplicate paths skipped)"));
+    }
+
+    #[test]
+    fn range_subtotal_skips_filtered_rows() {
+        let mut app = app_with_files(&["a", "b", "c", "d"]);
+        for (i, file) in app.file_list.files.iter_mut().enumerate() {
+            file.activity.distance_km = 10.0 * (i + 1) as f64;
+        }
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char(']'));
+        press(&mut app, KeyCode::Char('k'));
+        press(&mut app, KeyCode::Char('k'));
+        press(&mut app, KeyCode::Char('['));
+        assert_eq!(
+            app.range_subtotal().as_deref(),
+            Some("3 rides 90.0 km ↑ 0m moving 0:00")
+        );
+
+        // A file gone since it was listed is not counted, as in the totals.
+        app.file_list.files[1].missing = true;
+        assert_eq!(
+            app.range_subtotal().as_deref(),
+            Some("2 rides 70.0 km ↑ 0m moving 0:00")
+        );
+
+        // Hiding a row inside the range leaves the bounds in place.
+        app.filter.text = "c".to_string();
+        app.refresh_list();
+        assert_eq!(
+            app.range_subtotal().as_deref(),
+            Some("1 ride 30.0 km ↑ 0m moving 0:00")
+        );
+        let buf = render(&mut app, 100, 20);
+        assert!(text(&buf).contains("Range: 1 ride 30.0 km"));
+
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.range_subtotal(), None);
+    }
+
+    #[test]
+    fn footer_draws_the_recent_weeks() {
+        let mut app = app_with_files(&["last week", "this week", "long ago"]);
+        let now = Local::now().fixed_offset();
+        for (file, (days_ago, km)) in
+            app.file_list
+                .files
+                .iter_mut()
+                .zip([(7, 20.0), (0, 40.0), (400, 80.0)])
+        {
+            file.activity.start = Some(now - chrono::Days::new(days_ago));
+            file.activity.distance_km = km;
+        }
+        app.refresh_list();
+        // 20 km then 40 km in the last two weeks, in the left and right
+        // columns of the last character.
+        let sparkline = format!("{}⣼", "\u{2800}".repeat(5));
+        let screen = text(&render(&mut app, 160, 30));
+        assert!(screen.contains(&sparkline), "{screen}");
+
+        app.ascii = true;
+        let screen = text(&render(&mut app, 160, 30));
+        let sparkline = format!("140.000km {}-# +", " ".repeat(10));
+        assert!(screen.contains(&sparkline), "{screen}");
+
+        // A single week ridden draws nothing.
+        app.filter.text = "this".to_string();
+        app.refresh_list();
+        let screen = text(&render(&mut app, 160, 30));
+        assert!(screen.contains("40.000km +"), "{screen}");
+    }
+
+    #[test]
+    fn tiny_terminals_get_a_message() {
+        let mut app = App::default();
+        // Must not panic, even if the message does not fit.
+        render(&mut app, 0, 0);
+        render(&mut app, 1, 1);
+
+        for (width, height) in [(60, 10), (79, 40), (120, 14)] {
+            let buf = render(&mut app, width, height);
+            assert!(
+                text(&buf).contains("terminal too small"),
+                "{width}x{height}"
+            );
+        }
+
+        let buf = render(&mut app, 80, 15);
+        assert!(text(&buf).contains("Activities"));
+    }
+
+    #[test]
+    fn detail_shows_the_end_of_rides_on_the_next_day() {
+        let mut app = app_with_files(&["midnight", "spring forward"]);
+        for (file, (start, end)) in app.file_list.files.iter_mut().zip([
+            ("2024-06-08T23:40:00Z", "2024-06-09T01:10:00Z"),
+            ("2024-03-31T01:30:00+01:00", "2024-03-31T04:00:00+02:00"),
+        ]) {
+            file.activity.start = Some(DateTime::parse_from_rfc3339(start).unwrap());
+            file.activity.end = Some(DateTime::parse_from_rfc3339(end).unwrap());
+        }
+
+        app.file_list.state.select(Some(0));
+        let screen = text(&render(&mut app, 140, 40));
+        assert!(
+            screen.contains("time: 23:40 to 01:10 +1 day, 1:30:00 elapsed"),
+            "{screen}"
+        );
+
+        app.file_list.state.select(Some(1));
+        let screen = text(&render(&mut app, 140, 40));
+        assert!(
+            screen.contains("time: 01:30 (UTC+01:00) to 04:00 (UTC+02:00), 1:30:00 elapsed"),
+            "{screen}"
+        );
+    }
+
+    #[test]
+    fn devices_in_detail_and_statistics() {
+        let mut app = app_with_files(&["a", "b", "c"]);
+        for (file, creator) in app.file_list.files.iter_mut().zip([
+            Some("Garmin Edge 530"),
+            Some("Garmin Edge 530"),
+            None,
+        ]) {
+            file.activity.creator = creator.map(str::to_string);
+        }
+
+        let screen = text(&render(&mut app, 140, 40));
+        assert!(screen.contains("device: Garmin Edge 530"), "{screen}");
+
+        app.show_stats = true;
+        let screen = text(&render(&mut app, 140, 40));
+        assert!(screen.contains("Garmin Edge 530: 2 rides"), "{screen}");
+        assert!(screen.contains("unknown: 1 ride "));
+
+        app.filter.creator = Some("UNKNOWN".to_string());
+        app.refresh_list();
+        assert_eq!(app.file_list.visible, [2]);
+    }
+
+    #[test]
+    fn latest_keeps_the_most_recent_matching_rides() {
+        let mut app = app_with_files(&["undated ride", "old ride", "gravel", "new ride"]);
+        let start = chrono::DateTime::parse_from_rfc3339("2024-06-01T08:00:00Z").unwrap();
+        for (i, file) in app.file_list.files.iter_mut().enumerate().skip(1) {
+            file.activity.start = Some(start + chrono::TimeDelta::days(i as i64));
+        }
+        app.filter.latest = Some(2);
+        app.refresh_list();
+        assert_eq!(app.file_list.visible, [2, 3]);
+        let screen = text(&render(&mut app, 120, 20));
+        assert!(screen.contains("(showing latest 2 of 4)"), "{screen}");
+
+        // The text filter applies first.
+        app.filter.text = "ride".to_string();
+        app.refresh_list();
+        assert_eq!(app.file_list.visible, [1, 3]);
+        app.filter.include_undated = true;
+        app.refresh_list();
+        assert_eq!(app.file_list.visible, [0, 1, 3]);
+    }
+
+    #[test]
+    fn calendar_shows_the_day_under_the_cursor() {
+        let mut app = app_with_files(&["morning", "evening", "yesterday"]);
+        let now = Local::now().fixed_offset();
+        for (file, (days_ago, distance_km, elevation_gain)) in
+            app.file_list
+                .files
+                .iter_mut()
+                .zip([(0, 10.0, 50.0), (0, 30.0, 150.0), (1, 5.0, 0.0)])
+        {
+            file.activity.start = Some(now - chrono::TimeDelta::days(days_ago));
+            file.activity.distance_km = distance_km;
+            file.activity.elevation_gain = elevation_gain;
+        }
+        app.refresh_list();
+
+        press(&mut app, KeyCode::Char('s'));
+        let screen = text(&render(&mut app, 100, 50));
+        assert!(screen.contains("Rides by start time"), "{screen}");
+        assert!(screen.contains("    0  2  4  6  8  10 12"), "{screen}");
+        assert!(screen.contains("Sun "), "{screen}");
+        press(&mut app, KeyCode::Char('c'));
+        assert_eq!(app.calendar, Some(now.date_naive()));
+        let screen = text(&render(&mut app, 100, 30));
+        assert!(screen.contains("2 rides / 40.0 km / 200 m"), "{screen}");
+        assert!(screen.contains("■ > 31 km"), "{screen}");
+
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Up);
+        let screen = text(&render(&mut app, 100, 30));
+        assert!(screen.contains("1 ride / 5.0 km / 0 m"), "{screen}");
+
+        // `c` closes the calendar instead of toggling the commute tag.
+        press(&mut app, KeyCode::Char('c'));
+        assert_eq!(app.calendar, None);
+        assert!(app.show_stats);
+        assert!(app.history.undo().is_none());
+    }
+
+    #[test]
+    fn every_theme_styles_the_same_screen() {
+        let mut screens = vec![];
+        for name in [ThemeName::Dark, ThemeName::Light, ThemeName::HighContrast] {
+            let theme = Theme::new(name);
+            let mut app = app_with_files(&["themed"]);
+            app.theme = theme;
+
+            let buf = render(&mut app, 100, 30);
+
+            let activities = style_of(&buf, "Activities");
+            assert_eq!(activities.fg, theme.focused_title.fg, "{name:?}");
+            assert!(activities.add_modifier.contains(Modifier::BOLD));
+            assert_eq!(style_of(&buf, "0.000km ↑").fg, theme.value.fg, "{name:?}");
+            assert_eq!(style_of(&buf, "r rename").fg, theme.muted.fg, "{name:?}");
+            screens.push(text(&buf));
+        }
+        // Themes change the colors, never the layout.
+        assert!(screens.iter().all(|screen| *screen == screens[0]));
+        assert_ne!(
+            Theme::new(ThemeName::Dark).text,
+            Theme::new(ThemeName::Light).text
+        );
+    }
+
+    #[test]
+    fn no_color_resets_every_cell() {
+        let mut app = App {
+            color: false,
+            show_stats: true,
+            ..Default::default()
+        };
+
+        let buf = render(&mut app, 100, 30);
+
+        assert!(
+            buf.content
+                .iter()
+                .all(|cell| cell.fg == Color::Reset && cell.bg == Color::Reset)
+        );
+    }
+
+    #[test]
+    fn rows_fade_with_age() {
+        let mut app = app_with_files(&["Recent", "Old"]);
+        let start = |day| {
+            DateTime::parse_from_rfc3339(&format!("2024-07-{day:02}T08:00:00+02:00")).unwrap()
+        };
+        app.file_list.files[0].activity.start = Some(start(3));
+        app.file_list.files[1].activity.start = Some(start(1));
+        app.refresh_list();
+        // A Friday, with the older ride on the Monday before.
+        app.today = NaiveDate::from_ymd_opt(2024, 7, 5).unwrap();
+
+        let buf = render(&mut app, 100, 30);
+        assert_eq!(style_of(&buf, "Recent").fg, app.theme.recency[0].fg);
+        assert_eq!(style_of(&buf, "Old").fg, app.theme.recency[0].fg);
+
+        // The Monday after, the week changed but not the month.
+        app.today = NaiveDate::from_ymd_opt(2024, 7, 8).unwrap();
+        let buf = render(&mut app, 100, 30);
+        assert_eq!(style_of(&buf, "Recent").fg, app.theme.recency[1].fg);
+        app.today = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let buf = render(&mut app, 100, 30);
+        assert_eq!(style_of(&buf, "Old").fg, app.theme.recency[3].fg);
+
+        app.recency_colors = false;
+        let buf = render(&mut app, 100, 30);
+        assert_eq!(style_of(&buf, "Recent").fg, app.theme.text.fg);
+    }
+
+    #[test]
+    fn tuning_recomputes_and_saves() {
+        let dir = store_dir(&["tuning"]);
+        let mut app = App {
+            store: Store::new(dir.clone()),
+            config_path: dir.join("config.toml"),
+            ..Default::default()
+        };
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&app.config_path, "ftp = 250\n\n[tuning]\njitter_m = 9.0\n").unwrap();
+        // 100 m north, 1 m back and 100 m north again, with a small bump.
+        let points = [
+            (45.0, 100.0),
+            (45.0009, 100.5),
+            (45.00089, 100.2),
+            (45.0018, 102.0),
+        ]
+        .map(|(lat, elevation)| TrackPoint {
+            lat,
+            lon: 5.0,
+            elevation: Some(elevation),
+            ..Default::default()
+        });
+        let mut activity = Activity {
+            id: "1-0000000000000001".parse().unwrap(),
+            points: points.to_vec(),
+            ..Default::default()
+        };
+        activity.retune(Tuning::default());
+        let (distance_km, elevation_gain) = (activity.distance_km, activity.elevation_gain);
+        app.insert(activity).unwrap();
+        app.refresh_list();
+
+        press(&mut app, KeyCode::Char(','));
+        press(&mut app, KeyCode::Char('+'));
+        assert_eq!(app.tuning.elevation_threshold_m, 1.0);
+        assert!((app.totals(true).1 - (elevation_gain - 0.3)).abs() < 1e-9);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Char('+'));
+        press(&mut app, KeyCode::Char('+'));
+        assert!(app.totals(true).0 < distance_km - 0.001);
+
+        let screen = text(&render(&mut app, 140, 30));
+        assert!(
+            screen.contains("tuned: elevation threshold 1 m, jitter distance 2 m"),
+            "{screen}"
+        );
+        assert!(screen.contains("default 0 m"));
+        // The ride as recorded stays next to the tuned one.
+        assert!(screen.contains("What if, selected ride"), "{screen}");
+        assert!(screen.contains("as recorded"), "{screen}");
+        let (_, raw) = app.raw_metrics.unwrap();
+        assert!((raw.elevation_gain - elevation_gain).abs() < 1e-9);
+        assert!((raw.distance_km - distance_km).abs() < 1e-9);
+
+        press(&mut app, KeyCode::Char('s'));
+        assert!(
+            app.status
+                .as_deref()
+                .unwrap()
+                .starts_with("tuning saved to")
+        );
+        let config = Config::load(&app.config_path).unwrap();
+        assert_eq!(config.ftp, Some(250.0));
+        assert_eq!(config.tuning, app.tuning);
+
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('-'));
+        assert_eq!(app.settings, None);
+        assert_eq!(app.tuning.jitter_m, 2.0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Call `step` until `done`, for the work of the background threads.
+    fn wait_until(app: &mut App, step: fn(&mut App), done: fn(&App) -> bool) {
+        for _ in 0..1000 {
+            step(app);
+            if done(app) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("timed out");
+    }
+
+    #[test]
+    fn descents_are_charted_and_compared() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let mut app = App {
+            store: Store::new(store_dir(&["descents"])),
+            ..Default::default()
+        };
+        for name in ["alpine_descent.gpx", "mountain.gpx"] {
+            let activity = load_activity(&fixtures.join(name), &LoadOptions::default()).unwrap();
+            app.insert(activity).unwrap();
+        }
+        app.refresh_list();
+        let position = app
+            .file_list
+            .rows
+            .iter()
+            .position(|row| matches!(row, Row::File { index, .. } if app.file_list.files[*index].descending.is_some()))
+            .unwrap();
+        app.file_list.state.select(Some(position));
+
+        let screen = text(&render(&mut app, 160, 40));
+        assert!(
+            screen.contains("descending: 54.0 km/h on 1 descent"),
+            "{screen}"
+        );
+        assert!(screen.contains("Activity Elevation"), "{screen}");
+        press(&mut app, KeyCode::Char('e'));
+        let screen = text(&render(&mut app, 160, 40));
+        assert!(screen.contains("Rate of Climb"), "{screen}");
+        assert!(!screen.contains("Activity Elevation"), "{screen}");
+
+        app.show_stats = true;
+        let screen = text(&render(&mut app, 160, 60));
+        assert!(
+            screen.contains("Descending, last 2 rides: 15.3 km/h"),
+            "{screen}"
+        );
+    }
+
+    #[test]
+    fn detail_sections_fold_and_scroll() {
+        let mut app = app_with_files(&["a", "b"]);
+        for file in &mut app.file_list.files {
+            file.sidecar.notes = Some(format!("notes of {}", file.activity.name));
+        }
+        app.refresh_list();
+        let screen = text(&render(&mut app, 120, 40));
+        assert!(screen.contains("▾ Summary"), "{screen}");
+        assert!(screen.contains("name source:"), "{screen}");
+        assert!(screen.contains("▾ Notes"), "{screen}");
+        assert!(screen.contains("notes of a"), "{screen}");
+
+        // Enter folds the section at the top of the detail once focused.
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::Enter);
+        let screen = text(&render(&mut app, 120, 40));
+        assert!(screen.contains("▸ Summary"), "{screen}");
+        assert!(!screen.contains("name source:"), "{screen}");
+        assert_eq!(app.file_list.state.selected(), Some(0));
+
+        // The next section is at the top one line down.
+        press(&mut app, KeyCode::Char('j'));
+        render(&mut app, 120, 40);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.detail_scroll, 1);
+        let screen = text(&render(&mut app, 120, 40));
+        assert!(screen.contains("▸ Terrain"), "{screen}");
+        assert!(!screen.contains("▸ Summary"), "{screen}");
+        assert_eq!(app.session().folded_sections, ["summary", "terrain"]);
+
+        // Scrolling stops at the last line, and the folds stay for the next
+        // ride.
+        for _ in 0..50 {
+            press(&mut app, KeyCode::Char('j'));
+        }
+        render(&mut app, 120, 40);
+        assert_eq!(usize::from(app.detail_scroll), app.detail_layout.height - 1);
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::Char('j'));
+        let screen = text(&render(&mut app, 120, 40));
+        assert!(screen.contains("▸ Summary"), "{screen}");
+        assert!(screen.contains("notes of b"), "{screen}");
+    }
+
+    #[test]
+    fn ferries_are_struck_through_when_left_out() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let mut app = App {
+            store: Store::new(store_dir(&["transport"])),
+            exclude_transport: true,
+            ..Default::default()
+        };
+        let activity = load_activity(&fixtures.join("ferry.gpx"), &LoadOptions::default()).unwrap();
+        app.insert(activity).unwrap();
+        app.refresh_list();
+        assert!((app.file_list.files[0].activity.distance_km - 7.0).abs() < 0.01);
+
+        let buf = render(&mut app, 160, 50);
+        let screen = text(&buf);
+        assert!(screen.contains("▾ Ferries and trains"), "{screen}");
+        let ferry = "10.0 km at 30.0 km/h from km 4.0 in 20:00, without pedaling";
+        assert!(screen.contains(ferry), "{screen}");
+        assert!(
+            style_of(&buf, ferry)
+                .add_modifier
+                .contains(Modifier::CROSSED_OUT)
+        );
+        assert!(
+            screen.contains("left out of the distance and time"),
+            "{screen}"
+        );
+
+        // Counted by default.
+        let mut app = App {
+            store: Store::new(store_dir(&["transport"])),
+            ..Default::default()
+        };
+        let activity = load_activity(&fixtures.join("ferry.gpx"), &LoadOptions::default()).unwrap();
+        app.insert(activity).unwrap();
+        app.refresh_list();
+        assert!((app.file_list.files[0].activity.distance_km - 17.0).abs() < 0.01);
+        let buf = render(&mut app, 160, 50);
+        assert!(
+            !style_of(&buf, ferry)
+                .add_modifier
+                .contains(Modifier::CROSSED_OUT)
+        );
+    }
+
+    #[test]
+    fn out_and_backs_show_the_wind() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let mut app = App {
+            store: Store::new(store_dir(&["wind"])),
+            ..Default::default()
+        };
+        let activity =
+            load_activity(&fixtures.join("out_and_back.gpx"), &LoadOptions::default()).unwrap();
+        app.insert(activity).unwrap();
+        app.refresh_list();
+
+        let screen = text(&render(&mut app, 160, 50));
+        assert!(screen.contains("▾ Wind"), "{screen}");
+        assert!(
+            screen.contains("tailwind on the way out: 2.5 km/h"),
+            "{screen}"
+        );
+        assert!(
+            screen.contains("speed without the wind: 25.0 km/h"),
+            "{screen}"
+        );
+        assert!(
+            screen.contains("estimated from out-and-back symmetry, on 10.0 km"),
+            "{screen}"
+        );
+    }
+
+    #[test]
+    fn hidden_view_restores_what_can_be_shown() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let mut app = App {
+            store: Store::new(store_dir(&["hidden"])),
+            ..Default::default()
+        };
+        for name in ["flat.gpx", "mountain.gpx", "flat.gpx"] {
+            let activity = load_activity(&fixtures.join(name), &LoadOptions::default()).unwrap();
+            app.insert(activity).unwrap();
+        }
+        app.insert(Activity {
+            id: "3-0000000000000003".parse().unwrap(),
+            name: "No points".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        app.failures
+            .push("parsing corrupt.gpx: unexpected end".to_string());
+        app.refresh_list();
+        assert_eq!(app.file_list.visible.len(), 2);
+
+        app.file_list.select(app.file_list.files[0].activity.id);
+        let hidden = app.selected().unwrap().activity.id;
+        press(&mut app, KeyCode::Char('h'));
+        assert_eq!(app.file_list.visible.len(), 1);
+        assert_eq!(app.summary.rides(), 1);
+
+        press(&mut app, KeyCode::Char('H'));
+        let screen = text(&render(&mut app, 160, 40));
+        assert!(screen.contains("Hidden: 4"), "{screen}");
+        for group in [
+            "Duplicates (1)",
+            "Without points (1)",
+            "Hidden by hand (1)",
+            "Not read (1)",
+        ] {
+            assert!(screen.contains(group), "{group}: {screen}");
+        }
+        assert!(screen.find("Duplicates").unwrap() < screen.find("Not read").unwrap());
+
+        // The duplicate cannot be shown.
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(
+            app.status.as_deref(),
+            Some("the file holds a ride already listed")
+        );
+        // The ride without points, then the ride hidden by hand.
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.file_list.visible.len(), 2);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.file_list.visible.len(), 3);
+        assert_eq!(app.summary.rides(), 3);
+        assert!(app.file_list.select(hidden));
+        assert_eq!(app.hidden_view, Some(1));
+
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.hidden_view, None);
+        press(&mut app, KeyCode::Char('u'));
+        assert_eq!(app.file_list.visible.len(), 2);
+    }
+
+    #[test]
+    fn quality_dashboard_lists_the_worst_rides_first() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let mut app = App {
+            store: Store::new(store_dir(&["quality"])),
+            ..Default::default()
+        };
+        for name in ["barometric.gpx", "tunnel.gpx", "jitter.gpx"] {
+            let activity = load_activity(&fixtures.join(name), &LoadOptions::default()).unwrap();
+            app.insert(activity).unwrap();
+        }
+        app.refresh_list();
+
+        // The list shows a bar as high as the score.
+        let screen = text(&render(&mut app, 160, 30));
+        assert!(screen.contains("█ "), "{screen}");
+        assert!(screen.contains("▆ "), "{screen}");
+
+        press(&mut app, KeyCode::Char('Q'));
+        let screen = text(&render(&mut app, 160, 30));
+        assert!(
+            screen.contains("Data quality: 3 rides, 88/100 on average"),
+            "{screen}"
+        );
+        let row_of = |score: &str| screen.find(score).unwrap();
+        assert!(row_of("▆  80  ") < row_of("▆  85  "), "{screen}");
+        assert!(row_of("▆  85  ") < row_of("█ 100  "), "{screen}");
+        assert!(screen.contains("GPS jitter −20"), "{screen}");
+        assert!(screen.contains("gaps −15"), "{screen}");
+
+        // Enter shows the data quality of the ride under the cursor.
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.quality_view, None);
+        assert_eq!(app.focus, Focus::Detail);
+        assert_eq!(app.selected().unwrap().name(), "Tunnel ride");
+        let screen = text(&render(&mut app, 160, 30));
+        let start = app
+            .detail_layout
+            .starts
+            .iter()
+            .find(|(key, _)| *key == "quality")
+            .map(|&(_, start)| start);
+        assert_eq!(start, Some(usize::from(app.detail_scroll)));
+        assert!(screen.contains("score: 85/100 (gaps −15)"), "{screen}");
+        assert!(screen.contains("gaps: 0.17 km without points"), "{screen}");
+    }
+
+    #[test]
+    fn reference_climbs_check_the_elevation_gain() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let mut app = App {
+            store: Store::new(store_dir(&["reference_climbs"])),
+            reference_climbs: load_reference_climbs(&fixtures.join("climbs.toml")).unwrap(),
+            ..Default::default()
+        };
+        let activity =
+            load_activity(&fixtures.join("mountain.gpx"), &LoadOptions::default()).unwrap();
+        app.insert(activity).unwrap();
+        app.refresh_list();
+
+        let screen = text(&render(&mut app, 160, 50));
+        assert!(screen.contains("▾ Reference climbs"), "{screen}");
+        assert!(
+            screen.contains("Col du Test: measured 280 m vs official 282 m (-0.7%)"),
+            "{screen}"
+        );
+        assert!(!screen.contains("old road"), "{screen}");
+    }
+
+    #[test]
+    fn files_are_read_again_from_disk() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let dir = store_dir(&["reload"]);
+        let rides = dir.join("rides");
+        fs::create_dir_all(&rides).unwrap();
+        let (ride, broken) = (rides.join("ride.gpx"), rides.join("broken.gpx"));
+        fs::copy(fixtures.join("mountain.gpx"), &ride).unwrap();
+        fs::copy(fixtures.join("corrupt.gpx"), &broken).unwrap();
+        let mut app = App {
+            store: Store::new(dir.clone()),
+            sources: vec![rides.join("*.gpx")],
+            ..Default::default()
+        };
+        app.insert(load_activity(&ride, &LoadOptions::default()).unwrap())
+            .unwrap();
+        app.refresh_list();
+        app.file_list.files[0].sidecar.tags = vec!["alps".to_string()];
+        press(&mut app, KeyCode::Char(' '));
+        app.file_list.state.select(Some(0));
+
+        // Edited into another activity, with another id.
+        fs::copy(fixtures.join("flat.gpx"), &ride).unwrap();
+        press(&mut app, KeyCode::Char('R'));
+        let file = app.selected().unwrap();
+        assert_eq!(file.activity.name, "Flat ride");
+        assert_eq!(file.sidecar.tags, ["alps"]);
+        assert!(app.marked.contains(&file.activity.id));
+        assert_eq!(app.marked.len(), 1);
+        assert_eq!(app.status.as_deref(), Some("Flat ride read again"));
+
+        // The broken file is fixed and the ride deleted.
+        fs::copy(fixtures.join("mountain.gpx"), &broken).unwrap();
+        fs::remove_file(&ride).unwrap();
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        wait_until(
+            &mut app,
+            |app| app.receive().unwrap(),
+            |app| app.loading.is_none(),
+        );
+        let files: Vec<(&str, bool)> = app
+            .file_list
+            .files
+            .iter()
+            .map(|file| (file.name(), file.missing))
+            .collect();
+        assert_eq!(files.len(), 2);
+        assert!(files.contains(&("Flat ride", true)), "{files:?}");
+        assert!(files.contains(&("Col du Test", false)), "{files:?}");
+        let mountain = app
+            .file_list
+            .files
+            .iter()
+            .find(|file| !file.missing)
+            .unwrap();
+        assert_eq!(app.totals(true).0, mountain.activity.distance_km);
+        assert!(app.failures.is_empty());
+
+        let missing = app
+            .file_list
+            .rows
+            .iter()
+            .position(
+                |row| matches!(row, Row::File { index, .. } if app.file_list.files[*index].missing),
+            )
+            .unwrap();
+        app.file_list.state.select(Some(missing));
+        let screen = text(&render(&mut app, 160, 40));
+        assert!(screen.contains("Flat ride [missing]"), "{screen}");
+        assert!(
+            screen.contains("ride.gpx is gone, as last read:"),
+            "{screen}"
+        );
+        press(&mut app, KeyCode::Char('R'));
+        assert!(app.selected().unwrap().missing);
+        assert!(app.status.as_deref().unwrap().ends_with("ride.gpx is gone"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn points_are_read_again_for_the_detail() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let mut app = App {
+            store: Store::new(store_dir(&["details"])),
+            details: Lru::new(1),
+            ..Default::default()
+        };
+        for name in ["mountain.gpx", "intervals.gpx"] {
+            let activity = load_activity(&fixtures.join(name), &LoadOptions::default()).unwrap();
+            app.insert(activity).unwrap();
+        }
+        app.refresh_list();
+        app.finish_loading();
+        app.file_list.state.select(Some(0));
+        let file = &app.file_list.files[0];
+        let (moving, gain) = (file.activity.moving_seconds(), file.activity.elevation_gain);
+        assert!(file.released && file.activity.points.is_empty());
+        assert!(moving.is_some());
+        // Found before the points were released, not on each redraw.
+        assert!(
+            app.file_list
+                .files
+                .iter()
+                .any(|file| file.released && file.intervals.is_some())
+        );
+        assert_eq!(app.new_roads.len(), 2);
+        // Exploration cells are kept along with the new roads.
+        app.show_stats = true;
+        let screen = text(&render(&mut app, 160, 60));
+        assert!(screen.contains("Exploration: 2024 "), "{screen}");
+        app.show_stats = false;
+
+        assert!(text(&render(&mut app, 120, 40)).contains("loading…"));
+        wait_until(&mut app, App::load_detail, |app| {
+            app.points(app.selected().unwrap()).is_some()
+        });
+        let file = app.selected().unwrap();
+        assert!(!app.points(file).unwrap().is_empty());
+        assert!(!app.elevation_profile(file).unwrap().is_empty());
+        assert!(!text(&render(&mut app, 120, 40)).contains("loading…"));
+
+        // Viewing the other ride evicts the first one from the cache.
+        press(&mut app, KeyCode::Char('j'));
+        wait_until(&mut app, App::load_detail, |app| {
+            app.points(app.selected().unwrap()).is_some()
+        });
+        let first = app.file_list.files[0].activity.id;
+        assert!(app.details.peek(&first).is_none());
+
+        // A tuning change reads the files again, and releases them again.
+        press(&mut app, KeyCode::Char(','));
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        for _ in 0..100 {
+            press(&mut app, KeyCode::Char('+'));
+        }
+        assert!(
+            app.details
+                .peek(&app.file_list.files[1].activity.id)
+                .is_none()
+        );
+        wait_until(
+            &mut app,
+            |app| app.receive().unwrap(),
+            |app| app.loading.is_none(),
+        );
+        let file = &app.file_list.files[0];
+        assert!(file.released && file.activity.points.is_empty());
+        assert_eq!(file.activity.tuning.moving_min_speed, 10.5);
+        assert_eq!(file.activity.elevation_gain, gain);
+        assert!(file.activity.moving_seconds() < moving);
+    }
+
+    /// A ride on `date` climbing 1 km at 6% at `climb_kmh`, after 500 m of
+    /// flat.
+    fn climbing_ride(id: &str, date: &str, climb_kmh: f64) -> Activity {
+        let start = DateTime::parse_from_rfc3339(date).unwrap();
+        let mut points = vec![];
+        let mut seconds = 0.0;
+        for step in 0..=300 {
+            let position = step as f64 * 5.0;
+            let climbing = position > 500.0;
+            points.push(TrackPoint {
+                lat: 45.0 + position / 111_195.08,
+                lon: 5.0,
+                elevation: Some(200.0 + (position - 500.0).max(0.0) * 0.06),
+                time: Some(start + chrono::Duration::milliseconds((seconds * 1_000.0) as i64)),
+                ..Default::default()
+            });
+            seconds += 5.0 / (if climbing { climb_kmh } else { 30.0 } / 3.6);
+        }
+        Activity {
+            id: id.parse().unwrap(),
+            name: id.to_string(),
+            start: Some(start),
+            points,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn climb_leaderboard_and_new_record() {
+        let mut app = App {
+            store: Store::new(store_dir(&["climbs"])),
+            ..Default::default()
+        };
+        for (id, date, kmh) in [
+            ("1-0000000000000001", "2024-05-01T08:00:00Z", 10.0),
+            ("3-0000000000000003", "2024-07-01T08:00:00Z", 14.0),
+            ("2-0000000000000002", "2024-06-01T08:00:00Z", 12.0),
+        ] {
+            app.insert(climbing_ride(id, date, kmh)).unwrap();
+        }
+        app.refresh_list();
+        app.group_climbs();
+
+        assert_eq!(app.climb_groups.len(), 1);
+        let status = app.status.clone().unwrap();
+        assert!(
+            status.starts_with("new PR on 1.0 km at 6.0%: 4:"),
+            "{status}"
+        );
+
+        press(&mut app, KeyCode::Char('l'));
+        let screen = text(&render(&mut app, 140, 40));
+        assert!(screen.contains("climbed 3 times"), "{screen}");
+        assert!(screen.contains("1. 01-07-2024"));
+        assert!(screen.contains("3. 01-05-2024"));
+        assert!(screen.contains("◀ this ride"));
+    }
+
+    #[cfg(feature = "borders")]
+    #[test]
+    fn rides_are_tagged_with_the_countries_crossed() {
+        let mut app = App {
+            store: Store::new(store_dir(&["borders"])),
+            ..Default::default()
+        };
+        app.insert(climbing_ride(
+            "1-0000000000000001",
+            "2024-05-01T08:00:00Z",
+            12.0,
+        ))
+        .unwrap();
+        // South through Le Perthus, 10 km in France then 8.8 km in Spain.
+        let step = 100.0 / 111_195.08;
+        let mut crossing = climbing_ride("2-0000000000000002", "2024-06-01T08:00:00Z", 12.0);
+        crossing.points = (0..=188)
+            .map(|i| TrackPoint {
+                lat: 42.55 - i as f64 * step,
+                lon: 2.86,
+                ..Default::default()
+            })
+            .collect();
+        app.insert(crossing).unwrap();
+        app.refresh_list();
+
+        let tags: Vec<Vec<&str>> = app.file_list.files.iter().map(FileItem::tags).collect();
+        assert_eq!(tags, [vec!["FR"], vec!["FR", "ES"]]);
+        app.file_list.state.select(Some(1));
+        let screen = text(&render(&mut app, 160, 40));
+        assert!(
+            screen.contains("countries: France (FR) 10.0 km, Spain (ES) 8.8 km"),
+            "{screen}"
+        );
+
+        app.show_stats = true;
+        let screen = text(&render(&mut app, 160, 60));
+        assert!(screen.contains("France (FR): 2 rides / "), "{screen}");
+        assert!(screen.contains("Spain (ES): 1 ride / 9 km"), "{screen}");
+    }
+
+    /// Terminal sizes the screens are checked at: the common default, a
+    /// large window and the smallest one drawn.
+    const SNAPSHOT_SIZES: [(u16, u16); 3] = [(80, 24), (120, 40), (MIN_WIDTH, MIN_HEIGHT)];
+
+    /// Keys pressed one after the other, with the name of the screen they
+    /// lead to. The list has no sort key; the statistics stand in for it.
+    const SNAPSHOT_STEPS: &[(&str, &[KeyCode])] = &[
+        ("list", &[]),
+        ("navigate", &[KeyCode::Char('j'), KeyCode::Char('j')]),
+        ("detail", &[KeyCode::Tab, KeyCode::Char('j')]),
+        ("statistics", &[KeyCode::Tab, KeyCode::Char('s')]),
+        ("help", &[KeyCode::Char('s'), KeyCode::Char('?')]),
+    ];
+
+    /// Events of a test, then `q` to leave.
+    struct Script(Vec<KeyCode>);
+
+    impl EventSource for Script {
+        fn next_event(&mut self, _timeout: Duration) -> io::Result<Option<Event>> {
+            let code = if self.0.is_empty() {
+                KeyCode::Char('q')
+            } else {
+                self.0.remove(0)
+            };
+            Ok(Some(Event::Key(KeyEvent::from(code))))
+        }
+    }
+
+    #[test]
+    fn rides_in_two_formats_are_listed_once() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let load = |name: &str| load_activity(&fixtures.join(name), &LoadOptions::default());
+        for (first, second) in [
+            ("paired_ride.gpx", "paired_ride.fit"),
+            ("paired_ride.fit", "paired_ride.gpx"),
+        ] {
+            let mut app = App {
+                store: Store::new(store_dir(&["alternates", first])),
+                ..Default::default()
+            };
+            app.insert(load(first).unwrap()).unwrap();
+            app.insert(load(second).unwrap()).unwrap();
+            app.refresh_list();
+
+            assert_eq!(app.file_list.files.len(), 1);
+            assert!(app.duplicates.is_empty());
+            let screen = text(&render(&mut app, 140, 40));
+            assert!(
+                screen.contains("also as: paired_ride.gpx (kept: FIT)"),
+                "{screen}"
+            );
+        }
+    }
+
+    #[test]
+    fn the_status_tells_what_changed_since_the_last_run() {
+        let mut app = app_with_files(&["a", "b", "c"]);
+        for file in &mut app.file_list.files {
+            file.activity.distance_km = 40.0;
+        }
+        app.runs = vec![RunTotals {
+            at: "2024-06-01T08:00:00+02:00".to_string(),
+            rides: 1,
+            distance_km: 10.0,
+            elevation_m: 0.0,
+        }];
+        let now = DateTime::parse_from_rfc3339("2024-06-02T08:00:00+02:00").unwrap();
+        app.record_run(now);
+        assert_eq!(
+            app.status.as_deref(),
+            Some("since last run: +2 rides, +110 km")
+        );
+        assert_eq!(app.runs.len(), 2);
+
+        // Files deleted by a sync since.
+        app.file_list.files.truncate(1);
+        app.status = None;
+        app.config.run_history = Some(2);
+        app.record_run(now);
+        assert_eq!(
+            app.status.as_deref(),
+            Some("since last run: -2 rides, -80 km; were files deleted?")
+        );
+        assert_eq!(app.runs.len(), 2);
+        assert_eq!(app.session().runs, app.runs);
+
+        // Nothing to tell.
+        app.status = None;
+        app.record_run(now);
+        assert_eq!(app.status, None);
+    }
+
+    /// An app loading a few fixtures the way the interface does, in the
+    /// background.
+    fn loading_app(name: &str) -> App {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let names = ["flat.gpx", "mountain.gpx", "gravel.gpx", "intervals.gpx"];
+        let (sender, receiver) = mpsc::channel();
+        for name in names {
+            let _ = sender.send(load_activity(&fixtures.join(name), &LoadOptions::default()));
+        }
+        let dir = store_dir(&["snapshots", name]);
+        let _ = fs::remove_dir_all(&dir);
+        App {
+            store: Store::new(dir),
+            loading: Some(Loading {
+                receiver,
+                received: 0,
+                total: names.len(),
+                reload: false,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Draw until the points of the selected ride are read, so that the
+    /// screen does not depend on how fast the disk is.
+    fn settle(app: &mut App, terminal: &mut Terminal<TestBackend>) {
+        loop {
+            app.tick(terminal, &mut io::sink()).unwrap();
+            if app.loading.is_none()
+                && app.detail_loading.is_none()
+                && app.model_distances_computing.is_none()
+            {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        app.tick(terminal, &mut io::sink()).unwrap();
+    }
+
+    /// Compare the screen with `tests/snapshots/{name}.txt`, writing it
+    /// instead when missing or with `UPDATE_SNAPSHOTS` set.
+    fn assert_snapshot(name: &str, buf: &Buffer) {
+        let screen: String = (0..buf.area.height)
+            .map(|y| {
+                let row: String = (0..buf.area.width).map(|x| buf[(x, y)].symbol()).collect();
+                format!("{}\n", row.trim_end())
+            })
+            .collect();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/snapshots")
+            .join(format!("{name}.txt"));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &screen).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!("{name} has no snapshot, run with UPDATE_SNAPSHOTS=1 to write it:\n{screen}")
+        });
+        assert!(
+            screen == expected,
+            "{name} changed, run with UPDATE_SNAPSHOTS=1 if expected:\n{screen}\nwas:\n{expected}"
+        );
+    }
+
+    #[test]
+    fn long_names_are_cut_in_the_middle_at_both_widths() {
+        let mut app = app_with_files(&[
+            "Morning loop through the vineyards and back over the Col de la Croix de Fer",
+            "Short spin",
+        ]);
+        for (i, file) in app.file_list.files.iter_mut().enumerate() {
+            file.activity.start = Some(
+                DateTime::parse_from_rfc3339(&format!("2024-06-0{}T08:00:00Z", i + 1)).unwrap(),
+            );
+            file.activity.distance_km = 42.5 + i as f64;
+            file.activity.elevation_gain = 1_250.0;
+        }
+        app.refresh_list();
+
+        // Two lines a ride, the highlight moving by ride.
+        let buf = render(&mut app, 80, 24);
+        assert_snapshot("long_names_80x24", &buf);
+        press(&mut app, KeyCode::Char('j'));
+        let buf = render(&mut app, 80, 24);
+        let row = |y| -> String { (0..40).map(|x| buf[(x, y)].symbol()).collect() };
+        assert!(row(3).starts_with(">> "), "{}", row(3));
+        assert!(
+            row(4).contains("02-06-2024 · 43.5 km · 1250 m"),
+            "{}",
+            row(4)
+        );
+
+        let buf = render(&mut app, 120, 40);
+        assert_snapshot("long_names_120x40", &buf);
+    }
+
+    #[test]
+    fn names_are_cut_in_the_middle() {
+        assert_eq!(ellipsize_middle("Col du Test", 20, "…"), "Col du Test");
+        assert_eq!(ellipsize_middle("Col du Galibier", 9, "…"), "Col…bier");
+        assert_eq!(ellipsize_middle("Tour du lac", 8, "~"), "Tour~lac");
+        assert_eq!(ellipsize_middle("Col", 0, "…"), "");
+    }
+
+    #[test]
+    fn ascii_screens_hold_only_ascii() {
+        let mut app = loading_app("ascii");
+        app.ascii = true;
+        app.lang = Lang::Fr;
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        for (step, keys) in SNAPSHOT_STEPS {
+            for &key in *keys {
+                app.handle_event(Event::Key(KeyEvent::from(key)));
+            }
+            settle(&mut app, &mut terminal);
+            let screen = text(terminal.backend().buffer());
+            assert!(screen.is_ascii(), "{step}:\n{screen}");
+            // The detail and the footer pick their own symbols, the
+            // accents are dropped.
+            if *step == "list" {
+                assert!(screen.contains("v Resume"), "{screen}");
+                assert!(screen.contains("km + 305m"), "{screen}");
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "borders", ignore = "the countries crossed are listed too")]
+    fn screens_match_their_snapshots_at_each_size() {
+        for (width, height) in SNAPSHOT_SIZES {
+            let size = format!("{width}x{height}");
+            let mut app = loading_app(&size);
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            for (step, keys) in SNAPSHOT_STEPS {
+                for &key in *keys {
+                    app.handle_event(Event::Key(KeyEvent::from(key)));
+                }
+                settle(&mut app, &mut terminal);
+                let buf = terminal.backend().buffer();
+                // The footer keeps the last line to itself, when there is
+                // room for the interface at all.
+                if width >= MIN_WIDTH && height >= MIN_HEIGHT {
+                    let footer: String =
+                        (0..width).map(|x| buf[(x, height - 1)].symbol()).collect();
+                    assert!(footer.contains(": commands"), "{step} at {size}");
+                }
+                assert_snapshot(&format!("{step}_{size}"), buf);
+            }
+        }
+    }
+
+    #[test]
+    fn scripted_keys_drive_the_interface_to_the_end() {
+        let mut app = loading_app("script");
+        let dir = store_dir(&["snapshots", "script"]);
+        app.split = 40;
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let mut out = vec![];
+        let keys = vec![KeyCode::Char('j'), KeyCode::Char('>'), KeyCode::Char('y')];
+        app.drive(&mut terminal, &mut Script(keys), &mut out)
+            .unwrap();
+
+        // The session is saved on the way out.
+        let session = Store::new(dir).session().unwrap();
+        assert_eq!(session.split, Some(40 + SPLIT_STEP));
+        assert!(session.selected.is_some());
+        // The share text went to the clipboard, out of the backend.
+        assert!(String::from_utf8(out).unwrap().starts_with("\x1b]52;c;"));
+    }
+
+    #[test]
+    fn distance_models_are_computed_once_asked_for() {
+        let mut app = loading_app("distance_models");
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        settle(&mut app, &mut terminal);
+        assert!(!text(terminal.backend().buffer()).contains("Distance models"));
+        assert!(app.model_distances.is_empty());
+
+        press(&mut app, KeyCode::Char('D'));
+        settle(&mut app, &mut terminal);
+        let screen = text(terminal.backend().buffer());
+        assert!(screen.contains("▾ Distance models"), "{screen}");
+        assert!(screen.contains("(used)"), "{screen}");
+        assert!(screen.contains("geodesic"), "{screen}");
+        let id = app.selected().unwrap().activity.id;
+        let distances = app.model_distances[&id];
+        let haversine = distances.km(DistanceModel::Haversine);
+        assert!((haversine - app.selected().unwrap().activity.distance_km).abs() < 1e-9);
+
+        // Each ride selected is measured once; the toggle hides them.
+        press(&mut app, KeyCode::Char('j'));
+        settle(&mut app, &mut terminal);
+        press(&mut app, KeyCode::Char('k'));
+        settle(&mut app, &mut terminal);
+        assert_eq!(app.model_distances.len(), 2);
+        press(&mut app, KeyCode::Char('D'));
+        settle(&mut app, &mut terminal);
+        assert!(!text(terminal.backend().buffer()).contains("Distance models"));
+    }
+
+    #[test]
+    fn time_above_the_thresholds_is_told() {
+        let mut app = loading_app("time_above");
+        app.altitude_thresholds = vec![500.0, 700.0, 2_000.0];
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        settle(&mut app, &mut terminal);
+        let mountain = app
+            .file_list
+            .files
+            .iter()
+            .find(|file| file.name() == "Col du Test")
+            .unwrap();
+        let [above_500, above_700, above_2000] = mountain.time_above[..] else {
+            panic!("{:?}", mountain.time_above);
+        };
+        assert!(
+            above_500 > above_700 && above_700 > 0.0,
+            "{above_500} {above_700}"
+        );
+        assert_eq!(above_2000, 0.0);
+        let flat = app
+            .file_list
+            .files
+            .iter()
+            .find(|file| file.name() == "Flat ride");
+        assert_eq!(flat.unwrap().time_above, [0.0; 3]);
+
+        press(&mut app, KeyCode::Char('s'));
+        settle(&mut app, &mut terminal);
+        let screen = text(terminal.backend().buffer());
+        let expected = format!(
+            "Time above: 500 m {}, 700 m {}",
+            format_duration(above_500),
+            format_duration(above_700)
+        );
+        assert!(screen.contains(&expected), "{screen}");
+        assert!(!screen.contains("2000 m"), "{screen}");
+    }
+
+    #[test]
+    fn resizing_keeps_the_selection_in_view() {
+        let names: Vec<String> = (0..40).map(|i| format!("ride {i:02}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut app = app_with_files(&names);
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        for _ in 0..30 {
+            press(&mut app, KeyCode::Char('j'));
+        }
+        app.tick(&mut terminal, &mut io::sink()).unwrap();
+        let selected = app.selected().unwrap().activity.name.clone();
+        assert!(text(terminal.backend().buffer()).contains(&selected));
+
+        // Below the smallest size only the message is drawn, with nothing
+        // left over from the larger screen.
+        terminal.backend_mut().resize(60, 15);
+        app.resize(&mut terminal).unwrap();
+        app.tick(&mut terminal, &mut io::sink()).unwrap();
+        let screen = text(terminal.backend().buffer());
+        assert!(screen.contains("terminal too small"), "{screen}");
+        assert!(!screen.contains("ride "), "{screen}");
+
+        // At the smallest size the list scrolls to keep the selection.
+        terminal.backend_mut().resize(MIN_WIDTH, MIN_HEIGHT);
+        app.resize(&mut terminal).unwrap();
+        app.tick(&mut terminal, &mut io::sink()).unwrap();
+        let screen = text(terminal.backend().buffer());
+        assert!(screen.contains(&selected), "{screen}");
+    }
 }
@@ -0,0 +1,160 @@
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+use crate::activity::Activity;
+
+/// Runs whose totals are kept when not configured.
+pub const DEFAULT_RUN_HISTORY: usize = 20;
+
+/// Headline numbers of the archive at one run of the interface, kept in the
+/// session to tell what changed since.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunTotals {
+    /// When the files were read, in RFC 3339.
+    pub at: String,
+    pub rides: usize,
+    pub distance_km: f64,
+    pub elevation_m: f64,
+}
+
+impl RunTotals {
+    pub fn of<'a>(
+        activities: impl Iterator<Item = &'a Activity>,
+        at: DateTime<FixedOffset>,
+    ) -> Self {
+        let mut totals = Self {
+            at: at.to_rfc3339(),
+            rides: 0,
+            distance_km: 0.0,
+            elevation_m: 0.0,
+        };
+        for activity in activities {
+            totals.rides += 1;
+            totals.distance_km += activity.distance_km;
+            totals.elevation_m += activity.elevation_gain;
+        }
+        totals
+    }
+
+    /// What changed from `previous` to these totals.
+    pub fn since(&self, previous: &Self) -> RunDelta {
+        RunDelta {
+            rides: self.rides as i64 - previous.rides as i64,
+            distance_km: self.distance_km - previous.distance_km,
+            elevation_m: self.elevation_m - previous.elevation_m,
+        }
+    }
+}
+
+/// Difference between the totals of two runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunDelta {
+    pub rides: i64,
+    pub distance_km: f64,
+    pub elevation_m: f64,
+}
+
+impl RunDelta {
+    /// Whether rides or distance went missing, e.g. files deleted by a sync.
+    pub fn is_loss(&self) -> bool {
+        self.rides < 0 || self.distance_km <= -0.05
+    }
+
+    /// Whether nothing changed, to the tenth of a kilometer.
+    pub fn is_none(&self) -> bool {
+        self.rides == 0 && self.distance_km.abs() < 0.05
+    }
+}
+
+/// Add `totals` to the history, keeping the last `keep` runs, and tell what
+/// changed since the previous run, if any.
+pub fn record_run(runs: &mut Vec<RunTotals>, totals: RunTotals, keep: usize) -> Option<RunDelta> {
+    let delta = runs.last().map(|previous| totals.since(previous));
+    runs.push(totals);
+    let pruned = runs.len().saturating_sub(keep);
+    runs.drain(..pruned);
+    delta
+}
+
+/// Print the run-over-run table of the history, the oldest run first.
+pub fn write_run_history(runs: &[RunTotals], out: &mut impl Write) -> io::Result<()> {
+    if runs.is_empty() {
+        return writeln!(out, "no run recorded yet");
+    }
+    writeln!(
+        out,
+        "{:<16}  {:>6} {:>6}  {:>9} {:>8}  {:>10} {:>8}",
+        "run", "rides", "Δ", "km", "Δ km", "elevation", "Δ m"
+    )?;
+    let mut previous: Option<&RunTotals> = None;
+    for run in runs {
+        let at = DateTime::parse_from_rfc3339(&run.at).map_or_else(
+            |_| run.at.clone(),
+            |at| at.format("%Y-%m-%d %H:%M").to_string(),
+        );
+        let (rides, distance, elevation) = match previous.map(|previous| run.since(previous)) {
+            Some(delta) => (
+                format!("{:+}", delta.rides),
+                format!("{:+.1}", delta.distance_km),
+                format!("{:+.0}", delta.elevation_m),
+            ),
+            None => Default::default(),
+        };
+        writeln!(
+            out,
+            "{at:<16}  {:>6} {rides:>6}  {:>9.1} {distance:>8}  {:>10.0} {elevation:>8}",
+            run.rides, run.distance_km, run.elevation_m
+        )?;
+        previous = Some(run);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totals(day: u32, rides: usize, distance_km: f64) -> RunTotals {
+        RunTotals {
+            at: format!("2024-06-{day:02}T08:00:00+02:00"),
+            rides,
+            distance_km,
+            elevation_m: distance_km * 10.0,
+        }
+    }
+
+    #[test]
+    fn runs_are_compared_and_pruned() {
+        let mut runs = vec![];
+        assert_eq!(record_run(&mut runs, totals(1, 10, 300.0), 3), None);
+        let delta = record_run(&mut runs, totals(2, 13, 514.0), 3).unwrap();
+        assert_eq!((delta.rides, delta.distance_km), (3, 214.0));
+        assert!(!delta.is_loss());
+        assert!(
+            record_run(&mut runs, totals(3, 13, 514.0), 3)
+                .unwrap()
+                .is_none()
+        );
+
+        // A sync deleted files.
+        let delta = record_run(&mut runs, totals(4, 11, 450.0), 3).unwrap();
+        assert!(delta.is_loss());
+        let days: Vec<&str> = runs.iter().map(|run| &run.at[..10]).collect();
+        assert_eq!(days, ["2024-06-02", "2024-06-03", "2024-06-04"]);
+    }
+
+    #[test]
+    fn history_table() {
+        let mut out = vec![];
+        write_run_history(&[totals(1, 10, 300.0), totals(2, 13, 514.0)], &mut out).unwrap();
+        let table = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3, "{table}");
+        assert!(lines[1].starts_with("2024-06-01 08:00      10"), "{table}");
+        assert!(lines[1].trim_end().ends_with("3000"), "{table}");
+        assert!(lines[2].contains("+3"), "{table}");
+        assert!(lines[2].contains("+214.0"), "{table}");
+        assert!(lines[2].trim_end().ends_with("+2140"), "{table}");
+    }
+}
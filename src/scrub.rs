@@ -0,0 +1,166 @@
+use chrono::{DateTime, FixedOffset, TimeDelta};
+
+use crate::activity::TrackPoint;
+use crate::stats::GRADE_SECTION_M;
+use crate::tuning::{Tuning, cumulative_distance};
+
+/// Values of a ride at a distance from its start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Where the sample was taken, within the ride.
+    pub distance_km: f64,
+    pub time: Option<DateTime<FixedOffset>>,
+    pub elevation: Option<f64>,
+    /// Over [`GRADE_SECTION_M`] around the sample, in percent.
+    pub grade_percent: Option<f64>,
+    /// Over [`GRADE_SECTION_M`] around the sample, in km/h.
+    pub speed_kmh: Option<f64>,
+    pub heart_rate: Option<f64>,
+    pub power: Option<f64>,
+}
+
+/// The points kept by the tuning, with their distance from the start in
+/// meters, as on the elevation profile.
+struct Track<'a> {
+    along: Vec<(f64, &'a TrackPoint)>,
+}
+
+impl Track<'_> {
+    /// The two points around `distance_m` and the share of the way from
+    /// the first to the second.
+    fn around(&self, distance_m: f64) -> (&TrackPoint, &TrackPoint, f64) {
+        let after = self
+            .along
+            .partition_point(|&(along, _)| along < distance_m)
+            .clamp(1, self.along.len() - 1);
+        let ((d1, a), (d2, b)) = (self.along[after - 1], self.along[after]);
+        let share = if d2 > d1 {
+            ((distance_m - d1) / (d2 - d1)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (a, b, share)
+    }
+
+    fn value_at(&self, distance_m: f64, value: fn(&TrackPoint) -> Option<f64>) -> Option<f64> {
+        let (a, b, share) = self.around(distance_m);
+        Some(lerp(value(a)?, value(b)?, share))
+    }
+
+    fn time_at(&self, distance_m: f64) -> Option<DateTime<FixedOffset>> {
+        let (a, b, share) = self.around(distance_m);
+        let (t1, t2) = (a.time?, b.time?);
+        let millis = ((t2 - t1).num_milliseconds() as f64 * share).round() as i64;
+        Some(t1 + TimeDelta::milliseconds(millis))
+    }
+}
+
+fn lerp(a: f64, b: f64, share: f64) -> f64 {
+    a + (b - a) * share
+}
+
+/// The ride at `distance_km` from its start, interpolated linearly in
+/// distance between the two points around it; distances past either end
+/// give the end. `None` without points.
+pub fn sample_at(points: &[TrackPoint], tuning: &Tuning, distance_km: f64) -> Option<Sample> {
+    let track = Track {
+        along: cumulative_distance(points, tuning).collect(),
+    };
+    let (&(length_m, _), &(_, first)) = (track.along.last()?, track.along.first()?);
+    if track.along.len() == 1 {
+        return Some(Sample {
+            distance_km: 0.0,
+            time: first.time,
+            elevation: first.elevation,
+            grade_percent: None,
+            speed_kmh: None,
+            heart_rate: first.heart_rate,
+            power: first.power,
+        });
+    }
+
+    let distance_m = (distance_km * 1_000.0).clamp(0.0, length_m);
+    // The window around the sample, shifted to stay within the ride.
+    let from =
+        (distance_m - GRADE_SECTION_M / 2.0).clamp(0.0, (length_m - GRADE_SECTION_M).max(0.0));
+    let to = (from + GRADE_SECTION_M).min(length_m);
+    let grade_percent = (to > from)
+        .then(|| {
+            let climb = track.value_at(to, |point| point.elevation)?
+                - track.value_at(from, |point| point.elevation)?;
+            Some(climb / (to - from) * 100.0)
+        })
+        .flatten();
+    let speed_kmh = match (track.time_at(from), track.time_at(to)) {
+        (Some(t1), Some(t2)) if t2 > t1 => Some((to - from) / (t2 - t1).as_seconds_f64() * 3.6),
+        _ => None,
+    };
+
+    Some(Sample {
+        distance_km: distance_m / 1_000.0,
+        time: track.time_at(distance_m),
+        elevation: track.value_at(distance_m, |point| point.elevation),
+        grade_percent,
+        speed_kmh,
+        heart_rate: track.value_at(distance_m, |point| point.heart_rate),
+        power: track.value_at(distance_m, |point| point.power),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every 100 m north, 20 s apart, climbing 5 m at each point.
+    fn ride() -> Vec<TrackPoint> {
+        let start = DateTime::parse_from_rfc3339("2024-06-01T07:00:00+02:00").unwrap();
+        (0..=10)
+            .map(|i| TrackPoint {
+                lat: 45.0 + i as f64 * 100.0 / 111_195.08,
+                lon: 5.0,
+                elevation: Some(200.0 + i as f64 * 5.0),
+                time: Some(start + TimeDelta::seconds(i * 20)),
+                heart_rate: (i < 5).then_some(120.0 + i as f64 * 2.0),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn values_are_interpolated_in_distance() {
+        let sample = sample_at(&ride(), &Tuning::default(), 0.25).unwrap();
+
+        assert!((sample.distance_km - 0.25).abs() < 1e-9);
+        assert_eq!(
+            sample.time.unwrap().format("%H:%M:%S").to_string(),
+            "07:00:50"
+        );
+        assert!((sample.elevation.unwrap() - 212.5).abs() < 1e-6);
+        assert!((sample.grade_percent.unwrap() - 5.0).abs() < 1e-6);
+        // 100 m in 20 s.
+        assert!((sample.speed_kmh.unwrap() - 18.0).abs() < 1e-6);
+        assert!((sample.heart_rate.unwrap() - 125.0).abs() < 1e-6);
+        // Past the last heart rate reading.
+        let later = sample_at(&ride(), &Tuning::default(), 0.45).unwrap();
+        assert_eq!(later.heart_rate, None);
+        assert_eq!(later.power, None);
+    }
+
+    #[test]
+    fn the_ends_are_held() {
+        let points = ride();
+        let before = sample_at(&points, &Tuning::default(), -1.0).unwrap();
+        assert_eq!(before.distance_km, 0.0);
+        assert_eq!(before.elevation, Some(200.0));
+        // The window stays within the ride.
+        assert!((before.grade_percent.unwrap() - 5.0).abs() < 1e-6);
+
+        let after = sample_at(&points, &Tuning::default(), 99.0).unwrap();
+        assert!((after.distance_km - 1.0).abs() < 1e-6);
+        assert!((after.elevation.unwrap() - 250.0).abs() < 1e-6);
+
+        assert_eq!(sample_at(&[], &Tuning::default(), 1.0), None);
+        let single = sample_at(&points[..1], &Tuning::default(), 1.0).unwrap();
+        assert_eq!((single.distance_km, single.grade_percent), (0.0, None));
+    }
+}
@@ -0,0 +1,66 @@
+use ratatui::text::Text;
+
+/// A part of the detail pane, listed under its title, which folds it.
+#[derive(Debug)]
+pub struct Section<T, I> {
+    /// Kept in the session while the section is folded.
+    pub key: &'static str,
+    pub title: &'static str,
+    /// Text of the section for an item, `None` to leave it out.
+    pub body: fn(&T, &I) -> Option<Text<'static>>,
+}
+
+/// Sections laid out one below the other, by the line each starts on.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SectionLayout {
+    /// Key and first line of each shown section, in order.
+    pub starts: Vec<(&'static str, usize)>,
+    /// Lines of all the sections together.
+    pub height: usize,
+}
+
+impl SectionLayout {
+    /// Lay out the sections of the given keys and heights, after `offset`
+    /// lines that belong to none.
+    pub fn new(offset: usize, sections: impl IntoIterator<Item = (&'static str, usize)>) -> Self {
+        let mut layout = Self {
+            starts: vec![],
+            height: offset,
+        };
+        for (key, height) in sections {
+            layout.starts.push((key, layout.height));
+            layout.height += height;
+        }
+        layout
+    }
+
+    /// The section `line` is in, with the line it starts on; the first one
+    /// for the lines above it.
+    pub fn at(&self, line: usize) -> Option<(&'static str, usize)> {
+        self.starts
+            .iter()
+            .rev()
+            .find(|(_, start)| *start <= line)
+            .or(self.starts.first())
+            .copied()
+            .filter(|_| line < self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_fall_in_the_section_above_them() {
+        let layout = SectionLayout::new(2, [("summary", 5), ("terrain", 1), ("notes", 3)]);
+        assert_eq!(layout.height, 11);
+        assert_eq!(layout.at(0), Some(("summary", 2)));
+        assert_eq!(layout.at(2), Some(("summary", 2)));
+        assert_eq!(layout.at(6), Some(("summary", 2)));
+        assert_eq!(layout.at(7), Some(("terrain", 7)));
+        assert_eq!(layout.at(10), Some(("notes", 8)));
+        assert_eq!(layout.at(11), None);
+        assert_eq!(SectionLayout::new(0, []).at(0), None);
+    }
+}
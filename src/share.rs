@@ -0,0 +1,212 @@
+use anyhow::{Result, bail};
+
+use crate::activity::Activity;
+
+/// Share text used when the configuration has no `share_template`.
+pub const DEFAULT_SHARE_TEMPLATE: &str =
+    "{date} – {name}: {distance} / {elevation}↑ in {moving_time} ({avg_speed})";
+
+/// Written in place of a metric the activity does not have, such as the
+/// moving time of a file without timestamps.
+const MISSING: &str = "?";
+
+/// Metrics a share template can refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Date,
+    Name,
+    Distance,
+    Elevation,
+    MovingTime,
+    AvgSpeed,
+    Energy,
+}
+
+impl Placeholder {
+    const ALL: [(&str, Placeholder); 7] = [
+        ("date", Placeholder::Date),
+        ("name", Placeholder::Name),
+        ("distance", Placeholder::Distance),
+        ("elevation", Placeholder::Elevation),
+        ("moving_time", Placeholder::MovingTime),
+        ("avg_speed", Placeholder::AvgSpeed),
+        ("energy", Placeholder::Energy),
+    ];
+
+    /// The metric with its unit.
+    fn render(self, activity: &Activity) -> Option<String> {
+        match self {
+            Placeholder::Date => activity
+                .start
+                .map(|start| start.format("%Y-%m-%d").to_string()),
+            Placeholder::Name => Some(activity.name.clone()),
+            Placeholder::Distance => Some(format!("{:.1} km", activity.distance_km)),
+            Placeholder::Elevation => Some(format!("{:.0} m", activity.elevation_gain)),
+            Placeholder::MovingTime => activity.moving_seconds().map(|seconds| {
+                let seconds = seconds.round() as u64;
+                let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+                if hours > 0 {
+                    format!("{hours}:{minutes:02}:{seconds:02}")
+                } else {
+                    format!("{minutes}:{seconds:02}")
+                }
+            }),
+            Placeholder::AvgSpeed => activity
+                .average_speed()
+                .map(|speed| format!("{speed:.1} km/h")),
+            Placeholder::Energy => activity
+                .energy()
+                .map(|energy| format!("{:.0} kJ", energy.kilojoules)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// A one-line summary of a ride to paste in a chat, written as
+/// `{date} – {name}: {distance}` with the placeholders of the metrics;
+/// `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShareTemplate {
+    parts: Vec<Part>,
+}
+
+impl Default for ShareTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_SHARE_TEMPLATE).expect("the default share template is valid")
+    }
+}
+
+impl ShareTemplate {
+    /// Fails on an unclosed brace or, listing them all, on unknown
+    /// placeholders.
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut unknown = vec![];
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.next_if_eq(&'{').is_some() => text.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => text.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed || name.contains('{') {
+                        bail!("share template {template:?}: unclosed `{{`");
+                    }
+                    match Placeholder::ALL.iter().find(|(known, _)| *known == name) {
+                        Some(&(_, placeholder)) => {
+                            parts.push(Part::Text(std::mem::take(&mut text)));
+                            parts.push(Part::Placeholder(placeholder));
+                        }
+                        None => unknown.push(format!("{{{name}}}")),
+                    }
+                }
+                '}' => bail!("share template {template:?}: unmatched `}}`"),
+                c => text.push(c),
+            }
+        }
+        if !unknown.is_empty() {
+            let known: Vec<String> = Placeholder::ALL
+                .iter()
+                .map(|(name, _)| format!("{{{name}}}"))
+                .collect();
+            bail!(
+                "share template {template:?}: unknown placeholder{} {}, expected one of {}",
+                if unknown.len() > 1 { "s" } else { "" },
+                unknown.join(", "),
+                known.join(", ")
+            );
+        }
+        parts.push(Part::Text(text));
+        parts.retain(|part| *part != Part::Text(String::new()));
+
+        Ok(Self { parts })
+    }
+
+    pub fn render(&self, activity: &Activity) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Placeholder(placeholder) => placeholder
+                    .render(activity)
+                    .unwrap_or_else(|| MISSING.to_string()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::TrackPoint;
+    use chrono::{DateTime, TimeDelta};
+
+    /// 0.1° of latitude north (~11.1 km) in 25 minutes.
+    fn ride() -> Activity {
+        let start = DateTime::parse_from_rfc3339("2024-06-01T07:00:00+02:00").unwrap();
+        let points = [(45.0, 0), (45.1, 25)].map(|(lat, minutes)| TrackPoint {
+            lat,
+            lon: 5.0,
+            time: Some(start + TimeDelta::minutes(minutes)),
+            ..Default::default()
+        });
+        Activity {
+            name: "Morning Ride".to_string(),
+            start: Some(start),
+            distance_km: 11.12,
+            elevation_gain: 123.4,
+            points: points.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn placeholders_are_rendered_with_their_unit() {
+        let text = ShareTemplate::default().render(&ride());
+        assert_eq!(
+            text,
+            "2024-06-01 – Morning Ride: 11.1 km / 123 m↑ in 25:00 (26.7 km/h)"
+        );
+
+        let literal = ShareTemplate::parse("{{name}} is {name}").unwrap();
+        assert_eq!(literal.render(&ride()), "{name} is Morning Ride");
+    }
+
+    #[test]
+    fn missing_metrics_are_question_marks() {
+        let untimed = Activity {
+            points: vec![],
+            start: None,
+            ..ride()
+        };
+        let template = ShareTemplate::parse("{date} {moving_time} {avg_speed}").unwrap();
+        assert_eq!(template.render(&untimed), "? ? ?");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_all_listed() {
+        let err = ShareTemplate::parse("{name} {speed} {dist}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "share template \"{name} {speed} {dist}\": unknown placeholders {speed}, {dist}, \
+             expected one of {date}, {name}, {distance}, {elevation}, {moving_time}, \
+             {avg_speed}, {energy}"
+        );
+        assert!(ShareTemplate::parse("{name").is_err());
+        assert!(ShareTemplate::parse("name}").is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::activity::{Activity, ActivityId};
+use crate::runs::RunTotals;
+use crate::storage::{Stored, read_versioned, write_atomic, write_versioned};
+
+/// Extension of the legacy sidecar files, stored next to the GPX file and
+/// keyed by its path (`ride.gpx` -> `ride.gpx.cyclemetrics.toml`).
+const LEGACY_SIDECAR_EXTENSION: &str = "cyclemetrics.toml";
+
+/// User metadata attached to an activity.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sidecar {
+    /// Overrides the track name read from the GPX file.
+    pub name: Option<String>,
+    pub notes: Option<String>,
+    /// Overrides the automatic commute detection.
+    pub commute: Option<bool>,
+    /// Tags set by hand, shown with the ones from the tagging rules.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Multi-day tour the ride is a stage of.
+    pub tour: Option<String>,
+    /// Hides the ride from the list and the totals, or shows a ride hidden
+    /// for having no points.
+    pub hidden: Option<bool>,
+    /// Fields written by a newer version, kept as they are.
+    #[serde(flatten)]
+    pub unknown: toml::Table,
+}
+
+/// State restored between two runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub selected: Option<String>,
+    /// Share of the width given to the list pane, in percent.
+    pub split: Option<u16>,
+    /// Whether the rides of a day are listed as one entry.
+    #[serde(default)]
+    pub combine_days: bool,
+    /// Whether the rides of each month are listed under a header.
+    #[serde(default)]
+    pub group_months: bool,
+    /// Days whose rides are listed under their entry, as `YYYY-MM-DD`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expanded_days: Vec<String>,
+    /// Sections of the detail pane folded to their title.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub folded_sections: Vec<String>,
+    /// Totals of the archive at the last runs, the oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<RunTotals>,
+    /// Fields written by a newer version, kept as they are.
+    #[serde(flatten)]
+    pub unknown: toml::Table,
+}
+
+/// Directory holding sidecars (keyed by [`ActivityId`]) and the session.
+#[derive(Debug, Clone)]
+pub struct Store {
+    dir: PathBuf,
+    /// Files found broken and set aside since the last
+    /// [`Store::take_warnings`], shared by the clones.
+    warnings: Arc<Mutex<Vec<String>>>,
+}
+
+impl Store {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            warnings: Arc::default(),
+        }
+    }
+
+    /// `$CYCLEMETRICS_HOME`, `$XDG_DATA_HOME/cyclemetrics` or
+    /// `~/.local/share/cyclemetrics`, in that order.
+    pub fn default_dir() -> PathBuf {
+        if let Some(dir) = std::env::var_os("CYCLEMETRICS_HOME") {
+            return PathBuf::from(dir);
+        }
+        if let Some(dir) = std::env::var_os("XDG_DATA_HOME") {
+            return PathBuf::from(dir).join("cyclemetrics");
+        }
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".local/share/cyclemetrics")
+    }
+
+    fn sidecar_path(&self, id: ActivityId) -> PathBuf {
+        self.dir.join("activities").join(format!("{id}.toml"))
+    }
+
+    fn session_path(&self) -> PathBuf {
+        self.dir.join("session.toml")
+    }
+
+    /// Load the sidecar of an activity, moving a legacy path-keyed sidecar
+    /// into the store first if there is one.
+    pub fn sidecar(&self, activity: &Activity) -> Result<Sidecar> {
+        let path = self.sidecar_path(activity.id);
+        let legacy = legacy_sidecar_path(&activity.path);
+
+        if !path.exists() && legacy.exists() {
+            let bytes =
+                fs::read(&legacy).with_context(|| format!("migrating {}", legacy.display()))?;
+            write_atomic(&path, &bytes)?;
+            fs::remove_file(&legacy)?;
+        }
+
+        Ok(self.read_toml(&path)?.unwrap_or_default())
+    }
+
+    pub fn save_sidecar(&self, id: ActivityId, sidecar: &Sidecar) -> Result<()> {
+        write_versioned(&self.sidecar_path(id), sidecar)
+    }
+
+    pub fn session(&self) -> Result<Session> {
+        Ok(self.read_toml(&self.session_path())?.unwrap_or_default())
+    }
+
+    pub fn save_session(&self, session: &Session) -> Result<()> {
+        write_versioned(&self.session_path(), session)
+    }
+
+    /// What was said about the broken files set aside since the last call.
+    pub fn take_warnings(&self) -> Vec<String> {
+        std::mem::take(&mut *self.warnings.lock().unwrap_or_else(|err| err.into_inner()))
+    }
+
+    /// Read a file of the store, a broken one being set aside and read as
+    /// missing with a warning.
+    fn read_toml<T: DeserializeOwned>(&self, path: &Path) -> Result<Option<T>> {
+        Ok(match read_versioned(path)? {
+            Stored::Missing => None,
+            Stored::Found(value) => Some(value),
+            Stored::Recovered(recovered) => {
+                let mut warnings = self.warnings.lock().unwrap_or_else(|err| err.into_inner());
+                warnings.push(recovered.to_string());
+                None
+            }
+        })
+    }
+}
+
+/// Move a GPX file to the `archive` directory next to it.
+pub fn archive_gpx(path: &Path) -> Result<()> {
+    let dir = path.with_file_name("archive");
+    fs::create_dir_all(&dir)?;
+    let target = dir.join(path.file_name().unwrap_or_default());
+    fs::rename(path, &target).with_context(|| format!("archiving {}", path.display()))
+}
+
+fn legacy_sidecar_path(gpx_path: &Path) -> PathBuf {
+    let mut file_name = gpx_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(LEGACY_SIDECAR_EXTENSION);
+    gpx_path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cyclemetrics-sidecar-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn activity(path: PathBuf) -> Activity {
+        Activity {
+            id: "1717228800-00000000deadbeef".parse().unwrap(),
+            path,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_legacy_sidecar_moves_into_the_store() {
+        let dir = temp_dir("legacy");
+        let store = Store::new(dir.join("store"));
+        let ride = activity(dir.join("ride.gpx"));
+        let legacy = dir.join("ride.gpx.cyclemetrics.toml");
+        fs::write(&legacy, "name = \"Col du Test\"\ntags = [\"alps\"]\n").unwrap();
+
+        let sidecar = store.sidecar(&ride).unwrap();
+        assert_eq!(sidecar.name.as_deref(), Some("Col du Test"));
+        assert_eq!(sidecar.tags, ["alps"]);
+        assert!(!legacy.exists());
+        assert!(store.sidecar_path(ride.id).exists());
+        // Read from the store from then on.
+        assert_eq!(store.sidecar(&ride).unwrap(), sidecar);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_sidecar_follows_the_ride_to_another_path() {
+        let dir = temp_dir("moved");
+        let store = Store::new(dir.join("store"));
+        let ride = activity(dir.join("ride.gpx"));
+        let sidecar = Sidecar {
+            notes: Some("headwind all the way".to_string()),
+            ..Default::default()
+        };
+        store.save_sidecar(ride.id, &sidecar).unwrap();
+
+        let moved = activity(dir.join("archive").join("renamed.gpx"));
+        assert_eq!(store.sidecar(&moved).unwrap(), sidecar);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+/// Braille dots of the left column of a character, from the bottom up.
+const LEFT_DOTS: [u32; 4] = [0x40, 0x04, 0x02, 0x01];
+/// Braille dots of the right column of a character, from the bottom up.
+const RIGHT_DOTS: [u32; 4] = [0x80, 0x20, 0x10, 0x08];
+const BRAILLE_BLANK: u32 = 0x2800;
+
+/// Bars of [`ascii_sparkline`], from nothing to the highest value.
+pub const ASCII_LEVELS: [char; 5] = [' ', '_', '-', '=', '#'];
+
+/// Height of each value on a scale of `0..=levels`, the highest value
+/// taking them all. Any value above zero is at least one level high.
+fn heights(values: &[f64], levels: usize) -> impl Iterator<Item = usize> {
+    let max = values.iter().copied().fold(0.0, f64::max);
+    values.iter().map(move |&value| {
+        if value <= 0.0 || max <= 0.0 {
+            0
+        } else {
+            ((value / max * levels as f64).round() as usize).clamp(1, levels)
+        }
+    })
+}
+
+/// Bars four dots high in braille characters, two values per character.
+pub fn braille_sparkline(values: &[f64]) -> String {
+    let heights: Vec<usize> = heights(values, LEFT_DOTS.len()).collect();
+    heights
+        .chunks(2)
+        .map(|pair| {
+            let column = |dots: &[u32; 4], height: usize| dots[..height].iter().sum::<u32>();
+            let left = column(&LEFT_DOTS, pair[0]);
+            let right = pair.get(1).map_or(0, |&height| column(&RIGHT_DOTS, height));
+            char::from_u32(BRAILLE_BLANK + left + right).unwrap_or(' ')
+        })
+        .collect()
+}
+
+/// Bars of [`ASCII_LEVELS`], one value per character, for terminals
+/// without Unicode.
+pub fn ascii_sparkline(values: &[f64]) -> String {
+    heights(values, ASCII_LEVELS.len() - 1)
+        .map(|height| ASCII_LEVELS[height])
+        .collect()
+}
+
+/// Whether the locale of the terminal, from the first of `LC_ALL`,
+/// `LC_CTYPE` and `LANG` that is set, uses UTF-8. Windows terminals always
+/// do.
+pub fn unicode_locale(var: impl Fn(&str) -> Option<String>) -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .find_map(|name| var(name).filter(|value| !value.is_empty()))
+        .is_some_and(|locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_scaled_to_the_highest() {
+        assert_eq!(braille_sparkline(&[0.0, 4.0, 1.0, 2.0]), "⢸⣠");
+        // A lone value takes the left column.
+        assert_eq!(braille_sparkline(&[4.0, 4.0, 4.0]), "⣿⡇");
+        assert_eq!(ascii_sparkline(&[0.0, 0.1, 2.0, 3.0, 4.0]), " _-=#");
+        assert_eq!(ascii_sparkline(&[0.0, 0.0]), "  ");
+        assert_eq!(braille_sparkline(&[]), "");
+    }
+
+    #[test]
+    fn utf8_locales_are_unicode() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert!(unicode_locale(env(&[("LANG", "fr_FR.UTF-8")])));
+        assert!(unicode_locale(env(&[("LC_CTYPE", "en_US.utf8")])));
+        // The first one set wins.
+        assert!(!unicode_locale(env(&[
+            ("LC_ALL", "C"),
+            ("LANG", "en_US.UTF-8")
+        ])));
+        assert!(unicode_locale(env(&[
+            ("LC_ALL", ""),
+            ("LANG", "en_US.UTF-8")
+        ])));
+        assert!(!unicode_locale(env(&[])));
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+use std::path::Path;
+
+use crate::activity::{Activity, ActivityId};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS activities (
+    id TEXT PRIMARY KEY,
+    file TEXT NOT NULL,
+    name TEXT NOT NULL,
+    start_time TEXT,
+    start_lat REAL,
+    start_lon REAL,
+    distance_m REAL NOT NULL,
+    gain_m REAL NOT NULL,
+    corrected_gain_m REAL,
+    moving_s REAL,
+    avg_speed_kmh REAL
+);
+CREATE TABLE IF NOT EXISTS points (
+    activity_id TEXT NOT NULL REFERENCES activities (id) ON DELETE CASCADE,
+    seq INTEGER NOT NULL,
+    lat REAL NOT NULL,
+    lon REAL NOT NULL,
+    elevation REAL,
+    time TEXT,
+    PRIMARY KEY (activity_id, seq)
+);
+";
+
+const UPSERT_ACTIVITY: &str = "
+INSERT INTO activities (
+    id, file, name, start_time, start_lat, start_lon, distance_m, gain_m,
+    corrected_gain_m, moving_s, avg_speed_kmh
+)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+ON CONFLICT (id) DO UPDATE SET
+    file = excluded.file,
+    name = excluded.name,
+    start_time = excluded.start_time,
+    start_lat = excluded.start_lat,
+    start_lon = excluded.start_lon,
+    distance_m = excluded.distance_m,
+    gain_m = excluded.gain_m,
+    corrected_gain_m = excluded.corrected_gain_m,
+    moving_s = excluded.moving_s,
+    avg_speed_kmh = excluded.avg_speed_kmh
+";
+
+/// Write the activities to a SQLite database, updating the rows already
+/// there by [`crate::ActivityId`].
+///
+/// With `with_points`, the track points go in the `points` table, replacing
+/// the previous ones of the activity. Activities loaded without their points
+/// (see [`crate::LoadOptions::full_detail`]) keep the points already stored.
+pub fn export_sqlite(activities: &[Activity], path: &Path, with_points: bool) -> Result<()> {
+    let mut connection =
+        Connection::open(path).with_context(|| format!("opening {}", path.display()))?;
+    connection.execute_batch(SCHEMA)?;
+
+    let transaction = connection.transaction()?;
+    {
+        let mut upsert = transaction.prepare(UPSERT_ACTIVITY)?;
+        let mut delete_points = transaction.prepare("DELETE FROM points WHERE activity_id = ?1")?;
+        let mut insert_point = transaction.prepare(
+            "INSERT INTO points (activity_id, seq, lat, lon, elevation, time)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+
+        for activity in activities {
+            let id = activity.id.to_string();
+            upsert.execute(params![
+                id,
+                activity.path.display().to_string(),
+                activity.name,
+                activity.start.map(|start| start.to_rfc3339()),
+                activity.start_point.map(|(lat, _)| lat),
+                activity.start_point.map(|(_, lon)| lon),
+                activity.distance_km * 1_000.0,
+                activity.elevation_gain,
+                activity.raw_elevation_gain.map(|_| activity.elevation_gain),
+                activity.moving_seconds(),
+                activity.average_speed(),
+            ])?;
+
+            if with_points && !activity.points.is_empty() {
+                delete_points.execute([&id])?;
+                for (seq, point) in activity.points.iter().enumerate() {
+                    insert_point.execute(params![
+                        id,
+                        seq as i64,
+                        point.lat,
+                        point.lon,
+                        point.elevation,
+                        point.time.map(|time| time.to_rfc3339()),
+                    ])?;
+                }
+            }
+        }
+    }
+    transaction
+        .commit()
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Delete the activities, and their points, from a SQLite database written
+/// by [`export_sqlite`].
+pub fn remove_from_sqlite(ids: &[ActivityId], path: &Path) -> Result<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let mut connection =
+        Connection::open(path).with_context(|| format!("opening {}", path.display()))?;
+    connection.execute_batch(SCHEMA)?;
+
+    let transaction = connection.transaction()?;
+    {
+        // Foreign keys are off unless enabled, so the cascade cannot be relied on.
+        let mut delete_points = transaction.prepare("DELETE FROM points WHERE activity_id = ?1")?;
+        let mut delete = transaction.prepare("DELETE FROM activities WHERE id = ?1")?;
+        for id in ids {
+            let id = id.to_string();
+            delete_points.execute([&id])?;
+            delete.execute([&id])?;
+        }
+    }
+    transaction
+        .commit()
+        .with_context(|| format!("writing {}", path.display()))
+}
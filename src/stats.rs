@@ -0,0 +1,1810 @@
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveTime, Timelike, Weekday};
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Add;
+
+use crate::activity::{Activity, MOVING_MIN_SPEED, TrackPoint};
+use crate::energy::{Energy, EnergySource, kcal};
+use crate::gpx::{GradeModel, grade_factor};
+use crate::intensity::{IntensityThresholds, SensorSummary, classify};
+use crate::peaks::Peak;
+
+/// Start points closer than this to a cluster center join that cluster. Wide
+/// enough to absorb the usual ~100 m of GPS scatter around a front door.
+pub const CLUSTER_RADIUS_M: f64 = 300.0;
+
+/// Rides starting from roughly the same place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocationCluster {
+    /// Mean start point, as (latitude, longitude).
+    pub center: (f64, f64),
+    pub rides: usize,
+    pub distance_km: f64,
+}
+
+/// Group the start points of the activities into clusters of `radius_m`.
+/// Virtual rides have no real start point and are left out.
+///
+/// Each start point joins the first cluster whose center is within the
+/// radius, or starts a new one. Activities are visited in their (sorted)
+/// order and clusters are returned by ride count, then distance, then
+/// position, so the output is deterministic.
+pub fn start_location_clusters<'a>(
+    activities: impl IntoIterator<Item = &'a Activity>,
+    radius_m: f64,
+) -> Vec<LocationCluster> {
+    let activities: Vec<&Activity> = activities
+        .into_iter()
+        .filter(|activity| activity.start_point.is_some() && !activity.virtual_ride)
+        .collect();
+    let starts = activities
+        .iter()
+        .filter_map(|activity| activity.start_point);
+
+    let mut clusters: Vec<LocationCluster> = cluster_points(starts, radius_m)
+        .into_iter()
+        .map(|cluster| LocationCluster {
+            center: cluster.center,
+            rides: cluster.members.len(),
+            distance_km: cluster
+                .members
+                .iter()
+                .map(|&i| activities[i].distance_km)
+                .sum(),
+        })
+        .collect();
+    clusters.sort_by(|a, b| {
+        b.rides
+            .cmp(&a.rides)
+            .then(b.distance_km.total_cmp(&a.distance_km))
+            .then(a.center.0.total_cmp(&b.center.0))
+            .then(a.center.1.total_cmp(&b.center.1))
+    });
+
+    clusters
+}
+
+/// Points close to one another, as found by [`cluster_points`].
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PointCluster {
+    /// Mean of the points, as (latitude, longitude).
+    pub center: (f64, f64),
+    /// Indices of the points, in their order.
+    pub members: Vec<usize>,
+}
+
+/// Group points given as (latitude, longitude) into clusters of `radius_m`,
+/// in the order they are started. Each point joins the first cluster whose
+/// center is within the radius, or starts a new one.
+///
+/// Centers too far north or south are skipped before measuring the
+/// distance, which keeps thousands of points fast.
+pub(crate) fn cluster_points(
+    points: impl IntoIterator<Item = (f64, f64)>,
+    radius_m: f64,
+) -> Vec<PointCluster> {
+    // A degree of latitude is at least this long anywhere.
+    const MIN_DEGREE_M: f64 = 110_500.0;
+    let max_lat_delta = radius_m / MIN_DEGREE_M;
+    // Running sums of latitude and longitude, to compute the centers.
+    let mut clusters: Vec<(PointCluster, (f64, f64))> = vec![];
+
+    for (i, (lat, lon)) in points.into_iter().enumerate() {
+        let start = point!(x: lon, y: lat);
+        let existing = clusters.iter_mut().find(|(cluster, _)| {
+            let (center_lat, center_lon) = cluster.center;
+            (center_lat - lat).abs() <= max_lat_delta
+                && Haversine.distance(start, point!(x: center_lon, y: center_lat)) <= radius_m
+        });
+
+        match existing {
+            Some((cluster, (sum_lat, sum_lon))) => {
+                cluster.members.push(i);
+                *sum_lat += lat;
+                *sum_lon += lon;
+                let count = cluster.members.len() as f64;
+                cluster.center = (*sum_lat / count, *sum_lon / count);
+            }
+            None => clusters.push((
+                PointCluster {
+                    center: (lat, lon),
+                    members: vec![i],
+                },
+                (lat, lon),
+            )),
+        }
+    }
+
+    clusters.into_iter().map(|(cluster, _)| cluster).collect()
+}
+
+/// Rides whose distance is within this share of another ride's distance are
+/// considered of similar length.
+pub const SIMILAR_DISTANCE_TOLERANCE: f64 = 0.2;
+
+/// The value below which a share `p` of the `sorted` values fall,
+/// interpolated between the two nearest ones.
+pub(crate) fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    let last = sorted.len().checked_sub(1)?;
+    let rank = p.clamp(0.0, 1.0) * last as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    let fraction = rank - lower as f64;
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * fraction)
+}
+
+/// Months averaged together by [`moving_average`] on the monthly charts.
+pub const MOVING_AVERAGE_MONTHS: usize = 3;
+
+/// Straight line fitted through points by least squares.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinearTrend {
+    /// Change of y for each unit of x, e.g. kilometers per month.
+    pub slope: f64,
+    pub intercept: f64,
+}
+
+impl LinearTrend {
+    /// The line closest to `points`, as (x, y); `None` without two distinct
+    /// x to draw it through.
+    pub fn fit(points: &[(f64, f64)]) -> Option<Self> {
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|&(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|&(_, y)| y).sum::<f64>() / n;
+        let spread: f64 = points.iter().map(|&(x, _)| (x - mean_x).powi(2)).sum();
+        if points.len() < 2 || spread == 0.0 {
+            return None;
+        }
+        let slope = points
+            .iter()
+            .map(|&(x, y)| (x - mean_x) * (y - mean_y))
+            .sum::<f64>()
+            / spread;
+        Some(Self {
+            slope,
+            intercept: mean_y - slope * mean_x,
+        })
+    }
+
+    pub fn at(&self, x: f64) -> f64 {
+        self.intercept + self.slope * x
+    }
+}
+
+/// Mean of each value with the `window - 1` before it, `None` while there
+/// are fewer.
+pub fn moving_average(values: &[f64], window: usize) -> Vec<Option<f64>> {
+    (0..values.len())
+        .map(|i| {
+            (window > 0 && i + 1 >= window)
+                .then(|| values[i + 1 - window..=i].iter().sum::<f64>() / window as f64)
+        })
+        .collect()
+}
+
+/// Width of the buckets of [`Summary::distance_histogram`], in kilometers.
+pub const RIDE_LENGTH_BUCKET_KM: f64 = 10.0;
+
+/// Metrics of one ride kept by [`Summary`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RideMetrics {
+    pub name: String,
+    /// Day the ride started on, in its own time zone.
+    pub date: Option<NaiveDate>,
+    /// Time of day the ride started, in its own time zone.
+    pub time: Option<NaiveTime>,
+    pub distance_km: f64,
+    pub elevation_gain: f64,
+    pub moving_seconds: Option<f64>,
+    /// Average moving speed, in km/h.
+    pub average_speed: Option<f64>,
+    pub energy: Option<Energy>,
+    pub sensors: SensorSummary,
+    pub peak: Option<Peak>,
+}
+
+impl RideMetrics {
+    pub fn new(activity: &Activity) -> Self {
+        let moving_seconds = activity.moving_seconds();
+        Self {
+            name: activity.name.clone(),
+            date: activity.start.map(|start| start.date_naive()),
+            time: activity.start.map(|start| start.time()),
+            distance_km: activity.distance_km,
+            elevation_gain: activity.elevation_gain,
+            moving_seconds,
+            average_speed: moving_seconds
+                .filter(|&seconds| seconds > 0.0)
+                .map(|seconds| activity.distance_km / (seconds / 3600.0)),
+            energy: activity.energy(),
+            sensors: activity.sensor_summary(),
+            peak: activity.peak,
+        }
+    }
+}
+
+/// Hours of the day in each column of [`Summary::start_slots`].
+pub const START_SLOT_HOURS: u32 = 2;
+
+/// Columns of [`Summary::start_slots`], from midnight.
+pub const START_SLOTS: usize = (24 / START_SLOT_HOURS) as usize;
+
+/// Rides of one day, or of one start slot, added up.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DayTotals {
+    pub rides: usize,
+    pub distance_km: f64,
+    pub elevation_gain: f64,
+}
+
+impl Add for DayTotals {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            rides: self.rides + other.rides,
+            distance_km: self.distance_km + other.distance_km,
+            elevation_gain: self.elevation_gain + other.elevation_gain,
+        }
+    }
+}
+
+/// Weeks of the energy trend, compared with as many weeks before.
+pub const ENERGY_TREND_WEEKS: u64 = 4;
+
+/// Calendar periods that energy is added up over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    /// Monday to Sunday.
+    Week,
+    Month,
+}
+
+impl Period {
+    /// First day of the period `date` falls in.
+    pub fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Week => date - Days::new(u64::from(date.weekday().num_days_from_monday())),
+            Period::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+}
+
+/// How long ago a ride was, by calendar period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Recency {
+    ThisWeek,
+    ThisMonth,
+    ThisYear,
+    Older,
+}
+
+impl Recency {
+    /// Recency of a ride of `date`, seen on `today`. Rides dated later, on
+    /// a clock running ahead, count as this week's.
+    pub fn of(date: NaiveDate, today: NaiveDate) -> Self {
+        if date >= Period::Week.start(today) {
+            Recency::ThisWeek
+        } else if date >= Period::Month.start(today) {
+            Recency::ThisMonth
+        } else if date.year() == today.year() {
+            Recency::ThisYear
+        } else {
+            Recency::Older
+        }
+    }
+}
+
+/// Energy of a set of rides, added up.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EnergyTotals {
+    pub kilojoules: f64,
+    /// Rides measured with a power meter.
+    pub power_rides: usize,
+    /// Rides estimated from speed and grade.
+    pub model_rides: usize,
+}
+
+impl EnergyTotals {
+    fn add(&mut self, energy: Energy) {
+        self.kilojoules += energy.kilojoules;
+        match energy.source {
+            EnergySource::Power => self.power_rides += 1,
+            EnergySource::Model => self.model_rides += 1,
+        }
+    }
+
+    pub fn kcal(&self) -> f64 {
+        kcal(self.kilojoules)
+    }
+
+    /// Whether measured and estimated rides are added together, making the
+    /// total less reliable than either alone.
+    pub fn mixed(&self) -> bool {
+        self.power_rides > 0 && self.model_rides > 0
+    }
+}
+
+/// Rides of one week or month added up.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeriodTotals {
+    pub totals: DayTotals,
+    pub moving_seconds: f64,
+    /// Name and kilometers of the longest ride.
+    pub longest: Option<(String, f64)>,
+}
+
+impl PeriodTotals {
+    fn add(&mut self, ride: &RideMetrics) {
+        self.totals = self.totals
+            + DayTotals {
+                rides: 1,
+                distance_km: ride.distance_km,
+                elevation_gain: ride.elevation_gain,
+            };
+        self.moving_seconds += ride.moving_seconds.unwrap_or_default();
+        if self
+            .longest
+            .as_ref()
+            .is_none_or(|&(_, km)| ride.distance_km > km)
+        {
+            self.longest = Some((ride.name.clone(), ride.distance_km));
+        }
+    }
+}
+
+/// Energy of the last [`ENERGY_TREND_WEEKS`] weeks and of as many weeks
+/// before.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EnergyTrend {
+    pub recent: EnergyTotals,
+    pub previous: EnergyTotals,
+}
+
+impl EnergyTrend {
+    /// Change of the recent weeks over the previous ones, in percent. `None`
+    /// when nothing was ridden before.
+    pub fn change_percent(&self) -> Option<f64> {
+        (self.previous.kilojoules > 0.0)
+            .then(|| (self.recent.kilojoules / self.previous.kilojoules - 1.0) * 100.0)
+    }
+
+    pub fn mixed(&self) -> bool {
+        let power = self.recent.power_rides + self.previous.power_rides;
+        let model = self.recent.model_rides + self.previous.model_rides;
+        power > 0 && model > 0
+    }
+}
+
+/// Aggregates over a set of activities, used as baselines to compare a ride
+/// against.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    rides: Vec<RideMetrics>,
+}
+
+impl Summary {
+    pub fn new<'a>(activities: impl IntoIterator<Item = &'a Activity>) -> Self {
+        Self {
+            rides: activities.into_iter().map(RideMetrics::new).collect(),
+        }
+    }
+
+    pub fn rides(&self) -> usize {
+        self.rides.len()
+    }
+
+    pub fn total_distance_km(&self) -> f64 {
+        self.rides.iter().map(|ride| ride.distance_km).sum()
+    }
+
+    pub fn total_elevation_gain(&self) -> f64 {
+        self.rides.iter().map(|ride| ride.elevation_gain).sum()
+    }
+
+    pub fn total_moving_seconds(&self) -> f64 {
+        self.rides
+            .iter()
+            .filter_map(|ride| ride.moving_seconds)
+            .sum()
+    }
+
+    /// The `n` longest rides, longest first.
+    pub fn longest_rides(&self, n: usize) -> Vec<&RideMetrics> {
+        let mut rides: Vec<&RideMetrics> = self.rides.iter().collect();
+        rides.sort_by(|a, b| b.distance_km.total_cmp(&a.distance_km));
+        rides.truncate(n);
+        rides
+    }
+
+    /// The ride with the most elevation gain.
+    pub fn biggest_climb(&self) -> Option<&RideMetrics> {
+        self.rides
+            .iter()
+            .max_by(|a, b| a.elevation_gain.total_cmp(&b.elevation_gain))
+    }
+
+    /// The ride that went the highest, the altitude record.
+    pub fn highest_point(&self) -> Option<(&RideMetrics, Peak)> {
+        self.rides
+            .iter()
+            .filter_map(|ride| Some((ride, ride.peak?)))
+            .max_by(|(_, a), (_, b)| a.elevation_m.total_cmp(&b.elevation_m))
+    }
+
+    /// Kilometers ridden on each day with at least one dated ride.
+    pub fn daily_distance_km(&self) -> BTreeMap<NaiveDate, f64> {
+        let mut days = BTreeMap::new();
+        for ride in &self.rides {
+            if let Some(date) = ride.date {
+                *days.entry(date).or_default() += ride.distance_km;
+            }
+        }
+        days
+    }
+
+    /// Rides, kilometers and elevation gain of each day with at least one
+    /// dated ride.
+    pub fn daily_totals(&self) -> BTreeMap<NaiveDate, DayTotals> {
+        let mut days: BTreeMap<NaiveDate, DayTotals> = BTreeMap::new();
+        for ride in &self.rides {
+            if let Some(date) = ride.date {
+                let day = days.entry(date).or_default();
+                day.rides += 1;
+                day.distance_km += ride.distance_km;
+                day.elevation_gain += ride.elevation_gain;
+            }
+        }
+        days
+    }
+
+    /// Column of [`Summary::start_slots`] of a ride started at `time`; a slot
+    /// includes its first hour, so 06:00 starts the 06–08 slot.
+    pub fn start_slot(time: NaiveTime) -> usize {
+        (time.hour() / START_SLOT_HOURS) as usize
+    }
+
+    /// Rides by the weekday and the time of day they started, in their own
+    /// time zone: a row per weekday from Monday, a column per
+    /// [`START_SLOT_HOURS`] from midnight. Undated rides are left out.
+    pub fn start_slots(&self) -> [[DayTotals; START_SLOTS]; 7] {
+        let mut slots = [[DayTotals::default(); START_SLOTS]; 7];
+        for ride in &self.rides {
+            if let (Some(date), Some(time)) = (ride.date, ride.time) {
+                let slot = &mut slots[date.weekday().num_days_from_monday() as usize]
+                    [Self::start_slot(time)];
+                slot.rides += 1;
+                slot.distance_km += ride.distance_km;
+                slot.elevation_gain += ride.elevation_gain;
+            }
+        }
+        slots
+    }
+
+    /// Kilometers ridden in each month of `year`, January first.
+    pub fn monthly_distance_km(&self, year: i32) -> [f64; 12] {
+        let mut months = [0.0; 12];
+        for (date, km) in self.daily_distance_km() {
+            if date.year() == year {
+                months[date.month0() as usize] += km;
+            }
+        }
+        months
+    }
+
+    /// Kilometers of each month from the first one with a dated ride to
+    /// that of `until`, keyed by their first day. Months without rides count
+    /// as zero; rides after `until` are left out.
+    pub fn monthly_series(&self, until: NaiveDate) -> Vec<(NaiveDate, f64)> {
+        let days = self.daily_distance_km();
+        let first_of_month = |date: NaiveDate| date - Days::new(u64::from(date.day0()));
+        let mut months = BTreeMap::new();
+        let mut month = days.keys().next().copied().map(first_of_month);
+        while let Some(first) = month.filter(|&first| first <= until) {
+            months.insert(first, 0.0);
+            month = first.checked_add_months(Months::new(1));
+        }
+        for (date, km) in days.range(..=until) {
+            if let Some(total) = months.get_mut(&first_of_month(*date)) {
+                *total += km;
+            }
+        }
+        months.into_iter().collect()
+    }
+
+    /// Energy of each week or month with at least one dated ride, keyed by
+    /// its first day.
+    pub fn energy_by_period(&self, period: Period) -> BTreeMap<NaiveDate, EnergyTotals> {
+        let mut periods: BTreeMap<NaiveDate, EnergyTotals> = BTreeMap::new();
+        for ride in &self.rides {
+            if let Some(date) = ride.date {
+                let totals = periods.entry(period.start(date)).or_default();
+                if let Some(energy) = ride.energy {
+                    totals.add(energy);
+                }
+            }
+        }
+        periods
+    }
+
+    /// Rides, distance, elevation gain, moving time and longest ride of each
+    /// week or month with at least one dated ride, keyed by its first day.
+    pub fn totals_by_period(&self, period: Period) -> BTreeMap<NaiveDate, PeriodTotals> {
+        let mut periods: BTreeMap<NaiveDate, PeriodTotals> = BTreeMap::new();
+        for ride in &self.rides {
+            if let Some(date) = ride.date {
+                periods.entry(period.start(date)).or_default().add(ride);
+            }
+        }
+        periods
+    }
+
+    /// Energy of the rides from `first` to `last`, both included.
+    pub fn energy_between(&self, first: NaiveDate, last: NaiveDate) -> EnergyTotals {
+        let mut totals = EnergyTotals::default();
+        for ride in &self.rides {
+            if let (Some(date), Some(energy)) = (ride.date, ride.energy)
+                && (first..=last).contains(&date)
+            {
+                totals.add(energy);
+            }
+        }
+        totals
+    }
+
+    /// Energy of the [`ENERGY_TREND_WEEKS`] weeks ending on `today` against
+    /// the weeks before them.
+    pub fn energy_trend(&self, today: NaiveDate) -> EnergyTrend {
+        let weeks = Days::new(7 * ENERGY_TREND_WEEKS);
+        let recent_first = today - weeks + Days::new(1);
+        EnergyTrend {
+            recent: self.energy_between(recent_first, today),
+            previous: self.energy_between(recent_first - weeks, recent_first - Days::new(1)),
+        }
+    }
+
+    /// Moving hours of each week with at least one dated ride, keyed by its
+    /// Monday, split by intensity in the order of
+    /// [`Intensity::ALL`](crate::intensity::Intensity::ALL).
+    /// Rides without moving time are left out.
+    pub fn weekly_intensity_hours(
+        &self,
+        thresholds: &IntensityThresholds,
+    ) -> BTreeMap<NaiveDate, [f64; 4]> {
+        let mut weeks: BTreeMap<NaiveDate, [f64; 4]> = BTreeMap::new();
+        for ride in &self.rides {
+            let (Some(date), Some(seconds)) = (ride.date, ride.moving_seconds) else {
+                continue;
+            };
+            if let Some(intensity) = classify(ride, &ride.sensors, thresholds) {
+                weeks.entry(Period::Week.start(date)).or_default()[intensity as usize] +=
+                    seconds / 3600.0;
+            }
+        }
+        weeks
+    }
+
+    /// Largest E such that E days had at least E km each.
+    pub fn eddington_number(&self) -> usize {
+        let mut days: Vec<f64> = self.daily_distance_km().into_values().collect();
+        days.sort_by(|a, b| b.total_cmp(a));
+        days.iter()
+            .enumerate()
+            .take_while(|&(i, &km)| km >= (i + 1) as f64)
+            .count()
+    }
+
+    /// Most consecutive days with a ride.
+    pub fn longest_streak(&self) -> usize {
+        let mut longest = 0;
+        let mut current = 0;
+        let mut previous: Option<NaiveDate> = None;
+        for date in self.daily_distance_km().into_keys() {
+            current = match previous {
+                Some(previous) if previous.succ_opt() == Some(date) => current + 1,
+                _ => 1,
+            };
+            longest = longest.max(current);
+            previous = Some(date);
+        }
+        longest
+    }
+
+    pub fn mean_distance_km(&self) -> Option<f64> {
+        mean(self.rides.iter().map(|ride| ride.distance_km))
+    }
+
+    pub fn mean_elevation_gain(&self) -> Option<f64> {
+        mean(self.rides.iter().map(|ride| ride.elevation_gain))
+    }
+
+    /// Ride distance below which a share `p` (in `0..=1`) of the rides fall,
+    /// interpolated between the two closest rides. `None` without rides.
+    pub fn distance_percentile(&self, p: f64) -> Option<f64> {
+        let mut distances: Vec<f64> = self.rides.iter().map(|ride| ride.distance_km).collect();
+        distances.sort_by(f64::total_cmp);
+        percentile(&distances, p)
+    }
+
+    pub fn median_distance_km(&self) -> Option<f64> {
+        self.distance_percentile(0.5)
+    }
+
+    /// Number of rides in each `bucket_km` range of distance, from zero to
+    /// the longest ride.
+    pub fn distance_histogram(&self, bucket_km: f64) -> Vec<u64> {
+        let mut histogram = vec![];
+        for ride in &self.rides {
+            let bucket = (ride.distance_km / bucket_km).floor().max(0.0) as usize;
+            if histogram.len() <= bucket {
+                histogram.resize(bucket + 1, 0);
+            }
+            histogram[bucket] += 1;
+        }
+
+        histogram
+    }
+
+    /// Mean average speed of the rides whose distance is within
+    /// [`SIMILAR_DISTANCE_TOLERANCE`] of `distance_km`, in km/h.
+    pub fn mean_speed_for_distance(&self, distance_km: f64) -> Option<f64> {
+        let tolerance = distance_km * SIMILAR_DISTANCE_TOLERANCE;
+        mean(
+            self.rides
+                .iter()
+                .filter(|ride| (ride.distance_km - distance_km).abs() <= tolerance)
+                .filter_map(|ride| ride.average_speed),
+        )
+    }
+}
+
+/// Mean of the values, `None` when there is none.
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// Grade buckets of the histogram: label and upper bound, in percent.
+pub const GRADE_BUCKETS: [(&str, f64); 6] = [
+    ("<-6%", -6.0),
+    ("-6..-2", -2.0),
+    ("-2..2", 2.0),
+    ("2..6", 6.0),
+    ("6..10", 10.0),
+    (">10%", f64::INFINITY),
+];
+
+/// Sections shorter than this are merged with the next points before their
+/// grade is computed, to smooth out GPS jitter.
+pub const GRADE_SECTION_M: f64 = 100.0;
+
+/// A stretch of track with a constant grade.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradedSection {
+    pub distance_m: f64,
+    pub grade_percent: f64,
+    pub ascent_m: f64,
+    /// Time spent above [`MOVING_MIN_SPEED`]; zero without timestamps.
+    pub moving_seconds: f64,
+}
+
+/// Iterate over the track in sections of at least `min_length_m`, skipping
+/// points without elevation.
+pub fn graded_sections(
+    points: &[TrackPoint],
+    min_length_m: f64,
+) -> impl Iterator<Item = GradedSection> + '_ {
+    let mut points = points.iter().filter(|point| point.elevation.is_some());
+    let mut start = points.next();
+    let mut previous = start;
+    let mut distance_m = 0.0;
+    let mut moving_seconds = 0.0;
+
+    std::iter::from_fn(move || {
+        for point in points.by_ref() {
+            let prev = previous?;
+            let step_m = Haversine.distance(
+                point!(x: prev.lon, y: prev.lat),
+                point!(x: point.lon, y: point.lat),
+            );
+            if let (Some(t1), Some(t2)) = (prev.time, point.time) {
+                let seconds = (t2 - t1).as_seconds_f64();
+                if seconds > 0.0 && step_m / seconds >= MOVING_MIN_SPEED {
+                    moving_seconds += seconds;
+                }
+            }
+            distance_m += step_m;
+            previous = Some(point);
+
+            if distance_m >= min_length_m {
+                let climb = point.elevation? - start?.elevation?;
+                let section = GradedSection {
+                    distance_m,
+                    grade_percent: climb / distance_m * 100.0,
+                    ascent_m: climb.max(0.0),
+                    moving_seconds,
+                };
+                start = Some(point);
+                distance_m = 0.0;
+                moving_seconds = 0.0;
+                return Some(section);
+            }
+        }
+        None
+    })
+}
+
+/// Equivalent flat kilometers of a ride, each graded section weighted by
+/// [`grade_factor`]. Stretches without elevation count as flat.
+pub fn grade_adjusted_km(activity: &Activity, model: GradeModel) -> f64 {
+    let extra_m: f64 = grade_adjustments(&activity.points, model)
+        .map(|(_, extra_m)| extra_m)
+        .sum();
+    activity.distance_km + extra_m / 1000.0
+}
+
+/// Length of each graded section, and the meters it adds to the flat
+/// distance; negative on descents. Both [`grade_adjusted_km`] and the ride
+/// plans weigh the grades with it.
+pub fn grade_adjustments(
+    points: &[TrackPoint],
+    model: GradeModel,
+) -> impl Iterator<Item = (f64, f64)> + '_ {
+    graded_sections(points, GRADE_SECTION_M).map(move |section| {
+        (
+            section.distance_m,
+            section.distance_m * (grade_factor(section.grade_percent, model) - 1.0),
+        )
+    })
+}
+
+/// Distance and ascent across all activities, by grade bucket.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GradeHistogram {
+    pub distance_km: [f64; GRADE_BUCKETS.len()],
+    pub ascent_m: [f64; GRADE_BUCKETS.len()],
+}
+
+impl GradeHistogram {
+    /// Add the distance and ascent of another histogram, such as the one of
+    /// a single ride.
+    pub fn add(&mut self, other: &GradeHistogram) {
+        for (total, value) in self.distance_km.iter_mut().zip(other.distance_km) {
+            *total += value;
+        }
+        for (total, value) in self.ascent_m.iter_mut().zip(other.ascent_m) {
+            *total += value;
+        }
+    }
+}
+
+pub fn grade_histogram<'a>(activities: impl IntoIterator<Item = &'a Activity>) -> GradeHistogram {
+    let mut histogram = GradeHistogram::default();
+
+    for activity in activities {
+        for section in graded_sections(&activity.points, GRADE_SECTION_M) {
+            let bucket = GRADE_BUCKETS
+                .iter()
+                .position(|&(_, upper)| section.grade_percent < upper)
+                .unwrap_or(GRADE_BUCKETS.len() - 1);
+            histogram.distance_km[bucket] += section.distance_m / 1000.0;
+            histogram.ascent_m[bucket] += section.ascent_m;
+        }
+    }
+
+    histogram
+}
+
+/// Rides and distance recorded by one device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceUsage {
+    /// See [`Activity::device`].
+    pub device: String,
+    pub rides: usize,
+    pub distance_km: f64,
+}
+
+/// Rides and distance per device, most used first, then by distance and
+/// name.
+pub fn device_usage<'a>(activities: impl IntoIterator<Item = &'a Activity>) -> Vec<DeviceUsage> {
+    let mut devices: BTreeMap<&str, DeviceUsage> = BTreeMap::new();
+    for activity in activities {
+        let usage = devices
+            .entry(activity.device())
+            .or_insert_with(|| DeviceUsage {
+                device: activity.device().to_string(),
+                rides: 0,
+                distance_km: 0.0,
+            });
+        usage.rides += 1;
+        usage.distance_km += activity.distance_km;
+    }
+
+    let mut devices: Vec<DeviceUsage> = devices.into_values().collect();
+    devices.sort_by(|a, b| {
+        b.rides
+            .cmp(&a.rides)
+            .then(b.distance_km.total_cmp(&a.distance_km))
+            .then_with(|| a.device.cmp(&b.device))
+    });
+    devices
+}
+
+/// Distance of each of the `weeks` weeks up to the one of `today`, oldest
+/// first, in km. Rides without a date or after the week of `today` are left
+/// out.
+pub fn weekly_distance_km<'a>(
+    activities: impl IntoIterator<Item = &'a Activity>,
+    today: NaiveDate,
+    weeks: usize,
+) -> Vec<f64> {
+    let mut distances = vec![0.0; weeks];
+    let this_week = Period::Week.start(today);
+    for activity in activities {
+        let Some(date) = activity.start.map(|start| start.date_naive()) else {
+            continue;
+        };
+        let ago = (this_week - Period::Week.start(date)).num_weeks();
+        match usize::try_from(ago) {
+            Ok(ago) if ago < weeks => distances[weeks - 1 - ago] += activity.distance_km,
+            _ => {}
+        }
+    }
+    distances
+}
+
+/// Days of the weekend when not configured.
+pub const DEFAULT_WEEKEND: [Weekday; 2] = [Weekday::Sat, Weekday::Sun];
+
+/// Rides of one kind of day, commutes apart.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CommuteSplit {
+    pub commute: DayTotals,
+    pub leisure: DayTotals,
+}
+
+impl CommuteSplit {
+    pub fn total(&self) -> DayTotals {
+        self.commute + self.leisure
+    }
+}
+
+/// Rides of the weekdays and of the weekend, each split into commutes and
+/// leisure.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WeekSplit {
+    pub weekdays: CommuteSplit,
+    pub weekend: CommuteSplit,
+    /// Rides without a start time, on neither.
+    pub undated: CommuteSplit,
+}
+
+impl WeekSplit {
+    pub fn total(&self) -> DayTotals {
+        self.weekdays.total() + self.weekend.total() + self.undated.total()
+    }
+
+    /// The rides that are not commutes, whatever the day.
+    pub fn leisure(&self) -> DayTotals {
+        self.weekdays.leisure + self.weekend.leisure + self.undated.leisure
+    }
+
+    pub fn has_commutes(&self) -> bool {
+        [self.weekdays, self.weekend, self.undated]
+            .iter()
+            .any(|split| split.commute.rides > 0)
+    }
+
+    /// The lines of a table of the split, as (name, whether it is a part of
+    /// the last line that is not, totals): the kinds of day with rides, each
+    /// split into commutes and leisure when some rides are commutes, then
+    /// all the leisure.
+    pub fn rows(&self) -> Vec<(&'static str, bool, DayTotals)> {
+        let commutes = self.has_commutes();
+        let mut rows = vec![];
+        for (name, day) in [
+            ("Weekdays", self.weekdays),
+            ("Weekends", self.weekend),
+            ("Undated", self.undated),
+        ] {
+            if day.total().rides == 0 {
+                continue;
+            }
+            rows.push((name, false, day.total()));
+            if commutes {
+                rows.push(("commutes", true, day.commute));
+                rows.push(("leisure", true, day.leisure));
+            }
+        }
+        if commutes {
+            rows.push(("All leisure", false, self.leisure()));
+        }
+        rows
+    }
+
+    /// The split with its distances and elevation gains rounded to
+    /// `decimals` by [`round_to_total`], so that the parts shown add up to the
+    /// totals shown.
+    pub fn rounded(&self, decimals: i32) -> Self {
+        let mut rounded = *self;
+        let parts = rounded.parts_mut();
+        let distances = round_to_total(&parts.each_ref().map(|part| part.distance_km), decimals);
+        let elevations =
+            round_to_total(&parts.each_ref().map(|part| part.elevation_gain), decimals);
+        for ((part, distance_km), elevation_gain) in
+            parts.into_iter().zip(distances).zip(elevations)
+        {
+            part.distance_km = distance_km;
+            part.elevation_gain = elevation_gain;
+        }
+        rounded
+    }
+
+    fn parts_mut(&mut self) -> [&mut DayTotals; 6] {
+        [
+            &mut self.weekdays.commute,
+            &mut self.weekdays.leisure,
+            &mut self.weekend.commute,
+            &mut self.weekend.leisure,
+            &mut self.undated.commute,
+            &mut self.undated.leisure,
+        ]
+    }
+}
+
+/// Rides by whether they started on a weekday or on a day of `weekend`, in
+/// their own time zone, and by whether they are commutes.
+pub fn week_split<'a>(
+    rides: impl IntoIterator<Item = (&'a Activity, bool)>,
+    weekend: &[Weekday],
+) -> WeekSplit {
+    let mut split = WeekSplit::default();
+    for (activity, commute) in rides {
+        let day = match activity.start {
+            Some(start) if weekend.contains(&start.weekday()) => &mut split.weekend,
+            Some(_) => &mut split.weekdays,
+            None => &mut split.undated,
+        };
+        let totals = if commute {
+            &mut day.commute
+        } else {
+            &mut day.leisure
+        };
+        totals.rides += 1;
+        totals.distance_km += activity.distance_km;
+        totals.elevation_gain += activity.elevation_gain;
+    }
+    split
+}
+
+/// `values` rounded to `decimals` so that they add up to their total rounded
+/// alike: the largest remainders are rounded up first, the others down.
+pub fn round_to_total(values: &[f64], decimals: i32) -> Vec<f64> {
+    let scale = 10f64.powi(decimals);
+    let total = (values.iter().sum::<f64>() * scale).round();
+    let mut units: Vec<f64> = values.iter().map(|value| (value * scale).floor()).collect();
+    let missing = (total - units.iter().sum::<f64>()).max(0.0) as usize;
+    let mut by_remainder: Vec<usize> = (0..values.len()).collect();
+    by_remainder.sort_by(|&a, &b| {
+        let remainder = |i: usize| values[i] * scale - units[i];
+        remainder(b).total_cmp(&remainder(a))
+    });
+    for i in by_remainder.into_iter().take(missing) {
+        units[i] += 1.0;
+    }
+    units.into_iter().map(|units| units / scale).collect()
+}
+
+/// Sections at least this steep, up or down, are climbs or descents, in
+/// percent.
+pub const TERRAIN_GRADE_PERCENT: f64 = 2.0;
+
+/// Kind of ground a part of a ride is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Terrain {
+    Climbing,
+    Flat,
+    Descending,
+    /// The ride has no elevation data.
+    Unknown,
+}
+
+impl fmt::Display for Terrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Climbing => "climbing",
+            Self::Flat => "flat",
+            Self::Descending => "descending",
+            Self::Unknown => "unknown terrain",
+        })
+    }
+}
+
+/// Distance and moving time of a ride on one kind of terrain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainSplit {
+    pub terrain: Terrain,
+    pub distance_km: f64,
+    /// `None` when the points have no timestamps.
+    pub moving_seconds: Option<f64>,
+}
+
+impl TerrainSplit {
+    /// Average moving speed, in km/h.
+    pub fn average_speed(&self) -> Option<f64> {
+        self.moving_seconds
+            .filter(|&seconds| seconds > 0.0)
+            .map(|seconds| self.distance_km / (seconds / 3600.0))
+    }
+}
+
+/// Climbing, flat and descending parts of a ride, split by
+/// [`TERRAIN_GRADE_PERCENT`] over [`GRADE_SECTION_M`] sections, so the
+/// climbing speed is not inflated by the descents. Rides without elevation
+/// have a single [`Terrain::Unknown`] row.
+pub fn terrain_breakdown(activity: &Activity) -> Vec<TerrainSplit> {
+    let timed = activity.moving_seconds().is_some();
+    let mut splits =
+        [Terrain::Climbing, Terrain::Flat, Terrain::Descending].map(|terrain| TerrainSplit {
+            terrain,
+            distance_km: 0.0,
+            moving_seconds: timed.then_some(0.0),
+        });
+
+    let mut graded = false;
+    for section in graded_sections(&activity.points, GRADE_SECTION_M) {
+        graded = true;
+        let split = if section.grade_percent >= TERRAIN_GRADE_PERCENT {
+            &mut splits[0]
+        } else if section.grade_percent <= -TERRAIN_GRADE_PERCENT {
+            &mut splits[2]
+        } else {
+            &mut splits[1]
+        };
+        split.distance_km += section.distance_m / 1000.0;
+        if let Some(seconds) = &mut split.moving_seconds {
+            *seconds += section.moving_seconds;
+        }
+    }
+
+    if !graded {
+        return vec![TerrainSplit {
+            terrain: Terrain::Unknown,
+            distance_km: activity.distance_km,
+            moving_seconds: activity.moving_seconds(),
+        }];
+    }
+    splits.to_vec()
+}
+
+/// Lower bounds of the moderate, steep and very steep grades, in percent.
+/// Gentler climbs and every descent are flat.
+pub const STEEPNESS_GRADE_PERCENT: [f64; 3] = [4.0, 7.0, 10.0];
+
+/// How hard a stretch of road is to climb, as colored on the elevation
+/// profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Steepness {
+    Flat,
+    Moderate,
+    Steep,
+    VerySteep,
+}
+
+impl Steepness {
+    pub const ALL: [Steepness; 4] = [
+        Steepness::Flat,
+        Steepness::Moderate,
+        Steepness::Steep,
+        Steepness::VerySteep,
+    ];
+
+    pub fn of_grade(grade_percent: f64) -> Self {
+        let class = STEEPNESS_GRADE_PERCENT
+            .iter()
+            .filter(|&&bound| grade_percent >= bound)
+            .count();
+        Self::ALL[class]
+    }
+
+    /// Range of grades, such as `4–7%`.
+    pub fn label(self) -> &'static str {
+        match self {
+            Steepness::Flat => "<4%",
+            Steepness::Moderate => "4–7%",
+            Steepness::Steep => "7–10%",
+            Steepness::VerySteep => ">10%",
+        }
+    }
+}
+
+/// Stretches of an elevation profile of `(km, m)` points with the same
+/// steepness, over sections of at least [`GRADE_SECTION_M`]. Each stretch
+/// ends on the first point of the next, so that they draw a continuous line.
+pub fn steepness_runs(profile: &[(f64, f64)]) -> Vec<(Steepness, &[(f64, f64)])> {
+    // Steepness and bounds of the runs, in points of the profile.
+    let mut runs: Vec<(Steepness, usize, usize)> = vec![];
+    let mut start = 0;
+    for (i, &(km, elevation)) in profile.iter().enumerate().skip(1) {
+        let (start_km, start_elevation) = profile[start];
+        let distance_m = (km - start_km) * 1_000.0;
+        if distance_m < GRADE_SECTION_M && i + 1 < profile.len() || distance_m <= 0.0 {
+            continue;
+        }
+        let steepness = Steepness::of_grade((elevation - start_elevation) / distance_m * 100.0);
+        match runs.last_mut() {
+            Some((last, _, end)) if *last == steepness => *end = i,
+            _ => runs.push((steepness, start, i)),
+        }
+        start = i;
+    }
+    runs.into_iter()
+        .map(|(steepness, start, end)| (steepness, &profile[start..=end]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(start_point: (f64, f64), distance_km: f64) -> Activity {
+        Activity {
+            start_point: Some(start_point),
+            distance_km,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn recency_follows_the_calendar() {
+        let day = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+        // A Wednesday.
+        let today = day(7, 3);
+        assert_eq!(Recency::of(day(7, 1), today), Recency::ThisWeek);
+        assert_eq!(Recency::of(day(7, 5), today), Recency::ThisWeek);
+        // Last week, but still this month, then last month.
+        assert_eq!(Recency::of(day(7, 1), day(7, 8)), Recency::ThisMonth);
+        assert_eq!(Recency::of(day(6, 30), today), Recency::ThisYear);
+        assert_eq!(
+            Recency::of(NaiveDate::from_ymd_opt(2023, 12, 31).unwrap(), today),
+            Recency::Older
+        );
+    }
+
+    #[test]
+    fn climbs_count_double_at_ten_percent() {
+        // Ten sections of ~111 m at 10%.
+        let points: Vec<TrackPoint> = (0..=10)
+            .map(|i| TrackPoint {
+                lat: 45.0 + i as f64 * 0.001,
+                lon: 5.0,
+                elevation: Some(i as f64 * 11.12),
+                ..Default::default()
+            })
+            .collect();
+        let climb = Activity {
+            distance_km: 1.112,
+            points,
+            ..Default::default()
+        };
+        let adjusted = grade_adjusted_km(&climb, GradeModel::Piecewise);
+        assert!((adjusted - 2.224).abs() < 0.01, "{adjusted}");
+
+        let no_elevation = Activity {
+            distance_km: 1.112,
+            points: climb
+                .points
+                .iter()
+                .map(|p| TrackPoint {
+                    elevation: None,
+                    ..*p
+                })
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            grade_adjusted_km(&no_elevation, GradeModel::Polynomial),
+            1.112
+        );
+    }
+
+    #[test]
+    fn clusters_absorb_gps_scatter() {
+        // ~0.001° of latitude is ~110 m.
+        let activities = [
+            activity((45.0, 5.0), 30.0),
+            activity((45.2, 5.2), 10.0),
+            activity((45.001, 5.0), 40.0),
+            activity((44.999, 5.001), 50.0),
+            activity((45.2008, 5.2), 20.0),
+        ];
+
+        let clusters = start_location_clusters(&activities, CLUSTER_RADIUS_M);
+
+        assert_eq!(clusters.len(), 2);
+        assert_eq!((clusters[0].rides, clusters[0].distance_km), (3, 120.0));
+        assert_eq!((clusters[1].rides, clusters[1].distance_km), (2, 30.0));
+    }
+
+    #[test]
+    fn speed_baseline_only_uses_rides_of_similar_length() {
+        // 0.1° of latitude is ~11.1 km, ridden in 30 minutes.
+        let ride = |lat_delta: f64| {
+            let start = chrono::DateTime::parse_from_rfc3339("2024-06-01T07:00:00Z").unwrap();
+            let points = [(0.0, 0), (lat_delta, 30)].map(|(lat, minutes)| TrackPoint {
+                lat: 45.0 + lat,
+                lon: 5.0,
+                time: Some(start + chrono::Duration::minutes(minutes)),
+                ..Default::default()
+            });
+            Activity {
+                distance_km: lat_delta * 111.2,
+                points: points.to_vec(),
+                ..Default::default()
+            }
+        };
+        let activities = [ride(0.1), ride(0.11), ride(0.3)];
+
+        let summary = Summary::new(&activities);
+
+        let speed = summary.mean_speed_for_distance(11.1).unwrap();
+        assert!((speed - 23.4).abs() < 0.1, "speed = {speed}");
+        assert_eq!(summary.mean_speed_for_distance(100.0), None);
+        assert!((summary.mean_distance_km().unwrap() - 18.90).abs() < 0.01);
+        assert_eq!(Summary::default().mean_elevation_gain(), None);
+    }
+
+    /// A ride on `date` whose points were released, with the energy kept.
+    fn ride_with_energy(date: &str, kilojoules: f64, source: EnergySource) -> Activity {
+        Activity {
+            start: Some(
+                chrono::DateTime::parse_from_rfc3339(&format!("{date}T07:00:00Z")).unwrap(),
+            ),
+            released_energy: Some(Energy { kilojoules, source }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn energy_adds_up_by_week_and_month() {
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let activities = [
+            // Sunday, then Monday and Wednesday of the next week.
+            ride_with_energy("2024-06-02", 500.0, EnergySource::Model),
+            ride_with_energy("2024-06-03", 1_000.0, EnergySource::Power),
+            ride_with_energy("2024-06-05", 800.0, EnergySource::Power),
+            ride_with_energy("2024-07-01", 600.0, EnergySource::Model),
+        ];
+
+        let summary = Summary::new(&activities);
+
+        let weeks = summary.energy_by_period(Period::Week);
+        assert_eq!(
+            weeks.keys().copied().collect::<Vec<_>>(),
+            [date("2024-05-27"), date("2024-06-03"), date("2024-07-01"),]
+        );
+        let week = weeks[&date("2024-06-03")];
+        assert_eq!(
+            (week.kilojoules, week.power_rides, week.model_rides),
+            (1_800.0, 2, 0)
+        );
+        assert!(!week.mixed());
+        // 1800 kJ at 24% efficiency is 7500 kJ of food, or 1792.5 kcal.
+        assert!((week.kcal() - 1_792.54).abs() < 0.01);
+
+        let months = summary.energy_by_period(Period::Month);
+        let june = months[&date("2024-06-01")];
+        assert_eq!(june.kilojoules, 2_300.0);
+        assert!(june.mixed());
+        assert_eq!(months[&date("2024-07-01")].kilojoules, 600.0);
+    }
+
+    #[test]
+    fn rides_add_up_by_month() {
+        let ride = |name: &str, date: &str, distance_km: f64, moving: f64| Activity {
+            name: name.to_string(),
+            start: Some(
+                chrono::DateTime::parse_from_rfc3339(&format!("{date}T07:00:00Z")).unwrap(),
+            ),
+            released_moving_seconds: Some(moving * 60.0),
+            distance_km,
+            elevation_gain: distance_km * 10.0,
+            ..Default::default()
+        };
+        let activities = [
+            ride("Col", "2024-06-02", 80.0, 240.0),
+            ride("Lake", "2024-06-20", 40.0, 90.0),
+            ride("Coast", "2024-07-01", 60.0, 150.0),
+            Activity::default(),
+        ];
+
+        let months = Summary::new(&activities).totals_by_period(Period::Month);
+        let june = &months[&NaiveDate::from_ymd_opt(2024, 6, 1).unwrap()];
+        assert_eq!(
+            june.totals,
+            DayTotals {
+                rides: 2,
+                distance_km: 120.0,
+                elevation_gain: 1_200.0,
+            }
+        );
+        assert_eq!(june.moving_seconds, 330.0 * 60.0);
+        assert_eq!(june.longest, Some(("Col".to_string(), 80.0)));
+        // Undated rides belong to no month.
+        assert_eq!(months.len(), 2);
+    }
+
+    #[test]
+    fn the_profile_is_split_by_steepness() {
+        // Every 125 m: flat for 250 m, then 8% for 250 m, then down again.
+        let profile: Vec<(f64, f64)> = [100.0, 100.0, 100.0, 110.0, 120.0, 110.0, 100.0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, elevation)| (i as f64 * 0.125, elevation))
+            .collect();
+
+        let runs = steepness_runs(&profile);
+
+        let bounds: Vec<(Steepness, f64, f64)> = runs
+            .iter()
+            .map(|(steepness, run)| (*steepness, run[0].0, run[run.len() - 1].0))
+            .collect();
+        assert_eq!(
+            bounds,
+            [
+                (Steepness::Flat, 0.0, 0.25),
+                (Steepness::Steep, 0.25, 0.5),
+                (Steepness::Flat, 0.5, 0.75),
+            ]
+        );
+        assert_eq!(Steepness::of_grade(-12.0), Steepness::Flat);
+        assert_eq!(Steepness::of_grade(10.0), Steepness::VerySteep);
+        assert!(steepness_runs(&profile[..1]).is_empty());
+    }
+
+    #[test]
+    fn intensity_hours_add_up_by_week() {
+        // Rides whose points were released, `hours` long at `km_h`.
+        let ride = |date: &str, hours: f64, km_h: f64, heart_rate: Option<f64>| Activity {
+            distance_km: hours * km_h,
+            released_moving_seconds: Some(hours * 3600.0),
+            released_sensors: SensorSummary {
+                average_power: None,
+                average_heart_rate: heart_rate,
+            },
+            ..ride_with_energy(date, 0.0, EnergySource::Model)
+        };
+        let activities = [
+            ride("2024-06-03", 1.0, 18.0, None),
+            ride("2024-06-05", 2.0, 27.0, None),
+            // 171 bpm is 90% of the default 190: hard, at any speed.
+            ride("2024-06-09", 1.5, 18.0, Some(171.0)),
+            ride("2024-06-10", 0.5, 32.0, None),
+        ];
+
+        let weeks =
+            Summary::new(&activities).weekly_intensity_hours(&IntensityThresholds::default());
+
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        assert_eq!(
+            weeks,
+            BTreeMap::from([
+                (date("2024-06-03"), [1.0, 0.0, 2.0, 1.5]),
+                (date("2024-06-10"), [0.0, 0.0, 0.0, 0.5]),
+            ])
+        );
+    }
+
+    #[test]
+    fn rides_are_bucketed_by_weekday_and_start_time() {
+        let time = |s: &str| NaiveTime::parse_from_str(s, "%H:%M:%S").unwrap();
+        assert_eq!(Summary::start_slot(time("00:00:00")), 0);
+        assert_eq!(Summary::start_slot(time("05:59:59")), 2);
+        assert_eq!(Summary::start_slot(time("06:00:00")), 3);
+        assert_eq!(Summary::start_slot(time("23:59:59")), START_SLOTS - 1);
+
+        let ride = |start: &str, distance_km: f64| Activity {
+            start: Some(chrono::DateTime::parse_from_rfc3339(start).unwrap()),
+            distance_km,
+            ..Default::default()
+        };
+        let activities = [
+            // Saturdays, the first at 06:00 sharp.
+            ride("2024-06-01T06:00:00+02:00", 80.0),
+            ride("2024-06-08T07:59:00+02:00", 60.0),
+            // Tuesday evening, in its own time zone rather than UTC.
+            ride("2024-06-04T18:30:00+02:00", 30.0),
+            Activity::default(),
+        ];
+
+        let slots = Summary::new(&activities).start_slots();
+
+        assert_eq!(slots[5][3].rides, 2);
+        assert_eq!(slots[5][3].distance_km, 140.0);
+        assert_eq!(slots[1][9].rides, 1);
+        let rides: usize = slots.iter().flatten().map(|slot| slot.rides).sum();
+        assert_eq!(rides, 3);
+    }
+
+    #[test]
+    fn the_energy_trend_compares_four_weeks_with_the_four_before() {
+        let activities = [
+            // The previous four weeks run from May 5 to June 1.
+            ride_with_energy("2024-05-04", 9_000.0, EnergySource::Model),
+            ride_with_energy("2024-05-05", 1_000.0, EnergySource::Model),
+            ride_with_energy("2024-06-01", 1_000.0, EnergySource::Model),
+            // The recent ones from June 2 to June 29, today.
+            ride_with_energy("2024-06-02", 1_500.0, EnergySource::Model),
+            ride_with_energy("2024-06-29", 1_500.0, EnergySource::Power),
+            ride_with_energy("2024-06-30", 9_000.0, EnergySource::Model),
+        ];
+        let today = NaiveDate::from_ymd_opt(2024, 6, 29).unwrap();
+
+        let trend = Summary::new(&activities).energy_trend(today);
+
+        assert_eq!(trend.previous.kilojoules, 2_000.0);
+        assert_eq!(trend.recent.kilojoules, 3_000.0);
+        assert_eq!(trend.change_percent(), Some(50.0));
+        assert!(trend.mixed());
+        assert_eq!(EnergyTrend::default().change_percent(), None);
+    }
+
+    #[test]
+    fn percentiles_of_few_rides() {
+        let summary = |distances: &[f64]| {
+            let activities: Vec<Activity> = distances
+                .iter()
+                .map(|&distance_km| activity((45.0, 5.0), distance_km))
+                .collect();
+            Summary::new(&activities)
+        };
+
+        assert_eq!(summary(&[]).median_distance_km(), None);
+        assert_eq!(summary(&[42.0]).distance_percentile(0.25), Some(42.0));
+        assert_eq!(summary(&[10.0, 30.0]).median_distance_km(), Some(20.0));
+        let three = summary(&[50.0, 10.0, 30.0]);
+        assert_eq!(three.distance_percentile(0.25), Some(20.0));
+        assert_eq!(three.median_distance_km(), Some(30.0));
+        assert_eq!(three.distance_percentile(0.75), Some(40.0));
+        assert_eq!(
+            three.distance_histogram(RIDE_LENGTH_BUCKET_KM),
+            [0, 1, 0, 1, 0, 1]
+        );
+    }
+
+    #[test]
+    fn histogram_buckets_sections_by_grade() {
+        // 0.001° of latitude is ~111 m: a flat section then a 9% climb.
+        let points =
+            [(45.0, 100.0), (45.001, 100.0), (45.002, 110.0)].map(|(lat, ele)| TrackPoint {
+                lat,
+                lon: 5.0,
+                elevation: Some(ele),
+                ..Default::default()
+            });
+        let activity = Activity {
+            points: points.to_vec(),
+            ..Default::default()
+        };
+
+        let histogram = grade_histogram([&activity]);
+
+        assert!((histogram.distance_km[2] - 0.111).abs() < 0.001);
+        assert!((histogram.distance_km[4] - 0.111).abs() < 0.001);
+        assert_eq!(histogram.ascent_m, [0.0, 0.0, 0.0, 0.0, 10.0, 0.0]);
+    }
+
+    #[test]
+    fn usage_per_device() {
+        let ride = |creator: Option<&str>, distance_km| Activity {
+            creator: creator.map(str::to_string),
+            distance_km,
+            ..Default::default()
+        };
+        let activities = [
+            ride(Some("Garmin Edge 530"), 40.0),
+            ride(None, 10.0),
+            ride(Some("Wahoo ELEMNT"), 100.0),
+            ride(Some("Garmin Edge 530"), 20.0),
+        ];
+
+        let usage: Vec<(String, usize, f64)> = device_usage(&activities)
+            .into_iter()
+            .map(|usage| (usage.device, usage.rides, usage.distance_km))
+            .collect();
+
+        assert_eq!(
+            usage,
+            [
+                ("Garmin Edge 530".to_string(), 2, 60.0),
+                ("Wahoo ELEMNT".to_string(), 1, 100.0),
+                ("unknown".to_string(), 1, 10.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn terrain_splits_climbs_from_descents() {
+        // Up ~111 m sections at 9% in a minute each, then down in 20 s each,
+        // with a flat section at 20 km/h between them.
+        let start = chrono::DateTime::parse_from_rfc3339("2024-06-01T07:00:00Z").unwrap();
+        let mut seconds = 0;
+        let mut points = vec![];
+        for (i, (ele, duration)) in [
+            (0.0, 0),
+            (10.0, 60),
+            (20.0, 60),
+            (20.0, 20),
+            (10.0, 20),
+            (0.0, 20),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            seconds += duration;
+            points.push(TrackPoint {
+                lat: 45.0 + i as f64 * 0.001,
+                lon: 5.0,
+                elevation: Some(ele),
+                time: Some(start + chrono::Duration::seconds(seconds)),
+                ..Default::default()
+            });
+        }
+        let activity = Activity {
+            points,
+            ..Default::default()
+        };
+
+        let splits = terrain_breakdown(&activity);
+
+        let terrains: Vec<Terrain> = splits.iter().map(|split| split.terrain).collect();
+        assert_eq!(
+            terrains,
+            [Terrain::Climbing, Terrain::Flat, Terrain::Descending]
+        );
+        let speeds: Vec<f64> = splits
+            .iter()
+            .map(|split| split.average_speed().unwrap().round())
+            .collect();
+        assert_eq!(speeds, [7.0, 20.0, 20.0]);
+        assert!((splits[0].distance_km - 0.222).abs() < 0.001);
+    }
+
+    #[test]
+    fn terrain_without_elevation_is_unknown() {
+        let activity = Activity {
+            distance_km: 12.0,
+            points: vec![TrackPoint::default(); 3],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            terrain_breakdown(&activity),
+            [TerrainSplit {
+                terrain: Terrain::Unknown,
+                distance_km: 12.0,
+                moving_seconds: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn monthly_trend() {
+        let ride = |date: &str, distance_km: f64| Activity {
+            start: Some(
+                chrono::DateTime::parse_from_rfc3339(&format!("{date}T08:00:00Z")).unwrap(),
+            ),
+            distance_km,
+            ..Default::default()
+        };
+        // Nothing in April, and a ride on the incomplete current month.
+        let summary = Summary::new(&[
+            ride("2024-02-10", 100.0),
+            ride("2024-03-05", 80.0),
+            ride("2024-03-20", 60.0),
+            ride("2024-05-01", 300.0),
+            ride("2024-06-02", 40.0),
+        ]);
+        let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+        let series = summary.monthly_series(date(5, 31));
+        let months: Vec<(u32, f64)> = series
+            .iter()
+            .map(|(first, km)| (first.month(), *km))
+            .collect();
+        assert_eq!(months, [(2, 100.0), (3, 140.0), (4, 0.0), (5, 300.0)]);
+        assert_eq!(summary.monthly_series(date(6, 1)).len(), 5);
+        assert!(Summary::default().monthly_series(date(6, 1)).is_empty());
+
+        let points: Vec<(f64, f64)> = months
+            .iter()
+            .enumerate()
+            .map(|(i, &(_, km))| (i as f64, km))
+            .collect();
+        let trend = LinearTrend::fit(&points).unwrap();
+        assert!((trend.slope - 46.0).abs() < 1e-9, "{trend:?}");
+        assert!((trend.at(0.0) - 66.0).abs() < 1e-9, "{trend:?}");
+        assert_eq!(LinearTrend::fit(&points[..1]), None);
+
+        let values: Vec<f64> = months.iter().map(|&(_, km)| km).collect();
+        assert_eq!(
+            moving_average(&values, MOVING_AVERAGE_MONTHS),
+            [None, None, Some(80.0), Some(440.0 / 3.0)]
+        );
+    }
+
+    #[test]
+    fn weekdays_and_weekends_add_up_to_the_totals() {
+        let ride = |start: Option<&str>, distance_km: f64, elevation_gain: f64| Activity {
+            start: start.map(|start| chrono::DateTime::parse_from_rfc3339(start).unwrap()),
+            distance_km,
+            elevation_gain,
+            ..Default::default()
+        };
+        let rides = [
+            // A Friday evening in New York, already Saturday in UTC.
+            (ride(Some("2024-06-07T21:30:00-05:00"), 12.34, 40.4), true),
+            (ride(Some("2024-06-08T09:00:00+02:00"), 61.26, 720.6), false),
+            (
+                ride(Some("2024-06-09T09:00:00+02:00"), 80.45, 1_010.2),
+                false,
+            ),
+            (ride(Some("2024-06-10T08:00:00+02:00"), 12.45, 40.3), true),
+            (ride(Some("2024-06-12T18:00:00+02:00"), 30.45, 150.3), false),
+            (ride(None, 20.26, 0.0), false),
+        ];
+        let all = || rides.iter().map(|(activity, commute)| (activity, *commute));
+        let summary = Summary::new(rides.iter().map(|(activity, _)| activity));
+
+        let split = week_split(all(), &DEFAULT_WEEKEND);
+        assert_eq!(split.weekdays.commute.rides, 2);
+        assert_eq!(split.weekdays.leisure.rides, 1);
+        assert_eq!(split.weekend.total().rides, 2);
+        assert_eq!(split.undated.total().rides, 1);
+        assert!(split.has_commutes());
+        assert_eq!(split.leisure().rides, 4);
+        let total = split.total();
+        assert_eq!(total.rides, summary.rides());
+        assert!((total.distance_km - summary.total_distance_km()).abs() < 1e-9);
+        assert!((total.elevation_gain - summary.total_elevation_gain()).abs() < 1e-9);
+
+        // The weekend of Friday and Saturday takes the New York ride.
+        let split = week_split(all(), &[Weekday::Fri, Weekday::Sat]);
+        assert_eq!(split.weekend.commute.rides, 1);
+        assert_eq!(split.weekend.leisure.rides, 1);
+        assert_eq!(split.weekdays.total().rides, 3);
+
+        // Rounded alone, the parts would add up to 216 km, not 217.
+        let rounded = split.rounded(0);
+        let parts = [
+            rounded.weekdays.commute,
+            rounded.weekdays.leisure,
+            rounded.weekend.commute,
+            rounded.weekend.leisure,
+            rounded.undated.commute,
+            rounded.undated.leisure,
+        ];
+        let distance: f64 = parts.iter().map(|part| part.distance_km).sum();
+        let elevation: f64 = parts.iter().map(|part| part.elevation_gain).sum();
+        assert_eq!(distance, summary.total_distance_km().round());
+        assert_eq!(elevation, summary.total_elevation_gain().round());
+        assert_eq!(rounded.total().distance_km, distance);
+        assert!(parts.iter().all(|part| part.distance_km.fract() == 0.0));
+
+        let names: Vec<_> = rounded.rows().iter().map(|&(name, _, _)| name).collect();
+        assert_eq!(
+            names,
+            [
+                "Weekdays",
+                "commutes",
+                "leisure",
+                "Weekends",
+                "commutes",
+                "leisure",
+                "Undated",
+                "commutes",
+                "leisure",
+                "All leisure"
+            ]
+        );
+
+        assert_eq!(week_split([], &DEFAULT_WEEKEND), WeekSplit::default());
+        assert!(WeekSplit::default().rows().is_empty());
+    }
+
+    #[test]
+    fn rounded_parts_keep_their_total() {
+        assert_eq!(round_to_total(&[0.4, 0.4, 0.4], 0), [1.0, 0.0, 0.0]);
+        assert_eq!(round_to_total(&[1.24, 2.23, 3.25], 1), [1.2, 2.2, 3.3]);
+        assert_eq!(round_to_total(&[2.0, 3.0], 0), [2.0, 3.0]);
+        assert!(round_to_total(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn yearly_review_figures() {
+        let ride = |date: &str, distance_km: f64, elevation_gain: f64| Activity {
+            name: format!("{date} ride"),
+            start: Some(
+                chrono::DateTime::parse_from_rfc3339(&format!("{date}T08:00:00Z")).unwrap(),
+            ),
+            distance_km,
+            elevation_gain,
+            ..Default::default()
+        };
+        let mut activities = [
+            ride("2024-03-01", 3.0, 10.0),
+            ride("2024-03-02", 1.0, 900.0),
+            ride("2024-03-02", 2.0, 20.0),
+            ride("2024-03-03", 5.0, 30.0),
+            ride("2024-05-10", 2.0, 0.0),
+            ride("2023-12-31", 8.0, 0.0),
+        ];
+
+        for (activity, elevation_m) in activities.iter_mut().zip([420.0, 1_480.0, 95.0]) {
+            activity.peak = Some(Peak {
+                elevation_m,
+                distance_km: 0.5,
+            });
+        }
+        let summary = Summary::new(&activities);
+
+        assert_eq!(summary.total_distance_km(), 21.0);
+        assert_eq!(summary.monthly_distance_km(2024)[2], 11.0);
+        assert_eq!(summary.monthly_distance_km(2024)[4], 2.0);
+        // Daily distances are 8, 5, 3, 3 and 2 km.
+        assert_eq!(summary.eddington_number(), 3);
+        assert_eq!(summary.longest_streak(), 3);
+        assert_eq!(summary.biggest_climb().unwrap().name, "2024-03-02 ride");
+        let (ride, peak) = summary.highest_point().unwrap();
+        assert_eq!(
+            (ride.name.as_str(), peak.elevation_m),
+            ("2024-03-02 ride", 1_480.0)
+        );
+        let longest: Vec<f64> = summary
+            .longest_rides(2)
+            .iter()
+            .map(|ride| ride.distance_km)
+            .collect();
+        assert_eq!(longest, [8.0, 5.0]);
+        assert_eq!(Summary::default().eddington_number(), 0);
+    }
+
+    #[test]
+    fn distance_is_added_up_by_week() {
+        let ride = |date: &str, distance_km: f64| Activity {
+            start: Some(
+                chrono::DateTime::parse_from_rfc3339(&format!("{date}T08:00:00Z")).unwrap(),
+            ),
+            distance_km,
+            ..Default::default()
+        };
+        let activities = [
+            // Monday and Sunday of the current week.
+            ride("2024-06-10", 10.0),
+            ride("2024-06-16", 5.0),
+            ride("2024-06-09", 20.0),
+            ride("2024-05-20", 7.0),
+            // Too old, and in the future.
+            ride("2024-05-19", 100.0),
+            ride("2024-06-17", 100.0),
+            Activity::default(),
+        ];
+        let today = NaiveDate::from_ymd_opt(2024, 6, 12).unwrap();
+
+        assert_eq!(
+            weekly_distance_km(&activities, today, 4),
+            [7.0, 0.0, 20.0, 15.0]
+        );
+        assert!(weekly_distance_km(&activities, today, 0).is_empty());
+    }
+
+    #[test]
+    fn ties_are_ordered_deterministically() {
+        let activities = [activity((46.0, 5.0), 10.0), activity((45.0, 5.0), 10.0)];
+
+        let clusters = start_location_clusters(&activities, CLUSTER_RADIUS_M);
+
+        assert_eq!(clusters[0].center, (45.0, 5.0));
+        assert_eq!(clusters[1].center, (46.0, 5.0));
+    }
+}
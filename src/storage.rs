@@ -0,0 +1,273 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Serialize, de::DeserializeOwned};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml::{Table, Value};
+
+/// Version of the files written by [`write_versioned`]. Files written
+/// before there were versions are version 0 and read as they are.
+pub const SCHEMA_VERSION: i64 = 1;
+
+/// Key of the schema version in every versioned file.
+const VERSION_KEY: &str = "version";
+
+/// Appended to the name of a file that could not be read, after the time
+/// it is set aside at; see [`aside_path`].
+pub const CORRUPT_EXTENSION: &str = "corrupt";
+
+/// Write a file so that a reader, or a crash, leaves either the old content
+/// or the new one, never a part: the bytes go to a temporary file next to
+/// it, flushed to the disk, which is then renamed over it.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("{} is not a file path", path.display()))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp = dir.join(temp_name);
+
+    let written = File::create(&temp).and_then(|mut file| {
+        file.write_all(bytes)?;
+        file.sync_all()
+    });
+    if let Err(err) = written.and_then(|()| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(err).with_context(|| format!("writing {}", path.display()));
+    }
+    // The rename is only durable once the directory is.
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// A file that could not be read, moved aside to start over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recovered {
+    pub path: PathBuf,
+    /// Where the file is now.
+    pub aside: PathBuf,
+    pub error: String,
+}
+
+impl fmt::Display for Recovered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} could not be read ({}), moved to {} and started over",
+            self.path.display(),
+            self.error,
+            self.aside.display()
+        )
+    }
+}
+
+/// What [`read_versioned`] found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stored<T> {
+    Missing,
+    Found(T),
+    /// The file was broken, and is moved aside.
+    Recovered(Recovered),
+}
+
+impl<T: Default> Stored<T> {
+    /// The value found, or the default one.
+    pub fn value(self) -> T {
+        match self {
+            Self::Found(value) => value,
+            Self::Missing | Self::Recovered(_) => T::default(),
+        }
+    }
+}
+
+/// Read a TOML file written by [`write_versioned`], of any version. The
+/// fields of a newer version are kept only by types that collect the
+/// unknown ones, such as with `#[serde(flatten)]` into a [`Table`], and the
+/// version of such a file is kept with them so that it is written back
+/// unchanged. A file that does not parse, e.g. cut short by a crash, is
+/// renamed to an [`aside_path`] so that the next write starts over.
+pub fn read_versioned<T: DeserializeOwned>(path: &Path) -> Result<Stored<T>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Stored::Missing),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+    };
+    match parse_versioned(&bytes) {
+        Ok(value) => Ok(Stored::Found(value)),
+        Err(err) => {
+            let aside = aside_path(path);
+            fs::rename(path, &aside).with_context(|| format!("moving {} aside", path.display()))?;
+            Ok(Stored::Recovered(Recovered {
+                path: path.to_path_buf(),
+                aside,
+                error: format!("{err:#}"),
+            }))
+        }
+    }
+}
+
+/// A name for a broken file that no earlier broken copy has, e.g.
+/// `session.toml.1718000000.corrupt` from the Unix time, with a counter
+/// when one was already set aside in the same second.
+fn aside_path(path: &Path) -> PathBuf {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    (0..)
+        .map(|count: u32| {
+            let mut aside = path.as_os_str().to_owned();
+            aside.push(format!(".{now}"));
+            if count > 0 {
+                aside.push(format!(".{count}"));
+            }
+            aside.push(".");
+            aside.push(CORRUPT_EXTENSION);
+            PathBuf::from(aside)
+        })
+        .find(|aside| !aside.exists())
+        .unwrap()
+}
+
+fn parse_versioned<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let mut table: Table = std::str::from_utf8(bytes)?.parse()?;
+    let version = match table.get(VERSION_KEY) {
+        None => 0,
+        Some(Value::Integer(version)) => *version,
+        Some(_) => return Err(anyhow!("`{VERSION_KEY}` is not a number")),
+    };
+    if version <= SCHEMA_VERSION {
+        table.remove(VERSION_KEY);
+    }
+    Ok(Value::Table(table).try_into()?)
+}
+
+/// Write a value as a TOML file with its schema version, atomically.
+pub fn write_versioned<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let Value::Table(mut table) = Value::try_from(value)? else {
+        return Err(anyhow!("{} is not written as a table", path.display()));
+    };
+    // A newer version read back keeps its number, its fields being kept too.
+    table
+        .entry(VERSION_KEY)
+        .or_insert(Value::Integer(SCHEMA_VERSION));
+    write_atomic(path, toml::to_string(&table)?.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Note {
+        text: Option<String>,
+        #[serde(flatten)]
+        unknown: Table,
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cyclemetrics-storage-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn files_round_trip_with_their_version() {
+        let dir = temp_dir("round-trip");
+        let path = dir.join("notes").join("note.toml");
+        let note = Note {
+            text: Some("windy".to_string()),
+            ..Default::default()
+        };
+        write_versioned(&path, &note).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("version = 1"), "{content}");
+        assert_eq!(read_versioned(&path).unwrap(), Stored::Found(note));
+        // No temporary file is left behind.
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn old_and_new_versions_are_read() {
+        let dir = temp_dir("versions");
+        let path = dir.join("note.toml");
+        fs::create_dir_all(&dir).unwrap();
+
+        // Written before there were versions.
+        fs::write(&path, "text = \"old\"\n").unwrap();
+        let note: Note = read_versioned(&path).unwrap().value();
+        assert_eq!(note.text.as_deref(), Some("old"));
+        assert!(note.unknown.is_empty());
+
+        // A newer version keeps its number and its fields once written back.
+        fs::write(&path, "version = 7\ntext = \"new\"\nmood = \"happy\"\n").unwrap();
+        let note: Note = read_versioned(&path).unwrap().value();
+        assert_eq!(note.text.as_deref(), Some("new"));
+        write_versioned(&path, &note).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("mood = \"happy\""), "{content}");
+        assert!(content.contains("version = 7"), "{content}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_files_are_set_aside() {
+        let dir = temp_dir("truncated");
+        let path = dir.join("note.toml");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "version = 1\ntext = \"cut sh").unwrap();
+
+        let Stored::Recovered(recovered) = read_versioned::<Note>(&path).unwrap() else {
+            panic!("the truncated file is read");
+        };
+        let aside = recovered.aside.file_name().unwrap().to_string_lossy();
+        assert!(aside.starts_with("note.toml.1"), "{aside}");
+        assert!(aside.ends_with(".corrupt"), "{aside}");
+        assert!(
+            recovered
+                .to_string()
+                .contains(&*recovered.aside.to_string_lossy()),
+            "{recovered}"
+        );
+        assert!(
+            recovered.to_string().contains("started over"),
+            "{recovered}"
+        );
+        assert!(!path.exists());
+        assert!(recovered.aside.exists());
+        assert_eq!(read_versioned::<Note>(&path).unwrap(), Stored::Missing);
+
+        // Broken again, it keeps the first copy.
+        fs::write(&path, "version = 1\ntext = \"cut again").unwrap();
+        let Stored::Recovered(again) = read_versioned::<Note>(&path).unwrap() else {
+            panic!("the truncated file is read");
+        };
+        assert_ne!(again.aside, recovered.aside);
+        assert_eq!(
+            fs::read_to_string(&recovered.aside).unwrap(),
+            "version = 1\ntext = \"cut sh"
+        );
+        assert_eq!(
+            fs::read_to_string(&again.aside).unwrap(),
+            "version = 1\ntext = \"cut again"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,352 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, FixedOffset};
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::activity::{Activity, ActivityId, TrackPoint, device_name};
+use crate::gpx::{NameSource, file_name_or_default, is_plausible_time};
+use crate::peaks::PeakFinder;
+use crate::virtual_rides::is_virtual_ride;
+
+/// Element whose text is being read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    None,
+    Name(NameSource),
+    Elevation,
+    Time,
+    HeartRate,
+    Power,
+    Cadence,
+    Distance,
+}
+
+/// Receives the content of a GPX document, in document order.
+trait TrackVisitor {
+    /// `creator` attribute of the document.
+    fn creator(&mut self, _creator: &str) {}
+    /// Name of the first track, the metadata or the first route.
+    fn name(&mut self, _name: &str, _source: NameSource) {}
+    fn point(&mut self, point: TrackPoint);
+    fn end_segment(&mut self) {}
+}
+
+/// Metrics accumulated point by point, in a single pass.
+#[derive(Debug, Default)]
+struct Totals {
+    creator: Option<String>,
+    /// Names found, by source.
+    names: Vec<(String, NameSource)>,
+    first: Option<TrackPoint>,
+    last: Option<TrackPoint>,
+    /// Previous point of the current segment; distances are not summed
+    /// across segments.
+    previous: Option<TrackPoint>,
+    start: Option<DateTime<FixedOffset>>,
+    end: Option<DateTime<FixedOffset>>,
+    /// Timestamps left out, see [`is_plausible_time`].
+    implausible_timestamps: usize,
+    /// Points identical to the previous one, skipped.
+    duplicate_points: usize,
+    distance_m: f64,
+    elevation_gain: f64,
+    peak: PeakFinder,
+}
+
+impl TrackVisitor for Totals {
+    fn creator(&mut self, creator: &str) {
+        self.creator = Some(creator.to_string());
+    }
+
+    fn name(&mut self, name: &str, source: NameSource) {
+        if !name.is_empty() {
+            self.names.push((name.to_string(), source));
+        }
+    }
+
+    fn point(&mut self, point: TrackPoint) {
+        if self.previous == Some(point) {
+            self.duplicate_points += 1;
+            return;
+        }
+        if let Some(prev) = self.previous {
+            self.distance_m += Haversine.distance(
+                point!(x: prev.lon, y: prev.lat),
+                point!(x: point.lon, y: point.lat),
+            );
+            if let (Some(e1), Some(e2)) = (prev.elevation, point.elevation) {
+                self.elevation_gain += (e2 - e1).max(0.0);
+            }
+        }
+        if let Some(elevation) = point.elevation {
+            self.peak.push(self.distance_m, elevation);
+        }
+        match point.time {
+            Some(time) if is_plausible_time(&time) => {
+                self.start = Some(self.start.map_or(time, |start| start.min(time)));
+                self.end = Some(self.end.map_or(time, |end| end.max(time)));
+            }
+            Some(_) => self.implausible_timestamps += 1,
+            None => {}
+        }
+
+        self.first.get_or_insert(point);
+        self.last = Some(point);
+        self.previous = Some(point);
+    }
+
+    fn end_segment(&mut self) {
+        self.previous = None;
+    }
+}
+
+/// Points of every segment, the first one of each marked, without their
+/// implausible timestamps nor the points identical to the previous one.
+#[derive(Debug, Default)]
+struct Points {
+    points: Vec<TrackPoint>,
+    /// Last point read, before its timestamp is checked.
+    previous: Option<TrackPoint>,
+    segment_ended: bool,
+}
+
+impl TrackVisitor for Points {
+    fn point(&mut self, point: TrackPoint) {
+        if !self.segment_ended && self.previous == Some(point) {
+            return;
+        }
+        self.previous = Some(point);
+        self.points.push(TrackPoint {
+            segment_start: self.points.is_empty() || self.segment_ended,
+            time: point.time.filter(is_plausible_time),
+            ..point
+        });
+        self.segment_ended = false;
+    }
+
+    fn end_segment(&mut self) {
+        self.segment_ended = true;
+    }
+}
+
+/// Read the points of every track segment, in order, including the heart
+/// rate, power, cadence and distance extensions that the `gpx` crate leaves out.
+pub fn read_track_points(bytes: &[u8]) -> Result<Vec<TrackPoint>> {
+    let mut points = Points::default();
+    read_track(bytes, &mut points)?;
+
+    Ok(points.points)
+}
+
+/// Compute the metrics of a GPX file without building the whole document.
+///
+/// Memory use does not depend on the file size, but the per-point data
+/// (elevation profile, points, elevation noise) is left empty; the peak is
+/// found on the way. Files without a track are parsed in full.
+pub fn stream_activity(path: &Path) -> Result<Activity> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut totals = Totals::default();
+    read_track(BufReader::new(file), &mut totals)
+        .with_context(|| format!("parsing {}", path.display()))?;
+    // A file without a track is told by its routes or its whole content,
+    // which only the full parse reads.
+    if totals.first.is_none() {
+        let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        return Activity::from_bytes(path.to_path_buf(), &bytes);
+    }
+
+    // Same order of preference as `gpx_name`.
+    let (name, name_source) = [NameSource::Track, NameSource::Metadata, NameSource::Route]
+        .into_iter()
+        .find_map(|source| totals.names.iter().find(|(_, found)| *found == source))
+        .cloned()
+        .unwrap_or_else(|| file_name_or_default(path));
+
+    let start_point = totals.first.map(|point| (point.lat, point.lon));
+
+    Ok(Activity {
+        id: ActivityId::from_endpoints(totals.first.as_ref(), totals.last.as_ref()),
+        path: path.to_path_buf(),
+        name,
+        name_source,
+        start: totals.start,
+        end: totals.end,
+        start_point,
+        end_point: totals.last.as_ref().map(|point| (point.lat, point.lon)),
+        virtual_ride: is_virtual_ride(totals.creator.as_deref(), start_point),
+        creator: totals.creator.as_deref().and_then(device_name),
+        distance_km: totals.distance_m / 1_000.0,
+        elevation_gain: totals.elevation_gain,
+        peak: totals.peak.finish(),
+        implausible_timestamps: totals.implausible_timestamps,
+        duplicate_points: totals.duplicate_points,
+        warnings: vec!["large file: per-point detail skipped (see --full-detail)".to_string()],
+        ..Default::default()
+    })
+}
+
+fn read_track(reader: impl BufRead, visitor: &mut impl TrackVisitor) -> Result<()> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = vec![];
+    let mut depth = 0;
+    let (mut tracks, mut routes) = (0, 0);
+    // Element whose `name` child is read, with its depth.
+    let mut named: Option<(usize, NameSource)> = None;
+    let mut point: Option<TrackPoint> = None;
+    let mut field = Field::None;
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(element) => {
+                depth += 1;
+                match element.local_name().as_ref() {
+                    // Like `gpx_name`, only the first track and route are named.
+                    b"gpx" => {
+                        if let Some(creator) = element.try_get_attribute("creator")? {
+                            visitor.creator(&creator.unescape_value()?);
+                        }
+                    }
+                    b"trk" => {
+                        tracks += 1;
+                        named = (tracks == 1).then_some((depth, NameSource::Track));
+                    }
+                    b"rte" => {
+                        routes += 1;
+                        named = (routes == 1).then_some((depth, NameSource::Route));
+                    }
+                    b"metadata" => named = Some((depth, NameSource::Metadata)),
+                    b"trkpt" => point = Some(track_point(&element)?),
+                    b"name" => match named {
+                        Some((parent, source)) if parent + 1 == depth => {
+                            field = Field::Name(source)
+                        }
+                        _ => {}
+                    },
+                    b"ele" if point.is_some() => field = Field::Elevation,
+                    b"time" if point.is_some() => field = Field::Time,
+                    b"hr" | b"heartrate" if point.is_some() => field = Field::HeartRate,
+                    b"power" | b"PowerInWatts" if point.is_some() => field = Field::Power,
+                    b"cad" | b"cadence" if point.is_some() => field = Field::Cadence,
+                    b"distance" if point.is_some() => field = Field::Distance,
+                    _ => {}
+                }
+            }
+            Event::Empty(element) if element.local_name().as_ref() == b"trkpt" => {
+                visitor.point(track_point(&element)?)
+            }
+            Event::Text(text) if field != Field::None => {
+                let text = text.unescape()?;
+                let text = text.trim();
+                match (field, point.as_mut()) {
+                    (Field::Name(source), _) => visitor.name(text, source),
+                    (Field::Elevation, Some(point)) => point.elevation = Some(text.parse()?),
+                    (Field::Time, Some(point)) => {
+                        point.time = Some(DateTime::parse_from_rfc3339(text)?)
+                    }
+                    (Field::HeartRate, Some(point)) => point.heart_rate = Some(text.parse()?),
+                    (Field::Power, Some(point)) => point.power = Some(text.parse()?),
+                    (Field::Cadence, Some(point)) => point.cadence = Some(text.parse()?),
+                    (Field::Distance, Some(point)) => point.distance_m = Some(text.parse()?),
+                    _ => {}
+                }
+            }
+            Event::End(element) => {
+                field = Field::None;
+                if named.is_some_and(|(parent, _)| parent == depth) {
+                    named = None;
+                }
+                depth -= 1;
+                match element.local_name().as_ref() {
+                    b"trkseg" => visitor.end_segment(),
+                    b"trkpt" => {
+                        if let Some(point) = point.take() {
+                            visitor.point(point);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(())
+}
+
+fn track_point(element: &BytesStart) -> Result<TrackPoint> {
+    let coordinate = |name: &str| -> Result<f64> {
+        let attribute = element
+            .try_get_attribute(name)?
+            .ok_or_else(|| anyhow!("track point without {name}"))?;
+        Ok(attribute.unescape_value()?.trim().parse()?)
+    };
+
+    Ok(TrackPoint {
+        lat: coordinate("lat")?,
+        lon: coordinate("lon")?,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn streaming_matches_full_parse() {
+        for name in [
+            "flat.gpx",
+            "mountain.gpx",
+            "multi_track.gpx",
+            "no_elevation.gpx",
+            "no_timestamps.gpx",
+            "route_only.gpx",
+            "zwift.gpx",
+            "edge.gpx",
+            "duplicated.gpx",
+            "late_night.gpx",
+            "dst_spring.gpx",
+            "dst_autumn.gpx",
+        ] {
+            let full = Activity::from_bytes(fixture(name), &std::fs::read(fixture(name)).unwrap())
+                .unwrap();
+            let streamed = stream_activity(&fixture(name)).unwrap();
+
+            assert_eq!(streamed.id, full.id, "{name}");
+            assert_eq!(streamed.name, full.name, "{name}");
+            assert_eq!(streamed.start, full.start, "{name}");
+            assert_eq!(streamed.end, full.end, "{name}");
+            assert_eq!(streamed.start_point, full.start_point, "{name}");
+            assert_eq!(streamed.virtual_ride, full.virtual_ride, "{name}");
+            assert_eq!(streamed.creator, full.creator, "{name}");
+            assert_eq!(streamed.duplicate_points, full.duplicate_points, "{name}");
+            assert!(
+                (streamed.distance_km - full.distance_km).abs() < 1e-9,
+                "{name}"
+            );
+            assert!(
+                (streamed.elevation_gain - full.elevation_gain).abs() < 1e-9,
+                "{name}"
+            );
+            assert_eq!(streamed.peak, full.peak, "{name}");
+        }
+    }
+
+    #[test]
+    fn corrupt_file_fails() {
+        assert!(stream_activity(&fixture("corrupt.gpx")).is_err());
+    }
+}
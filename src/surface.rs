@@ -0,0 +1,156 @@
+use crate::activity::TrackPoint;
+use crate::gpx::{GradeModel, grade_factor};
+use crate::tuning::{Tuning, distance_m, starts_segment};
+
+/// Length of the stretches whose surface is guessed, in meters.
+pub const SURFACE_SECTION_M: f64 = 250.0;
+
+/// Stretches a ride needs for its usual speed to mean something.
+const MIN_SURFACE_SECTIONS: usize = 4;
+
+/// A rough guess of the unpaved distance of a ride, from its speed alone:
+/// no map data is involved.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SurfaceEstimate {
+    /// Distance of the stretches that look unpaved.
+    pub unpaved_km: f64,
+    /// Distance of every stretch judged, ridden and timed.
+    pub judged_km: f64,
+}
+
+impl SurfaceEstimate {
+    /// Share of the judged distance that looks unpaved, in percent.
+    pub fn unpaved_percent(&self) -> f64 {
+        if self.judged_km > 0.0 {
+            self.unpaved_km / self.judged_km * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Speed of a stretch of [`SURFACE_SECTION_M`].
+#[derive(Debug, Clone, Copy)]
+struct Stretch {
+    distance_m: f64,
+    /// Speed on the flat for the same effort, in m/s.
+    flat_speed: f64,
+    /// Standard deviation of the speed between points over its mean.
+    variation: f64,
+}
+
+/// The moving stretches of a track, without the segment gaps.
+fn stretches(points: &[TrackPoint], tuning: &Tuning) -> Vec<Stretch> {
+    let mut stretches = vec![];
+    // Distance, seconds and speeds of the stretch so far, and its first
+    // elevation.
+    let mut distance = 0.0;
+    let mut seconds = 0.0;
+    let mut speeds: Vec<f64> = vec![];
+    let mut first_elevation = None;
+    for pair in points.windows(2) {
+        if starts_segment(Some(&pair[0]), &pair[1], tuning.segment_joins) {
+            continue;
+        }
+        let (Some(t1), Some(t2)) = (pair[0].time, pair[1].time) else {
+            continue;
+        };
+        let elapsed = (t2 - t1).as_seconds_f64();
+        let meters = distance_m(&pair[0], &pair[1]);
+        if elapsed <= 0.0 || meters / elapsed < tuning.moving_min_speed {
+            continue;
+        }
+        if speeds.is_empty() {
+            first_elevation = pair[0].elevation;
+        }
+        distance += meters;
+        seconds += elapsed;
+        speeds.push(meters / elapsed);
+        if distance < SURFACE_SECTION_M {
+            continue;
+        }
+
+        let speed = distance / seconds;
+        let grade = match (first_elevation, pair[1].elevation) {
+            (Some(first), Some(last)) => (last - first) / distance * 100.0,
+            _ => 0.0,
+        };
+        let mean = speeds.iter().sum::<f64>() / speeds.len() as f64;
+        let deviation =
+            (speeds.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / speeds.len() as f64).sqrt();
+        stretches.push(Stretch {
+            distance_m: distance,
+            flat_speed: speed * grade_factor(grade, GradeModel::Piecewise),
+            variation: deviation / mean,
+        });
+        distance = 0.0;
+        seconds = 0.0;
+        speeds.clear();
+    }
+    stretches
+}
+
+/// Guess the unpaved distance of a ride. A stretch looks unpaved when its
+/// speed varies by more than [`Tuning::unpaved_variation_percent`] and is
+/// [`Tuning::unpaved_slowdown_percent`] below the usual speed of the ride
+/// for its grade. Slow but steady climbs and uneven but fast stretches
+/// stay paved. `None` without timestamps or on short rides.
+pub fn estimate_surface(points: &[TrackPoint], tuning: &Tuning) -> Option<SurfaceEstimate> {
+    let stretches = stretches(points, tuning);
+    if stretches.len() < MIN_SURFACE_SECTIONS {
+        return None;
+    }
+    let mut flat_speeds: Vec<f64> = stretches.iter().map(|stretch| stretch.flat_speed).collect();
+    flat_speeds.sort_by(f64::total_cmp);
+    let usual = flat_speeds[flat_speeds.len() / 2];
+
+    let mut estimate = SurfaceEstimate::default();
+    for stretch in &stretches {
+        let km = stretch.distance_m / 1_000.0;
+        estimate.judged_km += km;
+        if stretch.variation * 100.0 > tuning.unpaved_variation_percent
+            && stretch.flat_speed < usual * (1.0 - tuning.unpaved_slowdown_percent / 100.0)
+        {
+            estimate.unpaved_km += km;
+        }
+    }
+    Some(estimate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::Activity;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> Activity {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        Activity::from_bytes(path.clone(), &std::fs::read(&path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn gravel_rides_look_less_paved_than_road_rides() {
+        let tuning = Tuning::default();
+        let road = estimate_surface(&fixture("paved_road.gpx").points, &tuning).unwrap();
+        let gravel = estimate_surface(&fixture("gravel.gpx").points, &tuning).unwrap();
+
+        // The climb of the road ride is slow but steady.
+        assert!(road.unpaved_percent() < 10.0, "{road:?}");
+        // About 4 of the 9 km are on a track.
+        assert!(gravel.unpaved_percent() > 25.0, "{gravel:?}");
+        assert!((gravel.judged_km - 9.0).abs() < 0.3, "{gravel:?}");
+    }
+
+    #[test]
+    fn thresholds_move_the_guess() {
+        let points = fixture("gravel.gpx").points;
+        let strict = Tuning {
+            unpaved_variation_percent: 50.0,
+            ..Tuning::default()
+        };
+        assert_eq!(estimate_surface(&points, &strict).unwrap().unpaved_km, 0.0);
+        assert_eq!(estimate_surface(&points[..20], &Tuning::default()), None);
+    }
+}
@@ -0,0 +1,217 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+/// The symbols the interface chooses between, in Unicode or in ASCII only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symbols {
+    /// Before elevation gains and rising trends.
+    pub up: &'static str,
+    pub down: &'static str,
+    /// In place of the cut part of a name.
+    pub ellipsis: &'static str,
+    /// Colored by the intensity of a ride.
+    pub dot: &'static str,
+    /// Between the figures of a line.
+    pub separator: &'static str,
+    /// Along the rides of the selected range.
+    pub range: &'static str,
+    pub folded: &'static str,
+    pub unfolded: &'static str,
+    /// Bars of the data quality, from the lowest score.
+    pub bars: [char; 8],
+}
+
+impl Symbols {
+    pub const UNICODE: Self = Self {
+        up: "↑",
+        down: "↓",
+        ellipsis: "…",
+        dot: "●",
+        separator: "·",
+        range: "│",
+        folded: "▸",
+        unfolded: "▾",
+        bars: ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'],
+    };
+
+    pub const ASCII: Self = Self {
+        up: "+",
+        down: "-",
+        ellipsis: "~",
+        dot: "*",
+        separator: "-",
+        range: "|",
+        folded: ">",
+        unfolded: "v",
+        bars: ['_', '_', '-', '-', '=', '=', '#', '#'],
+    };
+
+    pub fn new(ascii: bool) -> &'static Self {
+        if ascii { &Self::ASCII } else { &Self::UNICODE }
+    }
+}
+
+/// ASCII stand-in of a character drawn without a symbol of its own: the
+/// borders and charts of the interface, or the arrows, units and accented
+/// letters of its texts and of the reports. Most take a single character,
+/// so that tables stay aligned.
+pub fn ascii_char(c: char) -> Cow<'static, str> {
+    let text = match c {
+        _ if c.is_ascii() => return c.to_string().into(),
+        '↑' | '▲' | '±' => "+",
+        '↓' | '▼' | '−' | '–' | '—' | '─' | '━' | '╌' => "-",
+        '←' => "<-",
+        '→' => "->",
+        '◀' => "<",
+        '▶' | '▸' => ">",
+        '▾' => "v",
+        '…' => "~",
+        '·' => "-",
+        '●' | '•' | '■' => "*",
+        '×' => "x",
+        '≤' => "<=",
+        '≥' => ">=",
+        '≈' => "~",
+        '°' => "deg",
+        '²' => "2",
+        'Δ' => "D",
+        'Σ' => "S",
+        '│' | '┃' | '║' => "|",
+        '┌' | '┐' | '└' | '┘' | '├' | '┤' | '┬' | '┴' | '┼' => "+",
+        '▁'..='▃' => "_",
+        '▄'..='▆' | '▀' => "=",
+        '▇' | '█' => "#",
+        // Blank braille cells stay blank, the others are plotted points.
+        '\u{2800}' => " ",
+        '\u{2801}'..='\u{28ff}' => ".",
+        '\u{a0}' | '\u{202f}' | '\u{2009}' => " ",
+        'à' | 'â' | 'ä' => "a",
+        'ç' => "c",
+        'é' | 'è' | 'ê' | 'ë' => "e",
+        'î' | 'ï' => "i",
+        'ô' | 'ö' => "o",
+        'ù' | 'û' | 'ü' => "u",
+        'À' | 'Â' => "A",
+        'Ç' => "C",
+        'É' | 'È' | 'Ê' => "E",
+        'Ô' => "O",
+        'œ' => "oe",
+        _ => "?",
+    };
+    text.into()
+}
+
+/// The single character standing in for a cell of a terminal screen, which
+/// the longer stand-ins of [`ascii_char`] would overflow.
+pub fn ascii_cell(c: char) -> char {
+    match c {
+        '°' => 'o',
+        _ => ascii_char(c).chars().next().unwrap_or('?'),
+    }
+}
+
+/// `text` with every character out of ASCII replaced by [`ascii_char`].
+pub fn to_ascii(text: &str) -> Cow<'_, str> {
+    if text.is_ascii() {
+        return text.into();
+    }
+    text.chars().map(ascii_char).collect::<String>().into()
+}
+
+/// Writes what it is given as ASCII, for reports pasted where Unicode is
+/// garbled. Characters split across writes are kept until complete.
+pub struct AsciiWriter<W: Write> {
+    inner: W,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> AsciiWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            pending: vec![],
+        }
+    }
+}
+
+impl<W: Write> Write for AsciiWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        let mut text = String::new();
+        let mut rest = self.pending.as_slice();
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(&to_ascii(valid));
+                    rest = &[];
+                    break;
+                }
+                Err(err) => {
+                    let (valid, after) = rest.split_at(err.valid_up_to());
+                    text.push_str(&to_ascii(std::str::from_utf8(valid).unwrap_or_default()));
+                    match err.error_len() {
+                        // Not UTF-8 at all.
+                        Some(len) => {
+                            text.push('?');
+                            rest = &after[len..];
+                        }
+                        // The start of a character, the end coming next.
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.pending = rest.to_vec();
+        self.inner.write_all(text.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texts_become_ascii() {
+        assert_eq!(to_ascii("Col du Test"), "Col du Test");
+        assert_eq!(
+            to_ascii("↑ 305m · 12 km² · ≤ 50 — Été"),
+            "+ 305m - 12 km2 - <= 50 - Ete"
+        );
+        assert_eq!(to_ascii("⠀⣼ │▅│ ◀ ×4 ✓"), " . |=| < x4 ?");
+        assert_eq!(ascii_cell('°'), 'o');
+        assert_eq!(ascii_cell('≤'), '<');
+
+        let symbols = Symbols::ASCII;
+        let all = [
+            symbols.up,
+            symbols.down,
+            symbols.ellipsis,
+            symbols.dot,
+            symbols.separator,
+            symbols.range,
+            symbols.folded,
+            symbols.unfolded,
+        ];
+        assert!(all.iter().all(|symbol| symbol.is_ascii()));
+        assert!(symbols.bars.iter().all(char::is_ascii));
+    }
+
+    #[test]
+    fn characters_split_across_writes_are_kept() {
+        let mut out = AsciiWriter::new(vec![]);
+        let text = "Uphill ↑ 305 m\n".as_bytes();
+        let arrow = text.iter().position(|&byte| byte > 0x7f).unwrap();
+        out.write_all(&text[..arrow + 1]).unwrap();
+        out.write_all(&text[arrow + 1..]).unwrap();
+        out.write_all(&[0xff, b'\n']).unwrap();
+        assert_eq!(String::from_utf8(out.inner).unwrap(), "Uphill + 305 m\n?\n");
+    }
+}
@@ -0,0 +1,435 @@
+use chrono::{DateTime, FixedOffset, TimeDelta};
+use geo::{Destination, Haversine, Point};
+use gpx::Gpx;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::activity::{Activity, TrackPoint};
+use crate::fit::{DEGREES_PER_SEMICIRCLE, FILE_ID, FIT_EPOCH_S, RECORD, fit_crc};
+use crate::gpx::write_gpx;
+
+/// `minutes` as a [`Duration`], for [`SyntheticRide::pause`].
+pub fn minutes(minutes: u64) -> Duration {
+    Duration::from_secs(minutes * 60)
+}
+
+/// Part of a synthetic ride.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Section {
+    Ride {
+        distance_m: f64,
+        grade_percent: f64,
+        speed_kmh: f64,
+        heading_deg: f64,
+        heart_rate: Option<f64>,
+        power: Option<f64>,
+    },
+    /// Time passing with nothing recorded, as with auto-pause.
+    Pause(Duration),
+}
+
+/// Builder of GPX rides with known metrics, for tests:
+///
+/// ```
+/// use cyclemetrics::testkit::{SyntheticRide, minutes};
+///
+/// let gpx = SyntheticRide::new()
+///     .speed_kmh(30.0)
+///     .flat(2.0)
+///     .climb(5.0, 8.0)
+///     .pause(minutes(10))
+///     .noise(3.0)
+///     .to_gpx();
+/// assert!(gpx.contains("<trkpt"));
+/// ```
+///
+/// Sections go along a great circle from the start, following the heading
+/// set before them, at the speed set before them and one point every
+/// [`SyntheticRide::interval`]. Distances are on the map, as the metrics
+/// measure them; the elevation changes by the grade over them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticRide {
+    name: String,
+    start: DateTime<FixedOffset>,
+    origin: (f64, f64),
+    elevation_m: f64,
+    interval: Duration,
+    speed_kmh: f64,
+    heading_deg: f64,
+    heart_rate: Option<f64>,
+    power: Option<f64>,
+    noise_m: f64,
+    seed: u64,
+    sections: Vec<Section>,
+}
+
+impl Default for SyntheticRide {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyntheticRide {
+    /// A ride north from 45°N 5°E at 100 m, on 2024-06-01 at 08:00 UTC, at
+    /// 25 km/h with a point every second.
+    pub fn new() -> Self {
+        Self {
+            name: "Synthetic ride".to_string(),
+            start: DateTime::parse_from_rfc3339("2024-06-01T08:00:00Z").expect("valid date"),
+            origin: (45.0, 5.0),
+            elevation_m: 100.0,
+            interval: Duration::from_secs(1),
+            speed_kmh: 25.0,
+            heading_deg: 0.0,
+            heart_rate: None,
+            power: None,
+            noise_m: 0.0,
+            seed: 1,
+            sections: vec![],
+        }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn start(mut self, start: DateTime<FixedOffset>) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// Start point, in degrees.
+    pub fn origin(mut self, lat: f64, lon: f64) -> Self {
+        self.origin = (lat, lon);
+        self
+    }
+
+    /// Elevation of the start, in meters.
+    pub fn elevation(mut self, elevation_m: f64) -> Self {
+        self.elevation_m = elevation_m;
+        self
+    }
+
+    /// Time between two recorded points.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Speed of the sections added next.
+    pub fn speed_kmh(mut self, speed_kmh: f64) -> Self {
+        self.speed_kmh = speed_kmh;
+        self
+    }
+
+    /// Direction of the sections added next, in degrees from north.
+    pub fn heading(mut self, heading_deg: f64) -> Self {
+        self.heading_deg = heading_deg;
+        self
+    }
+
+    /// Heart rate recorded on the sections added next, in bpm.
+    pub fn heart_rate(mut self, heart_rate: f64) -> Self {
+        self.heart_rate = Some(heart_rate);
+        self
+    }
+
+    /// Power recorded on the sections added next, in watts.
+    pub fn power(mut self, power: f64) -> Self {
+        self.power = Some(power);
+        self
+    }
+
+    /// Move every point up to `noise_m` away from where it should be, the
+    /// same way on every build.
+    pub fn noise(mut self, noise_m: f64) -> Self {
+        self.noise_m = noise_m;
+        self
+    }
+
+    /// Another draw of the noise.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn flat(self, km: f64) -> Self {
+        self.climb(km, 0.0)
+    }
+
+    /// `km` at `grade_percent`, negative going down.
+    pub fn climb(mut self, km: f64, grade_percent: f64) -> Self {
+        self.sections.push(Section::Ride {
+            distance_m: km * 1_000.0,
+            grade_percent,
+            speed_kmh: self.speed_kmh,
+            heading_deg: self.heading_deg,
+            heart_rate: self.heart_rate,
+            power: self.power,
+        });
+        self
+    }
+
+    pub fn descent(self, km: f64, grade_percent: f64) -> Self {
+        self.climb(km, -grade_percent.abs())
+    }
+
+    /// Stop for `duration` with the recording paused.
+    pub fn pause(mut self, duration: Duration) -> Self {
+        self.sections.push(Section::Pause(duration));
+        self
+    }
+
+    /// The points of the ride, noise included.
+    pub fn points(&self) -> Vec<TrackPoint> {
+        let mut random = Random(self.seed.max(1));
+        let mut position = Point::new(self.origin.1, self.origin.0);
+        let mut elevation = self.elevation_m;
+        let mut time = self.start;
+        let mut points = vec![TrackPoint {
+            lat: self.origin.0,
+            lon: self.origin.1,
+            elevation: Some(elevation),
+            time: Some(time),
+            segment_start: true,
+            ..Default::default()
+        }];
+
+        for section in &self.sections {
+            let &Section::Ride {
+                distance_m,
+                grade_percent,
+                speed_kmh,
+                heading_deg,
+                heart_rate,
+                power,
+            } = section
+            else {
+                if let Section::Pause(duration) = section {
+                    time += TimeDelta::from_std(*duration).unwrap_or_default();
+                }
+                continue;
+            };
+            // The start gets the sensors of the first section.
+            if let [start] = points.as_mut_slice() {
+                start.heart_rate = heart_rate;
+                start.power = power;
+            }
+
+            let step_m = speed_kmh / 3.6 * self.interval.as_secs_f64();
+            let steps = (distance_m / step_m).ceil().max(1.0) as usize;
+            let (origin, start_elevation, start_time) = (position, elevation, time);
+            for step in 1..=steps {
+                let along_m = (step as f64 * step_m).min(distance_m);
+                position = Haversine.destination(origin, heading_deg, along_m);
+                elevation = start_elevation + along_m * grade_percent / 100.0;
+                time = start_time + TimeDelta::milliseconds((along_m / speed_kmh * 3_600.0) as i64);
+                let (north_m, east_m) = random.offset(self.noise_m);
+                let noisy = Haversine.destination(
+                    Haversine.destination(position, 0.0, north_m),
+                    90.0,
+                    east_m,
+                );
+                points.push(TrackPoint {
+                    lat: noisy.y(),
+                    lon: noisy.x(),
+                    elevation: Some(elevation),
+                    time: Some(time),
+                    heart_rate,
+                    power,
+                    ..Default::default()
+                });
+            }
+        }
+        points
+    }
+
+    /// The ride as a GPX 1.1 file, with the heart rate and power in the
+    /// extensions read by [`Activity::from_bytes`].
+    pub fn to_gpx(&self) -> String {
+        write_gpx("cyclemetrics testkit", Some(&self.name), &self.points())
+    }
+
+    /// The ride as a FIT activity file recorded by a Garmin device, with
+    /// the heart rate and power in its records. Times are cut to the second
+    /// and positions rounded to the semicircle, as a device writes them.
+    pub fn to_fit(&self) -> Vec<u8> {
+        let mut data = vec![];
+        // Definition of local message 0, the file id: type and manufacturer.
+        data.extend([0x40, 0, 0]);
+        data.extend(FILE_ID.to_le_bytes());
+        data.extend([2, 0, 1, 0x00, 1, 2, 0x84]);
+        // An activity, by Garmin.
+        data.extend([0x00, 4, 1, 0]);
+        // Definition of local message 1, the records: timestamp, position,
+        // enhanced altitude, heart rate and power.
+        data.extend([0x41, 0, 0]);
+        data.extend(RECORD.to_le_bytes());
+        data.extend([
+            6, 253, 4, 0x86, 0, 4, 0x85, 1, 4, 0x85, 78, 4, 0x86, 3, 1, 0x02,
+        ]);
+        data.extend([7, 2, 0x84]);
+        for point in self.points() {
+            data.push(0x01);
+            let timestamp = point
+                .time
+                .map_or(u32::MAX, |time| (time.timestamp() - FIT_EPOCH_S) as u32);
+            data.extend(timestamp.to_le_bytes());
+            for degrees in [point.lat, point.lon] {
+                data.extend(((degrees / DEGREES_PER_SEMICIRCLE).round() as i32).to_le_bytes());
+            }
+            let altitude = point.elevation.map_or(u32::MAX, |elevation| {
+                ((elevation + 500.0) * 5.0).round() as u32
+            });
+            data.extend(altitude.to_le_bytes());
+            data.push(
+                point
+                    .heart_rate
+                    .map_or(u8::MAX, |heart_rate| heart_rate as u8),
+            );
+            let power = point.power.map_or(u16::MAX, |power| power as u16);
+            data.extend(power.to_le_bytes());
+        }
+
+        let mut fit = vec![14, 0x20];
+        fit.extend(2132u16.to_le_bytes());
+        fit.extend((data.len() as u32).to_le_bytes());
+        fit.extend(b".FIT");
+        let header_crc = fit_crc(&fit);
+        fit.extend(header_crc.to_le_bytes());
+        fit.extend(data);
+        let crc = fit_crc(&fit);
+        fit.extend(crc.to_le_bytes());
+        fit
+    }
+
+    /// The ride parsed back by the `gpx` crate.
+    pub fn build(&self) -> Gpx {
+        gpx::read(self.to_gpx().as_bytes()).expect("synthetic GPX is valid")
+    }
+
+    /// The ride read as a file named `synthetic.gpx` would be.
+    pub fn activity(&self) -> Activity {
+        Activity::from_bytes(PathBuf::from("synthetic.gpx"), self.to_gpx().as_bytes())
+            .expect("synthetic GPX is valid")
+    }
+}
+
+/// xorshift64, enough for noise that is the same on every run.
+#[derive(Debug)]
+struct Random(u64);
+
+impl Random {
+    /// Uniform in `[-1, 1)`.
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+
+    /// North and east offsets within a disc of `radius_m`.
+    fn offset(&mut self, radius_m: f64) -> (f64, f64) {
+        if radius_m <= 0.0 {
+            return (0.0, 0.0);
+        }
+        loop {
+            let (north, east) = (self.next(), self.next());
+            if north * north + east * east <= 1.0 {
+                return (north * radius_m, east * radius_m);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::climbs::detect_climbs;
+    use crate::tuning::{Tuning, distance_m, tuned_distance_m, tuned_moving_seconds};
+
+    #[test]
+    fn metrics_read_back_what_was_built() {
+        let ride = SyntheticRide::new()
+            .speed_kmh(30.0)
+            .flat(2.0)
+            .speed_kmh(15.0)
+            .climb(5.0, 8.0)
+            .pause(minutes(10))
+            .heading(90.0)
+            .speed_kmh(40.0)
+            .descent(2.0, 5.0);
+        let activity = ride.activity();
+
+        assert!(
+            (activity.distance_km - 9.0).abs() < 0.01,
+            "{}",
+            activity.distance_km
+        );
+        assert!(
+            (activity.elevation_gain - 400.0).abs() < 5.0,
+            "{}",
+            activity.elevation_gain
+        );
+        // 4 + 20 + 3 minutes riding, and the pause.
+        let elapsed = (activity.end.unwrap() - activity.start.unwrap()).num_seconds();
+        assert_eq!(elapsed, 37 * 60);
+        let moving = tuned_moving_seconds(&activity.points, &Tuning::default()).unwrap();
+        assert!((moving - 27.0 * 60.0).abs() < 5.0, "{moving}");
+
+        let climbs = detect_climbs(&activity.points, &activity.tuning);
+        assert_eq!(climbs.len(), 1);
+        assert!(
+            (climbs[0].length_m - 5_000.0).abs() < 100.0,
+            "{:?}",
+            climbs[0]
+        );
+        assert!(
+            (climbs[0].grade_percent() - 8.0).abs() < 0.2,
+            "{:?}",
+            climbs[0]
+        );
+
+        // East at the end: the last point is further east than the top.
+        let points = &activity.points;
+        assert!(points[points.len() - 1].lon > points[points.len() - 2].lon);
+        assert_eq!(
+            ride.build().tracks[0].segments[0].points.len(),
+            points.len()
+        );
+    }
+
+    #[test]
+    fn noise_moves_the_points_the_same_way_each_time() {
+        let clean = SyntheticRide::new().flat(1.0);
+        let noisy = clean.clone().noise(3.0);
+        assert_eq!(noisy.points(), noisy.points());
+        assert_ne!(noisy.points(), noisy.clone().seed(2).points());
+
+        let (clean, noisy) = (clean.points(), noisy.points());
+        for (a, b) in clean.iter().zip(&noisy).skip(1) {
+            assert!(distance_m(a, b) <= 3.0 + 1e-6);
+        }
+        // The jitter adds distance.
+        assert!(
+            tuned_distance_m(&noisy, &Tuning::default())
+                > tuned_distance_m(&clean, &Tuning::default())
+        );
+    }
+
+    #[test]
+    fn sensors_are_written() {
+        let activity = SyntheticRide::new()
+            .heart_rate(150.0)
+            .power(220.0)
+            .flat(0.5)
+            .activity();
+        assert!(
+            activity
+                .points
+                .iter()
+                .all(|point| point.heart_rate == Some(150.0) && point.power == Some(220.0))
+        );
+    }
+}
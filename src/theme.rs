@@ -0,0 +1,279 @@
+use clap::ValueEnum;
+use ratatui::style::palette::tailwind::{
+    AMBER, BLUE, CYAN, FUCHSIA, GREEN, ORANGE, RED, SLATE, YELLOW,
+};
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+
+use crate::calendar::HEAT_LEVELS;
+use crate::intensity::Intensity;
+use crate::stats::{Recency, Steepness};
+
+/// Themes shipped with the interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    /// Light text, for dark terminal backgrounds
+    Dark,
+    /// Dark text, for light terminal backgrounds
+    Light,
+    /// Plain bright colors and no dim text
+    HighContrast,
+}
+
+impl ThemeName {
+    /// Theme matching the terminal background told by `$COLORFGBG`
+    /// (`foreground;background` in ANSI colors), `None` when it is unset or
+    /// unclear.
+    pub fn detect(colorfgbg: Option<&str>) -> Option<Self> {
+        let background: u8 = colorfgbg?.rsplit(';').next()?.parse().ok()?;
+        // White and light gray, and the bright colors but dark gray.
+        Some(match background {
+            7 | 9..=15 => ThemeName::Light,
+            _ => ThemeName::Dark,
+        })
+    }
+}
+
+/// Every style of the interface, by what it shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Activities of the list.
+    pub text: Style,
+    /// Activities of the list ridden this week, this month, this year and
+    /// before, from the brightest.
+    pub recency: [Style; 4],
+    /// Figures, such as distances and times, and text being typed.
+    pub value: Style,
+    /// Hints and side notes.
+    pub muted: Style,
+    /// Titles of the sections of a popup.
+    pub heading: Style,
+    pub focused_title: Style,
+    /// Selected activity of the list.
+    pub selected: Style,
+    /// Selected row of the settings and the command palette.
+    pub menu_selected: Style,
+    /// Range selections.
+    pub accent: Style,
+    /// Tuning changed from the defaults.
+    pub notice: Style,
+    /// Faster rides and time gained.
+    pub good: Style,
+    /// Slower rides, time lost and failed files.
+    pub bad: Style,
+    /// Marked activities.
+    pub marked: Style,
+    /// Bars and lines of the charts.
+    pub chart: Style,
+    /// Reference lines of the charts.
+    pub reference: Style,
+    /// Track and start of the route map.
+    pub map_track: Color,
+    pub map_start: Color,
+    /// Calendar days, from no ride to the longest ones.
+    pub heat: [Color; HEAT_LEVELS],
+    pub calendar_cursor: Color,
+    /// Backgrounds of the tag chips, and their text.
+    pub tags: [Color; 6],
+    pub tag_text: Color,
+    /// Recovery, endurance, tempo and hard rides.
+    pub intensity: [Color; 4],
+    /// Flat, moderate, steep and very steep grades of the elevation profile.
+    pub steepness: [Color; 4],
+}
+
+/// Calendar colors of the yearly review, readable on both backgrounds.
+const HEAT: [Color; HEAT_LEVELS] = [
+    Color::Rgb(0xeb, 0xed, 0xf0),
+    Color::Rgb(0xc6, 0xe4, 0x8b),
+    Color::Rgb(0x7b, 0xc9, 0x6f),
+    Color::Rgb(0x23, 0x9a, 0x3b),
+    Color::Rgb(0x19, 0x61, 0x27),
+];
+
+const BOLD: Style = Style::new().add_modifier(Modifier::BOLD);
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new(ThemeName::Dark)
+    }
+}
+
+impl Theme {
+    pub fn new(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Dark => Self {
+                text: Style::new().fg(SLATE.c200),
+                recency: [
+                    BOLD.fg(SLATE.c50),
+                    Style::new().fg(SLATE.c200),
+                    Style::new().fg(SLATE.c400),
+                    Style::new().fg(SLATE.c500),
+                ],
+                value: Style::new().fg(Color::Yellow),
+                muted: Style::new().fg(Color::DarkGray),
+                heading: BOLD,
+                focused_title: BOLD.fg(Color::Yellow),
+                selected: BOLD,
+                menu_selected: BOLD.add_modifier(Modifier::REVERSED),
+                accent: Style::new().fg(Color::Cyan),
+                notice: Style::new().fg(Color::Magenta),
+                good: Style::new().fg(Color::Green),
+                bad: Style::new().fg(Color::Red),
+                marked: Style::new().fg(Color::Yellow),
+                chart: Style::new().fg(Color::Green),
+                reference: Style::new().fg(Color::DarkGray),
+                map_track: Color::Red,
+                map_start: Color::Green,
+                heat: HEAT,
+                calendar_cursor: Color::Yellow,
+                tags: [
+                    Color::Cyan,
+                    Color::Magenta,
+                    Color::Green,
+                    Color::Blue,
+                    Color::Yellow,
+                    Color::Red,
+                ],
+                tag_text: Color::Black,
+                intensity: [Color::Blue, Color::Green, Color::Yellow, Color::Red],
+                steepness: [
+                    Color::Green,
+                    Color::Yellow,
+                    Color::Rgb(0xff, 0x8c, 0x00),
+                    Color::Red,
+                ],
+            },
+            ThemeName::Light => Self {
+                text: Style::new().fg(SLATE.c800),
+                recency: [
+                    BOLD.fg(SLATE.c950),
+                    Style::new().fg(SLATE.c800),
+                    Style::new().fg(SLATE.c600),
+                    Style::new().fg(SLATE.c500),
+                ],
+                value: Style::new().fg(AMBER.c700),
+                muted: Style::new().fg(SLATE.c500),
+                heading: BOLD,
+                focused_title: BOLD.fg(BLUE.c700),
+                selected: BOLD,
+                menu_selected: BOLD.add_modifier(Modifier::REVERSED),
+                accent: Style::new().fg(CYAN.c700),
+                notice: Style::new().fg(FUCHSIA.c700),
+                good: Style::new().fg(GREEN.c700),
+                bad: Style::new().fg(RED.c700),
+                marked: Style::new().fg(AMBER.c700),
+                chart: Style::new().fg(GREEN.c600),
+                reference: Style::new().fg(SLATE.c400),
+                map_track: RED.c600,
+                map_start: GREEN.c700,
+                heat: HEAT,
+                calendar_cursor: BLUE.c600,
+                tags: [
+                    CYAN.c200,
+                    FUCHSIA.c200,
+                    GREEN.c200,
+                    BLUE.c200,
+                    YELLOW.c200,
+                    RED.c200,
+                ],
+                tag_text: SLATE.c900,
+                intensity: [BLUE.c600, GREEN.c600, AMBER.c600, RED.c600],
+                steepness: [GREEN.c600, YELLOW.c500, ORANGE.c500, RED.c600],
+            },
+            ThemeName::HighContrast => Self {
+                text: Style::new().fg(Color::White),
+                // Older rides are told apart without dimming them.
+                recency: [
+                    BOLD.fg(Color::White),
+                    Style::new().fg(Color::White),
+                    Style::new().fg(Color::Gray),
+                    Style::new().fg(Color::Gray).add_modifier(Modifier::ITALIC),
+                ],
+                value: BOLD.fg(Color::LightYellow),
+                muted: Style::new().fg(Color::Gray),
+                heading: BOLD.add_modifier(Modifier::UNDERLINED),
+                focused_title: BOLD.fg(Color::LightYellow),
+                selected: BOLD.add_modifier(Modifier::REVERSED),
+                menu_selected: BOLD.add_modifier(Modifier::REVERSED),
+                accent: BOLD.fg(Color::LightCyan),
+                notice: BOLD.fg(Color::LightMagenta),
+                good: BOLD.fg(Color::LightGreen),
+                bad: BOLD.fg(Color::LightRed),
+                marked: BOLD.fg(Color::LightYellow),
+                chart: Style::new().fg(Color::LightGreen),
+                reference: Style::new().fg(Color::Gray),
+                map_track: Color::LightRed,
+                map_start: Color::LightGreen,
+                heat: [
+                    Color::Gray,
+                    Color::Rgb(0xb0, 0xf0, 0x80),
+                    Color::Rgb(0x50, 0xd0, 0x40),
+                    Color::Rgb(0x10, 0xa0, 0x10),
+                    Color::Rgb(0x00, 0x60, 0x00),
+                ],
+                calendar_cursor: Color::LightYellow,
+                tags: [
+                    Color::LightCyan,
+                    Color::LightMagenta,
+                    Color::LightGreen,
+                    Color::LightBlue,
+                    Color::LightYellow,
+                    Color::LightRed,
+                ],
+                tag_text: Color::Black,
+                intensity: [
+                    Color::LightBlue,
+                    Color::LightGreen,
+                    Color::LightYellow,
+                    Color::LightRed,
+                ],
+                steepness: [
+                    Color::LightGreen,
+                    Color::LightYellow,
+                    Color::Rgb(0xff, 0x8c, 0x00),
+                    Color::LightRed,
+                ],
+            },
+        }
+    }
+
+    pub fn intensity_color(&self, intensity: Intensity) -> Color {
+        self.intensity[intensity as usize]
+    }
+
+    pub fn recency_style(&self, recency: Recency) -> Style {
+        self.recency[recency as usize]
+    }
+
+    pub fn steepness_color(&self, steepness: Steepness) -> Color {
+        self.steepness[steepness as usize]
+    }
+
+    /// Same tag, same color, from one run to the next.
+    pub fn tag_color(&self, tag: &str) -> Color {
+        let hash = tag.bytes().fold(0usize, |hash, byte| {
+            hash.wrapping_mul(31).wrapping_add(usize::from(byte))
+        });
+        self.tags[hash % self.tags.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_background_picks_the_theme() {
+        assert_eq!(ThemeName::detect(Some("15;0")), Some(ThemeName::Dark));
+        assert_eq!(ThemeName::detect(Some("0;15")), Some(ThemeName::Light));
+        // rxvt adds the cursor color in the middle.
+        assert_eq!(
+            ThemeName::detect(Some("0;default;7")),
+            Some(ThemeName::Light)
+        );
+        assert_eq!(ThemeName::detect(Some("default;default")), None);
+        assert_eq!(ThemeName::detect(None), None);
+    }
+}
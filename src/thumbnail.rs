@@ -0,0 +1,264 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::activity::{Activity, TrackPoint};
+use crate::privacy::{PrivacyZone, strip_private};
+
+/// Width and height of the thumbnail viewBox.
+pub const THUMBNAIL_SIZE: f64 = 200.0;
+
+/// Space left around the route, in viewBox units.
+const MARGIN: f64 = 10.0;
+
+/// The route is simplified further until the SVG is below this size.
+pub const MAX_THUMBNAIL_BYTES: usize = 50_000;
+
+/// Points closer than this to the simplified line are dropped, in viewBox
+/// units, doubled as long as the SVG is too large.
+const FIRST_TOLERANCE: f64 = 0.5;
+
+/// Plane coordinates of a point for a map centered on `mean_lat`: the
+/// longitude is scaled by the cosine of the latitude, so that a degree east
+/// is as long as one north.
+pub fn project(point: &TrackPoint, mean_lat: f64) -> (f64, f64) {
+    (point.lon * mean_lat.to_radians().cos(), point.lat)
+}
+
+/// Write one SVG thumbnail per activity in `dir`, named
+/// `<date>_<slug>.svg`, and return the problems of the activities left out.
+/// Points inside the privacy `zones` are removed as in the KML export.
+pub fn export_thumbnails(
+    activities: &[Activity],
+    dir: &Path,
+    zones: &[PrivacyZone],
+) -> Result<Vec<String>> {
+    fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    let mut names = HashSet::new();
+    let mut warnings = vec![];
+    for activity in activities {
+        let Some(svg) = thumbnail_svg(activity, zones) else {
+            warnings.push(format!(
+                "{}: fewer than 2 track points, no thumbnail",
+                activity.path.display()
+            ));
+            continue;
+        };
+        let path = unique_path(dir, &thumbnail_stem(activity), &mut names);
+        fs::write(&path, svg).with_context(|| format!("writing {}", path.display()))?;
+    }
+    Ok(warnings)
+}
+
+/// `<date>_<slug>.svg` in `dir`, with `-2`, `-3`… for the same day and name.
+fn unique_path(dir: &Path, stem: &str, names: &mut HashSet<String>) -> PathBuf {
+    let mut name = format!("{stem}.svg");
+    let mut n = 1;
+    while !names.insert(name.clone()) {
+        n += 1;
+        name = format!("{stem}-{n}.svg");
+    }
+    dir.join(name)
+}
+
+/// Start date and slug of the name, e.g. `2024-06-01_morning-ride`.
+pub fn thumbnail_stem(activity: &Activity) -> String {
+    let date = activity.start.map_or("undated".to_string(), |start| {
+        start.format("%Y-%m-%d").to_string()
+    });
+    format!("{date}_{}", slug(&activity.name))
+}
+
+/// Lowercase ASCII letters and digits, other characters becoming single
+/// dashes; `activity` when nothing is left.
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "activity".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// The route of an activity in a [`THUMBNAIL_SIZE`] square viewBox, with a
+/// dot at the start; `None` with fewer than 2 points left outside the
+/// `zones`.
+pub fn thumbnail_svg(activity: &Activity, zones: &[PrivacyZone]) -> Option<String> {
+    let stripped = strip_private(&activity.points, zones);
+    let points: Vec<&TrackPoint> = stripped.segments.iter().copied().flatten().collect();
+    if points.len() < 2 {
+        return None;
+    }
+    let mean_lat = points.iter().map(|point| point.lat).sum::<f64>() / points.len() as f64;
+
+    let projected: Vec<Vec<(f64, f64)>> = stripped
+        .segments
+        .iter()
+        .map(|segment| {
+            segment
+                .iter()
+                .map(|point| project(point, mean_lat))
+                .collect()
+        })
+        .collect();
+    let fit = fit_to_view(projected.iter().flatten());
+    let lines: Vec<Vec<(f64, f64)>> = projected
+        .iter()
+        .map(|segment| segment.iter().map(|&point| fit(point)).collect())
+        .collect();
+
+    let mut tolerance = FIRST_TOLERANCE;
+    loop {
+        let svg = svg_document(&lines, tolerance);
+        if svg.len() <= MAX_THUMBNAIL_BYTES || tolerance > THUMBNAIL_SIZE {
+            return Some(svg);
+        }
+        tolerance *= 2.0;
+    }
+}
+
+/// The transform fitting the points in the viewBox, keeping their aspect
+/// ratio, centered, with north up.
+fn fit_to_view<'a>(
+    points: impl Iterator<Item = &'a (f64, f64)>,
+) -> impl Fn((f64, f64)) -> (f64, f64) {
+    let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+    let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for &(x, y) in points {
+        (min_x, max_x) = (min_x.min(x), max_x.max(x));
+        (min_y, max_y) = (min_y.min(y), max_y.max(y));
+    }
+    let extent = (max_x - min_x).max(max_y - min_y);
+    let scale = if extent > 0.0 {
+        (THUMBNAIL_SIZE - 2.0 * MARGIN) / extent
+    } else {
+        0.0
+    };
+    let (center_x, center_y) = ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+    move |(x, y)| {
+        (
+            THUMBNAIL_SIZE / 2.0 + (x - center_x) * scale,
+            THUMBNAIL_SIZE / 2.0 - (y - center_y) * scale,
+        )
+    }
+}
+
+fn svg_document(lines: &[Vec<(f64, f64)>], tolerance: f64) -> String {
+    let mut path = String::new();
+    for line in lines {
+        for (i, (x, y)) in simplify(line, tolerance).into_iter().enumerate() {
+            let command = if i == 0 { 'M' } else { 'L' };
+            let _ = write!(path, "{command}{x:.1} {y:.1}");
+        }
+    }
+    let (start_x, start_y) = lines[0][0];
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {THUMBNAIL_SIZE} {THUMBNAIL_SIZE}\" width=\"{THUMBNAIL_SIZE}\" height=\"{THUMBNAIL_SIZE}\">"
+    );
+    let _ = writeln!(
+        svg,
+        "<path d=\"{path}\" fill=\"none\" stroke=\"#fc4c02\" stroke-width=\"2\" stroke-linejoin=\"round\" stroke-linecap=\"round\"/>"
+    );
+    let _ = writeln!(
+        svg,
+        "<circle cx=\"{start_x:.1}\" cy=\"{start_y:.1}\" r=\"4\" fill=\"#2e7d32\" fill-opacity=\"0.6\"/>"
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Ramer–Douglas–Peucker: keep the points further than `tolerance` from the
+/// line between the points kept around them.
+fn simplify(points: &[(f64, f64)], tolerance: f64) -> Vec<(f64, f64)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = ranges.pop() {
+        let farthest = (first + 1..last)
+            .map(|i| (i, distance_to_line(points[i], points[first], points[last])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, distance)) = farthest
+            && distance > tolerance
+        {
+            keep[i] = true;
+            ranges.push((first, i));
+            ranges.push((i, last));
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter_map(|(&point, keep)| keep.then_some(point))
+        .collect()
+}
+
+/// Distance from `point` to the segment from `a` to `b`.
+fn distance_to_line(point: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((point.0 - a.0) * dx + (point.1 - a.1) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (point.0 - (a.0 + t * dx)).hypot(point.1 - (a.1 + t * dy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs_keep_letters_and_digits() {
+        assert_eq!(slug("Col du Galibier (2642 m)!"), "col-du-galibier-2642-m");
+        assert_eq!(slug("Été à vélo"), "t-v-lo");
+        assert_eq!(slug("---"), "activity");
+    }
+
+    #[test]
+    fn straight_runs_are_simplified() {
+        let line: Vec<(f64, f64)> = (0..=10)
+            .map(|i| (i as f64, 0.0))
+            .chain([(10.0, 5.0)])
+            .collect();
+
+        assert_eq!(simplify(&line, 0.5), [(0.0, 0.0), (10.0, 0.0), (10.0, 5.0)]);
+    }
+
+    #[test]
+    fn longitudes_are_scaled_by_latitude() {
+        // Near 60° a degree of longitude is about half a degree of latitude:
+        // a ride 2° east and 0.5° north is about twice as wide as high.
+        let points = [(60.0, 5.0), (60.5, 7.0)].map(|(lat, lon)| TrackPoint {
+            lat,
+            lon,
+            ..Default::default()
+        });
+        let activity = Activity {
+            points: points.to_vec(),
+            ..Default::default()
+        };
+
+        let svg = thumbnail_svg(&activity, &[]).unwrap();
+
+        assert!(svg.contains("d=\"M10.0 145.3L190.0 54.7\""), "{svg}");
+        assert!(svg.contains("<circle cx=\"10.0\" cy=\"145.3\""));
+    }
+}
@@ -0,0 +1,145 @@
+use chrono::{DateTime, FixedOffset};
+
+use crate::activity::{Activity, ActivityId};
+
+/// One ride of a tour.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stage {
+    pub id: ActivityId,
+    pub name: String,
+    pub start: Option<DateTime<FixedOffset>>,
+    pub totals: TourTotals,
+}
+
+/// Distance, elevation gain and moving time added up over stages.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TourTotals {
+    pub distance_km: f64,
+    pub elevation_gain: f64,
+    /// Stages without timestamps add nothing.
+    pub moving_seconds: f64,
+}
+
+impl TourTotals {
+    fn add(&mut self, other: &TourTotals) {
+        self.distance_km += other.distance_km;
+        self.elevation_gain += other.elevation_gain;
+        self.moving_seconds += other.moving_seconds;
+    }
+}
+
+/// Rides given the same tour name, in the order they were ridden.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tour {
+    pub name: String,
+    pub stages: Vec<Stage>,
+}
+
+impl Tour {
+    pub fn totals(&self) -> TourTotals {
+        self.running_totals().last().copied().unwrap_or_default()
+    }
+
+    /// Totals of the tour up to and including each stage.
+    pub fn running_totals(&self) -> Vec<TourTotals> {
+        let mut totals = TourTotals::default();
+        self.stages
+            .iter()
+            .map(|stage| {
+                totals.add(&stage.totals);
+                totals
+            })
+            .collect()
+    }
+}
+
+/// Group the rides by tour name, given with the name each ride is shown
+/// with. Stages are ordered by start, the undated ones last, and tours by
+/// their first stage.
+pub fn group_tours<'a>(
+    rides: impl IntoIterator<Item = (&'a str, String, &'a Activity)>,
+) -> Vec<Tour> {
+    let mut tours: Vec<Tour> = vec![];
+    for (tour, name, activity) in rides {
+        let stage = Stage {
+            id: activity.id,
+            name,
+            start: activity.start,
+            totals: TourTotals {
+                distance_km: activity.distance_km,
+                elevation_gain: activity.elevation_gain,
+                moving_seconds: activity.moving_seconds().unwrap_or_default(),
+            },
+        };
+        match tours.iter_mut().find(|existing| existing.name == tour) {
+            Some(existing) => existing.stages.push(stage),
+            None => tours.push(Tour {
+                name: tour.to_string(),
+                stages: vec![stage],
+            }),
+        }
+    }
+
+    let order = |stage: &Stage| (stage.start.is_none(), stage.start, stage.id);
+    for tour in &mut tours {
+        tour.stages.sort_by_key(order);
+    }
+    tours.sort_by(|a, b| {
+        order(&a.stages[0])
+            .cmp(&order(&b.stages[0]))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    tours
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ride(id: u64, start: Option<&str>, distance_km: f64) -> Activity {
+        Activity {
+            id: format!("{id}-{id:016x}").parse().unwrap(),
+            start: start.map(|start| DateTime::parse_from_rfc3339(start).unwrap()),
+            distance_km,
+            elevation_gain: distance_km * 10.0,
+            released_moving_seconds: Some(distance_km * 180.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stages_add_up_in_the_order_ridden() {
+        let rides = [
+            ("Alps", ride(1, Some("2024-07-02T08:00:00+02:00"), 120.0)),
+            ("Jura", ride(2, Some("2024-05-01T08:00:00+02:00"), 80.0)),
+            ("Alps", ride(3, Some("2024-07-01T08:00:00+02:00"), 100.0)),
+            ("Alps", ride(4, None, 10.0)),
+        ];
+
+        let tours = group_tours(
+            rides
+                .iter()
+                .map(|(tour, activity)| (*tour, activity.id.to_string(), activity)),
+        );
+
+        let names: Vec<&str> = tours.iter().map(|tour| tour.name.as_str()).collect();
+        assert_eq!(names, ["Jura", "Alps"]);
+        let alps = &tours[1];
+        let stages: Vec<ActivityId> = alps.stages.iter().map(|stage| stage.id).collect();
+        assert_eq!(stages, [rides[2].1.id, rides[0].1.id, rides[3].1.id]);
+        let distances: Vec<f64> = alps
+            .running_totals()
+            .iter()
+            .map(|totals| totals.distance_km)
+            .collect();
+        assert_eq!(distances, [100.0, 220.0, 230.0]);
+        assert_eq!(
+            alps.totals(),
+            TourTotals {
+                distance_km: 230.0,
+                elevation_gain: 2_300.0,
+                moving_seconds: 41_400.0,
+            }
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use serde::Deserialize;
+
+use crate::activity::TrackPoint;
+use crate::tuning::{DerivedPoint, Tuning, derived_points, distance_m};
+
+/// Parameters of the detection of ferry and train stretches, from the
+/// `[transport]` table of the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransportDetection {
+    /// Shortest stretch, in meters.
+    pub min_length_m: f64,
+    /// Slowest speed between two points of a stretch, in km/h.
+    pub min_speed_kmh: f64,
+    /// Least share of the distance covered that the straight line from the
+    /// start of the stretch makes.
+    pub min_straightness: f64,
+    /// Largest standard deviation of the speed between points, as a share of
+    /// their average: engines keep a steady speed.
+    pub max_speed_variation: f64,
+    /// Shortest stretch of rides without cadence nor power, which only
+    /// their geometry tells, in meters.
+    pub min_length_without_sensors_m: f64,
+    /// Least straightness of rides without cadence nor power.
+    pub min_straightness_without_sensors: f64,
+}
+
+impl Default for TransportDetection {
+    fn default() -> Self {
+        Self {
+            min_length_m: 2_000.0,
+            min_speed_kmh: 10.0,
+            min_straightness: 0.95,
+            max_speed_variation: 0.2,
+            min_length_without_sensors_m: 5_000.0,
+            min_straightness_without_sensors: 0.995,
+        }
+    }
+}
+
+/// A stretch of a ride on a ferry or a train.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransportSegment {
+    /// Distance from the start of the ride to the stretch, in km.
+    pub start_km: f64,
+    pub length_km: f64,
+    pub seconds: f64,
+    /// Whether the cadence or power was zero all along, or only the
+    /// geometry tells.
+    pub by_sensors: bool,
+}
+
+impl TransportSegment {
+    pub fn speed_kmh(&self) -> f64 {
+        self.length_km / (self.seconds / 3_600.0)
+    }
+}
+
+/// The stretches of a ride where the rider did not pedal, covered in a
+/// nearly straight line at a steady speed. When the ride has cadence or
+/// power, they must be zero all along; without, only very straight and
+/// long stretches count, such as a crossing over water.
+pub fn detect_transport(
+    points: &[TrackPoint],
+    detection: &TransportDetection,
+    tuning: &Tuning,
+) -> Vec<TransportSegment> {
+    let by_sensors = points
+        .iter()
+        .any(|point| point.cadence.is_some() || point.power.is_some());
+    let (min_length_m, min_straightness) = if by_sensors {
+        (detection.min_length_m, detection.min_straightness)
+    } else {
+        (
+            detection.min_length_without_sensors_m,
+            detection.min_straightness_without_sensors,
+        )
+    };
+    let pedaling = |point: &TrackPoint| {
+        point.cadence.is_some_and(|cadence| cadence > 0.0)
+            || point.power.is_some_and(|power| power > 0.0)
+    };
+    let derived: Vec<DerivedPoint> = derived_points(points, tuning).collect();
+
+    let mut segments = vec![];
+    let mut from = 0;
+    while from + 1 < derived.len() {
+        let first = &derived[from];
+        let mut speeds = vec![];
+        let mut to = from;
+        while let Some(next) = derived.get(to + 1) {
+            let previous = &derived[to];
+            let (Some(start), Some(end)) = (previous.seconds, next.seconds) else {
+                break;
+            };
+            let length_m = next.distance_m - previous.distance_m;
+            let seconds = end - start;
+            if next.point.segment_start || pedaling(next.point) || seconds <= 0.0 {
+                break;
+            }
+            let speed = length_m / seconds * 3.6;
+            let straight_m = distance_m(first.point, next.point);
+            if speed < detection.min_speed_kmh
+                || straight_m < min_straightness * (next.distance_m - first.distance_m)
+            {
+                break;
+            }
+            speeds.push(speed);
+            to += 1;
+        }
+
+        let last = &derived[to];
+        let length_m = last.distance_m - first.distance_m;
+        if length_m >= min_length_m && is_steady(&speeds, detection.max_speed_variation) {
+            segments.push(TransportSegment {
+                start_km: first.distance_m / 1_000.0,
+                length_km: length_m / 1_000.0,
+                seconds: last.seconds.unwrap_or_default() - first.seconds.unwrap_or_default(),
+                by_sensors,
+            });
+            from = to;
+        } else {
+            from += 1;
+        }
+    }
+    segments
+}
+
+/// Whether the standard deviation of the speeds is at most `variation` of
+/// their average.
+fn is_steady(speeds: &[f64], variation: f64) -> bool {
+    if speeds.is_empty() {
+        return false;
+    }
+    let count = speeds.len() as f64;
+    let mean = speeds.iter().sum::<f64>() / count;
+    let variance = speeds
+        .iter()
+        .map(|speed| (speed - mean).powi(2))
+        .sum::<f64>()
+        / count;
+    variance.sqrt() <= variation * mean
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::Activity;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> Activity {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        Activity::from_bytes(path.clone(), &std::fs::read(&path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn ferry_crossings_are_found() {
+        // 4 km ridden, 10 km on a ferry at 30 km/h with no cadence, and
+        // 3 km ridden.
+        let ride = fixture("ferry.gpx");
+        let segments = detect_transport(
+            &ride.points,
+            &TransportDetection::default(),
+            &Tuning::default(),
+        );
+        assert_eq!(segments.len(), 1, "{segments:?}");
+        let ferry = segments[0];
+        assert!(ferry.by_sensors);
+        assert!((ferry.start_km - 4.0).abs() < 0.1, "{ferry:?}");
+        assert!((ferry.length_km - 10.0).abs() < 0.1, "{ferry:?}");
+        assert_eq!(ferry.seconds, 1_200.0);
+        assert!((ferry.speed_kmh() - 30.0).abs() < 0.5, "{ferry:?}");
+
+        // Too short for the configuration.
+        let detection = TransportDetection {
+            min_length_m: 12_000.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            detect_transport(&ride.points, &detection, &Tuning::default()),
+            []
+        );
+    }
+
+    #[test]
+    fn rides_are_not_transport() {
+        let detection = TransportDetection::default();
+        // Ridden with power, or too short without sensors.
+        for name in [
+            "intervals.gpx",
+            "mountain.gpx",
+            "flat.gpx",
+            "winding_dense.gpx",
+        ] {
+            assert_eq!(
+                detect_transport(&fixture(name).points, &detection, &Tuning::default()),
+                [],
+                "{name}"
+            );
+        }
+        // Without sensors, the straight line must be long.
+        let ride = fixture("ferry.gpx");
+        let points: Vec<_> = ride
+            .points
+            .iter()
+            .map(|point| TrackPoint {
+                cadence: None,
+                ..*point
+            })
+            .collect();
+        assert_eq!(
+            detect_transport(&points, &detection, &Tuning::default()).len(),
+            1
+        );
+        let detection = TransportDetection {
+            min_length_without_sensors_m: 15_000.0,
+            ..detection
+        };
+        assert_eq!(
+            detect_transport(&points, &detection, &Tuning::default()),
+            []
+        );
+        assert_eq!(detect_transport(&[], &detection, &Tuning::default()), []);
+    }
+}
@@ -0,0 +1,672 @@
+use geo::prelude::Distance;
+use geo::{Haversine, point};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::activity::{MOVING_MIN_SPEED, TrackPoint};
+use crate::surface::estimate_surface;
+
+/// Above this speed across a segment gap, in km/h, [`SegmentJoins::Auto`]
+/// takes the gap for a jump rather than a GPS dropout.
+pub const JOIN_MAX_SPEED_KMH: f64 = 60.0;
+
+/// Longer gaps between segments are pauses for [`SegmentJoins::Auto`], in
+/// seconds.
+pub const JOIN_MAX_GAP_S: f64 = 300.0;
+
+/// Above this median distance between points, in meters, a track is sparse,
+/// such as a planned course: the straight lines between the points cut the
+/// bends, and the distance comes up short.
+pub const SPARSE_SPACING_M: f64 = 300.0;
+
+/// Where the distance of an activity comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DistanceSource {
+    /// Summed between the track points.
+    #[default]
+    Track,
+    /// The `distance` extension of a sparse track's last point.
+    Embedded,
+    /// A sparse track's, raised by [`Tuning::sparse_correction_percent`].
+    Corrected,
+}
+
+/// Whether the distance between the last point of a track segment and the
+/// first point of the next one is ridden.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tui", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentJoins {
+    /// Always count the gap
+    Count,
+    /// Never count it, as for an auto-pause
+    #[default]
+    Ignore,
+    /// Count short gaps crossed at a plausible speed, such as tunnel
+    /// dropouts
+    Auto,
+}
+
+impl SegmentJoins {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Count => "count",
+            Self::Ignore => "ignore",
+            Self::Auto => "auto",
+        }
+    }
+
+    /// Whether the gap from `last` to `first` counts. Without timestamps
+    /// `Auto` cannot tell and ignores it.
+    pub fn joins(self, last: &TrackPoint, first: &TrackPoint) -> bool {
+        match self {
+            Self::Count => true,
+            Self::Ignore => false,
+            Self::Auto => {
+                let (Some(t1), Some(t2)) = (last.time, first.time) else {
+                    return false;
+                };
+                let seconds = (t2 - t1).as_seconds_f64();
+                seconds > 0.0
+                    && seconds <= JOIN_MAX_GAP_S
+                    && distance_m(last, first) / seconds * 3.6 < JOIN_MAX_SPEED_KMH
+            }
+        }
+    }
+}
+
+/// Parameters of the distance, elevation gain and moving time. The defaults
+/// give the metrics of the GPX files as recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tuning {
+    /// Climbs smaller than this are left out of the elevation gain, in
+    /// meters.
+    pub elevation_threshold_m: f64,
+    /// Points reached faster than this from the previous one are GPS glitches
+    /// and dropped, in km/h; 0 keeps them.
+    pub max_speed_kmh: f64,
+    /// Below this speed the rider is considered stopped, in m/s.
+    pub moving_min_speed: f64,
+    /// Points closer than this to the previous one are dropped, in meters.
+    pub jitter_m: f64,
+    pub segment_joins: SegmentJoins,
+    /// Added to the distance of [sparse](SPARSE_SPACING_M) tracks without an
+    /// embedded distance, in percent.
+    pub sparse_correction_percent: f64,
+    /// Stretches whose speed varies more than this may be unpaved, in
+    /// percent of their average speed.
+    pub unpaved_variation_percent: f64,
+    /// Stretches this much slower than the ride on the same grade may be
+    /// unpaved, in percent.
+    pub unpaved_slowdown_percent: f64,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            elevation_threshold_m: 0.0,
+            max_speed_kmh: 0.0,
+            moving_min_speed: MOVING_MIN_SPEED,
+            jitter_m: 0.0,
+            segment_joins: SegmentJoins::Ignore,
+            sparse_correction_percent: 7.0,
+            unpaved_variation_percent: 10.0,
+            unpaved_slowdown_percent: 15.0,
+        }
+    }
+}
+
+/// A parameter of [`Tuning`], as adjusted in the settings popup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TuningParameter {
+    ElevationThreshold,
+    MaxSpeed,
+    MovingMinSpeed,
+    Jitter,
+    SparseCorrection,
+    UnpavedVariation,
+    UnpavedSlowdown,
+}
+
+impl TuningParameter {
+    pub const ALL: [Self; 7] = [
+        Self::ElevationThreshold,
+        Self::MaxSpeed,
+        Self::MovingMinSpeed,
+        Self::Jitter,
+        Self::SparseCorrection,
+        Self::UnpavedVariation,
+        Self::UnpavedSlowdown,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::ElevationThreshold => "elevation threshold",
+            Self::MaxSpeed => "speed spike limit",
+            Self::MovingMinSpeed => "moving speed cutoff",
+            Self::Jitter => "jitter distance",
+            Self::SparseCorrection => "sparse track correction",
+            Self::UnpavedVariation => "unpaved speed variation",
+            Self::UnpavedSlowdown => "unpaved slowdown",
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            Self::ElevationThreshold | Self::Jitter => "m",
+            Self::MaxSpeed => "km/h",
+            Self::MovingMinSpeed => "m/s",
+            Self::SparseCorrection | Self::UnpavedVariation | Self::UnpavedSlowdown => "%",
+        }
+    }
+
+    /// Change of one key press.
+    pub fn step(self) -> f64 {
+        match self {
+            Self::ElevationThreshold | Self::Jitter | Self::SparseCorrection => 1.0,
+            Self::MaxSpeed => 10.0,
+            Self::MovingMinSpeed => 0.1,
+            Self::UnpavedVariation | Self::UnpavedSlowdown => 5.0,
+        }
+    }
+
+    pub fn value(self, tuning: &Tuning) -> f64 {
+        match self {
+            Self::ElevationThreshold => tuning.elevation_threshold_m,
+            Self::MaxSpeed => tuning.max_speed_kmh,
+            Self::MovingMinSpeed => tuning.moving_min_speed,
+            Self::Jitter => tuning.jitter_m,
+            Self::SparseCorrection => tuning.sparse_correction_percent,
+            Self::UnpavedVariation => tuning.unpaved_variation_percent,
+            Self::UnpavedSlowdown => tuning.unpaved_slowdown_percent,
+        }
+    }
+
+    fn value_mut(self, tuning: &mut Tuning) -> &mut f64 {
+        match self {
+            Self::ElevationThreshold => &mut tuning.elevation_threshold_m,
+            Self::MaxSpeed => &mut tuning.max_speed_kmh,
+            Self::MovingMinSpeed => &mut tuning.moving_min_speed,
+            Self::Jitter => &mut tuning.jitter_m,
+            Self::SparseCorrection => &mut tuning.sparse_correction_percent,
+            Self::UnpavedVariation => &mut tuning.unpaved_variation_percent,
+            Self::UnpavedSlowdown => &mut tuning.unpaved_slowdown_percent,
+        }
+    }
+}
+
+impl Tuning {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The numeric parameters changed from their default.
+    pub fn changed(&self) -> impl Iterator<Item = TuningParameter> + '_ {
+        let default = Self::default();
+        TuningParameter::ALL
+            .into_iter()
+            .filter(move |parameter| parameter.value(self) != parameter.value(&default))
+    }
+
+    /// Move a parameter by `steps` steps, rounded to a whole step and kept
+    /// positive. Values are rounded to a tenth so that going back gives the
+    /// default again.
+    pub fn adjust(&mut self, parameter: TuningParameter, steps: f64) {
+        let step = parameter.step();
+        let value = parameter.value_mut(self);
+        let moved = ((*value / step).round() + steps) * step;
+        *value = ((moved * 10.0).round() / 10.0).max(0.0);
+    }
+}
+
+/// Whether the distance from `previous` to `point` is not ridden: `point`
+/// starts the track, or a segment that is not joined to the previous one.
+pub fn starts_segment(
+    previous: Option<&TrackPoint>,
+    point: &TrackPoint,
+    joins: SegmentJoins,
+) -> bool {
+    match previous {
+        Some(previous) => point.segment_start && !joins.joins(previous, point),
+        None => true,
+    }
+}
+
+/// A filter of the points, which drops those it finds wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointFilter {
+    /// Closer than [`Tuning::jitter_m`] to the previous point.
+    Jitter,
+    /// Reached faster than [`Tuning::max_speed_kmh`].
+    Spike,
+}
+
+/// The points kept by the spike and jitter filters, with whether each one
+/// starts a segment. The first point of a segment is always kept.
+pub(crate) fn kept_points<'a>(
+    points: &'a [TrackPoint],
+    tuning: &Tuning,
+) -> impl Iterator<Item = (bool, &'a TrackPoint)> {
+    kept_points_with(points, tuning, |_| {})
+}
+
+/// [`kept_points`], calling `dropped` with the filter of each point left
+/// out.
+fn kept_points_with<'a>(
+    points: &'a [TrackPoint],
+    tuning: &Tuning,
+    mut dropped: impl FnMut(PointFilter) + 'a,
+) -> impl Iterator<Item = (bool, &'a TrackPoint)> {
+    let mut previous: Option<&TrackPoint> = None;
+    points.iter().filter_map(move |point| {
+        let starts = starts_segment(previous, point, tuning.segment_joins);
+        if !starts && let Some(previous) = previous {
+            let meters = distance_m(previous, point);
+            if meters < tuning.jitter_m {
+                dropped(PointFilter::Jitter);
+                return None;
+            }
+            if tuning.max_speed_kmh > 0.0
+                && let (Some(t1), Some(t2)) = (previous.time, point.time)
+            {
+                let seconds = (t2 - t1).as_seconds_f64();
+                if seconds > 0.0 && meters / seconds * 3.6 > tuning.max_speed_kmh {
+                    dropped(PointFilter::Spike);
+                    return None;
+                }
+            }
+        }
+        previous = Some(point);
+        Some((starts, point))
+    })
+}
+
+/// What the point filters of a tuning did to a track.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FilterReport {
+    /// Points dropped by [`PointFilter::Jitter`].
+    pub jitter: usize,
+    /// Points dropped by [`PointFilter::Spike`].
+    pub spikes: usize,
+    /// Distance of every point, in meters.
+    pub raw_m: f64,
+    /// Distance of the points kept, in meters.
+    pub filtered_m: f64,
+}
+
+/// The points each filter drops from the track, and the distance they take
+/// away. Segment gaps count as the tuning says in both distances.
+pub fn filter_report(points: &[TrackPoint], tuning: &Tuning) -> FilterReport {
+    let mut report = FilterReport {
+        raw_m: tuned_distance_m(
+            points,
+            &Tuning {
+                jitter_m: 0.0,
+                max_speed_kmh: 0.0,
+                ..*tuning
+            },
+        ),
+        ..FilterReport::default()
+    };
+    let (mut jitter, mut spikes) = (0, 0);
+    let mut previous: Option<&TrackPoint> = None;
+    for (starts, point) in kept_points_with(points, tuning, |filter| match filter {
+        PointFilter::Jitter => jitter += 1,
+        PointFilter::Spike => spikes += 1,
+    }) {
+        if !starts && let Some(previous) = previous {
+            report.filtered_m += distance_m(previous, point);
+        }
+        previous = Some(point);
+    }
+    report.jitter = jitter;
+    report.spikes = spikes;
+    report
+}
+
+/// Haversine distance between two points, in meters.
+pub(crate) fn distance_m(a: &TrackPoint, b: &TrackPoint) -> f64 {
+    Haversine.distance(point!(x: a.lon, y: a.lat), point!(x: b.lon, y: b.lat))
+}
+
+/// Distance from the start at each point kept, in meters, leaving out the
+/// dropped points and the segment gaps that are not joined.
+pub fn cumulative_distance<'a>(
+    points: &'a [TrackPoint],
+    tuning: &Tuning,
+) -> impl Iterator<Item = (f64, &'a TrackPoint)> {
+    let mut previous: Option<&TrackPoint> = None;
+    let mut total = 0.0;
+    kept_points(points, tuning).map(move |(starts, point)| {
+        if !starts && let Some(previous) = previous {
+            total += distance_m(previous, point);
+        }
+        previous = Some(point);
+        (total, point)
+    })
+}
+
+/// A track point with the distance and time covered since the first one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DerivedPoint<'a> {
+    pub point: &'a TrackPoint,
+    /// Along the track from the first point, in meters.
+    pub distance_m: f64,
+    /// Since the first timed point; `None` without a timestamp.
+    pub seconds: Option<f64>,
+}
+
+/// The points with their [`DerivedPoint`] values, summing the distance
+/// between every two points given but the segment gaps `tuning` does not
+/// join, as [`cumulative_distance`] does.
+pub fn derived_points<'a>(
+    points: impl IntoIterator<Item = &'a TrackPoint>,
+    tuning: &Tuning,
+) -> impl Iterator<Item = DerivedPoint<'a>> {
+    let joins = tuning.segment_joins;
+    let mut previous: Option<&TrackPoint> = None;
+    let mut start = None;
+    let mut total = 0.0;
+    points.into_iter().map(move |point| {
+        if let Some(previous) = previous
+            && !starts_segment(Some(previous), point, joins)
+        {
+            total += distance_m(previous, point);
+        }
+        previous = Some(point);
+        DerivedPoint {
+            point,
+            distance_m: total,
+            seconds: point
+                .time
+                .map(|time| (time - *start.get_or_insert(time)).as_seconds_f64()),
+        }
+    })
+}
+
+/// Length of the track in meters; see [`cumulative_distance`].
+pub fn tuned_distance_m(points: &[TrackPoint], tuning: &Tuning) -> f64 {
+    cumulative_distance(points, tuning)
+        .last()
+        .map_or(0.0, |(total, _)| total)
+}
+
+/// Median distance between consecutive points of the same segment, in
+/// meters.
+pub fn median_spacing_m(points: &[TrackPoint]) -> Option<f64> {
+    let mut spacings: Vec<f64> = points
+        .windows(2)
+        .filter(|pair| !pair[1].segment_start)
+        .map(|pair| distance_m(&pair[0], &pair[1]))
+        .collect();
+    spacings.sort_by(f64::total_cmp);
+    spacings.get(spacings.len() / 2).copied()
+}
+
+/// The distance of a track whose points are `measured_m` apart in total.
+/// [Sparse](SPARSE_SPACING_M) tracks use the distance embedded in the file
+/// when there is one, and are corrected otherwise.
+pub fn track_distance_m(
+    points: &[TrackPoint],
+    measured_m: f64,
+    tuning: &Tuning,
+) -> (f64, DistanceSource) {
+    let Some(spacing) = median_spacing_m(points).filter(|&spacing| spacing > SPARSE_SPACING_M)
+    else {
+        return (measured_m, DistanceSource::Track);
+    };
+    match points.iter().rev().find_map(|point| point.distance_m) {
+        Some(embedded) => {
+            debug!(
+                spacing_m = spacing,
+                measured_m,
+                embedded_m = embedded,
+                "sparse points, distance taken from the file"
+            );
+            (embedded, DistanceSource::Embedded)
+        }
+        None => {
+            let corrected_m = measured_m * (1.0 + tuning.sparse_correction_percent / 100.0);
+            debug!(
+                spacing_m = spacing,
+                measured_m, corrected_m, "sparse points, distance corrected"
+            );
+            (corrected_m, DistanceSource::Corrected)
+        }
+    }
+}
+
+/// Elevation gain in meters, counting a climb once it rises
+/// [`Tuning::elevation_threshold_m`] above the lowest point since the last
+/// counted one. A segment or a point without elevation starts over.
+pub fn tuned_elevation_gain(points: &[TrackPoint], tuning: &Tuning) -> f64 {
+    let mut base: Option<f64> = None;
+    let mut gain = 0.0;
+    for (starts, point) in kept_points(points, tuning) {
+        let Some(elevation) = point.elevation else {
+            base = None;
+            continue;
+        };
+        match base {
+            Some(low) if !starts => {
+                if elevation - low >= tuning.elevation_threshold_m && elevation > low {
+                    gain += elevation - low;
+                    base = Some(elevation);
+                } else if elevation < low {
+                    base = Some(elevation);
+                }
+            }
+            _ => base = Some(elevation),
+        }
+    }
+    gain
+}
+
+/// Time spent above [`Tuning::moving_min_speed`], in seconds. Segment gaps
+/// count only when the tuning joins them. `None` when the points have no
+/// timestamps.
+pub fn tuned_moving_seconds(points: &[TrackPoint], tuning: &Tuning) -> Option<f64> {
+    let mut moving = None;
+    for pair in points.windows(2) {
+        if starts_segment(Some(&pair[0]), &pair[1], tuning.segment_joins) {
+            continue;
+        }
+        let (Some(t1), Some(t2)) = (pair[0].time, pair[1].time) else {
+            continue;
+        };
+        let seconds = (t2 - t1).as_seconds_f64();
+        if seconds <= 0.0 {
+            continue;
+        }
+        let total = moving.get_or_insert(0.0);
+        if distance_m(&pair[0], &pair[1]) / seconds >= tuning.moving_min_speed {
+            *total += seconds;
+        }
+    }
+
+    moving
+}
+
+/// Distance, elevation gain, moving time and guessed unpaved distance of a
+/// track under a tuning.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TunedMetrics {
+    pub distance_km: f64,
+    pub elevation_gain: f64,
+    pub moving_seconds: Option<f64>,
+    pub unpaved_km: Option<f64>,
+}
+
+impl TunedMetrics {
+    pub fn new(points: &[TrackPoint], tuning: &Tuning) -> Self {
+        let (distance_m, _) = track_distance_m(points, tuned_distance_m(points, tuning), tuning);
+        Self {
+            distance_km: distance_m / 1_000.0,
+            elevation_gain: tuned_elevation_gain(points, tuning),
+            moving_seconds: tuned_moving_seconds(points, tuning),
+            unpaved_km: estimate_surface(points, tuning).map(|surface| surface.unpaved_km),
+        }
+    }
+
+    /// Average moving speed, in km/h.
+    pub fn average_speed(&self) -> Option<f64> {
+        self.moving_seconds
+            .filter(|&seconds| seconds > 0.0)
+            .map(|seconds| self.distance_km / (seconds / 3600.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration};
+
+    /// Points going north every `step_m` meters and 10 seconds, at the given
+    /// elevations.
+    fn track(step_m: f64, elevations: &[f64]) -> Vec<TrackPoint> {
+        let start = DateTime::parse_from_rfc3339("2024-06-01T07:00:00Z").unwrap();
+        elevations
+            .iter()
+            .enumerate()
+            .map(|(i, &elevation)| TrackPoint {
+                lat: 45.0 + i as f64 * step_m / 111_195.08,
+                lon: 5.0,
+                elevation: Some(elevation),
+                time: Some(start + Duration::seconds(10 * i as i64)),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn defaults_count_every_rise() {
+        let points = track(50.0, &[100.0, 101.0, 100.5, 102.0, 101.0]);
+        let tuning = Tuning::default();
+
+        assert!((tuned_distance_m(&points, &tuning) - 200.0).abs() < 0.01);
+        assert_eq!(tuned_elevation_gain(&points, &tuning), 2.5);
+        assert!(tuning.is_default());
+    }
+
+    #[test]
+    fn sparse_tracks_use_the_embedded_distance() {
+        let mut points = track(400.0, &[0.0; 4]);
+        // A 5 km gap to a new segment does not make the track sparser.
+        points[3].lat += 0.05;
+        points[3].segment_start = true;
+        assert!((median_spacing_m(&points).unwrap() - 400.0).abs() < 0.01);
+
+        let tuning = Tuning::default();
+        let (distance, source) = track_distance_m(&points, 800.0, &tuning);
+        assert!((distance - 856.0).abs() < 0.01);
+        assert_eq!(source, DistanceSource::Corrected);
+
+        points[3].distance_m = Some(900.0);
+        assert_eq!(
+            track_distance_m(&points, 800.0, &tuning),
+            (900.0, DistanceSource::Embedded)
+        );
+        assert_eq!(
+            track_distance_m(&track(50.0, &[0.0; 4]), 150.0, &tuning),
+            (150.0, DistanceSource::Track)
+        );
+    }
+
+    #[test]
+    fn threshold_ignores_small_bumps() {
+        let points = track(50.0, &[100.0, 101.0, 100.5, 102.0, 101.0, 108.0]);
+        let tuning = Tuning {
+            elevation_threshold_m: 3.0,
+            ..Tuning::default()
+        };
+
+        assert_eq!(tuned_elevation_gain(&points, &tuning), 8.0);
+    }
+
+    #[test]
+    fn spikes_and_jitter_are_dropped() {
+        let mut points = track(50.0, &[0.0; 5]);
+        // A glitch 2 km east, reached in 10 seconds.
+        points[2].lon += 2_000.0 / 78_700.0;
+        let tuning = Tuning {
+            max_speed_kmh: 120.0,
+            ..Tuning::default()
+        };
+        assert!((tuned_distance_m(&points, &tuning) - 200.0).abs() < 0.5);
+        let report = filter_report(&points, &tuning);
+        assert_eq!((report.jitter, report.spikes), (0, 1));
+        assert!(report.raw_m > 4_000.0);
+        assert!((report.filtered_m - 200.0).abs() < 0.5);
+
+        let tuning = Tuning {
+            jitter_m: 60.0,
+            ..Tuning::default()
+        };
+        let points = track(50.0, &[0.0; 5]);
+        assert!((tuned_distance_m(&points, &tuning) - 200.0).abs() < 0.01);
+        assert_eq!(kept_points(&points, &tuning).count(), 3);
+
+        let report = filter_report(&points, &tuning);
+        assert_eq!((report.jitter, report.spikes), (2, 0));
+        assert!((report.raw_m - 200.0).abs() < 0.01);
+        assert!((report.filtered_m - 200.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn segments_are_not_joined() {
+        let mut points = track(50.0, &[0.0, 0.0, 10.0, 10.0]);
+        points[2].segment_start = true;
+
+        assert!((tuned_distance_m(&points, &Tuning::default()) - 100.0).abs() < 0.01);
+        assert_eq!(tuned_elevation_gain(&points, &Tuning::default()), 0.0);
+    }
+
+    #[test]
+    fn metrics_follow_the_tuning() {
+        // 5 m/s, then two slow steps at 0.3 m/s.
+        let points = track(50.0, &[0.0, 2.0, 4.0]);
+        let mut slow = track(3.0, &[4.0, 4.0, 4.0]).split_off(1);
+        for (point, last) in slow.iter_mut().zip(1..) {
+            point.lat += points[2].lat - 45.0;
+            point.time = Some(points[2].time.unwrap() + Duration::seconds(10 * last));
+        }
+        let points: Vec<TrackPoint> = points.into_iter().chain(slow).collect();
+
+        let raw = TunedMetrics::new(&points, &Tuning::default());
+        assert_eq!(raw.moving_seconds, Some(20.0));
+        assert!((raw.average_speed().unwrap() - 0.106 / (20.0 / 3600.0)).abs() < 0.01);
+        assert!((raw.elevation_gain - 4.0).abs() < 1e-9);
+
+        let tuned = TunedMetrics::new(
+            &points,
+            &Tuning {
+                moving_min_speed: 0.2,
+                elevation_threshold_m: 5.0,
+                ..Tuning::default()
+            },
+        );
+        assert_eq!(tuned.moving_seconds, Some(40.0));
+        assert_eq!(tuned.elevation_gain, 0.0);
+        assert_eq!(
+            TunedMetrics::new(&points[..1], &Tuning::default()).average_speed(),
+            None
+        );
+    }
+
+    #[test]
+    fn adjust_rounds_to_steps() {
+        let mut tuning = Tuning::default();
+        tuning.adjust(TuningParameter::MovingMinSpeed, 1.0);
+        tuning.adjust(TuningParameter::Jitter, -1.0);
+        assert_eq!(tuning.moving_min_speed, 0.6);
+        assert_eq!(tuning.jitter_m, 0.0);
+        assert_eq!(
+            tuning.changed().collect::<Vec<_>>(),
+            [TuningParameter::MovingMinSpeed]
+        );
+
+        tuning.adjust(TuningParameter::MovingMinSpeed, -1.0);
+        assert!(tuning.is_default());
+    }
+}
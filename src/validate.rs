@@ -0,0 +1,93 @@
+use rayon::prelude::*;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::activity::Activity;
+use crate::quality::issues;
+use crate::stream::read_track_points;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// Not valid GPX, but the track points could still be read.
+    Recovered,
+    Failed,
+}
+
+/// Outcome of checking one file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validation {
+    pub path: PathBuf,
+    pub status: Status,
+    pub issues: Vec<String>,
+}
+
+/// Parse a file like the loader does, falling back to the lenient track
+/// point reader, and run the data-quality checks on what could be read.
+pub fn validate_file(path: &Path) -> Validation {
+    let validation = |status, issues| Validation {
+        path: path.to_path_buf(),
+        status,
+        issues,
+    };
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => return validation(Status::Failed, vec![err.to_string()]),
+    };
+
+    match Activity::from_bytes(path.to_path_buf(), &bytes) {
+        Ok(activity) => validation(Status::Ok, strings(issues(&activity))),
+        Err(err) => match read_track_points(&bytes) {
+            Ok(points) => {
+                let activity = Activity {
+                    points,
+                    ..Default::default()
+                };
+                let mut found = vec![format!("invalid GPX ({err})")];
+                found.extend(strings(issues(&activity)));
+                validation(Status::Recovered, found)
+            }
+            Err(_) => validation(Status::Failed, vec![err.to_string()]),
+        },
+    }
+}
+
+fn strings<T: ToString>(values: Vec<T>) -> Vec<String> {
+    values.iter().map(ToString::to_string).collect()
+}
+
+/// Check the files in parallel, keeping their order.
+pub fn validate_files(paths: &[PathBuf]) -> Vec<Validation> {
+    paths.par_iter().map(|path| validate_file(path)).collect()
+}
+
+/// One line per file, then the counts per status.
+pub fn write_validation_report(validations: &[Validation], out: &mut impl Write) -> io::Result<()> {
+    for validation in validations {
+        let status = match validation.status {
+            Status::Ok => "OK",
+            Status::Recovered => "RECOVERED",
+            Status::Failed => "FAILED",
+        };
+        write!(out, "{status:<10} {}", validation.path.display())?;
+        if !validation.issues.is_empty() {
+            write!(out, ": {}", validation.issues.join(", "))?;
+        }
+        writeln!(out)?;
+    }
+
+    let count = |status| validations.iter().filter(|v| v.status == status).count();
+    let with_issues = validations
+        .iter()
+        .filter(|v| v.status == Status::Ok && !v.issues.is_empty())
+        .count();
+    writeln!(
+        out,
+        "\n{} files: {} ok ({with_issues} with issues), {} recovered, {} failed",
+        validations.len(),
+        count(Status::Ok),
+        count(Status::Recovered),
+        count(Status::Failed),
+    )
+}
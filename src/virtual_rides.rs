@@ -0,0 +1,50 @@
+/// Tag given to virtual rides.
+pub const VIRTUAL_TAG: &str = "virtual";
+
+/// South-west and north-east corners, as (latitude, longitude).
+type Bounds = ((f64, f64), (f64, f64));
+
+/// Fictional Zwift worlds, drawn on an empty stretch of ocean. Worlds laid over real places (Richmond, London,
+/// New York...) cannot be told apart from outdoor rides by their position.
+const VIRTUAL_WORLDS: [(&str, Bounds); 4] = [
+    ("Watopia", ((-11.76, 166.86), (-11.60, 167.04))),
+    ("Makuri Islands", ((-10.87, 165.75), (-10.70, 165.90))),
+    ("France", ((-21.77, 166.13), (-21.62, 166.27))),
+    ("Crit City", ((-10.40, 165.78), (-10.36, 165.82))),
+];
+
+/// Name of the virtual world around a point, if any.
+pub fn virtual_world(lat: f64, lon: f64) -> Option<&'static str> {
+    VIRTUAL_WORLDS
+        .iter()
+        .find(|(_, (south_west, north_east))| {
+            (south_west.0..=north_east.0).contains(&lat)
+                && (south_west.1..=north_east.1).contains(&lon)
+        })
+        .map(|&(name, _)| name)
+}
+
+/// Whether a ride was recorded in a virtual world: the file was written by
+/// Zwift, or the ride starts in one of its fictional worlds.
+pub fn is_virtual_ride(creator: Option<&str>, start_point: Option<(f64, f64)>) -> bool {
+    creator.is_some_and(|creator| creator.to_lowercase().contains("zwift"))
+        || start_point.is_some_and(|(lat, lon)| virtual_world(lat, lon).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zwift_creator_or_world() {
+        assert!(is_virtual_ride(Some("Zwift"), Some((37.54, -77.43))));
+        assert!(is_virtual_ride(Some("zwift.com"), None));
+        assert!(is_virtual_ride(Some("Wahoo"), Some((-11.64, 166.95))));
+        assert!(!is_virtual_ride(
+            Some("Garmin Edge 530"),
+            Some((45.19, 5.72))
+        ));
+        assert!(!is_virtual_ride(None, None));
+        assert_eq!(virtual_world(-21.7, 166.2), Some("France"));
+    }
+}
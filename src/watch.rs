@@ -0,0 +1,320 @@
+//! Keeping the export files up to date as the watched files come, change and
+//! go, for `--watch`.
+
+use anyhow::{Error, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+use crate::activity::{Activity, ActivityId};
+use crate::loader::{LoadOptions, is_stdin, is_url, load_each, resolve_paths};
+use crate::prometheus::export_prometheus;
+use crate::report::export_csv;
+
+/// Time between two looks at the watched files.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The files written from the activities, each one optional.
+#[derive(Debug, Default)]
+pub struct Exports {
+    pub prometheus: Option<PathBuf>,
+    pub csv: Option<PathBuf>,
+    #[cfg(feature = "sqlite")]
+    pub sqlite: Option<PathBuf>,
+    /// Also write the track points to the SQLite database.
+    #[cfg(feature = "sqlite")]
+    pub with_points: bool,
+}
+
+impl Exports {
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "sqlite")]
+        let sqlite = self.sqlite.is_some();
+        #[cfg(not(feature = "sqlite"))]
+        let sqlite = false;
+
+        self.prometheus.is_none() && self.csv.is_none() && !sqlite
+    }
+
+    /// Write the `changed` activities and take the `removed` ones out of the
+    /// exports; the Prometheus totals are made again from `all` of them.
+    pub fn write(
+        &self,
+        changed: &[Activity],
+        removed: &[ActivityId],
+        all: &[Activity],
+    ) -> Result<()> {
+        if let Some(path) = &self.csv {
+            export_csv(changed, removed, path)?;
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(path) = &self.sqlite {
+            crate::sqlite::export_sqlite(changed, path, self.with_points)?;
+            crate::sqlite::remove_from_sqlite(removed, path)?;
+        }
+        if let Some(path) = &self.prometheus {
+            export_prometheus(all, path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Modification time and length of a file, which tell it changed.
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// What was seen of a watched file.
+#[derive(Debug, Clone, Copy)]
+struct Seen {
+    stamp: Stamp,
+    /// The activity exported from it, if any.
+    id: Option<ActivityId>,
+}
+
+/// What one look at the watched files changed in the exports.
+#[derive(Debug, Default)]
+pub struct WatchUpdate {
+    pub updated: usize,
+    pub removed: usize,
+    /// Files that could not be read; they are read again once they change.
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+/// The watched files and the activities exported from them.
+pub struct Watch<F> {
+    patterns: Vec<PathBuf>,
+    options: LoadOptions,
+    /// Prepares an activity read, telling whether it is exported.
+    keep: F,
+    exports: Exports,
+    files: BTreeMap<PathBuf, Seen>,
+    /// Exported activities, without their points.
+    activities: BTreeMap<ActivityId, Activity>,
+    /// Activities read and removed since the exports last failed, written
+    /// at the next look.
+    unwritten: Vec<Activity>,
+    unremoved: Vec<ActivityId>,
+}
+
+impl<F: FnMut(&mut Activity) -> bool> Watch<F> {
+    /// Watch the files of `patterns`, from which the `exported` activities
+    /// were just written; the other files are read again once they change.
+    pub fn new(
+        patterns: Vec<PathBuf>,
+        options: LoadOptions,
+        keep: F,
+        exports: Exports,
+        exported: Vec<Activity>,
+    ) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        for path in watched(&patterns)? {
+            let id = exported
+                .iter()
+                .find(|activity| activity.path == path || activity.alternates.contains(&path))
+                .map(|activity| activity.id);
+            let stamp = stamp(&path);
+            files.insert(path, Seen { stamp, id });
+        }
+        let activities = exported
+            .into_iter()
+            .map(|mut activity| {
+                activity.release_points();
+                (activity.id, activity)
+            })
+            .collect();
+
+        Ok(Self {
+            patterns,
+            options,
+            keep,
+            exports,
+            files,
+            activities,
+            unwritten: Vec::new(),
+            unremoved: Vec::new(),
+        })
+    }
+
+    /// Read the new and changed files, and write their activities to the
+    /// exports along with the removal of those of the files gone. When the
+    /// exports cannot be written, the next call tries again.
+    pub fn poll(&mut self) -> Result<WatchUpdate> {
+        let mut changed = BTreeMap::new();
+        let paths = watched(&self.patterns)?;
+        for path in &paths {
+            let stamp = stamp(path);
+            if self.files.get(path).is_none_or(|seen| seen.stamp != stamp) {
+                changed.insert(path.clone(), stamp);
+            }
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let to_read: Vec<PathBuf> = changed.keys().cloned().collect();
+        load_each(&to_read, &self.options, |path, result| {
+            let _ = sender.send((path.to_path_buf(), result));
+        });
+        drop(sender);
+
+        let mut update = WatchUpdate::default();
+        let mut written = std::mem::take(&mut self.unwritten);
+        let mut removed = std::mem::take(&mut self.unremoved);
+        for (path, result) in receiver {
+            let previous = self.files.get(&path).and_then(|seen| seen.id);
+            let id = match result {
+                Ok(mut activity) => {
+                    let id = (self.keep)(&mut activity).then_some(activity.id);
+                    if id.is_some() {
+                        written.push(activity);
+                    }
+                    id
+                }
+                // A file still being written keeps its activity until it
+                // can be read again.
+                Err(err) => {
+                    update.failed.push((path.clone(), err));
+                    previous
+                }
+            };
+            if let Some(previous) = previous
+                && id != Some(previous)
+            {
+                removed.push(previous);
+            }
+            let stamp = changed[&path];
+            self.files.insert(path, Seen { stamp, id });
+        }
+        self.files.retain(|path, seen| {
+            let kept = paths.contains(path);
+            if !kept && let Some(id) = seen.id {
+                removed.push(id);
+            }
+            kept
+        });
+
+        // Another file, such as the FIT of the same ride, may still hold it.
+        removed.retain(|id| !self.files.values().any(|seen| seen.id == Some(*id)));
+        removed.sort();
+        removed.dedup();
+        if written.is_empty() && removed.is_empty() {
+            return Ok(update);
+        }
+
+        // Read again since a failed write, a ride is written once as it is now.
+        written.reverse();
+        written.sort_by_key(|activity| (activity.start, activity.id));
+        written.dedup_by_key(|activity| activity.id);
+        written.retain(|activity| !removed.contains(&activity.id));
+        for id in &removed {
+            self.activities.remove(id);
+        }
+        for activity in &written {
+            let mut kept = activity.clone();
+            kept.release_points();
+            self.activities.insert(kept.id, kept);
+        }
+        let all: Vec<Activity> = self.activities.values().cloned().collect();
+        if let Err(err) = self.exports.write(&written, &removed, &all) {
+            self.unwritten = written;
+            self.unremoved = removed;
+            return Err(err);
+        }
+        update.updated = written.len();
+        update.removed = removed.len();
+
+        Ok(update)
+    }
+}
+
+/// The files of the patterns that can change; URLs and the standard input
+/// are read once.
+fn watched(patterns: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    Ok(resolve_paths(patterns)?
+        .paths
+        .into_iter()
+        .filter(|path| !is_url(path) && !is_stdin(path))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    fn rows(path: &Path) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|row| row.split(',').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn exports_follow_the_files_as_they_come_change_and_go() {
+        let dir = std::env::temp_dir().join(format!("cyclemetrics-watch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let rides = dir.join("rides");
+        fs::create_dir_all(&rides).unwrap();
+        let exports = Exports {
+            prometheus: Some(dir.join("rides.prom")),
+            csv: Some(dir.join("rides.csv")),
+            #[cfg(feature = "sqlite")]
+            sqlite: None,
+            #[cfg(feature = "sqlite")]
+            with_points: false,
+        };
+        let mut watch = Watch::new(
+            vec![rides.join("*.gpx")],
+            LoadOptions::default(),
+            |_: &mut Activity| true,
+            exports,
+            Vec::new(),
+        )
+        .unwrap();
+        let flat = "1717225200-82858a95dfd22838";
+        let mountain = "1720940400-98fedefd6347cc2d";
+        let multi_track = "1717398000-fbe9096c2fe46d35";
+
+        fs::copy(fixture("flat.gpx"), rides.join("a.gpx")).unwrap();
+        let update = watch.poll().unwrap();
+        assert_eq!((update.updated, update.removed), (1, 0));
+        assert_eq!(rows(&dir.join("rides.csv")), [flat]);
+
+        fs::copy(fixture("mountain.gpx"), rides.join("b.gpx")).unwrap();
+        let update = watch.poll().unwrap();
+        assert_eq!((update.updated, update.removed), (1, 0));
+        assert_eq!(rows(&dir.join("rides.csv")), [flat, mountain]);
+        let textfile = fs::read_to_string(dir.join("rides.prom")).unwrap();
+        assert!(textfile.contains("cyclemetrics_activity_count 2"));
+
+        // Nothing changed, nothing written.
+        let update = watch.poll().unwrap();
+        assert_eq!((update.updated, update.removed), (0, 0));
+
+        fs::copy(fixture("multi_track.gpx"), rides.join("a.gpx")).unwrap();
+        let update = watch.poll().unwrap();
+        assert_eq!((update.updated, update.removed), (1, 1));
+        assert_eq!(rows(&dir.join("rides.csv")), [mountain, multi_track]);
+
+        fs::remove_file(rides.join("b.gpx")).unwrap();
+        let update = watch.poll().unwrap();
+        assert_eq!((update.updated, update.removed), (0, 1));
+        assert_eq!(rows(&dir.join("rides.csv")), [multi_track]);
+        let textfile = fs::read_to_string(dir.join("rides.prom")).unwrap();
+        assert!(textfile.contains("cyclemetrics_activity_count 1"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
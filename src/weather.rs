@@ -0,0 +1,207 @@
+use anyhow::{Context, Result, bail};
+use chrono::{Local, NaiveDate};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::activity::Activity;
+
+/// Days with at least this much precipitation count as rainy, in mm.
+const RAIN_THRESHOLD_MM: f64 = 1.0;
+
+/// Conditions of one day, as provided by the user.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct DailyWeather {
+    pub temp_c: f64,
+    pub wind_kmh: f64,
+    pub precipitation_mm: f64,
+}
+
+impl DailyWeather {
+    pub fn is_rainy(&self) -> bool {
+        self.precipitation_mm >= RAIN_THRESHOLD_MM
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Record {
+    date: String,
+    #[serde(flatten)]
+    weather: DailyWeather,
+}
+
+/// Historical daily weather read from a user-provided file, keyed by date.
+#[derive(Debug, Clone, Default)]
+pub struct WeatherCache {
+    days: HashMap<NaiveDate, DailyWeather>,
+}
+
+impl WeatherCache {
+    /// Read a `.json` array of records or a CSV file with a header row, both
+    /// with the `date`, `temp_c`, `wind_kmh` and `precipitation_mm` fields.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        let cache = if is_json {
+            Self::parse_json(&content)
+        } else {
+            Self::parse_csv(&content)
+        };
+
+        cache.with_context(|| format!("parsing {}", path.display()))
+    }
+
+    pub fn parse_json(content: &str) -> Result<Self> {
+        let records: Vec<Record> = serde_json::from_str(content)?;
+        Self::from_records(records)
+    }
+
+    /// Columns may come in any order; blank lines are skipped.
+    pub fn parse_csv(content: &str) -> Result<Self> {
+        let mut lines = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        let header: Vec<&str> = lines
+            .next()
+            .map_or("", |(_, line)| line)
+            .split(',')
+            .map(str::trim)
+            .collect();
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|&column| column == name)
+                .with_context(|| format!("missing column {name}"))
+        };
+        let columns = [
+            column("date")?,
+            column("temp_c")?,
+            column("wind_kmh")?,
+            column("precipitation_mm")?,
+        ];
+
+        let mut records = vec![];
+        for (i, line) in lines {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [date, temp, wind, precipitation] = columns.map(|c| fields.get(c).copied());
+            let (Some(date), Some(temp), Some(wind), Some(precipitation)) =
+                (date, temp, wind, precipitation)
+            else {
+                bail!("line {}: expected {} fields", i + 1, header.len());
+            };
+            let number = |value: &str| {
+                value
+                    .parse::<f64>()
+                    .with_context(|| format!("line {}: invalid number {value:?}", i + 1))
+            };
+            records.push(Record {
+                date: date.to_string(),
+                weather: DailyWeather {
+                    temp_c: number(temp)?,
+                    wind_kmh: number(wind)?,
+                    precipitation_mm: number(precipitation)?,
+                },
+            });
+        }
+
+        Self::from_records(records)
+    }
+
+    fn from_records(records: Vec<Record>) -> Result<Self> {
+        let mut days = HashMap::new();
+        for record in records {
+            let date = NaiveDate::parse_from_str(&record.date, "%Y-%m-%d")
+                .with_context(|| format!("invalid date {:?}", record.date))?;
+            days.insert(date, record.weather);
+        }
+
+        Ok(Self { days })
+    }
+
+    /// Weather of the local day the activity started on, `None` when the
+    /// activity has no date or the day is missing from the file.
+    pub fn for_activity(&self, activity: &Activity) -> Option<&DailyWeather> {
+        let date = activity.start?.with_timezone(&Local).date_naive();
+        self.days.get(&date)
+    }
+
+    /// Kilometers ridden on rainy days, dry days and days without data.
+    pub fn rain_split<'a>(
+        &self,
+        activities: impl IntoIterator<Item = &'a Activity>,
+    ) -> (f64, f64, f64) {
+        activities
+            .into_iter()
+            .fold((0.0, 0.0, 0.0), |(rain, dry, unknown), activity| {
+                let km = activity.distance_km;
+                match self.for_activity(activity) {
+                    Some(weather) if weather.is_rainy() => (rain + km, dry, unknown),
+                    Some(_) => (rain, dry + km, unknown),
+                    None => (rain, dry, unknown + km),
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, TimeZone};
+
+    /// Activity starting at noon local time, so the date does not depend on
+    /// the time zone the tests run in.
+    fn activity(date: &str, distance_km: f64) -> Activity {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        let start = Local
+            .from_local_datetime(&date.and_hms_opt(12, 0, 0).unwrap())
+            .unwrap();
+        Activity {
+            start: Some(DateTime::from(start)),
+            distance_km,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn csv_columns_in_any_order() {
+        let cache = WeatherCache::parse_csv(
+            "date,precipitation_mm,temp_c,wind_kmh\n2024-06-01,3.5,14.0,20\n\n2024-06-02,0,22.5,5\n",
+        )
+        .unwrap();
+
+        let weather = cache.for_activity(&activity("2024-06-02", 10.0)).unwrap();
+        assert_eq!(weather.temp_c, 22.5);
+        assert!(!weather.is_rainy());
+    }
+
+    #[test]
+    fn missing_days_and_rain_split() {
+        let cache = WeatherCache::parse_json(
+            r#"[{"date": "2024-06-01", "temp_c": 14, "wind_kmh": 20, "precipitation_mm": 3.5},
+                {"date": "2024-06-02", "temp_c": 22, "wind_kmh": 5, "precipitation_mm": 0}]"#,
+        )
+        .unwrap();
+        let activities = [
+            activity("2024-06-01", 10.0),
+            activity("2024-06-02", 20.0),
+            activity("2024-06-05", 40.0),
+            Activity::default(),
+        ];
+
+        assert_eq!(cache.for_activity(&activities[2]), None);
+        assert_eq!(cache.rain_split(&activities), (10.0, 20.0, 40.0));
+    }
+
+    #[test]
+    fn invalid_csv_names_the_line() {
+        let error =
+            WeatherCache::parse_csv("date,temp_c,wind_kmh,precipitation_mm\n2024-06-01,x,1,2")
+                .unwrap_err();
+        assert_eq!(error.to_string(), "line 2: invalid number \"x\"");
+    }
+}
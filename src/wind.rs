@@ -0,0 +1,169 @@
+use crate::activity::TrackPoint;
+use crate::routes::{ROUTE_CELL_M, RouteSignature};
+use crate::tuning::{Tuning, derived_points, distance_m, tuned_moving_seconds};
+
+/// Share of the way out near the way back, and the other way round, for a
+/// ride to be an out-and-back.
+pub const MIN_OUT_AND_BACK_OVERLAP: f64 = 0.8;
+
+/// Length of the stretches of the way out compared with the same road on
+/// the way back, in meters.
+pub const WIND_SEGMENT_M: f64 = 1_000.0;
+
+/// Stretches steeper than this either way are left out, as gravity would
+/// pass for wind, in percent.
+pub const WIND_MAX_GRADE_PERCENT: f64 = 2.0;
+
+/// Wind estimated from the speeds on the same road both ways, assuming the
+/// rider put the same effort into both.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindEstimate {
+    /// Speed lost to the wind on the way out and gained on the way back, in
+    /// km/h; negative for a tailwind on the way out.
+    pub headwind_out_kmh: f64,
+    /// Average speed on the compared stretches without the wind, in km/h.
+    pub neutral_speed_kmh: f64,
+    /// Length of the way out compared with the way back, in km.
+    pub matched_km: f64,
+    pub stretches: usize,
+}
+
+/// The wind of an out-and-back ride, turning around at the point farthest
+/// from the start. Every [`WIND_SEGMENT_M`] stretch of the way out is
+/// compared with the road between the same two points on the way back;
+/// each pair counts by its length. `None` when the way back does not follow
+/// the way out or no flat enough stretch has a moving time both ways.
+pub fn estimate_wind(points: &[TrackPoint], tuning: &Tuning) -> Option<WindEstimate> {
+    let first = points.first()?;
+    let turn = (0..points.len())
+        .max_by(|&a, &b| distance_m(first, &points[a]).total_cmp(&distance_m(first, &points[b])))?;
+    let (out, back) = (&points[..=turn], &points[turn..]);
+    let overlap = RouteSignature::new(out)?.similarity(&RouteSignature::new(back)?);
+    if overlap < MIN_OUT_AND_BACK_OVERLAP {
+        return None;
+    }
+
+    let mut matched_m = 0.0;
+    let (mut wind, mut neutral) = (0.0, 0.0);
+    let mut stretches = 0;
+    for (from, to) in stretches_of(out, tuning) {
+        let climb_m =
+            out[to].elevation.unwrap_or_default() - out[from].elevation.unwrap_or_default();
+        let length_m = derived_points(&out[from..=to], tuning).last()?.distance_m;
+        if (climb_m / length_m * 100.0).abs() > WIND_MAX_GRADE_PERCENT {
+            continue;
+        }
+        // The way back reaches the end of the stretch first.
+        let (Some(back_from), Some(back_to)) = (nearest(back, &out[to]), nearest(back, &out[from]))
+        else {
+            continue;
+        };
+        if back_from >= back_to {
+            continue;
+        }
+        let (Some((_, out_kmh)), Some((back_m, back_kmh))) = (
+            speed(&out[from..=to], tuning),
+            speed(&back[back_from..=back_to], tuning),
+        ) else {
+            continue;
+        };
+        // A detour on the way back is another road.
+        if !(0.8..=1.25).contains(&(back_m / length_m)) {
+            continue;
+        }
+        matched_m += length_m;
+        wind += length_m * (back_kmh - out_kmh) / 2.0;
+        neutral += length_m * (back_kmh + out_kmh) / 2.0;
+        stretches += 1;
+    }
+
+    (stretches > 0).then(|| WindEstimate {
+        headwind_out_kmh: wind / matched_m,
+        neutral_speed_kmh: neutral / matched_m,
+        matched_km: matched_m / 1_000.0,
+        stretches,
+    })
+}
+
+/// First and last index of each whole [`WIND_SEGMENT_M`] stretch.
+fn stretches_of(points: &[TrackPoint], tuning: &Tuning) -> Vec<(usize, usize)> {
+    let mut stretches = vec![];
+    let (mut from, mut from_m) = (0, 0.0);
+    for (i, point) in derived_points(points, tuning).enumerate() {
+        if point.distance_m - from_m >= WIND_SEGMENT_M {
+            stretches.push((from, i));
+            (from, from_m) = (i, point.distance_m);
+        }
+    }
+    stretches
+}
+
+/// Index of the point of `points` closest to `to`, if within a route cell.
+fn nearest(points: &[TrackPoint], to: &TrackPoint) -> Option<usize> {
+    points
+        .iter()
+        .map(|point| distance_m(point, to))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .filter(|&(_, distance)| distance <= ROUTE_CELL_M)
+        .map(|(i, _)| i)
+}
+
+/// Length in meters and moving speed in km/h of a stretch.
+fn speed(points: &[TrackPoint], tuning: &Tuning) -> Option<(f64, f64)> {
+    let length_m = derived_points(points, tuning).last()?.distance_m;
+    let seconds = tuned_moving_seconds(points, tuning).filter(|&seconds| seconds > 0.0)?;
+    Some((length_m, length_m / seconds * 3.6))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::Activity;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> Activity {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        Activity::from_bytes(path.clone(), &std::fs::read(&path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn speeds_both_ways_give_the_wind() {
+        // 10 km north at 27.5 km/h and back at 22.5 km/h, i.e. 10% faster
+        // and slower than 25 km/h.
+        let ride = fixture("out_and_back.gpx");
+        let wind = estimate_wind(&ride.points, &Tuning::default()).unwrap();
+        assert!((wind.headwind_out_kmh + 2.5).abs() < 0.05, "{wind:?}");
+        assert!((wind.neutral_speed_kmh - 25.0).abs() < 0.05, "{wind:?}");
+        assert_eq!(wind.stretches, 10);
+        assert!((wind.matched_km - 10.0).abs() < 0.05, "{wind:?}");
+
+        // The other way round, the wind is a headwind on the way out.
+        let mut reversed = ride.points.clone();
+        reversed.reverse();
+        let start = ride.points[0].time.unwrap();
+        let end = reversed[0].time.unwrap();
+        for point in &mut reversed {
+            point.time = Some(start + (end - point.time.unwrap()));
+            point.segment_start = false;
+        }
+        let wind = estimate_wind(&reversed, &Tuning::default()).unwrap();
+        assert!((wind.headwind_out_kmh - 2.5).abs() < 0.05, "{wind:?}");
+    }
+
+    #[test]
+    fn only_flat_out_and_backs_are_estimated() {
+        // Up a climb and back down.
+        let ride = fixture("mountain.gpx");
+        assert_eq!(estimate_wind(&ride.points, &Tuning::default()), None);
+        let ride = fixture("flat.gpx");
+        assert_eq!(estimate_wind(&ride.points, &Tuning::default()), None);
+        // The way out alone.
+        let ride = fixture("out_and_back.gpx");
+        let out = &ride.points[..ride.points.len() / 2];
+        assert_eq!(estimate_wind(out, &Tuning::default()), None);
+        assert_eq!(estimate_wind(&[], &Tuning::default()), None);
+    }
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "tui")]
+
+mod common;
+
+use chrono::NaiveDate;
+use common::{fixture, fixture_path};
+use cyclemetrics::{
+    Anonymization, anonymize, gpx_elevation_gain, gpx_start_end_date, gpx_start_point,
+    gpx_total_distance, read_track_points,
+};
+use std::fs;
+
+fn anonymized(name: &str, anonymization: &Anonymization) -> String {
+    anonymize(&fs::read(fixture_path(name)).unwrap(), anonymization).unwrap()
+}
+
+#[test]
+fn distances_and_elevations_are_kept() {
+    for (name, offset, rotation_deg) in [
+        ("mountain.gpx", (-20.0, 130.0), 0.0),
+        ("barometric.gpx", (25.0, -70.0), 135.0),
+        ("multi_track.gpx", (10.0, 10.0), -45.0),
+    ] {
+        let original = fixture(name);
+        let gpx = anonymized(
+            name,
+            &Anonymization {
+                offset,
+                rotation_deg,
+                ..Default::default()
+            },
+        );
+        let copy = gpx::read(gpx.as_bytes()).unwrap();
+
+        let (distance, copied_distance) =
+            (gpx_total_distance(&original), gpx_total_distance(&copy));
+        assert!(
+            (copied_distance / distance - 1.0).abs() < 0.001,
+            "{name}: {distance} m became {copied_distance} m"
+        );
+        let (gain, copied_gain) = (gpx_elevation_gain(&original), gpx_elevation_gain(&copy));
+        assert!((copied_gain / gain - 1.0).abs() < 0.001, "{name}");
+
+        let (lat, lon) = gpx_start_point(&original).unwrap();
+        let (copied_lat, copied_lon) = gpx_start_point(&copy).unwrap();
+        assert!((copied_lat - (lat + offset.0)).abs() < 1e-6, "{name}");
+        assert!((copied_lon - (lon + offset.1)).abs() < 1e-6, "{name}");
+    }
+}
+
+#[test]
+fn personal_data_is_left_out() {
+    let gpx = anonymized("intervals.gpx", &Anonymization::default());
+
+    assert!(gpx.contains("creator=\"cyclemetrics\""));
+    assert!(!gpx.contains("<name>"));
+    let points = read_track_points(gpx.as_bytes()).unwrap();
+    assert!(
+        points
+            .iter()
+            .all(|point| point.heart_rate.is_none() && point.power.is_none())
+    );
+
+    let gpx = anonymized(
+        "intervals.gpx",
+        &Anonymization {
+            keep_sensors: true,
+            ..Default::default()
+        },
+    );
+    let original = read_track_points(&fs::read(fixture_path("intervals.gpx")).unwrap()).unwrap();
+    let points = read_track_points(gpx.as_bytes()).unwrap();
+    assert_eq!(
+        points.iter().map(|point| point.power).collect::<Vec<_>>(),
+        original.iter().map(|point| point.power).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn timestamps_move_to_the_date() {
+    let gpx = anonymized(
+        "mountain.gpx",
+        &Anonymization {
+            date: NaiveDate::from_ymd_opt(2020, 2, 29),
+            ..Default::default()
+        },
+    );
+
+    let (start, end) = gpx_start_end_date(&gpx::read(gpx.as_bytes()).unwrap()).unwrap();
+    assert_eq!(start.to_rfc3339(), "2020-02-29T07:00:00+00:00");
+    assert_eq!(end.to_rfc3339(), "2020-02-29T07:44:00+00:00");
+}
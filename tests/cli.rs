@@ -0,0 +1,1178 @@
+#![cfg(feature = "tui")]
+
+mod common;
+
+use assert_cmd::Command;
+use common::fixture_path;
+
+fn cyclemetrics() -> Command {
+    let mut cmd = Command::cargo_bin("cyclemetrics").unwrap();
+    cmd.current_dir(fixture_path("")).env("RUST_BACKTRACE", "0");
+    cmd
+}
+
+#[test]
+fn csv_output_is_sorted_by_start_date() {
+    let output = cyclemetrics()
+        .args([
+            "--format",
+            "csv",
+            "mountain.gpx",
+            "flat.gpx",
+            "no_timestamps.gpx",
+        ])
+        .args(["route_only.gpx", "multi_track.gpx"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "\
+id,path,name,start,distance_km,distance_estimated,elevation_gain_m,max_elevation_m,max_elevation_km
+0-7f23c2bd6a34e42b,route_only.gpx,Planned route,,0.000,false,0.0,,
+0-ad1c494be18fc489,no_timestamps.gpx,No timestamps,,0.445,false,4.0,104,0.4
+1717225200-82858a95dfd22838,flat.gpx,Flat ride,2024-06-01T07:00:00+00:00,1.001,false,5.0,101,0.3
+1717398000-fbe9096c2fe46d35,multi_track.gpx,Morning loop,2024-06-03T07:00:00+00:00,0.667,false,4.0,222,0.7
+1720940400-98fedefd6347cc2d,mountain.gpx,Col du Test,2024-07-14T07:00:00+00:00,3.127,false,280.0,780,1.6
+"
+    );
+}
+
+#[test]
+fn json_output() {
+    cyclemetrics()
+        .args(["--format", "json", "flat.gpx"])
+        .assert()
+        .success()
+        .stdout(
+            r#"[
+  {
+    "id": "1717225200-82858a95dfd22838",
+    "path": "flat.gpx",
+    "name": "Flat ride",
+    "start": "2024-06-01T07:00:00+00:00",
+    "distance_km": 1.001,
+    "distance_estimated": false,
+    "elevation_gain_m": 5.0,
+    "max_elevation_m": 101.0,
+    "max_elevation_km": 0.3
+  }
+]
+"#,
+        );
+}
+
+#[test]
+fn table_output_has_a_totals_row() {
+    cyclemetrics()
+        .args([
+            "--format",
+            "table",
+            "mountain.gpx",
+            "flat.gpx",
+            "no_timestamps.gpx",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "\
+┌────────────┬───────────────┬─────────────┬──────────────────┬───────────────┐
+│ date       │ name          │ distance_km │ elevation_gain_m │ avg_speed_kmh │
+├────────────┼───────────────┼─────────────┼──────────────────┼───────────────┤
+│            │ No timestamps │       0.445 │              4.0 │               │
+│ 2024-06-01 │ Flat ride     │       1.001 │              5.0 │           6.7 │
+│ 2024-07-14 │ Col du Test   │       3.127 │            280.0 │           5.7 │
+├────────────┼───────────────┼─────────────┼──────────────────┼───────────────┤
+│ total (3)  │               │       4.572 │            289.0 │           5.9 │
+└────────────┴───────────────┴─────────────┴──────────────────┴───────────────┘
+",
+        );
+}
+
+#[test]
+fn ascii_reports_hold_only_ascii() {
+    cyclemetrics()
+        .args(["--ascii", "--format", "table", "--columns", "name,distance"])
+        .args(["mountain.gpx", "flat.gpx"])
+        .assert()
+        .success()
+        .stdout(
+            "\
++-------------+-------------+
+| name        | distance_km |
++-------------+-------------+
+| Flat ride   |       1.001 |
+| Col du Test |       3.127 |
++-------------+-------------+
+| total (2)   |       4.128 |
++-------------+-------------+
+",
+        );
+
+    let output = cyclemetrics()
+        .args(["review", "--ascii", "--year", "2024", "--format", "text"])
+        .args(["--lang", "fr", "flat.gpx", "mountain.gpx"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).unwrap();
+    assert!(text.is_ascii(), "{text}");
+    assert!(text.contains("Plus gros denivele : Col du Test"), "{text}");
+}
+
+#[test]
+fn columns_are_chosen_by_name_or_alias() {
+    let output = cyclemetrics()
+        .args(["--format", "table", "--width", "40"])
+        .args([
+            "--columns",
+            "name,distance,path",
+            "mountain.gpx",
+            "flat.gpx",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).unwrap();
+    assert!(
+        text.lines().all(|line| line.chars().count() <= 40),
+        "{text}"
+    );
+    assert!(
+        text.contains("│ Col du Te… │       3.127 │ mountain… │\n"),
+        "{text}"
+    );
+
+    cyclemetrics()
+        .args([
+            "--format",
+            "csv",
+            "--columns",
+            "date,speed,name",
+            "flat.gpx",
+        ])
+        .assert()
+        .success()
+        .stdout("date,avg_speed_kmh,name\n2024-06-01,6.7,Flat ride\n");
+
+    let output = cyclemetrics()
+        .args(["--format", "csv", "--columns", "name,pace", "flat.gpx"])
+        .assert()
+        .code(3)
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("'pace' for '--columns <NAME,...>': unknown column, expected one of id,"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn standard_input_gives_the_numbers_of_the_file() {
+    let columns = "id,name,start,distance,distance_estimated,elevation,speed,quality";
+    let csv = |args: &[&str], stdin: Vec<u8>| {
+        let output = cyclemetrics()
+            .args(["--format", "csv", "--columns", columns])
+            .args(args)
+            .write_stdin(stdin)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        String::from_utf8(output).unwrap()
+    };
+    let fixture = std::fs::read("tests/fixtures/mountain.gpx").unwrap();
+    let from_file = csv(&["mountain.gpx"], vec![]);
+    assert_eq!(from_file.lines().count(), 2, "{from_file}");
+    assert_eq!(csv(&["-"], fixture.clone()), from_file);
+
+    // Without a terminal, as JSON by default.
+    let output = cyclemetrics()
+        .args(["--no-tui", "-"])
+        .write_stdin(fixture)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(json[0]["path"], "-");
+    assert_eq!(json[0]["name"], "Col du Test");
+}
+
+#[test]
+fn standard_input_is_limited_in_size() {
+    let output = cyclemetrics()
+        .args(["--no-tui", "--max-stdin-bytes", "100", "-"])
+        .write_stdin(std::fs::read("tests/fixtures/mountain.gpx").unwrap())
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("the standard input is larger than 100 bytes"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn quality_column_scores_the_data() {
+    cyclemetrics()
+        .args(["--format", "csv", "--columns", "name,quality"])
+        .args(["jitter.gpx", "tunnel.gpx", "flat.gpx"])
+        .assert()
+        .success()
+        .stdout("name,quality\nFlat ride,100\nTunnel ride,85\nWaiting at the lights,80\n");
+}
+
+#[test]
+fn expression_columns_are_computed_and_sorted() {
+    cyclemetrics()
+        .args(["--format", "csv", "--columns", "name,distance,elevation"])
+        .args(["--column-expr", "gain_per_km = elevation_m / distance_km"])
+        .args([
+            "--column-expr",
+            "half=distance_km/2",
+            "--sort",
+            "-gain_per_km",
+        ])
+        .args([
+            "flat.gpx",
+            "route_only.gpx",
+            "mountain.gpx",
+            "no_elevation.gpx",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "name,distance_km,elevation_gain_m,gain_per_km,half\n\
+             Col du Test,3.127,280.0,89.54,1.56\n\
+             Flat ride,1.001,5.0,5.00,0.50\n\
+             No elevation,0.445,0.0,0.00,0.22\n\
+             Planned route,0.000,0.0,,0.00\n",
+        );
+    // Division by zero is null, and the table has no total for it.
+    cyclemetrics()
+        .args(["--format", "json", "--columns", "name"])
+        .args([
+            "--column-expr",
+            "g = elevation_m / distance_km",
+            "route_only.gpx",
+        ])
+        .assert()
+        .success()
+        .stdout("[\n  {\n    \"name\": \"Planned route\",\n    \"g\": null\n  }\n]\n");
+    cyclemetrics()
+        .args(["--format", "table", "--columns", "name", "--sort", "per_h"])
+        .args(["--column-expr", "per_h = distance_km / moving_h"])
+        .args(["route_only.gpx", "mountain.gpx", "flat.gpx"])
+        .assert()
+        .success()
+        .stdout(
+            "┌───────────────┬───────┐\n\
+             │ name          │ per_h │\n\
+             ├───────────────┼───────┤\n\
+             │ Col du Test   │  5.69 │\n\
+             │ Flat ride     │  6.67 │\n\
+             │ Planned route │       │\n\
+             ├───────────────┼───────┤\n\
+             │ total (3)     │       │\n\
+             └───────────────┴───────┘\n",
+        );
+}
+
+#[test]
+fn invalid_expression_columns_are_usage_errors() {
+    let stderr = |args: &[&str]| {
+        let output = cyclemetrics()
+            .args(["--format", "csv"])
+            .args(args)
+            .arg("flat.gpx")
+            .assert()
+            .code(3)
+            .get_output()
+            .clone();
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let error = stderr(&["--column-expr", "x = elevation_m / speed"]);
+    assert!(
+        error.contains(
+            "'x = elevation_m / speed' for '--column-expr <NAME = EXPR>': unknown field `speed` \
+             at column 19, expected one of name,"
+        ),
+        "{error}"
+    );
+    let error = stderr(&["--column-expr", "x = (1 + 2"]);
+    assert!(error.contains(": missing `)` at column 11"), "{error}");
+    let error = stderr(&["--column-expr", "x = name"]);
+    assert!(
+        error.contains(": expected a number at column 5, found a string"),
+        "{error}"
+    );
+    let error = stderr(&["--column-expr", "distance = 1"]);
+    assert!(
+        error.contains(": column `distance` already exists"),
+        "{error}"
+    );
+    let error = stderr(&["--column-expr", "x"]);
+    assert!(error.contains(": expected `NAME = EXPRESSION`"), "{error}");
+    let error = stderr(&["--column-expr", "x = 1", "--column-expr", "x = 2"]);
+    assert!(
+        error.starts_with("Error: --column-expr: column `x` is defined twice"),
+        "{error}"
+    );
+    let error = stderr(&["--sort", "-pace"]);
+    assert!(
+        error.starts_with("Error: --sort -pace: unknown column, expected one of id,"),
+        "{error}"
+    );
+}
+
+#[test]
+fn exclude_transport_leaves_the_ferry_out() {
+    let csv = |args: &[&str]| {
+        let output = cyclemetrics()
+            .args(["--format", "csv", "--columns", "name,distance,speed"])
+            .args(args)
+            .arg("ferry.gpx")
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        String::from_utf8(output).unwrap()
+    };
+    assert_eq!(
+        csv(&[]),
+        "name,distance_km,avg_speed_kmh\nIsland hop,17.000,27.2\n"
+    );
+    assert_eq!(
+        csv(&["--exclude-transport"]),
+        "name,distance_km,avg_speed_kmh\nIsland hop,7.000,24.0\n"
+    );
+}
+
+#[test]
+fn glob_patterns_are_expanded() {
+    let output = cyclemetrics()
+        .args(["--format", "csv", "[fm]*.gpx"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let output = String::from_utf8(output).unwrap();
+    let paths: Vec<&str> = output
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(1).unwrap())
+        .collect();
+    assert_eq!(
+        paths,
+        ["flat.gpx", "multi_track.gpx", "ferry.gpx", "mountain.gpx"]
+    );
+}
+
+#[test]
+fn overlapping_patterns_count_each_file_once() {
+    let table = |patterns: &[&str]| {
+        cyclemetrics()
+            .args(["--format", "table"])
+            .args(patterns)
+            .assert()
+            .success()
+    };
+    let once = table(&["ferry.gpx", "flat.gpx", "mountain.gpx", "multi_track.gpx"])
+        .stderr("")
+        .get_output()
+        .stdout
+        .clone();
+    let overlapping = table(&["[fm]*.gpx", "f*.gpx", "./flat.gpx"])
+        .stderr("warning: 3 duplicate path(s) skipped\n")
+        .get_output()
+        .stdout
+        .clone();
+    let overlapping = String::from_utf8(overlapping).unwrap();
+    assert!(overlapping.contains("total (4)"), "{overlapping}");
+    assert_eq!(overlapping, String::from_utf8(once).unwrap());
+}
+
+#[test]
+fn corrupt_file_fails() {
+    cyclemetrics()
+        .args(["--format", "json", "--fail-fast", "flat.gpx", "corrupt.gpx"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr(
+            "Error: parsing corrupt.gpx\n\nCaused by:\n    error while parsing 'track' segment\n",
+        );
+}
+
+#[test]
+fn readable_files_are_written_before_failing() {
+    let output = cyclemetrics()
+        .args(["--format", "csv", "flat.gpx", "corrupt.gpx"])
+        .assert()
+        .code(1)
+        .stderr(
+            "error: parsing corrupt.gpx: error while parsing 'track' segment\n\
+             Error: 1 of 2 files could not be read\n",
+        )
+        .get_output()
+        .stdout
+        .clone();
+
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.lines().count(), 2, "{output}");
+    assert!(output.contains(",flat.gpx,Flat ride,"), "{output}");
+}
+
+#[test]
+fn exit_codes_tell_what_went_wrong() {
+    cyclemetrics()
+        .args(["--format", "json", "nothing*.gpx"])
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr("Error: no file matches nothing*.gpx\n");
+    cyclemetrics()
+        .args(["metrics", "nothing*.gpx"])
+        .assert()
+        .code(2);
+    cyclemetrics()
+        .args(["--format", "xml", "flat.gpx"])
+        .assert()
+        .code(3);
+    let config =
+        std::env::temp_dir().join(format!("cyclemetrics-exit-{}.toml", std::process::id()));
+    std::fs::write(&config, "ftp = \"high\"\n").unwrap();
+    cyclemetrics()
+        .arg("--config")
+        .arg(&config)
+        .args(["review", "--year", "2024", "flat.gpx"])
+        .assert()
+        .code(3);
+    std::fs::remove_file(&config).unwrap();
+    cyclemetrics().arg("--version").assert().code(0);
+}
+
+#[test]
+fn quiet_prints_nothing_but_errors() {
+    cyclemetrics()
+        .args(["validate", "--quiet", "flat.gpx", "no_timestamps.gpx"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+    cyclemetrics()
+        .args(["--quiet", "--format", "csv", "flat.gpx", "corrupt.gpx"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr(
+            "error: parsing corrupt.gpx: error while parsing 'track' segment\n\
+             Error: 1 of 2 files could not be read\n",
+        );
+}
+
+#[test]
+fn validate_reports_every_file() {
+    cyclemetrics()
+        .args(["validate", "flat.gpx", "no_timestamps.gpx", "truncated.gpx"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+OK         flat.gpx
+OK         no_timestamps.gpx: no timestamps
+RECOVERED  truncated.gpx: invalid GPX (error while parsing 'track' segment)
+
+3 files: 2 ok (1 with issues), 1 recovered, 0 failed
+",
+        );
+}
+
+#[test]
+fn validate_fails_on_unreadable_files() {
+    let output = cyclemetrics()
+        .args(["validate", "flat.gpx", "corrupt.gpx"])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("FAILED     corrupt.gpx"), "{stdout}");
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("1 of 2 files failed validation")
+    );
+}
+
+#[test]
+fn review_only_counts_rides_of_the_year() {
+    let output = cyclemetrics()
+        .args(["review", "--year", "2024", "--format", "text"])
+        .args(["flat.gpx", "mountain.gpx", "no_timestamps.gpx"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let text = String::from_utf8(output).unwrap();
+    assert!(
+        text.starts_with("2024 in review\n\n2 rides, 4 km"),
+        "{text}"
+    );
+    assert!(
+        text.contains("Biggest climb: Col du Test on 2024-07-14"),
+        "{text}"
+    );
+    // The first year ridden: every cell is new, with nothing to compare to.
+    assert!(text.contains("\nExploration: "), "{text}");
+    assert!(!text.contains("% on "), "{text}");
+}
+
+#[test]
+fn review_in_french() {
+    let output = cyclemetrics()
+        .args([
+            "review", "--year", "2024", "--format", "text", "--lang", "fr",
+        ])
+        .args(["flat.gpx", "mountain.gpx"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let text = String::from_utf8(output).unwrap();
+    assert!(
+        text.starts_with("2024 en revue\n\n2 sorties, 4 km"),
+        "{text}"
+    );
+    assert!(text.contains("Plus gros dénivelé : Col du Test le 2024-07-14"));
+}
+
+#[test]
+fn invalid_date_format_is_rejected() {
+    cyclemetrics()
+        .args(["--format", "csv", "--date-format", "%Q", "flat.gpx"])
+        .assert()
+        .code(3)
+        .stderr("Error: invalid --date-format \"%Q\"\n");
+}
+
+#[test]
+fn review_html_is_self_contained() {
+    let path =
+        std::env::temp_dir().join(format!("cyclemetrics-review-{}.html", std::process::id()));
+    cyclemetrics()
+        .args(["review", "--year", "2024", "--output"])
+        .arg(&path)
+        .args(["flat.gpx", "mountain.gpx"])
+        .assert()
+        .success()
+        .stdout("");
+
+    let html = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(html.contains("<svg"));
+    assert!(html.contains("<td>Col du Test</td>"));
+    assert!(!html.contains("<script"));
+    assert!(!html.contains("src="));
+    assert!(!html.contains("href="));
+}
+
+#[test]
+fn activity_type_selects_virtual_rides() {
+    let names = |activity_type: &str| {
+        let output = cyclemetrics()
+            .args(["--format", "csv", "--activity-type", activity_type])
+            .args(["flat.gpx", "zwift.gpx"])
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').nth(1).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names("all"), ["flat.gpx", "zwift.gpx"]);
+    assert_eq!(names("outdoor"), ["flat.gpx"]);
+    assert_eq!(names("virtual"), ["zwift.gpx"]);
+}
+
+#[test]
+fn metrics_jsonl_has_one_line_per_file() {
+    let output = cyclemetrics()
+        .args([
+            "metrics",
+            "--jsonl",
+            "flat.gpx",
+            "mountain.gpx",
+            "corrupt.gpx",
+        ])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3, "{stdout}");
+    lines.sort_by_key(|line| line["path"].as_str().unwrap().to_string());
+
+    let [corrupt, flat, mountain] = &lines[..] else {
+        unreachable!()
+    };
+    assert_eq!(corrupt["status"], "error");
+    assert!(
+        corrupt["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("parsing corrupt.gpx")
+    );
+    for ok in [flat, mountain] {
+        assert_eq!(ok["status"], "ok");
+        for field in ["id", "name", "start"] {
+            assert!(ok[field].is_string(), "{field} in {ok}");
+        }
+        for field in ["distance_km", "elevation_gain_m"] {
+            assert!(ok[field].is_f64(), "{field} in {ok}");
+        }
+    }
+    assert_eq!(flat["name"], "Flat ride");
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("1 of 3 files could not be read")
+    );
+}
+
+#[test]
+fn creator_selects_the_device() {
+    let names = |creator: &str| {
+        let output = cyclemetrics()
+            .args(["--format", "csv", "--creator", creator])
+            .args(["flat.gpx", "edge.gpx", "zwift.gpx"])
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').nth(1).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names("garmin edge 530"), ["edge.gpx"]);
+    assert_eq!(names("Zwift"), ["zwift.gpx"]);
+    assert!(names("unknown").is_empty());
+}
+
+#[test]
+fn latest_keeps_the_most_recent_after_the_filters() {
+    let names = |args: &[&str]| {
+        let output = cyclemetrics()
+            .args(["--format", "csv"])
+            .args(args)
+            .args(["flat.gpx", "edge.gpx", "zwift.gpx", "no_timestamps.gpx"])
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').nth(1).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(&["--latest", "2"]), ["zwift.gpx", "edge.gpx"]);
+    // The virtual ride is filtered out before the two are taken.
+    assert_eq!(
+        names(&["--latest", "2", "--activity-type", "outdoor"]),
+        ["flat.gpx", "edge.gpx"]
+    );
+    assert_eq!(
+        names(&["--latest", "1", "--include-undated"]),
+        ["no_timestamps.gpx", "edge.gpx"]
+    );
+}
+
+#[test]
+fn segment_joins_count_the_tunnel() {
+    let distance = |policy: &str| {
+        let output = cyclemetrics()
+            .args(["--format", "csv", "--segment-joins", policy, "tunnel.gpx"])
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        let csv = String::from_utf8(output).unwrap();
+        csv.lines()
+            .nth(1)
+            .unwrap()
+            .split(',')
+            .nth(4)
+            .unwrap()
+            .to_string()
+    };
+
+    assert_eq!(distance("ignore"), "1.001");
+    assert_eq!(distance("auto"), "1.168");
+}
+
+#[test]
+fn thumbnails_are_written_per_activity() {
+    let dir = std::env::temp_dir().join(format!("cyclemetrics-thumbnails-{}", std::process::id()));
+    cyclemetrics()
+        .args([
+            "--format",
+            "csv",
+            "--config",
+            "missing.toml",
+            "--export-thumbnails",
+        ])
+        .arg(&dir)
+        .args(["flat.gpx", "mountain.gpx", "route_only.gpx"])
+        .assert()
+        .success()
+        .stderr("warning: route_only.gpx: fewer than 2 track points, no thumbnail\n");
+
+    let mut names: Vec<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    let svg = std::fs::read_to_string(dir.join(&names[0])).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        names,
+        ["2024-06-01_flat-ride.svg", "2024-07-14_col-du-test.svg"]
+    );
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 200 200\""));
+    assert!(svg.contains("<circle"));
+}
+
+#[test]
+fn exports_warn_about_places_many_rides_start_from() {
+    let dir = std::env::temp_dir().join(format!("cyclemetrics-privacy-{}", std::process::id()));
+    let export = |ack: &[&str]| {
+        let output = cyclemetrics()
+            .args(["--format", "csv", "--config", "missing.toml"])
+            .args(ack)
+            .arg("--export-thumbnails")
+            .arg(&dir)
+            .args([
+                "flat.gpx",
+                "clock_reset.gpx",
+                "no_elevation.gpx",
+                "no_timestamps.gpx",
+                "mountain.gpx",
+            ])
+            .assert()
+            .success()
+            .get_output()
+            .clone();
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let stderr = export(&[]);
+    assert_eq!(
+        stderr,
+        "warning: 4 activities start or end near 45.00000, 5.00000, outside of the privacy \
+         zones (no_timestamps.gpx, flat.gpx, no_elevation.gpx, clock_reset.gpx); add a \
+         privacy zone there or pass --ack-privacy\n"
+    );
+    assert_eq!(export(&["--ack-privacy"]), "");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn prometheus_textfile_replaces_the_previous_one() {
+    let dir = std::env::temp_dir().join(format!("cyclemetrics-prometheus-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("cyclemetrics.prom");
+    std::fs::write(&path, "stale\n").unwrap();
+
+    cyclemetrics()
+        .args(["--format", "csv", "--export-prometheus"])
+        .arg(&path)
+        .args(["flat.gpx", "mountain.gpx"])
+        .assert()
+        .success();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    let names: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(names, ["cyclemetrics.prom"]);
+    assert!(text.contains("\ncyclemetrics_activity_count 2\n"), "{text}");
+    assert!(text.contains("\ncyclemetrics_month_activity_count{month=\"2024-07\"} 1\n"));
+    assert!(text.contains("\ncyclemetrics_last_activity_timestamp_seconds 1720940400\n"));
+}
+
+#[test]
+fn debug_traces_the_loading_as_json_lines() {
+    let path =
+        std::env::temp_dir().join(format!("cyclemetrics-debug-{}.jsonl", std::process::id()));
+
+    let output = cyclemetrics()
+        .args(["--format", "csv", "--debug-file"])
+        .arg(&path)
+        .arg("flat.gpx")
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.stderr.is_empty());
+    let events: Vec<serde_json::Value> = text
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let read = events
+        .iter()
+        .find(|event| event["fields"]["message"] == "file read")
+        .unwrap_or_else(|| panic!("{text}"));
+    assert_eq!(read["level"], "DEBUG");
+    assert!(read["span"]["path"].as_str().unwrap().ends_with("flat.gpx"));
+    let fields = &read["fields"];
+    assert!(fields["points"].as_u64().unwrap() > 0, "{fields}");
+    assert!(fields["parse_ms"].as_f64().is_some(), "{fields}");
+    for field in [
+        "jitter_dropped",
+        "spikes_dropped",
+        "distance_raw_m",
+        "distance_filtered_m",
+        "elevation_raw_m",
+        "elevation_gain_m",
+    ] {
+        assert!(fields[field].is_number(), "{field}: {fields}");
+    }
+}
+
+#[test]
+fn anonymize_writes_a_moved_copy() {
+    let path = std::env::temp_dir().join(format!(
+        "cyclemetrics-anonymized-{}.gpx",
+        std::process::id()
+    ));
+    cyclemetrics()
+        .args(["anonymize", "flat.gpx"])
+        .arg(&path)
+        .args([
+            "--offset",
+            "-10,20",
+            "--rotate",
+            "90",
+            "--date",
+            "2021-03-04",
+        ])
+        .assert()
+        .success()
+        .stdout("");
+
+    let gpx = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(gpx.contains("<trkpt lat=\"35.0000000\" lon=\"25.0000000\"><ele>100</ele><time>2021-03-04T07:00:00Z</time></trkpt>"), "{gpx}");
+
+    cyclemetrics()
+        .args(["anonymize", "flat.gpx", "out.gpx", "--offset", "north"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn share_prints_the_configured_text() {
+    let dir = std::env::temp_dir().join(format!("cyclemetrics-share-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("config.toml");
+    let share = |template: &str| {
+        std::fs::write(&config, format!("share_template = {template:?}\n")).unwrap();
+        let mut cmd = cyclemetrics();
+        cmd.arg("--config")
+            .arg(&config)
+            .args(["--share", "mountain.gpx", "flat.gpx"]);
+        cmd
+    };
+
+    share("{date} {name}: {distance} / {elevation}↑ in {moving_time} ({avg_speed})")
+        .assert()
+        .success()
+        .stdout(
+            "2024-06-01 Flat ride: 1.0 km / 5 m↑ in 9:00 (6.7 km/h)\n\
+             2024-07-14 Col du Test: 3.1 km / 280 m↑ in 33:00 (5.7 km/h)\n",
+        );
+    share("{name} {speed}").assert().failure().stderr(
+        "Error: share template \"{name} {speed}\": unknown placeholder {speed}, expected one of \
+         {date}, {name}, {distance}, {elevation}, {moving_time}, {avg_speed}, {energy}\n",
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn plan_times_the_stops_of_routes() {
+    cyclemetrics()
+        .args([
+            "plan",
+            "--plan-speed",
+            "20",
+            "--stop-every",
+            "0.1",
+            "route_only.gpx",
+            "flat.gpx",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "Planned route\n\
+             Plan at 20.0 km/h: 1:00\n    \
+             0.1 km     0:18  bottle stop\n    \
+             0.2 km     0:36  bottle stop\n    \
+             0.3 km     0:54  bottle stop\n    \
+             0.3 km     1:00  finish\n",
+        )
+        .stderr("warning: flat.gpx has timestamps, it is a recording rather than a plan\n");
+}
+
+#[test]
+fn csv_export_replaces_the_rows_of_the_same_activities() {
+    let path = std::env::temp_dir().join(format!("cyclemetrics-{}.csv", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let export = |files: &[&str]| {
+        cyclemetrics()
+            .args(["--format", "json", "--quiet", "--export-csv"])
+            .arg(&path)
+            .args(files)
+            .assert()
+            .success();
+        std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|row| row.split(',').next().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    export(&["flat.gpx", "mountain.gpx"]);
+    let ids = export(&["mountain.gpx", "multi_track.gpx"]);
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        ids,
+        [
+            "id",
+            "1717225200-82858a95dfd22838",
+            "1720940400-98fedefd6347cc2d",
+            "1717398000-fbe9096c2fe46d35",
+        ]
+    );
+}
+
+#[test]
+fn watch_needs_an_export() {
+    cyclemetrics()
+        .args(["--watch", "flat.gpx"])
+        .assert()
+        .code(3)
+        .stderr("Error: --watch needs --export-csv, --export-prometheus or --export-sqlite\n");
+}
+
+#[test]
+fn watch_waits_for_files_to_come() {
+    let dir = std::env::temp_dir().join(format!("cyclemetrics-watch-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let rides = dir.join("rides");
+    std::fs::create_dir_all(&rides).unwrap();
+    let csv = dir.join("rides.csv");
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("cyclemetrics"))
+        .args(["--watch", "--quiet", "--export-csv"])
+        .arg(&csv)
+        .arg(rides.join("*.gpx"))
+        .spawn()
+        .unwrap();
+
+    let wait_for = |done: &dyn Fn() -> bool| {
+        for _ in 0..100 {
+            if done() {
+                return true;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        false
+    };
+    let started = wait_for(&|| csv.exists());
+    std::fs::copy(fixture_path("flat.gpx"), rides.join("flat.gpx")).unwrap();
+    let exported = wait_for(&|| {
+        std::fs::read_to_string(&csv).is_ok_and(|text| text.contains("1717225200-82858a95dfd22838"))
+    });
+    let running = child.try_wait().unwrap().is_none();
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(started && running, "the watch ended on an empty directory");
+    assert!(exported);
+}
+
+#[test]
+fn plan_times_the_brevet_controls() {
+    cyclemetrics()
+        .args([
+            "plan",
+            "--plan-speed",
+            "20",
+            "--stop-every",
+            "0",
+            "--controls",
+            "0.1,0.2,5",
+            "route_only.gpx",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "Planned route\n\
+             Plan at 20.0 km/h: 1:00\n    \
+             0.3 km     1:00  finish\n\
+             \n\
+             Controls:\n    \
+             0.1 km     0:18  open 0:11–1:00:18  in time\n    \
+             0.2 km     0:36  open 0:21–1:00:36  in time\n    \
+             5.0 km        –  open 8:49–1:15:00  not reached\n",
+        );
+}
+
+#[test]
+fn info_tells_the_features_and_paths() {
+    let output = cyclemetrics()
+        .arg("info")
+        .env("CYCLEMETRICS_CONFIG", "/etc/cyclemetrics.toml")
+        .env("CYCLEMETRICS_HOME", "/srv/cyclemetrics")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).unwrap();
+
+    assert!(
+        text.starts_with(&format!("cyclemetrics {} (", env!("CARGO_PKG_VERSION"))),
+        "{text}"
+    );
+    let features = text
+        .lines()
+        .find_map(|line| line.strip_prefix("features: "))
+        .unwrap();
+    assert!(
+        features.split(", ").any(|feature| feature == "tui"),
+        "{text}"
+    );
+    assert!(
+        text.contains("\nconfig: /etc/cyclemetrics.toml\n"),
+        "{text}"
+    );
+    assert!(
+        text.ends_with("\ndata: /srv/cyclemetrics (sidecars and session)\n"),
+        "{text}"
+    );
+}
+
+#[test]
+fn history_prints_the_totals_of_each_run() {
+    let dir = std::env::temp_dir().join(format!("cyclemetrics-history-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("session.toml"),
+        "version = 1\n\n\
+         [[runs]]\nat = \"2024-06-01T08:00:00+02:00\"\nrides = 10\ndistance_km = 300.0\nelevation_m = 3000.0\n\n\
+         [[runs]]\nat = \"2024-06-02T08:00:00+02:00\"\nrides = 13\ndistance_km = 514.0\nelevation_m = 5140.0\n",
+    )
+    .unwrap();
+
+    let output = cyclemetrics()
+        .args(["history", "--data-dir"])
+        .arg(&dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 3, "{text}");
+    assert!(
+        lines[2].starts_with("2024-06-02 08:00      13     +3"),
+        "{text}"
+    );
+
+    cyclemetrics()
+        .arg("history")
+        .env("CYCLEMETRICS_HOME", dir.join("empty"))
+        .assert()
+        .success()
+        .stdout("no run recorded yet\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn report_uses_the_dem_elevation_gain() {
+    // A tile rising 30000 m a degree northwards, half as steep again as the
+    // recorded climb.
+    let dir = std::env::temp_dir().join(format!("cyclemetrics-cli-dem-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let tile: Vec<u8> = [30000i16, 30000, 30000, 15000, 15000, 15000, 0, 0, 0]
+        .iter()
+        .flat_map(|sample| sample.to_be_bytes())
+        .collect();
+    std::fs::write(dir.join("N45E006.hgt"), tile).unwrap();
+
+    let output = cyclemetrics()
+        .args(["--format", "csv", "--config", "missing.toml", "--dem"])
+        .arg(&dir)
+        .arg("mountain.gpx")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    let row: Vec<&str> = output.lines().nth(1).unwrap().split(',').collect();
+    assert_eq!((row[6], row[7]), ("420.0", "420"));
+}
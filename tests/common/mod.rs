@@ -0,0 +1,27 @@
+// Each test crate uses a different subset of these helpers.
+#![allow(dead_code)]
+
+use gpx::Gpx;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+pub fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+pub fn fixture(name: &str) -> Gpx {
+    let file = File::open(fixture_path(name)).unwrap();
+    gpx::read(BufReader::new(file)).unwrap()
+}
+
+/// Compare floats with a tolerance fine enough for golden values.
+#[track_caller]
+pub fn assert_close(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() < 1e-6,
+        "expected {expected}, got {actual}"
+    );
+}
@@ -0,0 +1,590 @@
+mod common;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use common::{assert_close, fixture, fixture_path};
+use cyclemetrics::testkit::{SyntheticRide, minutes};
+use cyclemetrics::{
+    Activity, DEFAULT_NAME, DistanceSource, GradeModel, Issue, MIN_PLAUSIBLE_YEAR, NameSource,
+    RideMetrics, SPARSE_SPACING_M, SegmentJoins, Tuning, UNKNOWN_CREATOR, activity_name,
+    cumulative_distance, elevation_profile_min_max, file_name_or_default, gpx_elevation_gain,
+    gpx_elevation_noise, gpx_elevation_profile, gpx_implausible_timestamps, gpx_name,
+    gpx_route_points, gpx_start_end_date, gpx_start_point, gpx_total_distance, gpx_track_name,
+    grade_factor, is_plausible_time, issues, median_spacing_m, read_track_points, tuned_distance_m,
+    write_gpx,
+};
+use std::fs;
+use std::path::Path;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+#[test]
+fn total_distance() {
+    assert_close(gpx_total_distance(&fixture("flat.gpx")), 1000.7557221018341);
+    assert_close(
+        gpx_total_distance(&fixture("mountain.gpx")),
+        3126.9465251774036,
+    );
+    assert_close(
+        gpx_total_distance(&fixture("no_elevation.gpx")),
+        444.7803209338851,
+    );
+    assert_close(
+        gpx_total_distance(&fixture("no_timestamps.gpx")),
+        444.7803209338851,
+    );
+    // Distance between segments and between tracks is not counted.
+    assert_close(
+        gpx_total_distance(&fixture("multi_track.gpx")),
+        667.1704814012227,
+    );
+    assert_close(gpx_total_distance(&fixture("route_only.gpx")), 0.0);
+}
+
+#[test]
+fn track_name() {
+    assert_eq!(gpx_track_name(&fixture("flat.gpx")), Some("Flat ride"));
+    assert_eq!(
+        gpx_track_name(&fixture("multi_track.gpx")),
+        Some("Morning loop")
+    );
+    assert_eq!(gpx_track_name(&fixture("route_only.gpx")), None);
+}
+
+#[test]
+fn gpx_names() {
+    assert_eq!(
+        gpx_name(&fixture("flat.gpx")),
+        Some(("Flat ride", NameSource::Track))
+    );
+    assert_eq!(
+        gpx_name(&fixture("multi_track.gpx")),
+        Some(("Morning loop", NameSource::Track))
+    );
+    assert_eq!(
+        gpx_name(&fixture("route_only.gpx")),
+        Some(("Planned route", NameSource::Route))
+    );
+    let blank = gpx::read(
+        r#"<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <metadata><name>  </name></metadata>
+  <trk><trkseg><trkpt lat="45.0" lon="5.0"/></trkseg></trk>
+</gpx>"#
+            .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(gpx_name(&blank), None);
+}
+
+#[test]
+fn file_names() {
+    assert_eq!(
+        file_name_or_default(&fixture_path("no_timestamps.gpx")),
+        ("no timestamps".to_string(), NameSource::FileName)
+    );
+    assert_eq!(
+        file_name_or_default(Path::new("export/Morning_Ride_2024-06-02T07-12-44Z.gpx")),
+        ("Morning Ride".to_string(), NameSource::FileName)
+    );
+    assert_eq!(
+        file_name_or_default(Path::new("activity_15273839213.gpx")),
+        (DEFAULT_NAME.to_string(), NameSource::Default)
+    );
+    assert_eq!(
+        file_name_or_default(Path::new("")),
+        (DEFAULT_NAME.to_string(), NameSource::Default)
+    );
+}
+
+#[test]
+fn route_points() {
+    let points = gpx_route_points(&fixture("route_only.gpx"));
+    let coordinates: Vec<(f64, f64, Option<f64>)> = points
+        .iter()
+        .map(|point| (point.lat, point.lon, point.elevation))
+        .collect();
+    assert_eq!(
+        coordinates,
+        [
+            (45.000, 5.000, Some(100.0)),
+            (45.001, 5.000, Some(100.0)),
+            (45.002, 5.000, Some(100.0)),
+            (45.003, 5.000, Some(100.0)),
+        ]
+    );
+    let starts: Vec<bool> = points.iter().map(|point| point.segment_start).collect();
+    assert_eq!(starts, [true, false, false, false]);
+    assert!(points.iter().all(|point| point.time.is_none()));
+    assert_close(
+        tuned_distance_m(&points, &Tuning::default()),
+        333.58524070061134,
+    );
+
+    assert_eq!(gpx_route_points(&fixture("flat.gpx")), []);
+}
+
+#[test]
+fn plausible_times() {
+    let plausible: Vec<bool> = fixture("clock_reset.gpx").tracks[0].segments[0]
+        .points
+        .iter()
+        .map(|point| {
+            let time = OffsetDateTime::from(point.time.unwrap());
+            let time = DateTime::parse_from_rfc3339(&time.format(&Rfc3339).unwrap()).unwrap();
+            is_plausible_time(&time)
+        })
+        .collect();
+    // Reset to the epoch, then to the largest 32-bit timestamp.
+    assert_eq!(
+        plausible,
+        [
+            false, false, false, true, true, true, true, true, true, false
+        ]
+    );
+
+    let first = DateTime::parse_from_rfc3339(&format!("{MIN_PLAUSIBLE_YEAR}-01-01T00:00:00Z"));
+    assert!(is_plausible_time(&first.unwrap()));
+    let before = DateTime::parse_from_rfc3339("1999-12-31T23:59:59+00:00").unwrap();
+    assert!(!is_plausible_time(&before));
+    let tomorrow = Utc::now().fixed_offset() + TimeDelta::hours(23);
+    assert!(is_plausible_time(&tomorrow));
+    let next_week = Utc::now().fixed_offset() + TimeDelta::days(7);
+    assert!(!is_plausible_time(&next_week));
+}
+
+#[test]
+fn written_gpx_is_the_fixture() {
+    let bytes = fs::read(fixture_path("paired_ride.gpx")).unwrap();
+    let points = read_track_points(&bytes).unwrap();
+
+    let written = write_gpx("cyclemetrics testkit", Some("Paired ride"), &points);
+    assert_eq!(written, String::from_utf8(bytes).unwrap());
+}
+
+#[test]
+fn written_gpx_reads_back() {
+    let bytes = fs::read(fixture_path("multi_track.gpx")).unwrap();
+    let mut points = read_track_points(&bytes).unwrap();
+    points[1].heart_rate = Some(142.0);
+    points[1].cadence = Some(88.0);
+    points[2].power = Some(215.0);
+    points[3].distance_m = Some(250.0);
+
+    let written = write_gpx("Edge 840 & co", Some("Loops <1>"), &points);
+    assert_eq!(read_track_points(written.as_bytes()).unwrap(), points);
+    let gpx = gpx::read(written.as_bytes()).unwrap();
+    assert_eq!(gpx.creator.as_deref(), Some("Edge 840 & co"));
+    assert_eq!(gpx_name(&gpx), Some(("Loops <1>", NameSource::Track)));
+    assert_eq!(gpx.tracks[0].segments.len(), 3);
+    assert_close(
+        gpx_total_distance(&gpx),
+        gpx_total_distance(&fixture("multi_track.gpx")),
+    );
+}
+
+#[test]
+fn name_fallback_chain() {
+    let path = Path::new("export/Morning_Ride_2024-06-02T07-12-44Z.gpx");
+    let name = |gpx| activity_name(&gpx, path);
+
+    assert_eq!(
+        name(fixture("flat.gpx")),
+        ("Flat ride".to_string(), NameSource::Track)
+    );
+    assert_eq!(
+        name(fixture("route_only.gpx")),
+        ("Planned route".to_string(), NameSource::Route)
+    );
+    let metadata = gpx::read(
+        r#"<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <metadata><name>Sunday club ride</name></metadata>
+  <trk><name> </name><trkseg><trkpt lat="45.0" lon="5.0"/></trkseg></trk>
+</gpx>"#
+            .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(
+        name(metadata),
+        ("Sunday club ride".to_string(), NameSource::Metadata)
+    );
+    let unnamed = gpx::read(
+        r#"<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1"/>"#
+            .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(
+        activity_name(&unnamed, path),
+        ("Morning Ride".to_string(), NameSource::FileName)
+    );
+    assert_eq!(
+        activity_name(&unnamed, Path::new("activity_15273839213.gpx")),
+        ("Activity".to_string(), NameSource::Default)
+    );
+}
+
+#[test]
+fn elevation_gain() {
+    assert_close(gpx_elevation_gain(&fixture("flat.gpx")), 5.0);
+    assert_close(gpx_elevation_gain(&fixture("mountain.gpx")), 280.0);
+    assert_close(gpx_elevation_gain(&fixture("no_elevation.gpx")), 0.0);
+    assert_close(gpx_elevation_gain(&fixture("no_timestamps.gpx")), 4.0);
+    assert_close(gpx_elevation_gain(&fixture("multi_track.gpx")), 4.0);
+    assert_close(gpx_elevation_gain(&fixture("route_only.gpx")), 0.0);
+}
+
+#[test]
+fn start_end_date() {
+    let (start, end) = gpx_start_end_date(&fixture("mountain.gpx")).unwrap();
+    assert_eq!(
+        start,
+        DateTime::parse_from_rfc3339("2024-07-14T07:00:00Z").unwrap()
+    );
+    assert_eq!(
+        end,
+        DateTime::parse_from_rfc3339("2024-07-14T07:44:00Z").unwrap()
+    );
+
+    let (start, end) = gpx_start_end_date(&fixture("multi_track.gpx")).unwrap();
+    assert_eq!(
+        start,
+        DateTime::parse_from_rfc3339("2024-06-03T07:00:00Z").unwrap()
+    );
+    assert_eq!(
+        end,
+        DateTime::parse_from_rfc3339("2024-06-03T09:02:00Z").unwrap()
+    );
+
+    assert_eq!(gpx_start_end_date(&fixture("no_timestamps.gpx")), None);
+    assert_eq!(gpx_start_end_date(&fixture("route_only.gpx")), None);
+}
+
+#[test]
+fn sparse_tracks_come_close_to_the_dense_recording() {
+    let load = |name: &str| {
+        Activity::from_bytes(name.into(), &fs::read(fixture_path(name)).unwrap()).unwrap()
+    };
+    // The same winding road recorded every 20 m, then kept every 400 m.
+    let dense = load("winding_dense.gpx");
+    let sparse = load("winding_sparse.gpx");
+    let course = load("winding_course.gpx");
+
+    assert!(median_spacing_m(&dense.points).unwrap() < 25.0);
+    assert!(median_spacing_m(&sparse.points).unwrap() > SPARSE_SPACING_M);
+    assert_eq!(dense.distance_source, DistanceSource::Track);
+    assert_close(dense.distance_km, 6.706_634_7);
+
+    // The straight lines between the sparse points cut the bends by 7%.
+    let measured_km = gpx_total_distance(&fixture("winding_sparse.gpx")) / 1_000.0;
+    assert!((measured_km / dense.distance_km - 0.925).abs() < 0.001);
+    assert_eq!(sparse.distance_source, DistanceSource::Corrected);
+    assert!((sparse.distance_km / dense.distance_km - 1.0).abs() < 0.01);
+
+    assert_eq!(course.distance_source, DistanceSource::Embedded);
+    assert_close(course.distance_km, 6.7066);
+
+    // Without correction the sparse distance is the measured one.
+    let mut uncorrected = sparse.clone();
+    uncorrected.retune(Tuning {
+        sparse_correction_percent: 0.0,
+        ..Tuning::default()
+    });
+    assert_close(uncorrected.distance_km, measured_km);
+    assert_eq!(uncorrected.distance_source, DistanceSource::Corrected);
+}
+
+#[test]
+fn synthetic_rides_measure_as_built() {
+    let gpx = SyntheticRide::new()
+        .speed_kmh(24.0)
+        .climb(3.0, 6.0)
+        .pause(minutes(5))
+        .descent(3.0, 6.0)
+        .build();
+
+    assert!((gpx_total_distance(&gpx) - 6_000.0).abs() < 1.0);
+    assert!((gpx_elevation_gain(&gpx) - 180.0).abs() < 1.0);
+    let (start, end) = gpx_start_end_date(&gpx).unwrap();
+    // 15 minutes of riding and the pause.
+    assert_eq!((end - start).num_minutes(), 20);
+}
+
+#[test]
+fn clock_resets_are_ignored() {
+    let gpx = fixture("clock_reset.gpx");
+    let (start, end) = gpx_start_end_date(&gpx).unwrap();
+    assert_eq!(
+        start,
+        DateTime::parse_from_rfc3339("2024-06-08T07:03:00Z").unwrap()
+    );
+    assert_eq!(
+        end,
+        DateTime::parse_from_rfc3339("2024-06-08T07:08:00Z").unwrap()
+    );
+    assert_eq!(gpx_implausible_timestamps(&gpx), 4);
+
+    let activity = Activity::from_bytes(
+        "clock_reset.gpx".into(),
+        &fs::read(fixture_path("clock_reset.gpx")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(activity.start, Some(start));
+    assert_eq!(activity.implausible_timestamps, 4);
+    // Every point counts for the distance, only the 5 minutes between the
+    // plausible ones for the time.
+    assert_eq!((activity.distance_km * 1_000.0).round(), 1001.0);
+    assert_eq!(activity.moving_seconds(), Some(300.0));
+    assert_eq!(
+        issues(&activity),
+        [Issue::ImplausibleTimestamps(4)],
+        "{:?}",
+        issues(&activity)
+    );
+}
+
+#[test]
+fn rides_across_midnight_and_daylight_saving_changes() {
+    let time = |text| Some(DateTime::parse_from_rfc3339(text).unwrap());
+    for (name, start, end, elapsed) in [
+        (
+            "late_night.gpx",
+            "2024-06-08T23:40:00Z",
+            "2024-06-09T01:10:00Z",
+            5_400.0,
+        ),
+        // Europe/Paris springs forward at 2:00: 2:30 on the clock, 1:30 ridden.
+        (
+            "dst_spring.gpx",
+            "2024-03-31T01:30:00+01:00",
+            "2024-03-31T04:00:00+02:00",
+            5_400.0,
+        ),
+        // And falls back at 3:00: the clock ends where it started, an hour later.
+        (
+            "dst_autumn.gpx",
+            "2024-10-27T02:30:00+02:00",
+            "2024-10-27T02:30:00+01:00",
+            3_600.0,
+        ),
+    ] {
+        let activity =
+            Activity::from_bytes(name.into(), &fs::read(fixture_path(name)).unwrap()).unwrap();
+        assert_eq!(activity.start, time(start), "{name}");
+        assert_eq!(activity.end, time(end), "{name}");
+        assert_eq!(activity.elapsed_seconds(), Some(elapsed), "{name}");
+        assert_eq!(activity.moving_seconds(), Some(elapsed), "{name}");
+        // Rides are dated by their start.
+        assert_eq!(
+            RideMetrics::new(&activity).date,
+            time(start).map(|start| start.date_naive()),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn duplicated_points_are_removed() {
+    let read = |name: &str| {
+        Activity::from_bytes(name.into(), &fs::read(fixture_path(name)).unwrap()).unwrap()
+    };
+    let original = read("mountain.gpx");
+    let merged = read("duplicated.gpx");
+
+    assert_eq!(merged.duplicate_points, 15);
+    assert_eq!(merged.points, original.points);
+    assert_eq!(merged.distance_km, original.distance_km);
+    assert_eq!(merged.elevation_gain, original.elevation_gain);
+    assert_eq!(merged.elevation_profile, original.elevation_profile);
+    assert_eq!(merged.elevation_noise, original.elevation_noise);
+    assert_eq!(merged.moving_seconds(), original.moving_seconds());
+    assert_eq!(merged.energy(), original.energy());
+    assert_eq!(issues(&merged), [Issue::DuplicatePoints(15)]);
+    assert!(issues(&original).is_empty());
+}
+
+#[test]
+fn start_point() {
+    assert_eq!(gpx_start_point(&fixture("flat.gpx")), Some((45.0, 5.0)));
+    assert_eq!(
+        gpx_start_point(&fixture("multi_track.gpx")),
+        Some((46.0, 5.0))
+    );
+    assert_eq!(gpx_start_point(&fixture("route_only.gpx")), None);
+}
+
+#[test]
+fn elevation_profile() {
+    let profile = gpx_elevation_profile(&fixture("mountain.gpx"));
+    assert_eq!(profile.len(), 14);
+    assert_close(profile[0].0, 0.2223901604669425);
+    assert_close(profile[0].1, 540.0);
+    assert_close(profile[13].0, 3.1269465251774036);
+    assert_close(profile[13].1, 500.0);
+
+    assert!(gpx_elevation_profile(&fixture("no_elevation.gpx")).is_empty());
+    assert!(gpx_elevation_profile(&fixture("route_only.gpx")).is_empty());
+}
+
+#[test]
+fn peak_of_a_partly_recorded_elevation() {
+    // A 8% climb from km 1 to km 3, up to 2,160 m, a point every 7 m.
+    let mut activity = SyntheticRide::new()
+        .elevation(2_000.0)
+        .flat(1.0)
+        .climb(2.0, 8.0)
+        .descent(2.0, 8.0)
+        .activity();
+    let top = activity.peak.unwrap();
+    assert!((top.elevation_m - 2_160.0).abs() < 1.0, "{top:?}");
+    assert!((top.distance_km - 3.0).abs() < 0.05, "{top:?}");
+
+    // A barometric spike on the flat, and no elevation over the last 500 m
+    // to the top.
+    activity.points[70].elevation = Some(4_000.0);
+    for point in &mut activity.points[360..430] {
+        point.elevation = None;
+    }
+    activity.retune(Tuning::default());
+    let peak = activity.peak.unwrap();
+    assert!((peak.elevation_m - 2_160.0).abs() < 1.0, "{peak:?}");
+    assert!((peak.distance_km - 3.0).abs() < 0.05, "{peak:?}");
+
+    activity.release_points();
+    assert_eq!(activity.peak, Some(peak));
+    assert_eq!(
+        Activity::from_bytes(
+            "no_elevation.gpx".into(),
+            &fs::read(fixture_path("no_elevation.gpx")).unwrap()
+        )
+        .unwrap()
+        .peak,
+        None
+    );
+}
+
+#[test]
+fn profile_min_max() {
+    let profile = gpx_elevation_profile(&fixture("mountain.gpx"));
+    assert_eq!(elevation_profile_min_max(&profile), Some((500.0, 780.0)));
+
+    let profile = gpx_elevation_profile(&fixture("multi_track.gpx"));
+    assert_eq!(elevation_profile_min_max(&profile), Some((201.0, 222.0)));
+
+    assert_eq!(elevation_profile_min_max(&[]), None);
+}
+
+#[test]
+fn elevation_noise() {
+    assert_close(
+        gpx_elevation_noise(&fixture("flat.gpx")).unwrap(),
+        0.47809144373376816,
+    );
+    // Too short to hold a full flat chunk.
+    assert_eq!(gpx_elevation_noise(&fixture("no_timestamps.gpx")), None);
+    assert_eq!(gpx_elevation_noise(&fixture("no_elevation.gpx")), None);
+}
+
+#[test]
+fn grade_factors() {
+    for model in [GradeModel::Piecewise, GradeModel::Polynomial] {
+        assert_close(grade_factor(0.0, model), 1.0);
+        assert_close(grade_factor(10.0, model), 2.0);
+        assert!(grade_factor(-2.0, model) < 1.0, "{model:?}");
+        assert_close(grade_factor(-15.0, model), 0.8);
+        // Clamped at 25%.
+        assert_close(grade_factor(40.0, model), grade_factor(25.0, model));
+    }
+    assert_close(grade_factor(5.0, GradeModel::Piecewise), 1.5);
+    assert_close(grade_factor(5.0, GradeModel::Polynomial), 1.45);
+    assert_close(grade_factor(20.0, GradeModel::Polynomial), 3.4);
+}
+
+#[test]
+fn creator_is_the_device() {
+    let activity = Activity::from_bytes(
+        "edge.gpx".into(),
+        &fs::read(fixture_path("edge.gpx")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(activity.creator.as_deref(), Some("Garmin Edge 530"));
+
+    let anonymous = Activity::from_bytes(
+        "anonymous.gpx".into(),
+        br#"<gpx version="1.1" creator=" "><trk><trkseg><trkpt lat="45" lon="5"/></trkseg></trk></gpx>"#,
+    )
+    .unwrap();
+    assert_eq!(anonymous.creator, None);
+    assert_eq!(anonymous.device(), UNKNOWN_CREATOR);
+}
+
+#[test]
+fn default_tuning_keeps_the_metrics() {
+    for name in [
+        "flat.gpx",
+        "mountain.gpx",
+        "multi_track.gpx",
+        "no_elevation.gpx",
+    ] {
+        let mut activity =
+            Activity::from_bytes(name.into(), &fs::read(fixture_path(name)).unwrap()).unwrap();
+        let (distance_km, elevation_gain) = (activity.distance_km, activity.elevation_gain);
+
+        activity.retune(Tuning::default());
+        assert_close(activity.distance_km, distance_km);
+        assert_close(activity.elevation_gain, elevation_gain);
+
+        activity.retune(Tuning {
+            elevation_threshold_m: 1_000.0,
+            ..Tuning::default()
+        });
+        assert_eq!(activity.elevation_gain, 0.0, "{name}");
+    }
+}
+
+#[test]
+fn segment_joins_policies() {
+    let load = |name: &str, segment_joins| {
+        let mut activity =
+            Activity::from_bytes(name.into(), &fs::read(fixture_path(name)).unwrap()).unwrap();
+        activity.retune(Tuning {
+            segment_joins,
+            ..Tuning::default()
+        });
+        activity
+    };
+    // 55.6 m every 10 seconds, with 3 steps ridden in a tunnel.
+    let step_km = 0.0005 * 111.19508;
+
+    let ignored = load("tunnel.gpx", SegmentJoins::Ignore);
+    assert!((ignored.distance_km - 18.0 * step_km).abs() < 1e-3);
+    assert_eq!(ignored.moving_seconds(), Some(180.0));
+    for segment_joins in [SegmentJoins::Count, SegmentJoins::Auto] {
+        let joined = load("tunnel.gpx", segment_joins);
+        assert!((joined.distance_km - 21.0 * step_km).abs() < 1e-3);
+        assert_eq!(joined.moving_seconds(), Some(210.0));
+    }
+
+    // Ten minutes at a café, 22 m from where the ride stopped.
+    let paused = load("auto_pause.gpx", SegmentJoins::Auto);
+    assert!((paused.distance_km - 18.0 * step_km).abs() < 1e-3);
+    assert_eq!(paused.moving_seconds(), Some(180.0));
+    let counted = load("auto_pause.gpx", SegmentJoins::Count);
+    assert!((counted.distance_km - paused.distance_km - 0.4 * step_km).abs() < 1e-3);
+    assert_eq!(counted.moving_seconds(), Some(180.0));
+}
+
+#[test]
+fn cumulative_distance_follows_the_joins() {
+    let points = read_track_points(&fs::read(fixture_path("tunnel.gpx")).unwrap()).unwrap();
+    let tuning = Tuning {
+        segment_joins: SegmentJoins::Auto,
+        ..Tuning::default()
+    };
+
+    let cumulative: Vec<f64> = cumulative_distance(&points, &tuning)
+        .map(|(meters, _)| meters)
+        .collect();
+
+    assert_eq!(cumulative.len(), points.len());
+    assert!(cumulative.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_close(cumulative[19], tuned_distance_m(&points, &tuning));
+    assert!((cumulative[10] - cumulative[9] - 3.0 * 0.0005 * 111_195.08).abs() < 1.0);
+}
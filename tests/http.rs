@@ -0,0 +1,86 @@
+#![cfg(feature = "http")]
+
+mod common;
+
+use common::fixture_path;
+use cyclemetrics::{LoadOptions, load_activity};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::thread;
+
+/// Serve a few canned responses on a local port, returning the base URL.
+fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let gpx = std::fs::read_to_string(fixture_path("flat.gpx")).unwrap();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                header.clear();
+            }
+
+            let path = request.split(' ').nth(1).unwrap_or_default();
+            let (status, location, body) = match path {
+                "/rides/flat.gpx" => ("200 OK", "", gpx.as_str()),
+                "/shared" => ("302 Found", "/rides/flat.gpx", ""),
+                "/loop" => ("302 Found", "/loop", ""),
+                "/page" => ("200 OK", "", "not a GPX file"),
+                _ => ("404 Not Found", "", ""),
+            };
+            let location = if location.is_empty() {
+                String::new()
+            } else {
+                format!("Location: {location}\r\n")
+            };
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\n{location}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        }
+    });
+
+    base
+}
+
+fn load(url: &str) -> anyhow::Result<cyclemetrics::Activity> {
+    load_activity(&PathBuf::from(url), &LoadOptions::default())
+}
+
+#[test]
+fn downloads_follow_redirects() {
+    let base = serve();
+
+    let activity = load(&format!("{base}/rides/flat.gpx")).unwrap();
+    assert_eq!(activity.name, "flat.gpx");
+    assert_eq!(
+        activity.path,
+        PathBuf::from(format!("{base}/rides/flat.gpx"))
+    );
+    assert!((activity.distance_km - 1.001).abs() < 0.001);
+
+    // Named after the URL given, not the one redirected to.
+    assert_eq!(load(&format!("{base}/shared")).unwrap().name, "shared");
+}
+
+#[test]
+fn download_failures_are_errors() {
+    let base = serve();
+
+    let error = |path: &str| format!("{:#}", load(&format!("{base}{path}")).unwrap_err());
+    assert!(
+        error("/missing.gpx").contains("404"),
+        "{}",
+        error("/missing.gpx")
+    );
+    assert!(error("/page").contains("is not an XML document"));
+    assert!(error("/loop").contains("redirect"), "{}", error("/loop"));
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "tui")]
+
+mod common;
+
+use common::fixture_path;
+use cyclemetrics::{LoadOptions, PrivacyZone, kml_document, load_activities};
+use std::collections::BTreeMap;
+
+#[test]
+fn activities_are_grouped_by_year() {
+    let paths = [
+        "flat.gpx",
+        "no_elevation.gpx",
+        "no_timestamps.gpx",
+        "route_only.gpx",
+    ]
+    .map(fixture_path);
+    let loaded = load_activities(&paths, &LoadOptions::default()).unwrap();
+
+    let kml = kml_document(&loaded.activities, &[], &BTreeMap::new());
+
+    assert_eq!(kml.matches("<Folder>").count(), 2);
+    assert!(kml.contains("<name>2024</name>"));
+    assert!(kml.contains("<name>Undated</name>"));
+    // The route has no track point and is left out.
+    assert_eq!(kml.matches("<Placemark>").count(), 3);
+    assert!(kml.contains("<name>2024-06-01 Flat ride</name>"));
+}
+
+#[test]
+fn tours_get_their_own_folder() {
+    let paths = ["flat.gpx", "no_elevation.gpx", "mountain.gpx"].map(fixture_path);
+    let loaded = load_activities(&paths, &LoadOptions::default()).unwrap();
+    let tours: BTreeMap<_, _> = loaded
+        .activities
+        .iter()
+        .filter(|activity| activity.name != "Flat ride")
+        .map(|activity| (activity.id, "Alps & Jura".to_string()))
+        .collect();
+
+    let kml = kml_document(&loaded.activities, &[], &tours);
+
+    assert_eq!(kml.matches("<Folder>").count(), 2);
+    let tour = kml.find("<name>Alps &amp; Jura</name>").unwrap();
+    assert!(kml.find("<name>2024</name>").unwrap() < tour);
+    assert_eq!(kml[tour..].matches("<Placemark>").count(), 2);
+}
+
+#[test]
+fn altitude_mode_depends_on_elevation() {
+    let paths = ["flat.gpx", "no_elevation.gpx"].map(fixture_path);
+    let loaded = load_activities(&paths, &LoadOptions::default()).unwrap();
+
+    let kml = kml_document(&loaded.activities, &[], &BTreeMap::new());
+
+    assert!(kml.contains("<altitudeMode>absolute</altitudeMode>"));
+    assert!(kml.contains("<altitudeMode>clampToGround</altitudeMode>"));
+    assert!(kml.contains("5,45.001,101\n"));
+    assert!(kml.contains("5,45.001\n"));
+}
+
+#[test]
+fn privacy_zones_remove_points() {
+    let loaded = load_activities(&[fixture_path("flat.gpx")], &LoadOptions::default()).unwrap();
+    let home = PrivacyZone {
+        lat: 45.0,
+        lon: 5.0,
+        radius_m: 150.0,
+    };
+
+    let kml = kml_document(&loaded.activities, &[home], &BTreeMap::new());
+
+    assert!(!kml.contains("5,45,100\n"));
+    assert!(!kml.contains("5,45.001,101\n"));
+    assert!(kml.contains("5,45.002,100\n"));
+    assert!(kml.contains("<description>2 points removed by privacy zones</description>"));
+    assert_eq!(loaded.activities[0].points.len(), 10);
+}
+
+#[test]
+fn rides_inside_a_zone_keep_an_empty_placemark() {
+    let loaded = load_activities(&[fixture_path("flat.gpx")], &LoadOptions::default()).unwrap();
+    let everywhere = PrivacyZone {
+        lat: 45.0,
+        lon: 5.0,
+        radius_m: 5_000.0,
+    };
+
+    let kml = kml_document(&loaded.activities, &[everywhere], &BTreeMap::new());
+
+    assert_eq!(kml.matches("<Placemark>").count(), 1);
+    assert!(!kml.contains("<LineString>"));
+    assert!(kml.contains("<description>10 points removed by privacy zones</description>"));
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "tui")]
+
+use cyclemetrics::{LoadOptions, load_activity};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+
+/// Peak resident memory of the test process, in kB.
+fn peak_memory_kb() -> u64 {
+    let status = fs::read_to_string("/proc/self/status").unwrap();
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .unwrap()
+}
+
+/// Write a ~120 MB GPX going north along a meridian, one point per second.
+fn write_large_gpx(path: &std::path::Path, points: usize) {
+    let mut out = BufWriter::new(File::create(path).unwrap());
+    writeln!(
+        out,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="cyclemetrics tests" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk>
+    <name>Logger dump</name>
+    <trkseg>"#
+    )
+    .unwrap();
+    for i in 0..points {
+        let (minutes, seconds) = (i / 60 % 60, i % 60);
+        let hours = i / 3600;
+        writeln!(
+            out,
+            r#"      <trkpt lat="{:.7}" lon="5.0000000"><ele>{:.1}</ele><time>2024-06-{:02}T{:02}:{minutes:02}:{seconds:02}Z</time></trkpt>"#,
+            45.0 + i as f64 * 1e-5,
+            200.0 + (i % 100) as f64,
+            1 + hours / 24,
+            hours % 24,
+        )
+        .unwrap();
+    }
+    writeln!(out, "    </trkseg>\n  </trk>\n</gpx>").unwrap();
+}
+
+#[test]
+#[ignore = "writes a 120 MB file; run with --ignored"]
+fn large_files_are_streamed_in_bounded_memory() {
+    let path = std::env::temp_dir().join(format!("cyclemetrics-large-{}.gpx", std::process::id()));
+    write_large_gpx(&path, 1_100_000);
+    assert!(fs::metadata(&path).unwrap().len() > 100 * 1024 * 1024);
+
+    let activity = load_activity(&path, &LoadOptions::default()).unwrap();
+    let peak_mb = peak_memory_kb() / 1024;
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(activity.name, "Logger dump");
+    assert!(activity.points.is_empty());
+    // 1.1 million steps of ~1.1 m.
+    assert!(
+        (activity.distance_km - 1223.1).abs() < 1.0,
+        "{}",
+        activity.distance_km
+    );
+    assert!(peak_mb < 50, "peak memory: {peak_mb} MB");
+}
@@ -0,0 +1,79 @@
+#![cfg(all(feature = "sqlite", feature = "tui"))]
+
+mod common;
+
+use common::fixture_path;
+use cyclemetrics::{LoadOptions, export_sqlite, load_activities, remove_from_sqlite};
+use rusqlite::Connection;
+
+#[test]
+fn export_upserts_by_activity_id() {
+    let path = std::env::temp_dir().join(format!("cyclemetrics-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let paths = ["flat.gpx", "mountain.gpx"].map(fixture_path);
+    let mut loaded = load_activities(&paths, &LoadOptions::default()).unwrap();
+
+    export_sqlite(&loaded.activities, &path, true).unwrap();
+    loaded.activities[0].name = "Renamed".to_string();
+    export_sqlite(&loaded.activities, &path, true).unwrap();
+
+    let db = Connection::open(&path).unwrap();
+    let count = |table: &str| -> i64 {
+        db.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    };
+    assert_eq!(count("activities"), 2);
+    assert_eq!(
+        count("points"),
+        loaded
+            .activities
+            .iter()
+            .map(|activity| activity.points.len() as i64)
+            .sum::<i64>()
+    );
+
+    let (name, distance_m, moving_s): (String, f64, Option<f64>) = db
+        .query_row(
+            "SELECT name, distance_m, moving_s FROM activities WHERE id = ?1",
+            [loaded.activities[0].id.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(name, "Renamed");
+    assert!((distance_m - 1000.756).abs() < 1e-3);
+    assert!(moving_s.is_some());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn removed_activities_leave_with_their_points() {
+    let path =
+        std::env::temp_dir().join(format!("cyclemetrics-remove-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let paths = ["flat.gpx", "mountain.gpx"].map(fixture_path);
+    let loaded = load_activities(&paths, &LoadOptions::default()).unwrap();
+
+    export_sqlite(&loaded.activities, &path, true).unwrap();
+    remove_from_sqlite(&[loaded.activities[0].id], &path).unwrap();
+
+    let db = Connection::open(&path).unwrap();
+    let ids: Vec<String> = db
+        .prepare("SELECT DISTINCT activity_id FROM points")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let remaining: String = db
+        .query_row("SELECT group_concat(id) FROM activities", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(ids, [loaded.activities[1].id.to_string()]);
+    assert_eq!(remaining, loaded.activities[1].id.to_string());
+
+    std::fs::remove_file(&path).unwrap();
+}